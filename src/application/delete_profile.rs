@@ -0,0 +1,39 @@
+use anyhow::Result;
+
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+use super::ensure_can_manage;
+
+/// Use case: remove a profile that isn't in use, with its domains.
+pub struct DeleteProfile<'a> {
+    config_store: &'a ConfigStore,
+    caller: &'a Caller,
+}
+
+impl<'a> DeleteProfile<'a> {
+    pub fn new(config_store: &'a ConfigStore, caller: &'a Caller) -> Self {
+        Self {
+            config_store,
+            caller,
+        }
+    }
+
+    /// Delete the `name` profile. Refused while one of its domains
+    /// belongs to another user.
+    pub fn execute(&self, name: &str) -> Result<()> {
+        let config = self.config_store.read()?;
+        let registrations = config
+            .profiles()
+            .into_iter()
+            .find(|(profile, _)| profile == name)
+            .map(|(_, registrations)| registrations)
+            .unwrap_or_default();
+        for registration in &registrations {
+            ensure_can_manage(self.caller, registration)?;
+        }
+
+        self.config_store.delete_profile(name)?;
+        Ok(())
+    }
+}
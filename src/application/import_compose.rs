@@ -0,0 +1,75 @@
+use anyhow::Result;
+
+use crate::domain::{DomainPattern, Route};
+use crate::infrastructure::certs::CertificateService;
+use crate::infrastructure::compose::ComposeService;
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+use super::StepOutcome;
+use super::register_domain::RegisterDomain;
+
+/// Use case: register a `<service>.roxy` domain for every compose
+/// service that publishes a port, routing `/` to the first one.
+pub struct ImportCompose<'a> {
+    config_store: &'a ConfigStore,
+    register: RegisterDomain<'a>,
+}
+
+impl<'a> ImportCompose<'a> {
+    pub fn new(
+        config_store: &'a ConfigStore,
+        cert_service: &'a CertificateService,
+        caller: &'a Caller,
+    ) -> Self {
+        Self {
+            config_store,
+            register: RegisterDomain::new(config_store, cert_service, caller),
+        }
+    }
+
+    /// Register each service, or with `dry_run` only report what would
+    /// be registered. One outcome per service, labelled with its name.
+    pub fn execute(
+        &self,
+        services: &[ComposeService],
+        dry_run: bool,
+    ) -> Result<Vec<(String, StepOutcome)>> {
+        services
+            .iter()
+            .map(|service| Ok((service.name.clone(), self.import(service, dry_run)?)))
+            .collect()
+    }
+
+    fn import(&self, service: &ComposeService, dry_run: bool) -> Result<StepOutcome> {
+        let Some(&port) = service.ports.first() else {
+            return Ok(StepOutcome::Skipped("No published ports.".into()));
+        };
+
+        let domain = service.domain();
+        let pattern = match DomainPattern::from_name(&domain, false) {
+            Ok(pattern) => pattern,
+            Err(e) => return Ok(StepOutcome::Warning(e.to_string())),
+        };
+        if self.config_store.get_domain(&pattern)?.is_some() {
+            return Ok(StepOutcome::Skipped(format!(
+                "{} is already registered.",
+                domain
+            )));
+        }
+
+        let route = Route::parse(&format!("/={}", port))?;
+        let summary = format!("{} (/ -> {})", domain, route.target);
+        if dry_run {
+            return Ok(StepOutcome::Success(format!("Would register {}", summary)));
+        }
+
+        let result = self.register.execute(pattern, vec![route])?;
+        Ok(match result.cert_outcome {
+            StepOutcome::Warning(msg) => {
+                StepOutcome::Warning(format!("Registered {}, but: {}", summary, msg))
+            }
+            _ => StepOutcome::Success(format!("Registered {}", summary)),
+        })
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::Result;
+
+use crate::domain::DomainRegistration;
+use crate::infrastructure::certs::CertificateService;
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+use super::unregister_domain::UnregisterDomain;
+use super::{StepOutcome, ensure_can_manage, sync_hosts_file};
+
+/// What to do with domains that are registered already.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Leave them as they are.
+    Add,
+    /// Overwrite them with the imported ones.
+    Merge,
+    /// Overwrite them, and unregister every domain the import doesn't
+    /// have.
+    Replace,
+}
+
+/// Use case: register domains exported by `roxy export`, issuing
+/// certificates for the ones served over HTTPS.
+pub struct ImportDomains<'a> {
+    config_store: &'a ConfigStore,
+    cert_service: &'a CertificateService,
+    caller: &'a Caller,
+}
+
+impl<'a> ImportDomains<'a> {
+    pub fn new(
+        config_store: &'a ConfigStore,
+        cert_service: &'a CertificateService,
+        caller: &'a Caller,
+    ) -> Self {
+        Self {
+            config_store,
+            cert_service,
+            caller,
+        }
+    }
+
+    /// Import each registration. One outcome per domain, labelled with
+    /// its pattern, plus one for the hosts file when it's in use.
+    pub fn execute(
+        &self,
+        registrations: Vec<DomainRegistration>,
+        mode: ImportMode,
+    ) -> Result<Vec<(String, StepOutcome)>> {
+        let mut steps = Vec::new();
+
+        if mode == ImportMode::Replace {
+            let unregister =
+                UnregisterDomain::new(self.config_store, self.cert_service, self.caller);
+            for existing in self.config_store.list_domains()? {
+                let pattern = existing.pattern();
+                if registrations.iter().any(|r| r.pattern() == pattern) {
+                    continue;
+                }
+                let outcome = match unregister.execute(pattern) {
+                    Ok(_) => StepOutcome::Success("Unregistered".into()),
+                    Err(e) => StepOutcome::Warning(e.to_string()),
+                };
+                steps.push((pattern.to_string(), outcome));
+            }
+        }
+
+        for registration in registrations {
+            let label = registration.pattern().to_string();
+            steps.push((label, self.import(registration, mode)?));
+        }
+
+        if let Some(outcome) = sync_hosts_file(&self.config_store.load()?) {
+            steps.push(("hosts file".into(), outcome));
+        }
+        Ok(steps)
+    }
+
+    fn import(
+        &self,
+        mut registration: DomainRegistration,
+        mode: ImportMode,
+    ) -> Result<StepOutcome> {
+        let pattern = registration.pattern().clone();
+        let existing = self.config_store.get_domain(&pattern)?;
+        if let Some(existing) = &existing {
+            if mode == ImportMode::Add {
+                return Ok(StepOutcome::Skipped(
+                    "Already registered. Use --merge to overwrite it.".into(),
+                ));
+            }
+            if let Err(e) = ensure_can_manage(self.caller, existing) {
+                return Ok(StepOutcome::Warning(e.to_string()));
+            }
+        }
+
+        registration.set_owner(self.caller.name().map(str::to_string));
+
+        // Certificates aren't exported, so this machine issues its own
+        let mut outcome = StepOutcome::Success(
+            if existing.is_some() {
+                "Updated"
+            } else {
+                "Registered"
+            }
+            .into(),
+        );
+        if registration.has_roxy_cert()
+            && !self.cert_service.exists(&pattern)
+            && let Err(e) = self.cert_service.create_and_install(&pattern)
+        {
+            registration.disable_https();
+            outcome = StepOutcome::Warning(format!(
+                "Imported, but failed to generate certificate: {}. \
+                 HTTPS will not be available for this domain.",
+                e
+            ));
+        }
+
+        let saved = if existing.is_some() {
+            self.config_store.update_domain(registration)
+        } else {
+            self.config_store.add_domain(registration)
+        };
+        Ok(match saved {
+            Ok(()) => outcome,
+            Err(e) => StepOutcome::Warning(e.to_string()),
+        })
+    }
+}
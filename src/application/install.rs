@@ -1,15 +1,30 @@
 use std::net::Ipv4Addr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 
 use crate::infrastructure::certs::CertificateService;
-use crate::infrastructure::config::{Config, ConfigStore};
-use crate::infrastructure::dns::get_dns_service;
+use crate::infrastructure::certs::ca::RootCA;
+use crate::infrastructure::config::{Config, ConfigStore, DaemonConfig};
+use crate::infrastructure::dns::{DnsBackend, DnsService, DnsmasqDnsService, get_dns_service};
 use crate::infrastructure::network::get_lan_ip;
 use crate::infrastructure::paths::RoxyPaths;
+use crate::infrastructure::pid::PidFile;
+use crate::infrastructure::port_forward::{Protocol, Redirect, get_port_forwarder};
+use crate::infrastructure::service_user::{SERVICE_USER, ServiceUser};
+use crate::infrastructure::user::Caller;
 
-use super::StepOutcome;
+use super::{StepOutcome, flush_dns_cache, sync_hosts_file};
+
+/// Ports the daemon moves to in rootless mode, when its configured ones
+/// need root.
+const ROOTLESS_HTTP_PORT: u16 = 8080;
+const ROOTLESS_HTTPS_PORT: u16 = 8443;
+const ROOTLESS_DNS_PORT: u16 = 5353;
+
+/// Ports below this need root to bind.
+const FIRST_UNPRIVILEGED_PORT: u16 = 1024;
 
 /// Result of the install operation.
 pub struct InstallResult {
@@ -17,13 +32,18 @@ pub struct InstallResult {
     pub steps: Vec<(String, StepOutcome)>,
 }
 
-/// Use case: initial setup — create directories, root CA, DNS.
+/// Use case: initial setup — create directories, root CA, DNS, and
+/// optionally the service user the daemon runs as, or rootless mode.
 pub struct Install<'a> {
     config_store: &'a ConfigStore,
     cert_service: &'a CertificateService,
     config_path: &'a Path,
     paths: &'a RoxyPaths,
     config: &'a Config,
+    service_user: bool,
+    rootless: bool,
+    /// Certificate and key of an existing CA to use as the Root CA.
+    ca_import: Option<(PathBuf, PathBuf)>,
 }
 
 impl<'a> Install<'a> {
@@ -33,6 +53,8 @@ impl<'a> Install<'a> {
         config_path: &'a Path,
         paths: &'a RoxyPaths,
         config: &'a Config,
+        service_user: bool,
+        rootless: bool,
     ) -> Self {
         Self {
             config_store,
@@ -40,18 +62,54 @@ impl<'a> Install<'a> {
             config_path,
             paths,
             config,
+            service_user,
+            rootless,
+            ca_import: None,
         }
     }
 
+    /// Import the CA at `cert` and `key` instead of generating one.
+    pub fn with_ca_import(mut self, cert: PathBuf, key: PathBuf) -> Self {
+        self.ca_import = Some((cert, key));
+        self
+    }
+
     pub fn execute(&self) -> Result<InstallResult> {
         let mut steps: Vec<(String, StepOutcome)> = Vec::new();
         let lan_ip = get_lan_ip();
-        let dns_port = self.config.daemon.dns_port;
 
         self.create_directories(&mut steps)?;
         self.ensure_config_file(&mut steps)?;
-        self.init_root_ca(&mut steps);
-        self.configure_dns(dns_port, &mut steps)?;
+        match &self.ca_import {
+            Some((cert, key)) => self.import_root_ca(cert, key, &mut steps)?,
+            None => self.init_root_ca(&mut steps),
+        }
+        if self.service_user {
+            self.setup_service_user(&mut steps)?;
+        }
+        let daemon = if self.rootless {
+            self.setup_rootless(&mut steps)?
+        } else {
+            self.config.daemon.clone()
+        };
+
+        let dns_port = daemon.dns_port;
+        match daemon.dns_backend {
+            DnsBackend::Resolver => {
+                self.configure_dns(&*get_dns_service()?, dns_port, &mut steps)?
+            }
+            DnsBackend::Dnsmasq => {
+                self.configure_dns(&DnsmasqDnsService::detect()?, dns_port, &mut steps)?
+            }
+            DnsBackend::Hosts => {
+                if let Some(outcome) = sync_hosts_file(self.config) {
+                    steps.push(("Hosts file".into(), outcome));
+                }
+            }
+        }
+        if let Some(outcome) = flush_dns_cache() {
+            steps.push(("DNS cache".into(), outcome));
+        }
 
         Ok(InstallResult { lan_ip, steps })
     }
@@ -85,7 +143,8 @@ impl<'a> Install<'a> {
 
     fn ensure_config_file(&self, steps: &mut Vec<(String, StepOutcome)>) -> Result<()> {
         if !self.config_path.exists() {
-            self.config_store.save(self.config)?;
+            // Not `self.config`: `ROXY_*` overrides are for this run only
+            self.config_store.save(&self.config_store.read()?)?;
             steps.push((
                 "Config file".into(),
                 StepOutcome::Success(format!(
@@ -120,8 +179,186 @@ impl<'a> Install<'a> {
         steps.push(("Root CA".into(), ca_outcome));
     }
 
-    fn configure_dns(&self, dns_port: u16, steps: &mut Vec<(String, StepOutcome)>) -> Result<()> {
-        let dns = get_dns_service()?;
+    /// Replace the Root CA with an imported one and reissue the existing
+    /// domain certificates with it, so they stay trusted.
+    fn import_root_ca(
+        &self,
+        cert: &Path,
+        key: &Path,
+        steps: &mut Vec<(String, StepOutcome)>,
+    ) -> Result<()> {
+        self.cert_service
+            .import_ca(cert, key)
+            .with_context(|| format!("Failed to import the CA from {}", cert.display()))?;
+        steps.push((
+            "Root CA".into(),
+            StepOutcome::Success(format!(
+                "Imported {}; it is trusted wherever its own tool installed it.",
+                cert.display()
+            )),
+        ));
+
+        let mut reissued = 0;
+        for registration in self.config.registrations() {
+            let pattern = registration.pattern();
+            if !self.cert_service.exists(pattern) {
+                continue;
+            }
+            if let Err(e) = self.cert_service.create_and_install(pattern) {
+                steps.push((
+                    format!("Cert: {}", registration.display_pattern()),
+                    StepOutcome::Warning(format!("Failed to reissue: {}", e)),
+                ));
+                continue;
+            }
+            reissued += 1;
+        }
+        if reissued > 0 {
+            steps.push((
+                "Certificates".into(),
+                StepOutcome::Success(format!(
+                    "Reissued {} with the imported CA. Restart the daemon to use them: \
+                     sudo roxy restart",
+                    if reissued == 1 {
+                        "1 certificate".to_string()
+                    } else {
+                        format!("{} certificates", reissued)
+                    }
+                )),
+            ));
+        }
+        Ok(())
+    }
+
+    fn setup_service_user(&self, steps: &mut Vec<(String, StepOutcome)>) -> Result<()> {
+        let user = ServiceUser::create(SERVICE_USER)?;
+
+        let ca = RootCA::new(self.paths.data_dir.clone());
+        for path in [
+            ca.cert_path(),
+            ca.key_path(),
+            self.paths.certs_dir.clone(),
+            self.paths.log_file.clone(),
+        ] {
+            user.chown_all(&path)?;
+        }
+        self.config_store.set_value("daemon.user", user.name())?;
+
+        steps.push((
+            "Service user".into(),
+            StepOutcome::Success(format!(
+                "The daemon will run as '{}', which owns the Root CA key, certificates and log. \
+                 Restart it to apply: sudo roxy restart",
+                user.name()
+            )),
+        ));
+        Ok(())
+    }
+
+    /// Move the daemon to unprivileged ports, forward the standard ports
+    /// to them and hand the daemon's files to the invoking user, so it can
+    /// run without sudo. Returns the updated daemon settings.
+    fn setup_rootless(&self, steps: &mut Vec<(String, StepOutcome)>) -> Result<DaemonConfig> {
+        // The PID file may move, and a root daemon holds the ports
+        let pid_file = PidFile::new(self.paths.pid_file.clone());
+        if pid_file.get_running_pid()?.is_some() {
+            pid_file.stop_gracefully(Duration::from_millis(500))?;
+            steps.push((
+                "Stop daemon".into(),
+                StepOutcome::Success("Stopped the daemon running as root.".into()),
+            ));
+        }
+
+        let current = &self.config.daemon;
+        let http_port = unprivileged(current.http_port, ROOTLESS_HTTP_PORT);
+        let https_port = unprivileged(current.https_port, ROOTLESS_HTTPS_PORT);
+        let dns_port = unprivileged(current.dns_port, ROOTLESS_DNS_PORT);
+
+        let mut redirects = vec![
+            Redirect::new(Protocol::Tcp, 80, http_port),
+            Redirect::new(Protocol::Tcp, 443, https_port),
+        ];
+        if dns_port != current.dns_port {
+            redirects.push(Redirect::new(Protocol::Udp, current.dns_port, dns_port));
+        }
+
+        let mut values = vec![
+            ("daemon.http_port", http_port.to_string()),
+            ("daemon.https_port", https_port.to_string()),
+            ("daemon.dns_port", dns_port.to_string()),
+            ("daemon.rootless", "true".to_string()),
+        ];
+
+        // /var/run is writable by root only
+        let run_dir = self.paths.data_dir.join("run");
+        let defaults = RoxyPaths::default();
+        if self.paths.pid_file == defaults.pid_file {
+            values.push((
+                "paths.pid_file",
+                run_dir.join("roxy.pid").display().to_string(),
+            ));
+        }
+        if self.paths.control_socket == defaults.control_socket {
+            values.push((
+                "paths.control_socket",
+                run_dir.join("roxy.sock").display().to_string(),
+            ));
+        }
+
+        std::fs::create_dir_all(&run_dir)
+            .with_context(|| format!("Failed to create run directory: {}", run_dir.display()))?;
+        for (key, value) in &values {
+            self.config_store.set_value(key, value)?;
+        }
+
+        get_port_forwarder()?.setup(&redirects)?;
+        steps.push((
+            "Port forwarding".into(),
+            StepOutcome::Success(format!(
+                "Forwarding {}.",
+                redirects
+                    .iter()
+                    .map(Redirect::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        ));
+
+        let owner_outcome = match Caller::current().name() {
+            Some(name) => {
+                let user = ServiceUser::lookup(name)?;
+                let ca = RootCA::new(self.paths.data_dir.clone());
+                let mut owned = vec![
+                    ca.cert_path(),
+                    ca.key_path(),
+                    self.paths.certs_dir.clone(),
+                    run_dir,
+                ];
+                owned.extend(self.paths.log_file.parent().map(Path::to_path_buf));
+                for path in owned {
+                    user.chown_all(&path)?;
+                }
+                StepOutcome::Success(format!(
+                    "'{}' owns the Root CA, certificates, log and run directory.",
+                    name
+                ))
+            }
+            None => StepOutcome::Warning(
+                "Run 'sudo roxy install --rootless' from the account that will run the daemon."
+                    .into(),
+            ),
+        };
+        steps.push(("Daemon files".into(), owner_outcome));
+
+        Ok(self.config_store.load()?.daemon)
+    }
+
+    fn configure_dns(
+        &self,
+        dns: &dyn DnsService,
+        dns_port: u16,
+        steps: &mut Vec<(String, StepOutcome)>,
+    ) -> Result<()> {
         let dns_outcome = if dns.is_configured() {
             StepOutcome::Skipped("DNS already configured.".into())
         } else {
@@ -138,3 +375,12 @@ impl<'a> Install<'a> {
         Ok(())
     }
 }
+
+/// `port` if it can be bound without root, otherwise `fallback`.
+fn unprivileged(port: u16, fallback: u16) -> u16 {
+    if port < FIRST_UNPRIVILEGED_PORT {
+        fallback
+    } else {
+        port
+    }
+}
@@ -0,0 +1,151 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::domain::DomainRegistration;
+use crate::infrastructure::certs::CertificateService;
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::project::{PROJECT_FILE, Project, ProjectDomain};
+use crate::infrastructure::user::Caller;
+
+use super::register_domain::RegisterDomain;
+use super::set_https::SetHttps;
+use super::unregister_domain::UnregisterDomain;
+use super::{StepOutcome, ensure_can_manage};
+
+/// Use case: bring the registered domains in line with a project's
+/// `.roxy.toml`, or remove the ones linked from it.
+pub struct LinkProject<'a> {
+    config_store: &'a ConfigStore,
+    cert_service: &'a CertificateService,
+    caller: &'a Caller,
+}
+
+impl<'a> LinkProject<'a> {
+    pub fn new(
+        config_store: &'a ConfigStore,
+        cert_service: &'a CertificateService,
+        caller: &'a Caller,
+    ) -> Self {
+        Self {
+            config_store,
+            cert_service,
+            caller,
+        }
+    }
+
+    /// Register the project's domains, update the ones already linked
+    /// from it, and unregister those it no longer lists. One outcome
+    /// per domain, labelled with its pattern.
+    pub fn link(&self, project: &Project) -> Result<Vec<(String, StepOutcome)>> {
+        let mut steps = project
+            .domains
+            .iter()
+            .map(|domain| {
+                Ok((
+                    domain.pattern.to_string(),
+                    self.link_domain(project, domain)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for registration in self.linked_from(&project.dir)? {
+            let pattern = registration.pattern();
+            if project
+                .domains
+                .iter()
+                .all(|domain| domain.pattern != *pattern)
+            {
+                UnregisterDomain::new(self.config_store, self.cert_service, self.caller)
+                    .execute(pattern)?;
+                steps.push((
+                    pattern.to_string(),
+                    StepOutcome::Success(format!("Unregistered, no longer in {}", PROJECT_FILE)),
+                ));
+            }
+        }
+
+        Ok(steps)
+    }
+
+    /// Unregister every domain linked from the project in `dir`.
+    pub fn unlink(&self, dir: &Path) -> Result<Vec<(String, StepOutcome)>> {
+        let unregister = UnregisterDomain::new(self.config_store, self.cert_service, self.caller);
+        self.linked_from(dir)?
+            .iter()
+            .map(|registration| {
+                let result = unregister.execute(registration.pattern())?;
+                let outcome = match result.cert_outcome {
+                    StepOutcome::Warning(msg) => {
+                        StepOutcome::Warning(format!("Unregistered, but: {}", msg))
+                    }
+                    _ => StepOutcome::Success("Unregistered".into()),
+                };
+                Ok((registration.pattern().to_string(), outcome))
+            })
+            .collect()
+    }
+
+    fn link_domain(&self, project: &Project, domain: &ProjectDomain) -> Result<StepOutcome> {
+        let pattern = &domain.pattern;
+        let Some(mut registration) = self.config_store.get_domain(pattern)? else {
+            let mut register =
+                RegisterDomain::new(self.config_store, self.cert_service, self.caller)
+                    .with_project(&project.dir);
+            if !domain.https {
+                register = register.without_https();
+            }
+            let result = register.execute(pattern.clone(), domain.routes.clone())?;
+            return Ok(match result.cert_outcome {
+                StepOutcome::Warning(msg) => {
+                    StepOutcome::Warning(format!("Registered, but: {}", msg))
+                }
+                _ => StepOutcome::Success("Registered".into()),
+            });
+        };
+
+        ensure_can_manage(self.caller, &registration)?;
+        match registration.project() {
+            Some(dir) if dir == project.dir => {}
+            Some(dir) => {
+                return Ok(StepOutcome::Warning(format!(
+                    "Already linked from {}. Run 'roxy unlink' there first.",
+                    dir.display()
+                )));
+            }
+            None => {
+                return Ok(StepOutcome::Warning(format!(
+                    "Already registered. Run 'roxy unregister {}' first to link it from here.",
+                    pattern.base_domain()
+                )));
+            }
+        }
+
+        registration.set_routes(domain.routes.clone());
+        registration.validate()?;
+        let https_changed =
+            registration.is_https_enabled() != domain.https && registration.custom_cert().is_none();
+        self.config_store.update_domain(registration)?;
+
+        if https_changed {
+            let set_https = SetHttps::new(self.config_store, self.cert_service, self.caller);
+            if let Err(e) = set_https.execute(pattern, domain.https) {
+                return Ok(StepOutcome::Warning(format!(
+                    "Updated, but failed to turn HTTPS {}: {}",
+                    if domain.https { "on" } else { "off" },
+                    e
+                )));
+            }
+        }
+        Ok(StepOutcome::Success("Updated".into()))
+    }
+
+    fn linked_from(&self, dir: &Path) -> Result<Vec<DomainRegistration>> {
+        Ok(self
+            .config_store
+            .list_domains()?
+            .into_iter()
+            .filter(|registration| registration.project() == Some(dir))
+            .collect())
+    }
+}
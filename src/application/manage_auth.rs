@@ -0,0 +1,87 @@
+use anyhow::{Result, anyhow, bail};
+
+use crate::domain::{BasicAuth, DomainPattern, DomainRegistration, PathPrefix};
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::password;
+use crate::infrastructure::user::Caller;
+
+use super::ensure_can_manage;
+
+/// Use case: manage basic auth users of a domain or one of its routes.
+pub struct ManageAuth<'a> {
+    config_store: &'a ConfigStore,
+    caller: &'a Caller,
+}
+
+impl<'a> ManageAuth<'a> {
+    pub fn new(config_store: &'a ConfigStore, caller: &'a Caller) -> Self {
+        Self {
+            config_store,
+            caller,
+        }
+    }
+
+    /// Add a user, or change their password. Only the hash is stored.
+    pub fn add_user(
+        &self,
+        pattern: &DomainPattern,
+        path: Option<&PathPrefix>,
+        user: &str,
+        password: &str,
+    ) -> Result<()> {
+        if user.is_empty() || user.contains(':') {
+            bail!("User name must be non-empty and can't contain ':'");
+        }
+        let password_hash = password::hash(password)?;
+
+        let mut registration = self.registration(pattern)?;
+        let auth = auth_mut(&mut registration, path)?;
+        auth.get_or_insert_default().set_user(user, password_hash);
+        self.config_store.update_domain(registration)?;
+
+        Ok(())
+    }
+
+    /// Remove a user. Removing the last one turns the login off again.
+    pub fn remove_user(
+        &self,
+        pattern: &DomainPattern,
+        path: Option<&PathPrefix>,
+        user: &str,
+    ) -> Result<()> {
+        let mut registration = self.registration(pattern)?;
+        let auth = auth_mut(&mut registration, path)?;
+        if !auth.as_mut().is_some_and(|auth| auth.remove_user(user)) {
+            bail!("User '{}' not found", user);
+        }
+        if auth.as_ref().is_some_and(BasicAuth::is_public) {
+            *auth = None;
+        }
+        self.config_store.update_domain(registration)?;
+
+        Ok(())
+    }
+
+    fn registration(&self, pattern: &DomainPattern) -> Result<DomainRegistration> {
+        let registration = self
+            .config_store
+            .get_domain(pattern)?
+            .ok_or_else(|| anyhow!("Domain '{}' not registered", pattern))?;
+        ensure_can_manage(self.caller, &registration)?;
+        Ok(registration)
+    }
+}
+
+/// The users of the route at `path`, or of the whole domain.
+fn auth_mut<'r>(
+    registration: &'r mut DomainRegistration,
+    path: Option<&PathPrefix>,
+) -> Result<&'r mut Option<BasicAuth>> {
+    match path {
+        Some(path) => registration
+            .route_mut(path)
+            .map(|route| &mut route.options.auth)
+            .ok_or_else(|| anyhow!("No route with path '{}'", path)),
+        None => Ok(registration.auth_mut()),
+    }
+}
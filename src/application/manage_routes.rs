@@ -1,35 +1,37 @@
 use anyhow::{Result, anyhow};
 
-use crate::domain::{DomainPattern, PathPrefix, Route, RouteTarget};
+use crate::domain::{DomainPattern, PathPrefix, Route};
 use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+use super::ensure_can_manage;
 
 /// Use case: manage routes for an existing domain registration.
 pub struct ManageRoutes<'a> {
     config_store: &'a ConfigStore,
+    caller: &'a Caller,
 }
 
 impl<'a> ManageRoutes<'a> {
-    pub fn new(config_store: &'a ConfigStore) -> Self {
-        Self { config_store }
+    pub fn new(config_store: &'a ConfigStore, caller: &'a Caller) -> Self {
+        Self {
+            config_store,
+            caller,
+        }
     }
 
-    /// Add a route to an existing domain. Returns the added route.
-    pub fn add_route(
-        &self,
-        pattern: &DomainPattern,
-        path_prefix: PathPrefix,
-        route_target: RouteTarget,
-    ) -> Result<Route> {
+    /// Add a route to an existing domain.
+    pub fn add_route(&self, pattern: &DomainPattern, route: Route) -> Result<()> {
         let mut registration = self
             .config_store
             .get_domain(pattern)?
             .ok_or_else(|| anyhow!("Domain '{}' not registered", pattern))?;
+        ensure_can_manage(self.caller, &registration)?;
 
-        let route = Route::new(path_prefix, route_target);
-        registration.add_route(route.clone())?;
+        registration.add_route(route)?;
         self.config_store.update_domain(registration)?;
 
-        Ok(route)
+        Ok(())
     }
 
     /// Remove a route from an existing domain.
@@ -38,6 +40,7 @@ impl<'a> ManageRoutes<'a> {
             .config_store
             .get_domain(pattern)?
             .ok_or_else(|| anyhow!("Domain '{}' not registered", pattern))?;
+        ensure_can_manage(self.caller, &registration)?;
 
         registration.remove_route(path_prefix)?;
         self.config_store.update_domain(registration)?;
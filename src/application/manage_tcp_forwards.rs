@@ -0,0 +1,51 @@
+use anyhow::{Result, anyhow};
+
+use crate::domain::{DomainPattern, TcpForward};
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+use super::ensure_can_manage;
+
+/// Use case: manage raw TCP forwards for an existing domain registration.
+pub struct ManageTcpForwards<'a> {
+    config_store: &'a ConfigStore,
+    caller: &'a Caller,
+}
+
+impl<'a> ManageTcpForwards<'a> {
+    pub fn new(config_store: &'a ConfigStore, caller: &'a Caller) -> Self {
+        Self {
+            config_store,
+            caller,
+        }
+    }
+
+    /// Forward a TCP port for an existing domain. The port can't be in
+    /// use by another domain or by the daemon.
+    pub fn add(&self, pattern: &DomainPattern, forward: TcpForward) -> Result<()> {
+        let mut registration = self
+            .config_store
+            .get_domain(pattern)?
+            .ok_or_else(|| anyhow!("Domain '{}' not registered", pattern))?;
+        ensure_can_manage(self.caller, &registration)?;
+
+        registration.add_tcp_forward(forward)?;
+        self.config_store.update_domain(registration)?;
+
+        Ok(())
+    }
+
+    /// Stop forwarding a TCP port for a domain.
+    pub fn remove(&self, pattern: &DomainPattern, port: u16) -> Result<()> {
+        let mut registration = self
+            .config_store
+            .get_domain(pattern)?
+            .ok_or_else(|| anyhow!("Domain '{}' not registered", pattern))?;
+        ensure_can_manage(self.caller, &registration)?;
+
+        registration.remove_tcp_forward(port)?;
+        self.config_store.update_domain(registration)?;
+
+        Ok(())
+    }
+}
@@ -1,11 +1,30 @@
+pub mod delete_profile;
+pub mod import_compose;
+pub mod import_domains;
 pub mod install;
+pub mod link_project;
+pub mod manage_auth;
 pub mod manage_routes;
+pub mod manage_tcp_forwards;
 pub mod register_domain;
+pub mod set_enabled;
+pub mod set_https;
+pub mod set_maintenance;
+pub mod set_tls_passthrough;
+pub mod switch_profile;
 pub mod uninstall;
 pub mod unregister_domain;
 
 use std::fmt;
 
+use anyhow::{Result, bail};
+
+use crate::domain::DomainRegistration;
+use crate::infrastructure::config::Config;
+use crate::infrastructure::dns::hosts::{HostsFile, hostnames};
+use crate::infrastructure::dns::{self, DnsBackend};
+use crate::infrastructure::user::Caller;
+
 /// Outcome of a single step in a multi-step operation.
 ///
 /// Used by application services to report partial success/failure
@@ -26,3 +45,47 @@ impl fmt::Display for StepOutcome {
         }
     }
 }
+
+/// Refuse to change a registration that belongs to another user.
+fn ensure_can_manage(caller: &Caller, registration: &DomainRegistration) -> Result<()> {
+    match registration.owner() {
+        Some(owner) if !caller.can_manage(Some(owner)) => bail!(
+            "Domain '{}' belongs to {}. Only they (or root) can change it.",
+            registration.display_pattern(),
+            owner
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Bring the hosts file in line with the registered domains. `None`
+/// unless `daemon.dns_backend` is `hosts`.
+fn sync_hosts_file(config: &Config) -> Option<StepOutcome> {
+    if config.daemon.dns_backend != DnsBackend::Hosts {
+        return None;
+    }
+
+    let hosts = HostsFile::system();
+    Some(match hosts.sync(&hostnames(&config.registrations())) {
+        Ok(true) => StepOutcome::Success(format!("Updated {}.", hosts.path().display())),
+        Ok(false) => StepOutcome::Skipped(format!("{} is up to date.", hosts.path().display())),
+        Err(e) => StepOutcome::Warning(format!(
+            "Failed to update {}: {}",
+            hosts.path().display(),
+            e
+        )),
+    })
+}
+
+/// Flush the system DNS cache, so a name looked up before it was
+/// registered resolves right away. `None` on platforms without one.
+fn flush_dns_cache() -> Option<StepOutcome> {
+    match dns::flush_cache() {
+        Ok(true) => Some(StepOutcome::Success("Flushed the DNS cache.".into())),
+        Ok(false) => None,
+        Err(e) => Some(StepOutcome::Warning(format!(
+            "{}. If the domain doesn't resolve, run: sudo killall -HUP mDNSResponder",
+            e
+        ))),
+    }
+}
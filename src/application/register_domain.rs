@@ -1,31 +1,85 @@
+use std::path::{Path, PathBuf};
+
 use anyhow::{Result, bail};
+use time::OffsetDateTime;
 
-use crate::domain::{DomainPattern, DomainRegistration, Route};
-use crate::infrastructure::certs::CertificateService;
+use crate::domain::{CustomCert, DomainPattern, DomainRegistration, Route};
+use crate::infrastructure::certs::{CertificateService, custom};
 use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
 
-use super::StepOutcome;
+use super::{StepOutcome, flush_dns_cache, sync_hosts_file};
 
 /// Result of a successful domain registration.
 pub struct RegisterResult {
     pub registration: DomainRegistration,
     pub cert_outcome: StepOutcome,
+    /// Set when domains are resolved through the hosts file.
+    pub hosts_outcome: Option<StepOutcome>,
+    /// Set on platforms whose resolver caches answers.
+    pub dns_cache_outcome: Option<StepOutcome>,
 }
 
 /// Use case: register a new domain with routes.
 pub struct RegisterDomain<'a> {
     config_store: &'a ConfigStore,
     cert_service: &'a CertificateService,
+    caller: &'a Caller,
+    custom_cert: Option<CustomCert>,
+    https: bool,
+    project: Option<PathBuf>,
+    description: Option<String>,
+    tags: Vec<String>,
 }
 
 impl<'a> RegisterDomain<'a> {
-    pub fn new(config_store: &'a ConfigStore, cert_service: &'a CertificateService) -> Self {
+    pub fn new(
+        config_store: &'a ConfigStore,
+        cert_service: &'a CertificateService,
+        caller: &'a Caller,
+    ) -> Self {
         Self {
             config_store,
             cert_service,
+            caller,
+            custom_cert: None,
+            https: true,
+            project: None,
+            description: None,
+            tags: Vec::new(),
         }
     }
 
+    /// Serve the domain over plain HTTP only, without a certificate.
+    pub fn without_https(mut self) -> Self {
+        self.https = false;
+        self
+    }
+
+    /// Serve `cert` instead of generating a certificate.
+    pub fn with_custom_cert(mut self, cert: CustomCert) -> Self {
+        self.custom_cert = Some(cert);
+        self
+    }
+
+    /// Record the domain as linked from the project in `dir`.
+    pub fn with_project(mut self, dir: &Path) -> Self {
+        self.project = Some(dir.to_path_buf());
+        self
+    }
+
+    /// Describe the domain for `roxy list`.
+    pub fn with_description(mut self, description: String) -> Self {
+        self.description = Some(description);
+        self
+    }
+
+    /// Tag the domain, for `roxy list --tag`.
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
     /// Validate inputs, generate a certificate, and persist the registration.
     pub fn execute(&self, pattern: DomainPattern, routes: Vec<Route>) -> Result<RegisterResult> {
         if routes.is_empty() {
@@ -52,6 +106,34 @@ impl<'a> RegisterDomain<'a> {
         }
 
         let mut registration = DomainRegistration::new(pattern.clone(), routes);
+        registration.set_owner(self.caller.name().map(str::to_string));
+        registration.set_project(self.project.clone());
+        registration.set_description(self.description.clone());
+        registration.set_tags(self.tags.clone());
+        registration.validate()?;
+
+        if let Some(cert) = &self.custom_cert {
+            // Unlike a generated one, a broken custom certificate is the
+            // caller's to fix, so it fails the registration
+            let loaded = custom::load(cert)?;
+            registration.set_custom_cert(Some(cert.clone()));
+            let cert_outcome = StepOutcome::Success(format!(
+                "Using {} ({} {}).",
+                cert.cert.display(),
+                if loaded.not_after < OffsetDateTime::now_utc() {
+                    "expired"
+                } else {
+                    "expires"
+                },
+                loaded.not_after.date()
+            ));
+            return self.save(registration, cert_outcome);
+        }
+
+        if !self.https {
+            let cert_outcome = StepOutcome::Skipped("HTTPS turned off.".into());
+            return self.save(registration, cert_outcome);
+        }
 
         // Generate certificate (graceful fallback)
         let cert_outcome = match self.cert_service.create_and_install(&pattern) {
@@ -65,12 +147,23 @@ impl<'a> RegisterDomain<'a> {
                 e
             )),
         };
+        self.save(registration, cert_outcome)
+    }
 
+    fn save(
+        &self,
+        registration: DomainRegistration,
+        cert_outcome: StepOutcome,
+    ) -> Result<RegisterResult> {
         self.config_store.add_domain(registration.clone())?;
+        let hosts_outcome = sync_hosts_file(&self.config_store.load()?);
+        let dns_cache_outcome = flush_dns_cache();
 
         Ok(RegisterResult {
             registration,
             cert_outcome,
+            hosts_outcome,
+            dns_cache_outcome,
         })
     }
 }
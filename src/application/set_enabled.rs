@@ -0,0 +1,38 @@
+use anyhow::{Result, anyhow};
+
+use crate::domain::DomainPattern;
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+use super::ensure_can_manage;
+
+/// Use case: stop serving a domain without unregistering it, or serve
+/// it again.
+pub struct SetEnabled<'a> {
+    config_store: &'a ConfigStore,
+    caller: &'a Caller,
+}
+
+impl<'a> SetEnabled<'a> {
+    pub fn new(config_store: &'a ConfigStore, caller: &'a Caller) -> Self {
+        Self {
+            config_store,
+            caller,
+        }
+    }
+
+    /// Enable or disable the domain. Routes and certificate are kept
+    /// either way.
+    pub fn execute(&self, pattern: &DomainPattern, enabled: bool) -> Result<()> {
+        let mut registration = self
+            .config_store
+            .get_domain(pattern)?
+            .ok_or_else(|| anyhow!("Domain '{}' not registered", pattern))?;
+        ensure_can_manage(self.caller, &registration)?;
+
+        registration.set_enabled(enabled);
+        self.config_store.update_domain(registration)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::{Result, anyhow, bail};
+
+use crate::domain::DomainPattern;
+use crate::infrastructure::certs::CertificateService;
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+use super::ensure_can_manage;
+
+/// Use case: serve a domain over HTTPS, or over plain HTTP only for
+/// tooling that misbehaves with HTTPS.
+pub struct SetHttps<'a> {
+    config_store: &'a ConfigStore,
+    cert_service: &'a CertificateService,
+    caller: &'a Caller,
+}
+
+impl<'a> SetHttps<'a> {
+    pub fn new(
+        config_store: &'a ConfigStore,
+        cert_service: &'a CertificateService,
+        caller: &'a Caller,
+    ) -> Self {
+        Self {
+            config_store,
+            cert_service,
+            caller,
+        }
+    }
+
+    /// Turn HTTPS on, issuing a certificate if the domain has none, or
+    /// off, deleting the one Roxy issued. A domain serving its own
+    /// certificate keeps HTTPS until the certificate is removed.
+    pub fn execute(&self, pattern: &DomainPattern, on: bool) -> Result<()> {
+        let mut registration = self
+            .config_store
+            .get_domain(pattern)?
+            .ok_or_else(|| anyhow!("Domain '{}' not registered", pattern))?;
+        ensure_can_manage(self.caller, &registration)?;
+
+        if on {
+            if registration.is_https_enabled() {
+                return Ok(());
+            }
+            if !self.cert_service.exists(pattern) {
+                self.cert_service.create_and_install(pattern)?;
+            }
+            registration.enable_https();
+        } else {
+            if registration.custom_cert().is_some() {
+                bail!(
+                    "'{}' serves its own certificate. Remove its certificate setting first.",
+                    pattern
+                );
+            }
+            registration.disable_https();
+        }
+
+        registration.validate()?;
+        self.config_store.update_domain(registration)?;
+        if !on {
+            self.cert_service.remove(pattern)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use anyhow::{Result, anyhow};
+
+use crate::domain::DomainPattern;
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+use super::ensure_can_manage;
+
+/// Use case: take a domain offline for maintenance, or bring it back.
+pub struct SetMaintenance<'a> {
+    config_store: &'a ConfigStore,
+    caller: &'a Caller,
+}
+
+impl<'a> SetMaintenance<'a> {
+    pub fn new(config_store: &'a ConfigStore, caller: &'a Caller) -> Self {
+        Self {
+            config_store,
+            caller,
+        }
+    }
+
+    /// Turn maintenance mode on, with an optional message for visitors,
+    /// or off. Routes are kept either way.
+    pub fn execute(
+        &self,
+        pattern: &DomainPattern,
+        on: bool,
+        message: Option<String>,
+    ) -> Result<()> {
+        let mut registration = self
+            .config_store
+            .get_domain(pattern)?
+            .ok_or_else(|| anyhow!("Domain '{}' not registered", pattern))?;
+        ensure_can_manage(self.caller, &registration)?;
+
+        registration.set_maintenance(on, message);
+        self.config_store.update_domain(registration)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use anyhow::{Result, anyhow};
+
+use crate::domain::DomainPattern;
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+use super::ensure_can_manage;
+
+/// Use case: hand a domain's HTTPS connections to its backend without
+/// decrypting them, or terminate TLS in Roxy again.
+pub struct SetTlsPassthrough<'a> {
+    config_store: &'a ConfigStore,
+    caller: &'a Caller,
+}
+
+impl<'a> SetTlsPassthrough<'a> {
+    pub fn new(config_store: &'a ConfigStore, caller: &'a Caller) -> Self {
+        Self {
+            config_store,
+            caller,
+        }
+    }
+
+    /// Turn passthrough on or off. Turning it on needs a proxy route for
+    /// `/`, whose backend receives the connections.
+    pub fn execute(&self, pattern: &DomainPattern, on: bool) -> Result<()> {
+        let mut registration = self
+            .config_store
+            .get_domain(pattern)?
+            .ok_or_else(|| anyhow!("Domain '{}' not registered", pattern))?;
+        ensure_can_manage(self.caller, &registration)?;
+
+        registration.set_tls_passthrough(on);
+        registration.validate()?;
+        self.config_store.update_domain(registration)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::Result;
+
+use crate::infrastructure::certs::CertificateService;
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+use super::{StepOutcome, ensure_can_manage, flush_dns_cache, sync_hosts_file};
+
+/// Use case: register another profile's domains in place of the
+/// active profile's.
+pub struct SwitchProfile<'a> {
+    config_store: &'a ConfigStore,
+    cert_service: &'a CertificateService,
+    caller: &'a Caller,
+}
+
+impl<'a> SwitchProfile<'a> {
+    pub fn new(
+        config_store: &'a ConfigStore,
+        cert_service: &'a CertificateService,
+        caller: &'a Caller,
+    ) -> Self {
+        Self {
+            config_store,
+            cert_service,
+            caller,
+        }
+    }
+
+    /// Switch to the `name` profile. Certificates are per name, so one a
+    /// profile's domain relied on may have been removed meanwhile by
+    /// another profile unregistering the same name; those are issued
+    /// again. Switching stops serving the active profile's domains, so
+    /// it's refused while one of them belongs to another user.
+    pub fn execute(&self, name: &str) -> Result<Vec<StepOutcome>> {
+        let config = self.config_store.read()?;
+        if config.active_profile() != name {
+            for registration in config.registrations() {
+                ensure_can_manage(self.caller, &registration)?;
+            }
+        }
+
+        let registrations = self.config_store.use_profile(name)?;
+        let mut steps = vec![StepOutcome::Success(format!(
+            "Using profile '{}' ({} domain{}).",
+            name,
+            registrations.len(),
+            if registrations.len() == 1 { "" } else { "s" }
+        ))];
+
+        for registration in &registrations {
+            let pattern = registration.pattern();
+            if !registration.has_roxy_cert() || self.cert_service.exists(pattern) {
+                continue;
+            }
+            steps.push(match self.cert_service.create_and_install(pattern) {
+                Ok(()) => StepOutcome::Success(format!("Issued a certificate for {}.", pattern)),
+                Err(e) => StepOutcome::Warning(format!(
+                    "Failed to issue a certificate for {}: {}",
+                    pattern, e
+                )),
+            });
+        }
+
+        steps.extend(sync_hosts_file(&self.config_store.load()?));
+        steps.extend(flush_dns_cache());
+        Ok(steps)
+    }
+}
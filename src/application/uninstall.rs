@@ -5,9 +5,12 @@ use anyhow::Result;
 
 use crate::infrastructure::certs::CertificateService;
 use crate::infrastructure::config::ConfigStore;
-use crate::infrastructure::dns::get_dns_service;
+use crate::infrastructure::dns::hosts::HostsFile;
+use crate::infrastructure::dns::{DnsBackend, DnsService, DnsmasqDnsService, get_dns_service};
 use crate::infrastructure::paths::RoxyPaths;
 use crate::infrastructure::pid::PidFile;
+use crate::infrastructure::port_forward::get_port_forwarder;
+use crate::infrastructure::service::get_service_manager;
 
 use super::StepOutcome;
 
@@ -59,6 +62,7 @@ impl<'a> Uninstall<'a> {
         self.stop_daemon(&mut steps)?;
         self.remove_certificates(&mut steps);
         self.remove_dns(&mut steps)?;
+        self.remove_port_forwarding(&mut steps);
         self.remove_data(&mut steps)?;
         self.cleanup_files(&mut steps);
 
@@ -66,6 +70,17 @@ impl<'a> Uninstall<'a> {
     }
 
     fn stop_daemon(&self, steps: &mut Vec<(String, StepOutcome)>) -> Result<()> {
+        // Otherwise the service manager would start the daemon again
+        if let Ok(service) = get_service_manager()
+            && service.is_installed()
+        {
+            let outcome = match service.uninstall() {
+                Ok(()) => StepOutcome::Success(format!("{} service removed.", service.name())),
+                Err(e) => StepOutcome::Warning(format!("Failed to remove service: {}", e)),
+            };
+            steps.push(("Remove service".into(), outcome));
+        }
+
         let pid_file = PidFile::new(self.paths.pid_file.clone());
         if pid_file.get_running_pid()?.is_some() {
             pid_file.stop_gracefully(Duration::from_millis(500))?;
@@ -94,7 +109,11 @@ impl<'a> Uninstall<'a> {
             steps.push((label, outcome));
         }
 
+        let imported = self.cert_service.is_ca_imported();
         let ca_outcome = match self.cert_service.remove_ca() {
+            Ok(_) if imported => StepOutcome::Success(
+                "Removed Roxy's copy of the imported Root CA; it stays trusted.".into(),
+            ),
             Ok(_) => StepOutcome::Success("Root CA removed.".into()),
             Err(e) => StepOutcome::Warning(format!("Failed to remove Root CA: {}", e)),
         };
@@ -102,7 +121,42 @@ impl<'a> Uninstall<'a> {
     }
 
     fn remove_dns(&self, steps: &mut Vec<(String, StepOutcome)>) -> Result<()> {
-        let dns = get_dns_service()?;
+        let hosts = HostsFile::system();
+        match hosts.remove() {
+            Ok(true) => steps.push((
+                "Remove hosts entries".into(),
+                StepOutcome::Success(format!("Removed from {}.", hosts.path().display())),
+            )),
+            Ok(false) => {}
+            Err(e) => steps.push((
+                "Remove hosts entries".into(),
+                StepOutcome::Warning(format!("Failed: {}", e)),
+            )),
+        }
+
+        if let Ok(dnsmasq) = DnsmasqDnsService::detect()
+            && dnsmasq.is_configured()
+        {
+            let outcome = match dnsmasq.cleanup() {
+                Ok(()) => {
+                    StepOutcome::Success(format!("Removed {}.", dnsmasq.snippet_path().display()))
+                }
+                Err(e) => StepOutcome::Warning(format!("Failed: {}", e)),
+            };
+            steps.push(("Remove dnsmasq snippet".into(), outcome));
+        }
+
+        let backend = self
+            .config_store
+            .load()
+            .map(|config| config.daemon.dns_backend)
+            .unwrap_or_default();
+        let dns = match get_dns_service() {
+            Ok(dns) => dns,
+            // The other backends work where no resolver integration exists
+            Err(_) if backend != DnsBackend::Resolver => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
         if dns.is_configured() {
             dns.cleanup()?;
             steps.push((
@@ -118,6 +172,21 @@ impl<'a> Uninstall<'a> {
         Ok(())
     }
 
+    fn remove_port_forwarding(&self, steps: &mut Vec<(String, StepOutcome)>) {
+        let Ok(forwarder) = get_port_forwarder() else {
+            return;
+        };
+        if !forwarder.is_configured() {
+            return;
+        }
+
+        let outcome = match forwarder.cleanup() {
+            Ok(()) => StepOutcome::Success("Port forwarding rules removed.".into()),
+            Err(e) => StepOutcome::Warning(format!("Failed: {}", e)),
+        };
+        steps.push(("Remove port forwarding".into(), outcome));
+    }
+
     fn remove_data(&self, steps: &mut Vec<(String, StepOutcome)>) -> Result<()> {
         if self.paths.data_dir.exists() {
             fs::remove_dir_all(&self.paths.data_dir)?;
@@ -3,35 +3,47 @@ use anyhow::{Result, anyhow};
 use crate::domain::{DomainPattern, DomainRegistration};
 use crate::infrastructure::certs::CertificateService;
 use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
 
-use super::StepOutcome;
+use super::{StepOutcome, ensure_can_manage, sync_hosts_file};
 
 /// Result of a successful domain unregistration.
 pub struct UnregisterResult {
     pub registration: DomainRegistration,
     pub cert_outcome: StepOutcome,
+    /// Set when domains are resolved through the hosts file.
+    pub hosts_outcome: Option<StepOutcome>,
 }
 
 /// Use case: unregister a domain and clean up its certificate.
 pub struct UnregisterDomain<'a> {
     config_store: &'a ConfigStore,
     cert_service: &'a CertificateService,
+    caller: &'a Caller,
 }
 
 impl<'a> UnregisterDomain<'a> {
-    pub fn new(config_store: &'a ConfigStore, cert_service: &'a CertificateService) -> Self {
+    pub fn new(
+        config_store: &'a ConfigStore,
+        cert_service: &'a CertificateService,
+        caller: &'a Caller,
+    ) -> Self {
         Self {
             config_store,
             cert_service,
+            caller,
         }
     }
 
     /// Look up the registration so the CLI can show a confirmation
     /// prompt before proceeding with `execute()`.
     pub fn preview(&self, pattern: &DomainPattern) -> Result<DomainRegistration> {
-        self.config_store
+        let registration = self
+            .config_store
             .get_domain(pattern)?
-            .ok_or_else(|| anyhow!("Domain '{}' is not registered.", pattern))
+            .ok_or_else(|| anyhow!("Domain '{}' is not registered.", pattern))?;
+        ensure_can_manage(self.caller, &registration)?;
+        Ok(registration)
     }
 
     /// Remove the domain certificate and config entry.
@@ -48,10 +60,12 @@ impl<'a> UnregisterDomain<'a> {
         };
 
         self.config_store.remove_domain(pattern)?;
+        let hosts_outcome = sync_hosts_file(&self.config_store.load()?);
 
         Ok(UnregisterResult {
             registration,
             cert_outcome,
+            hosts_outcome,
         })
     }
 }
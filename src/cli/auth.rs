@@ -0,0 +1,118 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+
+use anyhow::{Result, bail};
+
+use crate::application::manage_auth::ManageAuth;
+use crate::domain::{DomainPattern, PathPrefix};
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+/// Require a login for a domain or one of its routes
+pub fn add(
+    domain: String,
+    wildcard: bool,
+    user: String,
+    path: Option<String>,
+    config_path: &Path,
+) -> Result<()> {
+    let pattern = DomainPattern::from_name(&domain, wildcard)?;
+    let path_prefix = path.as_deref().map(PathPrefix::new).transpose()?;
+    let password = read_password()?;
+
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let caller = Caller::current();
+    let use_case = ManageAuth::new(&config_store, &caller);
+
+    use_case.add_user(&pattern, path_prefix.as_ref(), &user, &password)?;
+
+    println!("Added user '{}' to {}", user, scope(&pattern, &path_prefix));
+    println!("\nReload the daemon to apply changes: roxy reload");
+
+    Ok(())
+}
+
+/// Remove a user from a domain or route
+pub fn remove(
+    domain: String,
+    wildcard: bool,
+    user: String,
+    path: Option<String>,
+    config_path: &Path,
+) -> Result<()> {
+    let pattern = DomainPattern::from_name(&domain, wildcard)?;
+    let path_prefix = path.as_deref().map(PathPrefix::new).transpose()?;
+
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let caller = Caller::current();
+    let use_case = ManageAuth::new(&config_store, &caller);
+
+    use_case.remove_user(&pattern, path_prefix.as_ref(), &user)?;
+
+    println!(
+        "Removed user '{}' from {}",
+        user,
+        scope(&pattern, &path_prefix)
+    );
+    println!("\nReload the daemon to apply changes: roxy reload");
+
+    Ok(())
+}
+
+fn scope(pattern: &DomainPattern, path: &Option<PathPrefix>) -> String {
+    match path {
+        Some(path) => format!("{} route {}", pattern, path),
+        None => pattern.to_string(),
+    }
+}
+
+/// Ask for the password twice on a terminal; otherwise take the first
+/// line of stdin, so scripts can pipe it in.
+fn read_password() -> Result<String> {
+    if !io::stdin().is_terminal() {
+        return read_line();
+    }
+
+    eprint!("Password: ");
+    let password = read_hidden_line()?;
+    eprint!("Repeat password: ");
+    if read_hidden_line()? != password {
+        bail!("Passwords don't match");
+    }
+    Ok(password)
+}
+
+fn read_line() -> Result<String> {
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(unix)]
+fn read_hidden_line() -> Result<String> {
+    io::stderr().flush()?;
+    let fd = libc::STDIN_FILENO;
+
+    // SAFETY: termios is plain data, filled in by tcgetattr before use
+    let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+        return read_line();
+    }
+    let mut hidden = saved;
+    hidden.c_lflag &= !libc::ECHO;
+    // SAFETY: both point to initialized termios values on the stack
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &hidden) };
+
+    let line = read_line();
+
+    // SAFETY: as above; restores the settings read earlier
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &saved) };
+    eprintln!();
+    line
+}
+
+#[cfg(not(unix))]
+fn read_hidden_line() -> Result<String> {
+    io::stderr().flush()?;
+    read_line()
+}
@@ -0,0 +1,111 @@
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use tokio::net::TcpListener;
+
+use crate::daemon::ca_download::{self, SHARE_PATH};
+use crate::infrastructure::certs::ca::RootCA;
+use crate::infrastructure::config::Config;
+use crate::infrastructure::network::get_lan_ip;
+use crate::infrastructure::paths::RoxyPaths;
+use crate::infrastructure::pid::PidFile;
+use crate::infrastructure::qr::QrCode;
+
+/// Write the Root CA certificate to `path`, or to stdout without one
+pub fn export(path: Option<PathBuf>, config: &Config, paths: &RoxyPaths) -> Result<()> {
+    let ca = RootCA::new(paths.data_dir.clone());
+    if !ca.exists() {
+        bail!("No Root CA yet. Run: sudo roxy install");
+    }
+    let pem = std::fs::read_to_string(ca.cert_path())
+        .with_context(|| format!("Failed to read {}", ca.cert_path().display()))?;
+
+    let Some(path) = path else {
+        print!("{}", pem);
+        return Ok(());
+    };
+    std::fs::write(&path, pem).with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("Exported the Root CA to {}", path.display());
+
+    let port = http_port_suffix(config);
+    println!();
+    println!("While the daemon runs, other devices can download it too:");
+    println!("  http://roxy.roxy{}/ (with install instructions)", port);
+    println!("  http://{}{}/roxy-ca.pem", get_lan_ip(), port);
+
+    Ok(())
+}
+
+/// Show a QR code that opens the Root CA's install page on a phone on the
+/// same network. The running daemon serves the page; without one it is
+/// served from here, on `port` or any free one, until Ctrl-C.
+pub fn share(port: Option<u16>, config: &Config, paths: &RoxyPaths) -> Result<()> {
+    let ca = RootCA::new(paths.data_dir.clone());
+    if !ca.exists() {
+        bail!("No Root CA yet. Run: sudo roxy install");
+    }
+    let lan_ip = get_lan_ip();
+    if lan_ip.is_loopback() {
+        bail!("No network address found. Connect to the network the phone is on.");
+    }
+
+    if PidFile::new(paths.pid_file.clone()).is_running()? {
+        let url = format!(
+            "http://{}{}{}",
+            lan_ip,
+            http_port_suffix(config),
+            SHARE_PATH
+        );
+        print_qr(&url);
+        println!("Served by the running daemon.");
+        return Ok(());
+    }
+
+    let runtime = tokio::runtime::Runtime::new().context("Failed to start the async runtime")?;
+    runtime.block_on(async {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port.unwrap_or(0)))
+            .await
+            .context("Failed to open a port for the install page")?;
+        let url = format!(
+            "http://{}:{}{}",
+            lan_ip,
+            listener.local_addr()?.port(),
+            SHARE_PATH
+        );
+        print_qr(&url);
+        println!(
+            "The daemon isn't running, so the page is served from here. Press Ctrl-C to stop."
+        );
+
+        tokio::select! {
+            served = ca_download::serve(listener, ca.cert_path()) => {
+                served.context("Failed to serve the install page")
+            }
+            _ = tokio::signal::ctrl_c() => Ok(()),
+        }
+    })
+}
+
+fn print_qr(url: &str) {
+    println!("Scan with the phone's camera to install Roxy's Root CA:");
+    println!();
+    // The URL alone still works if it's too long to encode
+    if let Ok(qr) = QrCode::encode(url.as_bytes()) {
+        print!("{}", qr.to_terminal());
+        println!();
+    }
+    println!("  {}", url);
+    println!();
+}
+
+/// The `:port` in URLs of the daemon's HTTP listener, empty on port 80.
+/// Rootless installs forward port 80 to the daemon's port.
+fn http_port_suffix(config: &Config) -> String {
+    let daemon = &config.daemon;
+    if daemon.http_port == 80 || daemon.rootless {
+        String::new()
+    } else {
+        format!(":{}", daemon.http_port)
+    }
+}
@@ -0,0 +1,16 @@
+use anyhow::Result;
+
+use crate::domain::DomainPattern;
+use crate::infrastructure::control::{ControlClient, ControlRequest};
+use crate::infrastructure::paths::RoxyPaths;
+
+/// Drop the daemon's cached responses for a domain
+pub fn purge(domain: String, wildcard: bool, paths: &RoxyPaths) -> Result<()> {
+    let pattern = DomainPattern::from_name(&domain, wildcard)?;
+
+    let client = ControlClient::new(paths.control_socket.clone());
+    let message = client.send(&ControlRequest::PurgeCache(pattern.to_string()))?;
+    println!("{}", message);
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::infrastructure::config::{ConfigStore, Snapshot};
+
+/// Set a config value by dotted key
+pub fn set(key: String, value: String, config_path: &Path) -> Result<()> {
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    config_store.set_value(&key, &value)?;
+
+    println!("Set {} = {}", key, value);
+    println!("\nReload the daemon to apply changes: roxy reload");
+
+    Ok(())
+}
+
+/// Remove a config value, reverting it to the default
+pub fn unset(key: String, config_path: &Path) -> Result<()> {
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    config_store.unset_value(&key)?;
+
+    println!("Unset {}", key);
+    println!("\nReload the daemon to apply changes: roxy reload");
+
+    Ok(())
+}
+
+/// List the config snapshots `rollback` can restore
+pub fn history(config_path: &Path) -> Result<()> {
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let snapshots = config_store.history()?;
+    if snapshots.is_empty() {
+        println!("No config snapshots yet. One is kept before every change.");
+        return Ok(());
+    }
+
+    println!("Config snapshots, newest first:\n");
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        println!("  {:>2}  {}", i + 1, describe(snapshot));
+    }
+    println!("\nRestore one with: roxy config rollback <N>");
+
+    Ok(())
+}
+
+/// Restore a config snapshot, by default the one before the last change
+pub fn rollback(n: Option<usize>, config_path: &Path) -> Result<()> {
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let snapshot = config_store.rollback(n.unwrap_or(1))?;
+
+    println!("Restored the config from {}", describe(&snapshot));
+    println!("Undo with: roxy config rollback");
+    println!("\nReload the daemon to apply changes: roxy reload");
+
+    Ok(())
+}
+
+fn describe(snapshot: &Snapshot) -> String {
+    let saved_at = humantime::format_rfc3339_seconds(snapshot.saved_at);
+    match snapshot.domains {
+        Some(1) => format!("{} (1 domain)", saved_at),
+        Some(n) => format!("{} ({} domains)", saved_at, n),
+        None => format!("{} (unreadable)", saved_at),
+    }
+}
@@ -0,0 +1,41 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::application::set_enabled::SetEnabled;
+use crate::domain::DomainPattern;
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+/// Serve a disabled domain again
+pub fn enable(domain: String, wildcard: bool, config_path: &Path) -> Result<()> {
+    set(domain, wildcard, true, config_path)
+}
+
+/// Stop serving a domain, keeping its routes and certificate
+pub fn disable(domain: String, wildcard: bool, config_path: &Path) -> Result<()> {
+    set(domain, wildcard, false, config_path)
+}
+
+fn set(domain: String, wildcard: bool, enabled: bool, config_path: &Path) -> Result<()> {
+    let pattern = DomainPattern::from_name(&domain, wildcard)?;
+
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let caller = Caller::current();
+    let use_case = SetEnabled::new(&config_store, &caller);
+
+    use_case.execute(&pattern, enabled)?;
+
+    if enabled {
+        println!("Enabled {}", pattern);
+    } else {
+        println!("Disabled {}", pattern);
+        println!(
+            "Its routes and certificate are kept. Serve it again with: roxy enable {}",
+            domain
+        );
+    }
+    println!("\nReload the daemon to apply changes: roxy reload");
+
+    Ok(())
+}
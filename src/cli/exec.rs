@@ -0,0 +1,71 @@
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::infrastructure::config::Config;
+use crate::infrastructure::network::get_lan_ip;
+
+/// Run `command` with the URL of a registered domain in its environment.
+/// Replaces the current process (or, on Windows, waits for it), so the
+/// command's exit code is roxy's.
+pub fn execute(domain: String, command: Vec<String>, config: &Config) -> Result<()> {
+    let host = domain.trim_end_matches('.').to_lowercase();
+
+    // Exact registrations win over wildcards, as in the daemon
+    let mut domains = config.registrations();
+    domains.sort_by_key(|reg| reg.is_wildcard());
+    let registration = domains
+        .iter()
+        .find(|reg| reg.pattern().matches_hostname(&host))
+        .ok_or_else(|| {
+            anyhow!(
+                "Domain '{}' is not registered. See 'roxy list' for registered domains.",
+                domain
+            )
+        })?;
+
+    let daemon = &config.daemon;
+    let (scheme, port, default_port) = if registration.is_https_enabled() {
+        ("https", daemon.https_port, 443)
+    } else {
+        ("http", daemon.http_port, 80)
+    };
+    let port_suffix = if port == default_port {
+        String::new()
+    } else {
+        format!(":{}", port)
+    };
+
+    let (program, args) = command.split_first().context("No command given")?;
+    let mut child = Command::new(program);
+    child
+        .args(args)
+        .env("ROXY_URL", format!("{}://{}{}", scheme, host, port_suffix))
+        .env("ROXY_HOST", &host);
+
+    // Other devices can't verify a certificate for an IP, so plain HTTP
+    let lan_ip = get_lan_ip();
+    if !lan_ip.is_loopback() {
+        let http_suffix = if daemon.http_port == 80 {
+            String::new()
+        } else {
+            format!(":{}", daemon.http_port)
+        };
+        child.env("ROXY_LAN_URL", format!("http://{}{}", lan_ip, http_suffix));
+    }
+
+    #[cfg(unix)]
+    {
+        Err(child.exec()).context(format!("Failed to run '{}'", program))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let status = child
+            .status()
+            .context(format!("Failed to run '{}'", program))?;
+        std::process::exit(status.code().unwrap_or(1))
+    }
+}
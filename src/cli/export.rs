@@ -0,0 +1,15 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::infrastructure::config::{self, ConfigStore};
+
+/// Print every domain registration as a file for `roxy import`
+pub fn execute(config_path: &Path) -> Result<()> {
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let registrations = config_store.list_domains()?;
+
+    print!("{}", config::export_domains(&registrations)?);
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::application::set_https::SetHttps;
+use crate::domain::DomainPattern;
+use crate::infrastructure::certs::CertificateService;
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::paths::RoxyPaths;
+use crate::infrastructure::user::Caller;
+
+/// Serve the domain over HTTPS, issuing a certificate if needed
+pub fn on(domain: String, wildcard: bool, config_path: &Path, paths: &RoxyPaths) -> Result<()> {
+    set(domain, wildcard, true, config_path, paths)
+}
+
+/// Serve the domain over plain HTTP only
+pub fn off(domain: String, wildcard: bool, config_path: &Path, paths: &RoxyPaths) -> Result<()> {
+    set(domain, wildcard, false, config_path, paths)
+}
+
+fn set(
+    domain: String,
+    wildcard: bool,
+    on: bool,
+    config_path: &Path,
+    paths: &RoxyPaths,
+) -> Result<()> {
+    let pattern = DomainPattern::from_name(&domain, wildcard)?;
+
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let cert_service = CertificateService::new(paths).with_settings(&config_store.load()?.certs);
+    let caller = Caller::current();
+    let use_case = SetHttps::new(&config_store, &cert_service, &caller);
+
+    use_case.execute(&pattern, on)?;
+
+    if on {
+        println!("HTTPS on for {}", pattern);
+    } else {
+        println!("HTTPS off for {}", pattern);
+    }
+    println!("\nReload the daemon to apply changes: roxy reload");
+
+    Ok(())
+}
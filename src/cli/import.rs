@@ -0,0 +1,94 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::application::StepOutcome;
+use crate::application::import_compose::ImportCompose;
+use crate::application::import_domains::{ImportDomains, ImportMode};
+use crate::infrastructure::certs::CertificateService;
+use crate::infrastructure::compose;
+use crate::infrastructure::config::{self, ConfigStore};
+use crate::infrastructure::paths::RoxyPaths;
+use crate::infrastructure::user::Caller;
+
+pub fn compose(file: &Path, dry_run: bool, config_path: &Path, paths: &RoxyPaths) -> Result<()> {
+    let services = compose::load(file)?;
+    if services.is_empty() {
+        println!("No services found in {}.", file.display());
+        return Ok(());
+    }
+
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let cert_service = CertificateService::new(paths).with_settings(&config_store.load()?.certs);
+    let caller = Caller::current();
+    let use_case = ImportCompose::new(&config_store, &cert_service, &caller);
+
+    println!("Importing services from {}...\n", file.display());
+    let steps = use_case.execute(&services, dry_run)?;
+
+    for (service, outcome) in &steps {
+        match outcome {
+            StepOutcome::Success(msg) => println!("  {}: {}", service, msg),
+            StepOutcome::Warning(msg) => eprintln!("  {}: {}", service, msg),
+            StepOutcome::Skipped(msg) => println!("  {}: {}", service, msg),
+        }
+    }
+
+    if dry_run {
+        println!("\nDry run, nothing was registered. Run without --dry-run to apply.");
+    } else if steps
+        .iter()
+        .any(|(_, outcome)| !matches!(outcome, StepOutcome::Skipped(_)))
+    {
+        println!("\nReload the daemon to apply changes: roxy reload");
+    }
+
+    Ok(())
+}
+
+/// Register the domains in a file written by `roxy export`
+pub fn domains(
+    file: &Path,
+    merge: bool,
+    replace: bool,
+    config_path: &Path,
+    paths: &RoxyPaths,
+) -> Result<()> {
+    let registrations = config::read_domains(file)?;
+    if registrations.is_empty() && !replace {
+        println!("No domains found in {}.", file.display());
+        return Ok(());
+    }
+    let mode = if replace {
+        ImportMode::Replace
+    } else if merge {
+        ImportMode::Merge
+    } else {
+        ImportMode::Add
+    };
+
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let cert_service = CertificateService::new(paths).with_settings(&config_store.load()?.certs);
+    let caller = Caller::current();
+    let use_case = ImportDomains::new(&config_store, &cert_service, &caller);
+
+    println!("Importing domains from {}...\n", file.display());
+    let steps = use_case.execute(registrations, mode)?;
+
+    for (domain, outcome) in &steps {
+        match outcome {
+            StepOutcome::Success(msg) => println!("  {}: {}", domain, msg),
+            StepOutcome::Warning(msg) => eprintln!("  {}: {}", domain, msg),
+            StepOutcome::Skipped(msg) => println!("  {}: {}", domain, msg),
+        }
+    }
+
+    if steps
+        .iter()
+        .any(|(_, outcome)| !matches!(outcome, StepOutcome::Skipped(_)))
+    {
+        println!("\nReload the daemon to apply changes: roxy reload");
+    }
+
+    Ok(())
+}
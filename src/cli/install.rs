@@ -1,19 +1,44 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 
 use crate::application::StepOutcome;
 use crate::application::install::Install;
-use crate::infrastructure::certs::CertificateService;
+use crate::infrastructure::certs::{CertificateService, mkcert};
 use crate::infrastructure::config::{Config, ConfigStore};
 use crate::infrastructure::paths::RoxyPaths;
 
-pub fn execute(config_path: &Path, paths: &RoxyPaths, config: &Config) -> Result<()> {
+pub fn execute(
+    service_user: bool,
+    rootless: bool,
+    ca_from_mkcert: bool,
+    ca_files: Option<(PathBuf, PathBuf)>,
+    config_path: &Path,
+    paths: &RoxyPaths,
+    config: &Config,
+) -> Result<()> {
+    let ca_import = if ca_from_mkcert {
+        Some(mkcert::find_ca()?)
+    } else {
+        ca_files
+    };
+
     println!("Setting up Roxy...\n");
 
     let config_store = ConfigStore::new(config_path.to_path_buf());
-    let cert_service = CertificateService::new(paths);
-    let use_case = Install::new(&config_store, &cert_service, config_path, paths, config);
+    let cert_service = CertificateService::new(paths).with_settings(&config.certs);
+    let mut use_case = Install::new(
+        &config_store,
+        &cert_service,
+        config_path,
+        paths,
+        config,
+        service_user,
+        rootless,
+    );
+    if let Some((cert, key)) = ca_import {
+        use_case = use_case.with_ca_import(cert, key);
+    }
     let result = use_case.execute()?;
 
     println!("  Using IP address: {}", result.lan_ip);
@@ -32,6 +57,9 @@ pub fn execute(config_path: &Path, paths: &RoxyPaths, config: &Config) -> Result
     println!("\nRoxy installation complete!");
     println!();
     println!("Register domains with: roxy register <domain> --port <port>");
+    if rootless {
+        println!("Start the daemon without sudo: roxy start");
+    }
 
     Ok(())
 }
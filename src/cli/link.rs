@@ -0,0 +1,70 @@
+use std::env;
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::application::StepOutcome;
+use crate::application::link_project::LinkProject;
+use crate::infrastructure::certs::CertificateService;
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::paths::RoxyPaths;
+use crate::infrastructure::project::{self, PROJECT_FILE};
+use crate::infrastructure::user::Caller;
+
+/// Register the domains in the current project's `.roxy.toml`
+pub fn link(config_path: &Path, paths: &RoxyPaths) -> Result<()> {
+    let dir = project::find(&env::current_dir()?.canonicalize()?)?;
+    let project = project::load(&dir)?;
+
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let cert_service = CertificateService::new(paths).with_settings(&config_store.load()?.certs);
+    let caller = Caller::current();
+    let use_case = LinkProject::new(&config_store, &cert_service, &caller);
+
+    println!("Linking {}...\n", dir.join(PROJECT_FILE).display());
+    let steps = use_case.link(&project)?;
+    if steps.is_empty() {
+        println!("No domains in {}.", PROJECT_FILE);
+        return Ok(());
+    }
+    print_steps(&steps);
+
+    Ok(())
+}
+
+/// Unregister the domains linked from the current project
+pub fn unlink(config_path: &Path, paths: &RoxyPaths) -> Result<()> {
+    let cwd = env::current_dir()?.canonicalize()?;
+    let dir = project::find(&cwd).unwrap_or(cwd);
+
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let cert_service = CertificateService::new(paths).with_settings(&config_store.load()?.certs);
+    let caller = Caller::current();
+    let use_case = LinkProject::new(&config_store, &cert_service, &caller);
+
+    let steps = use_case.unlink(&dir)?;
+    if steps.is_empty() {
+        println!("No domains are linked from {}.", dir.display());
+        return Ok(());
+    }
+    print_steps(&steps);
+
+    Ok(())
+}
+
+fn print_steps(steps: &[(String, StepOutcome)]) {
+    for (domain, outcome) in steps {
+        match outcome {
+            StepOutcome::Success(msg) => println!("  {}: {}", domain, msg),
+            StepOutcome::Warning(msg) => eprintln!("  {}: {}", domain, msg),
+            StepOutcome::Skipped(msg) => println!("  {}: {}", domain, msg),
+        }
+    }
+
+    if steps
+        .iter()
+        .any(|(_, outcome)| !matches!(outcome, StepOutcome::Skipped(_)))
+    {
+        println!("\nReload the daemon to apply changes: roxy reload");
+    }
+}
@@ -1,17 +1,50 @@
 use std::path::Path;
 
 use anyhow::Result;
+use time::OffsetDateTime;
 
-use crate::domain::RouteTarget;
+use crate::domain::{DomainRegistration, RouteTarget};
 use crate::infrastructure::certs::CertificateService;
 use crate::infrastructure::config::ConfigStore;
 use crate::infrastructure::paths::RoxyPaths;
 
-pub fn execute(config_path: &Path, paths: &RoxyPaths) -> Result<()> {
+pub fn execute(
+    all_profiles: bool,
+    tag: Option<&str>,
+    config_path: &Path,
+    paths: &RoxyPaths,
+) -> Result<()> {
     let config_store = ConfigStore::new(config_path.to_path_buf());
     let cert_service = CertificateService::new(paths);
-    let domains = config_store.list_domains()?;
+    let config = config_store.load()?;
+    let profiles = config.profiles();
+    let tagged = |domains: Vec<DomainRegistration>| -> Vec<DomainRegistration> {
+        match tag {
+            Some(tag) => domains.into_iter().filter(|reg| reg.has_tag(tag)).collect(),
+            None => domains,
+        }
+    };
+
+    if all_profiles {
+        for (name, domains) in profiles {
+            let active = if name == config.active_profile() {
+                " (active)"
+            } else {
+                ""
+            };
+            println!("Profile {}{}:\n", name, active);
+            let domains = tagged(domains);
+            if domains.is_empty() {
+                println!("  No domains registered.\n");
+            }
+            for reg in &domains {
+                print_domain(reg, &cert_service);
+            }
+        }
+        return Ok(());
+    }
 
+    let domains = config.registrations();
     if domains.is_empty() {
         println!("No domains registered.");
         println!("\nRegister a domain with:");
@@ -19,32 +52,80 @@ pub fn execute(config_path: &Path, paths: &RoxyPaths) -> Result<()> {
         println!("  roxy register myapp.roxy --route \"/=3000\" --route \"/api=3001\"");
         return Ok(());
     }
+    let domains = tagged(domains);
+    if let Some(tag) = tag
+        && domains.is_empty()
+    {
+        println!("No domains tagged '{}'.", tag);
+        return Ok(());
+    }
 
-    println!("Registered domains:\n");
+    if profiles.len() > 1 {
+        println!(
+            "Registered domains (profile {}):\n",
+            config.active_profile()
+        );
+    } else {
+        println!("Registered domains:\n");
+    }
 
-    for reg in domains {
-        let has_cert = cert_service.exists(reg.pattern());
-        let https_status = if has_cert {
-            match cert_service.is_trusted() {
-                Ok(true) => "(HTTPS)",
-                Ok(false) => "(HTTPS untrusted)",
-                Err(_) => "(HTTPS error)",
-            }
-        } else {
-            ""
-        };
+    for reg in &domains {
+        print_domain(reg, &cert_service);
+    }
 
-        println!("  {} {}", reg.display_pattern(), https_status);
+    Ok(())
+}
 
-        for route in reg.routes() {
-            let target_str = match &route.target {
-                RouteTarget::Proxy(p) => p.to_string(),
-                RouteTarget::StaticFiles(p) => p.display().to_string(),
-            };
-            println!("    {:<15} -> {}", route.path, target_str);
+fn print_domain(reg: &DomainRegistration, cert_service: &CertificateService) {
+    let has_cert = reg.is_https_enabled()
+        && (reg.custom_cert().is_some() || cert_service.exists(reg.pattern()));
+    let https_status = if has_cert {
+        let trust = match cert_service.is_trusted() {
+            _ if reg.custom_cert().is_some() => "HTTPS custom cert",
+            Ok(true) => "HTTPS",
+            Ok(false) => "HTTPS untrusted",
+            Err(_) => "HTTPS error",
+        };
+        match cert_service.registration_expiry(reg) {
+            Ok(Some(expiry)) if expiry < OffsetDateTime::now_utc() => {
+                format!("({}, expired {})", trust, expiry.date())
+            }
+            Ok(Some(expiry)) => format!("({}, expires {})", trust, expiry.date()),
+            _ => format!("({})", trust),
         }
-        println!();
+    } else if !reg.is_https_enabled() {
+        "(HTTP only)".to_string()
+    } else {
+        String::new()
+    };
+
+    let owner = reg
+        .owner()
+        .map(|owner| format!(" [{}]", owner))
+        .unwrap_or_default();
+    let disabled = if reg.is_enabled() { "" } else { " (disabled)" };
+    let tags: String = reg.tags().iter().map(|tag| format!(" #{}", tag)).collect();
+    println!(
+        "  {} {}{}{}{}",
+        reg.display_pattern(),
+        https_status,
+        owner,
+        disabled,
+        tags
+    );
+    if let Some(description) = reg.description() {
+        println!("    {}", description);
     }
 
-    Ok(())
+    for route in reg.routes() {
+        let target_str = match &route.target {
+            RouteTarget::Proxy(p) => p.to_string(),
+            RouteTarget::LoadBalanced(_)
+            | RouteTarget::MergedStaticFiles(_)
+            | RouteTarget::Domain(_) => route.target.to_string(),
+            RouteTarget::StaticFiles(p) => p.display().to_string(),
+        };
+        println!("    {:<15} -> {}", route.path, target_str);
+    }
+    println!();
 }
@@ -0,0 +1,21 @@
+use anyhow::Result;
+
+use crate::domain::LogLevel;
+use crate::infrastructure::control::{ControlClient, ControlRequest};
+use crate::infrastructure::paths::RoxyPaths;
+
+/// Show or change the running daemon's log level
+pub fn execute(level: Option<String>, paths: &RoxyPaths) -> Result<()> {
+    let level = level.map(|l| l.parse::<LogLevel>()).transpose()?;
+
+    let client = ControlClient::new(paths.control_socket.clone());
+    let message = client.send(&ControlRequest::LogLevel(level))?;
+    println!("{}", message);
+
+    if level.is_some() {
+        println!("\nThis lasts until the daemon restarts. To keep it, run:");
+        println!("  sudo roxy config set daemon.log_level <level>");
+    }
+
+    Ok(())
+}
@@ -1,18 +1,35 @@
 use std::fs::{self, File};
-use std::io::{BufRead, BufReader, Seek, SeekFrom};
-use std::thread;
-use std::time::Duration;
+use std::io;
 
 use anyhow::{Context, Result};
 
+use crate::domain::DomainPattern;
+use crate::infrastructure::log_follow;
 use crate::infrastructure::paths::RoxyPaths;
 
-pub fn execute(lines: usize, clear: bool, follow: bool, paths: &RoxyPaths) -> Result<()> {
-    let log_path = &paths.log_file;
+/// Show the daemon's log, or with `backend` the output of the backends
+/// it started for that domain.
+pub fn execute(
+    lines: usize,
+    clear: bool,
+    follow: bool,
+    backend: Option<String>,
+    wildcard: bool,
+    paths: &RoxyPaths,
+) -> Result<()> {
+    let backend = backend
+        .map(|domain| DomainPattern::from_name(&domain, wildcard))
+        .transpose()?;
+    let log_path = &match &backend {
+        Some(pattern) => paths.backend_log(pattern),
+        None => paths.log_file.clone(),
+    };
 
     if clear {
+        // Truncate rather than delete: the daemon keeps the file open and
+        // would otherwise go on writing to the unlinked file
         if log_path.exists() {
-            fs::remove_file(log_path).context("Failed to clear log file")?;
+            File::create(log_path).context("Failed to clear log file")?;
         }
         println!("Logs cleared.");
         return Ok(());
@@ -21,7 +38,11 @@ pub fn execute(lines: usize, clear: bool, follow: bool, paths: &RoxyPaths) -> Re
     if !log_path.exists() {
         println!("No logs found.");
         println!("Log file: {}", log_path.display());
-        println!("\nStart the daemon to generate logs: sudo roxy start");
+        if backend.is_some() {
+            println!("\nThe daemon logs a backend's output once a route's cmd starts it.");
+        } else {
+            println!("\nStart the daemon to generate logs: sudo roxy start");
+        }
         return Ok(());
     }
 
@@ -33,7 +54,7 @@ pub fn execute(lines: usize, clear: bool, follow: bool, paths: &RoxyPaths) -> Re
 
     // Follow mode: keep watching for new lines
     if follow {
-        tail_follow(log_path)?;
+        log_follow::follow(log_path, &mut io::stdout())?;
     }
 
     Ok(())
@@ -51,29 +72,3 @@ fn tail_lines(path: &std::path::Path, n: usize) -> Result<String> {
         Ok(result + "\n")
     }
 }
-
-/// Follow log file for new content (like tail -f)
-fn tail_follow(path: &std::path::Path) -> Result<()> {
-    let file = File::open(path).context("Failed to open log file")?;
-    let mut reader = BufReader::new(file);
-
-    // Seek to end of file
-    reader.seek(SeekFrom::End(0))?;
-
-    loop {
-        let mut line = String::new();
-        match reader.read_line(&mut line) {
-            Ok(0) => {
-                // No new data, sleep and try again
-                thread::sleep(Duration::from_millis(100));
-            }
-            Ok(_) => {
-                // New line available
-                print!("{}", line);
-            }
-            Err(e) => {
-                return Err(e).context("Error reading log file");
-            }
-        }
-    }
-}
@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::application::set_maintenance::SetMaintenance;
+use crate::domain::DomainPattern;
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+/// Show a maintenance page instead of the domain's routes
+pub fn on(
+    domain: String,
+    wildcard: bool,
+    message: Option<String>,
+    config_path: &Path,
+) -> Result<()> {
+    set(domain, wildcard, true, message, config_path)
+}
+
+/// Serve the domain's routes again
+pub fn off(domain: String, wildcard: bool, config_path: &Path) -> Result<()> {
+    set(domain, wildcard, false, None, config_path)
+}
+
+fn set(
+    domain: String,
+    wildcard: bool,
+    on: bool,
+    message: Option<String>,
+    config_path: &Path,
+) -> Result<()> {
+    let pattern = DomainPattern::from_name(&domain, wildcard)?;
+
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let caller = Caller::current();
+    let use_case = SetMaintenance::new(&config_store, &caller);
+
+    use_case.execute(&pattern, on, message)?;
+
+    if on {
+        println!("Maintenance mode on for {}", pattern);
+    } else {
+        println!("Maintenance mode off for {}", pattern);
+    }
+    println!("\nReload the daemon to apply changes: roxy reload");
+
+    Ok(())
+}
@@ -1,12 +1,29 @@
+pub mod auth;
+pub mod ca;
+pub mod cache;
+pub mod config;
+pub mod enable;
+pub mod exec;
+pub mod export;
+pub mod https;
+pub mod import;
 pub mod install;
+pub mod link;
 pub mod list;
+pub mod log_level;
 pub mod logs;
+pub mod maintenance;
+pub mod passthrough;
+pub mod profile;
+pub mod ps;
 pub mod register;
 pub mod reload;
 pub mod restart;
 pub mod route;
+pub mod service;
 pub mod start;
 pub mod status;
 pub mod stop;
+pub mod tcp;
 pub mod uninstall;
 pub mod unregister;
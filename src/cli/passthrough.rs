@@ -0,0 +1,37 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::application::set_tls_passthrough::SetTlsPassthrough;
+use crate::domain::DomainPattern;
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+/// Send HTTPS connections to the backend still encrypted
+pub fn on(domain: String, wildcard: bool, config_path: &Path) -> Result<()> {
+    set(domain, wildcard, true, config_path)
+}
+
+/// Terminate TLS in Roxy again
+pub fn off(domain: String, wildcard: bool, config_path: &Path) -> Result<()> {
+    set(domain, wildcard, false, config_path)
+}
+
+fn set(domain: String, wildcard: bool, on: bool, config_path: &Path) -> Result<()> {
+    let pattern = DomainPattern::from_name(&domain, wildcard)?;
+
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let caller = Caller::current();
+    let use_case = SetTlsPassthrough::new(&config_store, &caller);
+
+    use_case.execute(&pattern, on)?;
+
+    if on {
+        println!("TLS passthrough on for {}", pattern);
+    } else {
+        println!("TLS passthrough off for {}", pattern);
+    }
+    println!("\nReload the daemon to apply changes: roxy reload");
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::application::StepOutcome;
+use crate::application::delete_profile::DeleteProfile;
+use crate::application::switch_profile::SwitchProfile;
+use crate::infrastructure::certs::CertificateService;
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::paths::RoxyPaths;
+use crate::infrastructure::user::Caller;
+
+/// Add an empty profile
+pub fn create(name: String, config_path: &Path) -> Result<()> {
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    config_store.create_profile(&name)?;
+
+    println!("Created profile '{}'", name);
+    println!("\nSwitch to it with: roxy profile use {}", name);
+
+    Ok(())
+}
+
+/// Register the profile's domains in place of the active profile's
+pub fn switch(name: String, config_path: &Path, paths: &RoxyPaths) -> Result<()> {
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let cert_service = CertificateService::new(paths).with_settings(&config_store.load()?.certs);
+    let caller = Caller::current();
+    let use_case = SwitchProfile::new(&config_store, &cert_service, &caller);
+
+    for outcome in use_case.execute(&name)? {
+        match outcome {
+            StepOutcome::Success(msg) => println!("{}", msg),
+            StepOutcome::Warning(msg) => eprintln!("Warning: {}", msg),
+            StepOutcome::Skipped(msg) => println!("{}", msg),
+        }
+    }
+    println!("\nReload the daemon to apply changes: roxy reload");
+
+    Ok(())
+}
+
+/// List profiles, marking the active one
+pub fn list(config_path: &Path) -> Result<()> {
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let config = config_store.load()?;
+
+    for (name, registrations) in config.profiles() {
+        let marker = if name == config.active_profile() {
+            "*"
+        } else {
+            " "
+        };
+        let count = registrations.len();
+        println!(
+            "{} {} ({} domain{})",
+            marker,
+            name,
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+    }
+
+    Ok(())
+}
+
+/// Remove a profile that isn't in use, with its domains
+pub fn delete(name: String, config_path: &Path) -> Result<()> {
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let caller = Caller::current();
+    DeleteProfile::new(&config_store, &caller).execute(&name)?;
+
+    println!("Deleted profile '{}'", name);
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::domain::DomainPattern;
+use crate::infrastructure::control::{ControlClient, ControlRequest, ProcessStatus};
+use crate::infrastructure::paths::RoxyPaths;
+
+/// List the backends the daemon started for routes with a `cmd`
+pub fn execute(paths: &RoxyPaths) -> Result<()> {
+    let client = ControlClient::new(paths.control_socket.clone());
+    let message = client.send(&ControlRequest::Processes)?;
+    let processes = message
+        .lines()
+        .map(str::parse)
+        .collect::<Result<Vec<ProcessStatus>, _>>()?;
+
+    if processes.is_empty() {
+        println!("No backends started.");
+        println!("\nRoutes with cmd= start their backend on the first request.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<24} {:<21} {:<10} {:>7} {:>10} {:>8}  COMMAND",
+        "DOMAIN", "TARGET", "STATE", "PID", "UPTIME", "RESTARTS"
+    );
+    for process in processes {
+        let pid = process
+            .pid
+            .map_or_else(|| "-".to_string(), |pid| pid.to_string());
+        let uptime = match process.pid {
+            Some(_) => humantime::format_duration(Duration::from_secs(process.uptime_secs))
+                .to_string()
+                .split(' ')
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:<24} {:<21} {:<10} {:>7} {:>10} {:>8}  {}",
+            process.domain,
+            process.target,
+            process.state,
+            pid,
+            uptime,
+            process.restarts,
+            process.cmd
+        );
+    }
+
+    Ok(())
+}
+
+/// Restart the backends the daemon started for a domain
+pub fn restart(domain: String, wildcard: bool, paths: &RoxyPaths) -> Result<()> {
+    let pattern = DomainPattern::from_name(&domain, wildcard)?;
+
+    let client = ControlClient::new(paths.control_socket.clone());
+    let message = client.send(&ControlRequest::RestartBackend(pattern.to_string()))?;
+    println!("{}", message);
+
+    Ok(())
+}
@@ -1,18 +1,28 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
 use crate::application::StepOutcome;
 use crate::application::register_domain::RegisterDomain;
-use crate::domain::{DomainPattern, Route};
+use crate::domain::{CustomCert, DomainPattern, Route};
 use crate::infrastructure::certs::CertificateService;
 use crate::infrastructure::config::ConfigStore;
 use crate::infrastructure::paths::RoxyPaths;
+use crate::infrastructure::user::Caller;
+
+/// The optional flags of `roxy register`.
+pub struct Options {
+    pub custom_cert: Option<(PathBuf, PathBuf)>,
+    pub no_https: bool,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
 
 pub fn execute(
     domain: String,
     wildcard: bool,
     routes: Vec<String>,
+    options: Options,
     config_path: &Path,
     paths: &RoxyPaths,
 ) -> Result<()> {
@@ -25,13 +35,31 @@ pub fn execute(
         .map_err(|e| anyhow::anyhow!("Invalid route: {}", e))?;
 
     let config_store = ConfigStore::new(config_path.to_path_buf());
-    let cert_service = CertificateService::new(paths);
-    let use_case = RegisterDomain::new(&config_store, &cert_service);
+    let cert_service = CertificateService::new(paths).with_settings(&config_store.load()?.certs);
+    let caller = Caller::current();
+    let mut use_case =
+        RegisterDomain::new(&config_store, &cert_service, &caller).with_tags(options.tags);
+    if let Some(description) = options.description {
+        use_case = use_case.with_description(description);
+    }
 
-    println!(
-        "Generating SSL certificate for {}...",
-        pattern.display_pattern()
-    );
+    // The daemon runs elsewhere, so store absolute paths
+    if let Some((cert, key)) = options.custom_cert {
+        let absolute = |path: PathBuf| {
+            std::path::absolute(&path).with_context(|| format!("Invalid path: {}", path.display()))
+        };
+        use_case = use_case.with_custom_cert(CustomCert {
+            cert: absolute(cert)?,
+            key: absolute(key)?,
+        });
+    } else if options.no_https {
+        use_case = use_case.without_https();
+    } else {
+        println!(
+            "Generating SSL certificate for {}...",
+            pattern.display_pattern()
+        );
+    }
 
     let result = use_case.execute(pattern, parsed_routes)?;
 
@@ -40,14 +68,22 @@ pub fn execute(
         StepOutcome::Warning(msg) => {
             eprintln!("  {}", msg);
             eprintln!(
-                "  Run 'sudo roxy register {}{}' to enable HTTPS.",
-                result.registration.domain(),
-                if wildcard { " --wildcard" } else { "" }
+                "  Run 'sudo roxy https on {}{}' to enable HTTPS.",
+                if wildcard { "--wildcard " } else { "" },
+                result.registration.domain()
             );
         }
         StepOutcome::Skipped(msg) => println!("  {}", msg),
     }
 
+    for outcome in [&result.hosts_outcome, &result.dns_cache_outcome] {
+        match outcome {
+            Some(StepOutcome::Success(msg)) => println!("  {}", msg),
+            Some(StepOutcome::Warning(msg)) => eprintln!("  {}", msg),
+            _ => {}
+        }
+    }
+
     println!(
         "\nRegistered domain: {}",
         result.registration.display_pattern()
@@ -64,6 +100,9 @@ pub fn execute(
             "disabled"
         }
     );
+    if !result.registration.tags().is_empty() {
+        println!("  Tags: {}", result.registration.tags().join(", "));
+    }
     println!("\nStart the proxy with: roxy start");
 
     Ok(())
@@ -3,6 +3,7 @@ use std::path::Path;
 use anyhow::{Result, bail};
 
 use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::control::{ControlClient, ControlError, ControlRequest};
 use crate::infrastructure::paths::RoxyPaths;
 use crate::infrastructure::pid::PidFile;
 
@@ -15,6 +16,20 @@ pub fn execute(verbose: bool, config_path: &Path, paths: &RoxyPaths) -> Result<(
 
     println!("Reloading Roxy daemon...");
 
+    // Apply the config in place so open connections and the DNS server
+    // keep running
+    let client = ControlClient::new(paths.control_socket.clone());
+    match client.send(&ControlRequest::Reload) {
+        Ok(summary) => {
+            println!("{}", summary);
+            println!("Daemon reloaded with updated configuration.");
+            return Ok(());
+        }
+        // A daemon from an older version has no control channel
+        Err(ControlError::NotRunning) => {}
+        Err(e) => return Err(e.into()),
+    }
+
     // Stop the daemon
     super::stop::execute(paths)?;
 
@@ -1,6 +1,6 @@
 use std::path::Path;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
 use super::{start, stop};
 use crate::infrastructure::config::ConfigStore;
@@ -8,6 +8,16 @@ use crate::infrastructure::paths::RoxyPaths;
 use crate::infrastructure::pid::PidFile;
 
 pub fn execute(verbose: bool, config_path: &Path, paths: &RoxyPaths) -> Result<()> {
+    if let Some(service) = super::service::installed() {
+        ConfigStore::new(config_path.to_path_buf())
+            .load()?
+            .validate()
+            .context("Configuration validation failed")?;
+        service.restart()?;
+        println!("Roxy daemon restarted by {}", service.name());
+        return Ok(());
+    }
+
     let pid_file = PidFile::new(paths.pid_file.clone());
 
     if pid_file.is_running()? {
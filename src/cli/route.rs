@@ -1,10 +1,46 @@
 use std::path::Path;
 
 use anyhow::Result;
+use clap::Args;
 
 use crate::application::manage_routes::ManageRoutes;
-use crate::domain::{DomainPattern, PathPrefix, RouteTarget};
+use crate::domain::{
+    DomainPattern, Fault, Latency, PathPrefix, ProxyTarget, Route, RouteTarget, TrafficSplit,
+};
 use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+/// Route options that can be set when adding a route
+#[derive(Args)]
+pub struct RouteFlags {
+    /// Weigh several targets ("3000,3001"), e.g. "90/10" to try a canary
+    #[arg(long)]
+    split: Option<String>,
+
+    /// Keep each browser on the target it first got
+    #[arg(long)]
+    sticky: bool,
+
+    /// Send a copy of every request to this target too, e.g. "4000"
+    #[arg(long)]
+    mirror: Option<String>,
+
+    /// Delay every request, e.g. "300ms" or "300ms±100ms" for jitter
+    #[arg(long)]
+    latency: Option<String>,
+
+    /// Fail a share of requests, e.g. "5% 503"
+    #[arg(long)]
+    fault: Option<String>,
+
+    /// Start the backend with this command when it isn't running, e.g. "npm run dev"
+    #[arg(long)]
+    cmd: Option<String>,
+
+    /// Directory to run --cmd in, e.g. "~/code/app"
+    #[arg(long, requires = "cmd")]
+    cwd: Option<String>,
+}
 
 /// Add a route to an existing domain
 pub fn add(
@@ -12,19 +48,53 @@ pub fn add(
     wildcard: bool,
     path: String,
     target: String,
+    flags: RouteFlags,
     config_path: &Path,
 ) -> Result<()> {
     let pattern = DomainPattern::from_name(&domain, wildcard)?;
     let path_prefix = PathPrefix::new(&path)?;
     let route_target = RouteTarget::parse(&target)
         .map_err(|e| anyhow::anyhow!("Invalid target '{}': {}", target, e))?;
+    let mut route = Route::new(path_prefix, route_target);
+    route.options.split = flags
+        .split
+        .as_deref()
+        .map(TrafficSplit::parse)
+        .transpose()?;
+    route.options.sticky = flags.sticky;
+    route.options.mirror = flags
+        .mirror
+        .as_deref()
+        .map(ProxyTarget::parse)
+        .transpose()?;
+    route.options.latency = flags.latency.as_deref().map(Latency::parse).transpose()?;
+    route.options.fault = flags.fault.as_deref().map(Fault::parse).transpose()?;
+    route.options.cmd = flags.cmd;
+    route.options.cwd = flags.cwd;
+    route.check_split()?;
 
     let config_store = ConfigStore::new(config_path.to_path_buf());
-    let use_case = ManageRoutes::new(&config_store);
+    let caller = Caller::current();
+    let use_case = ManageRoutes::new(&config_store, &caller);
 
-    let route = use_case.add_route(&pattern, path_prefix, route_target)?;
+    use_case.add_route(&pattern, route.clone())?;
 
     println!("Added route: {} -> {}", route.path, route.target);
+    if let Some(split) = &route.options.split {
+        println!("Splitting traffic {}", split);
+    }
+    if let Some(mirror) = &route.options.mirror {
+        println!("Mirroring requests to {}", mirror);
+    }
+    if let Some(latency) = route.options.latency {
+        println!("Delaying requests by {}", latency);
+    }
+    if let Some(fault) = route.options.fault {
+        println!("Failing {} of requests", fault);
+    }
+    if let Some(cmd) = &route.options.cmd {
+        println!("Starting the backend on demand with: {}", cmd);
+    }
     println!("\nReload the daemon to apply changes: roxy reload");
 
     Ok(())
@@ -36,7 +106,8 @@ pub fn remove(domain: String, wildcard: bool, path: String, config_path: &Path)
     let path_prefix = PathPrefix::new(&path)?;
 
     let config_store = ConfigStore::new(config_path.to_path_buf());
-    let use_case = ManageRoutes::new(&config_store);
+    let caller = Caller::current();
+    let use_case = ManageRoutes::new(&config_store, &caller);
 
     use_case.remove_route(&pattern, &path_prefix)?;
 
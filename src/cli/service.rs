@@ -0,0 +1,79 @@
+use std::env;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::infrastructure::paths::RoxyPaths;
+use crate::infrastructure::pid::PidFile;
+use crate::infrastructure::service::{ServiceManager, get_service_manager};
+
+/// The system service, if one has been installed with
+/// `roxy service install`. `start`, `stop` and `restart` go through it
+/// so the service manager stays in charge of the daemon.
+pub fn installed() -> Option<Box<dyn ServiceManager>> {
+    get_service_manager()
+        .ok()
+        .filter(|service| service.is_installed())
+}
+
+pub fn install(config_path: &Path, paths: &RoxyPaths) -> Result<()> {
+    let service = get_service_manager()?;
+    let exe = env::current_exe()?
+        .canonicalize()
+        .context("Failed to locate the roxy binary")?;
+    let config_path = std::path::absolute(config_path)?;
+
+    // The service starts its own daemon, which needs the ports
+    let pid_file = PidFile::new(paths.pid_file.clone());
+    if pid_file.get_running_pid()?.is_some() {
+        println!("Stopping the running daemon...");
+        pid_file.stop_gracefully(Duration::from_millis(500))?;
+    }
+
+    service.install(&exe, &config_path)?;
+    println!(
+        "Installed {} service: {}",
+        service.name(),
+        service.definition_path().display()
+    );
+    println!("The daemon now starts at boot and restarts if it crashes.");
+    println!("'roxy start', 'roxy stop' and 'roxy restart' go through the service.");
+    Ok(())
+}
+
+pub fn uninstall() -> Result<()> {
+    let service = get_service_manager()?;
+    if !service.is_installed() {
+        println!("No {} service installed.", service.name());
+        return Ok(());
+    }
+
+    service.uninstall()?;
+    println!(
+        "Removed {} service: {}",
+        service.name(),
+        service.definition_path().display()
+    );
+    println!("Start the daemon directly with: roxy start");
+    Ok(())
+}
+
+pub fn status() -> Result<()> {
+    let service = get_service_manager()?;
+    let path = service.definition_path();
+
+    println!("Service manager: {}", service.name());
+    if !service.is_installed() {
+        println!("  Installed: no ({})", path.display());
+        println!("\nInstall it with: sudo roxy service install");
+        return Ok(());
+    }
+
+    println!("  Installed: yes ({})", path.display());
+    println!(
+        "  Running:   {}",
+        if service.is_running() { "yes" } else { "no" }
+    );
+    Ok(())
+}
@@ -34,6 +34,11 @@ pub fn execute(
     if foreground {
         // Run in foreground (blocking)
         crate::daemon::lifecycle::run(verbose, config_path, paths)
+    } else if let Some(service) = super::service::installed() {
+        service.start()?;
+        println!("Roxy daemon started by {}", service.name());
+        println!("\nUse 'roxy status' to check status");
+        Ok(())
     } else {
         // Fork to background
         let exe = env::current_exe()?;
@@ -49,6 +54,16 @@ pub fn execute(
             cmd.env("ROXY_LOG", "debug");
         }
 
+        // Without a console of its own the daemon would stop when the
+        // one it was started from is closed
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            const DETACHED_PROCESS: u32 = 0x0000_0008;
+            const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+            cmd.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+        }
+
         let child = cmd
             .stdin(Stdio::null())
             .stdout(Stdio::null())
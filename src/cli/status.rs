@@ -1,17 +1,65 @@
+use std::io::{self, Write};
 use std::path::Path;
+use std::thread;
+use std::time::Duration;
 
 use anyhow::Result;
+use time::OffsetDateTime;
 
+use crate::domain::DomainRegistration;
 use crate::infrastructure::certs::CertificateService;
-use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::certs::ca::RootCA;
+use crate::infrastructure::config::{ConfigStore, DaemonConfig};
+use crate::infrastructure::control::{BackendStatus, ControlClient, ControlRequest, RequestCounts};
+use crate::infrastructure::dns::hosts::{HostsFile, hostnames};
+use crate::infrastructure::dns::{DnsBackend, DnsService, DnsmasqDnsService, get_dns_service};
+use crate::infrastructure::health::{self, HealthStatus, PROBE_HOSTNAME};
 use crate::infrastructure::network::get_lan_ip;
 use crate::infrastructure::paths::RoxyPaths;
 use crate::infrastructure::pid::PidFile;
+use crate::infrastructure::port_forward::get_port_forwarder;
 
-pub fn execute(config_path: &Path, paths: &RoxyPaths) -> Result<()> {
+/// Certificates expiring sooner than this are flagged.
+const EXPIRY_WARNING_DAYS: i64 = 30;
+
+pub fn execute(watch: Option<Duration>, config_path: &Path, paths: &RoxyPaths) -> Result<()> {
+    let Some(interval) = watch else {
+        return write_status(&mut io::stdout(), config_path, paths, false);
+    };
+
+    loop {
+        // Render off-screen first so slow checks don't leave a blank terminal
+        let mut frame = Vec::new();
+        writeln!(
+            frame,
+            "Every {}: roxy status (Ctrl+C to quit)\n",
+            humantime::format_duration(interval)
+        )?;
+        write_status(&mut frame, config_path, paths, true)?;
+
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "\x1b[2J\x1b[H")?;
+        stdout.write_all(&frame)?;
+        stdout.flush()?;
+        drop(stdout);
+
+        thread::sleep(interval);
+    }
+}
+
+/// Write the status report. `detailed` adds request counters and
+/// backend reachability for each domain.
+fn write_status(
+    out: &mut impl Write,
+    config_path: &Path,
+    paths: &RoxyPaths,
+    detailed: bool,
+) -> Result<()> {
     let pid_file = PidFile::new(paths.pid_file.clone());
     let config_store = ConfigStore::new(config_path.to_path_buf());
     let cert_service = CertificateService::new(paths);
+    let config = config_store.load()?;
+    let domains = config.registrations();
 
     // Get LAN IP
     let lan_ip = get_lan_ip();
@@ -25,52 +73,277 @@ pub fn execute(config_path: &Path, paths: &RoxyPaths) -> Result<()> {
     let ca_installed = cert_service.is_ca_installed().unwrap_or(false);
 
     // Check daemon status
-    match pid_file.get_running_pid()? {
+    let running = match pid_file.get_running_pid()? {
         Some(pid) => {
-            println!("Roxy daemon: running (PID: {})", pid);
-            println!("  LAN IP: {}{}", lan_ip, offline_note);
-            println!(
+            writeln!(out, "Roxy daemon: running (PID: {})", pid)?;
+            write_service(out)?;
+            writeln!(out, "  LAN IP: {}{}", lan_ip, offline_note)?;
+            writeln!(
+                out,
                 "  Root CA: {}",
                 if ca_installed {
                     "installed"
                 } else {
                     "not installed"
                 }
-            );
-            println!("  HTTP:  http://localhost:80");
-            println!("  HTTPS: https://localhost:443");
+            )?;
+            writeln!(out, "  HTTP:  http://localhost:{}", config.daemon.http_port)?;
+            writeln!(
+                out,
+                "  HTTPS: https://localhost:{}",
+                config.daemon.https_port
+            )?;
             if !lan_ip.is_loopback() {
-                println!("\n  Access from other devices: use http://{}", lan_ip);
+                writeln!(out, "\n  Access from other devices: use http://{}", lan_ip)?;
             }
+
+            write_health(out, &config.daemon, &domains, paths)?;
+            true
         }
         None => {
-            println!("Roxy daemon: stopped");
-            println!("  LAN IP: {}{}", lan_ip, offline_note);
-            println!(
+            writeln!(out, "Roxy daemon: stopped")?;
+            write_service(out)?;
+            writeln!(out, "  LAN IP: {}{}", lan_ip, offline_note)?;
+            writeln!(
+                out,
                 "  Root CA: {}",
                 if ca_installed {
                     "installed"
                 } else {
                     "not installed"
                 }
-            );
-            println!("\nStart with: sudo roxy start");
+            )?;
+            writeln!(out, "\nStart with: sudo roxy start")?;
+            false
         }
-    }
+    };
 
     // Show registered domains
-    let domains = config_store.list_domains()?;
-    if !domains.is_empty() {
-        println!("\nRegistered domains: {}", domains.len());
-        for reg in domains {
-            let scheme = if reg.is_https_enabled() {
-                "https"
+    if domains.is_empty() {
+        return Ok(());
+    }
+    if detailed {
+        let (counts, backends) = if running {
+            (request_counts(paths), backend_health(paths))
+        } else {
+            (None, None)
+        };
+        return write_domain_details(
+            out,
+            &domains,
+            &cert_service,
+            counts.as_deref(),
+            backends.as_deref(),
+        );
+    }
+
+    writeln!(out, "\nRegistered domains: {}", domains.len())?;
+    for reg in domains {
+        let scheme = if reg.is_https_enabled() {
+            "https"
+        } else {
+            "http"
+        };
+        writeln!(
+            out,
+            "  {}://{}{}",
+            scheme,
+            reg.display_pattern(),
+            expiry_note(&cert_service, &reg)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// A warning for a certificate that expired or expires within
+/// `EXPIRY_WARNING_DAYS`, empty otherwise.
+fn expiry_note(cert_service: &CertificateService, reg: &DomainRegistration) -> String {
+    if !reg.is_https_enabled() {
+        return String::new();
+    }
+    let Ok(Some(expiry)) = cert_service.registration_expiry(reg) else {
+        return String::new();
+    };
+    let days_left = (expiry - OffsetDateTime::now_utc()).whole_days();
+    if expiry < OffsetDateTime::now_utc() {
+        format!(" (certificate expired {})", expiry.date())
+    } else if days_left < EXPIRY_WARNING_DAYS {
+        format!(" (certificate expires in {} days)", days_left)
+    } else {
+        String::new()
+    }
+}
+
+/// Note the system service when the daemon is managed by one.
+fn write_service(out: &mut impl Write) -> Result<()> {
+    if let Some(service) = super::service::installed() {
+        writeln!(
+            out,
+            "  Service: {} ({})",
+            service.name(),
+            if service.is_running() {
+                "active"
             } else {
-                "http"
-            };
-            println!("  {}://{}", scheme, reg.display_pattern());
+                "inactive"
+            }
+        )?;
+    }
+    Ok(())
+}
+
+fn write_health(
+    out: &mut impl Write,
+    daemon: &DaemonConfig,
+    domains: &[DomainRegistration],
+    paths: &RoxyPaths,
+) -> Result<()> {
+    // Prefer a real HTTPS domain so its own certificate gets checked
+    let hostname = domains
+        .iter()
+        .find(|d| d.is_https_enabled() && !d.is_wildcard())
+        .map(|d| d.domain().to_string())
+        .unwrap_or_else(|| PROBE_HOSTNAME.to_string());
+    let ca_cert = RootCA::new(paths.data_dir.clone()).cert_path();
+
+    let (dns_check, resolver_check) = match daemon.dns_backend {
+        DnsBackend::Resolver => (
+            ("DNS server", health::check_dns(daemon.dns_port)),
+            (
+                "Resolver",
+                health::check_resolver(get_dns_service(), daemon.dns_port),
+            ),
+        ),
+        DnsBackend::Hosts => (
+            (
+                "DNS server",
+                HealthStatus::Skipped("dns_backend is hosts".into()),
+            ),
+            (
+                "Hosts file",
+                health::check_hosts_file(&HostsFile::system(), &hostnames(domains)),
+            ),
+        ),
+        // dnsmasq answers on the standard port
+        DnsBackend::Dnsmasq => (
+            ("dnsmasq", health::check_dns(53)),
+            (
+                "Resolver",
+                health::check_resolver(
+                    DnsmasqDnsService::detect().map(|d| Box::new(d) as Box<dyn DnsService>),
+                    53,
+                ),
+            ),
+        ),
+    };
+
+    let mut checks = vec![
+        dns_check,
+        resolver_check,
+        (
+            "TLS",
+            health::check_tls(daemon.https_port, &hostname, &ca_cert),
+        ),
+    ];
+    if daemon.rootless {
+        checks.push((
+            "Forwarding",
+            health::check_port_forwarding(get_port_forwarder()),
+        ));
+    }
+
+    writeln!(out, "\nHealth:")?;
+    for (name, status) in checks {
+        match status {
+            HealthStatus::Ok(detail) => writeln!(out, "  {:<11} ok - {}", name, detail)?,
+            HealthStatus::Broken { reason, hint } => {
+                writeln!(out, "  {:<11} broken - {}", name, reason)?;
+                writeln!(out, "  {:<11} {}", "", hint)?;
+            }
+            HealthStatus::Skipped(why) => writeln!(out, "  {:<11} skipped - {}", name, why)?,
         }
     }
+    Ok(())
+}
+
+/// Ask the daemon for its request counters. `None` if it can't be reached.
+fn request_counts(paths: &RoxyPaths) -> Option<Vec<RequestCounts>> {
+    let message = ControlClient::new(paths.control_socket.clone())
+        .send(&ControlRequest::Stats)
+        .ok()?;
+    message.lines().map(|line| line.parse().ok()).collect()
+}
+
+/// Ask the daemon for its backend health checks. `None` if it can't be
+/// reached.
+fn backend_health(paths: &RoxyPaths) -> Option<Vec<BackendStatus>> {
+    let message = ControlClient::new(paths.control_socket.clone())
+        .send(&ControlRequest::Health)
+        .ok()?;
+    message.lines().map(|line| line.parse().ok()).collect()
+}
 
+fn write_domain_details(
+    out: &mut impl Write,
+    domains: &[DomainRegistration],
+    cert_service: &CertificateService,
+    counts: Option<&[RequestCounts]>,
+    backends: Option<&[BackendStatus]>,
+) -> Result<()> {
+    let mut domains: Vec<_> = domains.iter().collect();
+    domains.sort_by_key(|d| d.display_pattern());
+
+    writeln!(
+        out,
+        "\n{:<42} {:>9} {:>7}",
+        format!("Registered domains: {}", domains.len()),
+        "requests",
+        "errors"
+    )?;
+    for reg in domains {
+        let scheme = if reg.is_https_enabled() {
+            "https"
+        } else {
+            "http"
+        };
+        let pattern = reg.display_pattern();
+        let (requests, errors) = match counts {
+            Some(counts) => counts
+                .iter()
+                .find(|c| c.domain == pattern)
+                .map_or(("0".into(), "0".into()), |c| {
+                    (c.requests.to_string(), c.errors.to_string())
+                }),
+            None => ("-".into(), "-".into()),
+        };
+        writeln!(
+            out,
+            "  {:<40} {:>9} {:>7}{}",
+            format!("{}://{}", scheme, pattern),
+            requests,
+            errors,
+            expiry_note(cert_service, reg)
+        )?;
+
+        for route in reg.routes() {
+            for target in route.target.proxy_targets() {
+                // Prefer the daemon's history; probe backends it doesn't check
+                let checked = backends
+                    .unwrap_or_default()
+                    .iter()
+                    .find(|status| status.target == target.to_string());
+                let state = match checked {
+                    Some(status) if status.up => "up".to_string(),
+                    Some(status) => format!(
+                        "down for {}",
+                        humantime::format_duration(Duration::from_secs(status.for_secs))
+                    ),
+                    None if health::is_backend_up(target) => "up".to_string(),
+                    None => "down".to_string(),
+                };
+                writeln!(out, "    {} -> {} ({})", route.path, target, state)?;
+            }
+        }
+    }
     Ok(())
 }
@@ -12,7 +12,10 @@ pub fn execute(paths: &RoxyPaths) -> Result<()> {
         bail!("Roxy daemon is not running.");
     }
 
-    pid_file.stop_gracefully(Duration::from_millis(500))?;
+    match super::service::installed() {
+        Some(service) => service.stop()?,
+        None => pid_file.stop_gracefully(Duration::from_millis(500))?,
+    }
     println!("Roxy daemon stopped.");
 
     Ok(())
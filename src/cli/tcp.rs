@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::application::manage_tcp_forwards::ManageTcpForwards;
+use crate::domain::{DomainPattern, TcpForward};
+use crate::infrastructure::config::ConfigStore;
+use crate::infrastructure::user::Caller;
+
+/// Forward a TCP port for a domain
+pub fn add(domain: String, wildcard: bool, forward: String, config_path: &Path) -> Result<()> {
+    let pattern = DomainPattern::from_name(&domain, wildcard)?;
+    let forward = TcpForward::parse(&forward)?;
+
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let caller = Caller::current();
+    let use_case = ManageTcpForwards::new(&config_store, &caller);
+
+    use_case.add(&pattern, forward.clone())?;
+
+    println!(
+        "Added TCP forward: {}:{} -> {}",
+        pattern.base_domain(),
+        forward.port(),
+        forward.target()
+    );
+    println!("\nRestart the daemon to listen on new ports: roxy restart");
+
+    Ok(())
+}
+
+/// Stop forwarding a TCP port for a domain
+pub fn remove(domain: String, wildcard: bool, port: u16, config_path: &Path) -> Result<()> {
+    let pattern = DomainPattern::from_name(&domain, wildcard)?;
+
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let caller = Caller::current();
+    let use_case = ManageTcpForwards::new(&config_store, &caller);
+
+    use_case.remove(&pattern, port)?;
+
+    println!("Removed TCP forward for port {}", port);
+    println!("\nReload the daemon to apply changes: roxy reload");
+
+    Ok(())
+}
+
+/// List TCP forwards for a domain
+pub fn list(domain: String, wildcard: bool, config_path: &Path) -> Result<()> {
+    let pattern = DomainPattern::from_name(&domain, wildcard)?;
+
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+
+    let registration = config_store
+        .get_domain(&pattern)?
+        .ok_or_else(|| anyhow::anyhow!("Domain '{}' not registered", pattern))?;
+
+    if registration.tcp_forwards().is_empty() {
+        println!(
+            "No TCP forwards configured for {}",
+            registration.display_pattern()
+        );
+        return Ok(());
+    }
+
+    println!("TCP forwards for {}:\n", registration.display_pattern());
+    println!("{:<20} {:<30}", "PORT", "TARGET");
+    println!("{}", "-".repeat(52));
+
+    for forward in registration.tcp_forwards() {
+        println!("{:<20} {:<30}", forward.port(), forward.target());
+    }
+
+    Ok(())
+}
@@ -8,6 +8,7 @@ use crate::domain::DomainPattern;
 use crate::infrastructure::certs::CertificateService;
 use crate::infrastructure::config::ConfigStore;
 use crate::infrastructure::paths::RoxyPaths;
+use crate::infrastructure::user::Caller;
 
 pub fn execute(
     domain: String,
@@ -20,7 +21,8 @@ pub fn execute(
 
     let config_store = ConfigStore::new(config_path.to_path_buf());
     let cert_service = CertificateService::new(paths);
-    let use_case = UnregisterDomain::new(&config_store, &cert_service);
+    let caller = Caller::current();
+    let use_case = UnregisterDomain::new(&config_store, &cert_service, &caller);
 
     if !force {
         let registration = use_case.preview(&pattern)?;
@@ -30,7 +32,7 @@ pub fn execute(
         for route in registration.routes() {
             println!("    {} -> {}", route.path, route.target);
         }
-        if registration.is_https_enabled() {
+        if registration.has_roxy_cert() {
             println!("  HTTPS certificate files will be removed");
         }
         println!("\nRun with --force to confirm.");
@@ -45,6 +47,12 @@ pub fn execute(
         StepOutcome::Skipped(_) => {}
     }
 
+    match &result.hosts_outcome {
+        Some(StepOutcome::Success(msg)) => println!("{}", msg),
+        Some(StepOutcome::Warning(msg)) => eprintln!("{}", msg),
+        _ => {}
+    }
+
     println!(
         "Unregistered domain: {}",
         result.registration.display_pattern()
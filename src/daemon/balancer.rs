@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::domain::{LoadBalancing, ProxyTarget, TrafficSplit};
+
+/// Picks the backend for routes with several targets. Like the request
+/// stats it lives outside the routing state, so a reload doesn't reset
+/// the rotation or forget requests still in flight.
+#[derive(Default)]
+pub struct Balancer {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Next turn per target list, keyed by its display form.
+    turns: HashMap<String, usize>,
+    /// Requests in flight per target.
+    active: HashMap<String, usize>,
+}
+
+/// A target handed out by [`Balancer::pick`]. It counts as busy until
+/// dropped.
+pub struct Lease {
+    balancer: Arc<Balancer>,
+    target: ProxyTarget,
+}
+
+impl Balancer {
+    /// Choose among the targets `usable` accepts, or among all of them
+    /// if it accepts none.
+    pub fn pick(
+        self: &Arc<Self>,
+        targets: &[ProxyTarget],
+        strategy: LoadBalancing,
+        usable: impl Fn(&ProxyTarget) -> bool,
+    ) -> Lease {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let key = targets
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        let turn = state.turns.entry(key).or_default();
+        let start = *turn % targets.len();
+        *turn = turn.wrapping_add(1);
+
+        // Start from this turn's target so ties rotate too
+        let rotated = || targets[start..].iter().chain(&targets[..start]);
+        let mut candidates: Vec<&ProxyTarget> = rotated().filter(|t| usable(t)).collect();
+        if candidates.is_empty() {
+            candidates = rotated().collect();
+        }
+        let mut candidates = candidates.into_iter();
+        let target = match strategy {
+            LoadBalancing::RoundRobin => candidates.next(),
+            LoadBalancing::LeastConnections => {
+                candidates.min_by_key(|t| state.active.get(&t.to_string()).copied().unwrap_or(0))
+            }
+        }
+        .expect("load-balanced routes have targets")
+        .clone();
+
+        self.lease_locked(&mut state, target)
+    }
+
+    /// Choose by the route's weights for the request drawing `random`,
+    /// among the targets `usable` accepts.
+    pub fn pick_split(
+        self: &Arc<Self>,
+        targets: &[ProxyTarget],
+        split: &TrafficSplit,
+        random: u64,
+        usable: impl Fn(&ProxyTarget) -> bool,
+    ) -> Lease {
+        let index = split.pick(random, |i| usable(&targets[i]));
+        self.lease(targets[index].clone())
+    }
+
+    /// Hand out `target` itself, for a browser pinned to it.
+    pub fn lease(self: &Arc<Self>, target: ProxyTarget) -> Lease {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        self.lease_locked(&mut state, target)
+    }
+
+    fn lease_locked(self: &Arc<Self>, state: &mut State, target: ProxyTarget) -> Lease {
+        *state.active.entry(target.to_string()).or_default() += 1;
+        Lease {
+            balancer: self.clone(),
+            target,
+        }
+    }
+}
+
+impl Lease {
+    pub fn target(&self) -> &ProxyTarget {
+        &self.target
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        let mut state = self
+            .balancer
+            .state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let key = self.target.to_string();
+        if let Some(count) = state.active.get_mut(&key) {
+            *count -= 1;
+            if *count == 0 {
+                state.active.remove(&key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets() -> Vec<ProxyTarget> {
+        ["3000", "3001", "3002"]
+            .iter()
+            .map(|t| ProxyTarget::parse(t).unwrap())
+            .collect()
+    }
+
+    fn port(lease: &Lease) -> u16 {
+        lease.target().port().value()
+    }
+
+    #[test]
+    fn round_robin_takes_turns() {
+        let balancer = Arc::new(Balancer::default());
+        let targets = targets();
+        let ports: Vec<u16> = (0..4)
+            .map(|_| port(&balancer.pick(&targets, LoadBalancing::RoundRobin, |_| true)))
+            .collect();
+        assert_eq!(ports, [3000, 3001, 3002, 3000]);
+    }
+
+    #[test]
+    fn least_connections_avoids_busy_targets() {
+        let balancer = Arc::new(Balancer::default());
+        let targets = targets();
+        let first = balancer.pick(&targets, LoadBalancing::LeastConnections, |_| true);
+        let second = balancer.pick(&targets, LoadBalancing::LeastConnections, |_| true);
+        assert_eq!((port(&first), port(&second)), (3000, 3001));
+
+        // 3000 is free again, 3001 is still busy
+        drop(first);
+        let third = balancer.pick(&targets, LoadBalancing::LeastConnections, |_| true);
+        let fourth = balancer.pick(&targets, LoadBalancing::LeastConnections, |_| true);
+        assert_eq!((port(&third), port(&fourth)), (3002, 3000));
+        drop(second);
+        let state = balancer.state.lock().unwrap();
+        assert!(!state.active.contains_key("127.0.0.1:3001"));
+    }
+
+    #[test]
+    fn skips_unusable_targets_unless_none_are_left() {
+        let balancer = Arc::new(Balancer::default());
+        let targets = targets();
+        let ports: Vec<u16> = (0..3)
+            .map(|_| {
+                let lease = balancer.pick(&targets, LoadBalancing::RoundRobin, |t| {
+                    t.port().value() != 3001
+                });
+                port(&lease)
+            })
+            .collect();
+        assert_eq!(ports, [3000, 3002, 3002]);
+
+        let lease = balancer.pick(&targets, LoadBalancing::RoundRobin, |_| false);
+        assert_eq!(port(&lease), 3000);
+    }
+
+    #[test]
+    fn split_leases_count_as_busy() {
+        let balancer = Arc::new(Balancer::default());
+        let targets = targets();
+        let split = TrafficSplit::parse("0/0/1").unwrap();
+        let lease = balancer.pick_split(&targets, &split, 42, |_| true);
+        assert_eq!(port(&lease), 3002);
+
+        // Least connections now avoids the canary
+        let next = balancer.pick(&targets, LoadBalancing::LeastConnections, |_| true);
+        assert_eq!(port(&next), 3000);
+        drop(lease);
+        assert_eq!(balancer.state.lock().unwrap().active.len(), 1);
+    }
+}
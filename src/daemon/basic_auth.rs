@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use base64::{Engine as _, engine::general_purpose};
+use ring::digest;
+
+use crate::domain::BasicAuth;
+use crate::infrastructure::password;
+
+/// Forget all remembered logins beyond this many.
+const MAX_REMEMBERED: usize = 1024;
+
+/// Logins that already passed a password check. Hashing is slow on
+/// purpose, so without this every request from a logged-in browser
+/// would pay for it. Entries are digests of the `Authorization` header
+/// together with the stored hash, so changing a password in the config
+/// invalidates them.
+#[derive(Default)]
+pub struct VerifiedLogins {
+    digests: Mutex<HashSet<Vec<u8>>>,
+}
+
+impl VerifiedLogins {
+    fn key(authorization: &[u8], password_hash: &str) -> Vec<u8> {
+        let mut ctx = digest::Context::new(&digest::SHA256);
+        ctx.update(authorization);
+        ctx.update(b"\0");
+        ctx.update(password_hash.as_bytes());
+        ctx.finish().as_ref().to_vec()
+    }
+
+    fn contains(&self, key: &[u8]) -> bool {
+        let digests = self.digests.lock().unwrap_or_else(|e| e.into_inner());
+        digests.contains(key)
+    }
+
+    fn insert(&self, key: Vec<u8>) {
+        let mut digests = self.digests.lock().unwrap_or_else(|e| e.into_inner());
+        if digests.len() >= MAX_REMEMBERED {
+            digests.clear();
+        }
+        digests.insert(key);
+    }
+}
+
+/// Whether the request's `Authorization` header logs in one of `auth`'s
+/// users.
+pub async fn is_authorized(auth: &BasicAuth, headers: &HeaderMap, logins: &VerifiedLogins) -> bool {
+    let Some(authorization) = headers.get(header::AUTHORIZATION) else {
+        return false;
+    };
+    let Some((user, password)) = parse_basic(authorization) else {
+        return false;
+    };
+    let Some(password_hash) = auth.password_hash(&user) else {
+        return false;
+    };
+
+    let key = VerifiedLogins::key(authorization.as_bytes(), password_hash);
+    if logins.contains(&key) {
+        return true;
+    }
+    let password_hash = password_hash.to_string();
+    let verified = tokio::task::spawn_blocking(move || password::verify(&password_hash, &password))
+        .await
+        .unwrap_or(false);
+    if verified {
+        logins.insert(key);
+    }
+    verified
+}
+
+/// Split `Basic <base64 user:password>` into user and password.
+fn parse_basic(value: &HeaderValue) -> Option<(String, String)> {
+    let value = value.to_str().ok()?;
+    let (scheme, credentials) = value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = general_purpose::STANDARD.decode(credentials.trim()).ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+    Some((user.to_string(), password.to_string()))
+}
+
+/// Ask the browser to log in.
+pub fn challenge(realm: &str) -> Response {
+    let realm = realm.replace(['"', '\\'], "");
+    let mut response = (StatusCode::UNAUTHORIZED, "Authentication required").into_response();
+    if let Ok(value) =
+        HeaderValue::from_str(&format!("Basic realm=\"{}\", charset=\"UTF-8\"", realm))
+    {
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth() -> BasicAuth {
+        let mut auth = BasicAuth::default();
+        auth.set_user("alice", password::hash("secret").unwrap());
+        auth
+    }
+
+    fn headers(user: &str, password: &str) -> HeaderMap {
+        let credentials = general_purpose::STANDARD.encode(format!("{}:{}", user, password));
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Basic {}", credentials)).unwrap(),
+        );
+        headers
+    }
+
+    #[tokio::test]
+    async fn accepts_only_known_users_with_the_right_password() {
+        let auth = auth();
+        let logins = VerifiedLogins::default();
+        assert!(is_authorized(&auth, &headers("alice", "secret"), &logins).await);
+        assert!(!is_authorized(&auth, &headers("alice", "wrong"), &logins).await);
+        assert!(!is_authorized(&auth, &headers("bob", "secret"), &logins).await);
+        assert!(!is_authorized(&auth, &HeaderMap::new(), &logins).await);
+    }
+
+    #[tokio::test]
+    async fn remembered_logins_follow_password_changes() {
+        let mut auth = auth();
+        let logins = VerifiedLogins::default();
+        let alice = headers("alice", "secret");
+        assert!(is_authorized(&auth, &alice, &logins).await);
+        assert_eq!(logins.digests.lock().unwrap().len(), 1);
+
+        auth.set_user("alice", password::hash("changed").unwrap());
+        assert!(!is_authorized(&auth, &alice, &logins).await);
+    }
+
+    #[test]
+    fn parses_basic_credentials() {
+        let value = HeaderValue::from_static("basic YWxpY2U6czpleA==");
+        assert_eq!(
+            parse_basic(&value),
+            Some(("alice".to_string(), "s:ex".to_string()))
+        );
+        assert_eq!(parse_basic(&HeaderValue::from_static("Bearer abc")), None);
+        assert_eq!(parse_basic(&HeaderValue::from_static("Basic !!")), None);
+    }
+
+    #[test]
+    fn challenge_names_the_realm() {
+        let response = challenge("api.roxy");
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[header::WWW_AUTHENTICATE],
+            "Basic realm=\"api.roxy\", charset=\"UTF-8\""
+        );
+    }
+}
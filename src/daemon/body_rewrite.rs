@@ -0,0 +1,170 @@
+//! Applies a route's body rewrite rules to backend responses.
+//!
+//! Only uncompressed text responses of a bounded size are rewritten;
+//! anything else streams through untouched.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use axum::body::{Body, Bytes};
+use axum::http::{HeaderMap, header};
+use http_body_util::BodyExt;
+use hyper::body::{Body as HttpBody, Frame, Incoming};
+use tracing::{debug, warn};
+
+use crate::domain::BodyRewrite;
+
+/// Bodies larger than this are passed through as they are.
+const MAX_REWRITE_BODY: usize = 5 * 1024 * 1024;
+
+/// Rewrite `body` if its headers say it is text we can safely edit.
+pub async fn rewrite_response(
+    rules: &[BodyRewrite],
+    headers: &mut HeaderMap,
+    body: Incoming,
+) -> Body {
+    if !is_rewritable(headers) {
+        return Body::new(body);
+    }
+
+    let mut body = body;
+    let mut buf = Vec::new();
+    while let Some(frame) = body.frame().await {
+        let data = match frame.map(Frame::into_data) {
+            Ok(Ok(data)) => data,
+            // Trailers end the body
+            Ok(Err(_)) => break,
+            Err(e) => {
+                warn!(error = %e, "Failed to read backend response for rewriting");
+                return Body::from(buf);
+            }
+        };
+        buf.extend_from_slice(&data);
+
+        if buf.len() > MAX_REWRITE_BODY {
+            debug!("Response too large to rewrite, passing it through");
+            return Body::new(Prefixed {
+                prefix: Some(Bytes::from(buf)),
+                rest: body,
+            });
+        }
+    }
+
+    match String::from_utf8(buf) {
+        Ok(text) => {
+            // The length changes; hyper sets it again from the new body
+            headers.remove(header::CONTENT_LENGTH);
+            Body::from(BodyRewrite::apply_all(rules, &text))
+        }
+        Err(e) => Body::from(e.into_bytes()),
+    }
+}
+
+/// Text formats worth rewriting. Event streams never end, and
+/// compressed bodies can't be edited as text.
+fn is_rewritable(headers: &HeaderMap) -> bool {
+    let encoded = headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| !v.eq_ignore_ascii_case("identity"));
+    let too_large = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<usize>().ok())
+        .is_some_and(|len| len > MAX_REWRITE_BODY);
+    if encoded || too_large {
+        return false;
+    }
+
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return false;
+    };
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    (mime.starts_with("text/") && mime != "text/event-stream")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime.as_str(),
+            "application/javascript" | "application/json" | "application/xml"
+        )
+}
+
+/// Bytes already read from a body, followed by the rest of it.
+pub(super) struct Prefixed<B = Incoming> {
+    pub(super) prefix: Option<Bytes>,
+    pub(super) rest: B,
+}
+
+impl<B> HttpBody for Prefixed<B>
+where
+    B: HttpBody<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        if let Some(prefix) = self.prefix.take() {
+            return Poll::Ready(Some(Ok(Frame::data(prefix))));
+        }
+        Pin::new(&mut self.rest).poll_frame(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(pairs: &[(header::HeaderName, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn text_formats_are_rewritable() {
+        for content_type in [
+            "text/html; charset=utf-8",
+            "application/javascript",
+            "application/json",
+            "application/manifest+json",
+            "image/svg+xml",
+        ] {
+            let h = headers(&[(header::CONTENT_TYPE, content_type)]);
+            assert!(is_rewritable(&h), "{}", content_type);
+        }
+    }
+
+    #[test]
+    fn binary_streams_and_compressed_are_not() {
+        assert!(!is_rewritable(&headers(&[(
+            header::CONTENT_TYPE,
+            "image/png"
+        )])));
+        assert!(!is_rewritable(&headers(&[(
+            header::CONTENT_TYPE,
+            "text/event-stream"
+        )])));
+        assert!(!is_rewritable(&headers(&[
+            (header::CONTENT_TYPE, "text/html"),
+            (header::CONTENT_ENCODING, "gzip"),
+        ])));
+        assert!(!is_rewritable(&headers(&[
+            (header::CONTENT_TYPE, "text/html"),
+            (header::CONTENT_LENGTH, "99999999"),
+        ])));
+        assert!(!is_rewritable(&HeaderMap::new()));
+    }
+}
@@ -0,0 +1,418 @@
+//! Download page for the Root CA.
+//!
+//! `http://roxy.roxy/` explains how to trust Roxy's certificates on other
+//! devices and links the CA certificate. Teammates' phones and
+//! containers don't resolve `.roxy`, so the page at `/roxy-ca` and the
+//! certificate files are also served to requests addressed to the
+//! machine's IP. `roxy share-ca` points phones there with a QR code.
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use axum::{
+    Router,
+    body::Body,
+    http::{HeaderMap, StatusCode, Uri, header},
+    response::{IntoResponse, Response},
+};
+use base64::{Engine as _, engine::general_purpose};
+use ring::digest;
+use rustls::pki_types::CertificateDer;
+use rustls::pki_types::pem::PemObject;
+use tokio::net::TcpListener;
+
+use super::theme;
+
+/// Host of the download page. A registered `roxy.roxy` takes precedence.
+pub const CA_HOST: &str = "roxy.roxy";
+
+/// The CA certificate in PEM form.
+pub const PEM_PATH: &str = "/roxy-ca.pem";
+
+/// The same bytes, typed so iOS and Android offer to install them.
+pub const CRT_PATH: &str = "/roxy-ca.crt";
+
+/// The CA wrapped in a configuration profile, which iOS installs.
+pub const MOBILECONFIG_PATH: &str = "/roxy-ca.mobileconfig";
+
+/// The download page on a bare IP address, where `/` isn't Roxy's.
+pub const SHARE_PATH: &str = "/roxy-ca";
+
+/// Whether a request for an unregistered `host` is for the CA: anything
+/// on `roxy.roxy`, or the page and certificate on a bare IP address.
+pub fn is_ca_request(host: &str, path: &str) -> bool {
+    let name = strip_port(host).trim_end_matches('.').to_lowercase();
+    if name == CA_HOST {
+        return true;
+    }
+    let ip = name.trim_start_matches('[').trim_end_matches(']');
+    ip.parse::<IpAddr>().is_ok()
+        && matches!(path, SHARE_PATH | PEM_PATH | CRT_PATH | MOBILECONFIG_PATH)
+}
+
+/// Answer a CA request to `host` with the certificate at `ca_cert`.
+pub fn respond(ca_cert: Option<&Path>, host: &str, path: &str) -> Response {
+    let pem = ca_cert.and_then(|p| std::fs::read(p).ok());
+    match (path, pem) {
+        ("/" | SHARE_PATH, pem) => instructions_page(host, pem.is_some()),
+        (PEM_PATH, Some(pem)) => certificate(pem, "application/x-pem-file"),
+        (CRT_PATH, Some(pem)) => certificate(pem, "application/x-x509-ca-cert"),
+        (MOBILECONFIG_PATH, Some(pem)) => match mobileconfig(&pem) {
+            Some(profile) => certificate(profile, "application/x-apple-aspen-config"),
+            None => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "The Root CA certificate can't be read\n",
+            )
+                .into_response(),
+        },
+        (PEM_PATH | CRT_PATH | MOBILECONFIG_PATH, None) => (
+            StatusCode::NOT_FOUND,
+            "No Root CA yet. Run on the machine running Roxy: sudo roxy install\n",
+        )
+            .into_response(),
+        _ => (StatusCode::NOT_FOUND, "Not found\n").into_response(),
+    }
+}
+
+/// Serve the download page and certificate files to any host on
+/// `listener`, for `roxy share-ca` while the daemon isn't running.
+pub async fn serve(listener: TcpListener, ca_cert: PathBuf) -> std::io::Result<()> {
+    let app = Router::new().fallback(move |headers: HeaderMap, uri: Uri| {
+        let ca_cert = ca_cert.clone();
+        async move {
+            let host = headers
+                .get(header::HOST)
+                .and_then(|h| h.to_str().ok())
+                .unwrap_or_default();
+            respond(Some(&ca_cert), host, uri.path())
+        }
+    });
+    axum::serve(listener, app).await
+}
+
+/// A configuration profile that installs the PEM CA certificate on iOS.
+/// Its identifiers derive from the certificate, so installing it again
+/// replaces the earlier copy.
+fn mobileconfig(pem: &[u8]) -> Option<Vec<u8>> {
+    let der = CertificateDer::from_pem_slice(pem).ok()?;
+    let hash = digest::digest(&digest::SHA256, &der);
+    let (profile_uuid, payload_uuid) = hash.as_ref().split_at(16);
+    let profile_uuid = uuid(profile_uuid);
+    let payload_uuid = uuid(payload_uuid);
+
+    Some(
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>PayloadContent</key>
+  <array>
+    <dict>
+      <key>PayloadCertificateFileName</key>
+      <string>roxy-ca.crt</string>
+      <key>PayloadContent</key>
+      <data>{cert}</data>
+      <key>PayloadDisplayName</key>
+      <string>Roxy Root CA</string>
+      <key>PayloadIdentifier</key>
+      <string>roxy.ca.{payload_uuid}</string>
+      <key>PayloadType</key>
+      <string>com.apple.security.root</string>
+      <key>PayloadUUID</key>
+      <string>{payload_uuid}</string>
+      <key>PayloadVersion</key>
+      <integer>1</integer>
+    </dict>
+  </array>
+  <key>PayloadDescription</key>
+  <string>Trusts the certificates Roxy issues for local development.</string>
+  <key>PayloadDisplayName</key>
+  <string>Roxy Root CA</string>
+  <key>PayloadIdentifier</key>
+  <string>roxy.ca.{profile_uuid}</string>
+  <key>PayloadType</key>
+  <string>Configuration</string>
+  <key>PayloadUUID</key>
+  <string>{profile_uuid}</string>
+  <key>PayloadVersion</key>
+  <integer>1</integer>
+</dict>
+</plist>
+"#,
+            cert = general_purpose::STANDARD.encode(&der),
+        )
+        .into_bytes(),
+    )
+}
+
+/// A version 4 style UUID from 16 bytes.
+fn uuid(bytes: &[u8]) -> String {
+    let hex: String = bytes
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| match i {
+            6 => b & 0x0f | 0x40,
+            8 => b & 0x3f | 0x80,
+            _ => b,
+        })
+        .map(|b| format!("{:02X}", b))
+        .collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn strip_port(host: &str) -> &str {
+    // [::1]:80 keeps its brackets; a bare IPv6 address has no port
+    if let Some(end) = host.find(']') {
+        return &host[..=end];
+    }
+    match host.rsplit_once(':') {
+        Some((name, _)) if !name.contains(':') => name,
+        _ => host,
+    }
+}
+
+fn certificate(pem: Vec<u8>, content_type: &'static str) -> Response {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from(pem))
+        .unwrap()
+}
+
+fn instructions_page(host: &str, has_ca: bool) -> Response {
+    let base = format!("http://{}", theme::html_escape(host));
+    let pem_url = format!("{}{}", base, PEM_PATH);
+    let crt_url = format!("{}{}", base, CRT_PATH);
+    let mobileconfig_url = format!("{}{}", base, MOBILECONFIG_PATH);
+
+    let mut body = String::new();
+    body.push_str("<div class=\"ca-card\">\n");
+    body.push_str("<h1 class=\"ca-title\">Trust Roxy's Certificates</h1>\n");
+    if !has_ca {
+        body.push_str("<p>There is no Root CA yet. On the machine running Roxy, run:</p>\n");
+        body.push_str("<div class=\"command\">sudo roxy install</div>\n");
+        body.push_str("</div>");
+        return page(StatusCode::NOT_FOUND, &body);
+    }
+
+    body.push_str("<p>Roxy signs its HTTPS certificates with its own Root CA. ");
+    body.push_str("Devices that trust it open <code>https://</code> sites ");
+    body.push_str("without warnings.</p>\n");
+    body.push_str("<p class=\"downloads\"><a class=\"button\" href=\"");
+    body.push_str(PEM_PATH);
+    body.push_str("\" download>Download roxy-ca.pem</a> ");
+    body.push_str("<a class=\"button\" href=\"");
+    body.push_str(MOBILECONFIG_PATH);
+    body.push_str("\">Install on iPhone or iPad</a> ");
+    body.push_str("<a class=\"button\" href=\"");
+    body.push_str(CRT_PATH);
+    body.push_str("\">Install on Android</a></p>\n");
+
+    let mut section = |label: &str, steps: &str| {
+        body.push_str("<p class=\"help-label\">");
+        body.push_str(label);
+        body.push_str("</p>\n<div class=\"command\">");
+        body.push_str(steps);
+        body.push_str("</div>\n");
+    };
+    section(
+        "macOS",
+        &format!(
+            "curl -o roxy-ca.pem {}<br>\
+             sudo security add-trusted-cert -d -r trustRoot \
+             -k /Library/Keychains/System.keychain roxy-ca.pem",
+            pem_url
+        ),
+    );
+    section(
+        "Linux (Debian, Ubuntu)",
+        &format!(
+            "sudo curl -o /usr/local/share/ca-certificates/roxy-ca.crt {}<br>\
+             sudo update-ca-certificates",
+            pem_url
+        ),
+    );
+    section(
+        "Windows (as administrator)",
+        &format!(
+            "curl.exe -o roxy-ca.pem {}<br>certutil -addstore -f ROOT roxy-ca.pem",
+            pem_url
+        ),
+    );
+    section(
+        "iPhone and iPad",
+        &format!(
+            "Open {} in Safari and allow the profile<br>\
+             Settings &gt; General &gt; VPN &amp; Device Management: install it<br>\
+             Settings &gt; General &gt; About &gt; Certificate Trust Settings: turn it on",
+            mobileconfig_url
+        ),
+    );
+    section(
+        "Android",
+        &format!(
+            "Download {}<br>\
+             Settings &gt; Security &gt; Encryption &amp; credentials &gt; \
+             Install a certificate &gt; CA certificate",
+            crt_url
+        ),
+    );
+    section(
+        "Firefox (keeps its own list)",
+        "Settings &gt; Privacy &amp; Security &gt; Certificates &gt; View Certificates \
+         &gt; Authorities &gt; Import",
+    );
+    section(
+        "Docker images and Node.js",
+        &format!(
+            "RUN curl -o /usr/local/share/ca-certificates/roxy-ca.crt {}<br>\
+             RUN update-ca-certificates<br>\
+             ENV NODE_EXTRA_CA_CERTS=/usr/local/share/ca-certificates/roxy-ca.crt",
+            pem_url
+        ),
+    );
+    body.push_str("<p class=\"note\">Only trust this CA on devices you control. ");
+    body.push_str("Anyone holding its key can issue certificates they accept.</p>\n");
+    body.push_str("</div>");
+
+    page(StatusCode::OK, &body)
+}
+
+fn page(status: StatusCode, body: &str) -> Response {
+    let html = theme::render_page("Root CA", body, CA_CSS, "");
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Body::from(html))
+        .unwrap()
+}
+
+const CA_CSS: &str = "\
+.ca-card{\
+    background:var(--card-bg);border-radius:12px;\
+    border:1px solid var(--border);padding:40px;\
+    box-shadow:0 4px 16px rgba(0,0,0,.04);\
+    max-width:760px;margin:24px auto;\
+}\
+.ca-title{\
+    color:var(--fox-orange);font-size:1.7em;margin-bottom:16px;\
+    font-weight:700;\
+}\
+.ca-card p{margin-bottom:16px}\
+.downloads{display:flex;flex-wrap:wrap;gap:12px;margin:24px 0}\
+.button{\
+    background:var(--teal);color:#fff;padding:10px 18px;\
+    border-radius:8px;font-weight:600;\
+}\
+.button:hover{background:var(--teal-dark);color:#fff}\
+.help-label{\
+    font-size:.9em;color:var(--text-light);margin-bottom:8px;\
+    font-weight:500;text-transform:uppercase;letter-spacing:.03em;\
+}\
+.command{\
+    background:linear-gradient(135deg,#2D2520 0%,#3D3530 100%);\
+    color:#F8F0E8;padding:16px 20px;\
+    border-radius:10px;margin:12px 0 20px 0;\
+    font-family:'SF Mono',Monaco,'Cascadia Code',Menlo,Consolas,monospace;\
+    font-size:.88em;line-height:1.8;overflow-wrap:anywhere;\
+    border:1px solid #4D4540;\
+}\
+.note{font-size:.9em;color:var(--text-light)}\
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+
+    async fn body(response: Response) -> String {
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    #[test]
+    fn recognises_ca_requests() {
+        assert!(is_ca_request("roxy.roxy", "/"));
+        assert!(is_ca_request("ROXY.roxy.:8080", "/anything"));
+        assert!(is_ca_request("192.168.1.20", PEM_PATH));
+        assert!(is_ca_request("192.168.1.20:8080", CRT_PATH));
+        assert!(is_ca_request("[fd00::1]:80", PEM_PATH));
+        assert!(is_ca_request("192.168.1.20:8080", SHARE_PATH));
+        assert!(is_ca_request("192.168.1.20", MOBILECONFIG_PATH));
+
+        assert!(!is_ca_request("192.168.1.20", "/"));
+        assert!(!is_ca_request("myapp.roxy", PEM_PATH));
+        assert!(!is_ca_request("roxy.roxy.example.com", "/"));
+    }
+
+    #[tokio::test]
+    async fn serves_the_certificate_and_instructions() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ca = tmp.path().join("ca.crt");
+        std::fs::write(&ca, "-----BEGIN CERTIFICATE-----\n").unwrap();
+
+        let response = respond(Some(&ca), "192.168.1.20", PEM_PATH);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/x-pem-file"
+        );
+        assert_eq!(body(response).await, "-----BEGIN CERTIFICATE-----\n");
+
+        let response = respond(Some(&ca), "roxy.roxy:8080", "/");
+        assert_eq!(response.status(), StatusCode::OK);
+        let page = body(response).await;
+        assert!(page.contains("curl -o roxy-ca.pem http://roxy.roxy:8080/roxy-ca.pem"));
+        assert!(page.contains("update-ca-certificates"));
+
+        let response = respond(Some(&ca), "roxy.roxy", "/other");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn wraps_the_certificate_in_an_ios_profile() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ca = crate::infrastructure::certs::ca::RootCA::new(tmp.path().to_path_buf());
+        ca.generate().unwrap();
+
+        let response = respond(Some(&ca.cert_path()), "192.168.1.20", MOBILECONFIG_PATH);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/x-apple-aspen-config"
+        );
+        let profile = body(response).await;
+        let pem = std::fs::read(ca.cert_path()).unwrap();
+        let der = CertificateDer::from_pem_slice(&pem).unwrap();
+        assert!(profile.contains(&general_purpose::STANDARD.encode(&der)));
+        assert!(profile.contains("com.apple.security.root"));
+        // The same certificate gives the same profile
+        assert_eq!(mobileconfig(&pem).unwrap(), profile.as_bytes());
+
+        let response = respond(Some(&ca.cert_path()), "192.168.1.20:8080", SHARE_PATH);
+        let page = body(response).await;
+        assert!(page.contains("http://192.168.1.20:8080/roxy-ca.mobileconfig"));
+    }
+
+    #[tokio::test]
+    async fn says_how_to_create_a_missing_ca() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ca = tmp.path().join("ca.crt");
+
+        let response = respond(Some(&ca), "roxy.roxy", PEM_PATH);
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = respond(None, "roxy.roxy", "/");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(body(response).await.contains("sudo roxy install"));
+    }
+}
@@ -0,0 +1,18 @@
+//! Renews domain certificates before they expire, so HTTPS keeps
+//! working on a daemon that runs for months.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::reload::Reloader;
+
+/// How often to look for certificates close to expiry.
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Check the certificates now and every few hours after.
+pub async fn run(reloader: Arc<Reloader>) {
+    loop {
+        reloader.renew_expiring();
+        tokio::time::sleep(CHECK_INTERVAL).await;
+    }
+}
@@ -0,0 +1,148 @@
+//! Serves certificates replaced on disk, e.g. by `roxy register`, an
+//! imported CA or a renewed custom certificate, without waiting for
+//! `roxy reload` or a restart.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tracing::{info, warn};
+
+use super::file_watch::DirWatcher;
+use super::reload::Reloader;
+use crate::infrastructure::certs::ca::RootCA;
+use crate::infrastructure::paths::RoxyPaths;
+
+/// How long the certificate directories have to stay quiet before the
+/// files are loaded, so a certificate isn't read between its key and
+/// its chain being written.
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+/// Every certificate file and when it last changed.
+type Stamp = Vec<(PathBuf, Option<SystemTime>)>;
+
+/// Reload the TLS certificates whenever a file in the certs directory,
+/// the Root CA or a domain's custom certificate changes.
+pub async fn run(reloader: Arc<Reloader>, paths: RoxyPaths) {
+    let mut watcher = match DirWatcher::new() {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!(error = %e, "Can't watch certificate files; apply changes with roxy reload");
+            return;
+        }
+    };
+    let custom_files = reloader.custom_cert_files();
+    watcher.watch(watched_dirs(&paths, &custom_files));
+    let mut last = stamp(&paths, custom_files);
+
+    while watcher.changed(SETTLE_TIME).await {
+        // Domains may have brought other custom certificates along, and
+        // the certs directory may have been created meanwhile
+        let custom_files = reloader.custom_cert_files();
+        watcher.watch(watched_dirs(&paths, &custom_files));
+
+        let current = stamp(&paths, custom_files);
+        if current == last {
+            continue;
+        }
+        last = current;
+        match reloader.reload_certificates() {
+            Ok(()) => info!("Certificate files changed, serving them to new connections"),
+            Err(e) => warn!(error = %e, "Certificate files changed but could not be loaded"),
+        }
+    }
+}
+
+/// The certs directory, the data directory holding the Root CA, and
+/// every directory with a custom certificate or key.
+fn watched_dirs(paths: &RoxyPaths, custom_files: &[PathBuf]) -> Vec<PathBuf> {
+    let custom_dirs = custom_files
+        .iter()
+        .filter_map(|file| file.parent())
+        .map(|dir| dir.to_path_buf());
+    [paths.certs_dir.clone(), paths.data_dir.clone()]
+        .into_iter()
+        .chain(custom_dirs)
+        .collect()
+}
+
+fn stamp(paths: &RoxyPaths, custom_files: Vec<PathBuf>) -> Stamp {
+    let ca = RootCA::new(paths.data_dir.clone());
+    let mut files: Vec<_> = fs::read_dir(&paths.certs_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .chain([ca.cert_path(), ca.key_path()])
+        .chain(custom_files)
+        .collect();
+    files.sort();
+
+    files
+        .into_iter()
+        .map(|path| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamp_changes_with_the_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let paths = RoxyPaths {
+            data_dir: tmp.path().to_path_buf(),
+            certs_dir: tmp.path().join("certs"),
+            ..RoxyPaths::default()
+        };
+        fs::create_dir(&paths.certs_dir).unwrap();
+        let empty = stamp(&paths, Vec::new());
+
+        fs::write(paths.certs_dir.join("myapp.roxy.crt"), "cert").unwrap();
+        let added = stamp(&paths, Vec::new());
+        assert_ne!(added, empty);
+        assert_eq!(stamp(&paths, Vec::new()), added);
+
+        fs::remove_file(paths.certs_dir.join("myapp.roxy.crt")).unwrap();
+        assert_eq!(stamp(&paths, Vec::new()), empty);
+    }
+
+    #[test]
+    fn watches_the_directories_of_custom_certificates() {
+        let paths = RoxyPaths {
+            data_dir: PathBuf::from("/var/lib/roxy"),
+            certs_dir: PathBuf::from("/var/lib/roxy/certs"),
+            ..RoxyPaths::default()
+        };
+        let custom = [PathBuf::from("/etc/letsencrypt/live/app/fullchain.pem")];
+        assert_eq!(
+            watched_dirs(&paths, &custom),
+            [
+                PathBuf::from("/var/lib/roxy/certs"),
+                PathBuf::from("/var/lib/roxy"),
+                PathBuf::from("/etc/letsencrypt/live/app"),
+            ]
+        );
+    }
+
+    #[test]
+    fn stamp_covers_custom_certificates() {
+        let tmp = tempfile::tempdir().unwrap();
+        let paths = RoxyPaths {
+            data_dir: tmp.path().to_path_buf(),
+            certs_dir: tmp.path().join("certs"),
+            ..RoxyPaths::default()
+        };
+        let custom = tmp.path().join("fullchain.pem");
+        let before = stamp(&paths, vec![custom.clone()]);
+
+        fs::write(&custom, "cert").unwrap();
+        assert_ne!(stamp(&paths, vec![custom]), before);
+    }
+}
@@ -0,0 +1,71 @@
+use std::convert::Infallible;
+use std::future;
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{HeaderValue, header},
+    response::Response,
+};
+use tower::{Layer, ServiceExt, service_fn};
+use tower_http::compression::CompressionLayer;
+
+/// Compress `response` with gzip or brotli if the client accepts it,
+/// the way a CDN would. Tiny, already encoded, image and event-stream
+/// responses pass through untouched.
+pub async fn compress(accept_encoding: Option<HeaderValue>, response: Response) -> Response {
+    let mut request = Request::new(Body::empty());
+    if let Some(accept_encoding) = accept_encoding {
+        request
+            .headers_mut()
+            .insert(header::ACCEPT_ENCODING, accept_encoding);
+    }
+
+    let mut response = Some(response);
+    let service = CompressionLayer::new().layer(service_fn(move |_: Request| {
+        let response = response.take().expect("compression service is called once");
+        future::ready(Ok::<_, Infallible>(response))
+    }));
+    match service.oneshot(request).await {
+        Ok(response) => response.map(Body::new),
+        Err(never) => match never {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+
+    fn text_response() -> Response {
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(Body::from("hello ".repeat(100)))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn compresses_for_clients_that_accept_it() {
+        let response = compress(Some(HeaderValue::from_static("gzip")), text_response()).await;
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(body.len() < 600);
+
+        let response = compress(Some(HeaderValue::from_static("br, gzip")), text_response()).await;
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "br");
+    }
+
+    #[tokio::test]
+    async fn leaves_responses_alone_otherwise() {
+        let response = compress(None, text_response()).await;
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+
+        let mut encoded = text_response();
+        encoded.headers_mut().insert(
+            header::CONTENT_ENCODING,
+            HeaderValue::from_static("deflate"),
+        );
+        let response = compress(Some(HeaderValue::from_static("gzip")), encoded).await;
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "deflate");
+    }
+}
@@ -0,0 +1,63 @@
+//! Applies changes to the config file, e.g. by `roxy register` or an
+//! editor, without waiting for `roxy reload`.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use super::file_watch::DirWatcher;
+use super::reload::Reloader;
+
+/// How long the config directory has to stay quiet before the file is
+/// applied, so an editor's or a bulk import's burst of writes causes
+/// one reload.
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+/// The config file's content; `None` while it doesn't exist. Compared
+/// whole, as two quick writes can leave the modification time the same.
+type Stamp = Option<Vec<u8>>;
+
+/// Reload the config whenever the file changes. A change that doesn't
+/// validate is logged and leaves the running config in place.
+pub async fn run(reloader: Arc<Reloader>) {
+    let path = reloader.config_path().to_path_buf();
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut watcher = match DirWatcher::new() {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!(error = %e, "Can't watch the config file; apply changes with roxy reload");
+            return;
+        }
+    };
+    watcher.watch([dir.to_path_buf()]);
+
+    let mut last = stamp(&path);
+    while watcher.changed(SETTLE_TIME).await {
+        // Other files in the directory change too, e.g. the history
+        let current = stamp(&path);
+        if current == last {
+            continue;
+        }
+        last = current;
+        match reloader.reload().await {
+            Ok(summary) => info!(
+                changes = %summary.replace('\n', "; "),
+                "Config file changed, applied it"
+            ),
+            Err(e) => warn!(
+                error = format!("{:#}", e),
+                "Config file changed but can't be applied, keeping the running config"
+            ),
+        }
+    }
+}
+
+fn stamp(path: &Path) -> Stamp {
+    fs::read(path).ok()
+}
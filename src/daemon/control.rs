@@ -0,0 +1,240 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+#[cfg(windows)]
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+#[cfg(windows)]
+use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tracing::{debug, info, warn};
+
+use super::health_check::BackendHealth;
+use super::reload::Reloader;
+use super::response_cache::ResponseCache;
+use super::stats::RequestStats;
+use super::supervisor::Supervisor;
+use crate::infrastructure::control::{ControlRequest, MAX_REQUEST_LEN, encode_response};
+use crate::infrastructure::tracing::LogLevelHandle;
+
+/// Where the CLI reaches the daemon: a Unix socket, or a named pipe on
+/// Windows.
+pub struct ControlListener {
+    #[cfg(unix)]
+    listener: UnixListener,
+    #[cfg(windows)]
+    pipe: PathBuf,
+    /// The pipe instance waiting for the next client
+    #[cfg(windows)]
+    next: NamedPipeServer,
+}
+
+impl ControlListener {
+    #[cfg(unix)]
+    fn bind(socket_path: &Path) -> Result<Self> {
+        // A socket left behind by a crashed daemon would block the bind
+        match fs::remove_file(socket_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(e).context(format!(
+                    "Failed to remove stale control socket {}",
+                    socket_path.display()
+                ));
+            }
+            _ => {}
+        }
+        if let Some(parent) = socket_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let listener = UnixListener::bind(socket_path).context(format!(
+            "Failed to bind control socket {}",
+            socket_path.display()
+        ))?;
+        Ok(Self { listener })
+    }
+
+    #[cfg(windows)]
+    fn bind(pipe: &Path) -> Result<Self> {
+        let next = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(pipe)
+            .context(format!("Failed to create control pipe {}", pipe.display()))?;
+        Ok(Self {
+            pipe: pipe.to_path_buf(),
+            next,
+        })
+    }
+
+    #[cfg(unix)]
+    async fn accept(&mut self) -> io::Result<UnixStream> {
+        Ok(self.listener.accept().await?.0)
+    }
+
+    /// Hand out the connected pipe instance and open a fresh one for
+    /// the next client.
+    #[cfg(windows)]
+    async fn accept(&mut self) -> io::Result<NamedPipeServer> {
+        self.next.connect().await?;
+        let fresh = ServerOptions::new().create(&self.pipe)?;
+        Ok(std::mem::replace(&mut self.next, fresh))
+    }
+}
+
+/// Daemon side of the control channel: accepts requests from the CLI
+/// and applies them to the running daemon.
+pub struct ControlServer {
+    log_level: LogLevelHandle,
+    reloader: Arc<Reloader>,
+    stats: Arc<RequestStats>,
+    health: Arc<BackendHealth>,
+    cache: Arc<ResponseCache>,
+    supervisor: Arc<Supervisor>,
+}
+
+impl ControlServer {
+    /// Bind the control channel. Kept apart from `new` so the daemon can
+    /// bind it before dropping root.
+    pub fn listen(socket_path: &Path) -> Result<ControlListener> {
+        let listener = ControlListener::bind(socket_path)?;
+        info!(socket = %socket_path.display(), "Control channel listening");
+        Ok(listener)
+    }
+
+    pub fn new(
+        log_level: LogLevelHandle,
+        reloader: Arc<Reloader>,
+        stats: Arc<RequestStats>,
+        health: Arc<BackendHealth>,
+        cache: Arc<ResponseCache>,
+        supervisor: Arc<Supervisor>,
+    ) -> Self {
+        Self {
+            log_level,
+            reloader,
+            stats,
+            health,
+            cache,
+            supervisor,
+        }
+    }
+
+    pub async fn run(self, mut listener: ControlListener) {
+        let server = Arc::new(self);
+        loop {
+            let stream = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!(error = %e, "Failed to accept control connection");
+                    continue;
+                }
+            };
+
+            let server = server.clone();
+            tokio::spawn(async move {
+                if let Err(e) = server.handle_connection(stream).await {
+                    debug!(error = %e, "Control connection failed");
+                }
+            });
+        }
+    }
+
+    async fn handle_connection<S>(&self, stream: S) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut line = String::new();
+        BufReader::new(reader.take(MAX_REQUEST_LEN as u64))
+            .read_line(&mut line)
+            .await?;
+
+        let response = match line.trim().parse::<ControlRequest>() {
+            Ok(request) => {
+                debug!(request = %request, "Control request");
+                self.handle(request).await
+            }
+            Err(e) => Err(e.to_string()),
+        };
+
+        writer
+            .write_all(encode_response(&response).as_bytes())
+            .await?;
+        writer.shutdown().await
+    }
+
+    async fn handle(&self, request: ControlRequest) -> Result<String, String> {
+        match request {
+            ControlRequest::LogLevel(None) => self
+                .log_level
+                .current()
+                .map(|filter| format!("Current log filter: {}", filter))
+                .ok_or_else(|| "Log filter is not available".to_string()),
+            ControlRequest::LogLevel(Some(level)) => {
+                self.log_level
+                    .set(level)
+                    .map_err(|e| format!("Failed to change log level: {}", e))?;
+                info!(level = %level, "Log level changed");
+                Ok(format!("Log level set to {}", level))
+            }
+            ControlRequest::Reload => self.reloader.reload().await.map_err(|e| {
+                warn!(error = %e, "Reload failed");
+                format!("{:#}", e)
+            }),
+            ControlRequest::Stats => Ok(self
+                .stats
+                .snapshot()
+                .iter()
+                .map(|counts| counts.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")),
+            ControlRequest::Health => Ok(self
+                .health
+                .snapshot()
+                .iter()
+                .map(|status| status.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")),
+            ControlRequest::PurgeCache(domain) => {
+                let purged = self.cache.purge(&domain);
+                info!(domain = %domain, purged, "Response cache purged");
+                Ok(format!(
+                    "Purged {} cached response{} for {}",
+                    purged,
+                    if purged == 1 { "" } else { "s" },
+                    domain
+                ))
+            }
+            ControlRequest::Processes => Ok(self
+                .supervisor
+                .snapshot()
+                .iter()
+                .map(|status| status.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")),
+            ControlRequest::RestartBackend(domain) => {
+                match self.supervisor.restart(&domain).await {
+                    0 => Err(format!("No backends were started for {}", domain)),
+                    restarted => {
+                        info!(domain = %domain, restarted, "Backends restarted");
+                        Ok(format!(
+                            "Restarting {} backend{} for {}",
+                            restarted,
+                            if restarted == 1 { "" } else { "s" },
+                            domain
+                        ))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Remove the socket file, e.g. on shutdown. Pipes go away with the
+    /// process.
+    pub fn cleanup(socket_path: &Path) {
+        let _ = fs::remove_file(socket_path);
+    }
+}
@@ -0,0 +1,147 @@
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+
+use crate::domain::CorsPolicy;
+
+/// A browser asking whether a cross-origin request is allowed.
+pub fn is_preflight(request: &Request) -> bool {
+    request.method() == Method::OPTIONS
+        && request.headers().contains_key(header::ORIGIN)
+        && request
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+/// Answer a preflight from `policy` instead of forwarding it.
+pub fn preflight_response(policy: &CorsPolicy, request: &Request) -> Response {
+    let headers = request.headers();
+    let origin = header_str(headers, header::ORIGIN).unwrap_or_default();
+    let Some(allowed_origin) = policy.allowed_origin(origin) else {
+        return (StatusCode::FORBIDDEN, "CORS origin not allowed").into_response();
+    };
+
+    let mut builder = Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, &allowed_origin)
+        .header(
+            header::ACCESS_CONTROL_ALLOW_METHODS,
+            policy.allow_methods.join(", "),
+        )
+        .header(header::ACCESS_CONTROL_MAX_AGE, policy.max_age)
+        .header(header::VARY, "Origin");
+
+    let requested = header_str(headers, header::ACCESS_CONTROL_REQUEST_HEADERS);
+    if let Some(allowed) = policy.allowed_headers(requested) {
+        builder = builder.header(header::ACCESS_CONTROL_ALLOW_HEADERS, allowed);
+    }
+    if policy.allow_credentials {
+        builder = builder.header(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true");
+    }
+
+    builder
+        .body(Body::empty())
+        .unwrap_or_else(|_| (StatusCode::BAD_REQUEST, "Invalid CORS preflight").into_response())
+}
+
+/// Add CORS headers to a response for a request from `origin`, unless
+/// the backend already set its own.
+pub fn apply_policy(policy: &CorsPolicy, origin: &str, response: &mut Response) {
+    let headers = response.headers_mut();
+    if headers.contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN) {
+        return;
+    }
+    let Some(allowed) = policy
+        .allowed_origin(origin)
+        .and_then(|o| HeaderValue::from_str(&o).ok())
+    else {
+        return;
+    };
+
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allowed);
+    headers.append(header::VARY, HeaderValue::from_static("Origin"));
+    if policy.allow_credentials {
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+            HeaderValue::from_static("true"),
+        );
+    }
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preflight(origin: &str) -> Request {
+        Request::builder()
+            .method(Method::OPTIONS)
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn detects_preflight() {
+        assert!(is_preflight(&preflight("http://localhost:5173")));
+
+        let plain_options = Request::builder()
+            .method(Method::OPTIONS)
+            .body(Body::empty())
+            .unwrap();
+        assert!(!is_preflight(&plain_options));
+    }
+
+    #[test]
+    fn answers_allowed_preflight() {
+        let response =
+            preflight_response(&CorsPolicy::default(), &preflight("http://localhost:5173"));
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let headers = response.headers();
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_HEADERS],
+            "content-type"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+    }
+
+    #[test]
+    fn rejects_unlisted_origin() {
+        let policy = CorsPolicy {
+            allow_origins: vec!["https://web.roxy".into()],
+            ..CorsPolicy::default()
+        };
+        let response = preflight_response(&policy, &preflight("https://evil.example"));
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn backend_headers_win() {
+        let mut response = Response::builder()
+            .header(
+                header::ACCESS_CONTROL_ALLOW_ORIGIN,
+                "https://backend.example",
+            )
+            .body(Body::empty())
+            .unwrap();
+        apply_policy(&CorsPolicy::default(), "https://web.roxy", &mut response);
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://backend.example"
+        );
+
+        let mut response = Response::new(Body::empty());
+        apply_policy(&CorsPolicy::default(), "https://web.roxy", &mut response);
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    }
+}
@@ -1,121 +1,501 @@
-use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use anyhow::Result;
-use simple_dns::rdata::{A, AAAA, RData};
-use simple_dns::{CLASS, Name, Packet, PacketFlag, QTYPE, Question, RCODE, ResourceRecord, TYPE};
+use ipnet::{IpNet, Ipv4Net};
+use simple_dns::rdata::{A, AAAA, CNAME, OPT, RData, TXT};
+use simple_dns::{
+    CLASS, CharacterString, Name, Packet, PacketFlag, QTYPE, Question, RCODE, ResourceRecord, TYPE,
+};
+use socket2::{Domain, Protocol, Socket, Type};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream, UdpSocket};
-use tracing::{debug, info};
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tracing::{debug, error, info};
 
-/// Resolves .roxy domains to the configured LAN IP.
-#[derive(Clone)]
+use crate::domain::{DnsRecords, DomainPattern, DomainRegistration};
+use crate::infrastructure::config::DaemonConfig;
+use crate::infrastructure::dns::UnknownNames;
+
+/// Largest UDP response to a query without EDNS (RFC 1035).
+const MAX_UDP_SIZE: usize = 512;
+
+/// UDP payload size offered over EDNS: small enough to arrive
+/// unfragmented on any network (DNS Flag Day 2020).
+const EDNS_UDP_SIZE: u16 = 1232;
+
+/// How long a TCP connection may sit idle between queries.
+const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How a query arrived, which limits the size of its response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    Udp,
+    Tcp,
+}
+
+/// A network a container VM (or VPN, or VM bridge) queries from, and
+/// the address the host has on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ContainerNetwork {
+    name: String,
+    network: IpNet,
+    host_ip: Ipv4Addr,
+}
+
+impl ContainerNetwork {
+    fn new(name: impl Into<String>, network: IpNet, host_ip: Ipv4Addr) -> Self {
+        Self {
+            name: name.into(),
+            network,
+            host_ip,
+        }
+    }
+}
+
+/// Default VM networks of the common container runtimes. Containers
+/// there reach the host through the runtime's gateway, not the LAN IP.
+/// A network the host's own LAN falls in is left out, since queries
+/// from it come from real machines.
+fn runtime_networks(lan_ip: Ipv4Addr) -> Vec<ContainerNetwork> {
+    [
+        ("Docker Desktop", [192, 168, 65, 0], [192, 168, 65, 254]),
+        ("Colima/Lima", [192, 168, 5, 0], [192, 168, 5, 2]),
+        ("OrbStack", [198, 19, 248, 0], [198, 19, 248, 254]),
+        ("Podman machine", [192, 168, 127, 0], [192, 168, 127, 254]),
+    ]
+    .into_iter()
+    .map(|(name, network, host_ip)| {
+        ContainerNetwork::new(
+            name,
+            Ipv4Net::new_assert(network.into(), 24).into(),
+            host_ip.into(),
+        )
+    })
+    .filter(|n| !n.network.contains(&IpAddr::V4(lan_ip)))
+    .collect()
+}
+
+/// The host's addresses as one asking machine can reach them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostAddrs {
+    pub v4: Ipv4Addr,
+    /// `None` answers AAAA queries with no records, so clients use IPv4.
+    pub v6: Option<Ipv6Addr>,
+}
+
+impl From<Ipv4Addr> for HostAddrs {
+    fn from(v4: Ipv4Addr) -> Self {
+        Self { v4, v6: None }
+    }
+}
+
+/// Resolves .roxy domains to the host address the asking machine can
+/// reach: the runtime gateway for container VMs, the LAN IP otherwise.
 pub struct IpResolver {
-    lan_ip: Ipv4Addr,
+    lan: RwLock<HostAddrs>,
+    configured: RwLock<Vec<ContainerNetwork>>,
+    runtime: RwLock<Vec<ContainerNetwork>>,
+    windows_host: Option<Ipv4Addr>,
 }
 
 impl IpResolver {
-    pub fn new(lan_ip: Ipv4Addr) -> Self {
-        Self { lan_ip }
+    pub fn new(lan_ip: Ipv4Addr, configured: &BTreeMap<String, Ipv4Addr>) -> Self {
+        let resolver = Self {
+            lan: RwLock::new(lan_ip.into()),
+            configured: RwLock::new(Vec::new()),
+            runtime: RwLock::new(runtime_networks(lan_ip)),
+            windows_host: None,
+        };
+        resolver.set_networks(configured);
+        resolver
     }
 
-    /// Returns the LAN IP for all queries.
-    pub fn resolve(&self) -> Ipv4Addr {
-        self.lan_ip
+    /// Running inside WSL2 behind its NAT: queries from `host` come from
+    /// Windows, whose browsers reach the daemon through WSL's localhost
+    /// forwarding rather than the WSL address.
+    pub fn with_windows_host(mut self, host: Option<Ipv4Addr>) -> Self {
+        self.windows_host = host;
+        self
+    }
+
+    /// The host's LAN IPv6 address, for AAAA answers to other machines.
+    pub fn with_ipv6(mut self, lan_ipv6: Option<Ipv6Addr>) -> Self {
+        self.lan.get_mut().unwrap_or_else(|e| e.into_inner()).v6 = lan_ipv6;
+        self
+    }
+
+    /// The host's LAN addresses answers currently carry.
+    pub fn lan(&self) -> HostAddrs {
+        *self.lan.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Answer with `lan` from now on, after the host joined another
+    /// network.
+    pub fn set_lan(&self, lan: HostAddrs) {
+        *self.runtime.write().unwrap_or_else(|e| e.into_inner()) = runtime_networks(lan.v4);
+        *self.lan.write().unwrap_or_else(|e| e.into_inner()) = lan;
+    }
+
+    /// Replace the configured networks. They are checked before anything
+    /// else, the most specific first, so they can override the Windows
+    /// host and the built-in runtimes.
+    pub fn set_networks(&self, configured: &BTreeMap<String, Ipv4Addr>) {
+        let mut networks: Vec<_> = configured
+            .iter()
+            .filter_map(|(network, host_ip)| {
+                // Invalid entries are rejected when the config is validated
+                let network = network.parse().ok()?;
+                Some(ContainerNetwork::new("configured", network, *host_ip))
+            })
+            .collect();
+        networks.sort_by_key(|n| std::cmp::Reverse(n.network.prefix_len()));
+        *self.configured.write().unwrap_or_else(|e| e.into_inner()) = networks;
+    }
+
+    /// The network `source` queries from, if it is not an ordinary LAN
+    /// machine.
+    fn network_of(&self, source: IpAddr) -> Option<ContainerNetwork> {
+        let configured = self.configured.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(network) = configured.iter().find(|n| n.network.contains(&source)) {
+            return Some(network.clone());
+        }
+        let IpAddr::V4(v4) = source else {
+            return None;
+        };
+        if self.windows_host == Some(v4) {
+            return Some(ContainerNetwork::new(
+                "Windows host",
+                source.into(),
+                Ipv4Addr::LOCALHOST,
+            ));
+        }
+        let runtime = self.runtime.read().unwrap_or_else(|e| e.into_inner());
+        runtime
+            .iter()
+            .find(|n| n.network.contains(&source))
+            .cloned()
+    }
+
+    /// The address to answer a query from `source` with.
+    pub fn resolve(&self, source: IpAddr) -> Ipv4Addr {
+        let source = source.to_canonical();
+        match self.network_of(source) {
+            Some(network) => {
+                debug!(source = %source, network = %network.name, "DNS query from container network");
+                network.host_ip
+            }
+            None => self.lan().v4,
+        }
+    }
+
+    /// The IPv6 address to answer a query from `source` with, if any.
+    pub fn resolve_v6(&self, source: IpAddr) -> Option<Ipv6Addr> {
+        let source = source.to_canonical();
+        match self.network_of(source) {
+            // The host is reached on its IPv4 loopback address, so ::1 works too
+            Some(network) if network.host_ip.is_loopback() => Some(Ipv6Addr::LOCALHOST),
+            // Container VMs and configured networks get an IPv4 address only
+            Some(_) => None,
+            None if source.is_loopback() => Some(Ipv6Addr::LOCALHOST),
+            None => self.lan().v6,
+        }
+    }
+
+    /// Both addresses to answer a query from `source` with.
+    pub fn addresses(&self, source: IpAddr) -> HostAddrs {
+        HostAddrs {
+            v4: self.resolve(source),
+            v6: self.resolve_v6(source),
+        }
+    }
+}
+
+/// How queries are answered, beyond the address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Answers {
+    /// Seconds resolvers may cache a record for
+    pub ttl: u32,
+    pub unknown: UnknownNames,
+    /// TXT and CNAME records of every registered domain, most specific
+    /// pattern first.
+    records: Vec<(DomainPattern, DnsRecords)>,
+}
+
+impl Answers {
+    pub fn from_config(daemon: &DaemonConfig) -> Self {
+        Self {
+            ttl: daemon.dns_ttl,
+            unknown: daemon.dns_unknown,
+            records: Vec::new(),
+        }
+    }
+
+    /// Also answer with the records `registrations` configure.
+    pub fn with_records(mut self, registrations: &[DomainRegistration]) -> Self {
+        self.records = registrations
+            .iter()
+            .map(|r| (r.pattern().clone(), r.dns().clone()))
+            .collect();
+        // Same order the HTTP router matches hosts in
+        self.records.sort_by(|(a, _), (b, _)| {
+            b.specificity()
+                .cmp(&a.specificity())
+                .then_with(|| a.is_wildcard().cmp(&b.is_wildcard()))
+        });
+        self
+    }
+
+    /// Records of the domain `name` belongs to, if it's registered.
+    fn records(&self, name: &str) -> Option<&DnsRecords> {
+        self.records
+            .iter()
+            .find(|(pattern, _)| pattern.matches_hostname(name))
+            .map(|(_, records)| records)
+    }
+}
+
+impl Default for Answers {
+    fn default() -> Self {
+        Self::from_config(&DaemonConfig::default())
     }
 }
 
 pub struct DnsServer {
     port: u16,
-    ttl: u32,
+    answers: Arc<RwLock<Arc<Answers>>>,
     ip_resolver: Arc<IpResolver>,
+    udp_v4: UdpSocket,
+    udp_v6: UdpSocket,
+    tcp_v4: TcpListener,
+    tcp_v6: TcpListener,
 }
 
 impl DnsServer {
-    pub fn new(port: u16, lan_ip: Ipv4Addr) -> Self {
-        Self {
+    /// Bind all DNS sockets up front so a port conflict is reported
+    /// before anything else is torn down.
+    pub fn bind(port: u16, ip_resolver: Arc<IpResolver>, answers: Answers) -> Result<Self> {
+        // Bind to all interfaces so Docker containers can reach us directly
+        let ipv4_addr = SocketAddr::from(([0, 0, 0, 0], port));
+        let ipv6_addr = SocketAddr::from((Ipv6Addr::UNSPECIFIED, port));
+
+        let bind = || -> io::Result<_> {
+            Ok((
+                bind_udp(ipv4_addr)?,
+                bind_udp(ipv6_addr)?,
+                bind_tcp(ipv4_addr)?,
+                bind_tcp(ipv6_addr)?,
+            ))
+        };
+        let (udp_v4, udp_v6, tcp_v4, tcp_v6) = bind().map_err(|e| bind_error(port, e))?;
+
+        Ok(Self {
             port,
-            ttl: 1,
-            ip_resolver: Arc::new(IpResolver::new(lan_ip)),
-        }
+            answers: Arc::new(RwLock::new(Arc::new(answers))),
+            ip_resolver,
+            udp_v4,
+            udp_v6,
+            tcp_v4,
+            tcp_v6,
+        })
     }
 
-    pub async fn run(&self) -> Result<()> {
-        // Bind to all interfaces so Docker containers can reach us directly
-        let ipv4_addr = SocketAddr::from(([0, 0, 0, 0], self.port));
-        let ipv6_addr = SocketAddr::from((Ipv6Addr::UNSPECIFIED, self.port));
-
-        // Bind UDP sockets
-        let udp_v4 = UdpSocket::bind(ipv4_addr).await?;
-        let udp_v6 = UdpSocket::bind(ipv6_addr).await?;
-
-        // Bind TCP listeners
-        let tcp_v4 = TcpListener::bind(ipv4_addr).await?;
-        let tcp_v6 = TcpListener::bind(ipv6_addr).await?;
+    /// Serve queries in the background until the returned task is dropped.
+    pub fn spawn(self) -> DnsTask {
+        let port = self.port;
+        let answers = self.answers.clone();
+        let ip_resolver = self.ip_resolver.clone();
+        let handle = tokio::spawn(async move {
+            if let Err(e) = self.run().await {
+                error!(error = %e, "DNS server error");
+            }
+        });
+        DnsTask {
+            port,
+            answers,
+            ip_resolver,
+            handle,
+        }
+    }
 
+    async fn run(self) -> Result<()> {
+        let lan = self.ip_resolver.lan();
         info!(
-            ipv4 = %ipv4_addr,
-            ipv6 = %ipv6_addr,
-            response_ip = %self.ip_resolver.lan_ip,
+            port = self.port,
+            response_ip = %lan.v4,
+            response_ipv6 = ?lan.v6,
             "DNS server listening"
         );
 
-        let ttl = self.ttl;
-        let resolver = self.ip_resolver.clone();
+        let answers = self.answers;
+        let resolver = self.ip_resolver;
 
         tokio::select! {
-            r = serve_udp(udp_v4, ttl, resolver.clone()) => r,
-            r = serve_udp(udp_v6, ttl, resolver.clone()) => r,
-            r = serve_tcp(tcp_v4, ttl, resolver.clone()) => r,
-            r = serve_tcp(tcp_v6, ttl, resolver) => r,
+            r = serve_udp(self.udp_v4, answers.clone(), resolver.clone()) => r,
+            r = serve_udp(self.udp_v6, answers.clone(), resolver.clone()) => r,
+            r = serve_tcp(self.tcp_v4, answers.clone(), resolver.clone()) => r,
+            r = serve_tcp(self.tcp_v6, answers, resolver) => r,
         }
     }
 }
 
-async fn serve_udp(socket: UdpSocket, ttl: u32, resolver: Arc<IpResolver>) -> Result<()> {
-    let mut buf = [0u8; 512]; // Standard DNS UDP size
-    let response_ip = resolver.resolve();
+fn bind_udp(addr: SocketAddr) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    if addr.is_ipv6() {
+        // Linux makes IPv6 sockets dual-stack by default, which would
+        // collide with the IPv4 socket on the same port
+        socket.set_only_v6(true)?;
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+fn bind_tcp(addr: SocketAddr) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// Explain why the DNS port couldn't be bound and what to do about it.
+fn bind_error(port: u16, error: io::Error) -> anyhow::Error {
+    let hint = match error.kind() {
+        io::ErrorKind::PermissionDenied => privileged_port_hint(),
+        io::ErrorKind::AddrInUse if port == 53 && cfg!(target_os = "linux") => {
+            "systemd-resolved usually holds port 53. \
+             Set DNSStubListener=no in /etc/systemd/resolved.conf and restart it."
+                .to_string()
+        }
+        _ => format!("Is another service using it? Try: sudo lsof -i :{}", port),
+    };
+    anyhow::Error::new(error).context(format!("Failed to bind DNS to port {}. {}", port, hint))
+}
+
+#[cfg(target_os = "linux")]
+fn privileged_port_hint() -> String {
+    "Ports below 1024 need root or CAP_NET_BIND_SERVICE. Run with sudo, \
+     or grant the capability: sudo setcap cap_net_bind_service=+ep $(which roxy)"
+        .to_string()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn privileged_port_hint() -> String {
+    "Ports below 1024 need root. Run with sudo.".to_string()
+}
+
+/// A running DNS server. Dropping it stops the server and closes its
+/// sockets.
+pub struct DnsTask {
+    port: u16,
+    answers: Arc<RwLock<Arc<Answers>>>,
+    ip_resolver: Arc<IpResolver>,
+    handle: JoinHandle<()>,
+}
+
+impl DnsTask {
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn ip_resolver(&self) -> &Arc<IpResolver> {
+        &self.ip_resolver
+    }
+
+    /// Answer later queries with `answers`.
+    pub fn set_answers(&self, answers: Answers) {
+        *self.answers.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(answers);
+    }
+}
+
+impl Drop for DnsTask {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+fn current(answers: &RwLock<Arc<Answers>>) -> Arc<Answers> {
+    answers.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+async fn serve_udp(
+    socket: UdpSocket,
+    answers: Arc<RwLock<Arc<Answers>>>,
+    resolver: Arc<IpResolver>,
+) -> Result<()> {
+    let mut buf = [0u8; EDNS_UDP_SIZE as usize];
 
     loop {
         let (len, addr) = socket.recv_from(&mut buf).await?;
-        let response = handle_query(&buf[..len], ttl, response_ip);
+        let response = handle_query(
+            &buf[..len],
+            &current(&answers),
+            resolver.addresses(addr.ip()),
+            Transport::Udp,
+        );
         let _ = socket.send_to(&response, addr).await;
     }
 }
 
-async fn serve_tcp(listener: TcpListener, ttl: u32, resolver: Arc<IpResolver>) -> Result<()> {
-    let response_ip = resolver.resolve();
-
+async fn serve_tcp(
+    listener: TcpListener,
+    answers: Arc<RwLock<Arc<Answers>>>,
+    resolver: Arc<IpResolver>,
+) -> Result<()> {
     loop {
-        let (stream, _addr) = listener.accept().await?;
-        tokio::spawn(handle_tcp_connection(stream, ttl, response_ip));
+        let (stream, addr) = listener.accept().await?;
+        tokio::spawn(handle_tcp_connection(
+            stream,
+            current(&answers),
+            resolver.addresses(addr.ip()),
+        ));
     }
 }
 
 async fn handle_tcp_connection(
     mut stream: TcpStream,
-    ttl: u32,
-    response_ip: Ipv4Addr,
+    answers: Arc<Answers>,
+    host: HostAddrs,
 ) -> Result<()> {
-    // TCP DNS uses 2-byte length prefix
-    let mut len_buf = [0u8; 2];
-    stream.read_exact(&mut len_buf).await?;
-    let len = u16::from_be_bytes(len_buf) as usize;
-
-    let mut query_buf = vec![0u8; len];
-    stream.read_exact(&mut query_buf).await?;
+    // Resolvers retrying a truncated answer may send several queries
+    // over one connection (RFC 7766)
+    loop {
+        // TCP DNS uses 2-byte length prefix
+        let mut len_buf = [0u8; 2];
+        match timeout(TCP_IDLE_TIMEOUT, stream.read_exact(&mut len_buf)).await {
+            Ok(Ok(_)) => {}
+            // Closed or idle: done
+            Ok(Err(_)) | Err(_) => return Ok(()),
+        }
+        let len = u16::from_be_bytes(len_buf) as usize;
 
-    let response = handle_query(&query_buf, ttl, response_ip);
+        let mut query_buf = vec![0u8; len];
+        stream.read_exact(&mut query_buf).await?;
 
-    // Send response with length prefix
-    let resp_len = (response.len() as u16).to_be_bytes();
-    stream.write_all(&resp_len).await?;
-    stream.write_all(&response).await?;
+        let response = handle_query(&query_buf, &answers, host, Transport::Tcp);
 
-    Ok(())
+        // Send response with length prefix
+        let resp_len = (response.len() as u16).to_be_bytes();
+        stream.write_all(&resp_len).await?;
+        stream.write_all(&response).await?;
+    }
 }
 
-fn handle_query(query: &[u8], ttl: u32, response_ip: Ipv4Addr) -> Vec<u8> {
+fn handle_query(
+    query: &[u8],
+    answers: &Answers,
+    response: HostAddrs,
+    transport: Transport,
+) -> Vec<u8> {
     // Parse incoming query
     let packet = match Packet::parse(query) {
         Ok(p) => p,
@@ -127,28 +507,77 @@ fn handle_query(query: &[u8], ttl: u32, response_ip: Ipv4Addr) -> Vec<u8> {
         None => return build_format_error(query),
     };
 
+    // Only EDNS version 0 exists
+    if packet.opt().is_some_and(|opt| opt.version > 0) {
+        return build_badvers_response(&packet);
+    }
+
+    let reply = answer_query(&packet, question, answers, response);
+    match transport {
+        Transport::Udp => fit_udp(&packet, reply),
+        Transport::Tcp => reply,
+    }
+}
+
+fn answer_query(
+    packet: &Packet,
+    question: &Question,
+    answers: &Answers,
+    response: HostAddrs,
+) -> Vec<u8> {
     let domain = question.qname.to_string().to_lowercase();
+    let domain = domain.trim_end_matches('.');
 
     // Check if domain ends with .roxy
-    if !domain.trim_end_matches('.').ends_with(".roxy") {
-        debug!(domain = %domain, "DNS refused (not .roxy)");
-        return build_refused_response(&packet);
+    if !domain.ends_with(".roxy") {
+        return match answers.unknown {
+            UnknownNames::Refused => {
+                debug!(domain = %domain, "DNS refused (not .roxy)");
+                build_refused_response(packet)
+            }
+            UnknownNames::NxDomain => {
+                debug!(domain = %domain, "DNS NXDOMAIN (not .roxy)");
+                build_nxdomain_response(packet)
+            }
+        };
     }
 
     info!(
         domain = %domain,
         qtype = ?question.qtype,
-        response = %response_ip,
+        response = %response.v4,
         "DNS query"
     );
 
+    let name = question.qname.to_string();
+    let ttl = answers.ttl;
+    let records = answers.records(domain);
+
+    // An alias stands in for every other record of the name
+    if let Some(target) = records.and_then(|r| r.cname.as_deref()) {
+        let mut records = vec![cname_record(&name, ttl, target)];
+        // Save the resolver a second query when the alias is ours too
+        let target_name = target.trim_end_matches('.').to_lowercase();
+        if target_name.ends_with(".roxy")
+            && answers
+                .records(&target_name)
+                .is_none_or(|r| r.cname.is_none())
+        {
+            records.extend(address_records(question.qtype, target, ttl, response));
+        }
+        return build_answer(packet, question, records);
+    }
+
     // Build response based on query type
-    match question.qtype {
-        QTYPE::TYPE(TYPE::A) => build_a_response(&packet, question, ttl, response_ip),
-        QTYPE::TYPE(TYPE::AAAA) => build_aaaa_response(&packet, question, ttl),
-        QTYPE::ANY => build_any_response(&packet, question, ttl, response_ip),
-        _ => build_empty_response(&packet),
+    let txt = records.map_or(&[][..], |r| &r.txt);
+    let mut records = address_records(question.qtype, &name, ttl, response);
+    if matches!(question.qtype, QTYPE::TYPE(TYPE::TXT) | QTYPE::ANY) {
+        records.extend(txt.iter().filter_map(|value| txt_record(&name, ttl, value)));
     }
+    if records.is_empty() {
+        return build_empty_response(packet);
+    }
+    build_answer(packet, question, records)
 }
 
 fn build_format_error(query: &[u8]) -> Vec<u8> {
@@ -163,54 +592,100 @@ fn build_format_error(query: &[u8]) -> Vec<u8> {
     response.set_flags(PacketFlag::RESPONSE | PacketFlag::RECURSION_DESIRED);
     *response.rcode_mut() = RCODE::FormatError;
 
-    response.build_bytes_vec().unwrap_or_default()
+    response.build_bytes_vec_compressed().unwrap_or_default()
 }
 
-fn build_refused_response(packet: &Packet) -> Vec<u8> {
+/// A reply to `packet`, carrying an OPT record if the query had one
+/// (RFC 6891).
+fn reply_to(packet: &Packet) -> Packet<'static> {
     let mut response = Packet::new_reply(packet.id());
+    if packet.opt().is_some() {
+        *response.opt_mut() = Some(OPT {
+            opt_codes: Vec::new(),
+            udp_packet_size: EDNS_UDP_SIZE,
+            version: 0,
+        });
+    }
+    response
+}
+
+/// The UDP response size `query` allows: 512 bytes, or what it offers
+/// over EDNS up to the size we offer.
+fn udp_limit(query: &Packet) -> usize {
+    query.opt().map_or(MAX_UDP_SIZE, |opt| {
+        usize::from(opt.udp_packet_size).clamp(MAX_UDP_SIZE, usize::from(EDNS_UDP_SIZE))
+    })
+}
+
+/// Fit `response` in a UDP datagram. One that's too big goes out as
+/// its header and question alone with TC set, which tells the resolver
+/// to ask again over TCP.
+fn fit_udp(query: &Packet, response: Vec<u8>) -> Vec<u8> {
+    if response.len() <= udp_limit(query) {
+        return response;
+    }
+    let Ok(full) = Packet::parse(&response) else {
+        return response;
+    };
+
+    let mut truncated = reply_to(query);
+    truncated.set_flags(
+        PacketFlag::RESPONSE
+            | PacketFlag::AUTHORITATIVE_ANSWER
+            | PacketFlag::RECURSION_DESIRED
+            | PacketFlag::TRUNCATION,
+    );
+    *truncated.rcode_mut() = full.rcode();
+    truncated.questions = full.questions.clone();
+    debug!(size = response.len(), "DNS response truncated");
+    truncated.build_bytes_vec_compressed().unwrap_or(response)
+}
+
+fn build_badvers_response(packet: &Packet) -> Vec<u8> {
+    let mut response = reply_to(packet);
+    response.set_flags(PacketFlag::RESPONSE | PacketFlag::RECURSION_DESIRED);
+    *response.rcode_mut() = RCODE::BADVERS;
+
+    response.build_bytes_vec_compressed().unwrap_or_default()
+}
+
+fn build_refused_response(packet: &Packet) -> Vec<u8> {
+    let mut response = reply_to(packet);
     response.set_flags(PacketFlag::RESPONSE | PacketFlag::RECURSION_DESIRED);
     *response.rcode_mut() = RCODE::Refused;
 
-    response.build_bytes_vec().unwrap_or_default()
+    response.build_bytes_vec_compressed().unwrap_or_default()
 }
 
-fn build_empty_response(packet: &Packet) -> Vec<u8> {
-    let mut response = Packet::new_reply(packet.id());
-    response.set_flags(
-        PacketFlag::RESPONSE | PacketFlag::AUTHORITATIVE_ANSWER | PacketFlag::RECURSION_DESIRED,
-    );
-    *response.rcode_mut() = RCODE::NoError;
+fn build_nxdomain_response(packet: &Packet) -> Vec<u8> {
+    let mut response = reply_to(packet);
+    response.set_flags(PacketFlag::RESPONSE | PacketFlag::RECURSION_DESIRED);
+    *response.rcode_mut() = RCODE::NameError;
 
-    // Clone questions into the response
     for q in &packet.questions {
         response.questions.push(q.clone());
     }
 
-    response.build_bytes_vec().unwrap_or_default()
+    response.build_bytes_vec_compressed().unwrap_or_default()
 }
 
-fn build_a_response(packet: &Packet, question: &Question, ttl: u32, ip: Ipv4Addr) -> Vec<u8> {
-    let mut response = Packet::new_reply(packet.id());
+fn build_empty_response(packet: &Packet) -> Vec<u8> {
+    let mut response = reply_to(packet);
     response.set_flags(
         PacketFlag::RESPONSE | PacketFlag::AUTHORITATIVE_ANSWER | PacketFlag::RECURSION_DESIRED,
     );
     *response.rcode_mut() = RCODE::NoError;
 
-    // Add the question
-    response.questions.push(question.clone());
-
-    // Add A record
-    let name_str = question.qname.to_string();
-    let name = Name::new_unchecked(&name_str);
-    let a_record = A::from(ip);
-    let record = ResourceRecord::new(name, CLASS::IN, ttl, RData::A(a_record));
-    response.answers.push(record);
+    // Clone questions into the response
+    for q in &packet.questions {
+        response.questions.push(q.clone());
+    }
 
-    response.build_bytes_vec().unwrap_or_default()
+    response.build_bytes_vec_compressed().unwrap_or_default()
 }
 
-fn build_aaaa_response(packet: &Packet, question: &Question, ttl: u32) -> Vec<u8> {
-    let mut response = Packet::new_reply(packet.id());
+fn build_answer(packet: &Packet, question: &Question, records: Vec<ResourceRecord>) -> Vec<u8> {
+    let mut response = reply_to(packet);
     response.set_flags(
         PacketFlag::RESPONSE | PacketFlag::AUTHORITATIVE_ANSWER | PacketFlag::RECURSION_DESIRED,
     );
@@ -218,65 +693,202 @@ fn build_aaaa_response(packet: &Packet, question: &Question, ttl: u32) -> Vec<u8
 
     // Add the question
     response.questions.push(question.clone());
+    response.answers = records;
 
-    // Add AAAA record: ::1
-    let name_str = question.qname.to_string();
-    let name = Name::new_unchecked(&name_str);
-    let aaaa_record = AAAA::from(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1));
-    let record = ResourceRecord::new(name, CLASS::IN, ttl, RData::AAAA(aaaa_record));
-    response.answers.push(record);
-
-    response.build_bytes_vec().unwrap_or_default()
+    response.build_bytes_vec_compressed().unwrap_or_default()
 }
 
-fn build_any_response(packet: &Packet, question: &Question, ttl: u32, ip: Ipv4Addr) -> Vec<u8> {
-    let mut response = Packet::new_reply(packet.id());
-    response.set_flags(
-        PacketFlag::RESPONSE | PacketFlag::AUTHORITATIVE_ANSWER | PacketFlag::RECURSION_DESIRED,
-    );
-    *response.rcode_mut() = RCODE::NoError;
-
-    // Add the question
-    response.questions.push(question.clone());
+/// The A and AAAA records of `name` that a `qtype` query asks for.
+fn address_records(qtype: QTYPE, name: &str, ttl: u32, host: HostAddrs) -> Vec<ResourceRecord<'_>> {
+    let a = || {
+        ResourceRecord::new(
+            Name::new_unchecked(name),
+            CLASS::IN,
+            ttl,
+            RData::A(A::from(host.v4)),
+        )
+    };
+    let aaaa = |ip: Ipv6Addr| {
+        ResourceRecord::new(
+            Name::new_unchecked(name),
+            CLASS::IN,
+            ttl,
+            RData::AAAA(AAAA::from(ip)),
+        )
+    };
+    match qtype {
+        QTYPE::TYPE(TYPE::A) => vec![a()],
+        QTYPE::TYPE(TYPE::AAAA) => host.v6.map(aaaa).into_iter().collect(),
+        QTYPE::ANY => std::iter::once(a()).chain(host.v6.map(aaaa)).collect(),
+        _ => Vec::new(),
+    }
+}
 
-    // Add A record
-    let name_str = question.qname.to_string();
-    let name_a = Name::new_unchecked(&name_str);
-    let a_record = A::from(ip);
-    response.answers.push(ResourceRecord::new(
-        name_a,
+fn cname_record<'a>(name: &'a str, ttl: u32, target: &'a str) -> ResourceRecord<'a> {
+    let target = CNAME(Name::new_unchecked(target.trim_end_matches('.')));
+    ResourceRecord::new(
+        Name::new_unchecked(name),
         CLASS::IN,
         ttl,
-        RData::A(a_record),
-    ));
-
-    // Add AAAA record
-    let name_aaaa = Name::new_unchecked(&name_str);
-    let aaaa_record = AAAA::from(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1));
-    response.answers.push(ResourceRecord::new(
-        name_aaaa,
+        RData::CNAME(target),
+    )
+}
+
+/// A TXT record holding `value`, split into strings of at most 255
+/// bytes as the format requires.
+fn txt_record<'a>(name: &'a str, ttl: u32, value: &'a str) -> Option<ResourceRecord<'a>> {
+    let mut txt = TXT::new();
+    if value.is_empty() {
+        txt.add_char_string(CharacterString::new(b"").ok()?);
+    }
+    for chunk in value.as_bytes().chunks(255) {
+        txt.add_char_string(CharacterString::new(chunk).ok()?);
+    }
+    Some(ResourceRecord::new(
+        Name::new_unchecked(name),
         CLASS::IN,
         ttl,
-        RData::AAAA(aaaa_record),
-    ));
-
-    response.build_bytes_vec().unwrap_or_default()
+        RData::TXT(txt),
+    ))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::{DomainName, PathPrefix, ProxyTarget, Route, RouteTarget};
 
     const TEST_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 100);
+    const TEST_IPV6: Ipv6Addr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x100);
 
     fn test_resolver() -> IpResolver {
-        IpResolver::new(TEST_IP)
+        IpResolver::new(TEST_IP, &BTreeMap::new())
     }
 
     #[test]
     fn test_ip_resolver_returns_configured_ip() {
         let resolver = test_resolver();
-        assert_eq!(resolver.resolve(), TEST_IP);
+        assert_eq!(resolver.resolve("127.0.0.1".parse().unwrap()), TEST_IP);
+        assert_eq!(resolver.resolve("192.168.1.20".parse().unwrap()), TEST_IP);
+        assert_eq!(resolver.resolve("::1".parse().unwrap()), TEST_IP);
+    }
+
+    #[test]
+    fn test_ip_resolver_answers_runtime_gateway() {
+        let resolver = test_resolver();
+        let cases = [
+            ("192.168.65.3", Ipv4Addr::new(192, 168, 65, 254)),
+            ("192.168.5.15", Ipv4Addr::new(192, 168, 5, 2)),
+            ("198.19.248.2", Ipv4Addr::new(198, 19, 248, 254)),
+            ("::ffff:192.168.127.2", Ipv4Addr::new(192, 168, 127, 254)),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(resolver.resolve(source.parse().unwrap()), expected);
+        }
+    }
+
+    #[test]
+    fn test_ip_resolver_answers_ipv6_by_source() {
+        let resolver = test_resolver().with_ipv6(Some(TEST_IPV6));
+        let cases = [
+            ("192.168.1.20", Some(TEST_IPV6)),
+            ("fd00::20", Some(TEST_IPV6)),
+            ("127.0.0.1", Some(Ipv6Addr::LOCALHOST)),
+            ("::1", Some(Ipv6Addr::LOCALHOST)),
+            ("192.168.65.3", None),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(resolver.resolve_v6(source.parse().unwrap()), expected);
+        }
+
+        let resolver = test_resolver();
+        assert_eq!(resolver.resolve_v6("192.168.1.20".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn test_ip_resolver_ignores_runtime_network_on_lan() {
+        let resolver = IpResolver::new(Ipv4Addr::new(192, 168, 5, 40), &BTreeMap::new());
+        assert_eq!(
+            resolver.resolve("192.168.5.15".parse().unwrap()),
+            Ipv4Addr::new(192, 168, 5, 40)
+        );
+    }
+
+    #[test]
+    fn test_ip_resolver_follows_network_changes() {
+        let resolver = test_resolver().with_ipv6(Some(TEST_IPV6));
+        let home = Ipv4Addr::new(192, 168, 5, 40);
+        resolver.set_lan(home.into());
+
+        assert_eq!(resolver.lan(), HostAddrs { v4: home, v6: None });
+        assert_eq!(resolver.resolve("192.168.1.20".parse().unwrap()), home);
+        assert_eq!(resolver.resolve_v6("192.168.1.20".parse().unwrap()), None);
+        // The new LAN overlaps the Colima network, which is now ignored
+        assert_eq!(resolver.resolve("192.168.5.15".parse().unwrap()), home);
+
+        resolver.set_lan(TEST_IP.into());
+        assert_eq!(
+            resolver.resolve("192.168.5.15".parse().unwrap()),
+            Ipv4Addr::new(192, 168, 5, 2)
+        );
+    }
+
+    #[test]
+    fn test_ip_resolver_answers_windows_host_with_loopback() {
+        let windows = Ipv4Addr::new(172, 20, 0, 1);
+        let resolver = test_resolver().with_windows_host(Some(windows));
+        assert_eq!(resolver.resolve(windows.into()), Ipv4Addr::LOCALHOST);
+        assert_eq!(resolver.resolve("172.20.0.5".parse().unwrap()), TEST_IP);
+    }
+
+    #[test]
+    fn test_ip_resolver_prefers_configured_networks() {
+        let resolver = test_resolver();
+        resolver.set_networks(&BTreeMap::from([
+            ("192.168.5.0/24".to_string(), Ipv4Addr::new(192, 168, 5, 1)),
+            ("10.0.2.0/24".to_string(), Ipv4Addr::new(10, 0, 2, 2)),
+        ]));
+        assert_eq!(
+            resolver.resolve("192.168.5.15".parse().unwrap()),
+            Ipv4Addr::new(192, 168, 5, 1)
+        );
+        assert_eq!(
+            resolver.resolve("10.0.2.15".parse().unwrap()),
+            Ipv4Addr::new(10, 0, 2, 2)
+        );
+    }
+
+    #[test]
+    fn test_ip_resolver_configured_networks_take_precedence() {
+        let windows = Ipv4Addr::new(172, 20, 0, 1);
+        let resolver = test_resolver()
+            .with_windows_host(Some(windows))
+            .with_ipv6(Some(TEST_IPV6));
+        resolver.set_networks(&BTreeMap::from([
+            ("10.8.0.0/16".to_string(), Ipv4Addr::new(10, 8, 0, 1)),
+            ("10.8.4.0/24".to_string(), Ipv4Addr::new(10, 8, 4, 1)),
+            ("fd42::/64".to_string(), Ipv4Addr::new(10, 8, 0, 1)),
+            ("172.20.0.0/16".to_string(), Ipv4Addr::new(172, 20, 0, 2)),
+        ]));
+
+        // The most specific network wins, whatever the key order
+        let cases = [
+            ("10.8.4.7", Ipv4Addr::new(10, 8, 4, 1)),
+            ("10.8.9.7", Ipv4Addr::new(10, 8, 0, 1)),
+            ("fd42::7", Ipv4Addr::new(10, 8, 0, 1)),
+            ("172.20.0.1", Ipv4Addr::new(172, 20, 0, 2)),
+            ("fd00::20", TEST_IP),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(resolver.resolve(source.parse().unwrap()), expected);
+        }
+
+        // Configured networks get an IPv4 answer only
+        assert_eq!(resolver.resolve_v6("fd42::7".parse().unwrap()), None);
+        assert_eq!(resolver.resolve_v6(windows.into()), None);
+        assert_eq!(
+            resolver.resolve_v6("fd00::20".parse().unwrap()),
+            Some(TEST_IPV6)
+        );
     }
 
     #[test]
@@ -288,7 +900,7 @@ mod tests {
         packet.questions.push(question);
 
         let query = packet.build_bytes_vec().unwrap();
-        let response = handle_query(&query, 1, TEST_IP);
+        let response = handle_query(&query, &Answers::default(), TEST_IP.into(), Transport::Udp);
 
         let parsed = Packet::parse(&response).unwrap();
         assert_eq!(parsed.rcode(), RCODE::NoError);
@@ -304,13 +916,234 @@ mod tests {
         packet.questions.push(question);
 
         let query = packet.build_bytes_vec().unwrap();
-        let response = handle_query(&query, 1, TEST_IP);
+        let response = handle_query(&query, &Answers::default(), TEST_IP.into(), Transport::Udp);
 
         let parsed = Packet::parse(&response).unwrap();
         assert_eq!(parsed.rcode(), RCODE::Refused);
         assert_eq!(parsed.answers.len(), 0);
     }
 
+    #[test]
+    fn test_query_handler_non_roxy_domain_nxdomain() {
+        let mut packet = Packet::new_query(1234);
+        let name = Name::new_unchecked("google.com");
+        let question = Question::new(name, TYPE::A.into(), CLASS::IN.into(), false);
+        packet.questions.push(question);
+
+        let query = packet.build_bytes_vec().unwrap();
+        let answers = Answers {
+            unknown: UnknownNames::NxDomain,
+            ..Answers::default()
+        };
+        let response = handle_query(&query, &answers, TEST_IP.into(), Transport::Udp);
+
+        let parsed = Packet::parse(&response).unwrap();
+        assert_eq!(parsed.rcode(), RCODE::NameError);
+        assert_eq!(parsed.questions.len(), 1);
+        assert_eq!(parsed.answers.len(), 0);
+    }
+
+    #[test]
+    fn test_query_handler_uses_configured_ttl() {
+        let mut packet = Packet::new_query(4321);
+        let name = Name::new_unchecked("test.roxy");
+        let question = Question::new(name, TYPE::A.into(), CLASS::IN.into(), false);
+        packet.questions.push(question);
+
+        let query = packet.build_bytes_vec().unwrap();
+        let answers = Answers {
+            ttl: 300,
+            ..Answers::default()
+        };
+        let response = handle_query(&query, &answers, TEST_IP.into(), Transport::Udp);
+
+        let parsed = Packet::parse(&response).unwrap();
+        assert_eq!(parsed.answers[0].ttl, 300);
+    }
+
+    fn query(name: &str, qtype: TYPE) -> Vec<u8> {
+        let mut packet = Packet::new_query(2468);
+        let question = Question::new(
+            Name::new_unchecked(name),
+            qtype.into(),
+            CLASS::IN.into(),
+            false,
+        );
+        packet.questions.push(question);
+        packet.build_bytes_vec().unwrap()
+    }
+
+    fn answers_with(domain: &str, dns: DnsRecords) -> Answers {
+        let route = Route::new(
+            PathPrefix::new("/").unwrap(),
+            RouteTarget::Proxy(ProxyTarget::parse("3000").unwrap()),
+        );
+        let pattern = DomainPattern::Exact(DomainName::new(domain).unwrap());
+        let mut registration = DomainRegistration::new(pattern, vec![route]);
+        registration.set_dns(dns);
+        Answers::default().with_records(&[registration])
+    }
+
+    #[test]
+    fn test_query_handler_txt() {
+        let long = "v".repeat(300);
+        let answers = answers_with(
+            "verify.roxy",
+            DnsRecords {
+                txt: vec!["site-verification=abc".to_string(), long.clone()],
+                ..DnsRecords::default()
+            },
+        );
+
+        let response = handle_query(
+            &query("verify.roxy", TYPE::TXT),
+            &answers,
+            TEST_IP.into(),
+            Transport::Udp,
+        );
+        let parsed = Packet::parse(&response).unwrap();
+        assert_eq!(parsed.rcode(), RCODE::NoError);
+        assert_eq!(parsed.answers.len(), 2);
+        let RData::TXT(txt) = &parsed.answers[1].rdata else {
+            panic!("Expected TXT record");
+        };
+        let value: Vec<u8> = txt.iter_raw().flat_map(|(key, _)| key.to_vec()).collect();
+        assert_eq!(value, long.as_bytes());
+
+        // Domains without TXT values answer empty
+        let response = handle_query(
+            &query("other.roxy", TYPE::TXT),
+            &answers,
+            TEST_IP.into(),
+            Transport::Udp,
+        );
+        let parsed = Packet::parse(&response).unwrap();
+        assert_eq!(parsed.rcode(), RCODE::NoError);
+        assert!(parsed.answers.is_empty());
+    }
+
+    fn edns_query(name: &str, qtype: TYPE, udp_packet_size: u16, version: u8) -> Vec<u8> {
+        let plain = query(name, qtype);
+        let mut packet = Packet::parse(&plain).unwrap();
+        *packet.opt_mut() = Some(OPT {
+            opt_codes: Vec::new(),
+            udp_packet_size,
+            version,
+        });
+        packet.build_bytes_vec().unwrap()
+    }
+
+    #[test]
+    fn test_oversized_udp_response_is_truncated() {
+        let answers = answers_with(
+            "big.roxy",
+            DnsRecords {
+                txt: (0..4).map(|i| format!("{i}{}", "x".repeat(200))).collect(),
+                ..DnsRecords::default()
+            },
+        );
+
+        let plain = query("big.roxy", TYPE::TXT);
+        let response = handle_query(&plain, &answers, TEST_IP.into(), Transport::Udp);
+        assert!(response.len() <= MAX_UDP_SIZE);
+        let parsed = Packet::parse(&response).unwrap();
+        assert!(parsed.has_flags(PacketFlag::TRUNCATION));
+        assert_eq!(parsed.questions.len(), 1);
+        assert!(parsed.answers.is_empty());
+
+        // TCP has room for everything
+        let response = handle_query(&plain, &answers, TEST_IP.into(), Transport::Tcp);
+        let parsed = Packet::parse(&response).unwrap();
+        assert!(!parsed.has_flags(PacketFlag::TRUNCATION));
+        assert_eq!(parsed.answers.len(), 4);
+
+        // So does UDP with EDNS, which the reply echoes
+        let edns = edns_query("big.roxy", TYPE::TXT, 4096, 0);
+        let response = handle_query(&edns, &answers, TEST_IP.into(), Transport::Udp);
+        let parsed = Packet::parse(&response).unwrap();
+        assert!(!parsed.has_flags(PacketFlag::TRUNCATION));
+        assert_eq!(parsed.answers.len(), 4);
+        assert_eq!(parsed.opt().unwrap().udp_packet_size, EDNS_UDP_SIZE);
+    }
+
+    #[test]
+    fn test_unknown_edns_version_is_rejected() {
+        let query = edns_query("test.roxy", TYPE::A, 1232, 1);
+        let response = handle_query(&query, &Answers::default(), TEST_IP.into(), Transport::Udp);
+        let parsed = Packet::parse(&response).unwrap();
+        assert_eq!(parsed.rcode(), RCODE::BADVERS);
+        assert!(parsed.answers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_connection_answers_several_queries() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            handle_tcp_connection(stream, Arc::new(Answers::default()), TEST_IP.into()).await
+        });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        for name in ["one.roxy", "two.roxy"] {
+            let query = query(name, TYPE::A);
+            stream
+                .write_all(&(query.len() as u16).to_be_bytes())
+                .await
+                .unwrap();
+            stream.write_all(&query).await.unwrap();
+
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len).await.unwrap();
+            let mut response = vec![0u8; u16::from_be_bytes(len) as usize];
+            stream.read_exact(&mut response).await.unwrap();
+            let parsed = Packet::parse(&response).unwrap();
+            assert_eq!(parsed.answers[0].name.to_string(), name);
+        }
+    }
+
+    #[test]
+    fn test_query_handler_cname() {
+        let answers = answers_with(
+            "docs.roxy",
+            DnsRecords {
+                cname: Some("docs.example.com".to_string()),
+                ..DnsRecords::default()
+            },
+        );
+        let response = handle_query(
+            &query("docs.roxy", TYPE::A),
+            &answers,
+            TEST_IP.into(),
+            Transport::Udp,
+        );
+        let parsed = Packet::parse(&response).unwrap();
+        assert_eq!(parsed.answers.len(), 1);
+        let RData::CNAME(target) = &parsed.answers[0].rdata else {
+            panic!("Expected CNAME record");
+        };
+        assert_eq!(target.0.to_string(), "docs.example.com");
+
+        // An alias of another .roxy name comes with its address
+        let answers = answers_with(
+            "old.roxy",
+            DnsRecords {
+                cname: Some("new.roxy".to_string()),
+                ..DnsRecords::default()
+            },
+        );
+        let response = handle_query(
+            &query("old.roxy", TYPE::A),
+            &answers,
+            TEST_IP.into(),
+            Transport::Udp,
+        );
+        let parsed = Packet::parse(&response).unwrap();
+        assert_eq!(parsed.answers.len(), 2);
+        assert_eq!(parsed.answers[1].name.to_string(), "new.roxy");
+        assert_eq!(parsed.answers[1].rdata, RData::A(A::from(TEST_IP)));
+    }
+
     #[test]
     fn test_query_handler_aaaa() {
         let mut packet = Packet::new_query(5678);
@@ -319,11 +1152,22 @@ mod tests {
         packet.questions.push(question);
 
         let query = packet.build_bytes_vec().unwrap();
-        let response = handle_query(&query, 1, TEST_IP);
+        let host = HostAddrs {
+            v4: TEST_IP,
+            v6: Some(TEST_IPV6),
+        };
+        let response = handle_query(&query, &Answers::default(), host, Transport::Udp);
 
         let parsed = Packet::parse(&response).unwrap();
         assert_eq!(parsed.rcode(), RCODE::NoError);
         assert_eq!(parsed.answers.len(), 1);
+        assert_eq!(parsed.answers[0].rdata, RData::AAAA(AAAA::from(TEST_IPV6)));
+
+        // Without an IPv6 address the name exists but has no AAAA records
+        let response = handle_query(&query, &Answers::default(), TEST_IP.into(), Transport::Udp);
+        let parsed = Packet::parse(&response).unwrap();
+        assert_eq!(parsed.rcode(), RCODE::NoError);
+        assert!(parsed.answers.is_empty());
     }
 
     #[test]
@@ -335,7 +1179,7 @@ mod tests {
         packet.questions.push(question);
 
         let query = packet.build_bytes_vec().unwrap();
-        let response = handle_query(&query, 1, TEST_IP);
+        let response = handle_query(&query, &Answers::default(), TEST_IP.into(), Transport::Udp);
 
         let parsed = Packet::parse(&response).unwrap();
         assert_eq!(parsed.rcode(), RCODE::NoError);
@@ -351,7 +1195,12 @@ mod tests {
 
         let query = packet.build_bytes_vec().unwrap();
         let custom_ip = Ipv4Addr::new(10, 0, 0, 50);
-        let response = handle_query(&query, 1, custom_ip);
+        let response = handle_query(
+            &query,
+            &Answers::default(),
+            custom_ip.into(),
+            Transport::Udp,
+        );
 
         let parsed = Packet::parse(&response).unwrap();
         assert_eq!(parsed.rcode(), RCODE::NoError);
@@ -364,4 +1213,15 @@ mod tests {
             panic!("Expected A record");
         }
     }
+
+    #[tokio::test]
+    async fn test_bind_fails_when_port_is_taken() {
+        let taken = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+        let port = taken.local_addr().unwrap().port();
+
+        let err = DnsServer::bind(port, Arc::new(test_resolver()), Answers::default())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains(&port.to_string()));
+    }
 }
@@ -0,0 +1,115 @@
+//! File system notifications for the daemon's watchers, settled so an
+//! editor's or a bulk import's burst of writes wakes them once.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+/// Watches a set of directories, not their subdirectories. Directories
+/// are watched rather than files so that files replaced by a rename,
+/// as editors and atomic writes do, are still seen.
+pub struct DirWatcher {
+    watcher: RecommendedWatcher,
+    watched: BTreeSet<PathBuf>,
+    changes: mpsc::UnboundedReceiver<()>,
+}
+
+impl DirWatcher {
+    pub fn new() -> notify::Result<Self> {
+        let (tx, changes) = mpsc::unbounded_channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            // Reading a file to see what changed is no change itself
+            if let Ok(event) = event
+                && !event.kind.is_access()
+            {
+                let _ = tx.send(());
+            }
+        })?;
+        Ok(Self {
+            watcher,
+            watched: BTreeSet::new(),
+            changes,
+        })
+    }
+
+    /// Watch `dirs`, and nothing else. Directories that don't exist yet
+    /// are left out; call again once they might.
+    pub fn watch(&mut self, dirs: impl IntoIterator<Item = PathBuf>) {
+        let wanted: BTreeSet<PathBuf> = dirs.into_iter().filter(|dir| dir.is_dir()).collect();
+        for dir in self.watched.difference(&wanted) {
+            let _ = self.watcher.unwatch(dir);
+        }
+        for dir in wanted.difference(&self.watched) {
+            if let Err(e) = self.watcher.watch(dir, RecursiveMode::NonRecursive) {
+                debug!(dir = %dir.display(), error = %e, "Failed to watch directory");
+            }
+        }
+        self.watched = wanted;
+    }
+
+    /// Wait for a change, then until `settle` passes without another.
+    /// Returns `false` once the watcher has stopped.
+    pub async fn changed(&mut self, settle: Duration) -> bool {
+        if self.changes.recv().await.is_none() {
+            return false;
+        }
+        loop {
+            match tokio::time::timeout(settle, self.changes.recv()).await {
+                Err(_) => return true,
+                Ok(Some(())) => continue,
+                Ok(None) => return false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const SETTLE: Duration = Duration::from_millis(300);
+
+    #[tokio::test]
+    async fn settles_once_writes_stop() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        let mut watcher = DirWatcher::new().unwrap();
+        watcher.watch([tmp.path().to_path_buf()]);
+
+        let writer = {
+            let path = path.clone();
+            tokio::spawn(async move {
+                for i in 0..3 {
+                    fs::write(&path, format!("version {}", i)).unwrap();
+                    tokio::time::sleep(SETTLE / 3).await;
+                }
+            })
+        };
+        assert!(watcher.changed(SETTLE).await);
+        assert!(writer.is_finished());
+        writer.await.unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "version 2");
+    }
+
+    #[tokio::test]
+    async fn picks_up_directories_created_later() {
+        let tmp = tempfile::tempdir().unwrap();
+        let certs = tmp.path().join("certs");
+        let mut watcher = DirWatcher::new().unwrap();
+        watcher.watch([tmp.path().to_path_buf(), certs.clone()]);
+
+        fs::create_dir(&certs).unwrap();
+        assert!(watcher.changed(SETTLE).await);
+        watcher.watch([tmp.path().to_path_buf(), certs.clone()]);
+
+        fs::write(certs.join("myapp.roxy.crt"), "cert").unwrap();
+        let changed = tokio::time::timeout(SETTLE * 10, watcher.changed(SETTLE)).await;
+        assert_eq!(changed.ok(), Some(true));
+    }
+}
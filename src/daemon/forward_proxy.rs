@@ -0,0 +1,653 @@
+//! Explicit forward proxy, for devices configured to use Roxy as their
+//! HTTP proxy instead of its DNS server.
+//!
+//! `CONNECT` tunnels to `.roxy` hosts are decrypted with Roxy's own
+//! certificates and served like any other request. Hosts listed in
+//! `daemon.proxy_intercept` get an on-demand certificate too, and their
+//! requests are logged and passed on to the real origin. Everything else
+//! is tunnelled to its destination untouched.
+//!
+//! Only clients `daemon.proxy_access` allows may use the proxy, and it
+//! won't reach loopback or link-local addresses unless
+//! `daemon.proxy_local_targets` is set, so LAN devices can't get at
+//! services this machine keeps to itself.
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use axum::body::Body;
+use axum::http::{Method, Request, Response, StatusCode, Uri, header};
+use axum::response::IntoResponse;
+use axum::{Extension, Router};
+use hyper::body::Incoming;
+use hyper::service::service_fn;
+use hyper::upgrade::Upgraded;
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::connect::dns::Name;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use rustls::pki_types::ServerName;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use tower::{Service, ServiceExt};
+use tracing::{debug, error, info, warn};
+
+use super::proxy::{ClientAddr, HeaderCasing, Scheme, strip_hop_by_hop_headers};
+use super::router::{SharedState, create_router};
+use super::server::serve_connection;
+use super::tls::SharedAcceptor;
+use crate::domain::{AccessPolicy, DomainName};
+
+/// Sent by some clients to the proxy itself; never forwarded.
+const PROXY_CONNECTION: &str = "proxy-connection";
+
+/// How a `CONNECT` tunnel is handled.
+#[derive(Debug, PartialEq, Eq)]
+enum Tunnel {
+    /// Served by Roxy itself
+    Local,
+    /// Decrypted, logged and forwarded to the real host
+    Intercept,
+    /// Bytes copied to the real host as they are
+    Passthrough,
+    /// Bytes copied to the backend of a TLS passthrough domain
+    Backend,
+}
+
+pub struct ForwardProxy {
+    state: SharedState,
+    tls: Option<SharedAcceptor>,
+    intercept: Vec<String>,
+    intercept_tls: Option<TlsAcceptor>,
+    upstream_tls: TlsConnector,
+    access: AccessPolicy,
+    resolver: Resolver,
+    http_router: Router,
+    https_router: Router,
+    client: Client<HttpConnector<Resolver>, Incoming>,
+}
+
+impl ForwardProxy {
+    pub fn new(
+        state: SharedState,
+        tls: Option<SharedAcceptor>,
+        intercept: Vec<String>,
+        intercept_tls: Option<TlsAcceptor>,
+        upstream_tls: TlsConnector,
+    ) -> Self {
+        let http_router = create_router(state.clone()).layer(Extension(Scheme::Http));
+        let https_router = create_router(state.clone()).layer(Extension(Scheme::Https));
+        let resolver = Resolver { local: false };
+        let client = Client::builder(TokioExecutor::new())
+            .build(HttpConnector::new_with_resolver(resolver.clone()));
+
+        Self {
+            state,
+            tls,
+            intercept,
+            intercept_tls,
+            upstream_tls,
+            access: AccessPolicy::Lan,
+            resolver,
+            http_router,
+            https_router,
+            client,
+        }
+    }
+
+    /// Serve only clients `access` allows.
+    pub fn with_access(mut self, access: AccessPolicy) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// Let tunnels and requests reach loopback and link-local addresses.
+    pub fn with_local_targets(mut self, local: bool) -> Self {
+        self.resolver = Resolver { local };
+        self.client = Client::builder(TokioExecutor::new())
+            .build(HttpConnector::new_with_resolver(self.resolver.clone()));
+        self
+    }
+
+    pub async fn run(self, listener: TcpListener) {
+        let proxy = Arc::new(self);
+        loop {
+            let (stream, addr) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    error!(error = %e, "Failed to accept proxy connection");
+                    continue;
+                }
+            };
+
+            let proxy = proxy.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |request| {
+                    let proxy = proxy.clone();
+                    async move { Ok::<_, Infallible>(proxy.handle(request, addr).await) }
+                });
+                if let Err(e) = hyper::server::conn::http1::Builder::new()
+                    .preserve_header_case(true)
+                    .serve_connection(TokioIo::new(stream), service)
+                    .with_upgrades()
+                    .await
+                {
+                    debug!(error = %e, "Proxy connection closed");
+                }
+            });
+        }
+    }
+
+    async fn handle(
+        self: Arc<Self>,
+        request: Request<Incoming>,
+        addr: SocketAddr,
+    ) -> Response<Body> {
+        if !self.access.allows(addr.ip()) {
+            info!(client = %addr.ip(), "Proxy client not allowed");
+            return (StatusCode::FORBIDDEN, "Not allowed to use this proxy").into_response();
+        }
+        if request.method() == Method::CONNECT {
+            return self.connect(request, addr.ip()).await;
+        }
+
+        let host = request
+            .uri()
+            .host()
+            .map(str::to_string)
+            .or_else(|| host_header(&request));
+        let Some(host) = host else {
+            return (StatusCode::BAD_REQUEST, "Missing Host header").into_response();
+        };
+
+        if self.is_local(&host) {
+            let router = self
+                .http_router
+                .clone()
+                .layer(Extension(ClientAddr(addr.ip())));
+            let request = HeaderCasing::set_aside(request.map(Body::new));
+            return match router.oneshot(request).await {
+                Ok(response) => response,
+                Err(never) => match never {},
+            };
+        }
+
+        self.forward(request, &host).await
+    }
+
+    /// Answer a `CONNECT` and handle the tunnel once the client starts
+    /// using it. Tunnels that only copy bytes connect first, so the
+    /// client hears when that fails.
+    async fn connect(
+        self: Arc<Self>,
+        request: Request<Incoming>,
+        client_ip: IpAddr,
+    ) -> Response<Body> {
+        let Some(authority) = request.uri().authority().cloned() else {
+            return (StatusCode::BAD_REQUEST, "CONNECT needs host:port").into_response();
+        };
+        let host = authority.host().to_lowercase();
+        let port = authority.port_u16().unwrap_or(443);
+        // Domains with TLS passthrough are tunneled straight to their backend
+        let backend = (port != 80)
+            .then(|| self.state.current().passthrough_target(&host))
+            .flatten();
+        let (mode, host, port) = match backend {
+            Some(target) => (
+                Tunnel::Backend,
+                target.host().to_string(),
+                target.port().value(),
+            ),
+            None => (self.tunnel_mode(&host), host, port),
+        };
+        info!(host = %host, port, mode = ?mode, "Proxy tunnel");
+
+        let upstream = match mode {
+            Tunnel::Passthrough => Some(self.resolver.connect(&host, port).await),
+            Tunnel::Backend => Some(TcpStream::connect((host.as_str(), port)).await),
+            Tunnel::Local | Tunnel::Intercept => None,
+        };
+        let upstream = match upstream.transpose() {
+            Ok(upstream) => upstream,
+            Err(e) => {
+                warn!(host = %host, port, error = %e, "Proxy tunnel connect failed");
+                let status = if e.kind() == io::ErrorKind::PermissionDenied {
+                    StatusCode::FORBIDDEN
+                } else {
+                    StatusCode::BAD_GATEWAY
+                };
+                return (status, format!("Proxy error: {}", e)).into_response();
+            }
+        };
+
+        tokio::spawn(async move {
+            let upgraded = match hyper::upgrade::on(request).await {
+                Ok(upgraded) => TokioIo::new(upgraded),
+                Err(e) => {
+                    warn!(error = %e, "Proxy tunnel upgrade failed");
+                    return;
+                }
+            };
+
+            match (mode, upstream) {
+                (_, Some(upstream)) => passthrough(upgraded, upstream).await,
+                (Tunnel::Intercept, None) => self.intercept(upgraded, host, port).await,
+                (_, None) => self.serve_local(upgraded, port, client_ip).await,
+            }
+        });
+
+        Response::new(Body::empty())
+    }
+
+    fn tunnel_mode(&self, host: &str) -> Tunnel {
+        if self.is_local(host) {
+            Tunnel::Local
+        } else if self.intercept_tls.is_some() && matches_any(&self.intercept, host) {
+            Tunnel::Intercept
+        } else {
+            Tunnel::Passthrough
+        }
+    }
+
+    /// Hosts Roxy answers for: registered domains (and their aliases)
+    /// plus any `.roxy` name, which gets the "not registered" page.
+    fn is_local(&self, host: &str) -> bool {
+        self.state.current().get_domain(host).is_some() || DomainName::new(host).is_ok()
+    }
+
+    async fn serve_local(&self, io: TokioIo<Upgraded>, port: u16, client_ip: IpAddr) {
+        let client = Extension(ClientAddr(client_ip));
+        match &self.tls {
+            Some(tls) if port != 80 => match tls.current().accept(io).await {
+                Ok(stream) => {
+                    serve_connection(stream, self.https_router.clone().layer(client)).await
+                }
+                Err(e) => warn!(error = %e, "Proxy TLS handshake failed"),
+            },
+            _ => serve_connection(io, self.http_router.clone().layer(client)).await,
+        }
+    }
+
+    /// Decrypt the tunnel and relay each request to the real host.
+    async fn intercept(&self, io: TokioIo<Upgraded>, host: String, port: u16) {
+        let Some(acceptor) = &self.intercept_tls else {
+            return;
+        };
+        let stream = match acceptor.accept(io).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!(host = %host, error = %e, "Proxy TLS handshake failed");
+                return;
+            }
+        };
+
+        let upstream_tls = self.upstream_tls.clone();
+        let resolver = self.resolver.clone();
+        let service = service_fn(move |request| {
+            let upstream_tls = upstream_tls.clone();
+            let resolver = resolver.clone();
+            let host = host.clone();
+            async move {
+                let response = relay_tls(upstream_tls, resolver, &host, port, request).await;
+                Ok::<_, Infallible>(response)
+            }
+        });
+        if let Err(e) = hyper::server::conn::http1::Builder::new()
+            .serve_connection(TokioIo::new(stream), service)
+            .await
+        {
+            debug!(error = %e, "Intercepted connection closed");
+        }
+    }
+
+    /// Forward a plain `GET http://host/...` style request to its origin.
+    async fn forward(&self, mut request: Request<Incoming>, host: &str) -> Response<Body> {
+        if request.uri().authority().is_none() {
+            return (StatusCode::BAD_REQUEST, "Not a proxy request").into_response();
+        }
+        // The connector only asks the resolver about names
+        if let Ok(ip) = host.trim_matches(['[', ']']).parse::<IpAddr>()
+            && !self.resolver.allows(ip)
+        {
+            info!(host = %host, "Proxy target is local");
+            return (StatusCode::FORBIDDEN, "Proxy target is local").into_response();
+        }
+        strip_proxy_headers(&mut request);
+        debug!(host = %host, uri = %request.uri(), "Proxying to origin");
+
+        match self.client.request(request).await {
+            Ok(response) => response.map(Body::new),
+            Err(e) => {
+                warn!(host = %host, error = %e, "Proxy request failed");
+                (StatusCode::BAD_GATEWAY, format!("Proxy error: {}", e)).into_response()
+            }
+        }
+    }
+}
+
+/// Send one decrypted request to `host` over a fresh TLS connection.
+async fn relay_tls(
+    connector: TlsConnector,
+    resolver: Resolver,
+    host: &str,
+    port: u16,
+    mut request: Request<Incoming>,
+) -> Response<Body> {
+    let method = request.method().clone();
+    let path = request.uri().path_and_query().map(|p| p.to_string());
+    strip_proxy_headers(&mut request);
+    if let Some(path) = path.as_deref().and_then(|p| p.parse::<Uri>().ok()) {
+        *request.uri_mut() = path;
+    }
+
+    let result = async {
+        let server_name = ServerName::try_from(host.to_string())?;
+        let tcp = resolver.connect(host, port).await?;
+        let tls = connector.connect(server_name, tcp).await?;
+        let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(tls)).await?;
+        tokio::spawn(conn);
+        Ok::<_, anyhow::Error>(sender.send_request(request).await?)
+    }
+    .await;
+
+    match result {
+        Ok(response) => {
+            info!(
+                host = %host,
+                method = %method,
+                path = path.as_deref().unwrap_or("/"),
+                status = %response.status(),
+                "Intercepted"
+            );
+            response.map(Body::new)
+        }
+        Err(e) => {
+            warn!(host = %host, error = %e, "Intercepted request failed");
+            (StatusCode::BAD_GATEWAY, format!("Proxy error: {}", e)).into_response()
+        }
+    }
+}
+
+/// Copy bytes between the client and `upstream` until either side
+/// closes.
+async fn passthrough(mut io: TokioIo<Upgraded>, mut upstream: TcpStream) {
+    let _ = tokio::io::copy_bidirectional(&mut io, &mut upstream).await;
+}
+
+/// Looks up the hosts the proxy connects to, leaving out loopback and
+/// link-local addresses unless `local` is set.
+#[derive(Debug, Clone)]
+struct Resolver {
+    local: bool,
+}
+
+impl Resolver {
+    fn allows(&self, ip: IpAddr) -> bool {
+        self.local || !is_local(ip)
+    }
+
+    async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        let name = host.trim_matches(['[', ']']);
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name, port))
+            .await?
+            .filter(|addr| self.allows(addr.ip()))
+            .collect();
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is local", host),
+            ));
+        }
+        Ok(addrs)
+    }
+
+    async fn connect(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        TcpStream::connect(&*self.resolve(host, port).await?).await
+    }
+}
+
+/// For the HTTP client; the connector fills in the port.
+impl Service<Name> for Resolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let resolver = self.clone();
+        Box::pin(async move { Ok(resolver.resolve(name.as_str(), 0).await?.into_iter()) })
+    }
+}
+
+/// Addresses that lead back to this machine or its link: loopback,
+/// unspecified (which connects to loopback) and link-local.
+fn is_local(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => ip.is_loopback() || ip.is_unspecified() || ip.is_link_local(),
+        IpAddr::V6(ip) => ip.is_loopback() || ip.is_unspecified() || ip.is_unicast_link_local(),
+    }
+}
+
+fn strip_proxy_headers(request: &mut Request<Incoming>) {
+    let headers = request.headers_mut();
+    strip_hop_by_hop_headers(headers);
+    headers.remove(PROXY_CONNECTION);
+}
+
+fn host_header(request: &Request<Incoming>) -> Option<String> {
+    let host = request.headers().get(header::HOST)?.to_str().ok()?;
+    Some(host.split(':').next().unwrap_or(host).to_string())
+}
+
+/// Whether `host` is listed, either exactly or under a `*.` wildcard.
+fn matches_any(patterns: &[String], host: &str) -> bool {
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.to_lowercase();
+        match pattern.strip_prefix("*.") {
+            Some(base) => host == base || host.ends_with(&format!(".{}", base)),
+            None => host == pattern,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::router::AppState;
+    use crate::daemon::tls::{create_intercept_acceptor, create_upstream_connector};
+    use crate::domain::{DomainPattern, DomainRegistration, Route};
+    use crate::infrastructure::certs::CertsConfig;
+    use crate::infrastructure::certs::ca::RootCA;
+    use crate::infrastructure::tls::TlsConfig;
+    use rustls::pki_types::CertificateDer;
+    use rustls::pki_types::pem::PemObject;
+    use rustls::{ClientConfig, RootCertStore};
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+    fn proxy(registrations: Vec<DomainRegistration>) -> ForwardProxy {
+        let state = SharedState::new(AppState::new(registrations, false));
+        ForwardProxy::new(state, None, Vec::new(), None, create_upstream_connector())
+    }
+
+    async fn start(proxy: ForwardProxy) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(proxy.run(listener));
+        addr
+    }
+
+    /// A server on this machine that sends back whatever it gets.
+    async fn echo_server() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (mut read, mut write) = stream.split();
+                    let _ = tokio::io::copy(&mut read, &mut write).await;
+                });
+            }
+        });
+        port
+    }
+
+    /// Everything up to the end of the response head.
+    async fn read_head(stream: &mut (impl AsyncRead + Unpin)) -> String {
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+            if stream.read(&mut byte).await.unwrap() == 0 {
+                break;
+            }
+            head.push(byte[0]);
+        }
+        String::from_utf8(head).unwrap()
+    }
+
+    /// Send `CONNECT target` and return the proxy's answer along with
+    /// the connection, ready for the tunnel.
+    async fn connect(proxy: SocketAddr, target: &str) -> (String, TcpStream) {
+        let mut stream = TcpStream::connect(proxy).await.unwrap();
+        let request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let head = read_head(&mut stream).await;
+        (head, stream)
+    }
+
+    async fn get(stream: &mut (impl AsyncRead + AsyncWrite + Unpin), host: &str) -> String {
+        let request =
+            format!("GET /page.txt HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response).await;
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    #[tokio::test]
+    async fn tunnels_reach_local_addresses_only_when_allowed() {
+        let port = echo_server().await;
+
+        let addr = start(proxy(Vec::new())).await;
+        for target in [format!("127.0.0.1:{port}"), format!("localhost:{port}")] {
+            let (head, _) = connect(addr, &target).await;
+            assert!(head.starts_with("HTTP/1.1 403"), "{}", head);
+        }
+
+        let addr = start(proxy(Vec::new()).with_local_targets(true)).await;
+        let (head, mut tunnel) = connect(addr, &format!("127.0.0.1:{port}")).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+        tunnel.write_all(b"ping").await.unwrap();
+        let mut echoed = [0; 4];
+        tunnel.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(&echoed, b"ping");
+    }
+
+    #[tokio::test]
+    async fn plain_requests_to_local_addresses_are_refused() {
+        let port = echo_server().await;
+        let addr = start(proxy(Vec::new())).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET http://127.0.0.1:{port}/ HTTP/1.1\r\nHost: 127.0.0.1:{port}\r\nConnection: close\r\n\r\n"
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let head = read_head(&mut stream).await;
+        assert!(head.starts_with("HTTP/1.1 403"), "{}", head);
+    }
+
+    #[tokio::test]
+    async fn roxy_domains_are_served_through_the_tunnel() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("page.txt"), "from myapp.roxy").unwrap();
+        let pattern = DomainPattern::from_name("myapp.roxy", false).unwrap();
+        let route = Route::parse(&format!("/={}", tmp.path().display())).unwrap();
+        let registration = DomainRegistration::new(pattern, vec![route]);
+        let addr = start(proxy(vec![registration])).await;
+
+        let (head, mut tunnel) = connect(addr, "myapp.roxy:80").await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+        let response = get(&mut tunnel, "myapp.roxy").await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.ends_with("from myapp.roxy"));
+    }
+
+    #[tokio::test]
+    async fn intercepted_tunnels_are_decrypted_and_kept_off_local_addresses() {
+        let tmp = tempfile::tempdir().unwrap();
+        RootCA::new(tmp.path().to_path_buf()).generate().unwrap();
+        let acceptor =
+            create_intercept_acceptor(tmp.path(), &CertsConfig::default(), &TlsConfig::default())
+                .unwrap();
+        let state = SharedState::new(AppState::new(Vec::new(), false));
+        let intercept = vec!["localhost".to_string()];
+        let proxy = ForwardProxy::new(
+            state,
+            None,
+            intercept,
+            acceptor,
+            create_upstream_connector(),
+        );
+        assert_eq!(proxy.tunnel_mode("localhost"), Tunnel::Intercept);
+        assert_eq!(proxy.tunnel_mode("example.com"), Tunnel::Passthrough);
+        assert_eq!(proxy.tunnel_mode("myapp.roxy"), Tunnel::Local);
+        let addr = start(proxy).await;
+
+        let port = echo_server().await;
+        let (head, tunnel) = connect(addr, &format!("localhost:{port}")).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+
+        // The client sees Roxy's certificate for the intercepted host
+        let mut roots = RootCertStore::empty();
+        let ca = RootCA::new(tmp.path().to_path_buf());
+        for cert in CertificateDer::pem_file_iter(ca.cert_path()).unwrap() {
+            roots.add(cert.unwrap()).unwrap();
+        }
+        let connector = TlsConnector::from(Arc::new(
+            ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        ));
+        let name = ServerName::try_from("localhost").unwrap();
+        let mut tls = connector.connect(name, tunnel).await.unwrap();
+
+        let response = get(&mut tls, "localhost").await;
+        assert!(response.starts_with("HTTP/1.1 502"), "{}", response);
+        assert!(response.contains("localhost is local"), "{}", response);
+    }
+
+    #[test]
+    fn local_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "0.0.0.0",
+            "169.254.169.254",
+            "::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(is_local(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["192.168.1.10", "93.184.216.34", "2001:db8::1"] {
+            assert!(!is_local(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn intercept_patterns() {
+        let patterns = vec!["api.example.com".to_string(), "*.Test.dev".to_string()];
+        assert!(matches_any(&patterns, "api.example.com"));
+        assert!(!matches_any(&patterns, "www.example.com"));
+        assert!(matches_any(&patterns, "test.dev"));
+        assert!(matches_any(&patterns, "a.b.test.dev"));
+        assert!(!matches_any(&patterns, "nottest.dev"));
+    }
+}
@@ -0,0 +1,259 @@
+//! Periodic health checks for proxy backends. Load-balanced routes skip
+//! targets that are down, and error pages and `roxy status` report how
+//! long a backend has been down.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::http::{Request, Uri};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+use tracing::{debug, info, warn};
+
+use super::router::SharedState;
+use crate::domain::ProxyTarget;
+use crate::infrastructure::control::BackendStatus;
+
+/// How long one check may take before the target counts as down.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often to look whether a reload turned health checks on.
+const IDLE_POLL: Duration = Duration::from_secs(10);
+
+/// Last check result per backend. Like the request stats it lives
+/// outside the routing state, so a reload keeps the history.
+#[derive(Default)]
+pub struct BackendHealth {
+    targets: Mutex<HashMap<String, TargetState>>,
+}
+
+struct TargetState {
+    up: bool,
+    since: Instant,
+}
+
+impl BackendHealth {
+    /// Record a check result. Returns whether the target changed state.
+    pub fn record(&self, target: &ProxyTarget, up: bool) -> bool {
+        let mut targets = self.targets.lock().unwrap_or_else(|e| e.into_inner());
+        match targets.get(&target.to_string()) {
+            Some(state) if state.up == up => false,
+            _ => {
+                let since = Instant::now();
+                targets.insert(target.to_string(), TargetState { up, since });
+                true
+            }
+        }
+    }
+
+    /// How long `target` has been failing its checks, `None` if it is up
+    /// or hasn't been checked.
+    pub fn down_for(&self, target: &ProxyTarget) -> Option<Duration> {
+        let targets = self.targets.lock().unwrap_or_else(|e| e.into_inner());
+        targets
+            .get(&target.to_string())
+            .filter(|state| !state.up)
+            .map(|state| state.since.elapsed())
+    }
+
+    pub fn is_down(&self, target: &ProxyTarget) -> bool {
+        self.down_for(target).is_some()
+    }
+
+    /// Forget targets no route uses anymore.
+    fn retain(&self, checks: &BTreeMap<String, Check>) {
+        let mut targets = self.targets.lock().unwrap_or_else(|e| e.into_inner());
+        targets.retain(|target, _| checks.contains_key(target));
+    }
+
+    pub fn snapshot(&self) -> Vec<BackendStatus> {
+        let targets = self.targets.lock().unwrap_or_else(|e| e.into_inner());
+        let mut snapshot: Vec<_> = targets
+            .iter()
+            .map(|(target, state)| BackendStatus {
+                target: target.clone(),
+                up: state.up,
+                for_secs: state.since.elapsed().as_secs(),
+            })
+            .collect();
+        snapshot.sort_by(|a, b| a.target.cmp(&b.target));
+        snapshot
+    }
+}
+
+/// What to check for one backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub target: ProxyTarget,
+    /// HTTP path to request; a TCP connect otherwise.
+    pub path: Option<String>,
+}
+
+/// Check every proxy backend of the current routing state, forever.
+pub async fn run(shared: SharedState) {
+    let health = shared.health();
+    loop {
+        let state = shared.current();
+        let Some(interval) = state.health_check_interval() else {
+            health.retain(&BTreeMap::new());
+            drop(state);
+            tokio::time::sleep(IDLE_POLL).await;
+            continue;
+        };
+        let checks = state.health_checks();
+        drop(state);
+
+        health.retain(&checks);
+        let mut probes = JoinSet::new();
+        for check in checks.into_values() {
+            probes.spawn(async move {
+                let up = probe(&check).await;
+                (check.target, up)
+            });
+        }
+        while let Some(result) = probes.join_next().await {
+            match result {
+                Ok((target, up)) => {
+                    if health.record(&target, up) {
+                        if up {
+                            info!(target = %target, "Backend is up");
+                        } else {
+                            warn!(target = %target, "Backend is down");
+                        }
+                    }
+                }
+                Err(e) => debug!(error = %e, "Health check failed to run"),
+            }
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+async fn probe(check: &Check) -> bool {
+    let result = match &check.path {
+        Some(path) => tokio::time::timeout(CHECK_TIMEOUT, probe_http(&check.target, path)).await,
+        None => tokio::time::timeout(CHECK_TIMEOUT, probe_tcp(&check.target)).await,
+    };
+    result.unwrap_or(false)
+}
+
+async fn probe_tcp(target: &ProxyTarget) -> bool {
+    TcpStream::connect(target.to_string()).await.is_ok()
+}
+
+/// Up if the path answers with a 2xx or 3xx status.
+async fn probe_http(target: &ProxyTarget, path: &str) -> bool {
+    let uri: Uri = match format!("http://{}{}", target, path).parse() {
+        Ok(uri) => uri,
+        Err(e) => {
+            debug!(target = %target, path, error = %e, "Invalid health check path");
+            return false;
+        }
+    };
+    let client: Client<_, Body> = Client::builder(TokioExecutor::new()).build(HttpConnector::new());
+    let request = Request::get(uri)
+        .header("User-Agent", "roxy-health-check")
+        .body(Body::empty())
+        .expect("GET request with a valid URI");
+    match client.request(request).await {
+        Ok(response) => response.status().is_success() || response.status().is_redirection(),
+        Err(e) => {
+            debug!(target = %target, error = %e, "Health check request failed");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(port: u16) -> ProxyTarget {
+        ProxyTarget::parse(&port.to_string()).unwrap()
+    }
+
+    #[test]
+    fn tracks_state_changes() {
+        let health = BackendHealth::default();
+        let backend = target(3000);
+        assert!(!health.is_down(&backend));
+
+        assert!(health.record(&backend, false));
+        assert!(!health.record(&backend, false));
+        assert!(health.is_down(&backend));
+
+        assert!(health.record(&backend, true));
+        assert_eq!(health.down_for(&backend), None);
+        assert_eq!(
+            health.snapshot(),
+            vec![BackendStatus {
+                target: "127.0.0.1:3000".into(),
+                up: true,
+                for_secs: 0,
+            }]
+        );
+    }
+
+    #[test]
+    fn forgets_unused_targets() {
+        let health = BackendHealth::default();
+        health.record(&target(3000), false);
+        health.record(&target(3001), false);
+
+        let check = Check {
+            target: target(3001),
+            path: None,
+        };
+        health.retain(&BTreeMap::from([(check.target.to_string(), check)]));
+        assert!(!health.is_down(&target(3000)));
+        assert!(health.is_down(&target(3001)));
+    }
+
+    #[tokio::test]
+    async fn probes_tcp_and_http() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 1024];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let status = if buf[..n].starts_with(b"GET /healthz ") {
+                    "200 OK"
+                } else {
+                    "503 Service Unavailable"
+                };
+                let response = format!("HTTP/1.1 {}\r\ncontent-length: 0\r\n\r\n", status);
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let check = |path: Option<&str>| Check {
+            target: target(port),
+            path: path.map(str::to_string),
+        };
+        assert!(probe(&check(None)).await);
+        assert!(probe(&check(Some("/healthz"))).await);
+        assert!(!probe(&check(Some("/broken"))).await);
+
+        let closed = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        assert!(
+            !probe(&Check {
+                target: target(closed),
+                path: None,
+            })
+            .await
+        );
+    }
+}
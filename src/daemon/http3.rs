@@ -0,0 +1,384 @@
+//! HTTP/3 listener: HTTPS over QUIC on the HTTPS port, UDP this time.
+//!
+//! Connections are set up with the HTTPS listener's certificates, so a
+//! reload reaches them too, and requests go through the same router.
+//! Browsers only try it once an HTTPS response's `Alt-Svc` header has
+//! told them about it; everything keeps working over TCP without it.
+
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, ready};
+
+use anyhow::{Context as _, Result};
+use axum::body::{Body, Bytes};
+use axum::http::header::{self, HeaderName, HeaderValue};
+use axum::http::{Request, Response, Uri};
+use axum::{Extension, Router};
+use http_body_util::BodyExt;
+use hyper::body::{Buf, Frame};
+use quinn::crypto::rustls::QuicServerConfig;
+use quinn::{Endpoint, EndpointConfig, Incoming, TokioRuntime};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio_rustls::TlsAcceptor;
+use tower::ServiceExt;
+use tracing::{debug, warn};
+
+use super::proxy::ClientAddr;
+use super::tls::SharedAcceptor;
+
+type H3Connection = h3::server::Connection<h3_quinn::Connection, Bytes>;
+type SendStream = h3::server::RequestStream<h3_quinn::SendStream<Bytes>, Bytes>;
+type RecvStream = h3::server::RequestStream<h3_quinn::RecvStream, Bytes>;
+
+/// Headers about the TCP connection, which HTTP/3 forbids.
+const CONNECTION_HEADERS: [HeaderName; 4] = [
+    header::CONNECTION,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+    HeaderName::from_static("keep-alive"),
+];
+
+pub struct Http3Server {
+    endpoint: Endpoint,
+}
+
+impl Http3Server {
+    /// Listen on UDP `port`, over IPv4 and IPv6 like the HTTPS listener.
+    pub fn bind(port: u16, acceptor: &TlsAcceptor) -> Result<Self> {
+        let socket = bind_dual_stack(port).context(format!(
+            "Failed to bind to UDP port {}. Is another service using it? Try: sudo lsof -i UDP:{}",
+            port, port
+        ))?;
+        let endpoint = Endpoint::new(
+            EndpointConfig::default(),
+            Some(quic_config(acceptor)?),
+            socket,
+            Arc::new(TokioRuntime),
+        )?;
+        Ok(Self { endpoint })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.endpoint.local_addr()
+    }
+
+    /// Serve connections with `router`, which gets the client address
+    /// as an extension like on the TCP listeners.
+    pub async fn run(self, tls: SharedAcceptor, router: Router) {
+        let mut configs = QuicConfigs::default();
+        while let Some(incoming) = self.endpoint.accept().await {
+            // Picked per connection so a reload applies to new ones
+            let config = match configs.get(&tls.current()) {
+                Ok(config) => config,
+                Err(e) => {
+                    warn!(error = %e, "HTTP/3 unavailable with the current [tls] settings");
+                    incoming.refuse();
+                    continue;
+                }
+            };
+            let client = ClientAddr(incoming.remote_address().ip().to_canonical());
+            let router = router.clone().layer(Extension(client));
+            tokio::spawn(serve_connection(incoming, config, router));
+        }
+    }
+}
+
+/// The QUIC config for the current acceptor, made again only when a
+/// reload replaced it.
+#[derive(Default)]
+struct QuicConfigs {
+    last: Option<(Arc<rustls::ServerConfig>, Arc<quinn::ServerConfig>)>,
+}
+
+impl QuicConfigs {
+    fn get(&mut self, acceptor: &TlsAcceptor) -> Result<Arc<quinn::ServerConfig>> {
+        match &self.last {
+            Some((tls, quic)) if Arc::ptr_eq(tls, acceptor.config()) => Ok(quic.clone()),
+            _ => {
+                let quic = Arc::new(quic_config(acceptor)?);
+                self.last = Some((acceptor.config().clone(), quic.clone()));
+                Ok(quic)
+            }
+        }
+    }
+}
+
+/// The acceptor's TLS settings and certificates, speaking `h3`.
+fn quic_config(acceptor: &TlsAcceptor) -> Result<quinn::ServerConfig> {
+    let mut tls = (**acceptor.config()).clone();
+    tls.alpn_protocols = vec![b"h3".to_vec()];
+    let crypto = QuicServerConfig::try_from(tls)
+        .context("HTTP/3 needs TLS13_AES_128_GCM_SHA256 among the [tls] cipher suites")?;
+    Ok(quinn::ServerConfig::with_crypto(Arc::new(crypto)))
+}
+
+async fn serve_connection(incoming: Incoming, config: Arc<quinn::ServerConfig>, router: Router) {
+    let connection = match incoming.accept_with(config) {
+        Ok(connecting) => connecting.await,
+        Err(e) => Err(e),
+    };
+    let connection = match connection {
+        Ok(connection) => connection,
+        Err(e) => return debug!(error = %e, "QUIC handshake failed"),
+    };
+    let mut connection: H3Connection =
+        match h3::server::Connection::new(h3_quinn::Connection::new(connection)).await {
+            Ok(connection) => connection,
+            Err(e) => return debug!(error = %e, "HTTP/3 connection failed"),
+        };
+
+    loop {
+        match connection.accept().await {
+            Ok(Some(resolver)) => {
+                let router = router.clone();
+                tokio::spawn(async move {
+                    let result = match resolver.resolve_request().await {
+                        Ok((request, stream)) => {
+                            let (send, recv) = stream.split();
+                            serve_request(request, send, recv, router).await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = result {
+                        debug!(error = %e, "Error serving HTTP/3 request");
+                    }
+                });
+            }
+            Ok(None) => break,
+            Err(e) => {
+                if !e.is_h3_no_error() {
+                    debug!(error = %e, "HTTP/3 connection closed");
+                }
+                break;
+            }
+        }
+    }
+}
+
+async fn serve_request(
+    request: Request<()>,
+    mut send: SendStream,
+    recv: RecvStream,
+    router: Router,
+) -> Result<(), h3::error::StreamError> {
+    let (mut parts, ()) = request.into_parts();
+    // The router reads the domain from Host, which HTTP/3 carries in
+    // the URI like HTTP/2 does
+    if let Some(authority) = parts.uri.authority()
+        && !parts.headers.contains_key(header::HOST)
+        && let Ok(host) = HeaderValue::from_str(authority.as_str())
+    {
+        parts.headers.insert(header::HOST, host);
+    }
+    parts.uri = parts
+        .uri
+        .path_and_query()
+        .map_or_else(|| Uri::from_static("/"), |path| Uri::from(path.clone()));
+
+    let request = Request::from_parts(parts, Body::new(RequestBody::new(recv)));
+    let Ok(response) = router.oneshot(request).await;
+
+    let (mut parts, mut body) = response.into_parts();
+    for name in &CONNECTION_HEADERS {
+        parts.headers.remove(name);
+    }
+    send.send_response(Response::from_parts(parts, ())).await?;
+
+    while let Some(frame) = body.frame().await {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(e) => {
+                // Cut short, so the client doesn't take it for the whole body
+                debug!(error = %e, "Response body failed");
+                send.stop_stream(h3::error::Code::H3_INTERNAL_ERROR);
+                return Ok(());
+            }
+        };
+        match frame.into_data() {
+            Ok(data) if data.is_empty() => {}
+            Ok(data) => send.send_data(data).await?,
+            Err(frame) => {
+                if let Ok(trailers) = frame.into_trailers() {
+                    send.send_trailers(trailers).await?;
+                }
+            }
+        }
+    }
+    send.finish().await
+}
+
+/// A request body read off an HTTP/3 stream, trailers included.
+struct RequestBody {
+    recv: RecvStream,
+    data_done: bool,
+}
+
+impl RequestBody {
+    fn new(recv: RecvStream) -> Self {
+        Self {
+            recv,
+            data_done: false,
+        }
+    }
+}
+
+impl hyper::body::Body for RequestBody {
+    type Data = Bytes;
+    type Error = h3::error::StreamError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        if !self.data_done {
+            match ready!(self.recv.poll_recv_data(cx)) {
+                Ok(Some(mut data)) => {
+                    let data = data.copy_to_bytes(data.remaining());
+                    return Poll::Ready(Some(Ok(Frame::data(data))));
+                }
+                Ok(None) => self.data_done = true,
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+        match ready!(self.recv.poll_recv_trailers(cx)) {
+            Ok(Some(trailers)) => Poll::Ready(Some(Ok(Frame::trailers(trailers)))),
+            Ok(None) => Poll::Ready(None),
+            Err(e) => Poll::Ready(Some(Err(e))),
+        }
+    }
+}
+
+/// The UDP twin of the server's TCP `bind_dual_stack`.
+fn bind_dual_stack(port: u16) -> io::Result<UdpSocket> {
+    let bind = |addr: SocketAddr| -> io::Result<UdpSocket> {
+        let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+        if addr.is_ipv6() {
+            socket.set_only_v6(false)?;
+        }
+        socket.set_nonblocking(true)?;
+        socket.bind(&addr.into())?;
+        Ok(socket.into())
+    };
+    match bind(SocketAddr::from((Ipv6Addr::UNSPECIFIED, port))) {
+        Err(e)
+            if e.kind() != io::ErrorKind::AddrInUse
+                && e.kind() != io::ErrorKind::PermissionDenied =>
+        {
+            debug!(error = %e, "IPv6 unavailable, listening on IPv4 only");
+            bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)))
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use rustls::RootCertStore;
+    use rustls::pki_types::CertificateDer;
+    use rustls::pki_types::pem::PemObject;
+
+    use crate::infrastructure::certs::CertsConfig;
+    use crate::infrastructure::certs::ca::RootCA;
+    use crate::infrastructure::tls::TlsConfig;
+
+    use super::super::tls::{DomainCerts, create_tls_acceptor};
+
+    async fn echo(
+        Extension(client): Extension<ClientAddr>,
+        headers: axum::http::HeaderMap,
+        body: Bytes,
+    ) -> String {
+        format!(
+            "{} from {}: {}",
+            headers[header::HOST].to_str().unwrap(),
+            client.0,
+            String::from_utf8_lossy(&body)
+        )
+    }
+
+    #[tokio::test]
+    async fn serves_requests_with_the_https_certificates() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ca = RootCA::new(tmp.path().to_path_buf());
+        ca.generate().unwrap();
+        let acceptor = create_tls_acceptor(
+            &DomainCerts::default(),
+            &tmp.path().join("certs"),
+            tmp.path(),
+            false,
+            &CertsConfig::default(),
+            &TlsConfig::default(),
+        )
+        .unwrap()
+        .unwrap();
+        let server = Http3Server::bind(0, &acceptor).unwrap();
+        let port = server.local_addr().unwrap().port();
+        let router = Router::new().route("/echo", post(echo));
+        tokio::spawn(server.run(SharedAcceptor::new(acceptor), router));
+
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_file_iter(ca.cert_path()).unwrap() {
+            roots.add(cert.unwrap()).unwrap();
+        }
+        let mut tls = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        tls.alpn_protocols = vec![b"h3".to_vec()];
+        let quic = quinn::crypto::rustls::QuicClientConfig::try_from(tls).unwrap();
+        let mut client = Endpoint::client(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+        client.set_default_client_config(quinn::ClientConfig::new(Arc::new(quic)));
+
+        let connection = client
+            .connect(SocketAddr::from((Ipv4Addr::LOCALHOST, port)), "other.roxy")
+            .unwrap()
+            .await
+            .unwrap();
+        let (mut driver, mut requests) = h3::client::new(h3_quinn::Connection::new(connection))
+            .await
+            .unwrap();
+        tokio::spawn(async move { std::future::poll_fn(|cx| driver.poll_close(cx)).await });
+
+        let request = Request::post("https://other.roxy/echo").body(()).unwrap();
+        let mut stream = requests.send_request(request).await.unwrap();
+        stream
+            .send_data(Bytes::from_static(b"hello"))
+            .await
+            .unwrap();
+        stream.finish().await.unwrap();
+
+        let response = stream.recv_response().await.unwrap();
+        assert_eq!(response.status(), 200);
+        let mut body = Vec::new();
+        while let Some(mut chunk) = stream.recv_data().await.unwrap() {
+            body.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+        }
+        assert_eq!(body, b"other.roxy from 127.0.0.1: hello");
+    }
+
+    #[test]
+    fn quic_configs_follow_reloads() {
+        let tmp = tempfile::tempdir().unwrap();
+        RootCA::new(tmp.path().to_path_buf()).generate().unwrap();
+        let acceptor = || {
+            create_tls_acceptor(
+                &DomainCerts::default(),
+                &tmp.path().join("certs"),
+                tmp.path(),
+                false,
+                &CertsConfig::default(),
+                &TlsConfig::default(),
+            )
+            .unwrap()
+            .unwrap()
+        };
+        let mut configs = QuicConfigs::default();
+        let first = acceptor();
+        let config = configs.get(&first).unwrap();
+        assert!(Arc::ptr_eq(&config, &configs.get(&first).unwrap()));
+        assert!(!Arc::ptr_eq(&config, &configs.get(&acceptor()).unwrap()));
+    }
+}
@@ -5,6 +5,7 @@ use anyhow::Result;
 use tracing::info;
 
 use super::Server;
+use super::control::ControlServer;
 use crate::infrastructure::config::ConfigStore;
 use crate::infrastructure::paths::RoxyPaths;
 use crate::infrastructure::pid::PidFile;
@@ -16,6 +17,11 @@ use crate::infrastructure::tracing::{TracingOutput, init_tracing};
 /// PID file management, signal handling, and server execution.
 #[tokio::main]
 pub async fn run(verbose: bool, config_path: &Path, paths: &RoxyPaths) -> Result<()> {
+    // Load config fresh from disk (this path is used by the forked
+    // subprocess, so it must re-read from the config file)
+    let config_store = ConfigStore::new(config_path.to_path_buf());
+    let config = config_store.load()?;
+
     // When running interactively (stdout is a TTY), log to stdout
     // When running as daemon (stdout is /dev/null), log to file
     let output = if std::io::stdout().is_terminal() {
@@ -23,7 +29,7 @@ pub async fn run(verbose: bool, config_path: &Path, paths: &RoxyPaths) -> Result
     } else {
         TracingOutput::File(paths.log_file.clone())
     };
-    init_tracing(verbose, output);
+    let log_level = init_tracing(verbose, &config.daemon.log_level, output);
 
     info!("Roxy daemon started");
 
@@ -32,21 +38,21 @@ pub async fn run(verbose: bool, config_path: &Path, paths: &RoxyPaths) -> Result
 
     // Handle Ctrl+C gracefully
     let cleanup_pid = PidFile::new(paths.pid_file.clone());
+    let cleanup_socket = paths.control_socket.clone();
     ctrlc::set_handler(move || {
         let _ = cleanup_pid.remove();
+        ControlServer::cleanup(&cleanup_socket);
         std::process::exit(0);
     })?;
 
     println!("Starting Roxy daemon...");
 
-    // Load config fresh from disk (this path is used by the forked
-    // subprocess, so it must re-read from the config file)
-    let config_store = ConfigStore::new(config_path.to_path_buf());
-    let config = config_store.load()?;
-
-    let server = Server::new(&config, paths)?;
+    let server = Server::new(&config, config_path, paths, log_level)?;
     let result = server.run().await;
 
-    pid_file.remove()?;
+    // After dropping root the daemon may not be allowed to remove these;
+    // `roxy stop` and the next start clean up after it
+    let _ = pid_file.remove();
+    ControlServer::cleanup(&paths.control_socket);
     result
 }
@@ -0,0 +1,369 @@
+//! Multicast DNS (Bonjour) responder.
+//!
+//! Answers `<name>.local` for every registered `<name>.roxy` domain with
+//! the LAN address, so phones and tablets on the same network reach the
+//! sites without pointing their DNS at Roxy.
+
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::Result;
+use simple_dns::rdata::{A, RData};
+use simple_dns::{CLASS, Name, Packet, PacketFlag, QTYPE, ResourceRecord, TYPE};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info};
+
+use crate::domain::DomainRegistration;
+
+const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+
+/// Seconds others may cache our records, as RFC 6762 suggests for
+/// address records.
+const TTL: u32 = 120;
+
+/// Replies to one-shot queries from plain resolvers must not be cached
+/// for longer than this (RFC 6762, section 6.7).
+const LEGACY_TTL: u32 = 10;
+
+/// The `.local` names published for `registrations`. Wildcard domains
+/// publish their base name only; mDNS has no wildcards.
+pub fn local_names(registrations: &[DomainRegistration]) -> Vec<String> {
+    let mut names: Vec<_> = registrations
+        .iter()
+        .map(|r| r.domain().local_name())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// A bound mDNS socket, not answering yet.
+pub struct MdnsResponder {
+    socket: UdpSocket,
+    ip: Ipv4Addr,
+    names: Vec<String>,
+}
+
+impl MdnsResponder {
+    /// Join the mDNS group on the interface with address `ip`. The port
+    /// is shared with the system's own responder (Avahi, mDNSResponder).
+    pub fn bind(ip: Ipv4Addr, names: Vec<String>) -> Result<Self> {
+        let socket = bind_multicast(ip).map_err(|e| {
+            anyhow::Error::new(e).context(format!(
+                "Failed to join mDNS on {}:{}. Is dns_port set to {}?",
+                ip, MDNS_PORT, MDNS_PORT
+            ))
+        })?;
+        Ok(Self { socket, ip, names })
+    }
+
+    /// Announce the names and answer queries in the background until the
+    /// returned task is dropped.
+    pub fn spawn(self) -> MdnsTask {
+        info!(ip = %self.ip, names = ?self.names, "mDNS responder listening");
+        let socket = Arc::new(self.socket);
+        let names = Arc::new(RwLock::new(self.names));
+        let ip = self.ip;
+
+        let handle = tokio::spawn(run(socket.clone(), ip, names.clone()));
+        let task = MdnsTask {
+            socket,
+            ip,
+            names,
+            handle,
+        };
+        task.announce();
+        task
+    }
+}
+
+fn bind_multicast(ip: Ipv4Addr) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, MDNS_PORT)).into())?;
+    socket.join_multicast_v4(&MDNS_GROUP, &ip)?;
+    socket.set_multicast_if_v4(&ip)?;
+    socket.set_multicast_ttl_v4(255)?;
+    UdpSocket::from_std(socket.into())
+}
+
+/// A running mDNS responder. Dropping it says goodbye, so others forget
+/// the names at once, and stops answering.
+pub struct MdnsTask {
+    socket: Arc<UdpSocket>,
+    ip: Ipv4Addr,
+    names: Arc<RwLock<Vec<String>>>,
+    handle: JoinHandle<()>,
+}
+
+impl MdnsTask {
+    /// Publish `names` instead, saying goodbye to the ones dropped.
+    pub fn set_names(&self, names: Vec<String>) {
+        let gone: Vec<_> = {
+            let mut current = self.names.write().unwrap_or_else(|e| e.into_inner());
+            let gone = current
+                .iter()
+                .filter(|name| !names.contains(name))
+                .cloned()
+                .collect();
+            *current = names;
+            gone
+        };
+        self.send(&gone, 0);
+        self.announce();
+    }
+
+    /// Tell the network about every name, twice a second apart as RFC
+    /// 6762 asks, in case the first packet is lost.
+    fn announce(&self) {
+        let socket = self.socket.clone();
+        let names = self.names();
+        let ip = self.ip;
+        tokio::spawn(async move {
+            for _ in 0..2 {
+                if let Some(packet) = build_announcement(&names, ip, TTL) {
+                    let _ = socket.send_to(&packet, (MDNS_GROUP, MDNS_PORT)).await;
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.names.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Send the records of `names` right away; a `ttl` of 0 withdraws them.
+    fn send(&self, names: &[String], ttl: u32) {
+        if let Some(packet) = build_announcement(names, self.ip, ttl) {
+            let _ = self
+                .socket
+                .try_send_to(&packet, (MDNS_GROUP, MDNS_PORT).into());
+        }
+    }
+}
+
+impl Drop for MdnsTask {
+    fn drop(&mut self) {
+        self.send(&self.names(), 0);
+        self.handle.abort();
+    }
+}
+
+async fn run(socket: Arc<UdpSocket>, ip: Ipv4Addr, names: Arc<RwLock<Vec<String>>>) {
+    let mut buf = [0u8; 9000]; // mDNS allows packets up to the jumbo frame size
+
+    loop {
+        let (len, addr) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(e) => {
+                error!(error = %e, "mDNS receive error");
+                continue;
+            }
+        };
+        let reply = {
+            let names = names.read().unwrap_or_else(|e| e.into_inner());
+            build_reply(&buf[..len], addr.port() != MDNS_PORT, &names, ip)
+        };
+        if let Some((packet, unicast)) = reply {
+            let to = if unicast {
+                addr
+            } else {
+                (MDNS_GROUP, MDNS_PORT).into()
+            };
+            let _ = socket.send_to(&packet, to).await;
+        }
+    }
+}
+
+/// The answer to a query for one of our `names`, and whether it goes
+/// straight back to the sender rather than to the group. Queries from
+/// ports other than 5353 come from plain resolvers (`legacy`), which
+/// get an ordinary DNS reply.
+fn build_reply(
+    query: &[u8],
+    legacy: bool,
+    names: &[String],
+    ip: Ipv4Addr,
+) -> Option<(Vec<u8>, bool)> {
+    let packet = Packet::parse(query).ok()?;
+    if packet.has_flags(PacketFlag::RESPONSE) {
+        return None;
+    }
+
+    let mut reply = Packet::new_reply(if legacy { packet.id() } else { 0 });
+    reply.set_flags(PacketFlag::RESPONSE | PacketFlag::AUTHORITATIVE_ANSWER);
+    let mut unicast = legacy;
+
+    for question in &packet.questions {
+        if !matches!(question.qtype, QTYPE::TYPE(TYPE::A) | QTYPE::ANY) {
+            continue;
+        }
+        let name = question.qname.to_string().to_lowercase();
+        let Some(name) = names.iter().find(|n| **n == name.trim_end_matches('.')) else {
+            continue;
+        };
+
+        debug!(name = %name, "mDNS query");
+        unicast |= question.unicast_response;
+        if legacy {
+            reply.questions.push(question.clone());
+            reply.answers.push(address_record(name, ip, LEGACY_TTL));
+        } else {
+            reply
+                .answers
+                .push(address_record(name, ip, TTL).with_cache_flush(true));
+        }
+    }
+
+    if reply.answers.is_empty() {
+        return None;
+    }
+    Some((reply.build_bytes_vec().ok()?, unicast))
+}
+
+/// An unsolicited response carrying the address of every name.
+fn build_announcement(names: &[String], ip: Ipv4Addr, ttl: u32) -> Option<Vec<u8>> {
+    if names.is_empty() {
+        return None;
+    }
+    let mut packet = Packet::new_reply(0);
+    packet.set_flags(PacketFlag::RESPONSE | PacketFlag::AUTHORITATIVE_ANSWER);
+    for name in names {
+        packet
+            .answers
+            .push(address_record(name, ip, ttl).with_cache_flush(true));
+    }
+    packet.build_bytes_vec().ok()
+}
+
+fn address_record(name: &str, ip: Ipv4Addr, ttl: u32) -> ResourceRecord<'_> {
+    ResourceRecord::new(
+        Name::new_unchecked(name),
+        CLASS::IN,
+        ttl,
+        RData::A(A::from(ip)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{DomainName, DomainPattern, PathPrefix, ProxyTarget, Route, RouteTarget};
+    use simple_dns::Question;
+
+    const LAN_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 20);
+
+    fn query(name: &str, qtype: TYPE, unicast_response: bool) -> Vec<u8> {
+        let mut packet = Packet::new_query(77);
+        packet.questions.push(Question::new(
+            Name::new_unchecked(name),
+            qtype.into(),
+            CLASS::IN.into(),
+            unicast_response,
+        ));
+        packet.build_bytes_vec().unwrap()
+    }
+
+    fn names() -> Vec<String> {
+        vec!["myapp.local".to_string()]
+    }
+
+    #[test]
+    fn publishes_local_names_of_registrations() {
+        let route = || {
+            Route::new(
+                PathPrefix::new("/").unwrap(),
+                RouteTarget::Proxy(ProxyTarget::parse("3000").unwrap()),
+            )
+        };
+        let exact = DomainPattern::Exact(DomainName::new("myapp.roxy").unwrap());
+        let wildcard = DomainPattern::Wildcard(DomainName::new("blog.roxy").unwrap());
+        let registrations = [
+            DomainRegistration::new(exact, vec![route()]),
+            DomainRegistration::new(wildcard, vec![route()]),
+        ];
+        assert_eq!(
+            local_names(&registrations),
+            vec!["blog.local", "myapp.local"]
+        );
+    }
+
+    #[test]
+    fn answers_queries_for_published_names() {
+        let (reply, unicast) = build_reply(
+            &query("myapp.local", TYPE::A, false),
+            false,
+            &names(),
+            LAN_IP,
+        )
+        .unwrap();
+        assert!(!unicast);
+        let reply = Packet::parse(&reply).unwrap();
+        assert_eq!(reply.id(), 0);
+        assert!(reply.questions.is_empty());
+        assert_eq!(reply.answers[0].rdata, RData::A(A::from(LAN_IP)));
+        assert!(reply.answers[0].cache_flush);
+        assert_eq!(reply.answers[0].ttl, TTL);
+
+        // QU questions ask for a unicast reply
+        let (_, unicast) = build_reply(
+            &query("MyApp.local", TYPE::A, true),
+            false,
+            &names(),
+            LAN_IP,
+        )
+        .unwrap();
+        assert!(unicast);
+    }
+
+    #[test]
+    fn ignores_other_names_and_responses() {
+        assert!(
+            build_reply(
+                &query("other.local", TYPE::A, false),
+                false,
+                &names(),
+                LAN_IP
+            )
+            .is_none()
+        );
+        assert!(
+            build_reply(
+                &query("myapp.local", TYPE::AAAA, false),
+                false,
+                &names(),
+                LAN_IP
+            )
+            .is_none()
+        );
+
+        let announcement = build_announcement(&names(), LAN_IP, TTL).unwrap();
+        assert!(build_reply(&announcement, false, &names(), LAN_IP).is_none());
+    }
+
+    #[test]
+    fn answers_plain_resolvers_like_dns() {
+        let (reply, unicast) = build_reply(
+            &query("myapp.local", TYPE::A, false),
+            true,
+            &names(),
+            LAN_IP,
+        )
+        .unwrap();
+        assert!(unicast);
+        let reply = Packet::parse(&reply).unwrap();
+        assert_eq!(reply.id(), 77);
+        assert_eq!(reply.questions.len(), 1);
+        assert_eq!(reply.answers[0].ttl, LEGACY_TTL);
+        assert!(!reply.answers[0].cache_flush);
+    }
+}
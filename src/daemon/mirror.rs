@@ -0,0 +1,96 @@
+use std::net::IpAddr;
+
+use axum::{body::Body, extract::Request, response::Response};
+use http_body_util::BodyExt;
+use tracing::debug;
+
+use crate::domain::Route;
+
+use super::proxy::{Timeouts, buffer_request, is_websocket_upgrade, proxy_request};
+
+/// Send a copy of `request` to the route's mirror in the background, and
+/// hand the request back for the real target. Whatever the mirror
+/// answers is thrown away.
+pub async fn mirror(
+    route: &Route,
+    request: Request,
+    host: &str,
+    proto: &str,
+    client_ip: Option<IpAddr>,
+    timeouts: Timeouts,
+) -> Result<Request, Response> {
+    let Some(target) = route.options.mirror.clone() else {
+        return Ok(request);
+    };
+    // A connection upgrade can only go to one place
+    if is_websocket_upgrade(&request) {
+        return Ok(request);
+    }
+
+    let (parts, body) = buffer_request(request).await?;
+    let mut copy = Request::new(Body::from(body.clone()));
+    *copy.method_mut() = parts.method.clone();
+    *copy.uri_mut() = parts.uri.clone();
+    *copy.version_mut() = parts.version;
+    *copy.headers_mut() = parts.headers.clone();
+
+    let (host, proto, options) = (host.to_string(), proto.to_string(), route.options.clone());
+    tokio::spawn(async move {
+        let response =
+            proxy_request(&target, copy, &host, &proto, client_ip, &options, timeouts).await;
+        let status = response.status();
+        // Read the body so the mirror isn't cut off mid-response
+        let _ = response.into_body().collect().await;
+        debug!(target = %target, status = %status, "Mirrored request");
+    });
+
+    Ok(Request::from_parts(parts, Body::from(body)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{PathPrefix, ProxyTarget, RouteTarget};
+    use axum::{Router, routing::post};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn copies_the_request_to_the_mirror() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/echo",
+            post(move |body: String| {
+                tx.send(body).unwrap();
+                async { "shadow" }
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let mut route = Route::new(
+            PathPrefix::new("/").unwrap(),
+            RouteTarget::Proxy(ProxyTarget::parse("3000").unwrap()),
+        );
+        route.options.mirror = Some(ProxyTarget::parse(&port.to_string()).unwrap());
+        let request = Request::post("/echo")
+            .header("Host", "myapp.roxy")
+            .body(Body::from("hello"))
+            .unwrap();
+
+        let request = mirror(
+            &route,
+            request,
+            "myapp.roxy",
+            "http",
+            None,
+            Timeouts::default(),
+        )
+        .await
+        .unwrap();
+        let body = request.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "hello");
+        assert_eq!(rx.recv().await.unwrap(), "hello");
+    }
+}
@@ -1,11 +1,38 @@
+pub mod balancer;
+pub mod basic_auth;
+pub mod body_rewrite;
+pub mod ca_download;
+pub mod cert_renewal;
+pub mod cert_watch;
+pub mod compression;
+pub mod config_watch;
+pub mod control;
+pub mod cors;
 pub mod dns_server;
 pub mod embedded_assets;
+pub mod file_watch;
+pub mod forward_proxy;
+pub mod health_check;
+pub mod http3;
+
 pub mod lifecycle;
+pub mod mdns;
+pub mod mirror;
+pub mod network_watch;
 pub mod proxy;
+pub mod rate_limit;
+pub mod reload;
+pub mod response_cache;
 pub mod router;
 pub mod server;
 pub mod static_files;
+pub mod stats;
+pub mod sticky;
+pub mod streaming;
+pub mod supervisor;
+pub mod tcp_forward;
 pub mod theme;
 pub mod tls;
+pub mod tls_passthrough;
 
 pub use server::Server;
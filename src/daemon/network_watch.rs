@@ -0,0 +1,43 @@
+//! Follows the host between networks, so DNS answers carry the address
+//! it has now rather than the one it had when the daemon started.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::info;
+
+use super::dns_server::{HostAddrs, IpResolver};
+use crate::infrastructure::network::{get_lan_ip, get_lan_ipv6};
+
+/// How often to look up the LAN addresses again.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Re-detect the LAN addresses every few seconds and hand changes to
+/// `resolver`.
+pub async fn run(resolver: Arc<IpResolver>) {
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        // Detection runs ifconfig or hostname
+        let Ok(detected) = tokio::task::spawn_blocking(detect).await else {
+            continue;
+        };
+        let current = resolver.lan();
+        if detected != current {
+            info!(
+                lan_ip = %detected.v4,
+                lan_ipv6 = ?detected.v6,
+                previous = %current.v4,
+                "Network changed, answering DNS queries with the new LAN address"
+            );
+            resolver.set_lan(detected);
+        }
+    }
+}
+
+fn detect() -> HostAddrs {
+    HostAddrs {
+        v4: get_lan_ip(),
+        v6: get_lan_ipv6(),
+    }
+}
@@ -1,20 +1,34 @@
 use std::net::IpAddr;
-use std::time::Instant;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
 
 use axum::{
     body::Body,
     extract::Request,
-    http::{HeaderMap, StatusCode, Uri, header, header::HeaderName, header::HeaderValue},
+    http::{
+        Extensions, HeaderMap, StatusCode, Uri, Version, header, header::HeaderName,
+        header::HeaderValue, request::Parts,
+    },
     response::{IntoResponse, Response},
 };
+use http_body_util::{BodyExt, Empty};
+use hyper::body::{Bytes, Incoming};
+use hyper::client::conn::http1;
+use hyper::ext::Protocol;
+use hyper::upgrade::OnUpgrade;
 use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::Error as ClientError;
 use hyper_util::client::legacy::connect::HttpConnector;
-use hyper_util::rt::TokioExecutor;
+use hyper_util::rt::{TokioExecutor, TokioIo};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tracing::{debug, info, warn};
+use tracing::{Instrument, debug, info, warn};
 
-use crate::domain::ProxyTarget;
+use super::body_rewrite;
+use super::router::{build_backend_down_response, build_gateway_timeout_response};
+use super::streaming::{self, IdleTimeout};
+use crate::domain::{BackendProtocol, CookieRewrite, ProxyTarget, RetryPolicy, RouteOptions};
+use crate::infrastructure::config::DaemonConfig;
 
 /// Non-standard (but de facto standard) forwarding header names.
 /// The `http` crate only provides constants for IANA-registered headers,
@@ -22,6 +36,7 @@ use crate::domain::ProxyTarget;
 const X_FORWARDED_FOR: &str = "x-forwarded-for";
 const X_FORWARDED_HOST: &str = "x-forwarded-host";
 const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+const X_FORWARDED_PORT: &str = "x-forwarded-port";
 const KEEP_ALIVE: &str = "keep-alive";
 
 /// Scheme of the original client request (injected by server layers).
@@ -40,6 +55,147 @@ impl Scheme {
     }
 }
 
+/// How long to wait for a backend. `None` waits forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Until the TCP connection is established.
+    pub connect: Option<Duration>,
+    /// Until the response headers arrive, connecting included. Event
+    /// streams aren't limited.
+    pub request: Option<Duration>,
+    /// Between two chunks of an event stream.
+    pub stream_idle: Option<Duration>,
+}
+
+/// Config values are milliseconds, with 0 meaning no limit.
+fn millis(value: u64) -> Option<Duration> {
+    (value > 0).then(|| Duration::from_millis(value))
+}
+
+impl Timeouts {
+    pub fn from_config(daemon: &DaemonConfig) -> Self {
+        Self {
+            connect: millis(daemon.connect_timeout),
+            request: millis(daemon.request_timeout),
+            stream_idle: millis(daemon.stream_idle_timeout),
+        }
+    }
+
+    /// Apply a route's overrides.
+    pub fn for_route(self, options: &RouteOptions) -> Self {
+        Self {
+            connect: options.connect_timeout.map_or(self.connect, millis),
+            request: options.request_timeout.map_or(self.request, millis),
+            stream_idle: options.stream_idle_timeout.map_or(self.stream_idle, millis),
+        }
+    }
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self::from_config(&DaemonConfig::default())
+    }
+}
+
+/// Whether an I/O error of `kind` caused a client error.
+fn caused_by(error: &ClientError, kind: std::io::ErrorKind) -> bool {
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        if let Some(io) = e.downcast_ref::<std::io::Error>()
+            && io.kind() == kind
+        {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// Read a request body so it can be sent more than once.
+pub(super) async fn buffer_request(request: Request) -> Result<(Parts, Bytes), Response> {
+    let (parts, body) = request.into_parts();
+    match body.collect().await {
+        Ok(collected) => Ok((parts, collected.to_bytes())),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            format!("Could not read request body: {}", e),
+        )
+            .into_response()),
+    }
+}
+
+/// Send a buffered request, resending it while the backend refuses
+/// connections and the route's retry policy allows.
+async fn send_with_retry(
+    client: &Client<HttpConnector, Body>,
+    (parts, body): (Parts, Bytes),
+    policy: &RetryPolicy,
+    target: &ProxyTarget,
+) -> Result<Response<Incoming>, ClientError> {
+    let attempt = || {
+        let mut request = Request::new(Body::from(body.clone()));
+        *request.method_mut() = parts.method.clone();
+        *request.uri_mut() = parts.uri.clone();
+        *request.version_mut() = parts.version;
+        *request.headers_mut() = parts.headers.clone();
+        *request.extensions_mut() = parts.extensions.clone();
+        request
+    };
+
+    let mut retry = 0;
+    loop {
+        match client.request(attempt()).await {
+            Err(e)
+                if retry < policy.attempts
+                    && caused_by(&e, std::io::ErrorKind::ConnectionRefused) =>
+            {
+                let delay = policy.delay(retry);
+                retry += 1;
+                debug!(target = %target, retry, delay_ms = delay.as_millis() as u64, "Backend refused the connection, retrying");
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Marks the 502 page for a backend that refused the connection.
+#[derive(Debug, Clone, Copy)]
+pub struct BackendUnreachable;
+
+/// How an HTTP/1 client spelled its headers, set aside as the request
+/// arrives. hyper records the casing in an extension of a private type
+/// and writes it out whenever a request carries it, so it only goes back
+/// on requests for routes with `preserve_header_case`.
+#[derive(Clone)]
+pub struct HeaderCasing(Extensions);
+
+impl HeaderCasing {
+    /// Move what hyper recorded about the request into a `HeaderCasing`,
+    /// leaving the handles upgrades need in place.
+    pub fn set_aside<B>(mut request: axum::http::Request<B>) -> axum::http::Request<B> {
+        let mut recorded = std::mem::take(request.extensions_mut());
+        if let Some(on_upgrade) = recorded.remove::<OnUpgrade>() {
+            request.extensions_mut().insert(on_upgrade);
+        }
+        if let Some(protocol) = recorded.remove::<Protocol>() {
+            request.extensions_mut().insert(protocol);
+        }
+        request.extensions_mut().insert(HeaderCasing(recorded));
+        request
+    }
+
+    /// Take the casing set aside for `request`, to put on the request to
+    /// the backend.
+    fn take<B>(request: &mut axum::http::Request<B>) -> Extensions {
+        request
+            .extensions_mut()
+            .remove::<HeaderCasing>()
+            .map(|casing| casing.0)
+            .unwrap_or_default()
+    }
+}
+
 /// Client IP address (injected by server layers).
 #[derive(Clone, Copy)]
 pub struct ClientAddr(pub IpAddr);
@@ -52,8 +208,40 @@ fn build_xff_value(existing: Option<&str>, client_ip: IpAddr) -> String {
     }
 }
 
+/// gRPC only runs over HTTP/2. gRPC-Web is designed for HTTP/1.1
+/// and is left alone.
+fn is_grpc(request: &Request) -> bool {
+    request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|ct| {
+            ct.starts_with("application/grpc") && !ct.starts_with("application/grpc-web")
+        })
+}
+
+/// Whether to talk HTTP/2 (h2c) to the backend for this request.
+fn uses_http2(protocol: BackendProtocol, request: &Request) -> bool {
+    match protocol {
+        BackendProtocol::Auto => is_grpc(request),
+        BackendProtocol::H1 => false,
+        BackendProtocol::H2c => true,
+    }
+}
+
+/// Whether the client announced it accepts trailers (`TE: trailers`),
+/// which gRPC requires.
+fn accepts_trailers(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::TE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|s| s.split(','))
+        .any(|t| t.trim().eq_ignore_ascii_case("trailers"))
+}
+
 /// Check if request is a WebSocket upgrade
-fn is_websocket_upgrade(request: &Request) -> bool {
+pub(super) fn is_websocket_upgrade(request: &Request) -> bool {
     request
         .headers()
         .get(header::UPGRADE)
@@ -62,92 +250,67 @@ fn is_websocket_upgrade(request: &Request) -> bool {
         .unwrap_or(false)
 }
 
-/// Build HTTP upgrade request string to send to backend
+/// Build the upgrade request sent to the backend: the client's headers
+/// without hop-by-hop ones, except `Connection` and `Upgrade`, which the
+/// backend needs for the WebSocket handshake.
 fn build_upgrade_request(
     request: &Request,
     target: &ProxyTarget,
     host: &str,
     scheme: &str,
     client_ip: Option<IpAddr>,
-) -> String {
-    let path = request.uri().path();
-    let query = request
-        .uri()
-        .query()
-        .map(|q| format!("?{}", q))
-        .unwrap_or_default();
+    preserve_host: bool,
+) -> hyper::Request<Empty<Bytes>> {
+    let mut upgrade = hyper::Request::new(Empty::new());
+    if let Some(path) = request.uri().path_and_query() {
+        *upgrade.uri_mut() = Uri::from(path.clone());
+    }
 
-    let mut req = format!(
-        "GET {}{} HTTP/1.1\r\nHost: {}:{}\r\n",
-        path,
-        query,
-        target.host(),
-        target.port()
-    );
+    let headers = upgrade.headers_mut();
+    *headers = request.headers().clone();
+    let protocol = headers.get(header::UPGRADE).cloned();
 
-    // Forwarding headers
-    req.push_str(&format!("X-Forwarded-Host: {}\r\n", host));
-    req.push_str(&format!("X-Forwarded-Proto: {}\r\n", scheme));
-    if let Some(ip) = client_ip {
-        let existing = request
-            .headers()
-            .get(X_FORWARDED_FOR)
-            .and_then(|v| v.to_str().ok());
-        let xff = build_xff_value(existing, ip);
-        req.push_str(&format!("X-Forwarded-For: {}\r\n", xff));
+    // Set forwarding headers before removing Host
+    set_forwarding_headers(headers, host, scheme, client_ip);
+
+    // Strip hop-by-hop headers (RFC 7230 §6.1), then put back the ones
+    // the handshake needs
+    strip_hop_by_hop_headers(headers);
+    if let Some(protocol) = protocol {
+        headers.insert(header::UPGRADE, protocol);
+        headers.insert(header::CONNECTION, HeaderValue::from_static("Upgrade"));
     }
 
-    // Collect any extra hop-by-hop header names declared in the Connection header
-    // (e.g. "Connection: X-Secret, keep-alive") so we can skip them below.
-    // "upgrade" is excluded because the backend needs it for the WebSocket handshake.
-    let dynamic_hop_by_hop: Vec<String> = request
-        .headers()
-        .get_all(header::CONNECTION)
-        .iter()
-        .filter_map(|v| v.to_str().ok())
-        .flat_map(|s| s.split(','))
-        .map(|t| t.trim().to_ascii_lowercase())
-        .filter(|t| !t.is_empty() && t != "upgrade")
-        .collect();
-
-    // Copy remaining headers, skipping Host, forwarding headers, and hop-by-hop
-    // headers (RFC 7230 §6.1). Connection and Upgrade are kept because the
-    // backend needs them for the WebSocket handshake.
-    for (name, value) in request.headers() {
-        if name == header::HOST
-            || name.as_str() == X_FORWARDED_HOST
-            || name.as_str() == X_FORWARDED_PROTO
-            || name.as_str() == X_FORWARDED_FOR
-            || name == header::PROXY_AUTHENTICATE
-            || name == header::PROXY_AUTHORIZATION
-            || name == header::TE
-            || name == header::TRAILER
-            || name == header::TRANSFER_ENCODING
-            || name.as_str() == KEEP_ALIVE
-            || dynamic_hop_by_hop.contains(&name.as_str().to_ascii_lowercase())
-        {
-            continue;
+    let backend_host = if preserve_host {
+        host.to_string()
+    } else {
+        format!("{}:{}", target.host(), target.port())
+    };
+    match HeaderValue::from_str(&backend_host) {
+        Ok(value) => {
+            headers.insert(header::HOST, value);
         }
-        if let Ok(v) = value.to_str() {
-            req.push_str(&format!("{}: {}\r\n", name, v));
+        Err(_) => {
+            headers.remove(header::HOST);
         }
     }
-    req.push_str("\r\n");
-    req
+
+    upgrade
 }
 
 /// Proxy a WebSocket connection
 async fn proxy_websocket(
     target: &ProxyTarget,
-    request: Request,
+    mut request: Request,
     host: &str,
     scheme: &str,
     client_ip: Option<IpAddr>,
+    options: &RouteOptions,
 ) -> Response {
     // Connect to backend
     let backend_addr = format!("{}:{}", target.host(), target.port());
     debug!(target = %target, "Connecting to backend for WebSocket");
-    let mut backend = match TcpStream::connect(&backend_addr).await {
+    let backend = match TcpStream::connect(&backend_addr).await {
         Ok(s) => s,
         Err(_) => {
             warn!(target = %target, "WebSocket backend connection failed");
@@ -160,22 +323,39 @@ async fn proxy_websocket(
     };
     let start_time = Instant::now();
 
-    // Build and send the upgrade request to backend
-    let upgrade_request = build_upgrade_request(&request, target, host, scheme, client_ip);
+    let (mut sender, connection) = match http1::handshake(TokioIo::new(backend)).await {
+        Ok(handshake) => handshake,
+        Err(e) => {
+            return (
+                StatusCode::BAD_GATEWAY,
+                format!("Backend handshake error: {}", e),
+            )
+                .into_response();
+        }
+    };
+    tokio::spawn(async move {
+        if let Err(e) = connection.with_upgrades().await {
+            debug!(error = %e, "WebSocket backend connection error");
+        }
+    });
 
-    if let Err(e) = backend.write_all(upgrade_request.as_bytes()).await {
-        return (
-            StatusCode::BAD_GATEWAY,
-            format!("Backend write error: {}", e),
-        )
-            .into_response();
+    // Build and send the upgrade request to backend
+    let mut upgrade_request = build_upgrade_request(
+        &request,
+        target,
+        host,
+        scheme,
+        client_ip,
+        options.preserve_host,
+    );
+    if options.preserve_header_case {
+        upgrade_request
+            .extensions_mut()
+            .extend(HeaderCasing::take(&mut request));
     }
-    debug!(target = %target, "WebSocket upgrade request sent to backend");
 
-    // Read backend response (101 Switching Protocols expected)
-    let mut response_buf = vec![0u8; 4096];
-    let n = match backend.read(&mut response_buf).await {
-        Ok(n) => n,
+    let response = match sender.send_request(upgrade_request).await {
+        Ok(response) => response,
         Err(e) => {
             return (
                 StatusCode::BAD_GATEWAY,
@@ -184,14 +364,10 @@ async fn proxy_websocket(
                 .into_response();
         }
     };
+    debug!(target = %target, "WebSocket upgrade request sent to backend");
 
     // Verify we got 101 Switching Protocols
-    let response_str = String::from_utf8_lossy(&response_buf[..n]);
-    let is_101 = response_str
-        .lines()
-        .next()
-        .is_some_and(|line| line.contains("101"));
-    if !is_101 {
+    if response.status() != StatusCode::SWITCHING_PROTOCOLS {
         warn!(target = %target, "Backend rejected WebSocket upgrade");
         return (
             StatusCode::BAD_GATEWAY,
@@ -201,13 +377,10 @@ async fn proxy_websocket(
     }
 
     // Extract Sec-WebSocket-Accept from backend response
-    let accept_key = response_str
-        .lines()
-        .find(|line| line.to_lowercase().starts_with("sec-websocket-accept:"))
-        .and_then(|line| line.split(':').nth(1))
-        .map(|s| s.trim().to_string());
+    let accept_key = response.headers().get(header::SEC_WEBSOCKET_ACCEPT).cloned();
 
-    // Get the OnUpgrade handle from the request
+    // Get the OnUpgrade handles from both sides
+    let backend_upgrade = hyper::upgrade::on(response);
     let on_upgrade = hyper::upgrade::on(request);
 
     info!(target = %target, "WebSocket connection established");
@@ -215,64 +388,69 @@ async fn proxy_websocket(
     // Clone target for the spawned task
     let target_str = target.to_string();
 
-    // Spawn task to handle the bidirectional copy after upgrade
-    tokio::spawn(async move {
-        match on_upgrade.await {
-            Ok(upgraded) => {
-                let client = hyper_util::rt::TokioIo::new(upgraded);
-                let (mut client_read, mut client_write) = tokio::io::split(client);
-                let (mut backend_read, mut backend_write) = backend.into_split();
-
-                let client_to_backend = async {
-                    let mut buf = [0u8; 8192];
-                    loop {
-                        let n = match client_read.read(&mut buf).await {
-                            Ok(0) => break,
-                            Ok(n) => n,
-                            Err(_) => break,
-                        };
-                        if backend_write.write_all(&buf[..n]).await.is_err() {
-                            break;
+    // Spawn task to handle the bidirectional copy after upgrade.
+    // It stays in the request span so log level overrides still apply.
+    tokio::spawn(
+        async move {
+            match tokio::try_join!(on_upgrade, backend_upgrade) {
+                Ok((client, backend)) => {
+                    let (mut client_read, mut client_write) =
+                        tokio::io::split(TokioIo::new(client));
+                    let (mut backend_read, mut backend_write) =
+                        tokio::io::split(TokioIo::new(backend));
+
+                    let client_to_backend = async {
+                        let mut buf = [0u8; 8192];
+                        loop {
+                            let n = match client_read.read(&mut buf).await {
+                                Ok(0) => break,
+                                Ok(n) => n,
+                                Err(_) => break,
+                            };
+                            if backend_write.write_all(&buf[..n]).await.is_err() {
+                                break;
+                            }
                         }
-                    }
-                };
-
-                let backend_to_client = async {
-                    let mut buf = [0u8; 8192];
-                    loop {
-                        let n = match backend_read.read(&mut buf).await {
-                            Ok(0) => break,
-                            Ok(n) => n,
-                            Err(_) => break,
-                        };
-                        if client_write.write_all(&buf[..n]).await.is_err() {
-                            break;
+                    };
+
+                    let backend_to_client = async {
+                        let mut buf = [0u8; 8192];
+                        loop {
+                            let n = match backend_read.read(&mut buf).await {
+                                Ok(0) => break,
+                                Ok(n) => n,
+                                Err(_) => break,
+                            };
+                            if client_write.write_all(&buf[..n]).await.is_err() {
+                                break;
+                            }
                         }
-                    }
-                };
-
-                let closed_by = tokio::select! {
-                    _ = client_to_backend => "client",
-                    _ = backend_to_client => "backend",
-                };
-
-                let duration = start_time.elapsed();
-                info!(
-                    target = %target_str,
-                    duration_ms = duration.as_millis() as u64,
-                    "WebSocket connection closed"
-                );
-                debug!(
-                    target = %target_str,
-                    closed_by = closed_by,
-                    "WebSocket close details"
-                );
-            }
-            Err(e) => {
-                warn!(target = %target_str, error = %e, "WebSocket upgrade failed");
+                    };
+
+                    let closed_by = tokio::select! {
+                        _ = client_to_backend => "client",
+                        _ = backend_to_client => "backend",
+                    };
+
+                    let duration = start_time.elapsed();
+                    info!(
+                        target = %target_str,
+                        duration_ms = duration.as_millis() as u64,
+                        "WebSocket connection closed"
+                    );
+                    debug!(
+                        target = %target_str,
+                        closed_by = closed_by,
+                        "WebSocket close details"
+                    );
+                }
+                Err(e) => {
+                    warn!(target = %target_str, error = %e, "WebSocket upgrade failed");
+                }
             }
         }
-    });
+        .in_current_span(),
+    );
 
     // Build 101 Switching Protocols response for client
     let mut builder = Response::builder()
@@ -281,7 +459,7 @@ async fn proxy_websocket(
         .header(header::CONNECTION, "Upgrade");
 
     if let Some(key) = accept_key {
-        builder = builder.header("Sec-WebSocket-Accept", key);
+        builder = builder.header(header::SEC_WEBSOCKET_ACCEPT, key);
     }
 
     builder.body(Body::empty()).unwrap_or_else(|_| {
@@ -293,7 +471,17 @@ async fn proxy_websocket(
     })
 }
 
-/// Set `X-Forwarded-For`, `X-Forwarded-Proto`, and `X-Forwarded-Host` headers.
+/// The port the client connected to: the one in `Host`, or the scheme's
+/// default. Behind a port forward this differs from the one Roxy
+/// listens on.
+fn forwarded_port(host: &str, scheme: &str) -> u16 {
+    host.rsplit_once(':')
+        .and_then(|(_, port)| port.parse().ok())
+        .unwrap_or(if scheme == "https" { 443 } else { 80 })
+}
+
+/// Set `X-Forwarded-For`, `X-Forwarded-Proto`, `X-Forwarded-Port`, and
+/// `X-Forwarded-Host` headers.
 fn set_forwarding_headers(
     headers: &mut HeaderMap,
     host: &str,
@@ -308,6 +496,11 @@ fn set_forwarding_headers(
         headers.insert(X_FORWARDED_PROTO, value);
     }
 
+    headers.insert(
+        X_FORWARDED_PORT,
+        HeaderValue::from(forwarded_port(host, scheme)),
+    );
+
     if let Some(ip) = client_ip {
         let existing = headers.get(X_FORWARDED_FOR).and_then(|v| v.to_str().ok());
         let xff = build_xff_value(existing, ip);
@@ -319,6 +512,7 @@ fn set_forwarding_headers(
     debug!(
         x_forwarded_host = %host,
         x_forwarded_proto = %scheme,
+        x_forwarded_port = forwarded_port(host, scheme),
         x_forwarded_for = %client_ip.map(|ip| ip.to_string()).unwrap_or_default(),
         "Forwarding headers set"
     );
@@ -327,7 +521,7 @@ fn set_forwarding_headers(
 /// Remove hop-by-hop headers that must not be forwarded (RFC 7230 §6.1).
 ///
 /// Also strips any extra headers listed in the `Connection` header value.
-fn strip_hop_by_hop_headers(headers: &mut HeaderMap) {
+pub fn strip_hop_by_hop_headers(headers: &mut HeaderMap) {
     // Collect headers named in all Connection values (e.g. "Connection: X-Custom, keep-alive").
     // Connection can legally appear multiple times (RFC 7230 §6.1).
     let connection_headers: Vec<HeaderName> = headers
@@ -352,18 +546,47 @@ fn strip_hop_by_hop_headers(headers: &mut HeaderMap) {
     }
 }
 
-/// Proxy a request to a backend (supports HTTP/1.1, HTTP/2, and WebSocket)
+/// Apply a route's cookie rules to every `Set-Cookie` header.
+fn rewrite_set_cookies(headers: &mut HeaderMap, rules: &CookieRewrite) {
+    let rewritten: Vec<HeaderValue> = headers
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| {
+            let Ok(cookie) = v.to_str() else {
+                return Some(v.clone());
+            };
+            HeaderValue::from_str(&rules.apply(cookie)).ok()
+        })
+        .collect();
+
+    headers.remove(header::SET_COOKIE);
+    for value in rewritten {
+        headers.append(header::SET_COOKIE, value);
+    }
+}
+
+/// Proxy a request to a backend (supports HTTP/1.1, h2c, and WebSocket)
 pub async fn proxy_request(
     target: &ProxyTarget,
     request: Request,
     host: &str,
     scheme: &str,
     client_ip: Option<IpAddr>,
+    options: &RouteOptions,
+    timeouts: Timeouts,
 ) -> Response {
     // Check for WebSocket upgrade
     if is_websocket_upgrade(&request) {
         debug!(target = %target, "Proxying WebSocket request");
-        return proxy_websocket(target, request, host, scheme, client_ip).await;
+        return proxy_websocket(
+            target,
+            request,
+            host,
+            scheme,
+            client_ip,
+            options,
+        )
+        .await;
     }
 
     debug!(target = %target, "Proxying HTTP request");
@@ -371,8 +594,14 @@ pub async fn proxy_request(
     // Regular HTTP proxy
     let mut connector = HttpConnector::new();
     connector.set_nodelay(true);
+    connector.set_connect_timeout(timeouts.connect);
 
-    let client = Client::builder(TokioExecutor::new()).build(connector);
+    let http2 = uses_http2(options.protocol, &request);
+    let mut builder = Client::builder(TokioExecutor::new());
+    builder
+        .http1_preserve_header_case(options.preserve_header_case)
+        .http2_only(http2);
+    let client = builder.build(connector);
 
     // Rewrite the URI to target the backend
     let path = request.uri().path();
@@ -399,33 +628,109 @@ pub async fn proxy_request(
     let mut request = request;
     *request.uri_mut() = uri;
 
+    // The client's HTTP version says nothing about the backend's
+    let client_version = request.version();
+    *request.version_mut() = match client_version {
+        _ if http2 => Version::HTTP_2,
+        Version::HTTP_2 | Version::HTTP_3 => Version::HTTP_11,
+        version => version,
+    };
+    let trailers = accepts_trailers(request.headers());
+
+    // The casing recorded by the server only reaches routes that ask
+    if options.preserve_header_case {
+        let casing = HeaderCasing::take(&mut request);
+        request.extensions_mut().extend(casing);
+    }
+
     // Set forwarding headers before removing Host
     set_forwarding_headers(request.headers_mut(), host, scheme, client_ip);
 
-    // Remove original Host header (hyper client sets it for the target)
-    request.headers_mut().remove(header::HOST);
+    // Without a Host header, the hyper client sets one for the target
+    if options.preserve_host
+        && let Ok(value) = HeaderValue::from_str(host)
+    {
+        request.headers_mut().insert(header::HOST, value);
+    } else {
+        request.headers_mut().remove(header::HOST);
+    }
 
     // Strip hop-by-hop headers
     strip_hop_by_hop_headers(request.headers_mut());
 
+    // HTTP/2 allows `TE: trailers` end to end, and gRPC servers insist
+    // on it
+    if http2 && trailers {
+        request
+            .headers_mut()
+            .insert(header::TE, HeaderValue::from_static("trailers"));
+    }
+
+    // Compressed bodies can't be rewritten, so ask for plain ones
+    if !options.rewrite.is_empty() {
+        request.headers_mut().remove(header::ACCEPT_ENCODING);
+    }
+
+    let request_timeout = timeouts
+        .request
+        .filter(|_| !streaming::accepts_event_stream(request.headers()));
+
     // Forward the request
-    match client.request(request).await {
+    // Retries resend the body, so it has to be kept around
+    let send: Pin<Box<dyn Future<Output = _> + Send>> = if options
+        .retry
+        .applies_to(request.method().as_str())
+    {
+        match buffer_request(request).await {
+            Ok(buffered) => Box::pin(send_with_retry(&client, buffered, &options.retry, target)),
+            Err(response) => return response,
+        }
+    } else {
+        Box::pin(client.request(request))
+    };
+    let response = match request_timeout {
+        Some(limit) => match tokio::time::timeout(limit, send).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!(target = %target, timeout_ms = limit.as_millis() as u64, "Backend did not respond in time");
+                return build_gateway_timeout_response(target, "respond", limit);
+            }
+        },
+        None => send.await,
+    };
+
+    match response {
         Ok(response) => {
             debug!(target = %target, status = %response.status(), "Proxy response");
             let (mut parts, body) = response.into_parts();
+            parts.version = client_version;
             strip_hop_by_hop_headers(&mut parts.headers);
-            Response::from_parts(parts, Body::new(body))
+            if !options.cookies.is_empty() {
+                rewrite_set_cookies(&mut parts.headers, &options.cookies);
+            }
+            let body = match timeouts.stream_idle {
+                Some(idle) if streaming::is_event_stream(&parts.headers) => {
+                    debug!(target = %target, "Streaming event stream");
+                    Body::new(IdleTimeout::new(body, idle))
+                }
+                _ if options.rewrite.is_empty() => Body::new(body),
+                _ => {
+                    body_rewrite::rewrite_response(&options.rewrite, &mut parts.headers, body).await
+                }
+            };
+            Response::from_parts(parts, body)
         }
         Err(e) => {
             // Check if it's a connection error (service not running)
-            let error_msg = e.to_string();
-            if error_msg.contains("Connection refused") {
+            if let Some(limit) = timeouts
+                .connect
+                .filter(|_| caused_by(&e, std::io::ErrorKind::TimedOut))
+            {
+                warn!(target = %target, timeout_ms = limit.as_millis() as u64, "Backend connection timed out");
+                build_gateway_timeout_response(target, "accept the connection", limit)
+            } else if caused_by(&e, std::io::ErrorKind::ConnectionRefused) {
                 warn!(target = %target, "Service not running");
-                (
-                    StatusCode::BAD_GATEWAY,
-                    format!("Service not running at {}", target),
-                )
-                    .into_response()
+                build_backend_down_response(target, None)
             } else {
                 warn!(target = %target, error = %e, "Proxy failed");
                 (StatusCode::BAD_GATEWAY, format!("Proxy error: {}", e)).into_response()
@@ -487,6 +792,294 @@ mod tests {
         assert_eq!(build_xff_value(None, ip), "::1");
     }
 
+    // --- rewrite_set_cookies ---
+
+    #[test]
+    fn rewrites_every_set_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.append(header::SET_COOKIE, HeaderValue::from_static("a=1; Secure"));
+        headers.append(header::SET_COOKIE, HeaderValue::from_static("b=2; Secure"));
+        let rules = CookieRewrite {
+            secure: Some(false),
+            ..CookieRewrite::default()
+        };
+
+        rewrite_set_cookies(&mut headers, &rules);
+
+        let cookies: Vec<_> = headers.get_all(header::SET_COOKIE).iter().collect();
+        assert_eq!(cookies, ["a=1", "b=2"]);
+    }
+
+    // --- uses_http2 ---
+
+    fn request_with_content_type(content_type: &str) -> Request<Body> {
+        Request::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn auto_uses_http2_for_grpc_only() {
+        let grpc = request_with_content_type("application/grpc+proto");
+        let grpc_web = request_with_content_type("application/grpc-web+proto");
+        let json = request_with_content_type("application/json");
+
+        assert!(uses_http2(BackendProtocol::Auto, &grpc));
+        assert!(!uses_http2(BackendProtocol::Auto, &grpc_web));
+        assert!(!uses_http2(BackendProtocol::Auto, &json));
+        assert!(!uses_http2(BackendProtocol::H1, &grpc));
+        assert!(uses_http2(BackendProtocol::H2c, &json));
+    }
+
+    #[test]
+    fn detects_te_trailers() {
+        let mut headers = HeaderMap::new();
+        assert!(!accepts_trailers(&headers));
+        headers.insert(header::TE, HeaderValue::from_static("gzip, Trailers"));
+        assert!(accepts_trailers(&headers));
+    }
+
+    #[tokio::test]
+    async fn h2c_backend_keeps_trailers() {
+        use axum::body::Bytes;
+        use http_body_util::{BodyExt, Full};
+        use hyper_util::rt::TokioIo;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let service = hyper::service::service_fn(|request: Request<hyper::body::Incoming>| {
+                let te = request.headers().get(header::TE).cloned();
+                async move {
+                    let mut trailers = HeaderMap::new();
+                    trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                    let body = Full::new(Bytes::from_static(b"ok"))
+                        .with_trailers(async move { Some(Ok(trailers)) });
+                    let mut response = hyper::Response::new(body);
+                    if let Some(te) = te {
+                        response.headers_mut().insert("x-te", te);
+                    }
+                    Ok::<_, std::convert::Infallible>(response)
+                }
+            });
+            hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+                .unwrap();
+        });
+
+        let target = ProxyTarget::parse(&port.to_string()).unwrap();
+        let options = RouteOptions {
+            protocol: BackendProtocol::H2c,
+            ..RouteOptions::default()
+        };
+        let request = Request::builder()
+            .uri("/helloworld.Greeter/SayHello")
+            .header(header::CONTENT_TYPE, "application/grpc")
+            .header(header::TE, "trailers")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = proxy_request(
+            &target,
+            request,
+            "grpc.roxy",
+            "http",
+            None,
+            &options,
+            Timeouts::default(),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.version(), Version::HTTP_11);
+        assert_eq!(response.headers().get("x-te").unwrap(), "trailers");
+        let collected = response.into_body().collect().await.unwrap();
+        assert_eq!(collected.trailers().unwrap()["grpc-status"], "0");
+        assert_eq!(collected.to_bytes(), "ok");
+    }
+
+    // --- timeouts ---
+
+    #[test]
+    fn route_overrides_daemon_timeouts() {
+        let daemon = DaemonConfig {
+            connect_timeout: 250,
+            request_timeout: 0,
+            ..DaemonConfig::default()
+        };
+        let timeouts = Timeouts::from_config(&daemon);
+        assert_eq!(timeouts.connect, Some(Duration::from_millis(250)));
+        assert_eq!(timeouts.request, None);
+
+        let options = RouteOptions {
+            connect_timeout: Some(0),
+            request_timeout: Some(1_500),
+            ..RouteOptions::default()
+        };
+        let overridden = timeouts.for_route(&options);
+        assert_eq!(overridden.connect, None);
+        assert_eq!(overridden.request, Some(Duration::from_millis(1_500)));
+        assert_eq!(timeouts.for_route(&RouteOptions::default()), timeouts);
+    }
+
+    #[tokio::test]
+    async fn hung_backend_gets_gateway_timeout() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            // Accept and hold the connection without ever responding
+            let (_stream, _) = listener.accept().await.unwrap();
+            std::future::pending::<()>().await;
+        });
+
+        let target = ProxyTarget::parse(&port.to_string()).unwrap();
+        let timeouts = Timeouts {
+            connect: None,
+            request: Some(Duration::from_millis(100)),
+            stream_idle: None,
+        };
+        let request = Request::builder().uri("/").body(Body::empty()).unwrap();
+
+        let response = proxy_request(
+            &target,
+            request,
+            "hung.roxy",
+            "http",
+            None,
+            &RouteOptions::default(),
+            timeouts,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    // --- retry ---
+
+    /// A port with nothing listening that starts answering `200 OK`
+    /// after `delay`.
+    async fn backend_starting_after(delay: Duration) -> ProxyTarget {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+                .await
+                .unwrap();
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 1024];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+                    .await;
+            }
+        });
+        ProxyTarget::parse(&port.to_string()).unwrap()
+    }
+
+    async fn proxy_with_retry(target: &ProxyTarget, method: &str) -> Response {
+        let options = RouteOptions {
+            retry: RetryPolicy {
+                attempts: 5,
+                backoff: 50,
+                ..RetryPolicy::default()
+            },
+            ..RouteOptions::default()
+        };
+        let request = Request::builder()
+            .method(method)
+            .uri("/")
+            .body(Body::empty())
+            .unwrap();
+        proxy_request(
+            target,
+            request,
+            "restarting.roxy",
+            "http",
+            None,
+            &options,
+            Timeouts::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn retries_while_backend_restarts() {
+        let target = backend_starting_after(Duration::from_millis(150)).await;
+        let response = proxy_with_retry(&target, "GET").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_unlisted_methods() {
+        let target = backend_starting_after(Duration::from_millis(150)).await;
+        let response = proxy_with_retry(&target, "POST").await;
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert!(response.extensions().get::<BackendUnreachable>().is_some());
+    }
+
+    // --- preserve_host ---
+
+    /// Proxy to a backend that answers with the Host header it got.
+    async fn backend_host(options: &RouteOptions) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let n = stream.read(&mut buf).await.unwrap();
+            let head = String::from_utf8_lossy(&buf[..n]).to_string();
+            let host = head
+                .lines()
+                .find_map(|l| l.strip_prefix("host: "))
+                .unwrap_or_default()
+                .to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
+                host.len(),
+                host
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+
+        let request = Request::builder()
+            .uri("/")
+            .header(header::HOST, "myapp.roxy")
+            .body(Body::empty())
+            .unwrap();
+        let target = ProxyTarget::parse(&port.to_string()).unwrap();
+        let response = proxy_request(
+            &target,
+            request,
+            "myapp.roxy",
+            "http",
+            None,
+            options,
+            Timeouts::default(),
+        )
+        .await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn sends_backend_host_unless_preserved() {
+        let host = backend_host(&RouteOptions::default()).await;
+        assert!(host.starts_with("127.0.0.1:"));
+
+        let options = RouteOptions {
+            preserve_host: true,
+            ..RouteOptions::default()
+        };
+        assert_eq!(backend_host(&options).await, "myapp.roxy");
+    }
+
     // --- is_websocket_upgrade ---
 
     #[test]
@@ -531,9 +1124,19 @@ mod tests {
 
         assert_eq!(headers.get(X_FORWARDED_HOST).unwrap(), "myapp.roxy");
         assert_eq!(headers.get(X_FORWARDED_PROTO).unwrap(), "https");
+        assert_eq!(headers.get(X_FORWARDED_PORT).unwrap(), "443");
         assert_eq!(headers.get(X_FORWARDED_FOR).unwrap(), "127.0.0.1");
     }
 
+    #[test]
+    fn forwarded_port_comes_from_host_or_scheme() {
+        assert_eq!(forwarded_port("myapp.roxy:8443", "https"), 8443);
+        assert_eq!(forwarded_port("myapp.roxy", "https"), 443);
+        assert_eq!(forwarded_port("myapp.roxy", "http"), 80);
+        assert_eq!(forwarded_port("[::1]:8080", "http"), 8080);
+        assert_eq!(forwarded_port("[::1]", "http"), 80);
+    }
+
     #[test]
     fn forwarding_headers_without_client_ip() {
         let mut headers = HeaderMap::new();
@@ -655,11 +1258,14 @@ mod tests {
         let req = ws_request("/ws");
         let target = make_target();
 
-        let raw = build_upgrade_request(&req, &target, "myapp.roxy", "https", Some(LOCALHOST));
+        let upgrade =
+            build_upgrade_request(&req, &target, "myapp.roxy", "https", Some(LOCALHOST), false);
 
-        assert!(raw.contains("X-Forwarded-Host: myapp.roxy\r\n"));
-        assert!(raw.contains("X-Forwarded-Proto: https\r\n"));
-        assert!(raw.contains("X-Forwarded-For: 127.0.0.1\r\n"));
+        let headers = upgrade.headers();
+        assert_eq!(headers[X_FORWARDED_HOST], "myapp.roxy");
+        assert_eq!(headers[X_FORWARDED_PROTO], "https");
+        assert_eq!(headers[X_FORWARDED_PORT], "443");
+        assert_eq!(headers[X_FORWARDED_FOR], "127.0.0.1");
     }
 
     #[test]
@@ -667,11 +1273,12 @@ mod tests {
         let req = ws_request("/ws");
         let target = make_target();
 
-        let raw = build_upgrade_request(&req, &target, "myapp.roxy", "https", None);
+        let upgrade = build_upgrade_request(&req, &target, "myapp.roxy", "https", None, false);
 
-        assert!(raw.contains("X-Forwarded-Host: myapp.roxy\r\n"));
-        assert!(raw.contains("X-Forwarded-Proto: https\r\n"));
-        assert!(!raw.contains("X-Forwarded-For"));
+        let headers = upgrade.headers();
+        assert_eq!(headers[X_FORWARDED_HOST], "myapp.roxy");
+        assert_eq!(headers[X_FORWARDED_PROTO], "https");
+        assert!(!headers.contains_key(X_FORWARDED_FOR));
     }
 
     #[test]
@@ -686,9 +1293,10 @@ mod tests {
             .unwrap();
         let target = make_target();
 
-        let raw = build_upgrade_request(&req, &target, "myapp.roxy", "https", Some(LOCALHOST));
+        let upgrade =
+            build_upgrade_request(&req, &target, "myapp.roxy", "https", Some(LOCALHOST), false);
 
-        assert!(raw.contains("X-Forwarded-For: 10.0.0.1, 127.0.0.1\r\n"));
+        assert_eq!(upgrade.headers()[X_FORWARDED_FOR], "10.0.0.1, 127.0.0.1");
     }
 
     #[test]
@@ -696,14 +1304,23 @@ mod tests {
         let req = ws_request("/ws");
         let target = make_target();
 
-        let raw = build_upgrade_request(&req, &target, "myapp.roxy", "https", Some(LOCALHOST));
+        let upgrade =
+            build_upgrade_request(&req, &target, "myapp.roxy", "https", Some(LOCALHOST), false);
 
         // Should have the backend Host, not the original
-        assert!(raw.contains("Host: 127.0.0.1:3000\r\n"));
-        // The original Host header should NOT appear as a repeated header.
-        // Count lines starting with "Host:" (not substring matches like "X-Forwarded-Host:").
-        let host_count = raw.lines().filter(|l| l.starts_with("Host:")).count();
-        assert_eq!(host_count, 1);
+        let hosts: Vec<_> = upgrade.headers().get_all(header::HOST).iter().collect();
+        assert_eq!(hosts, ["127.0.0.1:3000"]);
+    }
+
+    #[test]
+    fn upgrade_request_can_preserve_original_host() {
+        let req = ws_request("/ws");
+        let target = make_target();
+
+        let upgrade = build_upgrade_request(&req, &target, "myapp.roxy", "https", None, true);
+
+        let hosts: Vec<_> = upgrade.headers().get_all(header::HOST).iter().collect();
+        assert_eq!(hosts, ["myapp.roxy"]);
     }
 
     #[test]
@@ -711,10 +1328,10 @@ mod tests {
         let req = ws_request("/ws");
         let target = make_target();
 
-        let raw = build_upgrade_request(&req, &target, "myapp.roxy", "https", None);
+        let upgrade = build_upgrade_request(&req, &target, "myapp.roxy", "https", None, false);
 
-        assert!(raw.contains("upgrade: websocket\r\n"));
-        assert!(raw.contains("connection: Upgrade\r\n"));
+        assert_eq!(upgrade.headers()[header::UPGRADE], "websocket");
+        assert_eq!(upgrade.headers()[header::CONNECTION], "Upgrade");
     }
 
     #[test]
@@ -733,13 +1350,14 @@ mod tests {
             .unwrap();
         let target = make_target();
 
-        let raw = build_upgrade_request(&req, &target, "myapp.roxy", "https", None);
+        let upgrade = build_upgrade_request(&req, &target, "myapp.roxy", "https", None, false);
 
-        assert!(!raw.contains("proxy-authorization:"));
-        assert!(!raw.contains("te:"));
-        assert!(!raw.contains("trailer:"));
-        assert!(!raw.contains("transfer-encoding:"));
-        assert!(!raw.contains("keep-alive:"));
+        let headers = upgrade.headers();
+        assert!(!headers.contains_key(header::PROXY_AUTHORIZATION));
+        assert!(!headers.contains_key(header::TE));
+        assert!(!headers.contains_key(header::TRAILER));
+        assert!(!headers.contains_key(header::TRANSFER_ENCODING));
+        assert!(!headers.contains_key(KEEP_ALIVE));
     }
 
     #[test]
@@ -755,10 +1373,11 @@ mod tests {
             .unwrap();
         let target = make_target();
 
-        let raw = build_upgrade_request(&req, &target, "myapp.roxy", "https", None);
+        let upgrade = build_upgrade_request(&req, &target, "myapp.roxy", "https", None, false);
 
-        assert!(!raw.contains("x-secret:"));
-        assert!(raw.contains("x-safe: kept\r\n"));
+        assert!(!upgrade.headers().contains_key("x-secret"));
+        assert_eq!(upgrade.headers()["x-safe"], "kept");
+        assert_eq!(upgrade.headers()[header::UPGRADE], "websocket");
     }
 
     #[test]
@@ -766,18 +1385,143 @@ mod tests {
         let req = ws_request("/ws?token=abc");
         let target = make_target();
 
-        let raw = build_upgrade_request(&req, &target, "myapp.roxy", "https", None);
+        let upgrade = build_upgrade_request(&req, &target, "myapp.roxy", "https", None, false);
 
-        assert!(raw.starts_with("GET /ws?token=abc HTTP/1.1\r\n"));
+        assert_eq!(upgrade.uri(), "/ws?token=abc");
     }
 
-    #[test]
-    fn upgrade_request_ends_with_blank_line() {
-        let req = ws_request("/ws");
-        let target = make_target();
+    // --- preserve_header_case ---
+
+    /// A backend that answers one request with `response` and hands
+    /// back the head it received, then echoes whatever follows.
+    async fn recording_backend(
+        response: &'static str,
+    ) -> (u16, tokio::sync::oneshot::Receiver<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !head.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    return;
+                }
+                head.extend_from_slice(&buf[..n]);
+            }
+            let _ = tx.send(String::from_utf8_lossy(&head).into_owned());
+            stream.write_all(response.as_bytes()).await.unwrap();
+            loop {
+                match stream.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if stream.write_all(&buf[..n]).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        (port, rx)
+    }
+
+    /// Serve a route to `backend` the way the daemon does and send it
+    /// `request` as raw bytes, returning the connection to the client.
+    async fn send_through_route(backend: u16, preserve: bool, request: &str) -> TcpStream {
+        let target = ProxyTarget::parse(&backend.to_string()).unwrap();
+        let options = RouteOptions {
+            preserve_header_case: preserve,
+            ..Default::default()
+        };
+        let router = axum::Router::new().fallback(move |request: Request<Body>| {
+            let (target, options) = (target.clone(), options.clone());
+            async move {
+                proxy_request(
+                    &target,
+                    request,
+                    "myapp.roxy",
+                    "http",
+                    None,
+                    &options,
+                    Timeouts::default(),
+                )
+                .await
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            crate::daemon::server::serve_connection(stream, router).await;
+        });
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(request.as_bytes()).await.unwrap();
+        client
+    }
+
+    const CASED_REQUEST: &str =
+        "GET / HTTP/1.1\r\nHost: myapp.roxy\r\nX-Custom-Header: yes\r\n\r\n";
+
+    const CASED_UPGRADE: &str = "GET /ws HTTP/1.1\r\nHost: myapp.roxy\r\n\
+        Upgrade: websocket\r\nConnection: Upgrade\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nX-Custom-Header: yes\r\n\r\n";
+
+    const SWITCHING: &str = "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+        Connection: Upgrade\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n";
+
+    #[tokio::test]
+    async fn backend_sees_the_original_header_casing_when_asked() {
+        let (backend, head) = recording_backend("HTTP/1.1 204 No Content\r\n\r\n").await;
+
+        let _client = send_through_route(backend, true, CASED_REQUEST).await;
+
+        let head = head.await.unwrap();
+        assert!(head.contains("\r\nX-Custom-Header: yes\r\n"), "{head}");
+    }
+
+    #[tokio::test]
+    async fn backend_sees_lowercase_headers_by_default() {
+        let (backend, head) = recording_backend("HTTP/1.1 204 No Content\r\n\r\n").await;
+
+        let _client = send_through_route(backend, false, CASED_REQUEST).await;
+
+        let head = head.await.unwrap();
+        assert!(head.contains("\r\nx-custom-header: yes\r\n"), "{head}");
+    }
+
+    #[tokio::test]
+    async fn websocket_backend_sees_the_original_header_casing_when_asked() {
+        let (backend, head) = recording_backend(SWITCHING).await;
+
+        let mut client = send_through_route(backend, true, CASED_UPGRADE).await;
+
+        let head = head.await.unwrap();
+        assert!(head.contains("\r\nX-Custom-Header: yes\r\n"), "{head}");
+        assert!(head.contains("\r\nSec-WebSocket-Key: "), "{head}");
+
+        // The tunnel still carries frames both ways
+        let mut response = vec![0u8; 1024];
+        let n = client.read(&mut response).await.unwrap();
+        let response = String::from_utf8_lossy(&response[..n]);
+        assert!(response.starts_with("HTTP/1.1 101"), "{response}");
+        assert!(response.contains("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="), "{response}");
+        client.write_all(b"ping").await.unwrap();
+        let mut echo = [0u8; 4];
+        client.read_exact(&mut echo).await.unwrap();
+        assert_eq!(&echo, b"ping");
+    }
+
+    #[tokio::test]
+    async fn websocket_backend_sees_lowercase_headers_by_default() {
+        let (backend, head) = recording_backend(SWITCHING).await;
 
-        let raw = build_upgrade_request(&req, &target, "myapp.roxy", "https", None);
+        let _client = send_through_route(backend, false, CASED_UPGRADE).await;
 
-        assert!(raw.ends_with("\r\n\r\n"));
+        let head = head.await.unwrap();
+        assert!(head.contains("\r\nx-custom-header: yes\r\n"), "{head}");
+        assert!(!head.contains("X-Custom-Header"), "{head}");
     }
 }
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::{
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+
+use crate::domain::RateLimit;
+
+/// Token buckets per domain. Like the request stats they live outside
+/// the routing state, so a reload doesn't hand out a fresh burst.
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Take a token from `key`'s bucket, or say how long until the next
+    /// one is available.
+    pub fn acquire(&self, key: &str, limit: &RateLimit) -> Result<(), Duration> {
+        self.acquire_at(key, limit, Instant::now())
+    }
+
+    fn acquire_at(&self, key: &str, limit: &RateLimit, now: Instant) -> Result<(), Duration> {
+        let rate = f64::from(limit.requests_per_second.max(1));
+        let burst = f64::from(limit.burst());
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        let refill = now.saturating_duration_since(bucket.updated).as_secs_f64() * rate;
+        bucket.tokens = (bucket.tokens + refill).min(burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+}
+
+/// 429 telling the client when to try again.
+pub fn too_many_requests(retry_after: Duration) -> Response {
+    // Retry-After takes whole seconds; rounding down would invite
+    // another rejected request
+    let seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, seconds.to_string())],
+        "Too many requests",
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(requests_per_second: u32, burst: Option<u32>) -> RateLimit {
+        RateLimit {
+            requests_per_second,
+            burst,
+        }
+    }
+
+    #[test]
+    fn allows_a_burst_then_refills() {
+        let limiter = RateLimiter::default();
+        let limit = limit(2, Some(3));
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.acquire_at("app", &limit, start).is_ok());
+        }
+        assert_eq!(
+            limiter.acquire_at("app", &limit, start),
+            Err(Duration::from_millis(500))
+        );
+
+        // Half a second buys one request at 2/s
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.acquire_at("app", &limit, later).is_ok());
+        assert!(limiter.acquire_at("app", &limit, later).is_err());
+    }
+
+    #[test]
+    fn domains_have_separate_buckets() {
+        let limiter = RateLimiter::default();
+        let limit = limit(1, None);
+        let now = Instant::now();
+        assert!(limiter.acquire_at("a", &limit, now).is_ok());
+        assert!(limiter.acquire_at("a", &limit, now).is_err());
+        assert!(limiter.acquire_at("b", &limit, now).is_ok());
+    }
+
+    #[test]
+    fn retry_after_rounds_up() {
+        let response = too_many_requests(Duration::from_millis(200));
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+        let response = too_many_requests(Duration::from_millis(2100));
+        assert_eq!(response.headers()[header::RETRY_AFTER], "3");
+    }
+}
@@ -0,0 +1,324 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use super::dns_server::{Answers, DnsServer, DnsTask, IpResolver};
+use super::mdns::{MdnsTask, local_names};
+use super::proxy::Timeouts;
+use super::router::{AppState, SharedState};
+use super::tls::{DomainCerts, SharedAcceptor, create_tls_acceptor};
+use crate::domain::{DomainPattern, DomainRegistration};
+use crate::infrastructure::certs::{CertificateService, CertsConfig};
+use crate::infrastructure::config::{ConfigStore, DaemonConfig, DomainChanges};
+use crate::infrastructure::dns::hosts::{HostsFile, hostnames};
+use crate::infrastructure::dns::{DnsBackend, get_dns_service};
+use crate::infrastructure::paths::RoxyPaths;
+use crate::infrastructure::service_user::is_root;
+use crate::infrastructure::tls::TlsConfig;
+
+/// Applies the config file to the running daemon without a restart.
+///
+/// Everything that can fail (parsing, certificates, binding a new DNS
+/// port) happens before any running state is replaced, so a bad config
+/// or a busy port leaves the daemon exactly as it was.
+pub struct Reloader {
+    config_path: PathBuf,
+    paths: RoxyPaths,
+    /// Ports the HTTP and HTTPS listeners were bound with.
+    daemon: DaemonConfig,
+    state: SharedState,
+    tls: Option<SharedAcceptor>,
+    /// `None` when domains are resolved through the hosts file.
+    dns: Mutex<Option<DnsTask>>,
+    /// `None` unless `.local` names are published.
+    mdns: Option<MdnsTask>,
+}
+
+impl Reloader {
+    pub fn new(
+        config_path: PathBuf,
+        paths: RoxyPaths,
+        daemon: DaemonConfig,
+        state: SharedState,
+        tls: Option<SharedAcceptor>,
+        dns: Option<DnsTask>,
+    ) -> Self {
+        Self {
+            config_path,
+            paths,
+            daemon,
+            state,
+            tls,
+            dns: Mutex::new(dns),
+            mdns: None,
+        }
+    }
+
+    pub fn with_mdns(mut self, mdns: Option<MdnsTask>) -> Self {
+        self.mdns = mdns;
+        self
+    }
+
+    /// The config file `reload` reads.
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
+
+    /// Reload the config file and return a summary for the user.
+    pub async fn reload(&self) -> Result<String> {
+        let config = ConfigStore::new(self.config_path.clone()).load()?;
+        config
+            .validate()
+            .context("Configuration validation failed")?;
+
+        let registrations = config.registrations();
+        let domains = DomainCerts::new(&registrations);
+        let renewed = renew_certificates(
+            &self.paths,
+            &domains.issued,
+            config.daemon.cert_renewal_days,
+            &config.certs,
+        );
+        let acceptor = create_tls_acceptor(
+            &domains,
+            &self.paths.certs_dir,
+            &self.paths.data_dir,
+            config.daemon.localhost,
+            &config.certs,
+            &config.tls,
+        )?;
+
+        let dns_answers = Answers::from_config(&config.daemon).with_records(&registrations);
+
+        // Holding the lock keeps two reloads from racing for the DNS port
+        let mut dns = self.dns.lock().await;
+        let dns_moved = dns
+            .as_ref()
+            .is_some_and(|task| config.daemon.dns_port != task.port());
+        // A service user can't bind ports below 1024 or rewrite the
+        // resolver config, so the move waits for a restart as root
+        let dns_needs_restart = dns_moved && self.daemon.user.is_some() && !is_root();
+        let new_dns = match dns.as_ref() {
+            Some(task) if dns_moved && !dns_needs_restart => Some(rebind_dns(
+                config.daemon.dns_port,
+                task.ip_resolver().clone(),
+                dns_answers.clone(),
+            )?),
+            _ => None,
+        };
+
+        // Nothing below can fail: apply the new config
+        let mut summary = vec![format!("Loaded {} domain(s)", registrations.len())];
+        let changes = DomainChanges::between(self.state.current().registrations(), &registrations);
+        for (label, patterns) in [
+            ("Added", &changes.added),
+            ("Removed", &changes.removed),
+            ("Changed", &changes.changed),
+        ] {
+            if !patterns.is_empty() {
+                summary.push(format!("{}: {}", label, patterns.join(", ")));
+            }
+        }
+        summary.extend(renewed);
+        if let Some(mdns) = &self.mdns {
+            mdns.set_names(local_names(&registrations));
+        }
+        if self.daemon.dns_backend == DnsBackend::Hosts {
+            match sync_hosts_file(&registrations) {
+                Ok(true) => summary.push("Updated the hosts file".to_string()),
+                Ok(false) => {}
+                Err(e) => summary.push(format!("Could not update the hosts file: {}", e)),
+            }
+        }
+        self.state.replace(
+            AppState::new(registrations, config.daemon.localhost)
+                .with_mdns(config.daemon.mdns)
+                .with_timeouts(Timeouts::from_config(&config.daemon))
+                .with_health_checks(config.daemon.health_check_interval)
+                .with_https_port(config.daemon.public_https_port())
+                // The running listener, until a restart brings the new one
+                .with_http3(self.daemon.http3_port()),
+        );
+
+        match (&self.tls, acceptor) {
+            (Some(shared), Some(acceptor)) => shared.replace(acceptor),
+            (None, Some(_)) => summary
+                .push("HTTPS is not running yet. Start it with: sudo roxy restart".to_string()),
+            _ => {}
+        }
+
+        if let Some(task) = dns.as_ref() {
+            task.ip_resolver()
+                .set_networks(&config.daemon.container_networks);
+            task.set_answers(dns_answers);
+        }
+
+        if let (Some(server), Some(task)) = (new_dns, dns.as_mut()) {
+            let old_port = task.port();
+            // Dropping the old task closes its sockets; the new ones are
+            // already answering
+            *task = server.spawn();
+            info!(from = old_port, to = task.port(), "DNS server moved");
+            summary.push(format!(
+                "DNS moved from port {} to {}",
+                old_port,
+                task.port()
+            ));
+        }
+
+        if dns_needs_restart {
+            summary.push("DNS port changes take effect after: sudo roxy restart".to_string());
+        }
+
+        if config.daemon.dns_backend != self.daemon.dns_backend {
+            summary.push("DNS backend changes take effect after: sudo roxy restart".to_string());
+        }
+
+        if config.daemon.mdns != self.daemon.mdns {
+            summary.push("mDNS changes take effect after: sudo roxy restart".to_string());
+        }
+
+        if config.daemon.http_port != self.daemon.http_port
+            || config.daemon.https_port != self.daemon.https_port
+        {
+            summary
+                .push("HTTP/HTTPS port changes take effect after: sudo roxy restart".to_string());
+        }
+
+        if config.daemon.http3 != self.daemon.http3 {
+            summary.push("HTTP/3 changes take effect after: sudo roxy restart".to_string());
+        }
+
+        if config.daemon.proxy_port != self.daemon.proxy_port
+            || config.daemon.proxy_intercept != self.daemon.proxy_intercept
+        {
+            summary.push("Forward proxy changes take effect after: sudo roxy restart".to_string());
+        }
+
+        info!("Configuration reloaded");
+        Ok(summary.join("\n"))
+    }
+
+    /// Renew the certificates of the running domains that expire soon
+    /// and serve the new ones. The renewal window and `[certs]` settings
+    /// come from the config file, the domains from the running state, so
+    /// unapplied edits stay unapplied.
+    pub fn renew_expiring(&self) {
+        if self.tls.is_none() {
+            return;
+        }
+        let (renewal_days, settings, _) = self.cert_settings();
+
+        let patterns = DomainCerts::new(self.state.current().registrations()).issued;
+        if renew_certificates(&self.paths, &patterns, renewal_days, &settings).is_empty() {
+            return;
+        }
+        if let Err(e) = self.reload_certificates() {
+            warn!(error = %e, "Failed to load renewed certificates");
+        }
+    }
+
+    /// Load the certificates of the running domains from disk again and
+    /// serve them to new connections. Open connections keep theirs.
+    pub fn reload_certificates(&self) -> Result<()> {
+        let Some(tls) = &self.tls else {
+            return Ok(());
+        };
+        let (_, settings, tls_settings) = self.cert_settings();
+        let state = self.state.current();
+        let acceptor = create_tls_acceptor(
+            &DomainCerts::new(state.registrations()),
+            &self.paths.certs_dir,
+            &self.paths.data_dir,
+            state.localhost(),
+            &settings,
+            &tls_settings,
+        )?;
+        if let Some(acceptor) = acceptor {
+            tls.replace(acceptor);
+        }
+        Ok(())
+    }
+
+    /// The custom certificate and key files of the running domains.
+    pub fn custom_cert_files(&self) -> Vec<PathBuf> {
+        DomainCerts::new(self.state.current().registrations())
+            .custom
+            .into_iter()
+            .flat_map(|(_, cert)| [cert.cert, cert.key])
+            .collect()
+    }
+
+    /// The renewal window, `[certs]` and `[tls]` settings in the config
+    /// file, or the running ones if it can't be read.
+    fn cert_settings(&self) -> (u32, CertsConfig, TlsConfig) {
+        match ConfigStore::new(self.config_path.clone()).load() {
+            Ok(config) => (config.daemon.cert_renewal_days, config.certs, config.tls),
+            Err(_) => (
+                self.daemon.cert_renewal_days,
+                CertsConfig::default(),
+                TlsConfig::default(),
+            ),
+        }
+    }
+}
+
+/// Renew the certificates of `patterns` that expire within
+/// `renewal_days`; 0 renews none. Returns a line per certificate renewed
+/// or failed.
+fn renew_certificates(
+    paths: &RoxyPaths,
+    patterns: &[DomainPattern],
+    renewal_days: u32,
+    settings: &CertsConfig,
+) -> Vec<String> {
+    if renewal_days == 0 {
+        return Vec::new();
+    }
+    let service = CertificateService::new(paths).with_settings(settings);
+    let window = time::Duration::days(renewal_days.into());
+
+    patterns
+        .iter()
+        .filter_map(|pattern| match service.renew_if_expiring(pattern, window) {
+            Ok(true) => {
+                info!(domain = %pattern, "Certificate renewed");
+                Some(format!("Renewed the certificate for {}", pattern))
+            }
+            Ok(false) => None,
+            Err(e) => {
+                warn!(domain = %pattern, error = %e, "Certificate renewal failed");
+                Some(format!(
+                    "Could not renew the certificate for {}: {}",
+                    pattern, e
+                ))
+            }
+        })
+        .collect()
+}
+
+/// Bind the DNS server on `port` and point the system resolver at it.
+/// On failure the new sockets are dropped and the old server keeps running.
+fn rebind_dns(port: u16, ip_resolver: Arc<IpResolver>, answers: Answers) -> Result<DnsServer> {
+    let server = DnsServer::bind(port, ip_resolver, answers)?;
+
+    if let Ok(dns) = get_dns_service()
+        && dns.is_configured()
+    {
+        dns.setup(port)
+            .context("Failed to point the system resolver at the new DNS port")?;
+    }
+
+    Ok(server)
+}
+
+/// Write the registered domains to the hosts file. Returns whether it
+/// changed; an up-to-date file needs no privileges.
+pub fn sync_hosts_file(registrations: &[DomainRegistration]) -> Result<bool> {
+    HostsFile::system()
+        .sync(&hostnames(registrations))
+        .context("Failed to update the hosts file")
+}
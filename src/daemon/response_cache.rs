@@ -0,0 +1,560 @@
+//! In-memory cache for proxied `GET` responses on routes with
+//! `proxy_cache`, following the backend's `Cache-Control`, `ETag` and
+//! `Last-Modified` headers.
+//!
+//! Responses carry `X-Cache: HIT`, `MISS`, `REVALIDATED` or `BYPASS`
+//! (not storable), so caching can be checked in the browser.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, Bytes};
+use axum::extract::Request;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header};
+use axum::response::Response;
+use http_body_util::BodyExt;
+use hyper::body::Frame;
+use tracing::{debug, warn};
+
+use super::body_rewrite::Prefixed;
+use super::streaming;
+use crate::domain::DomainRegistration;
+
+/// Larger responses are passed through without caching.
+const MAX_ENTRY_BYTES: usize = 5 * 1024 * 1024;
+
+/// Oldest entries are dropped once the cache holds more than this.
+const MAX_TOTAL_BYTES: usize = 64 * 1024 * 1024;
+
+const X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+
+/// Cached responses for all domains. Like the request stats it lives
+/// outside the routing state, so a reload keeps what's cached.
+#[derive(Default)]
+pub struct ResponseCache {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<String, Arc<Entry>>,
+    bytes: usize,
+}
+
+struct Entry {
+    /// Config key of the domain, for purging.
+    domain: String,
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    /// Request headers named by `Vary`, as sent for the stored response.
+    vary: Vec<(HeaderName, Option<HeaderValue>)>,
+    stored: Instant,
+    fresh_for: Duration,
+}
+
+/// Where the response to a request is cached.
+pub struct CacheKey {
+    domain: String,
+    key: String,
+}
+
+impl CacheKey {
+    /// `None` for requests the cache stays out of: anything but `GET`,
+    /// and requests with credentials for the backend.
+    pub fn for_request(
+        registration: &DomainRegistration,
+        host: &str,
+        request: &Request,
+    ) -> Option<Self> {
+        let headers = request.headers();
+        if request.method() != Method::GET
+            || headers.contains_key(header::AUTHORIZATION)
+            || has_directive(headers, "no-store")
+        {
+            return None;
+        }
+        let path = request.uri().path_and_query().map_or("/", |p| p.as_str());
+        Some(Self {
+            domain: registration.config_key(),
+            key: format!("{}{}", host.to_lowercase(), path),
+        })
+    }
+}
+
+impl ResponseCache {
+    /// Answer from the cache if possible, otherwise `send` the request
+    /// (revalidating a stale entry) and keep the response if allowed.
+    pub async fn serve<F, Fut>(&self, key: CacheKey, mut request: Request, send: F) -> Response
+    where
+        F: FnOnce(Request) -> Fut,
+        Fut: Future<Output = Response>,
+    {
+        let request_headers = request.headers().clone();
+        // Hard reloads ask for a fresh copy
+        let reload = has_directive(&request_headers, "no-cache");
+        let conditional = request_headers.contains_key(header::IF_NONE_MATCH)
+            || request_headers.contains_key(header::IF_MODIFIED_SINCE);
+
+        let cached = self.get(&key.key, &request_headers);
+        if let Some(entry) = &cached {
+            if !reload && entry.stored.elapsed() < entry.fresh_for {
+                debug!(key = %key.key, "Serving from cache");
+                return entry.response("HIT", &request_headers);
+            }
+            if !conditional {
+                entry.add_validators(request.headers_mut());
+            }
+        }
+
+        let response = send(request).await;
+
+        if let Some(entry) = cached
+            && !conditional
+            && response.status() == StatusCode::NOT_MODIFIED
+        {
+            let entry = entry.revalidated(response.headers());
+            let response = entry.response("REVALIDATED", &request_headers);
+            if freshness(entry.status, &entry.headers).is_some() {
+                self.insert(key.key, entry);
+            } else {
+                self.remove(&key.key);
+            }
+            return response;
+        }
+
+        let Some(fresh_for) = freshness(response.status(), response.headers()) else {
+            return with_x_cache(response, "BYPASS");
+        };
+        let (parts, body) = response.into_parts();
+        let too_large = parts
+            .headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok()?.parse::<usize>().ok())
+            .is_some_and(|len| len > MAX_ENTRY_BYTES);
+        if too_large {
+            return with_x_cache(Response::from_parts(parts, body), "BYPASS");
+        }
+        let body = match read_body(body).await {
+            Ok(body) => body,
+            Err(body) => return with_x_cache(Response::from_parts(parts, body), "BYPASS"),
+        };
+
+        let vary = vary_names(&parts.headers)
+            .into_iter()
+            .map(|name| {
+                let value = request_headers.get(&name).cloned();
+                (name, value)
+            })
+            .collect();
+        let entry = Entry {
+            domain: key.domain,
+            status: parts.status,
+            headers: parts.headers,
+            body,
+            vary,
+            stored: Instant::now(),
+            fresh_for,
+        };
+        let response = entry.response("MISS", &request_headers);
+        self.insert(key.key, entry);
+        response
+    }
+
+    /// Drop everything cached for a domain. Returns how many responses
+    /// were dropped.
+    pub fn purge(&self, domain: &str) -> usize {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let before = state.entries.len();
+        state.entries.retain(|_, entry| entry.domain != domain);
+        state.bytes = state.entries.values().map(|e| e.body.len()).sum();
+        before - state.entries.len()
+    }
+
+    fn get(&self, key: &str, request: &HeaderMap) -> Option<Arc<Entry>> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state
+            .entries
+            .get(key)
+            .filter(|entry| {
+                entry
+                    .vary
+                    .iter()
+                    .all(|(name, value)| request.get(name) == value.as_ref())
+            })
+            .cloned()
+    }
+
+    fn insert(&self, key: String, entry: Entry) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.bytes += entry.body.len();
+        if let Some(old) = state.entries.insert(key, Arc::new(entry)) {
+            state.bytes -= old.body.len();
+        }
+        while state.bytes > MAX_TOTAL_BYTES {
+            let Some(oldest) = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            if let Some(old) = state.entries.remove(&oldest) {
+                state.bytes -= old.body.len();
+            }
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(old) = state.entries.remove(key) {
+            state.bytes -= old.body.len();
+        }
+    }
+}
+
+impl Entry {
+    fn response(&self, x_cache: &'static str, request: &HeaderMap) -> Response {
+        let etag = self.headers.get(header::ETAG);
+        let not_modified = etag.is_some()
+            && request
+                .get_all(header::IF_NONE_MATCH)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(','))
+                .any(|tag| {
+                    let tag = tag.trim();
+                    tag == "*" || etag.and_then(|e| e.to_str().ok()) == Some(tag)
+                });
+
+        let mut response = if not_modified {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+            *response.headers_mut() = self.headers.clone();
+            response.headers_mut().remove(header::CONTENT_LENGTH);
+            response
+        } else {
+            let mut response = Response::new(Body::from(self.body.clone()));
+            *response.status_mut() = self.status;
+            *response.headers_mut() = self.headers.clone();
+            response
+        };
+        response.headers_mut().insert(
+            header::AGE,
+            HeaderValue::from(self.stored.elapsed().as_secs()),
+        );
+        with_x_cache(response, x_cache)
+    }
+
+    /// Ask the backend to answer 304 if the stored response is current.
+    fn add_validators(&self, request: &mut HeaderMap) {
+        if let Some(etag) = self.headers.get(header::ETAG) {
+            request.insert(header::IF_NONE_MATCH, etag.clone());
+        }
+        if let Some(modified) = self.headers.get(header::LAST_MODIFIED) {
+            request.insert(header::IF_MODIFIED_SINCE, modified.clone());
+        }
+    }
+
+    /// The same response, updated with the headers of a 304.
+    fn revalidated(&self, not_modified: &HeaderMap) -> Entry {
+        let mut headers = self.headers.clone();
+        for name in not_modified.keys() {
+            if *name == header::CONTENT_LENGTH {
+                continue;
+            }
+            headers.remove(name);
+            for value in not_modified.get_all(name) {
+                headers.append(name.clone(), value.clone());
+            }
+        }
+        let fresh_for = freshness(StatusCode::OK, &headers).unwrap_or_default();
+        Entry {
+            domain: self.domain.clone(),
+            status: self.status,
+            headers,
+            body: self.body.clone(),
+            vary: self.vary.clone(),
+            stored: Instant::now(),
+            fresh_for,
+        }
+    }
+}
+
+/// How long a response may be served without asking the backend, or
+/// `None` if it must not be stored. Responses that are only
+/// revalidated get zero.
+fn freshness(status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+    if status != StatusCode::OK
+        || streaming::is_event_stream(headers)
+        || headers.contains_key(header::SET_COOKIE)
+        || varies_on_everything(headers)
+        || has_directive(headers, "no-store")
+        || has_directive(headers, "private")
+    {
+        return None;
+    }
+
+    let validators =
+        headers.contains_key(header::ETAG) || headers.contains_key(header::LAST_MODIFIED);
+    let max_age = directive(headers, "s-maxage")
+        .or_else(|| directive(headers, "max-age"))
+        .and_then(|secs| secs.parse::<u64>().ok());
+    match max_age {
+        Some(secs) if !has_directive(headers, "no-cache") && (secs > 0 || validators) => {
+            Some(Duration::from_secs(secs))
+        }
+        _ => validators.then_some(Duration::ZERO),
+    }
+}
+
+/// Header names listed in `Vary`.
+fn vary_names(headers: &HeaderMap) -> Vec<HeaderName> {
+    vary_values(headers)
+        .filter_map(|name| name.parse().ok())
+        .collect()
+}
+
+/// `Vary: *`, which can never match a later request.
+fn varies_on_everything(headers: &HeaderMap) -> bool {
+    vary_values(headers).any(|name| name == "*")
+}
+
+fn vary_values(headers: &HeaderMap) -> impl Iterator<Item = &str> {
+    headers
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
+fn has_directive(headers: &HeaderMap, name: &str) -> bool {
+    directives(headers).any(|(n, _)| n.eq_ignore_ascii_case(name))
+}
+
+fn directive<'h>(headers: &'h HeaderMap, name: &str) -> Option<&'h str> {
+    directives(headers)
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .and_then(|(_, value)| value)
+}
+
+/// `Cache-Control` directives as name and optional value.
+fn directives(headers: &HeaderMap) -> impl Iterator<Item = (&str, Option<&str>)> {
+    headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|directive| match directive.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+            None => (directive.trim(), None),
+        })
+}
+
+/// The whole body, or a body to pass on as it is if it is too large
+/// to keep.
+async fn read_body(mut body: Body) -> Result<Bytes, Body> {
+    let mut buf = Vec::new();
+    while let Some(frame) = body.frame().await {
+        let data = match frame.map(Frame::into_data) {
+            Ok(Ok(data)) => data,
+            // Trailers end the body
+            Ok(Err(_)) => break,
+            Err(e) => {
+                warn!(error = %e, "Failed to read backend response for caching");
+                return Err(Body::from(buf));
+            }
+        };
+        buf.extend_from_slice(&data);
+
+        if buf.len() > MAX_ENTRY_BYTES {
+            return Err(Body::new(Prefixed {
+                prefix: Some(Bytes::from(buf)),
+                rest: body,
+            }));
+        }
+    }
+    Ok(Bytes::from(buf))
+}
+
+fn with_x_cache(mut response: Response, value: &'static str) -> Response {
+    response
+        .headers_mut()
+        .insert(X_CACHE, HeaderValue::from_static(value));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::domain::{DomainName, DomainPattern, PathPrefix, Route, RouteTarget};
+
+    fn registration() -> DomainRegistration {
+        DomainRegistration::new(
+            DomainPattern::Exact(DomainName::new("api.roxy").unwrap()),
+            vec![Route::new(
+                PathPrefix::new("/").unwrap(),
+                RouteTarget::parse("3000").unwrap(),
+            )],
+        )
+    }
+
+    fn get(path: &str, headers: &[(HeaderName, &'static str)]) -> Request {
+        let mut request = Request::get(path).body(Body::empty()).unwrap();
+        for (name, value) in headers {
+            request
+                .headers_mut()
+                .insert(name, HeaderValue::from_static(value));
+        }
+        request
+    }
+
+    /// A backend answering with `headers`, counting its requests. With
+    /// `etag`, it answers 304 to requests that send it back.
+    struct Backend {
+        calls: AtomicUsize,
+        headers: Vec<(HeaderName, &'static str)>,
+    }
+
+    impl Backend {
+        fn new(headers: &[(HeaderName, &'static str)]) -> Self {
+            Self {
+                calls: AtomicUsize::new(0),
+                headers: headers.to_vec(),
+            }
+        }
+
+        async fn send(&self, request: Request) -> Response {
+            let n = self.calls.fetch_add(1, Ordering::SeqCst);
+            let etag = self
+                .headers
+                .iter()
+                .find(|(name, _)| name == header::ETAG)
+                .map(|(_, value)| *value);
+            let mut response = if etag.is_some()
+                && request
+                    .headers()
+                    .get(header::IF_NONE_MATCH)
+                    .map(|v| v.to_str().unwrap())
+                    == etag
+            {
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::NOT_MODIFIED;
+                response
+            } else {
+                Response::new(Body::from(format!("response {}", n)))
+            };
+            for (name, value) in &self.headers {
+                response
+                    .headers_mut()
+                    .insert(name, HeaderValue::from_static(value));
+            }
+            response
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    async fn fetch(cache: &ResponseCache, backend: &Backend, request: Request) -> (String, String) {
+        let key = CacheKey::for_request(&registration(), "api.roxy", &request).unwrap();
+        let response = cache.serve(key, request, |r| backend.send(r)).await;
+        let x_cache = response.headers()[X_CACHE].to_str().unwrap().to_string();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (x_cache, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn serves_fresh_responses_from_memory() {
+        let cache = ResponseCache::default();
+        let backend = Backend::new(&[(header::CACHE_CONTROL, "max-age=60")]);
+
+        let first = fetch(&cache, &backend, get("/users", &[])).await;
+        let second = fetch(&cache, &backend, get("/users", &[])).await;
+        assert_eq!(first, ("MISS".into(), "response 0".into()));
+        assert_eq!(second, ("HIT".into(), "response 0".into()));
+        assert_eq!(backend.calls(), 1);
+
+        // A hard reload goes to the backend
+        let reload = get("/users", &[(header::CACHE_CONTROL, "no-cache")]);
+        assert_eq!(fetch(&cache, &backend, reload).await.1, "response 1");
+    }
+
+    #[tokio::test]
+    async fn revalidates_with_etag() {
+        let cache = ResponseCache::default();
+        let backend = Backend::new(&[
+            (header::CACHE_CONTROL, "no-cache"),
+            (header::ETAG, "\"v1\""),
+        ]);
+
+        assert_eq!(fetch(&cache, &backend, get("/", &[])).await.0, "MISS");
+        let second = fetch(&cache, &backend, get("/", &[])).await;
+        assert_eq!(second, ("REVALIDATED".into(), "response 0".into()));
+        assert_eq!(backend.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn bypasses_uncacheable_responses() {
+        let cache = ResponseCache::default();
+        for headers in [
+            vec![],
+            vec![(header::CACHE_CONTROL, "no-store")],
+            vec![(header::CACHE_CONTROL, "private, max-age=60")],
+            vec![
+                (header::CACHE_CONTROL, "max-age=60"),
+                (header::SET_COOKIE, "session=1"),
+            ],
+            vec![(header::CACHE_CONTROL, "max-age=60"), (header::VARY, "*")],
+        ] {
+            let backend = Backend::new(&headers);
+            assert_eq!(fetch(&cache, &backend, get("/", &[])).await.0, "BYPASS");
+            assert_eq!(fetch(&cache, &backend, get("/", &[])).await.0, "BYPASS");
+            assert_eq!(backend.calls(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn respects_vary() {
+        let cache = ResponseCache::default();
+        let backend = Backend::new(&[
+            (header::CACHE_CONTROL, "max-age=60"),
+            (header::VARY, "Accept-Language"),
+        ]);
+        let en = || get("/", &[(header::ACCEPT_LANGUAGE, "en")]);
+        let de = get("/", &[(header::ACCEPT_LANGUAGE, "de")]);
+
+        assert_eq!(fetch(&cache, &backend, en()).await.0, "MISS");
+        assert_eq!(fetch(&cache, &backend, en()).await.0, "HIT");
+        assert_eq!(fetch(&cache, &backend, de).await.0, "MISS");
+    }
+
+    #[tokio::test]
+    async fn purges_by_domain() {
+        let cache = ResponseCache::default();
+        let backend = Backend::new(&[(header::CACHE_CONTROL, "max-age=60")]);
+        fetch(&cache, &backend, get("/a", &[])).await;
+        fetch(&cache, &backend, get("/b", &[])).await;
+
+        assert_eq!(cache.purge("other.roxy"), 0);
+        assert_eq!(cache.purge("api.roxy"), 2);
+        assert_eq!(fetch(&cache, &backend, get("/a", &[])).await.0, "MISS");
+    }
+
+    #[test]
+    fn skips_requests_it_must_not_cache() {
+        let reg = registration();
+        let post = Request::post("/").body(Body::empty()).unwrap();
+        assert!(CacheKey::for_request(&reg, "api.roxy", &post).is_none());
+        let authorized = get("/", &[(header::AUTHORIZATION, "Bearer x")]);
+        assert!(CacheKey::for_request(&reg, "api.roxy", &authorized).is_none());
+    }
+}
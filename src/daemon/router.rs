@@ -1,29 +1,71 @@
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
+use std::hash::{BuildHasher, RandomState};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
 use axum::{
     Extension, Router,
+    body::Body,
     extract::{Request, State},
-    http::{StatusCode, header},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
     routing::any,
 };
-use tracing::{debug, info};
+use http_body_util::BodyExt;
+use tracing::{Instrument, debug, info, warn};
 
-use crate::domain::{DomainRegistration, RouteTarget};
+use crate::domain::{
+    AccessPolicy, DomainName, DomainRegistration, PathPrefix, PathRewrite, ProxyTarget, Route,
+    RouteTarget,
+};
+use crate::infrastructure::certs::custom;
+use crate::infrastructure::paths::RoxyPaths;
+use crate::infrastructure::tracing::log_scope;
 
+use super::balancer::Balancer;
+use super::basic_auth::{self, VerifiedLogins};
+use super::ca_download;
+use super::compression;
+use super::cors;
 use super::embedded_assets;
-use super::proxy::{ClientAddr, Scheme, proxy_request};
-use super::static_files::serve_static;
+use super::health_check::{BackendHealth, Check};
+use super::mirror;
+use super::proxy::{BackendUnreachable, ClientAddr, Scheme, Timeouts, proxy_request};
+use super::rate_limit::{self, RateLimiter};
+use super::response_cache::{CacheKey, ResponseCache};
+use super::static_files::{LiveReload, is_live_reload_request, serve_static};
+use super::stats::RequestStats;
+use super::sticky;
+use super::supervisor::Supervisor;
 use super::theme;
 
 /// Shared state for the router
 pub struct AppState {
     /// All registrations sorted by pattern specificity (most specific first).
     registrations: Vec<DomainRegistration>,
+    /// Names covered by custom certificates, with the index of their
+    /// registration, so `app.dev.example.com` reaches the domain whose
+    /// certificate covers it.
+    cert_names: Vec<(String, usize)>,
+    /// Whether `<name>.localhost` is served as `<name>.roxy`.
+    localhost: bool,
+    /// Whether `<name>.local`, published over mDNS, is too.
+    mdns: bool,
+    /// Daemon-wide backend timeouts, before route overrides.
+    timeouts: Timeouts,
+    /// Time between backend health checks, `None` when they are off.
+    health_check_interval: Option<Duration>,
+    /// Port in redirects to HTTPS.
+    https_port: u16,
+    /// `Alt-Svc` sent over HTTPS while HTTP/3 is on.
+    alt_svc: Option<HeaderValue>,
 }
 
 impl AppState {
-    pub fn new(mut registrations: Vec<DomainRegistration>) -> Self {
+    pub fn new(mut registrations: Vec<DomainRegistration>, localhost: bool) -> Self {
         // Most-specific first: longer base domain wins.
         // At equal specificity, exact patterns come before wildcards.
         registrations.sort_by(|a, b| {
@@ -33,18 +75,327 @@ impl AppState {
                 .then_with(|| a.is_wildcard().cmp(&b.is_wildcard()))
         });
 
-        Self { registrations }
+        // Unreadable certificates are reported when the TLS acceptor loads them
+        let cert_names = registrations
+            .iter()
+            .enumerate()
+            .filter_map(|(i, r)| Some((i, custom::names(r.custom_cert()?).ok()?)))
+            .flat_map(|(i, names)| names.into_iter().map(move |name| (name, i)))
+            .collect();
+
+        Self {
+            registrations,
+            cert_names,
+            localhost,
+            mdns: false,
+            timeouts: Timeouts::default(),
+            health_check_interval: None,
+            https_port: 443,
+            alt_svc: None,
+        }
+    }
+
+    pub fn with_mdns(mut self, mdns: bool) -> Self {
+        self.mdns = mdns;
+        self
+    }
+
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Check backends every `interval` milliseconds; 0 turns checks off.
+    pub fn with_health_checks(mut self, interval: u64) -> Self {
+        self.health_check_interval = (interval > 0).then(|| Duration::from_millis(interval));
+        self
+    }
+
+    pub fn with_https_port(mut self, port: u16) -> Self {
+        self.https_port = port;
+        self
+    }
+
+    /// Point HTTPS clients at HTTP/3 on UDP `port`, if any.
+    pub fn with_http3(mut self, port: Option<u16>) -> Self {
+        self.alt_svc = port.and_then(|port| {
+            HeaderValue::from_maybe_shared(format!("h3=\":{}\"; ma=86400", port)).ok()
+        });
+        self
+    }
+
+    pub fn health_check_interval(&self) -> Option<Duration> {
+        self.health_check_interval
+    }
+
+    pub fn registrations(&self) -> &[DomainRegistration] {
+        &self.registrations
+    }
+
+    /// The registrations served; disabled ones are left out as if they
+    /// weren't registered.
+    fn enabled(&self) -> impl Iterator<Item = &DomainRegistration> {
+        self.registrations.iter().filter(|r| r.is_enabled())
+    }
+
+    pub fn localhost(&self) -> bool {
+        self.localhost
+    }
+
+    /// One check per proxy backend, keyed by its address. A backend used
+    /// by several routes is checked with the first HTTP path given.
+    pub fn health_checks(&self) -> BTreeMap<String, Check> {
+        let mut checks = BTreeMap::new();
+        for route in self.enabled().flat_map(|r| r.routes()) {
+            for target in route.target.proxy_targets() {
+                let path = route.options.health_check.clone();
+                match checks.entry(target.to_string()) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(Check {
+                            target: target.clone(),
+                            path,
+                        });
+                    }
+                    Entry::Occupied(mut entry) => {
+                        if entry.get().path.is_none() {
+                            entry.get_mut().path = path;
+                        }
+                    }
+                }
+            }
+        }
+        checks
     }
 
     pub fn get_domain(&self, host: &str) -> Option<&DomainRegistration> {
         // Strip port from host if present
         let domain = host.split(':').next().unwrap_or(host);
-        let domain = domain.trim_end_matches('.').to_lowercase();
+        let mut domain = domain.trim_end_matches('.').to_lowercase();
+        if self.localhost
+            && let Some(name) = DomainName::from_localhost(&domain)
+        {
+            domain = name.to_string();
+        }
+        if self.mdns
+            && let Some(name) = DomainName::from_local(&domain)
+        {
+            domain = name.to_string();
+        }
 
-        self.registrations
-            .iter()
+        self.enabled()
             .find(|r| r.pattern().matches_hostname(&domain))
+            .or_else(|| {
+                let (_, i) = self
+                    .cert_names
+                    .iter()
+                    .find(|(name, _)| custom::covers(name, &domain))?;
+                Some(&self.registrations[*i]).filter(|r| r.is_enabled())
+            })
+    }
+
+    /// Whether any domain takes its HTTPS connections undecrypted, so
+    /// the HTTPS listener has to look at the server name first.
+    pub fn has_tls_passthrough(&self) -> bool {
+        self.enabled().any(|r| r.is_tls_passthrough())
+    }
+
+    /// Every TCP port forwarded by a domain.
+    pub fn tcp_ports(&self) -> Vec<u16> {
+        self.enabled()
+            .flat_map(|r| r.tcp_forwards())
+            .map(|f| f.port())
+            .collect()
+    }
+
+    /// Where connections to the forwarded TCP `port` go.
+    pub fn tcp_forward_target(&self, port: u16) -> Option<ProxyTarget> {
+        self.enabled()
+            .flat_map(|r| r.tcp_forwards())
+            .find(|f| f.port() == port)
+            .map(|f| f.target().clone())
+    }
+
+    /// Backend for HTTPS connections to `host`, if its domain uses TLS
+    /// passthrough.
+    pub fn passthrough_target(&self, host: &str) -> Option<ProxyTarget> {
+        self.get_domain(host)?.passthrough_target().cloned()
+    }
+}
+
+/// State shared by the HTTP and HTTPS servers. Reloading swaps in a new
+/// `AppState`; requests already in flight keep the one they started with.
+#[derive(Clone)]
+pub struct SharedState {
+    app: Arc<RwLock<Arc<AppState>>>,
+    stats: Arc<RequestStats>,
+    balancer: Arc<Balancer>,
+    health: Arc<BackendHealth>,
+    logins: Arc<VerifiedLogins>,
+    rate_limiter: Arc<RateLimiter>,
+    cache: Arc<ResponseCache>,
+    supervisor: Arc<Supervisor>,
+    live_reload: Arc<LiveReload>,
+    /// Root CA certificate offered for download on `roxy.roxy`.
+    ca_cert: Option<Arc<Path>>,
+}
+
+impl SharedState {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            app: Arc::new(RwLock::new(Arc::new(state))),
+            stats: Arc::default(),
+            balancer: Arc::default(),
+            health: Arc::default(),
+            logins: Arc::default(),
+            rate_limiter: Arc::default(),
+            cache: Arc::default(),
+            supervisor: Arc::default(),
+            live_reload: Arc::default(),
+            ca_cert: None,
+        }
+    }
+
+    /// Log the output of started backends under `paths`.
+    pub fn with_backend_logs(mut self, paths: RoxyPaths) -> Self {
+        self.supervisor = Arc::new(Supervisor::new(paths));
+        self
+    }
+
+    /// Offer the Root CA certificate at `path` for download.
+    pub fn with_ca_cert(mut self, path: PathBuf) -> Self {
+        self.ca_cert = Some(path.into());
+        self
+    }
+
+    pub fn current(&self) -> Arc<AppState> {
+        self.app.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn replace(&self, state: AppState) {
+        *self.app.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(state);
+    }
+
+    pub fn stats(&self) -> Arc<RequestStats> {
+        self.stats.clone()
+    }
+
+    pub fn health(&self) -> Arc<BackendHealth> {
+        self.health.clone()
+    }
+
+    pub fn cache(&self) -> Arc<ResponseCache> {
+        self.cache.clone()
+    }
+
+    pub fn supervisor(&self) -> Arc<Supervisor> {
+        self.supervisor.clone()
+    }
+}
+
+/// Proxy `request` to one of the route's backends.
+async fn forward(
+    shared: &SharedState,
+    state: &AppState,
+    route: &Route,
+    mut request: Request,
+    host: &str,
+    proto: &str,
+    client_ip: Option<IpAddr>,
+) -> Response {
+    rewrite_request_path(route, &mut request);
+    let health = shared.health();
+    let targets = route.target.proxy_targets();
+    let usable = |target: &ProxyTarget| !health.is_down(target);
+    // A pinned browser moves only if its target is down and another is up
+    let pinned = route
+        .options
+        .sticky
+        .then(|| sticky::pinned(request.headers(), targets))
+        .flatten()
+        .filter(|target| usable(target) || !targets.iter().any(usable));
+    let lease = match (pinned, &route.options.split) {
+        (Some(target), _) => shared.balancer.lease(target.clone()),
+        (None, Some(split)) => shared.balancer.pick_split(targets, split, random(), usable),
+        (None, None) => shared.balancer.pick(targets, route.options.balance, usable),
+    };
+    let target = lease.target();
+    let timeouts = state.timeouts.for_route(&route.options);
+    let request = match mirror::mirror(route, request, host, proto, client_ip, timeouts).await {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
+    let mut response = proxy_request(
+        target,
+        request,
+        host,
+        proto,
+        client_ip,
+        &route.options,
+        timeouts,
+    )
+    .await;
+    if response.extensions().get::<BackendUnreachable>().is_some()
+        && let Some(down_for) = health.down_for(target)
+    {
+        response = build_backend_down_response(target, Some(down_for));
+    }
+    if route.options.strip_prefix {
+        restore_location(&route.path, response.headers_mut());
     }
+    if route.options.sticky && targets.len() > 1 && pinned != Some(target) {
+        response
+            .headers_mut()
+            .append(header::SET_COOKIE, sticky::pin(&route.path, target));
+    }
+    // The request stays in flight until its body is done
+    response.map(|body| {
+        Body::new(body.map_frame(move |frame| {
+            let _ = &lease;
+            frame
+        }))
+    })
+}
+
+/// How many times a request was handed from one registered domain to
+/// another. Kept in the request's extensions, where clients can't set it.
+#[derive(Debug, Clone, Copy)]
+struct ChainHops(u32);
+/// Domains a request may be handed through before it's taken for a loop.
+const MAX_HOPS: u32 = 8;
+
+/// Answer `request` with the routes of another registered domain, as if
+/// it had been sent there. A chain that leads back to itself ends with
+/// an error page once the hop count runs out.
+async fn chain(
+    shared: SharedState,
+    scheme: Option<Extension<Scheme>>,
+    client_addr: Option<Extension<ClientAddr>>,
+    route: &Route,
+    domain: &DomainName,
+    mut request: Request,
+) -> Response {
+    let hops = request
+        .extensions()
+        .get::<ChainHops>()
+        .map_or(0, |ChainHops(hops)| *hops);
+    if hops >= MAX_HOPS {
+        warn!(domain = %domain.as_str(), hops, "Domain chain loops");
+        return build_loop_detected_response(domain);
+    }
+    let Ok(host) = HeaderValue::from_str(domain.as_str()) else {
+        warn!(domain = %domain.as_str(), "Chained domain is not a valid Host header");
+        return (StatusCode::BAD_GATEWAY, "Invalid chained domain").into_response();
+    };
+
+    rewrite_request_path(route, &mut request);
+    request.headers_mut().insert(header::HOST, host);
+    request.extensions_mut().insert(ChainHops(hops + 1));
+
+    let mut response = Box::pin(handle_request(State(shared), scheme, client_addr, request)).await;
+    if route.options.strip_prefix {
+        restore_location(&route.path, response.headers_mut());
+    }
+    response
 }
 
 /// Extract host from request headers
@@ -57,7 +408,7 @@ fn get_host(request: &Request) -> Option<String> {
 }
 
 /// Create the main router
-pub fn create_router(state: Arc<AppState>) -> Router {
+pub fn create_router(state: SharedState) -> Router {
     Router::new()
         .route("/{*path}", any(handle_request))
         .route("/", any(handle_request))
@@ -66,7 +417,7 @@ pub fn create_router(state: Arc<AppState>) -> Router {
 
 /// Handle all incoming requests
 async fn handle_request(
-    State(state): State<Arc<AppState>>,
+    State(shared): State<SharedState>,
     scheme: Option<Extension<Scheme>>,
     client_addr: Option<Extension<ClientAddr>>,
     request: Request,
@@ -83,14 +434,40 @@ async fn handle_request(
     };
 
     // Look up the domain
+    let state = shared.current();
     let registration = match state.get_domain(&host) {
         Some(r) => r,
+        None if ca_download::is_ca_request(&host, uri.path()) => {
+            debug!(host = %host, path = %uri.path(), "Serving the Root CA");
+            return ca_download::respond(shared.ca_cert.as_deref(), &host, uri.path());
+        }
         None => {
             info!(host = %host, "Domain not registered");
             return build_not_registered_response(&host);
         }
     };
 
+    let is_https = matches!(scheme, Some(Extension(Scheme::Https)));
+    if registration.is_https_only() && !is_https {
+        debug!(host = %host, "Redirecting to HTTPS");
+        return https_redirect(&host, &uri, state.https_port);
+    }
+
+    let client_ip = client_addr.as_ref().map(|Extension(a)| a.0);
+    let allowed = match client_ip {
+        Some(ip) => registration.access().allows(ip),
+        None => registration.access().is_default(),
+    };
+    if !allowed {
+        info!(host = %host, client = ?client_ip, "Client not allowed");
+        return build_forbidden_response(registration, client_ip);
+    }
+
+    if registration.is_in_maintenance() {
+        debug!(host = %host, "Domain is in maintenance");
+        return build_maintenance_response(registration);
+    }
+
     // Match route by path (longest prefix wins)
     let path = uri.path();
     let route = match registration.match_route(path) {
@@ -101,36 +478,240 @@ async fn handle_request(
         }
     };
 
-    debug!(
-        method = %method,
-        host = %host,
-        path = %path,
-        route = %route.path,
-        "Routing request"
-    );
+    let proto = scheme
+        .as_ref()
+        .map(|Extension(s)| s.as_str())
+        .unwrap_or("http");
+    let origin = request
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let accept_encoding = request.headers().get(header::ACCEPT_ENCODING).cloned();
+
+    let handle = async {
+        debug!(
+            method = %method,
+            host = %host,
+            path = %path,
+            route = %route.path,
+            "Routing request"
+        );
+
+        let limited = registration.rate_limit().and_then(|limit| {
+            shared
+                .rate_limiter
+                .acquire(&registration.config_key(), limit)
+                .err()
+        });
 
-    let proto = scheme.map(|Extension(s)| s.as_str()).unwrap_or("http");
-    let client_ip = client_addr.map(|Extension(a)| a.0);
+        // Preflights never carry credentials, so the ones Roxy answers
+        // itself skip the login. Any other goes on to the backend and
+        // needs one like every request.
+        let answers_preflight = route.options.preflight && cors::is_preflight(&request);
+        let mut request = request;
+        let authorized = match registration.effective_auth(route) {
+            Some(auth) if limited.is_none() && !answers_preflight => {
+                let authorized =
+                    basic_auth::is_authorized(auth, request.headers(), &shared.logins).await;
+                // The credentials are for Roxy, not the backend
+                request.headers_mut().remove(header::AUTHORIZATION);
+                authorized
+            }
+            _ => true,
+        };
 
-    // Route to appropriate backend based on target type
-    let response = match &route.target {
-        RouteTarget::StaticFiles(dir) => {
-            serve_static(route.path.as_str(), dir.clone(), request).await
+        if let Some(latency) = route.options.latency
+            && authorized
+            && limited.is_none()
+        {
+            let delay = latency.pick(random());
+            debug!(host = %host, path = %path, delay = ?delay, "Injecting latency");
+            tokio::time::sleep(delay).await;
         }
-        RouteTarget::Proxy(target) => proxy_request(target, request, &host, proto, client_ip).await,
+
+        // Route to appropriate backend based on target type
+        let mut response = if let Some(retry_after) = limited {
+            info!(host = %host, path = %path, "Rate limited");
+            rate_limit::too_many_requests(retry_after)
+        } else if !authorized {
+            info!(host = %host, path = %path, "Login required");
+            basic_auth::challenge(&host)
+        } else if answers_preflight {
+            debug!(host = %host, path = %path, "Answering CORS preflight");
+            let policy = registration.cors().cloned().unwrap_or_default();
+            cors::preflight_response(&policy, &request)
+        } else if let Some(fault) = route.options.fault
+            && fault.strikes(random())
+        {
+            info!(host = %host, path = %path, status = fault.status(), "Injecting fault");
+            injected_fault(fault.status())
+        } else if let Err(response) = shared.supervisor.ensure_running(route, registration).await {
+            response
+        } else {
+            match &route.target {
+                RouteTarget::StaticFiles(_) | RouteTarget::MergedStaticFiles(_)
+                    if route.options.live_reload
+                        && is_live_reload_request(route.path.as_str(), path) =>
+                {
+                    debug!(host = %host, path = %path, "Opening live reload socket");
+                    let roots = route.target.static_roots();
+                    shared
+                        .live_reload
+                        .connect(roots, &route.options, request)
+                        .await
+                }
+                RouteTarget::StaticFiles(_) | RouteTarget::MergedStaticFiles(_) => {
+                    let roots = route.target.static_roots();
+                    serve_static(route.path.as_str(), roots, &route.options, request).await
+                }
+                RouteTarget::Proxy(_) | RouteTarget::LoadBalanced(_) => {
+                    let send =
+                        |request| forward(&shared, &state, route, request, &host, proto, client_ip);
+                    let cache_key = route
+                        .options
+                        .proxy_cache
+                        .then(|| CacheKey::for_request(registration, &host, &request))
+                        .flatten();
+                    match cache_key {
+                        Some(key) => shared.cache.serve(key, request, send).await,
+                        None => send(request).await,
+                    }
+                }
+                RouteTarget::Domain(domain) => {
+                    debug!(host = %host, path = %path, domain = %domain.as_str(), "Chaining to domain");
+                    chain(shared.clone(), scheme, client_addr, route, domain, request).await
+                }
+            }
+        };
+        if let (Some(policy), Some(origin)) = (registration.cors(), &origin) {
+            cors::apply_policy(policy, origin, &mut response);
+        }
+        if route.options.compress {
+            response = compression::compress(accept_encoding, response).await;
+        }
+
+        info!(
+            method = %method,
+            host = %host,
+            path = %path,
+            status = response.status().as_u16(),
+            "Request completed"
+        );
+
+        response
     };
 
-    info!(
-        method = %method,
-        host = %host,
-        path = %path,
-        status = response.status().as_u16(),
-        "Request completed"
-    );
+    // Per-domain/route log level overrides apply to everything logged
+    // while handling this request
+    let mut response = match registration.effective_log_level(route) {
+        Some(level) => handle.instrument(log_scope(level)).await,
+        None => handle.await,
+    };
+
+    if is_https
+        && let Some(hsts) = registration
+            .hsts_header()
+            .and_then(|hsts| HeaderValue::from_maybe_shared(hsts).ok())
+    {
+        response
+            .headers_mut()
+            .insert(header::STRICT_TRANSPORT_SECURITY, hsts);
+    }
+    if is_https && let Some(alt_svc) = &state.alt_svc {
+        response
+            .headers_mut()
+            .insert(header::ALT_SVC, alt_svc.clone());
+    }
 
+    shared
+        .stats
+        .record(&registration.display_pattern(), response.status());
     response
 }
 
+/// `301` to the same URL over HTTPS on `https_port`.
+fn https_redirect(host: &str, uri: &axum::http::Uri, https_port: u16) -> Response {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    };
+    let port = if https_port == 443 {
+        String::new()
+    } else {
+        format!(":{}", https_port)
+    };
+    let path = uri.path_and_query().map_or("/", |p| p.as_str());
+    let location = format!("https://{}{}{}", name, port, path);
+    match HeaderValue::from_str(&location) {
+        Ok(location) => Response::builder()
+            .status(StatusCode::MOVED_PERMANENTLY)
+            .header(header::LOCATION, location)
+            .body(Body::empty())
+            .unwrap(),
+        Err(_) => (StatusCode::BAD_REQUEST, "Invalid Host header").into_response(),
+    }
+}
+
+/// The path a proxied backend sees: the route's prefix stripped if asked,
+/// then the first matching rewrite rule applied. The query is kept.
+fn rewrite_request_path(route: &Route, request: &mut Request) {
+    let options = &route.options;
+    let mut path = request.uri().path().to_string();
+    if options.strip_prefix {
+        path = route.path.strip(&path);
+    }
+    if let Some(rewritten) = PathRewrite::apply_first(&options.path_rewrite, &path) {
+        debug!(from = %path, to = %rewritten, "Rewrote request path");
+        path = rewritten;
+    }
+    if path == request.uri().path() {
+        return;
+    }
+
+    let path_and_query = match request.uri().query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    };
+    match path_and_query.parse() {
+        Ok(uri) => *request.uri_mut() = uri,
+        Err(e) => debug!(path = %path_and_query, error = %e, "Rewritten path is not a valid URI"),
+    }
+}
+
+/// Put the route's path back on redirects to a path on the backend, so
+/// `Location: /login` from a stripped `/api` route sends the browser to
+/// `/api/login`.
+fn restore_location(prefix: &PathPrefix, headers: &mut HeaderMap) {
+    let Some(location) = headers.get(header::LOCATION).and_then(|v| v.to_str().ok()) else {
+        return;
+    };
+    // Absolute and protocol-relative URLs point somewhere else
+    if !location.starts_with('/') || location.starts_with("//") {
+        return;
+    }
+    if let Ok(value) = HeaderValue::from_str(&prefix.restore(location)) {
+        headers.insert(header::LOCATION, value);
+    }
+}
+
+/// A fresh random number for latency and fault injection.
+fn random() -> u64 {
+    RandomState::new().hash_one(Instant::now())
+}
+
+/// Stand-in for a failing backend. The header tells an injected failure
+/// apart from a real one in the browser's network tab.
+fn injected_fault(status: u16) -> Response {
+    let status = StatusCode::from_u16(status).unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+    (
+        status,
+        [("x-roxy-fault", "injected")],
+        format!("Injected fault: {}", status),
+    )
+        .into_response()
+}
+
 fn build_not_registered_response(domain: &str) -> Response {
     let domain = domain.split(':').next().unwrap_or(domain);
     let domain_raw = domain.trim_end_matches('.').to_lowercase();
@@ -228,6 +809,261 @@ fn build_no_route_response(registration: &DomainRegistration, host: &str, path:
         .unwrap()
 }
 
+/// Themed 403 page for a client the domain's access policy turns away.
+fn build_forbidden_response(
+    registration: &DomainRegistration,
+    client_ip: Option<IpAddr>,
+) -> Response {
+    let domain = theme::html_escape(&registration.display_pattern());
+    let client =
+        client_ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_canonical().to_string());
+    let image_data_uri = embedded_assets::roxy_error_data_uri();
+
+    let mut body = String::new();
+    body.push_str("<div class=\"error-container\">\n");
+    body.push_str("<div class=\"error-image\">\n");
+    body.push_str("<img src=\"");
+    body.push_str(image_data_uri);
+    body.push_str("\" alt=\"Access Denied - Roxy Fox\" ");
+    body.push_str("width=\"300\" height=\"225\">\n");
+    body.push_str("</div>\n");
+    body.push_str("<div class=\"error-card\">\n");
+    body.push_str("<h1 class=\"error-title\">Access Denied</h1>\n");
+    body.push_str("<p class=\"error-message\"><code>");
+    body.push_str(&domain);
+    body.push_str("</code> only answers ");
+    body.push_str(match registration.access() {
+        AccessPolicy::LocalhostOnly => "this machine",
+        AccessPolicy::Lan => "the local network",
+        _ => "selected addresses",
+    });
+    body.push_str(", not <code>");
+    body.push_str(&client);
+    body.push_str("</code>.</p>\n");
+    body.push_str("<div class=\"help-section\">\n");
+    body.push_str(
+        "<p class=\"help-label\">To let more clients in, on the machine running Roxy:</p>\n",
+    );
+    body.push_str("<div class=\"command\">roxy config set domains.");
+    body.push_str(&domain);
+    body.push_str(".access lan</div>\n");
+    body.push_str("<p class=\"help-label\">Then reload the Roxy daemon:</p>\n");
+    body.push_str("<div class=\"command\">roxy reload</div>\n");
+    body.push_str("</div></div></div>");
+
+    let html = theme::render_page("Access Denied", &body, ERROR_CSS, "");
+
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .header("Content-Type", "text/html; charset=utf-8")
+        .body(axum::body::Body::from(html))
+        .unwrap()
+}
+
+/// Themed 503 page for a domain in maintenance mode.
+fn build_maintenance_response(registration: &DomainRegistration) -> Response {
+    let domain = theme::html_escape(&registration.domain().to_string());
+    let image_data_uri = embedded_assets::roxy_error_data_uri();
+
+    let mut body = String::new();
+    body.push_str("<div class=\"error-container\">\n");
+    body.push_str("<div class=\"error-image\">\n");
+    body.push_str("<img src=\"");
+    body.push_str(image_data_uri);
+    body.push_str("\" alt=\"Under Maintenance - Roxy Fox\" ");
+    body.push_str("width=\"300\" height=\"225\">\n");
+    body.push_str("</div>\n");
+    body.push_str("<div class=\"error-card\">\n");
+    body.push_str("<h1 class=\"error-title\">Under Maintenance</h1>\n");
+    body.push_str("<p class=\"error-message\">");
+    match registration.maintenance_message() {
+        Some(message) => body.push_str(&theme::html_escape(message)),
+        None => {
+            body.push_str("<code>");
+            body.push_str(&domain);
+            body.push_str("</code> is down for maintenance. Please check back soon.");
+        }
+    }
+    body.push_str("</p>\n");
+    body.push_str("<div class=\"help-section\">\n");
+    body.push_str("<p class=\"help-label\">To bring it back, run:</p>\n");
+    body.push_str("<div class=\"command\">roxy maintenance off ");
+    if registration.is_wildcard() {
+        body.push_str("--wildcard ");
+    }
+    body.push_str(&domain);
+    body.push_str("</div>\n");
+    body.push_str("<p class=\"help-label\">Then reload the Roxy daemon:</p>\n");
+    body.push_str("<div class=\"command\">roxy reload</div>\n");
+    body.push_str("</div></div></div>");
+
+    let html = theme::render_page("Under Maintenance", &body, ERROR_CSS, "");
+
+    Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .header("Content-Type", "text/html; charset=utf-8")
+        .body(axum::body::Body::from(html))
+        .unwrap()
+}
+
+/// Themed 504 page for a backend that didn't `what` within `waited`.
+pub fn build_gateway_timeout_response(
+    target: &ProxyTarget,
+    what: &str,
+    waited: Duration,
+) -> Response {
+    let target = theme::html_escape(&target.to_string());
+    let image_data_uri = embedded_assets::roxy_error_data_uri();
+
+    let mut body = String::new();
+    body.push_str("<div class=\"error-container\">\n");
+    body.push_str("<div class=\"error-image\">\n");
+    body.push_str("<img src=\"");
+    body.push_str(image_data_uri);
+    body.push_str("\" alt=\"Gateway Timeout - Roxy Fox\" ");
+    body.push_str("width=\"300\" height=\"225\">\n");
+    body.push_str("</div>\n");
+    body.push_str("<div class=\"error-card\">\n");
+    body.push_str("<h1 class=\"error-title\">Gateway Timeout</h1>\n");
+    body.push_str("<p class=\"error-message\">The service at <code>");
+    body.push_str(&target);
+    body.push_str("</code> did not ");
+    body.push_str(what);
+    body.push_str(" within ");
+    body.push_str(&humantime::format_duration(waited).to_string());
+    body.push_str(".</p>\n");
+    body.push_str("<div class=\"help-section\">\n");
+    body.push_str(
+        "<p class=\"help-label\">If it needs longer, raise the limit (milliseconds):</p>\n",
+    );
+    body.push_str("<div class=\"command\">roxy config set daemon.request_timeout 120000</div>\n");
+    body.push_str("<p class=\"help-label\">Then reload the Roxy daemon:</p>\n");
+    body.push_str("<div class=\"command\">roxy reload</div>\n");
+    body.push_str("</div></div></div>");
+
+    let html = theme::render_page("Gateway Timeout", &body, ERROR_CSS, "");
+
+    Response::builder()
+        .status(StatusCode::GATEWAY_TIMEOUT)
+        .header("Content-Type", "text/html; charset=utf-8")
+        .body(axum::body::Body::from(html))
+        .unwrap()
+}
+
+/// Themed 502 page for a backend whose `cmd` didn't get it listening.
+pub fn build_start_failed_response(target: &ProxyTarget, cmd: &str, reason: &str) -> Response {
+    let target = theme::html_escape(&target.to_string());
+    let image_data_uri = embedded_assets::roxy_error_data_uri();
+
+    let mut body = String::new();
+    body.push_str("<div class=\"error-container\">\n");
+    body.push_str("<div class=\"error-image\">\n");
+    body.push_str("<img src=\"");
+    body.push_str(image_data_uri);
+    body.push_str("\" alt=\"Service Failed to Start - Roxy Fox\" ");
+    body.push_str("width=\"300\" height=\"225\">\n");
+    body.push_str("</div>\n");
+    body.push_str("<div class=\"error-card\">\n");
+    body.push_str("<h1 class=\"error-title\">Service Failed to Start</h1>\n");
+    body.push_str("<p class=\"error-message\">Roxy ran the command below for <code>");
+    body.push_str(&target);
+    body.push_str("</code>, but ");
+    body.push_str(&theme::html_escape(reason));
+    body.push_str(".</p>\n");
+    body.push_str("<div class=\"help-section\">\n");
+    body.push_str("<p class=\"help-label\">Try running it yourself to see its output:</p>\n");
+    body.push_str("<div class=\"command\">");
+    body.push_str(&theme::html_escape(cmd));
+    body.push_str("</div>\n");
+    body.push_str("</div></div></div>");
+
+    let html = theme::render_page("Service Failed to Start", &body, ERROR_CSS, "");
+
+    Response::builder()
+        .status(StatusCode::BAD_GATEWAY)
+        .header("Content-Type", "text/html; charset=utf-8")
+        .body(axum::body::Body::from(html))
+        .unwrap()
+}
+
+/// Themed 508 page for a request that was handed between domains until
+/// the hop count ran out.
+pub fn build_loop_detected_response(domain: &DomainName) -> Response {
+    let domain = theme::html_escape(domain.as_str());
+    let image_data_uri = embedded_assets::roxy_error_data_uri();
+
+    let mut body = String::new();
+    body.push_str("<div class=\"error-container\">\n");
+    body.push_str("<div class=\"error-image\">\n");
+    body.push_str("<img src=\"");
+    body.push_str(image_data_uri);
+    body.push_str("\" alt=\"Loop Detected - Roxy Fox\" ");
+    body.push_str("width=\"300\" height=\"225\">\n");
+    body.push_str("</div>\n");
+    body.push_str("<div class=\"error-card\">\n");
+    body.push_str("<h1 class=\"error-title\">Loop Detected</h1>\n");
+    body.push_str("<p class=\"error-message\">The request was handed on to <code>");
+    body.push_str(&domain);
+    body.push_str("</code> after passing through ");
+    body.push_str(&MAX_HOPS.to_string());
+    body.push_str(" domains already.</p>\n");
+    body.push_str("<div class=\"help-section\">\n");
+    body.push_str(
+        "<p class=\"help-label\">Check the <code>domain:</code> routes for a chain that leads back to itself:</p>\n",
+    );
+    body.push_str("<div class=\"command\">roxy list</div>\n");
+    body.push_str("</div></div></div>");
+
+    let html = theme::render_page("Loop Detected", &body, ERROR_CSS, "");
+
+    Response::builder()
+        .status(StatusCode::LOOP_DETECTED)
+        .header("Content-Type", "text/html; charset=utf-8")
+        .body(axum::body::Body::from(html))
+        .unwrap()
+}
+
+/// Themed 502 page for a backend that refused the connection, with how
+/// long health checks have seen it down when known.
+pub fn build_backend_down_response(target: &ProxyTarget, down_for: Option<Duration>) -> Response {
+    let target = theme::html_escape(&target.to_string());
+    let image_data_uri = embedded_assets::roxy_error_data_uri();
+
+    let mut body = String::new();
+    body.push_str("<div class=\"error-container\">\n");
+    body.push_str("<div class=\"error-image\">\n");
+    body.push_str("<img src=\"");
+    body.push_str(image_data_uri);
+    body.push_str("\" alt=\"Service Not Running - Roxy Fox\" ");
+    body.push_str("width=\"300\" height=\"225\">\n");
+    body.push_str("</div>\n");
+    body.push_str("<div class=\"error-card\">\n");
+    body.push_str("<h1 class=\"error-title\">Service Not Running</h1>\n");
+    body.push_str("<p class=\"error-message\">Nothing is listening at <code>");
+    body.push_str(&target);
+    body.push_str("</code>.");
+    if let Some(down_for) = down_for {
+        // Whole seconds read better than "2m 3s 417ms"
+        let down_for = Duration::from_secs(down_for.as_secs());
+        body.push_str(" The backend has been down for ");
+        body.push_str(&humantime::format_duration(down_for).to_string());
+        body.push('.');
+    }
+    body.push_str("</p>\n");
+    body.push_str("<div class=\"help-section\">\n");
+    body.push_str("<p class=\"help-label\">Start your service, then reload this page.</p>\n");
+    body.push_str("</div></div></div>");
+
+    let html = theme::render_page("Service Not Running", &body, ERROR_CSS, "");
+
+    Response::builder()
+        .status(StatusCode::BAD_GATEWAY)
+        .header("Content-Type", "text/html; charset=utf-8")
+        .extension(BackendUnreachable)
+        .body(axum::body::Body::from(html))
+        .unwrap()
+}
+
 fn wildcard_base_domain(domain: &str) -> Option<String> {
     let domain = domain.trim_end_matches('.');
     if !domain.ends_with(".roxy") {
@@ -294,8 +1130,19 @@ const ERROR_CSS: &str = "\
 
 #[cfg(test)]
 mod tests {
-    use super::AppState;
-    use crate::domain::{DomainName, DomainPattern, DomainRegistration, Route};
+    use super::{
+        AppState, SharedState, build_maintenance_response, create_router, restore_location,
+        rewrite_request_path,
+    };
+    use crate::domain::{
+        CustomCert, DomainName, DomainPattern, DomainRegistration, PathPrefix, PathRewrite, Route,
+        TcpForward,
+    };
+    use axum::body::Body;
+    use axum::extract::Request;
+    use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
 
     fn reg(domain: &str, wildcard: bool) -> DomainRegistration {
         let domain = DomainName::new(domain).unwrap();
@@ -312,7 +1159,7 @@ mod tests {
     fn test_exact_overrides_wildcard_for_base_domain() {
         let wildcard = reg("myapp.roxy", true);
         let exact = reg("myapp.roxy", false);
-        let state = AppState::new(vec![wildcard, exact]);
+        let state = AppState::new(vec![wildcard, exact], false);
 
         let found = state.get_domain("myapp.roxy").unwrap();
         // Exact patterns match before wildcards because both match,
@@ -323,7 +1170,7 @@ mod tests {
     #[test]
     fn test_wildcard_matches_subdomain() {
         let wildcard = reg("myapp.roxy", true);
-        let state = AppState::new(vec![wildcard]);
+        let state = AppState::new(vec![wildcard], false);
 
         let found = state.get_domain("blog.myapp.roxy").unwrap();
         assert!(found.is_wildcard());
@@ -333,7 +1180,7 @@ mod tests {
     #[test]
     fn test_wildcard_does_not_match_multi_level_subdomain() {
         let wildcard = reg("myapp.roxy", true);
-        let state = AppState::new(vec![wildcard]);
+        let state = AppState::new(vec![wildcard], false);
 
         // This previously matched (bug DA3) — now fixed by DomainPattern
         assert!(state.get_domain("a.b.myapp.roxy").is_none());
@@ -343,17 +1190,344 @@ mod tests {
     fn test_most_specific_wildcard_wins() {
         let broad = reg("myapp.roxy", true);
         let specific = reg("sub.myapp.roxy", true);
-        let state = AppState::new(vec![broad, specific]);
+        let state = AppState::new(vec![broad, specific], false);
 
         let found = state.get_domain("blog.sub.myapp.roxy").unwrap();
         assert_eq!(found.domain().as_str(), "sub.myapp.roxy");
     }
 
+    #[test]
+    fn disabled_domains_are_not_served() {
+        let mut disabled = reg("sub.myapp.roxy", false);
+        disabled.set_enabled(false);
+        disabled.set_tcp_forwards(vec![TcpForward::parse("5432=5433").unwrap()]);
+        let state = AppState::new(vec![disabled, reg("other.roxy", false)], false);
+
+        assert!(state.get_domain("sub.myapp.roxy").is_none());
+        assert!(state.tcp_ports().is_empty());
+
+        // A wildcard covering the name serves it instead
+        let mut disabled = reg("sub.myapp.roxy", false);
+        disabled.set_enabled(false);
+        let state = AppState::new(vec![disabled, reg("myapp.roxy", true)], false);
+        assert!(state.get_domain("sub.myapp.roxy").unwrap().is_wildcard());
+    }
+
     #[test]
     fn test_host_is_normalized_for_lookup() {
         let exact = reg("app.roxy", false);
-        let state = AppState::new(vec![exact]);
+        let state = AppState::new(vec![exact], false);
 
         assert!(state.get_domain("APP.ROXY:443").is_some());
     }
+
+    #[test]
+    fn test_custom_certificate_names_reach_their_domain() {
+        let tmp = tempfile::tempdir().unwrap();
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["*.dev.example.com".to_string()])
+            .unwrap()
+            .self_signed(&key)
+            .unwrap();
+        let custom = CustomCert {
+            cert: tmp.path().join("fullchain.pem"),
+            key: tmp.path().join("privkey.pem"),
+        };
+        std::fs::write(&custom.cert, cert.pem()).unwrap();
+
+        let mut with_cert = reg("myapp.roxy", false);
+        with_cert.set_custom_cert(Some(custom));
+        let state = AppState::new(vec![reg("other.roxy", false), with_cert], false);
+
+        let found = state.get_domain("app.dev.example.com:443").unwrap();
+        assert_eq!(found.domain().as_str(), "myapp.roxy");
+        assert!(state.get_domain("dev.example.com").is_none());
+    }
+
+    #[test]
+    fn test_localhost_alias_is_opt_in() {
+        let wildcard = reg("myapp.roxy", true);
+        let state = AppState::new(vec![wildcard.clone()], false);
+        assert!(state.get_domain("myapp.localhost").is_none());
+
+        let state = AppState::new(vec![wildcard], true);
+        assert!(state.get_domain("myapp.localhost:8080").is_some());
+        assert!(state.get_domain("blog.myapp.localhost").is_some());
+        assert!(state.get_domain("other.localhost").is_none());
+    }
+
+    #[test]
+    fn test_mdns_alias_is_opt_in() {
+        let exact = reg("myapp.roxy", false);
+        let state = AppState::new(vec![exact.clone()], false);
+        assert!(state.get_domain("myapp.local").is_none());
+
+        let state = AppState::new(vec![exact], false).with_mdns(true);
+        assert!(state.get_domain("myapp.local").is_some());
+        assert!(state.get_domain("other.local").is_none());
+    }
+
+    fn rewritten(route: &Route, uri: &str) -> String {
+        let mut request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        rewrite_request_path(route, &mut request);
+        request.uri().to_string()
+    }
+
+    #[test]
+    fn test_strip_prefix_keeps_query() {
+        let route = Route::parse("/api=3001;strip").unwrap();
+        assert_eq!(rewritten(&route, "/api/users?page=2"), "/users?page=2");
+    }
+
+    #[test]
+    fn test_path_rewrite_runs_after_strip() {
+        let mut route = Route::parse("/api=3001;strip").unwrap();
+        route.options.path_rewrite = vec![PathRewrite::parse("/old/(.*) -> /new/$1").unwrap()];
+        assert_eq!(rewritten(&route, "/api/old/users?x=1"), "/new/users?x=1");
+        assert_eq!(rewritten(&route, "/api/other"), "/other");
+    }
+
+    #[test]
+    fn test_restore_location_for_backend_paths_only() {
+        let prefix = PathPrefix::new("/api").unwrap();
+        let location = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::LOCATION, HeaderValue::from_static(value));
+            restore_location(&prefix, &mut headers);
+            headers[header::LOCATION].to_str().unwrap().to_string()
+        };
+        assert_eq!(location("/login?next=/"), "/api/login?next=/");
+        assert_eq!(location("https://example.com/"), "https://example.com/");
+        assert_eq!(location("//cdn.example.com/x"), "//cdn.example.com/x");
+        assert_eq!(location("relative"), "relative");
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_page_shows_the_message() {
+        let mut registration = reg("myapp.roxy", true);
+        registration.set_maintenance(true, Some("Back at <b>5pm</b>".to_string()));
+        let response = build_maintenance_response(&registration);
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains("Back at &lt;b&gt;5pm&lt;/b&gt;"));
+        assert!(html.contains("roxy maintenance off --wildcard myapp.roxy"));
+    }
+
+    fn chained(domain: &str, routes: &[&str]) -> DomainRegistration {
+        let pattern = DomainPattern::Exact(DomainName::new(domain).unwrap());
+        let routes = routes.iter().map(|r| Route::parse(r).unwrap()).collect();
+        DomainRegistration::new(pattern, routes)
+    }
+
+    async fn get(state: AppState, host: &str, path: &str) -> (StatusCode, String) {
+        let request = Request::builder()
+            .uri(path)
+            .header(header::HOST, host)
+            .body(Body::empty())
+            .unwrap();
+        let response = create_router(SharedState::new(state))
+            .oneshot(request)
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_https_only_redirects_and_sends_hsts() {
+        use super::super::proxy::Scheme;
+        use axum::Extension;
+
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("page.txt"), "secure").unwrap();
+        let mut registration = chained("myapp.roxy", &[&format!("/={}", tmp.path().display())]);
+        registration.enable_https();
+        registration.set_https_only(true, None);
+        let shared =
+            SharedState::new(AppState::new(vec![registration], false).with_https_port(8443));
+
+        let request = |scheme| {
+            let request = Request::builder()
+                .uri("/page.txt?v=1")
+                .header(header::HOST, "myapp.roxy:8080")
+                .body(Body::empty())
+                .unwrap();
+            create_router(shared.clone())
+                .layer(Extension(scheme))
+                .oneshot(request)
+        };
+
+        let response = request(Scheme::Http).await.unwrap();
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://myapp.roxy:8443/page.txt?v=1"
+        );
+        assert!(
+            !response
+                .headers()
+                .contains_key(header::STRICT_TRANSPORT_SECURITY)
+        );
+
+        let response = request(Scheme::Https).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::STRICT_TRANSPORT_SECURITY],
+            "max-age=300"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_https_responses_advertise_http3() {
+        use super::super::proxy::Scheme;
+        use axum::Extension;
+
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("page.txt"), "hello").unwrap();
+        let registration = chained("myapp.roxy", &[&format!("/={}", tmp.path().display())]);
+        let request = |state: AppState, scheme| {
+            let request = Request::builder()
+                .uri("/page.txt")
+                .header(header::HOST, "myapp.roxy")
+                .body(Body::empty())
+                .unwrap();
+            create_router(SharedState::new(state))
+                .layer(Extension(scheme))
+                .oneshot(request)
+        };
+
+        let http3 = || AppState::new(vec![registration.clone()], false).with_http3(Some(8443));
+        let response = request(http3(), Scheme::Https).await.unwrap();
+        assert_eq!(
+            response.headers()[header::ALT_SVC],
+            "h3=\":8443\"; ma=86400"
+        );
+        let response = request(http3(), Scheme::Http).await.unwrap();
+        assert!(!response.headers().contains_key(header::ALT_SVC));
+
+        let tcp_only = AppState::new(vec![registration.clone()], false);
+        let response = request(tcp_only, Scheme::Https).await.unwrap();
+        assert!(!response.headers().contains_key(header::ALT_SVC));
+    }
+
+    #[tokio::test]
+    async fn test_only_answered_preflights_skip_the_login() {
+        use crate::domain::BasicAuth;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let route = Route::parse(&format!("/={}", tmp.path().display())).unwrap();
+        let mut auth = BasicAuth::default();
+        auth.set_user("admin", "hash");
+        let shared = |preflight| {
+            let mut route = route.clone();
+            route.options.preflight = preflight;
+            let pattern = DomainPattern::Exact(DomainName::new("myapp.roxy").unwrap());
+            let mut registration = DomainRegistration::new(pattern, vec![route]);
+            registration.set_auth(Some(auth.clone()));
+            SharedState::new(AppState::new(vec![registration], false))
+        };
+        let preflight = || {
+            Request::builder()
+                .method("OPTIONS")
+                .uri("/")
+                .header(header::HOST, "myapp.roxy")
+                .header(header::ORIGIN, "https://other.roxy")
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = create_router(shared(false))
+            .oneshot(preflight())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = create_router(shared(true))
+            .oneshot(preflight())
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+    }
+
+    #[tokio::test]
+    async fn test_route_chains_to_another_domain() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("page.txt"), "from old.roxy").unwrap();
+        let old = chained("old.roxy", &[&format!("/={}", tmp.path().display())]);
+        let new = chained("new.roxy", &["/legacy=domain:old.roxy;strip"]);
+        let state = AppState::new(vec![old, new], false);
+
+        let (status, body) = get(state, "new.roxy", "/legacy/page.txt").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "from old.roxy");
+    }
+
+    #[tokio::test]
+    async fn test_unregistered_roxy_roxy_serves_the_root_ca() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ca = tmp.path().join("ca.crt");
+        std::fs::write(&ca, "-----BEGIN CERTIFICATE-----\n").unwrap();
+        let request = |host: &str| {
+            Request::builder()
+                .uri("/roxy-ca.pem")
+                .header(header::HOST, host)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let shared = SharedState::new(AppState::new(vec![], false)).with_ca_cert(ca.clone());
+        let response = create_router(shared)
+            .oneshot(request("roxy.roxy"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // A registered roxy.roxy is served like any other domain
+        std::fs::write(tmp.path().join("roxy-ca.pem"), "from the site").unwrap();
+        let static_dir = format!("/={}", tmp.path().display());
+        let state = AppState::new(vec![chained("roxy.roxy", &[&static_dir])], false);
+        let shared = SharedState::new(state).with_ca_cert(ca);
+        let response = create_router(shared)
+            .oneshot(request("roxy.roxy"))
+            .await
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "from the site");
+    }
+
+    #[tokio::test]
+    async fn test_client_cannot_forge_chain_hops() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("page.txt"), "from old.roxy").unwrap();
+        let old = chained("old.roxy", &[&format!("/={}", tmp.path().display())]);
+        let new = chained("new.roxy", &["/=domain:old.roxy"]);
+        let state = AppState::new(vec![old, new], false);
+
+        let request = Request::builder()
+            .uri("/page.txt")
+            .header(header::HOST, "new.roxy")
+            .header("x-roxy-hops", "99")
+            .body(Body::empty())
+            .unwrap();
+        let response = create_router(SharedState::new(state))
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_chain_loop_ends_with_an_error() {
+        let a = chained("a.roxy", &["/=domain:b.roxy"]);
+        let b = chained("b.roxy", &["/=domain:a.roxy"]);
+        let state = AppState::new(vec![a, b], false);
+
+        let (status, body) = get(state, "a.roxy", "/").await;
+        assert_eq!(status, StatusCode::LOOP_DETECTED);
+        assert!(body.contains("Loop Detected"));
+    }
 }
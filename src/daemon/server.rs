@@ -1,122 +1,318 @@
-use std::net::{Ipv4Addr, SocketAddr};
+use std::fs;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
-use axum::{
-    Extension, extract::ConnectInfo, extract::Request, middleware::Next, response::Response,
-};
+use axum::{Extension, Router};
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
-use tokio_rustls::TlsAcceptor;
-use tracing::{error, info, warn};
-
-use super::dns_server::DnsServer;
-use super::proxy::{ClientAddr, Scheme};
-use super::router::{AppState, create_router};
-use super::tls::create_tls_acceptor;
-use crate::infrastructure::config::Config;
-use crate::infrastructure::network::get_lan_ip;
-use crate::infrastructure::paths::RoxyPaths;
+use tower::ServiceExt;
+use tracing::{debug, error, info, warn};
 
-/// Middleware that copies the client IP from `ConnectInfo` into a `ClientAddr` extension.
-async fn inject_client_addr(
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
-    mut request: Request,
-    next: Next,
-) -> Response {
-    request.extensions_mut().insert(ClientAddr(addr.ip()));
-    next.run(request).await
-}
+use super::cert_renewal;
+use super::cert_watch;
+use super::config_watch;
+use super::control::ControlServer;
+use super::dns_server::{Answers, DnsServer, IpResolver};
+use super::forward_proxy::ForwardProxy;
+use super::health_check;
+use super::http3::Http3Server;
+use super::mdns::{MdnsResponder, local_names};
+use super::network_watch;
+use super::proxy::{ClientAddr, HeaderCasing, Scheme, Timeouts};
+use super::reload::{Reloader, sync_hosts_file};
+use super::router::{AppState, SharedState, create_router};
+use super::tcp_forward;
+use super::tls::{
+    DomainCerts, SharedAcceptor, create_intercept_acceptor, create_tls_acceptor,
+    create_upstream_connector,
+};
+use super::tls_passthrough;
+use crate::infrastructure::certs::CertsConfig;
+use crate::infrastructure::certs::ca::RootCA;
+use crate::infrastructure::config::{Config, DaemonConfig};
+use crate::infrastructure::dns::DnsBackend;
+use crate::infrastructure::network::{get_lan_ip, get_lan_ipv6, wsl};
+use crate::infrastructure::paths::RoxyPaths;
+use crate::infrastructure::service_user::{ServiceUser, is_root};
+use crate::infrastructure::tls::TlsConfig;
+use crate::infrastructure::tracing::LogLevelHandle;
 
 pub struct Server {
-    state: Arc<AppState>,
-    tls_acceptor: Option<TlsAcceptor>,
-    http_port: u16,
-    https_port: u16,
-    dns_port: u16,
+    state: SharedState,
+    tls_acceptor: Option<SharedAcceptor>,
+    daemon: DaemonConfig,
+    certs: CertsConfig,
+    tls: TlsConfig,
+    dns_answers: Answers,
+    /// Names to publish over mDNS, when it's on.
+    mdns_names: Vec<String>,
     lan_ip: Ipv4Addr,
+    lan_ipv6: Option<Ipv6Addr>,
+    config_path: PathBuf,
+    paths: RoxyPaths,
+    log_level: LogLevelHandle,
 }
 
 impl Server {
-    pub fn new(config: &Config, paths: &RoxyPaths) -> Result<Self> {
+    pub fn new(
+        config: &Config,
+        config_path: &Path,
+        paths: &RoxyPaths,
+        log_level: LogLevelHandle,
+    ) -> Result<Self> {
         // Validate config before starting
         config.validate()?;
 
         let registrations = config.registrations();
 
-        // Collect patterns for domains with HTTPS enabled
-        let https_patterns: Vec<_> = registrations
-            .iter()
-            .filter(|d| d.is_https_enabled())
-            .map(|d| d.pattern().clone())
-            .collect();
+        if config.daemon.dns_backend == DnsBackend::Hosts {
+            match sync_hosts_file(&registrations) {
+                Ok(true) => info!("Updated the hosts file"),
+                Ok(false) => {}
+                Err(e) => warn!(error = %e, "Registered domains may not resolve"),
+            }
+        }
+
+        let dns_answers = Answers::from_config(&config.daemon).with_records(&registrations);
+        let mdns_names = local_names(&registrations);
 
-        let state = Arc::new(AppState::new(registrations));
+        let tls_acceptor = create_tls_acceptor(
+            &DomainCerts::new(&registrations),
+            &paths.certs_dir,
+            &paths.data_dir,
+            config.daemon.localhost,
+            &config.certs,
+            &config.tls,
+        )?
+        .map(SharedAcceptor::new);
 
-        let tls_acceptor = create_tls_acceptor(&https_patterns, &paths.certs_dir, &paths.data_dir)?;
+        let state = SharedState::new(
+            AppState::new(registrations, config.daemon.localhost)
+                .with_mdns(config.daemon.mdns)
+                .with_timeouts(Timeouts::from_config(&config.daemon))
+                .with_health_checks(config.daemon.health_check_interval)
+                .with_https_port(config.daemon.public_https_port())
+                .with_http3(config.daemon.http3_port()),
+        )
+        .with_backend_logs(paths.clone())
+        .with_ca_cert(RootCA::new(paths.data_dir.clone()).cert_path());
 
         // Get LAN IP for DNS responses (DNS server handles source-based resolution)
         let lan_ip = get_lan_ip();
+        let lan_ipv6 = get_lan_ipv6();
 
         Ok(Self {
             state,
             tls_acceptor,
-            http_port: config.daemon.http_port,
-            https_port: config.daemon.https_port,
-            dns_port: config.daemon.dns_port,
+            daemon: config.daemon.clone(),
+            certs: config.certs.clone(),
+            tls: config.tls.clone(),
+            dns_answers,
+            mdns_names,
             lan_ip,
+            lan_ipv6,
+            config_path: config_path.to_path_buf(),
+            paths: paths.clone(),
+            log_level,
         })
     }
 
     pub async fn run(self) -> Result<()> {
+        let http_port = self.daemon.http_port;
+        let https_port = self.daemon.https_port;
         info!(
-            http = self.http_port,
-            https = self.https_port,
-            dns = self.dns_port,
+            http = http_port,
+            https = https_port,
+            dns = self.daemon.dns_port,
             lan_ip = %self.lan_ip,
+            lan_ipv6 = ?self.lan_ipv6,
             "Roxy daemon starting"
         );
 
+        let wsl = wsl::detect();
+        if let Some(wsl) = wsl {
+            info!(networking = ?wsl.networking, windows_host = ?wsl.windows_host, "Running inside WSL2");
+        }
+
+        // Bind everything that may need root before dropping privileges.
         // Start DNS server with LAN IP (handles source-based IP resolution internally)
-        let dns_server = DnsServer::new(self.dns_port, self.lan_ip);
-        let dns_handle = tokio::spawn(async move {
-            if let Err(e) = dns_server.run().await {
-                error!(error = %e, "DNS server error");
+        let dns_task = match self.daemon.dns_backend {
+            DnsBackend::Resolver => {
+                let resolver = IpResolver::new(self.lan_ip, &self.daemon.container_networks)
+                    .with_windows_host(wsl.and_then(|wsl| wsl.windows_host))
+                    .with_ipv6(self.lan_ipv6);
+                Some(
+                    DnsServer::bind(
+                        self.daemon.dns_port,
+                        Arc::new(resolver),
+                        self.dns_answers.clone(),
+                    )?
+                    .spawn(),
+                )
             }
-        });
+            DnsBackend::Hosts => {
+                info!("Resolving domains through the hosts file, DNS server disabled");
+                None
+            }
+            DnsBackend::Dnsmasq => {
+                info!("Resolving domains through dnsmasq, DNS server disabled");
+                None
+            }
+        };
 
-        let http_addr = SocketAddr::from(([0, 0, 0, 0], self.http_port));
-        let https_addr = SocketAddr::from(([0, 0, 0, 0], self.https_port));
+        // Like the control channel, mDNS is a convenience the daemon can
+        // do without
+        let mdns_task = match self.daemon.mdns {
+            true => MdnsResponder::bind(self.lan_ip, self.mdns_names)
+                .inspect_err(|e| error!(error = format!("{:#}", e), "mDNS error"))
+                .ok()
+                .map(MdnsResponder::spawn),
+            false => None,
+        };
 
-        // Start HTTP server - always serve content (no redirect to HTTPS)
-        let http_router = create_router(self.state.clone())
-            .layer(Extension(Scheme::Http))
-            .layer(axum::middleware::from_fn(inject_client_addr));
+        // The control channel is a convenience; the daemon keeps serving
+        // traffic if it can't be set up
+        let control_listener = ControlServer::listen(&self.paths.control_socket)
+            .inspect_err(|e| error!(error = %e, "Control channel error"))
+            .ok();
 
-        let http_listener = TcpListener::bind(http_addr).await.context(format!(
+        let http_listener = bind_dual_stack(http_port).context(format!(
             "Failed to bind to port {}. Is another service using it? Try: sudo lsof -i :{}",
-            self.http_port, self.http_port
+            http_port, http_port
         ))?;
+        info!(addr = %http_listener.local_addr()?, "HTTP server listening");
 
-        info!(addr = %http_addr, "HTTP server listening");
+        let https_listener = match self.tls_acceptor {
+            Some(_) => {
+                let listener = bind_dual_stack(https_port).context(format!(
+                    "Failed to bind to port {}. Is another service using it? Try: sudo lsof -i :{}",
+                    https_port, https_port
+                ))?;
+                info!(addr = %listener.local_addr()?, "HTTPS server listening");
+                Some(listener)
+            }
+            None => None,
+        };
 
-        let http_server = tokio::spawn(async move {
-            axum::serve(
-                http_listener,
-                http_router.into_make_service_with_connect_info::<SocketAddr>(),
+        let http3_server = match (&self.tls_acceptor, self.daemon.http3_port()) {
+            (Some(tls_acceptor), Some(port)) => {
+                let server = Http3Server::bind(port, &tls_acceptor.current())?;
+                info!(addr = %server.local_addr()?, "HTTP/3 server listening");
+                Some(server)
+            }
+            _ => None,
+        };
+
+        let proxy_listener = match self.daemon.proxy_port {
+            Some(port) => {
+                let addr = SocketAddr::from(([0, 0, 0, 0], port));
+                let listener = TcpListener::bind(addr).await.context(format!(
+                    "Failed to bind to port {}. Is another service using it? Try: sudo lsof -i :{}",
+                    port, port
+                ))?;
+                info!(addr = %addr, "Forward proxy listening");
+                Some(listener)
+            }
+            None => None,
+        };
+
+        let tcp_listeners = tcp_forward::bind(&self.state.current().tcp_ports()).await;
+
+        if let Some(user) = &self.daemon.user {
+            prepare_backend_logs(&self.paths.backend_logs_dir(), user);
+            drop_privileges(user)?;
+        }
+
+        for listener in tcp_listeners {
+            tokio::spawn(tcp_forward::run(listener, self.state.clone()));
+        }
+
+        if let Some(listener) = proxy_listener {
+            let intercept_tls = if self.daemon.proxy_intercept.is_empty() {
+                None
+            } else {
+                create_intercept_acceptor(&self.paths.data_dir, &self.certs, &self.tls)?
+            };
+            let proxy = ForwardProxy::new(
+                self.state.clone(),
+                self.tls_acceptor.clone(),
+                self.daemon.proxy_intercept.clone(),
+                intercept_tls,
+                create_upstream_connector(),
             )
-            .await
-            .map_err(|e| anyhow::anyhow!("HTTP server error: {}", e))
+            .with_access(self.daemon.proxy_access.clone())
+            .with_local_targets(self.daemon.proxy_local_targets);
+            tokio::spawn(proxy.run(listener));
+        }
+
+        // A reload that moves the DNS port keeps the resolver
+        if let Some(dns_task) = &dns_task {
+            tokio::spawn(network_watch::run(dns_task.ip_resolver().clone()));
+        }
+
+        let reloader = Arc::new(
+            Reloader::new(
+                self.config_path,
+                self.paths.clone(),
+                self.daemon,
+                self.state.clone(),
+                self.tls_acceptor.clone(),
+                dns_task,
+            )
+            .with_mdns(mdns_task),
+        );
+        tokio::spawn(cert_renewal::run(reloader.clone()));
+        tokio::spawn(cert_watch::run(reloader.clone(), self.paths.clone()));
+        tokio::spawn(config_watch::run(reloader.clone()));
+        if let Some(listener) = control_listener {
+            let control_server = ControlServer::new(
+                self.log_level,
+                reloader,
+                self.state.stats(),
+                self.state.health(),
+                self.state.cache(),
+                self.state.supervisor(),
+            );
+            tokio::spawn(control_server.run(listener));
+        }
+
+        tokio::spawn(health_check::run(self.state.clone()));
+
+        // Start HTTP server - always serve content (no redirect to HTTPS)
+        let http_router = create_router(self.state.clone()).layer(Extension(Scheme::Http));
+
+        let http_server = tokio::spawn(async move {
+            loop {
+                let (stream, addr) = match http_listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        error!(error = %e, "Failed to accept connection");
+                        continue;
+                    }
+                };
+
+                let client = ClientAddr(addr.ip().to_canonical());
+                let router = http_router.clone().layer(Extension(client));
+                tokio::spawn(serve_connection(stream, router));
+            }
         });
 
         // Start HTTPS server if TLS is available
-        if let Some(tls_acceptor) = self.tls_acceptor {
+        if let (Some(tls_acceptor), Some(https_listener)) = (self.tls_acceptor, https_listener) {
+            let state = self.state.clone();
             let https_router = create_router(self.state).layer(Extension(Scheme::Https));
-            let https_listener = TcpListener::bind(https_addr).await.context(format!(
-                "Failed to bind to port {}. Is another service using it? Try: sudo lsof -i :{}",
-                self.https_port, self.https_port
-            ))?;
 
-            info!(addr = %https_addr, "HTTPS server listening");
+            if let Some(server) = http3_server {
+                tokio::spawn(server.run(tls_acceptor.clone(), https_router.clone()));
+            }
 
             let https_server = tokio::spawn(async move {
                 loop {
@@ -128,52 +324,123 @@ impl Server {
                         }
                     };
 
-                    let acceptor = tls_acceptor.clone();
-                    // The HTTPS path uses manual TLS accept, so ConnectInfo is not
-                    // available. Instead, inject the client IP directly as an Extension
-                    // on each accepted connection.
-                    let router = https_router.clone().layer(Extension(ClientAddr(addr.ip())));
+                    // Picked per connection so a reload applies to new ones
+                    let acceptor = tls_acceptor.current();
+                    let client = ClientAddr(addr.ip().to_canonical());
+                    let router = https_router.clone().layer(Extension(client));
+                    let state = state.current();
 
                     tokio::spawn(async move {
-                        let stream = match acceptor.accept(stream).await {
-                            Ok(s) => s,
-                            Err(e) => {
-                                warn!(error = %e, "TLS handshake failed");
-                                return;
-                            }
-                        };
-
-                        let io = hyper_util::rt::TokioIo::new(stream);
-                        let service =
-                            hyper_util::service::TowerToHyperService::new(router.into_service());
-
-                        if let Err(e) = hyper_util::server::conn::auto::Builder::new(
-                            hyper_util::rt::TokioExecutor::new(),
-                        )
-                        .serve_connection_with_upgrades(io, service)
-                        .await
+                        if state.has_tls_passthrough()
+                            && let Some(target) = tls_passthrough::peek_server_name(&stream)
+                                .await
+                                .and_then(|host| state.passthrough_target(&host))
                         {
-                            error!(error = %e, "Error serving connection");
+                            debug!(client = %addr, target = %target, "TLS passthrough");
+                            return tcp_forward::pipe(stream, &target).await;
+                        }
+                        match acceptor.accept(stream).await {
+                            Ok(stream) => serve_connection(stream, router).await,
+                            Err(e) => warn!(error = %e, "TLS handshake failed"),
                         }
                     });
                 }
             });
 
             tokio::select! {
-                r = http_server => r??,
-                _ = https_server => {},
-                _ = dns_handle => {},
+                r = http_server => r?,
+                r = https_server => r?,
             }
         } else {
             warn!(
                 "No HTTPS certificates found, running HTTP only. Register a domain with sudo to enable HTTPS."
             );
-            tokio::select! {
-                r = http_server => r??,
-                _ = dns_handle => {},
-            }
+            http_server.await?;
         }
 
         Ok(())
     }
 }
+
+/// Listen on `port` over IPv4 and IPv6, so the AAAA answers of the DNS
+/// server lead somewhere. Hosts without IPv6 get an IPv4-only listener.
+fn bind_dual_stack(port: u16) -> io::Result<TcpListener> {
+    let bind = |addr: SocketAddr| -> io::Result<TcpListener> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        if addr.is_ipv6() {
+            // IPv4 clients arrive as mapped addresses (::ffff:a.b.c.d)
+            socket.set_only_v6(false)?;
+        }
+        socket.set_reuse_address(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&addr.into())?;
+        socket.listen(1024)?;
+        TcpListener::from_std(socket.into())
+    };
+    match bind(SocketAddr::from((Ipv6Addr::UNSPECIFIED, port))) {
+        Err(e)
+            if e.kind() != io::ErrorKind::AddrInUse
+                && e.kind() != io::ErrorKind::PermissionDenied =>
+        {
+            debug!(error = %e, "IPv6 unavailable, listening on IPv4 only");
+            bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)))
+        }
+        result => result,
+    }
+}
+
+/// Switch to the configured service user. Only root can; anyone else is
+/// already unprivileged, so the setting is ignored.
+fn drop_privileges(user: &str) -> Result<()> {
+    if !is_root() {
+        warn!(user, "Not running as root, ignoring daemon.user");
+        return Ok(());
+    }
+    ServiceUser::lookup(user)?.switch_to()?;
+    info!(user, "Dropped root privileges");
+    Ok(())
+}
+
+/// Hand the backend log directory to the service user, which can't
+/// create it under the root-owned log directory itself.
+fn prepare_backend_logs(dir: &Path, user: &str) {
+    if !is_root() {
+        return;
+    }
+    let prepared = fs::create_dir_all(dir)
+        .map_err(|e| e.to_string())
+        .and_then(|()| {
+            ServiceUser::lookup(user)
+                .and_then(|user| user.chown_all(dir))
+                .map_err(|e| e.to_string())
+        });
+    if let Err(e) = prepared {
+        warn!(dir = %dir.display(), error = %e, "Backend output may not be logged");
+    }
+}
+
+/// Serve one accepted connection, plain or TLS. The client IP is
+/// injected as an extension on the router by the caller.
+pub async fn serve_connection<S>(stream: S, router: Router)
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    // Set the recorded casing aside as soon as a request arrives, so
+    // only the routes that ask for it pass it on
+    let service = TowerToHyperService::new(
+        router
+            .into_service()
+            .map_request(HeaderCasing::set_aside::<Incoming>),
+    );
+    let mut builder = Builder::new(TokioExecutor::new());
+    // Record how HTTP/1 clients spelled their headers, for routes that
+    // pass the original casing on to the backend
+    builder.http1().preserve_header_case(true);
+
+    if let Err(e) = builder
+        .serve_connection_with_upgrades(TokioIo::new(stream), service)
+        .await
+    {
+        error!(error = %e, "Error serving connection");
+    }
+}
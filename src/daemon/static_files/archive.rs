@@ -0,0 +1,400 @@
+//! Zip downloads of directories on static routes (`?download=zip`).
+//!
+//! The directory is walked first, so limits are checked before anything
+//! is sent; the archive is then written on a blocking thread and
+//! streamed to the client as it is built.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+use axum::{
+    body::{Body, Bytes},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use hyper::body::{Body as HttpBody, Frame};
+use tokio::sync::mpsc;
+use tokio::task;
+use tracing::{debug, warn};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::hidden::HiddenPaths;
+
+/// Directories nested deeper than this aren't archived.
+const MAX_DEPTH: usize = 32;
+
+/// Most files and directories one archive holds.
+const MAX_ENTRIES: usize = 100_000;
+
+/// Most bytes, before compression, one archive holds.
+const MAX_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Bytes written to the client at a time.
+const CHUNK: usize = 64 * 1024;
+
+/// Whether the query asks for the directory as a zip archive.
+pub(super) fn wants_zip(query: Option<&str>) -> bool {
+    query.is_some_and(|q| q.split('&').any(|pair| pair == "download=zip"))
+}
+
+/// Stream `dirs`, merged, as a zip archive, leaving out hidden paths.
+pub(super) async fn download_zip(dirs: Vec<(PathBuf, HiddenPaths)>) -> Response {
+    let Some(dir) = dirs.first().map(|(dir, _)| dir.clone()) else {
+        return (StatusCode::NOT_FOUND, "Not found").into_response();
+    };
+    let entries = match task::spawn_blocking(move || collect(&dirs)).await {
+        Ok(Ok(entries)) => entries,
+        Ok(Err(Walk::TooLarge(reason))) => {
+            debug!(dir = %dir.display(), reason, "Directory too large to zip");
+            let message = format!("Directory too large to download as zip: {reason}");
+            return (StatusCode::FORBIDDEN, message).into_response();
+        }
+        Ok(Err(Walk::Io(e))) => {
+            warn!(dir = %dir.display(), error = %e, "Failed to read directory for zip");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read directory",
+            )
+                .into_response();
+        }
+        Err(_) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response();
+        }
+    };
+
+    let disposition = format!("attachment; filename=\"{}.zip\"", download_name(&dir));
+
+    let (tx, rx) = mpsc::channel(8);
+    task::spawn_blocking(move || {
+        let sender = tx.clone();
+        let writer = BufWriter::with_capacity(CHUNK, ChannelWriter(tx));
+        if let Err(e) = write_zip(writer, &entries) {
+            debug!(dir = %dir.display(), error = %e, "Zip download ended early");
+            let _ = sender.blocking_send(Err(e));
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/zip")
+        .header(header::CONTENT_DISPOSITION, disposition)
+        .body(Body::new(ChannelBody(rx)))
+        .unwrap_or_else(|_| {
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response()
+        })
+}
+
+/// The directory's name as it can go in a quoted `Content-Disposition`
+/// filename: visible ASCII only, quotes and backslashes replaced.
+fn download_name(dir: &Path) -> String {
+    let name: String = dir
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            ' ' | '!'..='~' => c,
+            _ => '_',
+        })
+        .collect();
+    if name.is_empty() {
+        "download".to_string()
+    } else {
+        name
+    }
+}
+
+/// A file or directory to archive, named relative to the archived
+/// directory.
+struct Entry {
+    path: PathBuf,
+    name: String,
+    is_dir: bool,
+}
+
+enum Walk {
+    TooLarge(&'static str),
+    Io(io::Error),
+}
+
+impl From<io::Error> for Walk {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Everything under `dirs` that isn't hidden, parents before children.
+/// When paths clash, the earlier directory wins, along with everything
+/// below a file or directory it contributed. Symlinks are followed to
+/// files the route's policy allows, never to directories, so loops
+/// can't occur.
+fn collect(dirs: &[(PathBuf, HiddenPaths)]) -> Result<Vec<Entry>, Walk> {
+    let mut entries = Vec::new();
+    // Name of every entry so far, and whether it's a directory
+    let mut taken: HashMap<String, bool> = HashMap::new();
+    let mut total = 0;
+
+    for (dir, hidden) in dirs {
+        let mut pending = vec![(dir.canonicalize()?, String::new(), 0)];
+
+        while let Some((path, prefix, depth)) = pending.pop() {
+            if depth > MAX_DEPTH {
+                return Err(Walk::TooLarge("nested too deeply"));
+            }
+            let mut children: Vec<_> = fs::read_dir(&path)?.collect::<Result<_, _>>()?;
+            children.sort_by_key(|child| child.file_name());
+
+            for child in children {
+                let child_path = child.path();
+                if hidden.hides(&child_path) {
+                    continue;
+                }
+                let name = format!("{prefix}{}", child.file_name().to_string_lossy());
+
+                let file_type = child.file_type()?;
+                let (path, is_dir) = if file_type.is_symlink() {
+                    match child_path.canonicalize() {
+                        Ok(target)
+                            if hidden.follows(&target)
+                                && target.is_file()
+                                && !hidden.hides(&target) =>
+                        {
+                            (target, false)
+                        }
+                        _ => continue,
+                    }
+                } else {
+                    (child_path, file_type.is_dir())
+                };
+
+                match taken.get(&name) {
+                    // Same directory in an earlier root: merge what's inside
+                    Some(true) if is_dir => {
+                        pending.push((path, format!("{name}/"), depth + 1));
+                        continue;
+                    }
+                    Some(_) => continue,
+                    None => {}
+                }
+                taken.insert(name.clone(), is_dir);
+
+                if is_dir {
+                    pending.push((path.clone(), format!("{name}/"), depth + 1));
+                } else {
+                    total += fs::metadata(&path)?.len();
+                    if total > MAX_BYTES {
+                        return Err(Walk::TooLarge("more than 2 GiB"));
+                    }
+                }
+                entries.push(Entry { path, name, is_dir });
+                if entries.len() > MAX_ENTRIES {
+                    return Err(Walk::TooLarge("more than 100000 files"));
+                }
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+fn write_zip(writer: impl Write, entries: &[Entry]) -> io::Result<()> {
+    let mut zip = ZipWriter::new_stream(writer);
+
+    for entry in entries {
+        let metadata = fs::metadata(&entry.path)?;
+        let mut options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(Some(1));
+        if let Some(modified) = metadata.modified().ok().and_then(zip_time) {
+            options = options.last_modified_time(modified);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            options = options.unix_permissions(metadata.permissions().mode());
+        }
+
+        if entry.is_dir {
+            zip.add_directory(&entry.name, options)?;
+        } else {
+            zip.start_file(&entry.name, options)?;
+            io::copy(&mut File::open(&entry.path)?, &mut zip)?;
+        }
+    }
+
+    zip.finish()?.flush()
+}
+
+/// Zip timestamps carry no time zone and start in 1980; UTC is used.
+fn zip_time(modified: SystemTime) -> Option<zip::DateTime> {
+    let utc = time::OffsetDateTime::from(modified);
+    zip::DateTime::from_date_and_time(
+        u16::try_from(utc.year()).ok()?,
+        utc.month().into(),
+        utc.day(),
+        utc.hour(),
+        utc.minute(),
+        utc.second(),
+    )
+    .ok()
+}
+
+/// Hands what the archive writer produces to the response body.
+struct ChannelWriter(mpsc::Sender<io::Result<Bytes>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client went away"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A response body fed by a [`ChannelWriter`].
+struct ChannelBody(mpsc::Receiver<io::Result<Bytes>>);
+
+impl HttpBody for ChannelBody {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, io::Error>>> {
+        self.0
+            .poll_recv(cx)
+            .map(|chunk| chunk.map(|chunk| chunk.map(Frame::data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{PathGlob, RouteOptions};
+    use http_body_util::BodyExt;
+    use std::io::{Cursor, Read};
+
+    async fn archive_of(dir: &Path, options: &RouteOptions) -> zip::ZipArchive<Cursor<Bytes>> {
+        let hidden = HiddenPaths::new(dir, options);
+        let response = download_zip(vec![(dir.to_path_buf(), hidden)]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/zip");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        zip::ZipArchive::new(Cursor::new(body)).unwrap()
+    }
+
+    #[test]
+    fn detects_zip_requests() {
+        assert!(wants_zip(Some("download=zip")));
+        assert!(wants_zip(Some("sort=name&download=zip")));
+        assert!(!wants_zip(Some("download=tar")));
+        assert!(!wants_zip(None));
+    }
+
+    #[test]
+    fn download_name_is_visible_ascii() {
+        assert_eq!(download_name(Path::new("/srv/dist")), "dist");
+        assert_eq!(download_name(Path::new("/srv/a\"b\\c")), "a_b_c");
+        assert_eq!(download_name(Path::new("/srv/new\nline\x7f")), "new_line_");
+        assert_eq!(download_name(Path::new("/srv/café")), "caf_");
+        assert_eq!(download_name(Path::new("/")), "download");
+    }
+
+    #[tokio::test]
+    async fn archives_the_directory_without_hidden_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dist = tmp.path().join("dist");
+        fs::create_dir_all(dist.join("assets")).unwrap();
+        fs::create_dir(dist.join(".cache")).unwrap();
+        fs::write(dist.join("index.html"), "<h1>hi</h1>").unwrap();
+        fs::write(dist.join("assets/app.js"), "console.log(1)").unwrap();
+        fs::write(dist.join("assets/app.js.map"), "{}").unwrap();
+        fs::write(dist.join(".cache/state"), "x").unwrap();
+        let options = RouteOptions {
+            deny: vec![PathGlob::parse("*.map").unwrap()],
+            ..RouteOptions::default()
+        };
+
+        let mut archive = archive_of(&dist, &options).await;
+        let mut names: Vec<_> = archive.file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(names, ["assets/", "assets/app.js", "index.html"]);
+
+        let mut content = String::new();
+        archive
+            .by_name("assets/app.js")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "console.log(1)");
+    }
+
+    #[tokio::test]
+    async fn merged_directories_are_archived_once() {
+        let dist = tempfile::tempdir().unwrap();
+        let public = tempfile::tempdir().unwrap();
+        fs::create_dir(dist.path().join("img")).unwrap();
+        fs::write(dist.path().join("img/logo.svg"), "new").unwrap();
+        fs::create_dir(public.path().join("img")).unwrap();
+        fs::write(public.path().join("img/logo.svg"), "old").unwrap();
+        fs::write(public.path().join("img/bg.png"), "png").unwrap();
+        // A file in the first directory shadows a directory in the second
+        fs::write(dist.path().join("fonts"), "").unwrap();
+        fs::create_dir(public.path().join("fonts")).unwrap();
+        fs::write(public.path().join("fonts/a.woff2"), "font").unwrap();
+
+        let options = RouteOptions::default();
+        let dirs = [dist.path(), public.path()]
+            .map(|dir| (dir.to_path_buf(), HiddenPaths::new(dir, &options)))
+            .to_vec();
+        let body = download_zip(dirs)
+            .await
+            .into_body()
+            .collect()
+            .await
+            .unwrap()
+            .to_bytes();
+        let mut archive = zip::ZipArchive::new(Cursor::new(body)).unwrap();
+
+        let mut names: Vec<_> = archive.file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(names, ["fonts", "img/", "img/bg.png", "img/logo.svg"]);
+        let mut logo = String::new();
+        archive
+            .by_name("img/logo.svg")
+            .unwrap()
+            .read_to_string(&mut logo)
+            .unwrap();
+        assert_eq!(logo, "new");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinks_out_of_the_root_are_left_out() {
+        let tmp = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+        fs::write(tmp.path().join("a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink(tmp.path().join("a.txt"), tmp.path().join("b.txt")).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("secret.txt"), tmp.path().join("c.txt"))
+            .unwrap();
+        std::os::unix::fs::symlink(tmp.path(), tmp.path().join("loop")).unwrap();
+
+        let archive = archive_of(tmp.path(), &RouteOptions::default()).await;
+        let mut names: Vec<_> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(names, ["a.txt", "b.txt"]);
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
@@ -7,10 +8,17 @@ use axum::response::Response;
 use tokio::task;
 
 use super::breadcrumb::build_breadcrumb;
+use super::hidden::HiddenPaths;
 use super::path_utils::format_size;
+use super::readme::{Readme, find_readme};
+use super::search::render_search_form;
 use super::styles::{FILEBROWSER_CSS, FILEBROWSER_JS};
+use super::thumbnail::Preview;
 use crate::daemon::theme;
 
+/// Entries shown per page of a listing; the rest load on demand.
+const PAGE_SIZE: usize = 1000;
+
 pub(super) struct DirEntry {
     pub name: String,
     pub is_dir: bool,
@@ -18,14 +26,27 @@ pub(super) struct DirEntry {
     pub modified: u64,
 }
 
-/// Read a directory and collect entries with metadata.
-pub(super) fn read_directory(path: &Path) -> Option<Vec<DirEntry>> {
+/// Read a directory and collect entries with metadata, leaving out
+/// hidden ones. Symlinks the route follows are listed as what they
+/// point to.
+pub(super) fn read_directory(path: &Path, hidden: &HiddenPaths) -> Option<Vec<DirEntry>> {
     let read_dir = fs::read_dir(path).ok()?;
 
     let mut entries: Vec<DirEntry> = read_dir
         .filter_map(|entry| {
             let entry = entry.ok()?;
-            let metadata = entry.metadata().ok()?;
+            if hidden.hides(&entry.path()) {
+                return None;
+            }
+            let metadata = if entry.file_type().ok()?.is_symlink() {
+                let target = entry.path().canonicalize().ok()?;
+                if !hidden.follows(&target) || hidden.hides(&target) {
+                    return None;
+                }
+                fs::metadata(&target).ok()?
+            } else {
+                entry.metadata().ok()?
+            };
             let name = entry.file_name().to_string_lossy().to_string();
             let is_dir = metadata.is_dir();
             let size = if is_dir { 0 } else { metadata.len() };
@@ -45,27 +66,67 @@ pub(super) fn read_directory(path: &Path) -> Option<Vec<DirEntry>> {
         })
         .collect();
 
-    // Default: directories first, then case-insensitive alphabetical
+    sort_entries(&mut entries);
+    Some(entries)
+}
+
+/// Default: directories first, then case-insensitive alphabetical
+fn sort_entries(entries: &mut [DirEntry]) {
     entries.sort_by(|a, b| {
         b.is_dir
             .cmp(&a.is_dir)
             .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
     });
+}
 
-    Some(entries)
+/// Read merged directories; when names clash, the earlier directory's
+/// entry wins. `None` if none of them could be read.
+fn read_directories(dirs: &[(PathBuf, HiddenPaths)]) -> Option<(Vec<DirEntry>, Option<Readme>)> {
+    let mut merged: Option<Vec<DirEntry>> = None;
+    let mut readme = None;
+    let mut seen = HashSet::new();
+
+    for (dir, hidden) in dirs {
+        let Some(entries) = read_directory(dir, hidden) else {
+            continue;
+        };
+        if readme.is_none() {
+            readme = find_readme(dir, &entries);
+        }
+        let merged = merged.get_or_insert_default();
+        merged.extend(
+            entries
+                .into_iter()
+                .filter(|entry| seen.insert(entry.name.clone())),
+        );
+    }
+
+    let mut entries = merged?;
+    sort_entries(&mut entries);
+    Some((entries, readme))
 }
 
-/// Try to render a directory listing for `resolved` and show it as `display_path`.
+/// Try to render a page (from 1) of the listing of `dirs`, merged, and
+/// show it as `display_path`.
 pub(super) async fn try_directory_listing(
     route_prefix: &str,
     display_path: &str,
-    resolved: PathBuf,
+    dirs: Vec<(PathBuf, HiddenPaths)>,
+    uploads: bool,
+    page: usize,
 ) -> Option<Response> {
-    let entries = task::spawn_blocking(move || read_directory(&resolved))
+    let (entries, readme) = task::spawn_blocking(move || read_directories(&dirs))
         .await
         .ok()??;
 
-    let html = render_directory_listing(route_prefix, display_path, &entries);
+    let html = render_directory_listing(
+        route_prefix,
+        display_path,
+        &entries,
+        readme.as_ref(),
+        uploads,
+        page,
+    );
 
     Response::builder()
         .status(StatusCode::OK)
@@ -77,8 +138,17 @@ pub(super) async fn try_directory_listing(
 /// Render the full themed HTML page for a directory listing.
 ///
 /// The `route_prefix` is used to determine the mount root and keep navigation
-/// within the mounted route.
-fn render_directory_listing(route_prefix: &str, uri_path: &str, entries: &[DirEntry]) -> String {
+/// within the mounted route. A README is previewed below the files, and
+/// routes taking uploads get a form for them. Large directories are
+/// shown `PAGE_SIZE` entries at a time, starting with `page`.
+fn render_directory_listing(
+    route_prefix: &str,
+    uri_path: &str,
+    entries: &[DirEntry],
+    readme: Option<&Readme>,
+    uploads: bool,
+    page: usize,
+) -> String {
     let display_path = theme::html_escape(uri_path);
     let breadcrumb = build_breadcrumb(route_prefix, uri_path);
 
@@ -87,13 +157,27 @@ fn render_directory_listing(route_prefix: &str, uri_path: &str, entries: &[DirEn
     // Page heading
     body.push_str("<h1 class=\"page-title\">Index of <code>");
     body.push_str(&display_path);
-    body.push_str("</code></h1>\n");
+    body.push_str("</code><a class=\"download\" href=\"?download=zip\">Download .zip</a></h1>\n");
+
+    let mount = route_prefix.trim_end_matches('/');
+    let scope = uri_path.strip_prefix(mount).unwrap_or(uri_path);
+    body.push_str(&render_search_form(route_prefix, scope, ""));
 
     // Breadcrumb navigation
     body.push_str("<nav class=\"breadcrumb\">");
     body.push_str(&breadcrumb);
     body.push_str("</nav>\n");
 
+    if uploads {
+        body.push_str(
+            "<form class=\"upload\" method=\"post\" enctype=\"multipart/form-data\">\
+             <input type=\"file\" name=\"file\" multiple required>\
+             <button type=\"submit\">Upload</button>\
+             <span class=\"upload-hint\">or drop files anywhere on the page</span>\
+             </form>\n",
+        );
+    }
+
     // File listing card
     body.push_str("<div class=\"file-card\">\n");
     body.push_str("<table id=\"listing\">\n<thead><tr>");
@@ -111,11 +195,28 @@ fn render_directory_listing(route_prefix: &str, uri_path: &str, entries: &[DirEn
     );
     body.push_str("</tr></thead>\n<tbody>\n");
 
+    let pages = entries.len().div_ceil(PAGE_SIZE).max(1);
+    let page = page.clamp(1, pages);
+    let start = (page - 1) * PAGE_SIZE;
+    let shown = &entries[start..entries.len().min(start + PAGE_SIZE)];
+
     render_parent_link(route_prefix, uri_path, &mut body);
-    render_entries(uri_path, entries, &mut body);
+    render_entries(uri_path, shown, &mut body);
+    if pages > 1 {
+        render_page_links(page, pages, start, shown.len(), entries.len(), &mut body);
+    }
 
     body.push_str("</tbody>\n</table>\n</div>");
 
+    if let Some(readme) = readme.filter(|_| page == 1) {
+        body.push_str("\n<div class=\"readme-card\">\n<div class=\"readme-title\">");
+        body.push_str(theme::FILE_ICON);
+        body.push_str(&theme::html_escape(&readme.name));
+        body.push_str("</div>\n<div class=\"readme-body\">");
+        body.push_str(&readme.html);
+        body.push_str("</div>\n</div>");
+    }
+
     theme::render_page(
         &format!("Index of {uri_path}"),
         &body,
@@ -166,6 +267,34 @@ fn render_parent_link(route_prefix: &str, uri_path: &str, body: &mut String) {
     }
 }
 
+/// Where this page sits among the rest. Scripts turn "Load more" into
+/// rows appended in place.
+fn render_page_links(
+    page: usize,
+    pages: usize,
+    start: usize,
+    shown: usize,
+    total: usize,
+    body: &mut String,
+) {
+    body.push_str("<tr class=\"more-row\"><td colspan=\"3\">");
+    body.push_str(&format!(
+        "Showing {}\u{2013}{} of {total}",
+        start + 1,
+        start + shown
+    ));
+    if page > 1 {
+        body.push_str(&format!(" <a href=\"?page={}\">Previous</a>", page - 1));
+    }
+    if page < pages {
+        body.push_str(&format!(
+            " <a class=\"load-more\" href=\"?page={}\">Load more</a>",
+            page + 1
+        ));
+    }
+    body.push_str("</td></tr>\n");
+}
+
 fn render_entries(uri_path: &str, entries: &[DirEntry], body: &mut String) {
     let normalized = if uri_path.ends_with('/') {
         uri_path.to_string()
@@ -193,6 +322,12 @@ fn render_entries(uri_path: &str, entries: &[DirEntry], body: &mut String) {
         } else {
             format_size(entry.size)
         };
+        let href = format!(
+            "{}{}",
+            theme::html_escape(&normalized),
+            theme::encode_path_segment(&entry.name)
+        );
+        let preview = Preview::of(&entry.name).filter(|_| !entry.is_dir);
 
         body.push_str("<tr data-name=\"");
         body.push_str(&name);
@@ -203,15 +338,31 @@ fn render_entries(uri_path: &str, entries: &[DirEntry], body: &mut String) {
         body.push_str("\" data-ts=\"");
         body.push_str(&entry.modified.to_string());
         body.push_str("\"><td>");
-        body.push_str(icon);
+        render_preview(preview, &href, entry.modified, icon, body);
         body.push_str("<a href=\"");
-        body.push_str(&theme::html_escape(&normalized));
-        body.push_str(&theme::encode_path_segment(&entry.name));
+        body.push_str(&href);
         body.push_str(trailing);
         body.push_str("\">");
         body.push_str(&name);
         body.push_str(trailing);
-        body.push_str("</a></td><td class=\"size\">");
+        body.push_str("</a>");
+        match preview {
+            // Scripts put the duration in front once it's known
+            Some(Preview::Video) => {
+                body.push_str("<span class=\"badge\">");
+                body.push_str(&size_str);
+                body.push_str("</span>");
+            }
+            Some(Preview::Audio) => {
+                body.push_str("<span class=\"badge\" data-media=\"");
+                body.push_str(&href);
+                body.push_str("\">");
+                body.push_str(&size_str);
+                body.push_str("</span>");
+            }
+            _ => {}
+        }
+        body.push_str("</td><td class=\"size\">");
         body.push_str(&size_str);
         body.push_str("</td><td class=\"modified\" data-ts=\"");
         body.push_str(&entry.modified.to_string());
@@ -219,10 +370,55 @@ fn render_entries(uri_path: &str, entries: &[DirEntry], body: &mut String) {
     }
 }
 
+/// The thumbnail in front of a file's name, falling back to `icon` if
+/// it can't be shown.
+fn render_preview(
+    preview: Option<Preview>,
+    href: &str,
+    modified: u64,
+    icon: &str,
+    body: &mut String,
+) {
+    const FALLBACK: &str = " onerror=\"this.classList.add('broken')\"";
+    match preview {
+        Some(Preview::Thumbnail | Preview::Vector) => {
+            body.push_str("<img class=\"thumb\" alt=\"\" loading=\"lazy\" src=\"");
+            body.push_str(href);
+            if preview == Some(Preview::Thumbnail) {
+                body.push_str("?thumb=");
+                body.push_str(&modified.to_string());
+            }
+            body.push('"');
+            body.push_str(FALLBACK);
+            body.push('>');
+        }
+        Some(Preview::Video) => {
+            body.push_str("<video class=\"thumb\" muted preload=\"none\" data-media=\"");
+            body.push_str(href);
+            // Shows the first frame instead of black
+            body.push_str("#t=0.1\"");
+            body.push_str(FALLBACK);
+            body.push_str("></video>");
+        }
+        Some(Preview::Audio) | None => {
+            body.push_str(icon);
+            return;
+        }
+    }
+    body.push_str("<span class=\"thumb-icon\">");
+    body.push_str(icon);
+    body.push_str("</span>");
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::path_utils::resolve_path;
     use super::*;
+    use crate::domain::{RouteOptions, SymlinkPolicy};
+
+    fn hidden(root: &Path) -> HiddenPaths {
+        HiddenPaths::new(root, &RouteOptions::default())
+    }
 
     #[test]
     fn test_read_directory_sorts_dirs_first() {
@@ -231,7 +427,7 @@ mod tests {
         fs::create_dir(tmp.path().join("a_dir")).unwrap();
         fs::write(tmp.path().join("a_file.txt"), "world").unwrap();
 
-        let entries = read_directory(tmp.path()).unwrap();
+        let entries = read_directory(tmp.path(), &hidden(tmp.path())).unwrap();
         assert_eq!(entries.len(), 3);
         assert!(entries[0].is_dir);
         assert_eq!(entries[0].name, "a_dir");
@@ -239,13 +435,80 @@ mod tests {
         assert_eq!(entries[2].name, "b_file.txt");
     }
 
+    #[test]
+    fn test_read_directory_leaves_out_hidden_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join(".env"), "SECRET=1").unwrap();
+        fs::create_dir(tmp.path().join(".git")).unwrap();
+        fs::write(tmp.path().join("server.key"), "key").unwrap();
+        fs::write(tmp.path().join("app.js"), "").unwrap();
+
+        let entries = read_directory(tmp.path(), &hidden(tmp.path())).unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["app.js", "server.key"]);
+
+        let options = RouteOptions {
+            dotfiles: true,
+            deny: vec![crate::domain::PathGlob::parse("*.key").unwrap()],
+            ..RouteOptions::default()
+        };
+        let entries = read_directory(tmp.path(), &HiddenPaths::new(tmp.path(), &options)).unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, [".git", ".env", "app.js"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_directory_lists_followed_symlinks_as_their_targets() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = tempfile::tempdir().unwrap();
+        fs::create_dir(store.path().join("react")).unwrap();
+        fs::create_dir(tmp.path().join("dist")).unwrap();
+        std::os::unix::fs::symlink(store.path().join("react"), tmp.path().join("react")).unwrap();
+        std::os::unix::fs::symlink(tmp.path().join("dist"), tmp.path().join("out")).unwrap();
+
+        let names = |symlinks| {
+            let options = RouteOptions {
+                follow_symlinks: symlinks,
+                ..RouteOptions::default()
+            };
+            let hidden = HiddenPaths::new(tmp.path(), &options);
+            let entries = read_directory(tmp.path(), &hidden).unwrap();
+            assert!(entries.iter().all(|e| e.is_dir));
+            entries.into_iter().map(|e| e.name).collect::<Vec<_>>()
+        };
+        assert_eq!(names(SymlinkPolicy::Never), ["dist"]);
+        assert_eq!(names(SymlinkPolicy::WithinRoot), ["dist", "out"]);
+        assert_eq!(names(SymlinkPolicy::Always), ["dist", "out", "react"]);
+    }
+
+    #[test]
+    fn test_read_directories_merges_with_earlier_first() {
+        let dist = tempfile::tempdir().unwrap();
+        let public = tempfile::tempdir().unwrap();
+        fs::write(dist.path().join("app.js"), "built").unwrap();
+        fs::write(dist.path().join("favicon.ico"), "new").unwrap();
+        fs::write(public.path().join("favicon.ico"), "older").unwrap();
+        fs::create_dir(public.path().join("images")).unwrap();
+
+        let dirs = [
+            (dist.path().to_path_buf(), hidden(dist.path())),
+            (public.path().to_path_buf(), hidden(public.path())),
+        ];
+        let (entries, _) = read_directories(&dirs).unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["images", "app.js", "favicon.ico"]);
+        let favicon = entries.iter().find(|e| e.name == "favicon.ico").unwrap();
+        assert_eq!(favicon.size, 3);
+    }
+
     #[test]
     fn test_read_directory_file_sizes() {
         let tmp = tempfile::tempdir().unwrap();
         fs::write(tmp.path().join("small.txt"), "hi").unwrap();
         fs::create_dir(tmp.path().join("dir")).unwrap();
 
-        let entries = read_directory(tmp.path()).unwrap();
+        let entries = read_directory(tmp.path(), &hidden(tmp.path())).unwrap();
         let dir = entries.iter().find(|e| e.name == "dir").unwrap();
         let file = entries.iter().find(|e| e.name == "small.txt").unwrap();
         assert_eq!(dir.size, 0);
@@ -269,7 +532,7 @@ mod tests {
             },
         ];
 
-        let html = render_directory_listing("/", "/project/", &entries);
+        let html = render_directory_listing("/", "/project/", &entries, None, false, 1);
 
         // Themed page structure
         assert!(html.contains("roxy-header"));
@@ -280,12 +543,102 @@ mod tests {
         assert!(html.contains("docs/</a>"));
         assert!(html.contains("readme.md</a>"));
         assert!(html.contains("4.0 KB"));
+        assert!(html.contains("href=\"?download=zip\""));
+    }
+
+    #[test]
+    fn test_render_directory_listing_previews_media() {
+        let file = |name: &str| DirEntry {
+            name: name.to_string(),
+            is_dir: false,
+            size: 2048,
+            modified: 1700000000,
+        };
+        let entries = [
+            file("shot 1.png"),
+            file("logo.svg"),
+            file("demo.mp4"),
+            file("voice.mp3"),
+        ];
+
+        let html = render_directory_listing("/", "/media/", &entries, None, false, 1);
+        assert!(html.contains("src=\"/media/shot%201.png?thumb=1700000000\""));
+        assert!(html.contains("src=\"/media/logo.svg\""));
+        assert!(html.contains("data-media=\"/media/demo.mp4#t=0.1\""));
+        assert!(
+            html.contains("<span class=\"badge\" data-media=\"/media/voice.mp3\">2.0 KB</span>")
+        );
+    }
+
+    #[test]
+    fn test_render_directory_listing_previews_readme() {
+        let readme = Readme {
+            name: "README.md".to_string(),
+            html: "<h1>Project</h1>".to_string(),
+        };
+        let html = render_directory_listing("/", "/", &[], Some(&readme), false, 1);
+        let card = html.find("class=\"readme-card\"").unwrap();
+        assert!(card > html.find("</table>").unwrap());
+        assert!(html[card..].contains("README.md"));
+        assert!(html[card..].contains("<h1>Project</h1>"));
+
+        let html = render_directory_listing("/", "/", &[], None, false, 1);
+        assert!(!html.contains("class=\"readme-card\""));
+    }
+
+    #[test]
+    fn test_render_directory_listing_search_form() {
+        let html = render_directory_listing("/static", "/static/docs/", &[], None, false, 1);
+        assert!(html.contains("action=\"/static/_search\""));
+        assert!(html.contains("name=\"path\" value=\"/docs/\""));
+    }
+
+    #[test]
+    fn test_render_directory_listing_pages_large_directories() {
+        let entries: Vec<_> = (0..2500)
+            .map(|i| DirEntry {
+                name: format!("file{i:04}.txt"),
+                is_dir: false,
+                size: 1,
+                modified: 0,
+            })
+            .collect();
+        let readme = Readme {
+            name: "README.md".to_string(),
+            html: "<p>Hello</p>".to_string(),
+        };
+        let rows = |html: &str| html.matches("<tr data-name=").count();
+
+        let html = render_directory_listing("/", "/data/", &entries, Some(&readme), false, 1);
+        assert_eq!(rows(&html), 1000);
+        assert!(html.contains("Showing 1\u{2013}1000 of 2500"));
+        assert!(html.contains("class=\"load-more\" href=\"?page=2\""));
+        assert!(html.contains("class=\"readme-card\""));
+
+        let html = render_directory_listing("/", "/data/", &entries, Some(&readme), false, 3);
+        assert_eq!(rows(&html), 500);
+        assert!(html.contains("data-name=\"file2000.txt\""));
+        assert!(html.contains("href=\"?page=2\">Previous"));
+        assert!(!html.contains("load-more\""));
+        assert!(!html.contains("class=\"readme-card\""));
+
+        // Past the end shows the last page
+        let html = render_directory_listing("/", "/data/", &entries, None, false, 9);
+        assert!(html.contains("Showing 2001\u{2013}2500 of 2500"));
+    }
+
+    #[test]
+    fn test_render_directory_listing_upload_form() {
+        let html = render_directory_listing("/", "/", &[], None, true, 1);
+        assert!(html.contains("<form class=\"upload\" method=\"post\""));
+        let html = render_directory_listing("/", "/", &[], None, false, 1);
+        assert!(!html.contains("<form class=\"upload\""));
     }
 
     #[test]
     fn test_render_directory_listing_parent_link() {
         let entries = vec![];
-        let html = render_directory_listing("/", "/images/photos/", &entries);
+        let html = render_directory_listing("/", "/images/photos/", &entries, None, false, 1);
         assert!(html.contains(">..</a>"));
         assert!(html.contains("/images/\""));
     }
@@ -293,21 +646,21 @@ mod tests {
     #[test]
     fn test_render_directory_listing_no_parent_at_root() {
         let entries = vec![];
-        let html = render_directory_listing("/", "/", &entries);
+        let html = render_directory_listing("/", "/", &entries, None, false, 1);
         assert!(!html.contains(".."));
     }
 
     #[test]
     fn test_render_directory_listing_no_parent_at_mount_root() {
         let entries = vec![];
-        let html = render_directory_listing("/static", "/static/", &entries);
+        let html = render_directory_listing("/static", "/static/", &entries, None, false, 1);
         assert!(!html.contains(".."));
     }
 
     #[test]
     fn test_render_directory_listing_empty_state() {
         let entries = vec![];
-        let html = render_directory_listing("/", "/", &entries);
+        let html = render_directory_listing("/", "/", &entries, None, false, 1);
         assert!(html.contains("empty"));
     }
 
@@ -328,7 +681,7 @@ mod tests {
             },
         ];
 
-        let html = render_directory_listing("/", "/", &entries);
+        let html = render_directory_listing("/", "/", &entries, None, false, 1);
         // Should use SVG icons, not emoji
         assert!(html.contains(r##"fill="#E8853A""##)); // folder orange
         assert!(html.contains(r##"fill="#3BB8A2""##)); // file teal
@@ -340,8 +693,9 @@ mod tests {
         fs::write(tmp.path().join("file.txt"), "content").unwrap();
         fs::create_dir(tmp.path().join("sub")).unwrap();
 
-        let resolved = resolve_path(tmp.path(), "/").unwrap();
-        let response = try_directory_listing("/", "/", resolved).await;
+        let hidden = hidden(tmp.path());
+        let resolved = resolve_path(tmp.path(), "/", &hidden).unwrap();
+        let response = try_directory_listing("/", "/", vec![(resolved, hidden)], false, 1).await;
         assert!(response.is_some());
         assert_eq!(response.unwrap().status(), StatusCode::OK);
     }
@@ -351,8 +705,10 @@ mod tests {
         let tmp = tempfile::tempdir().unwrap();
         fs::write(tmp.path().join("file.txt"), "content").unwrap();
 
-        let resolved = resolve_path(tmp.path(), "/file.txt").unwrap();
-        let response = try_directory_listing("/", "/file.txt", resolved).await;
+        let hidden = hidden(tmp.path());
+        let resolved = resolve_path(tmp.path(), "/file.txt", &hidden).unwrap();
+        let response =
+            try_directory_listing("/", "/file.txt", vec![(resolved, hidden)], false, 1).await;
         assert!(response.is_none());
     }
 }
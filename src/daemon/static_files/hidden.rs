@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+
+use crate::domain::{PathGlob, RouteOptions, SymlinkPolicy};
+
+/// Paths under a static root that are never served or listed: names
+/// starting with a dot unless the route allows them, whatever the
+/// route's `deny` globs match, and symlinks it doesn't follow.
+#[derive(Debug, Clone)]
+pub(super) struct HiddenPaths {
+    root: PathBuf,
+    dotfiles: bool,
+    deny: Vec<PathGlob>,
+    symlinks: SymlinkPolicy,
+}
+
+impl HiddenPaths {
+    /// Touches the filesystem to resolve `root`.
+    pub(super) fn new(root: &Path, options: &RouteOptions) -> Self {
+        Self {
+            root: root.canonicalize().unwrap_or_else(|_| root.to_path_buf()),
+            dotfiles: options.dotfiles,
+            deny: options.deny.clone(),
+            symlinks: options.follow_symlinks,
+        }
+    }
+
+    /// Whether a symlink under the root may be followed to `target`, the
+    /// canonical path it resolves to.
+    pub(super) fn follows(&self, target: &Path) -> bool {
+        match self.symlinks {
+            SymlinkPolicy::Never => false,
+            SymlinkPolicy::WithinRoot => target.starts_with(&self.root),
+            SymlinkPolicy::Always => true,
+        }
+    }
+
+    /// Whether a path on disk is hidden. Paths outside the root aren't
+    /// this policy's concern.
+    pub(super) fn hides(&self, path: &Path) -> bool {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) => self.hides_relative(&relative.to_string_lossy().replace('\\', "/")),
+            Err(_) => false,
+        }
+    }
+
+    /// Whether `relative`, a `/`-separated path below the root, is hidden.
+    pub(super) fn hides_relative(&self, relative: &str) -> bool {
+        let relative = relative.trim_matches('/');
+        if relative.is_empty() {
+            return false;
+        }
+        let dotted = relative
+            .split('/')
+            .any(|name| name.starts_with('.') && name != "." && name != "..");
+        (dotted && !self.dotfiles) || self.deny.iter().any(|glob| glob.matches(relative))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hidden(dotfiles: bool, deny: &[&str]) -> HiddenPaths {
+        let options = RouteOptions {
+            dotfiles,
+            deny: deny.iter().map(|g| PathGlob::parse(g).unwrap()).collect(),
+            ..RouteOptions::default()
+        };
+        HiddenPaths::new(Path::new("/srv/site"), &options)
+    }
+
+    #[test]
+    fn hides_dotfiles_unless_allowed() {
+        let policy = hidden(false, &[]);
+        assert!(policy.hides_relative(".env"));
+        assert!(policy.hides_relative("/.git/config"));
+        assert!(policy.hides_relative("app/.cache/x"));
+        assert!(!policy.hides_relative("app/index.html"));
+        assert!(!policy.hides_relative("/"));
+
+        let policy = hidden(true, &[]);
+        assert!(!policy.hides_relative(".well-known/security.txt"));
+    }
+
+    #[test]
+    fn hides_denied_paths() {
+        let policy = hidden(true, &["*.key", "private/**"]);
+        assert!(policy.hides(Path::new("/srv/site/certs/dev.key")));
+        assert!(policy.hides(Path::new("/srv/site/private")));
+        assert!(!policy.hides(Path::new("/srv/site/public/app.js")));
+        assert!(!policy.hides(Path::new("/elsewhere/dev.key")));
+    }
+
+    #[test]
+    fn follows_symlinks_by_policy() {
+        let follows = |symlinks, target: &str| {
+            let options = RouteOptions {
+                follow_symlinks: symlinks,
+                ..RouteOptions::default()
+            };
+            HiddenPaths::new(Path::new("/srv/site"), &options).follows(Path::new(target))
+        };
+        assert!(!follows(SymlinkPolicy::Never, "/srv/site/app.js"));
+        assert!(follows(SymlinkPolicy::WithinRoot, "/srv/site/app.js"));
+        assert!(!follows(SymlinkPolicy::WithinRoot, "/srv/site-old/app.js"));
+        assert!(follows(SymlinkPolicy::Always, "/etc/hosts"));
+    }
+}
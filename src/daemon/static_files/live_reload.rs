@@ -0,0 +1,350 @@
+//! Reloads browser tabs showing a static route when its files change.
+//!
+//! HTML pages get a small script that opens a WebSocket to
+//! `<route>/__roxy/live-reload`. Each route directory with an open page
+//! is watched, and every change that isn't to a hidden file is pushed
+//! to the pages as a `reload` message.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::{
+        FromRequestParts, Request,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use http_body_util::BodyExt;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::broadcast::{self, Receiver, error::RecvError};
+use tokio::sync::mpsc;
+use tokio::task;
+use tracing::{debug, warn};
+
+use super::hidden::HiddenPaths;
+use crate::daemon::theme;
+use crate::domain::RouteOptions;
+
+/// Path of the WebSocket endpoint, below the route's prefix.
+const ENDPOINT: &str = "/__roxy/live-reload";
+
+/// Editors save in several steps; changes this close together cause
+/// one reload.
+const SETTLE: Duration = Duration::from_millis(100);
+
+/// Pages larger than this are served without the script.
+const MAX_INJECT_BODY: u64 = 5 * 1024 * 1024;
+
+/// Reconnects after the daemon restarts and reloads on any message.
+const SCRIPT: &str = "(function(){var s=document.currentScript,u=(location.protocol==='https:'?'wss://':'ws://')+location.host+s.dataset.roxyLiveReload;function c(){var w=new WebSocket(u);w.onmessage=function(){location.reload()};w.onclose=function(){setTimeout(c,1000)}}c()})();";
+
+type Changes = Arc<[PathBuf]>;
+
+/// Watches the directories of static routes that have pages open.
+#[derive(Default)]
+pub struct LiveReload {
+    watches: Mutex<HashMap<PathBuf, Watch>>,
+}
+
+struct Watch {
+    changes: broadcast::Sender<Changes>,
+    _watcher: RecommendedWatcher,
+}
+
+impl LiveReload {
+    /// Answer a page's WebSocket request for the route serving `roots`.
+    pub async fn connect(
+        self: &Arc<Self>,
+        roots: &[PathBuf],
+        options: &RouteOptions,
+        request: Request,
+    ) -> Response {
+        let (mut parts, _) = request.into_parts();
+        let upgrade = match WebSocketUpgrade::from_request_parts(&mut parts, &()).await {
+            Ok(upgrade) => upgrade,
+            Err(rejection) => return rejection.into_response(),
+        };
+
+        let hub = self.clone();
+        let roots = roots.to_vec();
+        let options = options.clone();
+        let subscribed = task::spawn_blocking(move || {
+            roots
+                .iter()
+                .map(|root| {
+                    let hidden = HiddenPaths::new(root, &options);
+                    hub.subscribe(root).map(|changes| (changes, hidden))
+                })
+                .collect::<notify::Result<Vec<_>>>()
+        })
+        .await;
+        let subscriptions = match subscribed {
+            Ok(Ok(subscribed)) => subscribed,
+            Ok(Err(e)) => {
+                warn!(error = %e, "Failed to watch static files for live reload");
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to watch files")
+                    .into_response();
+            }
+            Err(_) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+                    .into_response();
+            }
+        };
+
+        // Holds one reload at most; a pending one covers later changes
+        let (reload, reloads) = mpsc::channel(1);
+        for (changes, hidden) in subscriptions {
+            tokio::spawn(forward_visible(changes, hidden, reload.clone()));
+        }
+        upgrade.on_upgrade(move |socket| push_reloads(socket, reloads))
+    }
+
+    /// Changes under `root`, watching it if no page is open for it yet.
+    /// Touches the filesystem.
+    fn subscribe(&self, root: &Path) -> notify::Result<Receiver<Changes>> {
+        let root = root.canonicalize().map_err(notify::Error::io)?;
+        let mut watches = self.watches.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Stop watching directories whose pages have all been closed
+        watches.retain(|_, watch| watch.changes.receiver_count() > 0);
+        if let Some(watch) = watches.get(&root) {
+            return Ok(watch.changes.subscribe());
+        }
+
+        let (changes, receiver) = broadcast::channel(64);
+        let sender = changes.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if let Ok(event) = event
+                && !event.kind.is_access()
+            {
+                let _ = sender.send(event.paths.into());
+            }
+        })?;
+        watcher.watch(&root, RecursiveMode::Recursive)?;
+        debug!(root = %root.display(), "Watching static files for live reload");
+
+        watches.insert(
+            root,
+            Watch {
+                changes,
+                _watcher: watcher,
+            },
+        );
+        Ok(receiver)
+    }
+}
+
+/// Whether `path` is the live-reload endpoint of the route at `route_prefix`.
+pub fn is_live_reload_request(route_prefix: &str, path: &str) -> bool {
+    path == endpoint(route_prefix)
+}
+
+fn endpoint(route_prefix: &str) -> String {
+    format!("{}{ENDPOINT}", route_prefix.trim_end_matches('/'))
+}
+
+/// Pass on changes under one root that touch a visible file, until
+/// the page goes away.
+async fn forward_visible(
+    mut changes: Receiver<Changes>,
+    hidden: HiddenPaths,
+    reload: mpsc::Sender<()>,
+) {
+    loop {
+        tokio::select! {
+            change = changes.recv() => match change {
+                Ok(paths) if !is_visible_change(&paths, &hidden) => {}
+                Ok(_) | Err(RecvError::Lagged(_)) => {
+                    let _ = reload.try_send(());
+                }
+                Err(RecvError::Closed) => return,
+            },
+            _ = reload.closed() => return,
+        }
+    }
+}
+
+/// Tell the page to reload whenever a visible file changes, until
+/// either side goes away.
+async fn push_reloads(mut socket: WebSocket, mut reloads: mpsc::Receiver<()>) {
+    loop {
+        tokio::select! {
+            reload = reloads.recv() => {
+                if reload.is_none() {
+                    return;
+                }
+                tokio::time::sleep(SETTLE).await;
+                while reloads.try_recv().is_ok() {}
+                if socket.send(Message::text("reload")).await.is_err() {
+                    return;
+                }
+            }
+            message = socket.recv() => {
+                // Pages never send anything but a close
+                if !matches!(message, Some(Ok(_))) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Whether a change touched anything the route would serve; saving
+/// swap files or committing to `.git/` shouldn't reload the page.
+fn is_visible_change(paths: &[PathBuf], hidden: &HiddenPaths) -> bool {
+    paths.iter().any(|path| !hidden.hides(path))
+}
+
+/// Add the live-reload script to an HTML page served by the route at
+/// `route_prefix`. Anything else passes through untouched.
+pub(super) async fn inject(route_prefix: &str, response: Response) -> Response {
+    let headers = response.headers();
+    let is_html = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("text/html"));
+    let too_large = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<u64>().ok())
+        .is_some_and(|len| len > MAX_INJECT_BODY);
+    if response.status() != StatusCode::OK || !is_html || too_large {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(collected) = body.collect().await else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response();
+    };
+    let page = with_script(&collected.to_bytes(), route_prefix);
+
+    // The page now differs from the file on disk, so validators for the
+    // file would let the browser keep a copy without the script
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::ETAG);
+    parts.headers.remove(header::LAST_MODIFIED);
+    parts
+        .headers
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    Response::from_parts(parts, Body::from(page))
+}
+
+/// `page` with the script before its last `</body>`, or at the end.
+fn with_script(page: &[u8], route_prefix: &str) -> Vec<u8> {
+    let tag = format!(
+        "<script data-roxy-live-reload=\"{}\">{SCRIPT}</script>",
+        theme::html_escape(&endpoint(route_prefix))
+    );
+    let at = page
+        .to_ascii_lowercase()
+        .windows(b"</body>".len())
+        .rposition(|w| w == b"</body>")
+        .unwrap_or(page.len());
+
+    let mut out = Vec::with_capacity(page.len() + tag.len());
+    out.extend_from_slice(&page[..at]);
+    out.extend_from_slice(tag.as_bytes());
+    out.extend_from_slice(&page[at..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn html(body: &'static str) -> Response {
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .header(header::CONTENT_LENGTH, body.len())
+            .header(header::ETAG, "\"abc\"")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn text_of(response: Response) -> String {
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8_lossy(&body).into_owned()
+    }
+
+    #[test]
+    fn endpoint_is_below_the_route() {
+        assert!(is_live_reload_request("/", "/__roxy/live-reload"));
+        assert!(is_live_reload_request("/docs", "/docs/__roxy/live-reload"));
+        assert!(!is_live_reload_request("/docs", "/__roxy/live-reload"));
+    }
+
+    #[tokio::test]
+    async fn script_goes_before_the_closing_body_tag() {
+        let response = inject("/docs", html("<html><BODY><p>hi</p></BODY></html>")).await;
+        assert!(response.headers().get(header::ETAG).is_none());
+        assert!(response.headers().get(header::CONTENT_LENGTH).is_none());
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+
+        let page = text_of(response).await;
+        assert!(page.starts_with(
+            "<html><BODY><p>hi</p><script data-roxy-live-reload=\"/docs/__roxy/live-reload\">"
+        ));
+        assert!(page.ends_with("</script></BODY></html>"));
+    }
+
+    #[tokio::test]
+    async fn script_is_appended_to_fragments() {
+        let page = text_of(inject("/", html("<p>hi</p>")).await).await;
+        assert!(page.starts_with("<p>hi</p><script"));
+        assert!(page.ends_with("</script>"));
+    }
+
+    #[tokio::test]
+    async fn other_responses_pass_through() {
+        let css = Response::builder()
+            .header(header::CONTENT_TYPE, "text/css")
+            .body(Body::from("p{}"))
+            .unwrap();
+        assert_eq!(text_of(inject("/", css).await).await, "p{}");
+
+        let mut missing = html("<p>gone</p>");
+        *missing.status_mut() = StatusCode::NOT_FOUND;
+        assert_eq!(text_of(inject("/", missing).await).await, "<p>gone</p>");
+    }
+
+    #[tokio::test]
+    async fn changes_under_the_root_are_reported() {
+        let tmp = tempfile::tempdir().unwrap();
+        let hub = LiveReload::default();
+        let mut changes = hub.subscribe(tmp.path()).unwrap();
+
+        fs::write(tmp.path().join("index.html"), "<p>hi</p>").unwrap();
+        let paths = tokio::time::timeout(Duration::from_secs(5), changes.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let hidden = HiddenPaths::new(tmp.path(), &RouteOptions::default());
+        assert!(is_visible_change(&paths, &hidden));
+
+        // A second page shares the watch
+        let _second = hub.subscribe(tmp.path()).unwrap();
+        assert_eq!(hub.watches.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn hidden_changes_are_ignored() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().canonicalize().unwrap();
+        let hidden = HiddenPaths::new(&root, &RouteOptions::default());
+
+        assert!(!is_visible_change(
+            &[root.join(".index.html.swp"), root.join(".git/index")],
+            &hidden
+        ));
+        assert!(is_visible_change(
+            &[root.join(".index.html.swp"), root.join("index.html")],
+            &hidden
+        ));
+    }
+}
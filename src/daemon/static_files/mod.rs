@@ -1,13 +1,21 @@
+mod archive;
 mod breadcrumb;
 mod directory;
+mod hidden;
+mod live_reload;
 mod path_utils;
+mod readme;
+mod search;
 mod styles;
+mod thumbnail;
+mod upload;
+mod webdav;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use axum::{
     extract::Request,
-    http::{StatusCode, Uri, header},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode, Uri, header},
     response::{IntoResponse, Response},
 };
 use tokio::task;
@@ -16,19 +24,88 @@ use tower_http::services::ServeDir;
 
 use super::embedded_assets;
 use super::theme;
+use crate::domain::{CachePolicy, CustomHeaders, RouteOptions};
 use directory::try_directory_listing;
-use path_utils::resolve_path;
+use hidden::HiddenPaths;
+use path_utils::{query_param, resolve_path};
 use styles::NOT_FOUND_CSS;
 
-/// Serve static files from a directory.
+pub use live_reload::{LiveReload, is_live_reload_request};
+
+/// Index file served for a directory when the route doesn't name any.
+const DEFAULT_INDEX: &str = "index.html";
+
+/// Page served for missing files when the route doesn't name one.
+const DEFAULT_NOT_FOUND: &str = "404.html";
+
+/// Serve static files from a directory, or from several merged into
+/// one: a file comes from the first root that has it, and directories
+/// list what all of them hold.
 ///
-/// If the request path maps to a directory without an `index.html`,
-/// renders an HTML directory listing with sortable columns.
-pub async fn serve_static(route_prefix: &str, root: PathBuf, request: Request) -> Response {
+/// If the request path maps to a directory without one of the route's
+/// index files (`index.html` by default), renders an HTML directory
+/// listing with sortable columns and previews of images and media;
+/// `?download=zip` fetches the directory as a zip archive, and
+/// `_search?q=` below the route finds files by name. Precompressed `.br`/`.gz` siblings are served to
+/// clients that accept them. Dotfiles and the route's `deny` globs are
+/// neither served nor listed. Routes with `uploads` on accept new files
+/// by `PUT` or multipart `POST`, and `webdav` ones can be mounted as a
+/// network drive. Missing files get the route's own 404
+/// page (`404.html` by default) when it has one.
+pub async fn serve_static(
+    route_prefix: &str,
+    roots: &[PathBuf],
+    options: &RouteOptions,
+    mut request: Request,
+) -> Response {
+    if options.cache == CachePolicy::Off {
+        strip_conditional_headers(request.headers_mut());
+    }
+
+    let method = request.method().clone();
+    let accept_encoding = request.headers().get(header::ACCEPT_ENCODING).cloned();
+    let mut response = serve(route_prefix, roots, options, request).await;
+    if response.status() == StatusCode::NOT_FOUND
+        && (method == axum::http::Method::GET || method == axum::http::Method::HEAD)
+        && let Some(page) = serve_not_found_page(roots, options, method, accept_encoding).await
+    {
+        response = page;
+    }
+    if response.headers().contains_key(header::CONTENT_ENCODING) {
+        response
+            .headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    }
+    apply_cache_policy(options.cache, &mut response);
+    apply_custom_headers(&options.headers, response.headers_mut());
+    if options.live_reload {
+        response = live_reload::inject(route_prefix, response).await;
+    }
+    response
+}
+
+/// A root in which the requested path exists.
+struct Found {
+    root: PathBuf,
+    resolved: PathBuf,
+    is_dir: bool,
+    hidden: HiddenPaths,
+}
+
+async fn serve(
+    route_prefix: &str,
+    roots: &[PathBuf],
+    options: &RouteOptions,
+    request: Request,
+) -> Response {
     let original_path = request.uri().path().to_string();
     let method = request.method().clone();
     let query = request.uri().query().map(|q| q.to_string());
 
+    let page = query_param(query.as_deref(), "page")
+        .and_then(|page| page.parse().ok())
+        .unwrap_or(1);
+
     let stripped_path = strip_route_prefix(&original_path, route_prefix);
 
     // Preserve the typical "directory path should end with '/'" behavior for mount roots.
@@ -47,11 +124,37 @@ pub async fn serve_static(route_prefix: &str, root: PathBuf, request: Request) -
     let mut request_for_service = request;
     rewrite_request_uri_path(&mut request_for_service, &stripped_path);
 
-    let service = ServeDir::new(&root).append_index_html_on_directories(true);
+    if options.webdav && webdav::handles(&request_for_service, options, &stripped_path) {
+        return webdav::handle(
+            roots,
+            options,
+            &stripped_path,
+            &original_path,
+            request_for_service,
+        )
+        .await;
+    }
+
+    if options.uploads && upload::is_upload(&method) {
+        return upload::receive(
+            roots,
+            options,
+            &stripped_path,
+            &original_path,
+            request_for_service,
+        )
+        .await;
+    }
 
     // Non-GET/HEAD methods should keep ServeDir's behavior (typically 405).
     if method != axum::http::Method::GET && method != axum::http::Method::HEAD {
-        return match service.oneshot(request_for_service).await {
+        let Some(root) = roots.first() else {
+            return build_not_found_response(&original_path);
+        };
+        return match file_service(root, options)
+            .oneshot(request_for_service)
+            .await
+        {
             Ok(mut response) => {
                 rewrite_redirect_location_to_include_mount_prefix(route_prefix, &mut response);
                 response.into_response()
@@ -60,23 +163,83 @@ pub async fn serve_static(route_prefix: &str, root: PathBuf, request: Request) -
         };
     }
 
-    // Security check: ensure the request resolves within the configured root.
-    let root_for_resolve = root.clone();
+    // Security check: ensure the request resolves within the configured roots.
+    let roots_for_resolve = roots.to_vec();
     let stripped_for_resolve = stripped_path.clone();
-    let resolved: Option<PathBuf> =
-        task::spawn_blocking(move || resolve_path(&root_for_resolve, &stripped_for_resolve))
-            .await
-            .unwrap_or_default();
-
-    let Some(resolved) = resolved else {
+    let candidates = index_candidates(&options.index);
+    let options_for_resolve = options.clone();
+    let resolved = task::spawn_blocking(move || {
+        let found = find_in_roots(
+            roots_for_resolve,
+            &stripped_for_resolve,
+            &options_for_resolve,
+        );
+        let index = found.iter().enumerate().find_map(|(layer, found)| {
+            find_index(&found.resolved, &candidates, &found.hidden).map(|name| (layer, name))
+        });
+        (found, index)
+    })
+    .await
+    .ok();
+
+    let Some((found, index_file)) = resolved.filter(|(found, _)| !found.is_empty()) else {
+        if search::is_search_request(&stripped_path) {
+            let headers = request_for_service.headers();
+            return search::search(route_prefix, roots, options, query.as_deref(), headers).await;
+        }
         return build_not_found_response(&original_path);
     };
 
-    match service.oneshot(request_for_service).await {
+    let mut served = &found[0];
+    if !served.is_dir && thumbnail::wants_thumbnail(query.as_deref()) {
+        return thumbnail::serve_thumbnail(served.resolved.clone()).await;
+    }
+    if served.is_dir {
+        if !stripped_path.ends_with('/') {
+            let location = match query {
+                Some(query) => format!("{original_path}/?{query}"),
+                None => format!("{original_path}/"),
+            };
+            return redirect_to(&location);
+        }
+        if archive::wants_zip(query.as_deref()) {
+            return archive::download_zip(listed_dirs(&found)).await;
+        }
+        let Some((layer, index_file)) = index_file else {
+            return match try_directory_listing(
+                route_prefix,
+                &original_path,
+                listed_dirs(&found),
+                options.uploads,
+                page,
+            )
+            .await
+            {
+                Some(listing) => listing,
+                None => build_not_found_response(&original_path),
+            };
+        };
+        served = &found[layer];
+        rewrite_request_uri_path(
+            &mut request_for_service,
+            &format!("{stripped_path}{index_file}"),
+        );
+    }
+
+    match file_service(&served.root, options)
+        .oneshot(request_for_service)
+        .await
+    {
         Ok(mut response) => {
             if response.status() == StatusCode::NOT_FOUND {
-                if let Some(listing) =
-                    try_directory_listing(route_prefix, &original_path, resolved.clone()).await
+                if let Some(listing) = try_directory_listing(
+                    route_prefix,
+                    &original_path,
+                    listed_dirs(&found),
+                    options.uploads,
+                    page,
+                )
+                .await
                 {
                     return listing;
                 }
@@ -90,6 +253,165 @@ pub async fn serve_static(route_prefix: &str, root: PathBuf, request: Request) -
     }
 }
 
+/// `ServeDir` for one root. Directories are handled by `serve`, with the
+/// route's own index files.
+fn file_service(root: &Path, options: &RouteOptions) -> ServeDir {
+    let service = ServeDir::new(root).append_index_html_on_directories(false);
+    // Built bundles often ship `app.js.br`/`app.js.gz` next to `app.js`.
+    // Live reload has to edit pages, so it gets the plain files.
+    if options.live_reload {
+        service
+    } else {
+        service.precompressed_br().precompressed_gzip()
+    }
+}
+
+/// The route's 404 page from the first root that has it, still with a
+/// 404 status, or `None` to fall back to the built-in page.
+async fn serve_not_found_page(
+    roots: &[PathBuf],
+    options: &RouteOptions,
+    method: axum::http::Method,
+    accept_encoding: Option<HeaderValue>,
+) -> Option<Response> {
+    let page = options.not_found.as_deref().unwrap_or(DEFAULT_NOT_FOUND);
+    let uri_path = page
+        .split('/')
+        .map(theme::encode_path_segment)
+        .fold(String::new(), |path, segment| path + "/" + &segment);
+
+    let roots = roots.to_vec();
+    let options_for_resolve = options.clone();
+    let path_for_resolve = uri_path.clone();
+    let root = task::spawn_blocking(move || {
+        roots.into_iter().find(|root| {
+            let hidden = HiddenPaths::new(root, &options_for_resolve);
+            resolve_path(root, &path_for_resolve, &hidden).is_some_and(|path| path.is_file())
+        })
+    })
+    .await
+    .ok()??;
+
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri_path)
+        .body(axum::body::Body::empty())
+        .ok()?;
+    if let Some(accept_encoding) = accept_encoding {
+        request
+            .headers_mut()
+            .insert(header::ACCEPT_ENCODING, accept_encoding);
+    }
+    let mut response = file_service(&root, options)
+        .oneshot(request)
+        .await
+        .ok()?
+        .into_response();
+    if response.status() != StatusCode::OK {
+        return None;
+    }
+    *response.status_mut() = StatusCode::NOT_FOUND;
+    // Validators would let a cache answer later misses with a 304.
+    response.headers_mut().remove(header::ETAG);
+    response.headers_mut().remove(header::LAST_MODIFIED);
+    Some(response)
+}
+
+/// Where `uri_path` exists among `roots`: the first file, or every
+/// directory if the first match is one. Touches the filesystem.
+fn find_in_roots(roots: Vec<PathBuf>, uri_path: &str, options: &RouteOptions) -> Vec<Found> {
+    let mut found: Vec<Found> = Vec::new();
+    for root in roots {
+        let hidden = HiddenPaths::new(&root, options);
+        let Some(resolved) = resolve_path(&root, uri_path, &hidden) else {
+            continue;
+        };
+        let is_dir = resolved.is_dir();
+        if found.is_empty() || is_dir {
+            found.push(Found {
+                root,
+                resolved,
+                is_dir,
+                hidden,
+            });
+        }
+        if !found[0].is_dir {
+            break;
+        }
+    }
+    found
+}
+
+/// The directories whose contents make up a listing, earlier ones first.
+fn listed_dirs(found: &[Found]) -> Vec<(PathBuf, HiddenPaths)> {
+    found
+        .iter()
+        .filter(|found| found.is_dir)
+        .map(|found| (found.resolved.clone(), found.hidden.clone()))
+        .collect()
+}
+
+/// The route's index file names, or `index.html` when it has none.
+fn index_candidates(index: &[String]) -> Vec<String> {
+    if index.is_empty() {
+        vec![DEFAULT_INDEX.to_string()]
+    } else {
+        index.to_vec()
+    }
+}
+
+/// The first of `candidates` that is a visible file in `dir`, if `dir`
+/// is a directory.
+fn find_index(dir: &Path, candidates: &[String], hidden: &HiddenPaths) -> Option<String> {
+    if !dir.is_dir() {
+        return None;
+    }
+    candidates
+        .iter()
+        .find(|name| {
+            let path = dir.join(name);
+            path.is_file() && !hidden.hides(&path)
+        })
+        .cloned()
+}
+
+/// Drop conditional request headers so `ServeDir` always sends the full file.
+fn strip_conditional_headers(headers: &mut HeaderMap) {
+    headers.remove(header::IF_MODIFIED_SINCE);
+    headers.remove(header::IF_UNMODIFIED_SINCE);
+    headers.remove(header::IF_NONE_MATCH);
+    headers.remove(header::IF_MATCH);
+    headers.remove(header::IF_RANGE);
+}
+
+fn apply_cache_policy(policy: CachePolicy, response: &mut Response) {
+    let headers = response.headers_mut();
+    match policy {
+        CachePolicy::Default => {}
+        CachePolicy::Revalidate => {
+            headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        }
+        CachePolicy::Off => {
+            headers.remove(header::LAST_MODIFIED);
+            headers.remove(header::ETAG);
+            headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        }
+    }
+}
+
+/// Add the route's configured headers, replacing any set by `ServeDir`.
+fn apply_custom_headers(custom: &CustomHeaders, headers: &mut HeaderMap) {
+    for (name, value) in custom.iter() {
+        // Names and values are validated when the config is loaded
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.insert(name, value);
+        }
+    }
+}
+
 fn strip_route_prefix(uri_path: &str, route_prefix: &str) -> String {
     if route_prefix == "/" {
         return uri_path.to_string();
@@ -197,6 +519,378 @@ fn build_not_found_response(uri_path: &str) -> Response {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
+
+    fn get(path: &str, headers: &[(header::HeaderName, &str)]) -> Request {
+        let mut builder = Request::builder().uri(path);
+        for (name, value) in headers {
+            builder = builder.header(name, *value);
+        }
+        builder.body(axum::body::Body::empty()).unwrap()
+    }
+
+    fn options(cache: CachePolicy) -> RouteOptions {
+        RouteOptions {
+            cache,
+            ..RouteOptions::default()
+        }
+    }
+
+    async fn last_modified_of(root: &std::path::Path) -> String {
+        let response = serve_static(
+            "/",
+            &[root.to_path_buf()],
+            &RouteOptions::default(),
+            get("/app.js", &[]),
+        )
+        .await;
+        response.headers()[header::LAST_MODIFIED]
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_default_cache_policy_honours_conditional_requests() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("app.js"), "console.log(1)").unwrap();
+        let last_modified = last_modified_of(tmp.path()).await;
+
+        let response = serve_static(
+            "/",
+            &[tmp.path().to_path_buf()],
+            &options(CachePolicy::Default),
+            get("/app.js", &[(header::IF_MODIFIED_SINCE, &last_modified)]),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(response.headers().get(header::CACHE_CONTROL).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_revalidate_cache_policy_sets_no_cache() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("app.js"), "console.log(1)").unwrap();
+        let last_modified = last_modified_of(tmp.path()).await;
+
+        let response = serve_static(
+            "/",
+            &[tmp.path().to_path_buf()],
+            &options(CachePolicy::Revalidate),
+            get("/app.js", &[(header::IF_MODIFIED_SINCE, &last_modified)]),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+    }
+
+    #[tokio::test]
+    async fn test_off_cache_policy_ignores_conditional_requests() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("app.js"), "console.log(1)").unwrap();
+        let last_modified = last_modified_of(tmp.path()).await;
+
+        let response = serve_static(
+            "/",
+            &[tmp.path().to_path_buf()],
+            &options(CachePolicy::Off),
+            get("/app.js", &[(header::IF_MODIFIED_SINCE, &last_modified)]),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::LAST_MODIFIED).is_none());
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+    }
+
+    #[tokio::test]
+    async fn test_custom_headers_are_added() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("index.html"), "<h1>hi</h1>").unwrap();
+
+        let headers: CustomHeaders = toml::from_str::<toml::Table>(
+            r#"
+            Cross-Origin-Opener-Policy = "same-origin"
+            Cross-Origin-Embedder-Policy = "require-corp"
+            "#,
+        )
+        .unwrap()
+        .try_into()
+        .unwrap();
+        let options = RouteOptions {
+            headers,
+            ..RouteOptions::default()
+        };
+
+        let response =
+            serve_static("/", &[tmp.path().to_path_buf()], &options, get("/", &[])).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["cross-origin-opener-policy"],
+            "same-origin"
+        );
+        assert_eq!(
+            response.headers()["cross-origin-embedder-policy"],
+            "require-corp"
+        );
+    }
+
+    async fn body_of(response: Response) -> String {
+        let body = http_body_util::BodyExt::collect(response.into_body())
+            .await
+            .unwrap()
+            .to_bytes();
+        String::from_utf8_lossy(&body).into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_index_files_are_tried_in_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir(tmp.path().join("docs")).unwrap();
+        fs::write(tmp.path().join("docs/index.htm"), "htm").unwrap();
+        fs::write(tmp.path().join("docs/default.html"), "default").unwrap();
+        let options = RouteOptions {
+            index: vec!["default.html".into(), "index.htm".into()],
+            ..RouteOptions::default()
+        };
+
+        let response = serve_static(
+            "/",
+            &[tmp.path().to_path_buf()],
+            &options,
+            get("/docs/", &[]),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_of(response).await, "default");
+
+        // Without the slash, the browser is sent to the directory first
+        let response = serve_static(
+            "/",
+            &[tmp.path().to_path_buf()],
+            &options,
+            get("/docs?x=1", &[]),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(response.headers()[header::LOCATION], "/docs/?x=1");
+    }
+
+    #[tokio::test]
+    async fn test_directory_without_a_listed_index_shows_a_listing() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("index.html"), "<h1>hi</h1>").unwrap();
+        let options = RouteOptions {
+            index: vec!["README.html".into()],
+            ..RouteOptions::default()
+        };
+
+        let response =
+            serve_static("/", &[tmp.path().to_path_buf()], &options, get("/", &[])).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(body_of(response).await.contains("index.html</a>"));
+
+        let response = serve_static(
+            "/",
+            &[tmp.path().to_path_buf()],
+            &RouteOptions::default(),
+            get("/", &[]),
+        )
+        .await;
+        assert_eq!(body_of(response).await, "<h1>hi</h1>");
+    }
+
+    #[tokio::test]
+    async fn test_dotfiles_are_hidden_by_default() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join(".env"), "SECRET=1").unwrap();
+        fs::create_dir(tmp.path().join(".git")).unwrap();
+        fs::write(tmp.path().join(".git/config"), "[core]").unwrap();
+        fs::write(tmp.path().join("app.js"), "").unwrap();
+        let root = [tmp.path().to_path_buf()];
+
+        for path in ["/.env", "/.git/config", "/.git/", "/%2eenv"] {
+            let response = serve_static("/", &root, &RouteOptions::default(), get(path, &[])).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{path}");
+        }
+
+        let response = serve_static("/", &root, &RouteOptions::default(), get("/", &[])).await;
+        let listing = body_of(response).await;
+        assert!(listing.contains("app.js</a>"));
+        assert!(!listing.contains(".env"));
+        assert!(!listing.contains(".git"));
+
+        let options = RouteOptions {
+            dotfiles: true,
+            ..RouteOptions::default()
+        };
+        let response = serve_static("/", &root, &options, get("/.env", &[])).await;
+        assert_eq!(body_of(response).await, "SECRET=1");
+    }
+
+    #[tokio::test]
+    async fn test_denied_paths_are_hidden() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir(tmp.path().join("certs")).unwrap();
+        fs::write(tmp.path().join("certs/dev.key"), "key").unwrap();
+        fs::write(tmp.path().join("certs/dev.pem"), "pem").unwrap();
+        let options = RouteOptions {
+            deny: vec![crate::domain::PathGlob::parse("*.key").unwrap()],
+            ..RouteOptions::default()
+        };
+        let root = [tmp.path().to_path_buf()];
+
+        let response = serve_static("/", &root, &options, get("/certs/dev.key", &[])).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = serve_static("/", &root, &options, get("/certs/", &[])).await;
+        let listing = body_of(response).await;
+        assert!(listing.contains("dev.pem</a>"));
+        assert!(!listing.contains("dev.key"));
+    }
+
+    #[tokio::test]
+    async fn test_precompressed_siblings_are_served_when_accepted() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("app.js"), "console.log(1)").unwrap();
+        fs::write(tmp.path().join("app.js.gz"), "gzipped").unwrap();
+        fs::write(tmp.path().join("app.js.br"), "brotli").unwrap();
+        let root = [tmp.path().to_path_buf()];
+        let options = RouteOptions::default();
+
+        let response = serve_static(
+            "/",
+            &root,
+            &options,
+            get("/app.js", &[(header::ACCEPT_ENCODING, "gzip")]),
+        )
+        .await;
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(response.headers()[header::VARY], "accept-encoding");
+        assert!(
+            response.headers()[header::CONTENT_TYPE]
+                .to_str()
+                .unwrap()
+                .contains("javascript")
+        );
+        assert_eq!(body_of(response).await, "gzipped");
+
+        let response = serve_static(
+            "/",
+            &root,
+            &options,
+            get("/app.js", &[(header::ACCEPT_ENCODING, "gzip, br")]),
+        )
+        .await;
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "br");
+
+        let response = serve_static("/", &root, &options, get("/app.js", &[])).await;
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+        assert_eq!(body_of(response).await, "console.log(1)");
+
+        // Pages must stay editable for the live reload script
+        let options = RouteOptions {
+            live_reload: true,
+            ..RouteOptions::default()
+        };
+        let response = serve_static(
+            "/",
+            &root,
+            &options,
+            get("/app.js", &[(header::ACCEPT_ENCODING, "gzip")]),
+        )
+        .await;
+        assert_eq!(body_of(response).await, "console.log(1)");
+    }
+
+    #[tokio::test]
+    async fn test_missing_files_get_the_route_404_page() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = [tmp.path().to_path_buf()];
+        let options = RouteOptions::default();
+
+        let response = serve_static("/", &root, &options, get("/missing", &[])).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(body_of(response).await.contains("error-container"));
+
+        fs::write(tmp.path().join("404.html"), "<h1>Gone</h1>").unwrap();
+        let response = serve_static("/", &root, &options, get("/missing", &[])).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(
+            response.headers()[header::CONTENT_TYPE]
+                .to_str()
+                .unwrap()
+                .starts_with("text/html")
+        );
+        assert!(response.headers().get(header::ETAG).is_none());
+        assert!(response.headers().get(header::LAST_MODIFIED).is_none());
+        assert_eq!(body_of(response).await, "<h1>Gone</h1>");
+
+        fs::create_dir(tmp.path().join("errors")).unwrap();
+        fs::write(tmp.path().join("errors/not found.html"), "custom").unwrap();
+        let options = RouteOptions {
+            not_found: Some("errors/not found.html".to_string()),
+            ..RouteOptions::default()
+        };
+        let response = serve_static("/", &root, &options, get("/docs/missing", &[])).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body_of(response).await, "custom");
+    }
+
+    #[tokio::test]
+    async fn test_hidden_404_page_is_not_served() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join(".404.html"), "secret").unwrap();
+        let options = RouteOptions {
+            not_found: Some(".404.html".to_string()),
+            ..RouteOptions::default()
+        };
+
+        let response =
+            serve_static("/", &[tmp.path().to_path_buf()], &options, get("/x", &[])).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(!body_of(response).await.contains("secret"));
+    }
+
+    #[tokio::test]
+    async fn test_merged_roots_are_searched_in_order() {
+        let dist = tempfile::tempdir().unwrap();
+        let public = tempfile::tempdir().unwrap();
+        fs::write(dist.path().join("app.js"), "built").unwrap();
+        fs::write(public.path().join("app.js"), "stale").unwrap();
+        fs::write(public.path().join("robots.txt"), "robots").unwrap();
+        fs::create_dir(public.path().join("docs")).unwrap();
+        fs::write(public.path().join("docs/index.html"), "docs").unwrap();
+        fs::create_dir(dist.path().join("docs")).unwrap();
+        fs::write(dist.path().join("docs/guide.html"), "guide").unwrap();
+        let roots = [dist.path().to_path_buf(), public.path().to_path_buf()];
+        let options = RouteOptions::default();
+
+        for (path, body) in [
+            ("/app.js", "built"),
+            ("/robots.txt", "robots"),
+            ("/docs/", "docs"),
+            ("/docs/guide.html", "guide"),
+        ] {
+            let response = serve_static("/", &roots, &options, get(path, &[])).await;
+            assert_eq!(response.status(), StatusCode::OK, "{path}");
+            assert_eq!(body_of(response).await, body, "{path}");
+        }
+
+        let response = serve_static("/", &roots, &options, get("/missing.js", &[])).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Without an index file, the listing shows both directories
+        fs::remove_file(public.path().join("docs/index.html")).unwrap();
+        fs::write(public.path().join("docs/faq.html"), "faq").unwrap();
+        let response = serve_static("/", &roots, &options, get("/docs/", &[])).await;
+        let listing = body_of(response).await;
+        assert!(listing.contains("guide.html</a>"));
+        assert!(listing.contains("faq.html</a>"));
+    }
 
     #[test]
     fn test_rewrite_redirect_location_to_include_mount_prefix() {
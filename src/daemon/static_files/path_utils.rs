@@ -1,12 +1,18 @@
 use std::path::{Path, PathBuf};
 
+use super::hidden::HiddenPaths;
+
 /// Resolve a URI path to a filesystem path within the root directory.
 ///
-/// Returns `None` if the path doesn't exist or escapes the root
-/// (path traversal protection).
-pub(super) fn resolve_path(root: &Path, uri_path: &str) -> Option<PathBuf> {
+/// Returns `None` if the path doesn't exist, climbs out of the root with
+/// `..`, passes through a symlink the route doesn't follow, or is
+/// hidden, either as requested or once symlinks are followed.
+pub(super) fn resolve_path(root: &Path, uri_path: &str, hidden: &HiddenPaths) -> Option<PathBuf> {
     let decoded = percent_decode(uri_path);
     let relative = decoded.trim_start_matches('/');
+    if hidden.hides_relative(relative) || relative.split(['/', '\\']).any(|name| name == "..") {
+        return None;
+    }
     let dir_path = if relative.is_empty() {
         root.to_path_buf()
     } else {
@@ -16,13 +22,31 @@ pub(super) fn resolve_path(root: &Path, uri_path: &str) -> Option<PathBuf> {
     let canonical = dir_path.canonicalize().ok()?;
     let canonical_root = root.canonicalize().ok()?;
 
-    if !canonical.starts_with(&canonical_root) {
+    let allowed = if passes_through_symlink(root, relative) {
+        hidden.follows(&canonical)
+    } else {
+        canonical.starts_with(&canonical_root)
+    };
+    if !allowed || hidden.hides(&canonical) {
         return None;
     }
 
     Some(canonical)
 }
 
+/// Whether any name along `relative` below `root` is a symlink.
+fn passes_through_symlink(root: &Path, relative: &str) -> bool {
+    let mut path = root.to_path_buf();
+    relative
+        .split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+        .any(|name| {
+            path.push(name);
+            path.symlink_metadata()
+                .is_ok_and(|metadata| metadata.file_type().is_symlink())
+        })
+}
+
 /// Decode percent-encoded URI path segments.
 pub(super) fn percent_decode(s: &str) -> String {
     let mut result = Vec::with_capacity(s.len());
@@ -43,6 +67,14 @@ pub(super) fn percent_decode(s: &str) -> String {
     String::from_utf8_lossy(&result).to_string()
 }
 
+/// A parameter of a form-encoded query.
+pub(super) fn query_param(query: Option<&str>, key: &str) -> Option<String> {
+    query?.split('&').find_map(|pair| {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (name == key).then(|| percent_decode(&value.replace('+', " ")))
+    })
+}
+
 /// Format bytes as a human-readable size string.
 pub(super) fn format_size(bytes: u64) -> String {
     const KB: u64 = 1024;
@@ -63,8 +95,17 @@ pub(super) fn format_size(bytes: u64) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::{RouteOptions, SymlinkPolicy};
     use std::fs;
 
+    fn resolve(root: &Path, uri_path: &str) -> Option<PathBuf> {
+        resolve_path(
+            root,
+            uri_path,
+            &HiddenPaths::new(root, &RouteOptions::default()),
+        )
+    }
+
     #[test]
     fn test_format_size_bytes() {
         assert_eq!(format_size(0), "0 B");
@@ -106,13 +147,21 @@ mod tests {
         assert_eq!(percent_decode("trailing%"), "trailing%");
     }
 
+    #[test]
+    fn test_query_param() {
+        let query = Some("q=login+screen&path=%2Fdesigns");
+        assert_eq!(query_param(query, "q").unwrap(), "login screen");
+        assert_eq!(query_param(query, "path").unwrap(), "/designs");
+        assert_eq!(query_param(query, "format"), None);
+    }
+
     #[test]
     fn test_resolve_path_basic() {
         let tmp = tempfile::tempdir().unwrap();
         let sub = tmp.path().join("subdir");
         fs::create_dir(&sub).unwrap();
 
-        let resolved = resolve_path(tmp.path(), "/subdir");
+        let resolved = resolve(tmp.path(), "/subdir");
         assert_eq!(resolved.unwrap(), sub.canonicalize().unwrap());
     }
 
@@ -120,7 +169,7 @@ mod tests {
     fn test_resolve_path_root() {
         let tmp = tempfile::tempdir().unwrap();
 
-        let resolved = resolve_path(tmp.path(), "/");
+        let resolved = resolve(tmp.path(), "/");
         assert_eq!(resolved.unwrap(), tmp.path().canonicalize().unwrap());
     }
 
@@ -130,7 +179,7 @@ mod tests {
         let sub = tmp.path().join("subdir");
         fs::create_dir(&sub).unwrap();
 
-        let resolved = resolve_path(&sub, "/../../../etc/passwd");
+        let resolved = resolve(&sub, "/../../../etc/passwd");
         assert!(resolved.is_none());
     }
 
@@ -138,7 +187,7 @@ mod tests {
     fn test_resolve_path_nonexistent() {
         let tmp = tempfile::tempdir().unwrap();
 
-        let resolved = resolve_path(tmp.path(), "/does-not-exist");
+        let resolved = resolve(tmp.path(), "/does-not-exist");
         assert!(resolved.is_none());
     }
 
@@ -148,7 +197,82 @@ mod tests {
         let sub = tmp.path().join("my dir");
         fs::create_dir(&sub).unwrap();
 
-        let resolved = resolve_path(tmp.path(), "/my%20dir");
+        let resolved = resolve(tmp.path(), "/my%20dir");
         assert_eq!(resolved.unwrap(), sub.canonicalize().unwrap());
     }
+
+    #[test]
+    fn test_resolve_path_hidden() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join(".env"), "SECRET=1").unwrap();
+        fs::create_dir(tmp.path().join(".git")).unwrap();
+        fs::write(tmp.path().join(".git/config"), "").unwrap();
+
+        assert!(resolve(tmp.path(), "/.env").is_none());
+        assert!(resolve(tmp.path(), "/%2Egit/config").is_none());
+        assert!(resolve(tmp.path(), "/.git/").is_none());
+
+        let allowed = RouteOptions {
+            dotfiles: true,
+            ..RouteOptions::default()
+        };
+        let hidden = HiddenPaths::new(tmp.path(), &allowed);
+        assert!(resolve_path(tmp.path(), "/.env", &hidden).is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_path_hidden_through_symlink() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join(".env"), "SECRET=1").unwrap();
+        std::os::unix::fs::symlink(tmp.path().join(".env"), tmp.path().join("env.txt")).unwrap();
+
+        assert!(resolve(tmp.path(), "/env.txt").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_path_symlink_policies() {
+        let tmp = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("site");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("app.js"), "").unwrap();
+        fs::write(outside.path().join("secret.txt"), "").unwrap();
+        std::os::unix::fs::symlink(root.join("app.js"), root.join("main.js")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.join("store")).unwrap();
+        std::os::unix::fs::symlink("..", root.join("up")).unwrap();
+
+        let resolve_with = |symlinks, uri_path: &str| {
+            let options = RouteOptions {
+                follow_symlinks: symlinks,
+                ..RouteOptions::default()
+            };
+            resolve_path(&root, uri_path, &HiddenPaths::new(&root, &options))
+        };
+
+        assert!(resolve_with(SymlinkPolicy::Never, "/app.js").is_some());
+        assert!(resolve_with(SymlinkPolicy::Never, "/main.js").is_none());
+
+        assert!(resolve_with(SymlinkPolicy::WithinRoot, "/main.js").is_some());
+        assert!(resolve_with(SymlinkPolicy::WithinRoot, "/store/secret.txt").is_none());
+        assert!(resolve_with(SymlinkPolicy::WithinRoot, "/up").is_none());
+        // Where the link leads matters, not the way there
+        assert!(resolve_with(SymlinkPolicy::WithinRoot, "/up/site/app.js").is_some());
+
+        let followed = resolve_with(SymlinkPolicy::Always, "/store/secret.txt").unwrap();
+        assert_eq!(
+            followed,
+            outside.path().join("secret.txt").canonicalize().unwrap()
+        );
+
+        // Following links never lets a request climb out with `..`
+        for uri_path in [
+            "/../site/app.js",
+            "/store/../site/app.js",
+            "/%2E%2E/site/app.js",
+        ] {
+            assert!(resolve_with(SymlinkPolicy::Always, uri_path).is_none());
+        }
+    }
 }
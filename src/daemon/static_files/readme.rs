@@ -0,0 +1,107 @@
+use std::fs;
+use std::path::Path;
+
+use pulldown_cmark::{Event, Options, Parser, html};
+
+use super::directory::DirEntry;
+use crate::daemon::theme;
+
+/// READMEs are looked for in this order, ignoring case.
+const README_NAMES: [&str; 4] = ["readme.md", "readme.markdown", "readme.txt", "readme"];
+
+/// Larger READMEs aren't previewed.
+const MAX_README_SIZE: u64 = 512 * 1024;
+
+/// A directory's README, rendered for its listing.
+pub(super) struct Readme {
+    pub name: String,
+    pub html: String,
+}
+
+/// Render the README among `entries` of `dir`, if there is one.
+pub(super) fn find_readme(dir: &Path, entries: &[DirEntry]) -> Option<Readme> {
+    let entry = README_NAMES.iter().find_map(|wanted| {
+        entries
+            .iter()
+            .find(|e| !e.is_dir && e.name.eq_ignore_ascii_case(wanted))
+    })?;
+    if entry.size > MAX_README_SIZE {
+        return None;
+    }
+
+    let text = fs::read(dir.join(&entry.name)).ok()?;
+    let text = String::from_utf8_lossy(&text);
+    let lower = entry.name.to_ascii_lowercase();
+    let html = if lower.ends_with(".md") || lower.ends_with(".markdown") {
+        render_markdown(&text)
+    } else {
+        format!("<pre>{}</pre>", theme::html_escape(&text))
+    };
+
+    Some(Readme {
+        name: entry.name.clone(),
+        html,
+    })
+}
+
+/// Markdown to HTML. Raw HTML in the file is shown as text, so a README
+/// can't run scripts in the listing.
+fn render_markdown(text: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let events = Parser::new_ext(text, options).map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+        event => event,
+    });
+
+    let mut out = String::with_capacity(text.len() * 3 / 2);
+    html::push_html(&mut out, events);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, size: u64) -> DirEntry {
+        DirEntry {
+            name: name.to_string(),
+            is_dir: false,
+            size,
+            modified: 0,
+        }
+    }
+
+    #[test]
+    fn renders_markdown_without_raw_html() {
+        let html = render_markdown("# Title\n\n- [x] done\n\n<script>alert(1)</script>\n");
+        assert!(html.contains("<h1>Title</h1>"));
+        assert!(html.contains("checkbox"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("<script>"));
+    }
+
+    #[test]
+    fn prefers_markdown_and_escapes_text() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("readme.txt"), "a < b").unwrap();
+        fs::write(tmp.path().join("README.md"), "**bold**").unwrap();
+
+        let entries = [file("readme.txt", 5), file("README.md", 8)];
+        let readme = find_readme(tmp.path(), &entries).unwrap();
+        assert_eq!(readme.name, "README.md");
+        assert!(readme.html.contains("<strong>bold</strong>"));
+
+        let readme = find_readme(tmp.path(), &entries[..1]).unwrap();
+        assert_eq!(readme.html, "<pre>a &lt; b</pre>");
+    }
+
+    #[test]
+    fn skips_missing_and_large_readmes() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(find_readme(tmp.path(), &[file("notes.md", 5)]).is_none());
+        assert!(find_readme(tmp.path(), &[file("README.md", MAX_README_SIZE + 1)]).is_none());
+    }
+}
@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use tokio::task;
+
+use super::hidden::HiddenPaths;
+use super::path_utils::{format_size, query_param};
+use super::styles::{FILEBROWSER_CSS, FILEBROWSER_JS};
+use super::{find_in_roots, listed_dirs};
+use crate::daemon::theme;
+use crate::domain::RouteOptions;
+
+/// Path below a static route that searches it, unless a file is there.
+const ENDPOINT: &str = "/_search";
+
+/// A search stops once it has this many hits...
+const MAX_RESULTS: usize = 500;
+/// ...or has looked at this many files.
+const MAX_VISITED: usize = 200_000;
+const MAX_DEPTH: usize = 32;
+
+pub(super) fn is_search_request(uri_path: &str) -> bool {
+    uri_path == ENDPOINT
+}
+
+#[derive(Debug, Serialize)]
+struct Results {
+    query: String,
+    /// Directory searched, below the route.
+    path: String,
+    hits: Vec<Hit>,
+    /// Whether the search stopped before looking everywhere.
+    truncated: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Hit {
+    /// Below the route, with a trailing `/` for directories.
+    path: String,
+    is_dir: bool,
+    size: u64,
+    modified: u64,
+}
+
+/// Find files whose path below the searched directory contains every
+/// word of `?q=`. `?path=` narrows the search to a directory. Answers
+/// with JSON for `?format=json` or clients that accept it, and with a
+/// page like the directory listing otherwise.
+pub(super) async fn search(
+    route_prefix: &str,
+    roots: &[PathBuf],
+    options: &RouteOptions,
+    query: Option<&str>,
+    headers: &HeaderMap,
+) -> Response {
+    let text = query_param(query, "q").unwrap_or_default();
+    let mut scope = query_param(query, "path").unwrap_or_else(|| "/".to_string());
+    if !scope.starts_with('/') {
+        scope.insert(0, '/');
+    }
+    if !scope.ends_with('/') {
+        scope.push('/');
+    }
+
+    let roots = roots.to_vec();
+    let options = options.clone();
+    let results = task::spawn_blocking(move || {
+        let found = find_in_roots(roots, &scope, &options);
+        if found.first().is_none_or(|found| !found.is_dir) {
+            return None;
+        }
+        let (hits, truncated) = walk(&listed_dirs(&found), &scope, &text);
+        Some(Results {
+            query: text,
+            path: scope,
+            hits,
+            truncated,
+        })
+    })
+    .await
+    .ok()
+    .flatten();
+
+    let Some(results) = results else {
+        return (StatusCode::NOT_FOUND, "Directory not found").into_response();
+    };
+    let wants_json = query_param(query, "format").as_deref() == Some("json")
+        || headers
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| accept.contains("application/json"));
+    if wants_json {
+        return match serde_json::to_string(&results) {
+            Ok(json) => ([(header::CONTENT_TYPE, "application/json")], json).into_response(),
+            Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+    }
+    (
+        [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+        render_results(route_prefix, &results),
+    )
+        .into_response()
+}
+
+/// Hits among everything visible under `dirs`, earlier directories
+/// winning where paths clash, and whether the walk was cut short.
+/// Symlinks are followed to files the route allows, never to
+/// directories. Touches the filesystem.
+fn walk(dirs: &[(PathBuf, HiddenPaths)], scope: &str, text: &str) -> (Vec<Hit>, bool) {
+    let terms: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+    let mut hits = Vec::new();
+    if terms.is_empty() {
+        return (hits, false);
+    }
+    // Every path so far, and whether it's a directory
+    let mut seen: HashMap<String, bool> = HashMap::new();
+    let mut visited = 0;
+    let mut truncated = false;
+
+    'walk: for (dir, hidden) in dirs {
+        let mut pending = vec![(dir.clone(), String::new(), 0)];
+        while let Some((path, prefix, depth)) = pending.pop() {
+            let Ok(read_dir) = fs::read_dir(&path) else {
+                continue;
+            };
+            let mut children: Vec<_> = read_dir.filter_map(Result::ok).collect();
+            children.sort_by_key(|child| child.file_name());
+
+            for child in children {
+                visited += 1;
+                if visited > MAX_VISITED {
+                    truncated = true;
+                    break 'walk;
+                }
+                let child_path = child.path();
+                if hidden.hides(&child_path) {
+                    continue;
+                }
+                let Ok(file_type) = child.file_type() else {
+                    continue;
+                };
+                let metadata = if file_type.is_symlink() {
+                    match child_path.canonicalize() {
+                        Ok(target)
+                            if target.is_file()
+                                && hidden.follows(&target)
+                                && !hidden.hides(&target) =>
+                        {
+                            fs::metadata(&target)
+                        }
+                        _ => continue,
+                    }
+                } else {
+                    child.metadata()
+                };
+                let Ok(metadata) = metadata else {
+                    continue;
+                };
+
+                let is_dir = metadata.is_dir();
+                let name = format!("{prefix}{}", child.file_name().to_string_lossy());
+                let descend = is_dir && depth < MAX_DEPTH;
+                match seen.get(&name) {
+                    // Same directory in an earlier root: search what's inside
+                    Some(true) if descend => {
+                        pending.push((child_path, format!("{name}/"), depth + 1));
+                        continue;
+                    }
+                    Some(_) => continue,
+                    None => {}
+                }
+                seen.insert(name.clone(), is_dir);
+                if descend {
+                    pending.push((child_path, format!("{name}/"), depth + 1));
+                }
+
+                let lower = name.to_lowercase();
+                if terms.iter().all(|term| lower.contains(term.as_str())) {
+                    hits.push(Hit {
+                        path: format!("{scope}{name}{}", if is_dir { "/" } else { "" }),
+                        is_dir,
+                        size: if is_dir { 0 } else { metadata.len() },
+                        modified: metadata
+                            .modified()
+                            .ok()
+                            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                            .map_or(0, |d| d.as_secs()),
+                    });
+                    if hits.len() >= MAX_RESULTS {
+                        truncated = true;
+                        break 'walk;
+                    }
+                }
+            }
+        }
+    }
+
+    hits.sort_by(|a, b| a.path.cmp(&b.path));
+    (hits, truncated)
+}
+
+/// The search box shown above listings, searching `scope` (below the
+/// route) and showing `text`.
+pub(super) fn render_search_form(route_prefix: &str, scope: &str, text: &str) -> String {
+    format!(
+        "<form class=\"search\" action=\"{}{ENDPOINT}\">\
+         <input type=\"search\" name=\"q\" value=\"{}\" placeholder=\"Search files\" required>\
+         <input type=\"hidden\" name=\"path\" value=\"{}\">\
+         </form>\n",
+        theme::html_escape(route_prefix.trim_end_matches('/')),
+        theme::html_escape(text),
+        theme::html_escape(scope),
+    )
+}
+
+fn render_results(route_prefix: &str, results: &Results) -> String {
+    let mount = route_prefix.trim_end_matches('/');
+    let mut body = String::with_capacity(4096);
+
+    body.push_str("<h1 class=\"page-title\">Results for <code>");
+    body.push_str(&theme::html_escape(&results.query));
+    body.push_str("</code> in <code>");
+    body.push_str(&theme::html_escape(&format!("{mount}{}", results.path)));
+    body.push_str("</code></h1>\n");
+    body.push_str(&render_search_form(
+        route_prefix,
+        &results.path,
+        &results.query,
+    ));
+
+    body.push_str("<div class=\"file-card\">\n<table id=\"listing\">\n<thead><tr>");
+    body.push_str("<th>Path</th><th class=\"col-size\">Size</th>");
+    body.push_str("<th class=\"col-mod\">Modified</th></tr></thead>\n<tbody>\n");
+    if results.hits.is_empty() {
+        body.push_str("<tr><td colspan=\"3\" class=\"empty-dir\">No files found</td></tr>\n");
+    }
+    for hit in &results.hits {
+        let href: Vec<String> = hit
+            .path
+            .split('/')
+            .map(theme::encode_path_segment)
+            .collect();
+        body.push_str("<tr><td>");
+        body.push_str(if hit.is_dir {
+            theme::FOLDER_ICON
+        } else {
+            theme::FILE_ICON
+        });
+        body.push_str("<a href=\"");
+        body.push_str(&theme::html_escape(mount));
+        body.push_str(&href.join("/"));
+        body.push_str("\">");
+        body.push_str(&theme::html_escape(
+            hit.path.strip_prefix(&results.path).unwrap_or(&hit.path),
+        ));
+        body.push_str("</a></td><td class=\"size\">");
+        if hit.is_dir {
+            body.push('\u{2014}');
+        } else {
+            body.push_str(&format_size(hit.size));
+        }
+        body.push_str("</td><td class=\"modified\" data-ts=\"");
+        body.push_str(&hit.modified.to_string());
+        body.push_str("\"></td></tr>\n");
+    }
+    if results.truncated {
+        body.push_str(
+            "<tr><td colspan=\"3\" class=\"empty-dir\">\
+             Search stopped early; try more specific words</td></tr>\n",
+        );
+    }
+    body.push_str("</tbody>\n</table>\n</div>");
+
+    theme::render_page(
+        &format!("Search: {}", results.query),
+        &body,
+        FILEBROWSER_CSS,
+        FILEBROWSER_JS,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+
+    async fn run(root: &std::path::Path, query: &str, accept: Option<&str>) -> Response {
+        let mut headers = HeaderMap::new();
+        if let Some(accept) = accept {
+            headers.insert(header::ACCEPT, accept.parse().unwrap());
+        }
+        search(
+            "/files",
+            &[root.to_path_buf()],
+            &RouteOptions::default(),
+            Some(query),
+            &headers,
+        )
+        .await
+    }
+
+    async fn json(response: Response) -> serde_json::Value {
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn tree() -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join("designs/2024")).unwrap();
+        fs::write(tmp.path().join("designs/2024/Login Screen.png"), "png").unwrap();
+        fs::write(tmp.path().join("designs/logo.svg"), "svg").unwrap();
+        fs::write(tmp.path().join("notes.txt"), "").unwrap();
+        fs::create_dir(tmp.path().join(".git")).unwrap();
+        fs::write(tmp.path().join(".git/login"), "").unwrap();
+        tmp
+    }
+
+    #[tokio::test]
+    async fn finds_files_matching_every_word() {
+        let tmp = tree();
+        let results = json(run(tmp.path(), "q=LOGIN+2024&format=json", None).await).await;
+        assert_eq!(results["hits"].as_array().unwrap().len(), 1);
+        assert_eq!(results["hits"][0]["path"], "/designs/2024/Login Screen.png");
+        assert_eq!(results["hits"][0]["size"], 3);
+        assert_eq!(results["truncated"], false);
+
+        // Hidden files stay hidden
+        let results = json(run(tmp.path(), "q=login", Some("application/json")).await).await;
+        assert_eq!(results["hits"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn searches_below_a_directory() {
+        let tmp = tree();
+        let results = json(run(tmp.path(), "q=o&path=/designs&format=json", None).await).await;
+        let paths: Vec<_> = results["hits"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hit| hit["path"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            paths,
+            ["/designs/2024/Login Screen.png", "/designs/logo.svg"]
+        );
+
+        let response = run(tmp.path(), "q=o&path=/missing", None).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = run(tmp.path(), "q=o&path=/.git", None).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn renders_a_results_page() {
+        let tmp = tree();
+        let response = run(tmp.path(), "q=login", None).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains("href=\"/files/designs/2024/Login%20Screen.png\""));
+        assert!(html.contains(">designs/2024/Login Screen.png</a>"));
+        assert!(html.contains("action=\"/files/_search\""));
+        assert!(html.contains("value=\"login\""));
+    }
+}
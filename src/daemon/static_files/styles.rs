@@ -38,6 +38,16 @@ pub(super) const FILEBROWSER_CSS: &str = "\
     font-size:1em;font-weight:600;color:var(--text);\
     background:transparent;padding:0;\
 }\
+.page-title .download{\
+    margin-left:auto;font-size:.85em;color:var(--fox-orange);\
+}\
+.search{margin-bottom:16px}\
+.search input[type=search]{\
+    width:100%;padding:10px 16px;border-radius:10px;\
+    border:1px solid var(--border);background:var(--card-bg);\
+    font:inherit;color:var(--text);outline:none;\
+}\
+.search input[type=search]:focus{border-color:var(--fox-orange)}\
 .breadcrumb{\
     display:flex;flex-wrap:wrap;align-items:center;gap:4px;\
     padding:12px 18px;margin-bottom:24px;\
@@ -93,22 +103,144 @@ pub(super) const FILEBROWSER_CSS: &str = "\
 .si{font-size:.75em;margin-left:6px;opacity:.3;transition:all .2s}\
 .si.active{opacity:1;color:var(--fox-orange);font-weight:700}\
 .empty-dir{padding:48px 18px;text-align:center;color:var(--text-light);font-style:italic}\
+.thumb{\
+    width:40px;height:40px;object-fit:cover;border-radius:6px;\
+    vertical-align:middle;margin-right:10px;background:var(--warm-bg);\
+}\
+.thumb-icon,.thumb.broken{display:none}\
+.thumb.broken+.thumb-icon{display:inline}\
+.badge{\
+    margin-left:8px;padding:1px 8px;border-radius:10px;\
+    background:var(--warm-bg);color:var(--text-light);\
+    font-size:.75em;white-space:nowrap;\
+}\
+.upload{\
+    display:flex;flex-wrap:wrap;align-items:center;gap:12px;\
+    padding:12px 18px;margin-bottom:24px;\
+    background:var(--card-bg);border-radius:10px;\
+    border:1px dashed var(--border-hover);font-size:.9em;\
+}\
+.upload button{\
+    padding:6px 14px;border-radius:6px;border:none;cursor:pointer;\
+    background:var(--fox-orange);color:#fff;font-weight:600;\
+}\
+.upload-hint{color:var(--text-light)}\
+.upload.busy{opacity:.5;pointer-events:none}\
+body.dropping .upload{border-color:var(--fox-orange);background:#FFF5ED}\
 .col-size{width:110px}\
 .col-mod{width:200px}\
+.readme-card{\
+    margin-top:24px;background:var(--card-bg);border-radius:12px;\
+    border:1px solid var(--border);overflow:hidden;\
+    box-shadow:0 4px 16px rgba(0,0,0,.04);\
+    animation:fadeIn .5s ease-out;\
+}\
+.readme-title{\
+    display:flex;align-items:center;gap:8px;padding:12px 18px;\
+    border-bottom:1px solid var(--border);\
+    font-size:.9em;font-weight:600;color:var(--text-light);\
+}\
+.readme-body{padding:8px 28px 24px;line-height:1.6;overflow-wrap:break-word}\
+.readme-body h1,.readme-body h2,.readme-body h3{margin:20px 0 10px}\
+.more-row td{padding:14px 18px;text-align:center;color:var(--text-light);font-size:.9em}\
+.more-row a{margin-left:12px;color:var(--fox-orange);font-weight:600;text-decoration:none}\
+.more-row.busy{opacity:.5;pointer-events:none}\
+.readme-body p,.readme-body ul,.readme-body ol,.readme-body pre,.readme-body table{margin:10px 0}\
+.readme-body ul,.readme-body ol{padding-left:24px}\
+.readme-body pre{overflow-x:auto;padding:12px;border-radius:8px;background:var(--warm-bg)}\
+.readme-body table{border-collapse:collapse}\
+.readme-body th,.readme-body td{border:1px solid var(--border);padding:6px 12px}\
+.readme-body img{max-width:100%}\
 @keyframes fadeIn{from{opacity:0}to{opacity:1}}\
 @media(max-width:768px){.col-mod{display:none}.col-size{width:80px}}\
 ";
 
 pub(super) const FILEBROWSER_JS: &str = "\
-document.querySelectorAll('.modified').forEach(function(el){\
-    var ts=parseInt(el.dataset.ts);\
-    if(ts>0){\
-        var d=new Date(ts*1000);\
-        el.textContent=d.toLocaleDateString(undefined,\
-            {year:'numeric',month:'short',day:'numeric'})\
-            +' '+d.toLocaleTimeString([],{hour:'2-digit',minute:'2-digit'});\
-    }\
+function showDates(root){\
+    root.querySelectorAll('.modified').forEach(function(el){\
+        var ts=parseInt(el.dataset.ts);\
+        if(ts>0){\
+            var d=new Date(ts*1000);\
+            el.textContent=d.toLocaleDateString(undefined,\
+                {year:'numeric',month:'short',day:'numeric'})\
+                +' '+d.toLocaleTimeString([],{hour:'2-digit',minute:'2-digit'});\
+        }\
+    });\
+}\
+showDates(document);\
+function duration(s){\
+    s=Math.round(s);\
+    var h=Math.floor(s/3600),m=Math.floor(s%3600/60),x=s%60;\
+    return(h?h+':'+(m<10?'0':''):'')+m+':'+(x<10?'0':'')+x;\
+}\
+function loadMedia(el){\
+    var media=el.tagName==='VIDEO'?el:document.createElement('audio');\
+    media.preload='metadata';\
+    media.addEventListener('loadedmetadata',function(){\
+        var badge=el.closest('tr').querySelector('.badge');\
+        if(badge&&isFinite(media.duration))\
+            badge.textContent=duration(media.duration)+' \\u00B7 '+badge.textContent;\
+    });\
+    media.src=el.dataset.media;\
+}\
+var seen='IntersectionObserver' in window?new IntersectionObserver(function(items){\
+    items.forEach(function(item){\
+        if(item.isIntersecting){seen.unobserve(item.target);loadMedia(item.target);}\
+    });\
+}):null;\
+function watchMedia(root){\
+    root.querySelectorAll('[data-media]').forEach(function(el){\
+        if(seen)seen.observe(el);else loadMedia(el);\
+    });\
+}\
+watchMedia(document);\
+document.addEventListener('click',function(e){\
+    var more=e.target.closest('.load-more');\
+    if(!more)return;\
+    e.preventDefault();\
+    var row=more.closest('tr');\
+    row.classList.add('busy');\
+    fetch(more.href).then(function(r){return r.text();}).then(function(html){\
+        var page=new DOMParser().parseFromString(html,'text/html');\
+        page.querySelectorAll('#listing tbody tr[data-name]').forEach(function(r){\
+            r=document.importNode(r,true);\
+            row.parentNode.insertBefore(r,row);\
+            showDates(r);watchMedia(r);\
+        });\
+        var next=page.querySelector('#listing .load-more');\
+        if(next)more.href=next.href;else row.remove();\
+        row.classList.remove('busy');\
+    }).catch(function(){location.href=more.href;});\
 });\
+var upload=document.querySelector('form.upload');\
+function send(files){\
+    if(!files.length)return;\
+    var data=new FormData();\
+    for(var i=0;i<files.length;i++)data.append('file',files[i]);\
+    upload.classList.add('busy');\
+    fetch(location.pathname,{method:'POST',body:data}).then(function(r){\
+        if(!r.ok)return r.text().then(function(t){throw t||r.status;});\
+        location.reload();\
+    }).catch(function(e){\
+        upload.classList.remove('busy');\
+        alert('Upload failed: '+e);\
+    });\
+}\
+if(upload){\
+    upload.addEventListener('submit',function(e){\
+        e.preventDefault();send(upload.querySelector('input').files);\
+    });\
+    document.addEventListener('dragover',function(e){\
+        e.preventDefault();document.body.classList.add('dropping');\
+    });\
+    document.addEventListener('dragleave',function(e){\
+        if(!e.relatedTarget)document.body.classList.remove('dropping');\
+    });\
+    document.addEventListener('drop',function(e){\
+        e.preventDefault();document.body.classList.remove('dropping');\
+        send(e.dataTransfer.files);\
+    });\
+}\
 var col=0,asc=true;\
 function sort(c){\
     if(col===c)asc=!asc;else{col=c;asc=true;}\
@@ -118,7 +250,8 @@ function sort(c){\
         el.textContent=i===col?(asc?'\\u25B2':'\\u25BC'):'';\
     }\
     var tbody=document.querySelector('#listing tbody');\
-    var nonEntryRows=Array.from(tbody.querySelectorAll('tr:not([data-name])'));\
+    var nonEntryRows=Array.from(tbody.querySelectorAll('tr:not([data-name]):not(.more-row)'));\
+    var moreRow=tbody.querySelector('.more-row');\
     var rows=Array.from(tbody.querySelectorAll('tr[data-name]'));\
     rows.sort(function(a,b){\
         var ad=parseInt(a.dataset.dir),bd=parseInt(b.dataset.dir);\
@@ -138,5 +271,6 @@ function sort(c){\
     while(tbody.firstChild)tbody.removeChild(tbody.firstChild);\
     nonEntryRows.forEach(function(r){tbody.appendChild(r);});\
     rows.forEach(function(r){tbody.appendChild(r);});\
+    if(moreRow)tbody.appendChild(moreRow);\
 }\
 ";
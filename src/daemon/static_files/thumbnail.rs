@@ -0,0 +1,206 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use axum::body::{Body, Bytes};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use image::ImageFormat;
+use tokio::task;
+
+/// Longest side of a thumbnail, in pixels: twice the size shown, for
+/// high-density screens.
+const THUMB_SIZE: u32 = 80;
+
+/// Larger images get the plain file icon instead of a thumbnail.
+const MAX_SOURCE_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Memory the most recently made thumbnails may take.
+const MAX_CACHE_BYTES: usize = 16 * 1024 * 1024;
+
+/// How a listing previews a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Preview {
+    /// A thumbnail from `?thumb=`.
+    Thumbnail,
+    /// The file itself, for images browsers scale on their own.
+    Vector,
+    Video,
+    Audio,
+}
+
+impl Preview {
+    pub(super) fn of(name: &str) -> Option<Self> {
+        let (_, extension) = name.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "png" | "jpg" | "jpeg" | "gif" | "webp" => Some(Self::Thumbnail),
+            "svg" => Some(Self::Vector),
+            "mp4" | "m4v" | "mov" | "webm" | "ogv" => Some(Self::Video),
+            "mp3" | "m4a" | "aac" | "wav" | "flac" | "ogg" | "oga" | "opus" => Some(Self::Audio),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the query asks for a thumbnail rather than the file. Its
+/// value is the file's modification time, so changed files get new URLs.
+pub(super) fn wants_thumbnail(query: Option<&str>) -> bool {
+    query.is_some_and(|query| {
+        query
+            .split('&')
+            .any(|pair| pair == "thumb" || pair.starts_with("thumb="))
+    })
+}
+
+/// A PNG thumbnail of the image at `path`, or 404 if it isn't one that
+/// can be shrunk.
+pub(super) async fn serve_thumbnail(path: PathBuf) -> Response {
+    if Preview::of(&path.to_string_lossy()) != Some(Preview::Thumbnail) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let thumbnail = task::spawn_blocking(move || cached_thumbnail(&path))
+        .await
+        .ok()
+        .flatten();
+
+    match thumbnail {
+        Some(png) => (
+            [
+                (header::CONTENT_TYPE, "image/png"),
+                // The URL changes along with the file
+                (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+            ],
+            Body::from(png),
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Thumbnails made lately, dropped oldest first.
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<PathBuf, Cached>,
+    order: VecDeque<PathBuf>,
+    bytes: usize,
+}
+
+struct Cached {
+    modified: SystemTime,
+    len: u64,
+    png: Bytes,
+}
+
+fn cache() -> &'static Mutex<Cache> {
+    static CACHE: OnceLock<Mutex<Cache>> = OnceLock::new();
+    CACHE.get_or_init(Mutex::default)
+}
+
+fn cached_thumbnail(path: &Path) -> Option<Bytes> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?;
+    if metadata.len() > MAX_SOURCE_SIZE {
+        return None;
+    }
+    if let Some(cached) = cache().lock().ok()?.entries.get(path)
+        && cached.modified == modified
+        && cached.len == metadata.len()
+    {
+        return Some(cached.png.clone());
+    }
+
+    let png = make_thumbnail(path)?;
+    let mut cache = cache().lock().ok()?;
+    if let Some(old) = cache.entries.remove(path) {
+        cache.bytes -= old.png.len();
+        cache.order.retain(|cached| cached != path);
+    }
+    cache.bytes += png.len();
+    cache.order.push_back(path.to_path_buf());
+    cache.entries.insert(
+        path.to_path_buf(),
+        Cached {
+            modified,
+            len: metadata.len(),
+            png: png.clone(),
+        },
+    );
+    while cache.bytes > MAX_CACHE_BYTES
+        && let Some(oldest) = cache.order.pop_front()
+    {
+        if let Some(evicted) = cache.entries.remove(&oldest) {
+            cache.bytes -= evicted.png.len();
+        }
+    }
+    Some(png)
+}
+
+fn make_thumbnail(path: &Path) -> Option<Bytes> {
+    let image = image::ImageReader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .decode()
+        .ok()?;
+    let mut png = Vec::new();
+    image
+        .thumbnail(THUMB_SIZE, THUMB_SIZE)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .ok()?;
+    Some(png.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+
+    #[test]
+    fn detects_thumbnail_requests() {
+        assert!(wants_thumbnail(Some("thumb=1712345678")));
+        assert!(wants_thumbnail(Some("sort=name&thumb")));
+        assert!(!wants_thumbnail(Some("thumbnail=1")));
+        assert!(!wants_thumbnail(None));
+    }
+
+    #[test]
+    fn previews_by_extension() {
+        assert_eq!(Preview::of("shot.PNG"), Some(Preview::Thumbnail));
+        assert_eq!(Preview::of("logo.svg"), Some(Preview::Vector));
+        assert_eq!(Preview::of("demo.webm"), Some(Preview::Video));
+        assert_eq!(Preview::of("voice.m4a"), Some(Preview::Audio));
+        assert_eq!(Preview::of("notes.txt"), None);
+        assert_eq!(Preview::of("png"), None);
+    }
+
+    #[tokio::test]
+    async fn shrinks_images_to_png() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("wide.jpg");
+        image::RgbImage::new(400, 200).save(&path).unwrap();
+
+        let response = serve_thumbnail(path.clone()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let thumbnail = image::load_from_memory(&body).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (80, 40));
+
+        // Served from the cache while the file is unchanged
+        assert_eq!(cached_thumbnail(&path).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn broken_images_are_not_found() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("broken.png");
+        fs::write(&path, "not a png").unwrap();
+        assert_eq!(serve_thumbnail(path).await.status(), StatusCode::NOT_FOUND);
+        let text = tmp.path().join("notes.txt");
+        fs::write(&text, "hello").unwrap();
+        assert_eq!(serve_thumbnail(text).await.status(), StatusCode::NOT_FOUND);
+    }
+}
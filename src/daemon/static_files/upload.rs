@@ -0,0 +1,480 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use axum::body::Bytes;
+use axum::extract::Request;
+use axum::http::{HeaderValue, Method, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use http_body_util::BodyExt;
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tokio::task;
+
+use super::hidden::HiddenPaths;
+use super::path_utils::{percent_decode, resolve_path};
+use crate::domain::RouteOptions;
+
+/// Most a single request may upload, over all its files.
+const MAX_UPLOAD_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Whether a request to a route with uploads on is one.
+pub(super) fn is_upload(method: &Method) -> bool {
+    method == Method::PUT || method == Method::POST
+}
+
+/// Save the files of an upload request to a static route. `uri_path`
+/// is the request path below the route; `location` is the full one.
+///
+/// `PUT` writes the body to the file at the path, replacing it if it
+/// exists. A multipart `POST` to a directory saves every file in the
+/// form there, numbering names that are taken, then sends the browser
+/// back to the directory. Files are written under a temporary name and
+/// moved into place once complete, so a failed upload leaves nothing.
+pub(super) async fn receive(
+    roots: &[PathBuf],
+    options: &RouteOptions,
+    uri_path: &str,
+    location: &str,
+    request: Request,
+) -> Response {
+    let result = if request.method() == Method::PUT {
+        put(roots, options, uri_path, request).await
+    } else {
+        post(roots, options, uri_path, location, request).await
+    };
+    result.unwrap_or_else(Failed::into_response)
+}
+
+#[derive(Debug)]
+enum Failed {
+    NotFound,
+    Conflict(&'static str),
+    Rejected(&'static str),
+    TooLarge,
+    Io(io::Error),
+}
+
+impl From<io::Error> for Failed {
+    fn from(error: io::Error) -> Self {
+        Failed::Io(error)
+    }
+}
+
+impl IntoResponse for Failed {
+    fn into_response(self) -> Response {
+        match self {
+            Failed::NotFound => (StatusCode::NOT_FOUND, "Directory not found").into_response(),
+            Failed::Conflict(reason) => (StatusCode::CONFLICT, reason).into_response(),
+            Failed::Rejected(reason) => (StatusCode::BAD_REQUEST, reason).into_response(),
+            Failed::TooLarge => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "Uploads are limited to 1 GiB",
+            )
+                .into_response(),
+            // The daemon usually runs as its service user, not the owner
+            Failed::Io(error) if error.kind() == io::ErrorKind::PermissionDenied => (
+                StatusCode::FORBIDDEN,
+                "Roxy's service user can't write to this directory",
+            )
+                .into_response(),
+            Failed::Io(error) => {
+                tracing::warn!("Upload failed: {error}");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Upload failed").into_response()
+            }
+        }
+    }
+}
+
+/// Create the directory at `uri_path` for WebDAV's `MKCOL`. Its parent
+/// has to exist already.
+pub(super) async fn make_directory(
+    roots: &[PathBuf],
+    options: &RouteOptions,
+    uri_path: &str,
+) -> Response {
+    let result = async {
+        let Some((parent, name)) = uri_path.trim_end_matches('/').rsplit_once('/') else {
+            return Err(Failed::Conflict("Parent directory not found"));
+        };
+        let name = percent_decode(name);
+        let (dir, hidden) = find_directory(roots, options, &format!("{parent}/"))
+            .await
+            .map_err(|_| Failed::Conflict("Parent directory not found"))?;
+        let relative = format!("{}/{name}", percent_decode(parent));
+        check_name(&hidden, &dir, &relative, &name)?;
+
+        let target = dir.join(&name);
+        if target.symlink_metadata().is_ok() {
+            return Ok((StatusCode::METHOD_NOT_ALLOWED, "Already exists").into_response());
+        }
+        fs::create_dir(&target).await?;
+        Ok(StatusCode::CREATED.into_response())
+    }
+    .await;
+    result.unwrap_or_else(Failed::into_response)
+}
+
+async fn put(
+    roots: &[PathBuf],
+    options: &RouteOptions,
+    uri_path: &str,
+    request: Request,
+) -> Result<Response, Failed> {
+    let Some((parent, name)) = uri_path.rsplit_once('/') else {
+        return Err(Failed::NotFound);
+    };
+    let name = percent_decode(name);
+    let (dir, hidden) = find_directory(roots, options, &format!("{parent}/")).await?;
+    let relative = format!("{}/{name}", percent_decode(parent));
+    check_name(&hidden, &dir, &relative, &name)?;
+    check_length(&request)?;
+
+    let target = dir.join(&name);
+    if target.is_dir() {
+        return Err(Failed::Rejected("A directory exists at this path"));
+    }
+    let existed = target.exists();
+
+    let mut budget = MAX_UPLOAD_SIZE;
+    let mut incoming = Incoming::create(&dir).await?;
+    let mut body = request.into_body();
+    while let Some(frame) = body.frame().await {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(_) => {
+                return Err(incoming
+                    .abandon(Failed::Rejected("Upload interrupted"))
+                    .await);
+            }
+        };
+        if let Ok(data) = frame.into_data()
+            && let Err(failed) = incoming.write(&data, &mut budget).await
+        {
+            return Err(incoming.abandon(failed).await);
+        }
+    }
+    incoming.finish(&target).await?;
+
+    Ok(if existed {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        StatusCode::CREATED.into_response()
+    })
+}
+
+async fn post(
+    roots: &[PathBuf],
+    options: &RouteOptions,
+    uri_path: &str,
+    location: &str,
+    request: Request,
+) -> Result<Response, Failed> {
+    if !uri_path.ends_with('/') {
+        return Err(Failed::Rejected("Files can only be posted to a directory"));
+    }
+    let boundary = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| multer::parse_boundary(value).ok())
+        .ok_or(Failed::Rejected("Expected a multipart/form-data upload"))?;
+    let (dir, hidden) = find_directory(roots, options, uri_path).await?;
+    check_length(&request)?;
+
+    let mut budget = MAX_UPLOAD_SIZE;
+    let mut form = multer::Multipart::new(request.into_body().into_data_stream(), boundary);
+    while let Some(mut field) = form.next_field().await.map_err(rejected)? {
+        // Some browsers send the path the file was picked from
+        let Some(name) = field
+            .file_name()
+            .and_then(|name| name.rsplit(['/', '\\']).next())
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+        else {
+            continue;
+        };
+        let relative = format!("{}{name}", percent_decode(uri_path));
+        check_name(&hidden, &dir, &relative, &name)?;
+
+        let mut incoming = Incoming::create(&dir).await?;
+        loop {
+            let chunk = match field.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(error) => return Err(incoming.abandon(rejected(error)).await),
+            };
+            if let Err(failed) = incoming.write(&chunk, &mut budget).await {
+                return Err(incoming.abandon(failed).await);
+            }
+        }
+        incoming.finish(&free_name(&dir, &name)).await?;
+    }
+
+    let mut response = StatusCode::SEE_OTHER.into_response();
+    if let Ok(location) = HeaderValue::from_str(location) {
+        response.headers_mut().insert(header::LOCATION, location);
+    }
+    Ok(response)
+}
+
+fn rejected(error: multer::Error) -> Failed {
+    tracing::debug!("Bad multipart upload: {error}");
+    Failed::Rejected("Malformed multipart upload")
+}
+
+/// The first root's directory at `uri_path` that uploads may go into.
+async fn find_directory(
+    roots: &[PathBuf],
+    options: &RouteOptions,
+    uri_path: &str,
+) -> Result<(PathBuf, HiddenPaths), Failed> {
+    let roots = roots.to_vec();
+    let options = options.clone();
+    let uri_path = uri_path.to_string();
+    task::spawn_blocking(move || {
+        roots.iter().find_map(|root| {
+            let hidden = HiddenPaths::new(root, &options);
+            resolve_path(root, &uri_path, &hidden)
+                .filter(|dir| dir.is_dir())
+                .map(|dir| (dir, hidden))
+        })
+    })
+    .await
+    .ok()
+    .flatten()
+    .ok_or(Failed::NotFound)
+}
+
+/// Refuse names that would leave `dir` or land on a hidden path, both
+/// as requested (`relative` to the root) and on disk.
+fn check_name(hidden: &HiddenPaths, dir: &Path, relative: &str, name: &str) -> Result<(), Failed> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']) {
+        return Err(Failed::Rejected("Invalid file name"));
+    }
+    if hidden.hides_relative(relative) || hidden.hides(&dir.join(name)) {
+        return Err(Failed::Rejected("This file name can't be uploaded here"));
+    }
+    Ok(())
+}
+
+/// Turn away uploads that announce they're too large before reading them.
+fn check_length(request: &Request) -> Result<(), Failed> {
+    let length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    match length {
+        Some(length) if length > MAX_UPLOAD_SIZE => Err(Failed::TooLarge),
+        _ => Ok(()),
+    }
+}
+
+/// `name` in `dir`, or `name (1)`, `name (2)`... if it's taken.
+fn free_name(dir: &Path, name: &str) -> PathBuf {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{extension}")),
+        _ => (name, String::new()),
+    };
+    let mut path = dir.join(name);
+    let mut n = 1;
+    while path.symlink_metadata().is_ok() {
+        path = dir.join(format!("{stem} ({n}){extension}"));
+        n += 1;
+    }
+    path
+}
+
+/// A file being received under a temporary, hidden name.
+struct Incoming {
+    file: File,
+    temp: PathBuf,
+}
+
+impl Incoming {
+    async fn create(dir: &Path) -> Result<Self, Failed> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let temp = dir.join(format!(
+            ".roxy-upload-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = File::create(&temp).await?;
+        Ok(Self { file, temp })
+    }
+
+    async fn write(&mut self, data: &Bytes, budget: &mut u64) -> Result<(), Failed> {
+        *budget = budget
+            .checked_sub(data.len() as u64)
+            .ok_or(Failed::TooLarge)?;
+        self.file.write_all(data).await?;
+        Ok(())
+    }
+
+    async fn finish(mut self, target: &Path) -> Result<(), Failed> {
+        let result = async {
+            self.file.flush().await?;
+            fs::rename(&self.temp, target).await
+        }
+        .await;
+        match result {
+            Ok(()) => Ok(()),
+            Err(error) => Err(self.abandon(Failed::Io(error)).await),
+        }
+    }
+
+    /// Remove the partial file and pass on why.
+    async fn abandon(self, failed: Failed) -> Failed {
+        drop(self.file);
+        let _ = fs::remove_file(&self.temp).await;
+        failed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    fn uploads() -> RouteOptions {
+        RouteOptions {
+            uploads: true,
+            ..RouteOptions::default()
+        }
+    }
+
+    fn request(method: Method, path: &str, content_type: &str, body: &str) -> Request {
+        Request::builder()
+            .method(method)
+            .uri(path)
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn put_creates_and_replaces_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tmp.path().join("notes")).unwrap();
+        let roots = [tmp.path().to_path_buf()];
+
+        let put = |body| request(Method::PUT, "/notes/a%20b.txt", "text/plain", body);
+        let response = receive(&roots, &uploads(), "/notes/a%20b.txt", "/", put("one")).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = receive(&roots, &uploads(), "/notes/a%20b.txt", "/", put("two")).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let saved = std::fs::read_to_string(tmp.path().join("notes/a b.txt")).unwrap();
+        assert_eq!(saved, "two");
+        assert_eq!(names(&tmp.path().join("notes")), ["a b.txt"]);
+    }
+
+    #[tokio::test]
+    async fn put_refuses_escapes_and_hidden_names() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("site");
+        std::fs::create_dir(&root).unwrap();
+        let roots = [root.clone()];
+
+        for path in ["/../evil.txt", "/%2E%2E/evil.txt", "/missing/evil.txt"] {
+            let put = request(Method::PUT, path, "text/plain", "x");
+            let response = receive(&roots, &uploads(), path, "/", put).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{path}");
+        }
+        for path in ["/.env", "/%2Eenv", "/a%2F..%2F..%2Fevil.txt"] {
+            let put = request(Method::PUT, path, "text/plain", "x");
+            let response = receive(&roots, &uploads(), path, "/", put).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{path}");
+        }
+        assert!(names(&root).is_empty());
+        assert_eq!(names(tmp.path()), ["site"]);
+    }
+
+    #[tokio::test]
+    async fn post_saves_form_files_without_overwriting() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("photo.jpg"), "old").unwrap();
+        let body = "--XX\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"C:\\\\Users\\\\me\\\\photo.jpg\"\r\n\
+            \r\n\
+            new\r\n\
+            --XX\r\n\
+            Content-Disposition: form-data; name=\"note\"\r\n\
+            \r\n\
+            ignored\r\n\
+            --XX--\r\n";
+        let post = request(Method::POST, "/", "multipart/form-data; boundary=XX", body);
+
+        let response = receive(
+            &[tmp.path().to_path_buf()],
+            &uploads(),
+            "/",
+            "/files/",
+            post,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[header::LOCATION], "/files/");
+        assert_eq!(names(tmp.path()), ["photo (1).jpg", "photo.jpg"]);
+        let saved = std::fs::read_to_string(tmp.path().join("photo (1).jpg")).unwrap();
+        assert_eq!(saved, "new");
+    }
+
+    #[tokio::test]
+    async fn make_directory_needs_a_parent_and_a_free_name() {
+        let tmp = tempfile::tempdir().unwrap();
+        let roots = [tmp.path().to_path_buf()];
+
+        let response = make_directory(&roots, &uploads(), "/new%20dir/").await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(tmp.path().join("new dir").is_dir());
+
+        let response = make_directory(&roots, &uploads(), "/new%20dir").await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        let response = make_directory(&roots, &uploads(), "/missing/child").await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = make_directory(&roots, &uploads(), "/.git").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn oversized_uploads_are_refused() {
+        let tmp = tempfile::tempdir().unwrap();
+        let put = Request::builder()
+            .method(Method::PUT)
+            .uri("/big.bin")
+            .header(header::CONTENT_LENGTH, (MAX_UPLOAD_SIZE + 1).to_string())
+            .body(Body::empty())
+            .unwrap();
+        let response = receive(
+            &[tmp.path().to_path_buf()],
+            &uploads(),
+            "/big.bin",
+            "/",
+            put,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let mut budget = 2;
+        let mut incoming = Incoming::create(tmp.path()).await.unwrap();
+        assert!(
+            incoming
+                .write(&Bytes::from("abc"), &mut budget)
+                .await
+                .is_err()
+        );
+        incoming.abandon(Failed::TooLarge).await;
+        assert!(names(tmp.path()).is_empty());
+    }
+}
@@ -0,0 +1,399 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use http_body_util::BodyExt;
+use tokio::task;
+
+use super::directory::{DirEntry, read_directory};
+use super::path_utils::percent_decode;
+use super::{find_in_roots, listed_dirs, upload};
+use crate::daemon::theme::{encode_path_segment, html_escape};
+use crate::domain::RouteOptions;
+
+/// Whether `handle` answers this request rather than the usual static
+/// file serving, which takes care of `GET`, `PUT` and the rest.
+///
+/// Without uploads the route is read-only: `OPTIONS` and `PROPFIND`.
+/// With them, `MKCOL` creates directories, and `LOCK` hands out locks
+/// that aren't enforced, since Finder only mounts writable shares that
+/// support locking.
+pub(super) fn handles(request: &Request, options: &RouteOptions, uri_path: &str) -> bool {
+    match request.method().as_str() {
+        "OPTIONS" | "PROPFIND" => true,
+        "MKCOL" | "LOCK" | "UNLOCK" => options.uploads,
+        "PUT" => options.uploads && !options.dotfiles && is_finder_metadata(uri_path),
+        _ => false,
+    }
+}
+
+/// Answer a WebDAV request. `uri_path` is the request path below the
+/// route; `location` is the full one, which hrefs in responses build on.
+pub(super) async fn handle(
+    roots: &[PathBuf],
+    options: &RouteOptions,
+    uri_path: &str,
+    location: &str,
+    request: Request,
+) -> Response {
+    match request.method().as_str() {
+        "OPTIONS" => describe(options.uploads),
+        "PROPFIND" => {
+            let depth_zero = request
+                .headers()
+                .get("depth")
+                .is_some_and(|depth| depth == "0");
+            propfind(roots, options, uri_path, location, depth_zero).await
+        }
+        "MKCOL" => upload::make_directory(roots, options, uri_path).await,
+        "LOCK" => lock(location),
+        "UNLOCK" => StatusCode::NO_CONTENT.into_response(),
+        "PUT" => discard(request).await,
+        _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+    }
+}
+
+fn describe(writable: bool) -> Response {
+    let (dav, allow) = if writable {
+        (
+            "1, 2",
+            "OPTIONS, GET, HEAD, PROPFIND, PUT, POST, MKCOL, LOCK, UNLOCK",
+        )
+    } else {
+        ("1", "OPTIONS, GET, HEAD, PROPFIND")
+    };
+    (
+        StatusCode::OK,
+        [
+            (header::HeaderName::from_static("dav"), dav),
+            (header::ALLOW, allow),
+            // Lets Windows clients know this is WebDAV
+            (header::HeaderName::from_static("ms-author-via"), "DAV"),
+        ],
+    )
+        .into_response()
+}
+
+/// A file or directory in a `PROPFIND` answer.
+struct Resource {
+    href: String,
+    entry: DirEntry,
+}
+
+async fn propfind(
+    roots: &[PathBuf],
+    options: &RouteOptions,
+    uri_path: &str,
+    location: &str,
+    depth_zero: bool,
+) -> Response {
+    let roots = roots.to_vec();
+    let options = options.clone();
+    let uri_path = uri_path.to_string();
+    let location = location.to_string();
+    let resources =
+        task::spawn_blocking(move || collect(roots, &options, &uri_path, &location, depth_zero))
+            .await
+            .ok()
+            .flatten();
+
+    match resources {
+        Some(resources) => Response::builder()
+            .status(StatusCode::MULTI_STATUS)
+            .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(Body::from(multistatus(&resources)))
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// The resource at `uri_path` and, unless `depth_zero`, what a directory
+/// holds. Merged roots are merged here too. Touches the filesystem.
+fn collect(
+    roots: Vec<PathBuf>,
+    options: &RouteOptions,
+    uri_path: &str,
+    location: &str,
+    depth_zero: bool,
+) -> Option<Vec<Resource>> {
+    let found = find_in_roots(roots, uri_path, options);
+    let first = found.first()?;
+    let metadata = fs::metadata(&first.resolved).ok()?;
+    let name = percent_decode(uri_path.trim_end_matches('/'))
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let href = if first.is_dir && !location.ends_with('/') {
+        format!("{location}/")
+    } else {
+        location.to_string()
+    };
+
+    let mut resources = vec![Resource {
+        href: href.clone(),
+        entry: DirEntry {
+            name,
+            is_dir: first.is_dir,
+            size: if first.is_dir { 0 } else { metadata.len() },
+            modified: seconds_since_epoch(metadata.modified().ok()),
+        },
+    }];
+    if !first.is_dir || depth_zero {
+        return Some(resources);
+    }
+
+    let mut seen = HashSet::new();
+    for (dir, hidden) in listed_dirs(&found) {
+        for entry in read_directory(&dir, &hidden).unwrap_or_default() {
+            if !seen.insert(entry.name.clone()) {
+                continue;
+            }
+            let trailing = if entry.is_dir { "/" } else { "" };
+            resources.push(Resource {
+                href: format!("{href}{}{trailing}", encode_path_segment(&entry.name)),
+                entry,
+            });
+        }
+    }
+    Some(resources)
+}
+
+fn seconds_since_epoch(time: Option<SystemTime>) -> u64 {
+    time.and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_secs())
+}
+
+fn multistatus(resources: &[Resource]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    for Resource { href, entry } in resources {
+        xml.push_str("<D:response><D:href>");
+        xml.push_str(&html_escape(href));
+        xml.push_str("</D:href><D:propstat><D:prop><D:displayname>");
+        xml.push_str(&html_escape(&entry.name));
+        xml.push_str("</D:displayname>");
+        if entry.is_dir {
+            xml.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+        } else {
+            xml.push_str("<D:resourcetype/><D:getcontentlength>");
+            xml.push_str(&entry.size.to_string());
+            xml.push_str("</D:getcontentlength>");
+        }
+        xml.push_str("<D:getlastmodified>");
+        xml.push_str(&httpdate::fmt_http_date(
+            UNIX_EPOCH + Duration::from_secs(entry.modified),
+        ));
+        xml.push_str(
+            "</D:getlastmodified></D:prop><D:status>HTTP/1.1 200 OK</D:status>\
+             </D:propstat></D:response>\n",
+        );
+    }
+    xml.push_str("</D:multistatus>\n");
+    xml
+}
+
+fn lock(location: &str) -> Response {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let token = format!(
+        "opaquelocktoken:roxy-{:x}-{:x}",
+        seconds_since_epoch(Some(SystemTime::now())),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    );
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <D:prop xmlns:D=\"DAV:\"><D:lockdiscovery><D:activelock>\
+         <D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope>\
+         <D:depth>0</D:depth><D:timeout>Second-3600</D:timeout>\
+         <D:locktoken><D:href>{token}</D:href></D:locktoken>\
+         <D:lockroot><D:href>{}</D:href></D:lockroot>\
+         </D:activelock></D:lockdiscovery></D:prop>\n",
+        html_escape(location)
+    );
+
+    let mut response = (
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        body,
+    )
+        .into_response();
+    if let Ok(token) = HeaderValue::from_str(&format!("<{token}>")) {
+        response
+            .headers_mut()
+            .insert(header::HeaderName::from_static("lock-token"), token);
+    }
+    response
+}
+
+/// Files Finder writes next to everything it copies, `._name` with
+/// extended attributes and `.DS_Store`.
+fn is_finder_metadata(uri_path: &str) -> bool {
+    let decoded = percent_decode(uri_path);
+    let name = decoded.rsplit('/').next().unwrap_or_default();
+    name.starts_with("._") || name == ".DS_Store"
+}
+
+/// Accept and drop a Finder metadata file. Refusing it would fail the
+/// whole copy, and storing it would break the route's dotfile hiding.
+async fn discard(request: Request) -> Response {
+    let mut body = request.into_body();
+    while let Some(frame) = body.frame().await {
+        if frame.is_err() {
+            break;
+        }
+    }
+    StatusCode::CREATED.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Method;
+
+    fn request(method: &str, path: &str, depth: Option<&str>) -> Request {
+        let mut builder = Request::builder()
+            .method(Method::from_bytes(method.as_bytes()).unwrap())
+            .uri(path);
+        if let Some(depth) = depth {
+            builder = builder.header("depth", depth);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    async fn dav(
+        root: &std::path::Path,
+        options: &RouteOptions,
+        method: &str,
+        path: &str,
+        depth: Option<&str>,
+    ) -> Option<Response> {
+        let request = request(method, path, depth);
+        if !handles(&request, options, path) {
+            return None;
+        }
+        let location = format!("/files{path}");
+        Some(handle(&[root.to_path_buf()], options, path, &location, request).await)
+    }
+
+    async fn text(response: Response) -> String {
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn propfind_lists_a_directory() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("a & b.txt"), "hello").unwrap();
+        fs::create_dir(tmp.path().join("docs")).unwrap();
+        fs::write(tmp.path().join(".env"), "SECRET=1").unwrap();
+        let options = RouteOptions::default();
+
+        let response = dav(tmp.path(), &options, "PROPFIND", "/", Some("1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+        let xml = text(response).await;
+        assert_eq!(xml.matches("<D:response>").count(), 3);
+        assert!(xml.contains("<D:href>/files/</D:href>"));
+        assert!(xml.contains("<D:href>/files/a%20%26%20b.txt</D:href>"));
+        assert!(xml.contains("<D:displayname>a &amp; b.txt</D:displayname>"));
+        assert!(xml.contains("<D:getcontentlength>5</D:getcontentlength>"));
+        assert!(xml.contains(
+            "<D:href>/files/docs/</D:href><D:propstat><D:prop><D:displayname>docs</D:displayname>\
+             <D:resourcetype><D:collection/></D:resourcetype>"
+        ));
+        assert!(!xml.contains(".env"));
+
+        let response = dav(tmp.path(), &options, "PROPFIND", "/docs", Some("0"))
+            .await
+            .unwrap();
+        let xml = text(response).await;
+        assert_eq!(xml.matches("<D:response>").count(), 1);
+        assert!(xml.contains("<D:href>/files/docs/</D:href>"));
+
+        let response = dav(tmp.path(), &options, "PROPFIND", "/.env", None)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn read_only_without_uploads() {
+        let tmp = tempfile::tempdir().unwrap();
+        let options = RouteOptions::default();
+
+        let response = dav(tmp.path(), &options, "OPTIONS", "/", None)
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["dav"], "1");
+        assert!(
+            !response.headers()[header::ALLOW]
+                .to_str()
+                .unwrap()
+                .contains("PUT")
+        );
+        assert!(
+            dav(tmp.path(), &options, "MKCOL", "/new", None)
+                .await
+                .is_none()
+        );
+        assert!(
+            dav(tmp.path(), &options, "LOCK", "/a.txt", None)
+                .await
+                .is_none()
+        );
+        assert!(dav(tmp.path(), &options, "GET", "/", None).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn writable_with_uploads() {
+        let tmp = tempfile::tempdir().unwrap();
+        let options = RouteOptions {
+            uploads: true,
+            ..RouteOptions::default()
+        };
+
+        let response = dav(tmp.path(), &options, "OPTIONS", "/", None)
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["dav"], "1, 2");
+
+        let response = dav(tmp.path(), &options, "MKCOL", "/new", None)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(tmp.path().join("new").is_dir());
+
+        let response = dav(tmp.path(), &options, "LOCK", "/new/a.txt", None)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let token = response.headers()["lock-token"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(
+            text(response)
+                .await
+                .contains(token.trim_matches(['<', '>']))
+        );
+
+        // Finder's metadata is accepted but never written
+        let response = dav(tmp.path(), &options, "PUT", "/new/._a.txt", None)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(!tmp.path().join("new/._a.txt").exists());
+        assert!(
+            dav(tmp.path(), &options, "PUT", "/new/a.txt", None)
+                .await
+                .is_none()
+        );
+    }
+}
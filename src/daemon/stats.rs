@@ -0,0 +1,66 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use axum::http::StatusCode;
+
+use crate::infrastructure::control::RequestCounts;
+
+/// Per-domain request counters. They live outside the routing state so
+/// a reload doesn't reset them.
+#[derive(Default)]
+pub struct RequestStats {
+    domains: Mutex<BTreeMap<String, (u64, u64)>>,
+}
+
+impl RequestStats {
+    pub fn record(&self, domain: &str, status: StatusCode) {
+        let mut domains = self.domains.lock().unwrap_or_else(|e| e.into_inner());
+        let (requests, errors) = domains.entry(domain.to_string()).or_default();
+        *requests += 1;
+        if status.is_server_error() {
+            *errors += 1;
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<RequestCounts> {
+        let domains = self.domains.lock().unwrap_or_else(|e| e.into_inner());
+        domains
+            .iter()
+            .map(|(domain, &(requests, errors))| RequestCounts {
+                domain: domain.clone(),
+                requests,
+                errors,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_requests_and_server_errors() {
+        let stats = RequestStats::default();
+        stats.record("myapp.roxy", StatusCode::OK);
+        stats.record("myapp.roxy", StatusCode::BAD_GATEWAY);
+        stats.record("myapp.roxy", StatusCode::NOT_FOUND);
+        stats.record("api.roxy", StatusCode::OK);
+
+        assert_eq!(
+            stats.snapshot(),
+            vec![
+                RequestCounts {
+                    domain: "api.roxy".into(),
+                    requests: 1,
+                    errors: 0,
+                },
+                RequestCounts {
+                    domain: "myapp.roxy".into(),
+                    requests: 3,
+                    errors: 1,
+                },
+            ]
+        );
+    }
+}
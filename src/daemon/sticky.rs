@@ -0,0 +1,66 @@
+use axum::http::{HeaderMap, HeaderValue, header};
+
+use crate::domain::{PathPrefix, ProxyTarget};
+
+const COOKIE: &str = "roxy_backend";
+
+/// The target a browser was pinned to, if it is still one of `targets`.
+pub fn pinned<'a>(headers: &HeaderMap, targets: &'a [ProxyTarget]) -> Option<&'a ProxyTarget> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .filter(|(name, _)| *name == COOKIE)
+        .find_map(|(_, value)| targets.iter().find(|t| t.to_string() == value))
+}
+
+/// `Set-Cookie` value pinning the browser to `target` for the route at
+/// `path`.
+pub fn pin(path: &PathPrefix, target: &ProxyTarget) -> HeaderValue {
+    let cookie = format!(
+        "{}={}; Path={}; HttpOnly; SameSite=Lax",
+        COOKIE, target, path
+    );
+    HeaderValue::from_str(&cookie).expect("targets and paths are valid in a cookie")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets() -> Vec<ProxyTarget> {
+        ["3000", "3001"]
+            .iter()
+            .map(|t| ProxyTarget::parse(t).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_pinned_target() {
+        let targets = targets();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_static("theme=dark; roxy_backend=127.0.0.1:3001"),
+        );
+        assert_eq!(pinned(&headers, &targets), Some(&targets[1]));
+
+        // A target that was since removed from the route doesn't count
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_static("roxy_backend=127.0.0.1:4000"),
+        );
+        assert_eq!(pinned(&headers, &targets), None);
+    }
+
+    #[test]
+    fn pin_cookie_is_scoped_to_the_route() {
+        let cookie = pin(&PathPrefix::new("/api").unwrap(), &targets()[0]);
+        assert_eq!(
+            cookie,
+            "roxy_backend=127.0.0.1:3000; Path=/api; HttpOnly; SameSite=Lax"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Long-lived streaming responses such as Server-Sent Events.
+//!
+//! Event streams pass through frame by frame, skip the request timeout
+//! (some servers only send headers with the first event) and are closed
+//! once they go quiet for longer than the stream idle timeout.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::http::{HeaderMap, header};
+use hyper::body::{Body as HttpBody, Frame};
+use tokio::time::{Instant, Sleep};
+use tracing::debug;
+
+const EVENT_STREAM: &str = "text/event-stream";
+
+/// Whether the client is asking for an event stream.
+pub fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| v.contains(EVENT_STREAM))
+}
+
+/// Whether a response is an event stream.
+pub fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case(EVENT_STREAM))
+}
+
+/// A body that ends early once no frame has arrived for `idle`.
+pub struct IdleTimeout<B> {
+    inner: B,
+    idle: Duration,
+    sleep: Pin<Box<Sleep>>,
+    done: bool,
+}
+
+impl<B> IdleTimeout<B> {
+    pub fn new(inner: B, idle: Duration) -> Self {
+        Self {
+            inner,
+            idle,
+            sleep: Box::pin(tokio::time::sleep(idle)),
+            done: false,
+        }
+    }
+}
+
+impl<B> HttpBody for IdleTimeout<B>
+where
+    B: HttpBody<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        match Pin::new(&mut self.inner).poll_frame(cx) {
+            Poll::Ready(frame) => {
+                let deadline = Instant::now() + self.idle;
+                self.sleep.as_mut().reset(deadline);
+                Poll::Ready(frame)
+            }
+            Poll::Pending => {
+                if self.sleep.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                debug!(idle = ?self.idle, "Closing idle stream");
+                self.done = true;
+                Poll::Ready(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use http_body_util::{BodyExt, Full};
+    use std::convert::Infallible;
+
+    /// A stream whose backend never sends another event.
+    struct Silent;
+
+    impl HttpBody for Silent {
+        type Data = Bytes;
+        type Error = Infallible;
+
+        fn poll_frame(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn detects_event_streams() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/event-stream; charset=utf-8"),
+        );
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("text/event-stream"),
+        );
+        assert!(is_event_stream(&headers));
+        assert!(accepts_event_stream(&headers));
+
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        headers.insert(header::ACCEPT, HeaderValue::from_static("*/*"));
+        assert!(!is_event_stream(&headers));
+        assert!(!accepts_event_stream(&headers));
+    }
+
+    #[tokio::test]
+    async fn ends_a_quiet_stream() {
+        let body = IdleTimeout::new(Silent, Duration::from_millis(50));
+        let started = Instant::now();
+        let collected = body.collect().await.unwrap().to_bytes();
+        assert!(collected.is_empty());
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn passes_events_through() {
+        let body = IdleTimeout::new(
+            Full::new(Bytes::from("data: hi\n\n")),
+            Duration::from_secs(30),
+        );
+        let collected = body.collect().await.unwrap().to_bytes();
+        assert_eq!(collected, "data: hi\n\n");
+    }
+}
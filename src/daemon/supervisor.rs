@@ -0,0 +1,833 @@
+//! Runs the backends of routes with a `cmd`. A backend is started the
+//! first time a request finds nothing listening on its target, restarted
+//! when it crashes, and its output goes to a log file per domain.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use axum::response::Response;
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use super::router::build_start_failed_response;
+use crate::domain::{DomainPattern, DomainRegistration, ProxyTarget, Route};
+use crate::infrastructure::certs::ca::RootCA;
+use crate::infrastructure::control::ProcessStatus;
+use crate::infrastructure::paths::RoxyPaths;
+#[cfg(unix)]
+use crate::infrastructure::service_user::current_uid;
+use crate::infrastructure::service_user::{ServiceUser, owns};
+
+/// How long a backend may take to start listening.
+const START_TIMEOUT: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A backend that exits sooner than this after starting has crashed
+/// rather than run its course.
+const STABLE_UPTIME: Duration = Duration::from_secs(10);
+/// How long a stopped backend gets to exit before it's killed.
+const STOP_GRACE: Duration = Duration::from_secs(5);
+/// Crashes in a row before the supervisor stops restarting a backend.
+const MAX_CRASHES: u32 = 5;
+/// Wait before the first restart, doubled for each crash in a row.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Backend logs are started over once they grow past this.
+const MAX_LOG_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Backends the daemon started, by target address. Survives reloads.
+#[derive(Default)]
+pub struct Supervisor {
+    /// Where backend output is logged; discarded when `None`
+    paths: Option<RoxyPaths>,
+    processes: Mutex<HashMap<String, Arc<Process>>>,
+}
+
+impl Supervisor {
+    /// A supervisor logging backend output next to the daemon's log.
+    pub fn new(paths: RoxyPaths) -> Self {
+        Self {
+            paths: Some(paths),
+            processes: Mutex::default(),
+        }
+    }
+
+    /// Make sure the route's backend is listening, starting it with the
+    /// route's `cmd` if it isn't. Routes without a command pass through.
+    pub async fn ensure_running(
+        &self,
+        route: &Route,
+        registration: &DomainRegistration,
+    ) -> Result<(), Response> {
+        let Some(cmd) = &route.options.cmd else {
+            return Ok(());
+        };
+        let Some(target) = route.target.proxy_targets().first() else {
+            return Ok(());
+        };
+        if is_listening(target).await {
+            return Ok(());
+        }
+
+        let process = self.process(target, registration.pattern());
+        process.set_spec(Spec {
+            cmd: cmd.clone(),
+            cwd: route.options.cwd.clone(),
+            owner: registration.owner().map(str::to_string),
+        });
+        let failed = |reason: String| {
+            warn!(target = %target, cmd = %cmd, reason = %reason, "Backend failed to start");
+            build_start_failed_response(target, cmd, &reason)
+        };
+
+        // One launch per backend; requests that arrive meanwhile wait here
+        {
+            let _starting = process.starting.lock().await;
+            if !process.is_supervised() {
+                process
+                    .start()
+                    .map_err(|e| failed(format!("could not run the command: {}", e)))?;
+            }
+        }
+
+        let deadline = Instant::now() + START_TIMEOUT;
+        loop {
+            if is_listening(target).await {
+                info!(target = %target, "Backend started");
+                return Ok(());
+            }
+            if let State::Restarting { reason } | State::Failed { reason } = process.state() {
+                return Err(failed(reason));
+            }
+            if Instant::now() >= deadline {
+                return Err(failed(format!(
+                    "nothing was listening after {}",
+                    humantime::format_duration(START_TIMEOUT)
+                )));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Restart the backends of `domain`, given as its pattern. Returns
+    /// how many there were.
+    pub async fn restart(&self, domain: &str) -> usize {
+        let processes: Vec<Arc<Process>> = self
+            .lock()
+            .values()
+            .filter(|process| process.domain.display_pattern() == domain)
+            .cloned()
+            .collect();
+
+        for process in &processes {
+            let _starting = process.starting.lock().await;
+            if process.is_supervised() {
+                process.restart.notify_one();
+            } else if let Err(e) = process.start() {
+                warn!(target = %process.target, error = %e, "Backend failed to start");
+            }
+        }
+        processes.len()
+    }
+
+    /// Every backend the daemon has started, by domain.
+    pub fn snapshot(&self) -> Vec<ProcessStatus> {
+        let mut statuses: Vec<ProcessStatus> = self
+            .lock()
+            .values()
+            .map(|process| process.status())
+            .collect();
+        statuses.sort_by(|a, b| (&a.domain, &a.target).cmp(&(&b.domain, &b.target)));
+        statuses
+    }
+
+    fn process(&self, target: &ProxyTarget, pattern: &DomainPattern) -> Arc<Process> {
+        let log_file = self.paths.as_ref().map(|paths| paths.backend_log(pattern));
+        let ca_key = self
+            .paths
+            .as_ref()
+            .map(|paths| RootCA::new(paths.data_dir.clone()).key_path());
+        self.lock()
+            .entry(target.to_string())
+            .or_insert_with(|| {
+                Arc::new(Process::new(
+                    pattern.clone(),
+                    target.clone(),
+                    log_file,
+                    ca_key,
+                ))
+            })
+            .clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Process>>> {
+        self.processes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// How to run a backend, refreshed from the route on every request so
+/// restarts pick up config changes.
+#[derive(Debug, Clone, Default)]
+struct Spec {
+    cmd: String,
+    cwd: Option<String>,
+    owner: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum State {
+    /// Not started yet
+    Stopped,
+    Running {
+        pid: Option<u32>,
+        since: Instant,
+    },
+    /// Exited, and about to be started again
+    Restarting {
+        reason: String,
+    },
+    /// Given up on until a request or `roxy restart-backend` starts it
+    Failed {
+        reason: String,
+    },
+}
+
+impl State {
+    fn name(&self) -> &'static str {
+        match self {
+            State::Stopped => "stopped",
+            State::Running { .. } => "running",
+            State::Restarting { .. } => "restarting",
+            State::Failed { .. } => "failed",
+        }
+    }
+}
+
+/// One supervised backend.
+struct Process {
+    domain: DomainPattern,
+    target: ProxyTarget,
+    log_file: Option<PathBuf>,
+    /// The Root CA key, which backends must not be able to read
+    ca_key: Option<PathBuf>,
+    spec: Mutex<Spec>,
+    state: Mutex<State>,
+    restarts: AtomicU32,
+    /// Wakes the supervising task to restart the backend
+    restart: Notify,
+    starting: tokio::sync::Mutex<()>,
+}
+
+impl Process {
+    fn new(
+        domain: DomainPattern,
+        target: ProxyTarget,
+        log_file: Option<PathBuf>,
+        ca_key: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            domain,
+            target,
+            log_file,
+            ca_key,
+            spec: Mutex::default(),
+            state: Mutex::new(State::Stopped),
+            restarts: AtomicU32::new(0),
+            restart: Notify::new(),
+            starting: tokio::sync::Mutex::default(),
+        }
+    }
+
+    fn spec(&self) -> Spec {
+        self.spec.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn set_spec(&self, spec: Spec) {
+        *self.spec.lock().unwrap_or_else(|e| e.into_inner()) = spec;
+    }
+
+    fn state(&self) -> State {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn set_state(&self, state: State) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = state;
+    }
+
+    /// Whether a task is watching the backend.
+    fn is_supervised(&self) -> bool {
+        matches!(
+            self.state(),
+            State::Running { .. } | State::Restarting { .. }
+        )
+    }
+
+    fn status(&self) -> ProcessStatus {
+        let state = self.state();
+        let (pid, uptime_secs) = match &state {
+            State::Running { pid, since } => (*pid, since.elapsed().as_secs()),
+            _ => (None, 0),
+        };
+        ProcessStatus {
+            domain: self.domain.display_pattern(),
+            target: self.target.to_string(),
+            state: state.name().to_string(),
+            pid,
+            uptime_secs,
+            restarts: self.restarts.load(Ordering::Relaxed),
+            cmd: self.spec().cmd,
+        }
+    }
+
+    /// Run the backend and hand it to a supervising task.
+    fn start(self: &Arc<Self>) -> io::Result<()> {
+        let child = self.spawn()?;
+        tokio::spawn(self.clone().supervise(child));
+        Ok(())
+    }
+
+    fn spawn(&self) -> io::Result<Backend> {
+        let spec = self.spec();
+        let log = self.open_log();
+        info!(target = %self.target, cmd = %spec.cmd, "Starting backend");
+        let holds_ca_key = self.ca_key.as_deref().is_some_and(owns);
+        match spawn(&spec, holds_ca_key, log.as_ref()) {
+            Ok(child) => {
+                let pid = child.id();
+                self.note(
+                    log,
+                    &format!(
+                        "Started `{}` (pid {})",
+                        spec.cmd,
+                        pid.map_or_else(|| "unknown".to_string(), |pid| pid.to_string())
+                    ),
+                );
+                self.set_state(State::Running {
+                    pid,
+                    since: Instant::now(),
+                });
+                Ok(Backend::new(child))
+            }
+            Err(e) => {
+                self.note(log, &format!("Failed to start `{}`: {}", spec.cmd, e));
+                self.set_state(State::Failed {
+                    reason: format!("could not run the command: {}", e),
+                });
+                Err(e)
+            }
+        }
+    }
+
+    /// Restart the backend whenever it exits or a restart is asked for,
+    /// backing off while it keeps crashing.
+    async fn supervise(self: Arc<Self>, mut backend: Backend) {
+        let mut crashes = 0;
+        loop {
+            let started = Instant::now();
+            tokio::select! {
+                status = backend.wait() => {
+                    // Whatever the shell started may still hold the port
+                    backend.stop().await;
+                    let reason = match status {
+                        Ok(status) => format!("the command exited ({})", status),
+                        Err(e) => format!("the command could not be watched: {}", e),
+                    };
+                    self.note(self.open_log(), &reason);
+                    if started.elapsed() >= STABLE_UPTIME {
+                        crashes = 0;
+                    }
+                    crashes += 1;
+                    if crashes >= MAX_CRASHES {
+                        warn!(target = %self.target, reason = %reason, crashes, "Backend keeps crashing, giving up");
+                        self.set_state(State::Failed {
+                            reason: format!("{}, and kept crashing", reason),
+                        });
+                        return;
+                    }
+
+                    let delay = RESTART_DELAY * 2u32.pow(crashes - 1);
+                    warn!(target = %self.target, reason = %reason, delay = ?delay, "Backend exited, restarting");
+                    self.set_state(State::Restarting { reason });
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = self.restart.notified() => crashes = 0,
+                    }
+                }
+                _ = self.restart.notified() => {
+                    info!(target = %self.target, "Restarting backend");
+                    self.note(self.open_log(), "Restarting on request");
+                    backend.stop().await;
+                    crashes = 0;
+                }
+            }
+
+            self.restarts.fetch_add(1, Ordering::Relaxed);
+            backend = match self.spawn() {
+                Ok(backend) => backend,
+                Err(e) => {
+                    warn!(target = %self.target, error = %e, "Backend failed to start");
+                    return;
+                }
+            };
+        }
+    }
+
+    /// Open the domain's backend log for appending, starting it over
+    /// once it gets too big.
+    fn open_log(&self) -> Option<File> {
+        let path = self.log_file.as_ref()?;
+        let opened = open_log(path);
+        if let Err(e) = &opened {
+            warn!(path = %path.display(), error = %e, "Backend output will not be logged");
+        }
+        opened.ok()
+    }
+
+    /// Record what the supervisor did in the backend log, between the
+    /// backend's own output.
+    fn note(&self, log: Option<File>, message: &str) {
+        if let Some(mut log) = log {
+            let now = humantime::format_rfc3339_seconds(SystemTime::now());
+            let _ = writeln!(log, "[roxy {}] {}: {}", now, self.target, message);
+        }
+    }
+}
+
+fn open_log(path: &Path) -> io::Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let too_big = fs::metadata(path).is_ok_and(|meta| meta.len() > MAX_LOG_SIZE);
+    let mut options = OpenOptions::new();
+    options.create(true);
+    if too_big {
+        options.write(true).truncate(true);
+    } else {
+        options.append(true);
+    }
+    options.open(path)
+}
+
+async fn is_listening(target: &ProxyTarget) -> bool {
+    TcpStream::connect(target.to_string()).await.is_ok()
+}
+
+/// A running backend: the shell and everything it started, which share
+/// a process group so they're stopped together. A dev server the shell
+/// handed off to would otherwise outlive it and keep the port.
+struct Backend {
+    child: Child,
+    #[cfg_attr(not(unix), allow(dead_code))] // Process groups are Unix only
+    pid: Option<u32>,
+    stopped: bool,
+}
+
+impl Backend {
+    fn new(child: Child) -> Self {
+        let pid = child.id();
+        Self {
+            child,
+            pid,
+            stopped: false,
+        }
+    }
+
+    /// Wait for the shell to exit.
+    async fn wait(&mut self) -> io::Result<std::process::ExitStatus> {
+        self.child.wait().await
+    }
+
+    /// Ask the whole group to exit, and kill what's left after
+    /// [`STOP_GRACE`].
+    #[cfg(unix)]
+    async fn stop(&mut self) {
+        if self.stopped {
+            return;
+        }
+        self.signal(libc::SIGTERM);
+        let deadline = Instant::now() + STOP_GRACE;
+        let _ = tokio::time::timeout_at(deadline, self.child.wait()).await;
+        while self.signal(0) && Instant::now() < deadline {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        self.signal(libc::SIGKILL);
+        let _ = self.child.wait().await;
+        self.stopped = true;
+    }
+
+    #[cfg(not(unix))]
+    async fn stop(&mut self) {
+        if let Err(e) = self.child.kill().await {
+            warn!(error = %e, "Failed to stop backend");
+        }
+        self.stopped = true;
+    }
+
+    /// Send `signal` to the backend's process group. Returns whether any
+    /// process was left to get it.
+    #[cfg(unix)]
+    fn signal(&self, signal: libc::c_int) -> bool {
+        let Some(pgid) = self.pid.and_then(|pid| libc::pid_t::try_from(pid).ok()) else {
+            return false;
+        };
+        // SAFETY: kill has no memory-safety preconditions.
+        unsafe { libc::kill(-pgid, signal) == 0 }
+    }
+}
+
+impl Drop for Backend {
+    /// The daemon is going away; take the whole group with it.
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if !self.stopped {
+            self.signal(libc::SIGKILL);
+        }
+    }
+}
+
+/// Run the command through the shell, with its output going to `log`.
+/// `holds_ca_key` tells whether the daemon's user owns the Root CA key.
+fn spawn(spec: &Spec, holds_ca_key: bool, log: Option<&File>) -> io::Result<Child> {
+    #[cfg(unix)]
+    let user = run_as(spec.owner.as_deref(), current_uid(), holds_ca_key)?;
+    #[cfg(not(unix))]
+    let user: Option<ServiceUser> = {
+        let _ = holds_ca_key;
+        None
+    };
+    let home = user
+        .as_ref()
+        .map(|user| user.home().to_path_buf())
+        .or_else(|| std::env::var_os("HOME").map(PathBuf::from));
+    let output = || match log {
+        Some(log) => log.try_clone().map(Stdio::from),
+        None => Ok(Stdio::null()),
+    };
+
+    let mut command = shell(&spec.cmd);
+    command
+        .stdin(Stdio::null())
+        .stdout(output()?)
+        .stderr(output()?)
+        .kill_on_drop(true);
+    #[cfg(unix)]
+    command.process_group(0);
+    if let Some(cwd) = &spec.cwd {
+        command.current_dir(expand_home(cwd, home.as_deref()));
+    }
+    #[cfg(unix)]
+    if let Some(user) = &user {
+        command
+            .uid(user.uid())
+            .gid(user.gid())
+            .env("HOME", user.home())
+            .env("USER", user.name())
+            .env("LOGNAME", user.name());
+    }
+    // Don't leave dev servers behind when the daemon goes away
+    #[cfg(target_os = "linux")]
+    // SAFETY: prctl is async-signal-safe and touches no memory.
+    unsafe {
+        command.pre_exec(|| {
+            libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM);
+            Ok(())
+        });
+    }
+    command.spawn()
+}
+
+#[cfg(unix)]
+fn shell(cmd: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(cmd);
+    command
+}
+
+#[cfg(windows)]
+fn shell(cmd: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(cmd);
+    command
+}
+
+/// The account a backend of a domain owned by `owner` runs as, for a
+/// daemon running as `uid`; `None` for the daemon's own. Root runs it as
+/// the owner, and not at all for a domain nobody owns. Any other daemon
+/// runs it as itself, unless it holds the Root CA key and the domain
+/// isn't its user's own: the command could then read the key and issue
+/// certificates every browser here trusts.
+#[cfg(unix)]
+fn run_as(owner: Option<&str>, uid: u32, holds_ca_key: bool) -> io::Result<Option<ServiceUser>> {
+    let lookup = |owner| ServiceUser::lookup(owner).map_err(|e| io::Error::other(e.to_string()));
+    if uid == 0 {
+        let Some(owner) = owner else {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the domain has no owner to run it as, and it won't be run as root; \
+                 register the domain as the user it should run as",
+            ));
+        };
+        return lookup(owner).map(Some);
+    }
+
+    let own_domain = owner.is_some_and(|owner| lookup(owner).is_ok_and(|user| user.uid() == uid));
+    if holds_ca_key && !own_domain {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the daemon runs as a service user that holds the Root CA key, which the \
+             command could read; start the backend yourself, or let the daemon run as \
+             root (remove daemon.user) so it runs commands as the domain's owner",
+        ));
+    }
+    Ok(None)
+}
+
+fn expand_home(path: &str, home: Option<&Path>) -> PathBuf {
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            home.join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::domain::PathPrefix;
+    use crate::domain::RouteTarget;
+    use crate::infrastructure::service_user::is_root;
+    use tokio::net::TcpListener;
+
+    fn route(port: u16, cmd: &str) -> Route {
+        let mut route = Route::new(
+            PathPrefix::new("/").unwrap(),
+            RouteTarget::Proxy(ProxyTarget::parse(&port.to_string()).unwrap()),
+        );
+        route.options.cmd = Some(cmd.to_string());
+        route
+    }
+
+    fn registration(route: &Route) -> DomainRegistration {
+        let mut registration = DomainRegistration::new(
+            DomainPattern::from_name("myapp.roxy", false).unwrap(),
+            vec![route.clone()],
+        );
+        // Only looked at when the tests run as root, which won't run the
+        // commands of unowned domains
+        registration.set_owner(Some("root".to_string()));
+        registration
+    }
+
+    async fn free_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    /// Stands in for a dev server that takes a moment to come up
+    fn backend(port: u16, after: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            tokio::time::sleep(after).await;
+            let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+            loop {
+                let _ = listener.accept().await;
+            }
+        })
+    }
+
+    #[test]
+    fn expands_the_home_directory() {
+        let home = Path::new("/home/alice");
+        assert_eq!(
+            expand_home("~/code/app", Some(home)),
+            PathBuf::from("/home/alice/code/app")
+        );
+        assert_eq!(expand_home("~", Some(home)), PathBuf::from("/home/alice"));
+        assert_eq!(
+            expand_home("~bob/app", Some(home)),
+            PathBuf::from("~bob/app")
+        );
+        assert_eq!(
+            expand_home("/srv/app", Some(home)),
+            PathBuf::from("/srv/app")
+        );
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_backend_to_listen() {
+        let port = free_port().await;
+        let backend = backend(port, Duration::from_millis(300));
+
+        let supervisor = Supervisor::default();
+        let route = route(port, "sleep 5");
+        let started = Instant::now();
+        let result = supervisor
+            .ensure_running(&route, &registration(&route))
+            .await;
+        assert!(result.is_ok());
+        assert!(started.elapsed() >= Duration::from_millis(300));
+
+        let statuses = supervisor.snapshot();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].domain, "myapp.roxy");
+        assert_eq!(statuses[0].state, "running");
+        assert_eq!(statuses[0].cmd, "sleep 5");
+        backend.abort();
+    }
+
+    #[tokio::test]
+    async fn root_does_not_run_commands_of_unowned_domains() {
+        if !is_root() {
+            return;
+        }
+        let spec = Spec {
+            cmd: "true".to_string(),
+            ..Spec::default()
+        };
+        let err = spawn(&spec, false, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_service_user_holding_the_ca_key_only_runs_its_own_commands() {
+        let denied = |result: io::Result<Option<ServiceUser>>| {
+            result.unwrap_err().kind() == io::ErrorKind::PermissionDenied
+        };
+        let service_user = ServiceUser::lookup("nobody").unwrap().uid();
+
+        assert!(denied(run_as(Some("root"), service_user, true)));
+        assert!(denied(run_as(None, service_user, true)));
+        // A rootless daemon runs as the user who registered the domain
+        assert!(
+            run_as(Some("nobody"), service_user, true)
+                .unwrap()
+                .is_none()
+        );
+        // Without the key there is nothing to guard
+        assert!(run_as(Some("root"), service_user, false).unwrap().is_none());
+        // Root switches to the owner instead
+        let owner = run_as(Some("nobody"), 0, true).unwrap().unwrap();
+        assert_eq!(owner.uid(), service_user);
+    }
+
+    #[tokio::test]
+    async fn a_daemon_holding_the_ca_key_does_not_start_other_users_backends() {
+        if is_root() {
+            return;
+        }
+        let tmp = tempfile::tempdir().unwrap();
+        let paths = RoxyPaths {
+            data_dir: tmp.path().to_path_buf(),
+            log_file: tmp.path().join("roxy.log"),
+            ..RoxyPaths::default()
+        };
+        fs::write(RootCA::new(paths.data_dir.clone()).key_path(), "key").unwrap();
+
+        let supervisor = Supervisor::new(paths);
+        let route = route(free_port().await, "true");
+        let response = supervisor
+            .ensure_running(&route, &registration(&route))
+            .await
+            .unwrap_err();
+        assert_eq!(response.status(), 502);
+        assert_eq!(supervisor.snapshot()[0].state, "failed");
+    }
+
+    #[tokio::test]
+    async fn reports_a_command_that_exits() {
+        let port = free_port().await;
+        let supervisor = Supervisor::default();
+        let route = route(port, "exit 3");
+        let response = supervisor
+            .ensure_running(&route, &registration(&route))
+            .await
+            .unwrap_err();
+        assert_eq!(response.status(), 502);
+    }
+
+    #[tokio::test]
+    async fn restarts_a_crashed_backend() {
+        let port = free_port().await;
+        let supervisor = Supervisor::default();
+        let route = route(port, "exit 3");
+        let _ = supervisor
+            .ensure_running(&route, &registration(&route))
+            .await;
+
+        tokio::time::sleep(RESTART_DELAY + Duration::from_millis(500)).await;
+        assert!(supervisor.snapshot()[0].restarts >= 1);
+    }
+
+    #[tokio::test]
+    async fn logs_output_and_restarts_on_request() {
+        let tmp = tempfile::tempdir().unwrap();
+        let paths = RoxyPaths {
+            log_file: tmp.path().join("roxy.log"),
+            ..RoxyPaths::default()
+        };
+        let port = free_port().await;
+        let backend = backend(port, Duration::from_millis(300));
+
+        let supervisor = Supervisor::new(paths.clone());
+        let route = route(port, "echo hello from the backend; sleep 5");
+        let registration = registration(&route);
+        supervisor
+            .ensure_running(&route, &registration)
+            .await
+            .unwrap();
+        assert_eq!(supervisor.restart("myapp.roxy").await, 1);
+        assert_eq!(supervisor.restart("other.roxy").await, 0);
+
+        // The old backend gets a moment to exit; orphans in its group may
+        // linger until init reaps them
+        let deadline = Instant::now() + STOP_GRACE + Duration::from_secs(1);
+        while supervisor.snapshot()[0].restarts == 0 && Instant::now() < deadline {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        let status = &supervisor.snapshot()[0];
+        assert_eq!(status.restarts, 1);
+        assert_eq!(status.state, "running");
+
+        let log = fs::read_to_string(paths.backend_log(registration.pattern())).unwrap();
+        let output = log.lines().filter(|line| *line == "hello from the backend");
+        assert_eq!(output.count(), 2);
+        assert!(log.contains("Restarting on request"));
+        backend.abort();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn restarting_stops_what_the_command_started() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pid_file = tmp.path().join("server.pid");
+        let port = free_port().await;
+        let backend = backend(port, Duration::ZERO);
+
+        let supervisor = Supervisor::default();
+        let cmd = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+        let route = route(port, &cmd);
+        supervisor
+            .ensure_running(&route, &registration(&route))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let server = fs::read_to_string(&pid_file).unwrap().trim().to_string();
+
+        assert_eq!(supervisor.restart("myapp.roxy").await, 1);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        // Gone, or a zombie waiting for init to reap it
+        let stat = fs::read_to_string(format!("/proc/{}/stat", server)).unwrap_or_default();
+        assert!(stat.is_empty() || stat.contains(") Z "), "{}", stat);
+        backend.abort();
+    }
+}
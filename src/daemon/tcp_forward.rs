@@ -0,0 +1,116 @@
+//! Raw TCP forwards: ports the daemon listens on for a domain, whose
+//! connections are piped byte for byte to a backend.
+//!
+//! Listeners are opened at startup, before privileges are dropped. A
+//! reload changes where each port goes, but new ports need a restart.
+
+use std::net::SocketAddr;
+
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, error, info, warn};
+
+use super::router::SharedState;
+use crate::domain::ProxyTarget;
+
+/// Open a listener for every forwarded port. A port that can't be bound
+/// is skipped so the rest of the daemon still starts.
+pub async fn bind(ports: &[u16]) -> Vec<TcpListener> {
+    let mut listeners = Vec::new();
+    for &port in ports {
+        let addr = SocketAddr::from(([0, 0, 0, 0], port));
+        match TcpListener::bind(addr).await {
+            Ok(listener) => {
+                info!(addr = %addr, "TCP forward listening");
+                listeners.push(listener);
+            }
+            Err(e) => warn!(
+                port,
+                error = %e,
+                "Failed to bind TCP forward. Is another service using it? Try: sudo lsof -i :{}",
+                port
+            ),
+        }
+    }
+    listeners
+}
+
+/// Accept connections on a forwarded port and pipe each one to the
+/// target currently configured for it.
+pub async fn run(listener: TcpListener, state: SharedState) {
+    let port = match listener.local_addr() {
+        Ok(addr) => addr.port(),
+        Err(e) => {
+            error!(error = %e, "TCP forward listener has no address");
+            return;
+        }
+    };
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!(error = %e, "Failed to accept connection");
+                continue;
+            }
+        };
+        // Removed since startup; the port stays open until a restart
+        let Some(target) = state.current().tcp_forward_target(port) else {
+            debug!(port, "No TCP forward for port, closing connection");
+            continue;
+        };
+        debug!(client = %addr, port, target = %target, "TCP forward");
+        tokio::spawn(async move { pipe(stream, &target).await });
+    }
+}
+
+/// Copy bytes between the client and `target` until either side closes.
+pub async fn pipe(mut client: TcpStream, target: &ProxyTarget) {
+    let mut backend = match TcpStream::connect(target.to_string()).await {
+        Ok(backend) => backend,
+        Err(e) => {
+            warn!(target = %target, error = %e, "Backend connect failed");
+            return;
+        }
+    };
+    match tokio::io::copy_bidirectional(&mut client, &mut backend).await {
+        Ok((sent, received)) => debug!(target = %target, sent, received, "Connection closed"),
+        Err(e) => debug!(target = %target, error = %e, "Connection ended"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::router::AppState;
+    use crate::domain::{DomainPattern, DomainRegistration, Route, TcpForward};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn pipes_bytes_to_the_target() {
+        let backend = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_port = backend.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = backend.accept().await.unwrap();
+            let mut buf = [0; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            stream.write_all(&buf).await.unwrap();
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut registration = DomainRegistration::new(
+            DomainPattern::from_name("db.roxy", false).unwrap(),
+            vec![Route::parse("/=3000").unwrap()],
+        );
+        registration
+            .add_tcp_forward(TcpForward::parse(&format!("{}={}", port, backend_port)).unwrap())
+            .unwrap();
+        let state = SharedState::new(AppState::new(vec![registration], false));
+        tokio::spawn(run(listener, state));
+
+        let mut client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        client.write_all(b"ping").await.unwrap();
+        let mut reply = [0; 4];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"ping");
+    }
+}
@@ -1,23 +1,42 @@
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::sync::RwLock;
+use std::sync::{Mutex, OnceLock, RwLock};
 
 use anyhow::{Context, Result};
-use rcgen::{Issuer, KeyPair, PKCS_ECDSA_P256_SHA256, SanType};
-use rustls::ServerConfig;
+use rcgen::{KeyPair, SanType};
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::ServerCertVerifier;
 use rustls::pki_types::pem::PemObject;
-use rustls::pki_types::{CertificateDer, PrivateKeyDer};
-use rustls::server::ResolvesServerCert;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::{
+    ProducesTickets, ResolvesServerCert, ServerSessionMemoryCache, StoresServerSessions,
+};
 use rustls::sign::CertifiedKey;
-use tokio_rustls::TlsAcceptor;
-use tracing::warn;
-
-use crate::domain::{DomainName, DomainPattern};
-use crate::infrastructure::certs::generator::{build_ca_cert_params, build_leaf_cert_params};
-
-const ON_DEMAND_CERT_CACHE_MAX: usize = 256;
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use time::OffsetDateTime;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use tracing::{debug, info, warn};
+
+use crate::domain::{CustomCert, DomainName, DomainPattern, DomainRegistration};
+use crate::infrastructure::certs::CertsConfig;
+use crate::infrastructure::certs::ca::{RootCA, issuer_from_pem};
+use crate::infrastructure::certs::custom;
+use crate::infrastructure::certs::generator::{
+    Certificate, CertificateGenerator, build_leaf_cert_params, not_after,
+};
+use crate::infrastructure::tls::TlsConfig;
+
+/// TLS sessions remembered for resumption by session ID.
+const SESSION_CACHE_SIZE: usize = 1024;
+
+/// Subdirectory of the certs directory that keeps on-demand certificates
+/// across restarts, apart from the registered domains' ones.
+const ON_DEMAND_DIR: &str = "on-demand";
+
+/// Warn about custom certificates expiring within this many days.
+const CUSTOM_CERT_WARNING_DAYS: i64 = 30;
 
 /// Custom certificate resolver that selects certificates based on SNI hostname.
 ///
@@ -28,16 +47,39 @@ const ON_DEMAND_CERT_CACHE_MAX: usize = 256;
 struct DomainCertResolver {
     /// All registered certificates, stored with their pattern for matching.
     certs: Vec<(DomainPattern, Arc<CertifiedKey>)>,
-    ca_key_pem: Option<String>,
-    on_demand: RwLock<HashMap<String, Arc<CertifiedKey>>>,
+    /// Custom certificates by the names they cover, for hostnames
+    /// outside `.roxy` such as `app.dev.example.com`.
+    named: Vec<(String, Arc<CertifiedKey>)>,
+    /// Domains with HTTPS turned off, refused rather than given an
+    /// on-demand certificate.
+    no_https: Vec<DomainPattern>,
+    ca: Option<CaPem>,
+    /// Also issue on-demand certificates for `<name>.localhost`.
+    localhost: bool,
+    /// Issue on-demand certificates for any hostname. Only used for
+    /// hosts the forward proxy has already decided to intercept.
+    any_host: bool,
+    /// Validity and key type of on-demand certificates.
+    settings: CertsConfig,
+    on_demand: Mutex<OnDemandCache>,
+    /// Where on-demand certificates are saved, if anywhere.
+    store: Option<OnDemandStore>,
 }
 
 impl ResolvesServerCert for DomainCertResolver {
     fn resolve(&self, client_hello: rustls::server::ClientHello) -> Option<Arc<CertifiedKey>> {
-        let hostname = client_hello.server_name()?.to_lowercase();
+        let Some(hostname) = client_hello.server_name() else {
+            return self.without_sni();
+        };
+        let hostname = hostname.to_lowercase();
+
+        if self.https_turned_off(&hostname) {
+            debug!(hostname = %hostname, "TLS: HTTPS is off for domain");
+            return None;
+        }
 
         // Try cached on-demand certs first (for unregistered but valid .roxy domains).
-        if let Some(cert) = self.on_demand.read().ok()?.get(hostname.as_str()).cloned() {
+        if let Some(cert) = self.on_demand.lock().ok()?.get(&hostname) {
             return Some(cert);
         }
 
@@ -48,22 +90,32 @@ impl ResolvesServerCert for DomainCertResolver {
                 return Some(cert.clone());
             }
         }
+        if let Some((_, cert)) = self
+            .named
+            .iter()
+            .find(|(name, _)| custom::covers(name, &hostname))
+        {
+            return Some(cert.clone());
+        }
 
-        // Generate an on-demand cert for valid `.roxy` hostnames if we
-        // can read the local CA private key.
-        let ca_key_pem = self.ca_key_pem.as_deref()?;
-        if DomainName::new(hostname.as_str()).is_err() {
+        // Generate an on-demand cert for valid `.roxy` hostnames (and
+        // `.localhost` aliases if enabled) if we can read the local CA
+        // private key.
+        let ca = self.ca.as_ref()?;
+        let is_alias = self.localhost && DomainName::from_localhost(&hostname).is_some();
+        if !self.any_host && !is_alias && DomainName::new(hostname.as_str()).is_err() {
             warn!(hostname = %hostname, "TLS: no certificate for domain");
             return None;
         }
 
-        match generate_on_demand_certified_key(hostname.as_str(), ca_key_pem) {
-            Ok(cert) => {
-                if let Ok(mut cache) = self.on_demand.write() {
-                    // Bound memory: on-demand certs are cheap to regenerate.
-                    if cache.len() >= ON_DEMAND_CERT_CACHE_MAX {
-                        cache.clear();
-                    }
+        let issued = generate_on_demand_cert(hostname.as_str(), ca, &self.settings)
+            .and_then(|cert| Ok((certified_key(&cert)?, cert)));
+        match issued {
+            Ok((cert, pem)) => {
+                if let Some(store) = &self.store {
+                    store.save(pem);
+                }
+                if let Ok(mut cache) = self.on_demand.lock() {
                     cache.insert(hostname, cert.clone());
                 }
                 Some(cert)
@@ -76,13 +128,259 @@ impl ResolvesServerCert for DomainCertResolver {
     }
 }
 
-/// Load all domain certificates into a single TLS acceptor with SNI
+impl DomainCertResolver {
+    /// The certificate for a client that sent no server name, as browsers
+    /// do for `https://192.168.1.50`. With `lan_ip_sans` every registered
+    /// certificate covers the host's addresses, so any of them will do.
+    fn without_sni(&self) -> Option<Arc<CertifiedKey>> {
+        if !self.settings.lan_ip_sans {
+            return None;
+        }
+        self.certs.first().map(|(_, cert)| cert.clone())
+    }
+
+    /// Whether `hostname` belongs to a domain with HTTPS turned off,
+    /// and no more specific registration has a certificate for it.
+    fn https_turned_off(&self, hostname: &str) -> bool {
+        let Some(off) = self
+            .no_https
+            .iter()
+            .filter(|p| p.matches_hostname(hostname))
+            .map(DomainPattern::specificity)
+            .max()
+        else {
+            return false;
+        };
+        !self
+            .certs
+            .iter()
+            .any(|(p, _)| p.matches_hostname(hostname) && p.specificity() > off)
+    }
+}
+
+/// On-demand certificates in memory, bounded by `on_demand_cache` in
+/// `[certs]`. Full, it drops the least recently served one rather than
+/// all of them, so busy names don't have to be signed again.
+#[derive(Debug)]
+struct OnDemandCache {
+    capacity: usize,
+    /// Each certificate and when it was last served.
+    entries: HashMap<String, (Arc<CertifiedKey>, u64)>,
+    clock: u64,
+}
+
+impl OnDemandCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn get(&mut self, hostname: &str) -> Option<Arc<CertifiedKey>> {
+        self.clock += 1;
+        let (cert, last_used) = self.entries.get_mut(hostname)?;
+        *last_used = self.clock;
+        Some(cert.clone())
+    }
+
+    fn insert(&mut self, hostname: String, cert: Arc<CertifiedKey>) {
+        self.clock += 1;
+        if !self.entries.contains_key(&hostname) && self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(hostname, _)| hostname.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(hostname, (cert, self.clock));
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// On-demand certificates on disk, named `<hostname>.crt` and
+/// `<hostname>.key` like the registered domains' ones, so a restart
+/// doesn't sign them again.
+#[derive(Debug)]
+struct OnDemandStore {
+    generator: CertificateGenerator,
+    dir: PathBuf,
+}
+
+impl OnDemandStore {
+    fn new(certs_dir: &Path, data_dir: &Path) -> Self {
+        let dir = certs_dir.join(ON_DEMAND_DIR);
+        Self {
+            generator: CertificateGenerator::new(data_dir.to_path_buf(), dir.clone()),
+            dir,
+        }
+    }
+
+    /// The saved certificates the Root CA signed that are still valid
+    /// for at least a day, by hostname, up to `limit` of them. Others
+    /// get issued again.
+    fn load(&self, ca: &CaPem, limit: usize) -> HashMap<String, Arc<CertifiedKey>> {
+        let mut loaded = HashMap::new();
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return loaded;
+        };
+        let verifier = match ca_verifier(ca) {
+            Ok(verifier) => verifier,
+            Err(e) => {
+                warn!(error = %e, "TLS: can't check saved on-demand certificates");
+                return loaded;
+            }
+        };
+
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if loaded.len() >= limit {
+                break;
+            }
+            let Some(hostname) = path
+                .extension()
+                .is_some_and(|ext| ext == "crt")
+                .then(|| path.file_stem())
+                .flatten()
+                .and_then(|stem| stem.to_str())
+            else {
+                continue;
+            };
+            let Ok(cert_pem) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let Ok(key_pem) = fs::read_to_string(path.with_extension("key")) else {
+                continue;
+            };
+            let cert = Certificate {
+                file_stem: hostname.to_string(),
+                cert_pem,
+                key_pem,
+            };
+            match check_saved(&cert, verifier.as_ref()).and_then(|()| certified_key(&cert)) {
+                Ok(key) => {
+                    loaded.insert(cert.file_stem, key);
+                }
+                Err(e) => {
+                    debug!(hostname, error = %e, "TLS: issuing saved on-demand certificate again")
+                }
+            }
+        }
+        loaded
+    }
+
+    /// Save `cert` for the next start. Failing only costs a signature
+    /// then, so it's logged and otherwise ignored.
+    fn save(&self, cert: Certificate) {
+        if let Err(e) = self.generator.save(&cert) {
+            debug!(hostname = %cert.file_stem, error = %e, "TLS: failed to save on-demand certificate");
+        }
+    }
+}
+
+/// Checks server certificates against the Root CA.
+fn ca_verifier(ca: &CaPem) -> Result<Arc<WebPkiServerVerifier>> {
+    let mut roots = RootCertStore::empty();
+    for cert in CertificateDer::pem_slice_iter(ca.cert.as_bytes()) {
+        roots
+            .add(cert.context("Failed to parse CA certificate")?)
+            .context("Unusable CA certificate")?;
+    }
+    WebPkiServerVerifier::builder_with_provider(
+        Arc::new(roots),
+        Arc::new(rustls::crypto::aws_lc_rs::default_provider()),
+    )
+    .build()
+    .context("Failed to set up certificate verification")
+}
+
+/// Whether a saved on-demand certificate is still good to serve: signed
+/// by the current Root CA, for its hostname, valid for another day.
+fn check_saved(cert: &Certificate, verifier: &dyn ServerCertVerifier) -> Result<()> {
+    let expiry = not_after(cert.cert_pem.as_bytes())?;
+    if expiry - OffsetDateTime::now_utc() < time::Duration::days(1) {
+        anyhow::bail!("expires {}", expiry);
+    }
+    let leaf = CertificateDer::from_pem_slice(cert.cert_pem.as_bytes())
+        .context("Failed to parse certificate")?;
+    let name = ServerName::try_from(cert.file_stem.clone())?;
+    verifier.verify_server_cert(&leaf, &[], &name, &[], UnixTime::now())?;
+    Ok(())
+}
+
+/// The Root CA's certificate and private key, which sign on-demand
+/// certificates.
+#[derive(Debug)]
+struct CaPem {
+    cert: String,
+    key: String,
+}
+
+/// TLS acceptor shared with the HTTPS accept loop. Reloading swaps in a
+/// new one; the listener itself stays open.
+#[derive(Clone)]
+pub struct SharedAcceptor(Arc<RwLock<TlsAcceptor>>);
+
+impl SharedAcceptor {
+    pub fn new(acceptor: TlsAcceptor) -> Self {
+        Self(Arc::new(RwLock::new(acceptor)))
+    }
+
+    pub fn current(&self) -> TlsAcceptor {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn replace(&self, acceptor: TlsAcceptor) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = acceptor;
+    }
+}
+
+/// Which certificates the registered domains are served with.
+#[derive(Debug, Default)]
+pub struct DomainCerts {
+    /// Domains with a certificate Roxy issued, kept in the certs directory.
+    pub issued: Vec<DomainPattern>,
+    /// Domains with a certificate of their own.
+    pub custom: Vec<(DomainPattern, CustomCert)>,
+    /// Domains with HTTPS turned off, which get no certificate at all.
+    pub no_https: Vec<DomainPattern>,
+}
+
+impl DomainCerts {
+    pub fn new(registrations: &[DomainRegistration]) -> Self {
+        let mut certs = Self::default();
+        for registration in registrations {
+            let pattern = registration.pattern().clone();
+            if let Some(cert) = registration.custom_cert() {
+                certs.custom.push((pattern, cert.clone()));
+            } else if registration.has_roxy_cert() {
+                certs.issued.push(pattern);
+            } else {
+                certs.no_https.push(pattern);
+            }
+        }
+        certs
+    }
+}
+
+/// Load all domain certificates into a single TLS acceptor with SNI:
+/// the ones Roxy issued from `certs_dir`, and the custom ones.
 pub fn create_tls_acceptor(
-    patterns: &[DomainPattern],
+    domains: &DomainCerts,
     certs_dir: &Path,
     data_dir: &Path,
+    localhost: bool,
+    settings: &CertsConfig,
+    tls: &TlsConfig,
 ) -> Result<Option<TlsAcceptor>> {
-    let ca_key_pem = match load_ca_key_pem(data_dir) {
+    let ca = match load_ca_pem(data_dir) {
         Ok(pem) => pem,
         Err(e) => {
             warn!(error = %e, "TLS: failed to load Roxy CA key (on-demand certificates disabled)");
@@ -92,13 +390,13 @@ pub fn create_tls_acceptor(
 
     // If we have neither per-domain certificates nor a Root CA to generate
     // on-demand certificates, HTTPS can't be served.
-    if patterns.is_empty() && ca_key_pem.is_none() {
+    if domains.issued.is_empty() && domains.custom.is_empty() && ca.is_none() {
         return Ok(None);
     }
 
     let mut certs: Vec<(DomainPattern, Arc<CertifiedKey>)> = Vec::new();
 
-    for pattern in patterns {
+    for pattern in &domains.issued {
         let stem = pattern.cert_name();
         let cert_path = certs_dir.join(format!("{}.crt", stem));
         let key_path = certs_dir.join(format!("{}.key", stem));
@@ -117,35 +415,194 @@ pub fn create_tls_acceptor(
         certs.push((pattern.clone(), certified_key));
     }
 
+    let mut named = Vec::new();
+    for (pattern, custom_cert) in &domains.custom {
+        let loaded = custom::load(custom_cert)
+            .with_context(|| format!("Failed to load the custom certificate for {}", pattern))?;
+        warn_if_expiring(pattern, custom_cert, loaded.not_after);
+
+        let certified_key = Arc::new(loaded.certified);
+        certs.push((pattern.clone(), certified_key.clone()));
+        named.extend(
+            loaded
+                .names
+                .into_iter()
+                .map(|name| (name, certified_key.clone())),
+        );
+    }
+
     // Most-specific pattern wins (longest base domain).
     certs.sort_by_key(|(p, _)| std::cmp::Reverse(p.specificity()));
 
+    // Saved certificates for names that have since been registered
+    // must not shadow the registered ones
+    let store = OnDemandStore::new(certs_dir, data_dir);
+    let mut on_demand = OnDemandCache::new(settings.on_demand_cache);
+    let saved = ca
+        .as_ref()
+        .map(|ca| store.load(ca, settings.on_demand_cache))
+        .unwrap_or_default();
+    for (hostname, cert) in saved {
+        if !certs.iter().any(|(p, _)| p.matches_hostname(&hostname))
+            && !named
+                .iter()
+                .any(|(name, _)| custom::covers(name, &hostname))
+        {
+            on_demand.insert(hostname, cert);
+        }
+    }
+
     let resolver = Arc::new(DomainCertResolver {
         certs,
-        ca_key_pem,
-        on_demand: RwLock::new(HashMap::new()),
+        named,
+        no_https: domains.no_https.clone(),
+        ca,
+        localhost,
+        any_host: false,
+        settings: settings.clone(),
+        on_demand: Mutex::new(on_demand),
+        store: Some(store),
     });
 
-    let config = ServerConfig::builder()
+    Ok(Some(server_config(resolver, tls)?))
+}
+
+/// Acceptor that issues an on-demand certificate for whatever host the
+/// client asks for, so the forward proxy can decrypt intercepted
+/// traffic. `None` without a readable Root CA key.
+pub fn create_intercept_acceptor(
+    data_dir: &Path,
+    settings: &CertsConfig,
+    tls: &TlsConfig,
+) -> Result<Option<TlsAcceptor>> {
+    let Some(ca) = load_ca_pem(data_dir)? else {
+        return Ok(None);
+    };
+
+    let resolver = Arc::new(DomainCertResolver {
+        certs: Vec::new(),
+        named: Vec::new(),
+        no_https: Vec::new(),
+        ca: Some(ca),
+        localhost: false,
+        any_host: true,
+        settings: settings.clone(),
+        on_demand: Mutex::new(OnDemandCache::new(settings.on_demand_cache)),
+        store: None,
+    });
+
+    Ok(Some(server_config(resolver, tls)?))
+}
+
+/// Log a custom certificate that has expired or expires soon; Roxy
+/// can't renew it.
+fn warn_if_expiring(pattern: &DomainPattern, cert: &CustomCert, not_after: OffsetDateTime) {
+    let days_left = (not_after - OffsetDateTime::now_utc()).whole_days();
+    if not_after < OffsetDateTime::now_utc() {
+        warn!(domain = %pattern, cert = %cert.cert.display(), expired = %not_after.date(), "TLS: custom certificate has expired");
+    } else if days_left < CUSTOM_CERT_WARNING_DAYS {
+        warn!(domain = %pattern, cert = %cert.cert.display(), days_left, "TLS: custom certificate expires soon");
+    } else {
+        info!(domain = %pattern, cert = %cert.cert.display(), "TLS: serving custom certificate");
+    }
+}
+
+/// Acceptor for `resolver`'s certificates, limited to the protocol
+/// versions and cipher suites of the `[tls]` section.
+fn server_config(resolver: Arc<DomainCertResolver>, tls: &TlsConfig) -> Result<TlsAcceptor> {
+    let mut config = ServerConfig::builder_with_provider(Arc::new(tls.crypto_provider()))
+        .with_protocol_versions(tls.protocol_versions())
+        .context("Invalid [tls] settings")?
         .with_no_client_auth()
         .with_cert_resolver(resolver);
 
-    Ok(Some(TlsAcceptor::from(Arc::new(config))))
+    let resumption = Resumption::shared();
+    config.session_storage = resumption.sessions.clone();
+    if let Some(ticketer) = &resumption.ticketer {
+        config.ticketer = ticketer.clone();
+    }
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
 }
 
-fn load_ca_key_pem(data_dir: &Path) -> Result<Option<String>> {
-    let ca_key_path = data_dir.join("ca.key");
-    if !ca_key_path.exists() {
+/// Session cache and ticket keys shared by every acceptor, so browsers
+/// resume their sessions instead of doing full handshakes, even right
+/// after a reload swapped in a new acceptor.
+struct Resumption {
+    sessions: Arc<dyn StoresServerSessions>,
+    /// `None` if no ticket key could be made; sessions then resume
+    /// through the cache only.
+    ticketer: Option<Arc<dyn ProducesTickets>>,
+}
+
+impl Resumption {
+    fn shared() -> &'static Self {
+        static SHARED: OnceLock<Resumption> = OnceLock::new();
+        SHARED.get_or_init(|| Self {
+            sessions: ServerSessionMemoryCache::new(SESSION_CACHE_SIZE),
+            ticketer: rustls::crypto::aws_lc_rs::Ticketer::new()
+                .inspect_err(|e| warn!(error = %e, "TLS: session tickets disabled"))
+                .ok(),
+        })
+    }
+}
+
+/// System CA bundles, checked in order. `SSL_CERT_FILE` wins if set.
+const SYSTEM_CA_BUNDLES: [&str; 4] = [
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/cert.pem",
+    "/etc/ssl/ca-bundle.pem",
+];
+
+/// TLS client for connecting to real origins, trusting the system CAs.
+pub fn create_upstream_connector() -> TlsConnector {
+    let mut roots = RootCertStore::empty();
+    let bundle = std::env::var_os("SSL_CERT_FILE")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(SYSTEM_CA_BUNDLES.iter().map(PathBuf::from))
+        .find(|path| path.exists());
+
+    match bundle.as_deref().map(load_certs) {
+        Some(Ok(certs)) => {
+            roots.add_parsable_certificates(certs);
+        }
+        Some(Err(e)) => warn!(error = %e, "TLS: failed to load system CA bundle"),
+        None => warn!("TLS: no system CA bundle found, upstream TLS will fail"),
+    }
+
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+}
+
+fn load_ca_pem(data_dir: &Path) -> Result<Option<CaPem>> {
+    let ca = RootCA::new(data_dir.to_path_buf());
+    if !ca.exists() {
         return Ok(None);
     }
 
-    let pem = fs::read_to_string(&ca_key_path)
-        .with_context(|| format!("Failed to read CA private key: {}", ca_key_path.display()))?;
-    Ok(Some(pem))
+    let key = fs::read_to_string(ca.key_path())
+        .with_context(|| format!("Failed to read CA private key: {}", ca.key_path().display()))?;
+    let cert = fs::read_to_string(ca.cert_path()).with_context(|| {
+        format!(
+            "Failed to read CA certificate: {}",
+            ca.cert_path().display()
+        )
+    })?;
+    Ok(Some(CaPem { cert, key }))
 }
 
-fn generate_on_demand_certified_key(hostname: &str, ca_key_pem: &str) -> Result<Arc<CertifiedKey>> {
-    let leaf_key_pair = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256)
+fn generate_on_demand_cert(
+    hostname: &str,
+    ca: &CaPem,
+    settings: &CertsConfig,
+) -> Result<Certificate> {
+    let leaf_key_pair = settings
+        .key_type
+        .generate()
         .context("Failed to generate leaf key pair")?;
 
     let san = SanType::DnsName(
@@ -153,19 +610,29 @@ fn generate_on_demand_certified_key(hostname: &str, ca_key_pem: &str) -> Result<
             .try_into()
             .map_err(|e| anyhow::anyhow!("Invalid hostname for SAN: {}", e))?,
     );
-    let params = build_leaf_cert_params(hostname, vec![san]);
+    let params = build_leaf_cert_params(hostname, vec![san], settings.leaf_days);
 
-    let ca_key_pair = KeyPair::from_pem(ca_key_pem).context("Failed to parse CA key")?;
-    let ca_params = build_ca_cert_params();
-    let issuer = Issuer::from_params(&ca_params, &ca_key_pair);
+    let ca_key_pair = KeyPair::from_pem(&ca.key).context("Failed to parse CA key")?;
+    let issuer =
+        issuer_from_pem(&ca.cert, ca_key_pair).context("Failed to parse CA certificate")?;
 
     let cert = params
         .signed_by(&leaf_key_pair, &issuer)
         .context("Failed to sign on-demand certificate")?;
 
-    let certs = vec![cert.der().clone()];
-    let key = PrivateKeyDer::try_from(leaf_key_pair.serialize_der())
-        .map_err(|e| anyhow::anyhow!("Failed to parse generated private key: {}", e))?;
+    Ok(Certificate {
+        file_stem: hostname.to_string(),
+        cert_pem: cert.pem(),
+        key_pem: leaf_key_pair.serialize_pem(),
+    })
+}
+
+fn certified_key(cert: &Certificate) -> Result<Arc<CertifiedKey>> {
+    let certs = CertificateDer::pem_slice_iter(cert.cert_pem.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to parse certificate")?;
+    let key = PrivateKeyDer::from_pem_slice(cert.key_pem.as_bytes())
+        .context("Failed to parse private key")?;
 
     let signing_key = rustls::crypto::aws_lc_rs::sign::any_supported_type(&key)
         .context("Failed to create signing key")?;
@@ -186,3 +653,205 @@ fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
     PrivateKeyDer::from_pem_file(path)
         .with_context(|| format!("Failed to load private key from: {}", path.display()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_ca(data_dir: &Path) -> CaPem {
+        RootCA::new(data_dir.to_path_buf()).generate().unwrap();
+        load_ca_pem(data_dir).unwrap().unwrap()
+    }
+
+    #[test]
+    fn on_demand_certificates_survive_a_restart() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ca = new_ca(tmp.path());
+        let store = OnDemandStore::new(&tmp.path().join("certs"), tmp.path());
+        assert!(store.load(&ca, 10).is_empty());
+
+        let cert = generate_on_demand_cert("other.roxy", &ca, &CertsConfig::default()).unwrap();
+        store.save(cert);
+        assert!(tmp.path().join("certs/on-demand/other.roxy.crt").exists());
+        assert!(store.load(&ca, 10).contains_key("other.roxy"));
+        assert!(store.load(&ca, 0).is_empty());
+    }
+
+    #[test]
+    fn saved_certificates_from_another_ca_are_dropped() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = OnDemandStore::new(&tmp.path().join("certs"), tmp.path());
+        let old_ca = new_ca(&tmp.path().join("old"));
+        let cert = generate_on_demand_cert("other.roxy", &old_ca, &CertsConfig::default()).unwrap();
+        store.save(cert);
+
+        let ca = new_ca(tmp.path());
+        assert!(store.load(&ca, 10).is_empty());
+    }
+
+    #[tokio::test]
+    async fn sessions_resume_across_reloads() {
+        use rustls::HandshakeKind;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let ca = new_ca(tmp.path());
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_slice_iter(ca.cert.as_bytes()) {
+            roots.add(cert.unwrap()).unwrap();
+        }
+        let connector = TlsConnector::from(Arc::new(
+            ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        ));
+
+        let mut kinds = Vec::new();
+        for _ in 0..2 {
+            // Each round stands for a reload: a new acceptor
+            let acceptor = create_tls_acceptor(
+                &DomainCerts::default(),
+                &tmp.path().join("certs"),
+                tmp.path(),
+                false,
+                &CertsConfig::default(),
+                &TlsConfig::default(),
+            )
+            .unwrap()
+            .unwrap();
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let server = tokio::spawn(async move {
+                let mut stream = acceptor.accept(server).await.unwrap();
+                stream.write_all(b"ok").await.unwrap();
+                stream.shutdown().await.unwrap();
+            });
+
+            let name = ServerName::try_from("other.roxy").unwrap();
+            let mut stream = connector.connect(name, client).await.unwrap();
+            kinds.push(stream.get_ref().1.handshake_kind());
+            // Reading takes in the session tickets sent after the handshake
+            let mut body = Vec::new();
+            stream.read_to_end(&mut body).await.unwrap();
+            server.await.unwrap();
+        }
+        assert_eq!(
+            kinds,
+            [Some(HandshakeKind::Full), Some(HandshakeKind::Resumed)]
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_registered_certificates_to_bare_ip_addresses() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ca = new_ca(tmp.path());
+        let certs_dir = tmp.path().join("certs");
+        let settings = CertsConfig {
+            lan_ip_sans: true,
+            ..CertsConfig::default()
+        };
+        let pattern = DomainPattern::Exact(DomainName::new("myapp.roxy").unwrap());
+        let generator = CertificateGenerator::new(tmp.path().to_path_buf(), certs_dir.clone())
+            .with_settings(&settings);
+        generator
+            .save(&generator.generate(&pattern).unwrap())
+            .unwrap();
+
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_slice_iter(ca.cert.as_bytes()) {
+            roots.add(cert.unwrap()).unwrap();
+        }
+        let connector = TlsConnector::from(Arc::new(
+            ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        ));
+        let domains = DomainCerts {
+            issued: vec![pattern],
+            ..DomainCerts::default()
+        };
+        let acceptor = create_tls_acceptor(
+            &domains,
+            &certs_dir,
+            tmp.path(),
+            false,
+            &settings,
+            &TlsConfig::default(),
+        )
+        .unwrap()
+        .unwrap();
+
+        // No SNI is sent for an IP address
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(async move { acceptor.accept(server).await.map(|_| ()) });
+        let name = ServerName::IpAddress(std::net::Ipv4Addr::LOCALHOST.into());
+        let _stream = connector.connect(name, client).await.unwrap();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_domains_with_https_turned_off() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ca = new_ca(tmp.path());
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_slice_iter(ca.cert.as_bytes()) {
+            roots.add(cert.unwrap()).unwrap();
+        }
+        let connector = TlsConnector::from(Arc::new(
+            ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        ));
+
+        let mut plain = DomainRegistration::new(
+            DomainPattern::from_name("plain.roxy", true).unwrap(),
+            Vec::new(),
+        );
+        plain.disable_https();
+        let domains = DomainCerts::new(&[plain]);
+        assert_eq!(domains.no_https.len(), 1);
+        let acceptor = create_tls_acceptor(
+            &domains,
+            &tmp.path().join("certs"),
+            tmp.path(),
+            false,
+            &CertsConfig::default(),
+            &TlsConfig::default(),
+        )
+        .unwrap()
+        .unwrap();
+
+        let mut connected = Vec::new();
+        for host in ["api.plain.roxy", "other.roxy"] {
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let acceptor = acceptor.clone();
+            let server = tokio::spawn(async move { acceptor.accept(server).await.is_ok() });
+            let name = ServerName::try_from(host).unwrap();
+            let stream = connector.connect(name, client).await;
+            connected.push(stream.is_ok() && server.await.unwrap());
+        }
+        assert_eq!(connected, [false, true]);
+    }
+
+    #[test]
+    fn cache_drops_the_least_recently_used_certificate() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ca = new_ca(tmp.path());
+        let cert = generate_on_demand_cert("a.roxy", &ca, &CertsConfig::default()).unwrap();
+        let key = certified_key(&cert).unwrap();
+
+        let mut cache = OnDemandCache::new(2);
+        cache.insert("a.roxy".into(), key.clone());
+        cache.insert("b.roxy".into(), key.clone());
+        assert!(cache.get("a.roxy").is_some());
+
+        cache.insert("c.roxy".into(), key.clone());
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("b.roxy").is_none());
+        assert!(cache.get("a.roxy").is_some());
+        assert!(cache.get("c.roxy").is_some());
+
+        // Replacing a cached name doesn't evict another
+        cache.insert("c.roxy".into(), key);
+        assert_eq!(cache.len(), 2);
+    }
+}
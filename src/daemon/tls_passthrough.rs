@@ -0,0 +1,201 @@
+//! TLS passthrough: HTTPS connections for some domains go to their
+//! backend still encrypted. The server name is read from the client's
+//! ClientHello without consuming it, so every other connection can still
+//! be handed to the TLS acceptor untouched.
+
+use std::time::Duration;
+
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+
+/// A ClientHello is a single TLS record, at most 16 KiB plus its header.
+const MAX_RECORD: usize = 5 + 16 * 1024;
+/// How long to wait for the rest of a ClientHello split across segments.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+const HANDSHAKE: u8 = 22;
+const CLIENT_HELLO: u8 = 1;
+const SERVER_NAME: u16 = 0;
+const HOST_NAME: u8 = 0;
+
+/// The server name the client asked for, read without taking any bytes
+/// off the stream. `None` when the client sent no SNI or isn't speaking
+/// TLS.
+pub async fn peek_server_name(stream: &TcpStream) -> Option<String> {
+    let deadline = Instant::now() + HELLO_TIMEOUT;
+    let mut buf = vec![0; MAX_RECORD];
+    let mut seen = 0;
+    loop {
+        let n = tokio::time::timeout_at(deadline, stream.peek(&mut buf))
+            .await
+            .ok()?
+            .ok()?;
+        match parse_server_name(&buf[..n]) {
+            Hello::Complete(name) => return name,
+            // Closed, or a record too big to be a ClientHello
+            Hello::Partial if n == 0 || n == buf.len() => return None,
+            Hello::Partial if Instant::now() >= deadline => return None,
+            // Peeking again returns at once, so wait for the rest
+            Hello::Partial if n == seen => tokio::time::sleep(Duration::from_millis(10)).await,
+            Hello::Partial => seen = n,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Hello {
+    /// More bytes are needed.
+    Partial,
+    /// The whole ClientHello is there, with the server name if it has one.
+    Complete(Option<String>),
+}
+
+/// Read the SNI host name from the first TLS record in `data`.
+fn parse_server_name(data: &[u8]) -> Hello {
+    let mut record = Reader(data);
+    let header = (record.u8(), record.skip(2), record.u16());
+    let (Some(content_type), Some(()), Some(len)) = header else {
+        return Hello::Partial;
+    };
+    if content_type != HANDSHAKE {
+        return Hello::Complete(None);
+    }
+    let Some(body) = record.bytes(len.into()) else {
+        return Hello::Partial;
+    };
+    Hello::Complete(server_name(body))
+}
+
+fn server_name(handshake: &[u8]) -> Option<String> {
+    let mut hello = Reader(handshake);
+    if hello.u8()? != CLIENT_HELLO {
+        return None;
+    }
+    // Length (3), version (2), random (32)
+    hello.skip(3 + 2 + 32)?;
+    let session_id = hello.u8()?;
+    hello.skip(session_id.into())?;
+    let cipher_suites = hello.u16()?;
+    hello.skip(cipher_suites.into())?;
+    let compression = hello.u8()?;
+    hello.skip(compression.into())?;
+
+    let extensions = hello.u16()?;
+    let mut extensions = Reader(hello.bytes(extensions.into())?);
+    while let (Some(kind), Some(len)) = (extensions.u16(), extensions.u16()) {
+        let data = extensions.bytes(len.into())?;
+        if kind != SERVER_NAME {
+            continue;
+        }
+        let mut list = Reader(data);
+        let len = list.u16()?;
+        let mut names = Reader(list.bytes(len.into())?);
+        while let (Some(kind), Some(len)) = (names.u8(), names.u16()) {
+            let name = names.bytes(len.into())?;
+            if kind == HOST_NAME {
+                return std::str::from_utf8(name).ok().map(str::to_lowercase);
+            }
+        }
+        return None;
+    }
+    None
+}
+
+/// Big-endian reads that give up instead of panicking on short input.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn skip(&mut self, n: usize) -> Option<()> {
+        self.bytes(n).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use rustls::pki_types::ServerName;
+    use rustls::{ClientConfig, ClientConnection, RootCertStore};
+
+    /// The first flight a real client sends for `host`.
+    fn client_hello(host: &str) -> Vec<u8> {
+        let config = ClientConfig::builder()
+            .with_root_certificates(RootCertStore::empty())
+            .with_no_client_auth();
+        let name = ServerName::try_from(host.to_string()).unwrap();
+        let mut conn = ClientConnection::new(Arc::new(config), name).unwrap();
+        let mut hello = Vec::new();
+        conn.write_tls(&mut hello).unwrap();
+        hello
+    }
+
+    #[test]
+    fn reads_the_server_name() {
+        let hello = client_hello("DB.myapp.roxy");
+        assert_eq!(
+            parse_server_name(&hello),
+            Hello::Complete(Some("db.myapp.roxy".to_string()))
+        );
+    }
+
+    #[test]
+    fn waits_for_the_whole_hello() {
+        let hello = client_hello("db.myapp.roxy");
+        assert_eq!(parse_server_name(&hello[..3]), Hello::Partial);
+        assert_eq!(parse_server_name(&hello[..hello.len() - 1]), Hello::Partial);
+    }
+
+    #[test]
+    fn ignores_plain_http() {
+        assert_eq!(
+            parse_server_name(b"GET / HTTP/1.1\r\n"),
+            Hello::Complete(None)
+        );
+    }
+
+    #[tokio::test]
+    async fn peeking_leaves_the_hello_on_the_stream() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let hello = client_hello("db.myapp.roxy");
+        let sent = hello.clone();
+        tokio::spawn(async move {
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            // Split the hello the way a small MTU would
+            client.write_all(&sent[..100]).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            client.write_all(&sent[100..]).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        assert_eq!(
+            peek_server_name(&stream).await.as_deref(),
+            Some("db.myapp.roxy")
+        );
+        let mut received = vec![0; hello.len()];
+        stream.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, hello);
+    }
+}
@@ -4,4 +4,9 @@ pub mod value_objects;
 pub use registration::DomainRegistration;
 #[allow(unused_imports)]
 pub use registration::RegistrationError;
-pub use value_objects::{DomainName, DomainPattern, PathPrefix, ProxyTarget, Route, RouteTarget};
+pub use value_objects::{
+    AccessPolicy, BackendProtocol, BasicAuth, BodyRewrite, CachePolicy, CookieRewrite, CorsPolicy,
+    CustomCert, CustomHeaders, DnsRecords, DomainName, DomainPattern, Fault, Latency,
+    LoadBalancing, LogLevel, PathGlob, PathPrefix, PathRewrite, ProxyTarget, RateLimit,
+    RetryPolicy, Route, RouteOptions, RouteTarget, SymlinkPolicy, TcpForward, TrafficSplit,
+};
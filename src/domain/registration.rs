@@ -1,5 +1,9 @@
-use super::{DomainName, DomainPattern, PathPrefix, Route, RouteTarget};
-use std::path::PathBuf;
+use super::value_objects::is_host_name;
+use super::{
+    AccessPolicy, BasicAuth, CorsPolicy, CustomCert, DnsRecords, DomainName, DomainPattern,
+    LogLevel, PathPrefix, ProxyTarget, RateLimit, Route, TcpForward,
+};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -18,13 +22,80 @@ pub enum RegistrationError {
 
     #[error("Cannot remove the last route - unregister the domain instead")]
     CannotRemoveLastRoute,
+
+    #[error("rate_limit.requests_per_second must be at least 1")]
+    InvalidRateLimit,
+
+    #[error("Route '{0}' needs one split weight per target")]
+    SplitMismatch(String),
+
+    #[error("TLS passthrough needs a proxy route for '/'")]
+    PassthroughWithoutProxy,
+
+    #[error("TCP port {0} is already forwarded")]
+    TcpForwardExists(u16),
+
+    #[error("No TCP forward found for port {0}")]
+    TcpForwardNotFound(u16),
+
+    #[error("Index file '{0}' must be a file name, not a path")]
+    InvalidIndexFile(String),
+
+    #[error("404 page '{0}' must be a path inside the route's directory")]
+    InvalidNotFoundPage(String),
+
+    #[error("CNAME target '{0}' must be another host name")]
+    InvalidCname(String),
+
+    #[error("Certificate file does not exist: {0}")]
+    CertFileNotFound(PathBuf),
+
+    #[error("https_only needs HTTPS: register the domain with a certificate")]
+    HttpsOnlyWithoutHttps,
+
+    #[error("Tag '{0}' must be letters, digits, '-' and '_'")]
+    InvalidTag(String),
 }
 
+/// HSTS `max-age` when `https_only` is on: short, so turning it off
+/// again takes effect in browsers within minutes.
+pub const DEFAULT_HSTS_MAX_AGE: u64 = 300;
+
 #[derive(Debug, Clone)]
 pub struct DomainRegistration {
     pattern: DomainPattern,
     routes: Vec<Route>,
     https_enabled: bool,
+    /// Served instead of the certificate Roxy issues.
+    custom_cert: Option<CustomCert>,
+    /// Redirect plain HTTP to HTTPS and send HSTS.
+    https_only: bool,
+    /// HSTS `max-age` in seconds, `DEFAULT_HSTS_MAX_AGE` if unset.
+    hsts_max_age: Option<u64>,
+    log_level: Option<LogLevel>,
+    /// User who registered the domain; `None` for registrations made
+    /// before ownership was tracked, which anyone may manage.
+    owner: Option<String>,
+    cors: Option<CorsPolicy>,
+    auth: Option<BasicAuth>,
+    access: AccessPolicy,
+    rate_limit: Option<RateLimit>,
+    maintenance: bool,
+    /// Shown on the maintenance page instead of the default text.
+    maintenance_message: Option<String>,
+    /// Hand HTTPS connections to the backend still encrypted.
+    tls_passthrough: bool,
+    tcp_forwards: Vec<TcpForward>,
+    dns: DnsRecords,
+    /// Directory whose `.roxy.toml` the domain was linked from.
+    project: Option<PathBuf>,
+    /// A disabled domain keeps its routes and certificate but isn't
+    /// served.
+    enabled: bool,
+    /// What the domain is for, shown by `roxy list`.
+    description: Option<String>,
+    /// Labels to filter `roxy list` by.
+    tags: Vec<String>,
 }
 
 impl DomainRegistration {
@@ -33,6 +104,24 @@ impl DomainRegistration {
             pattern,
             routes,
             https_enabled: false,
+            custom_cert: None,
+            https_only: false,
+            hsts_max_age: None,
+            log_level: None,
+            owner: None,
+            cors: None,
+            auth: None,
+            access: AccessPolicy::default(),
+            rate_limit: None,
+            maintenance: false,
+            maintenance_message: None,
+            tls_passthrough: false,
+            tcp_forwards: Vec::new(),
+            dns: DnsRecords::default(),
+            project: None,
+            enabled: true,
+            description: None,
+            tags: Vec::new(),
         }
     }
 
@@ -50,14 +139,141 @@ impl DomainRegistration {
         &self.routes
     }
 
+    /// Whether the domain is served over HTTPS, with a certificate Roxy
+    /// issued or a custom one.
     pub fn is_https_enabled(&self) -> bool {
+        self.https_enabled || self.custom_cert.is_some()
+    }
+
+    /// Whether Roxy issued a certificate for the domain. A custom
+    /// certificate takes precedence over it.
+    pub fn has_roxy_cert(&self) -> bool {
         self.https_enabled
     }
 
+    pub fn custom_cert(&self) -> Option<&CustomCert> {
+        self.custom_cert.as_ref()
+    }
+
+    pub fn is_https_only(&self) -> bool {
+        self.https_only
+    }
+
+    /// The configured HSTS `max-age`, if one was set.
+    pub fn hsts_max_age(&self) -> Option<u64> {
+        self.hsts_max_age
+    }
+
+    /// The `Strict-Transport-Security` value for HTTPS responses, when
+    /// the domain is HTTPS-only.
+    pub fn hsts_header(&self) -> Option<String> {
+        self.https_only.then(|| {
+            format!(
+                "max-age={}",
+                self.hsts_max_age.unwrap_or(DEFAULT_HSTS_MAX_AGE)
+            )
+        })
+    }
+
     pub fn is_wildcard(&self) -> bool {
         self.pattern.is_wildcard()
     }
 
+    pub fn log_level(&self) -> Option<LogLevel> {
+        self.log_level
+    }
+
+    pub fn owner(&self) -> Option<&str> {
+        self.owner.as_deref()
+    }
+
+    pub fn cors(&self) -> Option<&CorsPolicy> {
+        self.cors.as_ref()
+    }
+
+    pub fn auth(&self) -> Option<&BasicAuth> {
+        self.auth.as_ref()
+    }
+
+    pub fn access(&self) -> &AccessPolicy {
+        &self.access
+    }
+
+    pub fn rate_limit(&self) -> Option<&RateLimit> {
+        self.rate_limit.as_ref()
+    }
+
+    pub fn is_in_maintenance(&self) -> bool {
+        self.maintenance
+    }
+
+    pub fn maintenance_message(&self) -> Option<&str> {
+        self.maintenance_message.as_deref()
+    }
+
+    pub fn is_tls_passthrough(&self) -> bool {
+        self.tls_passthrough
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Where HTTPS connections go undecrypted: the first backend of the
+    /// `/` route, when passthrough is on.
+    pub fn passthrough_target(&self) -> Option<&ProxyTarget> {
+        if !self.tls_passthrough {
+            return None;
+        }
+        self.routes
+            .iter()
+            .find(|r| r.path.as_str() == "/")?
+            .target
+            .proxy_targets()
+            .first()
+    }
+
+    pub fn tcp_forwards(&self) -> &[TcpForward] {
+        &self.tcp_forwards
+    }
+
+    pub fn dns(&self) -> &DnsRecords {
+        &self.dns
+    }
+
+    pub fn project(&self) -> Option<&Path> {
+        self.project.as_deref()
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Users allowed on `route`, `None` if it needs no login.
+    /// A route-level setting wins over the domain-level one.
+    pub fn effective_auth<'a>(&'a self, route: &'a Route) -> Option<&'a BasicAuth> {
+        route
+            .options
+            .auth
+            .as_ref()
+            .or(self.auth.as_ref())
+            .filter(|auth| !auth.is_public())
+    }
+
+    /// Log level for requests handled by `route`.
+    /// A route-level override wins over the domain-level one.
+    pub fn effective_log_level(&self, route: &Route) -> Option<LogLevel> {
+        route.options.log_level.or(self.log_level)
+    }
+
     // --- Delegated pattern methods ---
 
     pub fn display_pattern(&self) -> String {
@@ -74,6 +290,99 @@ impl DomainRegistration {
         self.https_enabled = true;
     }
 
+    /// Serve the domain over plain HTTP only.
+    pub fn disable_https(&mut self) {
+        self.https_enabled = false;
+    }
+
+    pub fn set_custom_cert(&mut self, cert: Option<CustomCert>) {
+        self.custom_cert = cert;
+    }
+
+    pub fn set_https_only(&mut self, on: bool, hsts_max_age: Option<u64>) {
+        self.https_only = on;
+        self.hsts_max_age = hsts_max_age;
+    }
+
+    pub fn set_log_level(&mut self, level: Option<LogLevel>) {
+        self.log_level = level;
+    }
+
+    pub fn set_owner(&mut self, owner: Option<String>) {
+        self.owner = owner;
+    }
+
+    pub fn set_cors(&mut self, cors: Option<CorsPolicy>) {
+        self.cors = cors;
+    }
+
+    pub fn set_auth(&mut self, auth: Option<BasicAuth>) {
+        self.auth = auth;
+    }
+
+    pub fn set_access(&mut self, access: AccessPolicy) {
+        self.access = access;
+    }
+
+    pub fn set_rate_limit(&mut self, rate_limit: Option<RateLimit>) {
+        self.rate_limit = rate_limit;
+    }
+
+    /// Turn maintenance mode on or off. Turning it off drops the message.
+    pub fn set_maintenance(&mut self, on: bool, message: Option<String>) {
+        self.maintenance = on;
+        self.maintenance_message = message.filter(|_| on);
+    }
+
+    pub fn set_tls_passthrough(&mut self, on: bool) {
+        self.tls_passthrough = on;
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn set_tcp_forwards(&mut self, forwards: Vec<TcpForward>) {
+        self.tcp_forwards = forwards;
+    }
+
+    pub fn set_dns(&mut self, dns: DnsRecords) {
+        self.dns = dns;
+    }
+
+    pub fn set_project(&mut self, project: Option<PathBuf>) {
+        self.project = project;
+    }
+
+    /// An empty description is the same as none.
+    pub fn set_description(&mut self, description: Option<String>) {
+        self.description = description.filter(|d| !d.trim().is_empty());
+    }
+
+    /// Replace the tags, dropping repeated ones.
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        self.tags.clear();
+        for tag in tags {
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+    }
+
+    /// Replace all routes, e.g. with the ones a project file lists.
+    pub fn set_routes(&mut self, routes: Vec<Route>) {
+        self.routes = routes;
+    }
+
+    pub fn auth_mut(&mut self) -> &mut Option<BasicAuth> {
+        &mut self.auth
+    }
+
+    /// The route with exactly this path prefix.
+    pub fn route_mut(&mut self, path: &PathPrefix) -> Option<&mut Route> {
+        self.routes.iter_mut().find(|r| &r.path == path)
+    }
+
     /// Find the best matching route for a request path.
     /// Returns None if no route matches.
     /// Uses longest prefix matching (most specific match wins).
@@ -110,10 +419,74 @@ impl DomainRegistration {
         Ok(())
     }
 
+    /// Forward another TCP port for this domain.
+    /// Returns error if the port is already forwarded.
+    pub fn add_tcp_forward(&mut self, forward: TcpForward) -> Result<(), RegistrationError> {
+        if self.tcp_forwards.iter().any(|f| f.port() == forward.port()) {
+            return Err(RegistrationError::TcpForwardExists(forward.port()));
+        }
+        self.tcp_forwards.push(forward);
+        Ok(())
+    }
+
+    /// Stop forwarding a TCP port.
+    pub fn remove_tcp_forward(&mut self, port: u16) -> Result<(), RegistrationError> {
+        let len_before = self.tcp_forwards.len();
+        self.tcp_forwards.retain(|f| f.port() != port);
+
+        if self.tcp_forwards.len() == len_before {
+            return Err(RegistrationError::TcpForwardNotFound(port));
+        }
+        Ok(())
+    }
+
     /// Validate that the registration is still valid (e.g., paths exist)
     pub fn validate(&self) -> Result<(), RegistrationError> {
+        if self
+            .rate_limit
+            .as_ref()
+            .is_some_and(|limit| limit.requests_per_second == 0)
+        {
+            return Err(RegistrationError::InvalidRateLimit);
+        }
+        if self.https_only && !self.is_https_enabled() {
+            return Err(RegistrationError::HttpsOnlyWithoutHttps);
+        }
+        if self.tls_passthrough && self.passthrough_target().is_none() {
+            return Err(RegistrationError::PassthroughWithoutProxy);
+        }
+        if let Some(tag) = self.tags.iter().find(|tag| !is_tag(tag)) {
+            return Err(RegistrationError::InvalidTag(tag.clone()));
+        }
+        if let Some(cname) = &self.dns.cname
+            && (!is_host_name(cname)
+                || cname
+                    .trim_end_matches('.')
+                    .eq_ignore_ascii_case(self.domain().as_str()))
+        {
+            return Err(RegistrationError::InvalidCname(cname.clone()));
+        }
+        if let Some(file) = self
+            .custom_cert
+            .iter()
+            .flat_map(CustomCert::files)
+            .find(|file| !file.is_file())
+        {
+            return Err(RegistrationError::CertFileNotFound(file.clone()));
+        }
         for route in &self.routes {
-            if let RouteTarget::StaticFiles(path) = &route.target {
+            if route.check_split().is_err() {
+                return Err(RegistrationError::SplitMismatch(route.path.to_string()));
+            }
+            if let Some(name) = route.options.index.iter().find(|name| !is_file_name(name)) {
+                return Err(RegistrationError::InvalidIndexFile(name.clone()));
+            }
+            if let Some(page) = &route.options.not_found
+                && !page.split('/').all(is_file_name)
+            {
+                return Err(RegistrationError::InvalidNotFoundPage(page.clone()));
+            }
+            for path in route.target.static_roots() {
                 if !path.exists() {
                     return Err(RegistrationError::PathNotFound(path.clone()));
                 }
@@ -127,10 +500,22 @@ impl DomainRegistration {
     }
 }
 
+/// A name that stays inside the directory it's looked up in.
+fn is_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+fn is_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::domain::ProxyTarget;
+    use crate::domain::{ProxyTarget, RouteTarget};
 
     fn make_pattern(name: &str) -> DomainPattern {
         DomainPattern::Exact(DomainName::new(name).unwrap())
@@ -171,6 +556,67 @@ mod tests {
         assert!(reg.is_https_enabled());
     }
 
+    // --- effective_log_level ---
+
+    #[test]
+    fn effective_log_level_defaults_to_none() {
+        let reg = DomainRegistration::new(make_pattern("myapp.roxy"), vec![proxy_route("/", 3000)]);
+        assert_eq!(reg.effective_log_level(&reg.routes()[0]), None);
+    }
+
+    #[test]
+    fn effective_log_level_falls_back_to_domain() {
+        let mut reg =
+            DomainRegistration::new(make_pattern("myapp.roxy"), vec![proxy_route("/", 3000)]);
+        reg.set_log_level(Some(LogLevel::Warn));
+        assert_eq!(
+            reg.effective_log_level(&reg.routes()[0]),
+            Some(LogLevel::Warn)
+        );
+    }
+
+    #[test]
+    fn effective_log_level_prefers_route_override() {
+        let mut api = proxy_route("/api", 3001);
+        api.options.log_level = Some(LogLevel::Debug);
+        let mut reg = DomainRegistration::new(
+            make_pattern("myapp.roxy"),
+            vec![proxy_route("/", 3000), api],
+        );
+        reg.set_log_level(Some(LogLevel::Warn));
+
+        let api = reg.match_route("/api/users").unwrap();
+        assert_eq!(reg.effective_log_level(api), Some(LogLevel::Debug));
+        let root = reg.match_route("/").unwrap();
+        assert_eq!(reg.effective_log_level(root), Some(LogLevel::Warn));
+    }
+
+    // --- effective_auth ---
+
+    #[test]
+    fn effective_auth_prefers_route_and_allows_public_routes() {
+        let mut admin = BasicAuth::default();
+        admin.set_user("admin", "hash-a");
+        let mut team = BasicAuth::default();
+        team.set_user("team", "hash-t");
+
+        let mut api = proxy_route("/api", 3001);
+        api.options.auth = Some(admin.clone());
+        let mut health = proxy_route("/health", 3001);
+        health.options.auth = Some(BasicAuth::default());
+        let mut reg = DomainRegistration::new(
+            make_pattern("myapp.roxy"),
+            vec![proxy_route("/", 3000), api, health],
+        );
+        assert_eq!(reg.effective_auth(reg.match_route("/").unwrap()), None);
+
+        reg.set_auth(Some(team.clone()));
+        let route = |path| reg.match_route(path).unwrap();
+        assert_eq!(reg.effective_auth(route("/")), Some(&team));
+        assert_eq!(reg.effective_auth(route("/api/users")), Some(&admin));
+        assert_eq!(reg.effective_auth(route("/health")), None);
+    }
+
     // --- match_route: longest prefix wins ---
 
     #[test]
@@ -286,6 +732,44 @@ mod tests {
         assert!(matches!(result, Err(RegistrationError::PathNotFound(_))));
     }
 
+    #[test]
+    fn custom_certificate_enables_https_and_must_exist() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cert = tmp.path().join("fullchain.pem");
+        std::fs::write(&cert, "cert").unwrap();
+        let mut reg =
+            DomainRegistration::new(make_pattern("myapp.roxy"), vec![proxy_route("/", 3000)]);
+        reg.set_custom_cert(Some(CustomCert {
+            cert,
+            key: tmp.path().join("privkey.pem"),
+        }));
+
+        assert!(reg.is_https_enabled());
+        assert!(!reg.has_roxy_cert());
+        assert!(matches!(
+            reg.validate(),
+            Err(RegistrationError::CertFileNotFound(path)) if path.ends_with("privkey.pem")
+        ));
+
+        std::fs::write(tmp.path().join("privkey.pem"), "key").unwrap();
+        assert!(reg.validate().is_ok());
+    }
+
+    #[test]
+    fn https_only_needs_https() {
+        let mut reg =
+            DomainRegistration::new(make_pattern("myapp.roxy"), vec![proxy_route("/", 3000)]);
+        reg.set_https_only(true, Some(60));
+        assert!(matches!(
+            reg.validate(),
+            Err(RegistrationError::HttpsOnlyWithoutHttps)
+        ));
+
+        reg.enable_https();
+        assert!(reg.validate().is_ok());
+        assert_eq!(reg.hsts_header().as_deref(), Some("max-age=60"));
+    }
+
     #[test]
     fn validate_fails_for_file_not_directory() {
         let tmp = tempfile::tempdir().unwrap();
@@ -299,8 +783,158 @@ mod tests {
         assert!(matches!(result, Err(RegistrationError::NotADirectory(_))));
     }
 
+    #[test]
+    fn validate_fails_for_zero_rate_limit() {
+        let mut reg =
+            DomainRegistration::new(make_pattern("myapp.roxy"), vec![proxy_route("/", 3000)]);
+        reg.set_rate_limit(Some(RateLimit {
+            requests_per_second: 0,
+            burst: Some(5),
+        }));
+        let result = reg.validate();
+        assert!(matches!(result, Err(RegistrationError::InvalidRateLimit)));
+    }
+
+    #[test]
+    fn validate_fails_for_split_without_a_weight_per_target() {
+        let mut route = proxy_route("/", 3000);
+        route.options.split = Some(crate::domain::TrafficSplit::parse("90/10").unwrap());
+        let reg = DomainRegistration::new(make_pattern("myapp.roxy"), vec![route]);
+        let result = reg.validate();
+        assert!(matches!(result, Err(RegistrationError::SplitMismatch(path)) if path == "/"));
+    }
+
+    #[test]
+    fn validate_fails_for_index_file_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut route = static_route("/", tmp.path().to_path_buf());
+        route.options.index = vec!["index.htm".into(), "../secret.html".into()];
+        let reg = DomainRegistration::new(make_pattern("myapp.roxy"), vec![route]);
+        let result = reg.validate();
+        assert!(
+            matches!(result, Err(RegistrationError::InvalidIndexFile(name)) if name == "../secret.html")
+        );
+    }
+
+    #[test]
+    fn validate_fails_for_not_found_pages_outside_the_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut route = static_route("/", tmp.path().to_path_buf());
+        route.options.not_found = Some("errors/missing.html".into());
+        let mut reg = DomainRegistration::new(make_pattern("myapp.roxy"), vec![route.clone()]);
+        assert!(reg.validate().is_ok());
+
+        for page in ["/etc/passwd", "errors/../../secret.html", "errors//x.html"] {
+            route.options.not_found = Some(page.into());
+            reg = DomainRegistration::new(make_pattern("myapp.roxy"), vec![route.clone()]);
+            assert!(matches!(
+                reg.validate(),
+                Err(RegistrationError::InvalidNotFoundPage(p)) if p == page
+            ));
+        }
+    }
+
+    #[test]
+    fn turning_maintenance_off_drops_the_message() {
+        let mut reg =
+            DomainRegistration::new(make_pattern("myapp.roxy"), vec![proxy_route("/", 3000)]);
+        reg.set_maintenance(true, Some("Back at 5pm".to_string()));
+        assert!(reg.is_in_maintenance());
+        assert_eq!(reg.maintenance_message(), Some("Back at 5pm"));
+
+        reg.set_maintenance(false, Some("ignored".to_string()));
+        assert!(!reg.is_in_maintenance());
+        assert_eq!(reg.maintenance_message(), None);
+    }
+
+    #[test]
+    fn passthrough_goes_to_the_root_route_backend() {
+        let mut reg = DomainRegistration::new(
+            make_pattern("db.roxy"),
+            vec![proxy_route("/api", 4000), proxy_route("/", 8443)],
+        );
+        assert_eq!(reg.passthrough_target(), None);
+
+        reg.set_tls_passthrough(true);
+        assert_eq!(reg.passthrough_target().unwrap().port().value(), 8443);
+        assert!(reg.validate().is_ok());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let mut reg = DomainRegistration::new(
+            make_pattern("site.roxy"),
+            vec![static_route("/", tmp.path().to_path_buf())],
+        );
+        reg.set_tls_passthrough(true);
+        let result = reg.validate();
+        assert!(matches!(
+            result,
+            Err(RegistrationError::PassthroughWithoutProxy)
+        ));
+    }
+
+    #[test]
+    fn validate_checks_cname_target() {
+        let mut reg =
+            DomainRegistration::new(make_pattern("docs.roxy"), vec![proxy_route("/", 3000)]);
+        reg.set_dns(DnsRecords {
+            cname: Some("docs.example.com".to_string()),
+            ..DnsRecords::default()
+        });
+        assert!(reg.validate().is_ok());
+
+        for target in ["docs.roxy.", "not a name"] {
+            reg.set_dns(DnsRecords {
+                cname: Some(target.to_string()),
+                ..DnsRecords::default()
+            });
+            assert!(matches!(
+                reg.validate(),
+                Err(RegistrationError::InvalidCname(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn tcp_forward_ports_are_unique_per_domain() {
+        let mut reg =
+            DomainRegistration::new(make_pattern("db.roxy"), vec![proxy_route("/", 3000)]);
+        reg.add_tcp_forward(TcpForward::parse("5432=5433").unwrap())
+            .unwrap();
+        let result = reg.add_tcp_forward(TcpForward::parse("5432=6000").unwrap());
+        assert!(matches!(
+            result,
+            Err(RegistrationError::TcpForwardExists(5432))
+        ));
+
+        assert!(reg.remove_tcp_forward(5432).is_ok());
+        assert!(reg.tcp_forwards().is_empty());
+        let result = reg.remove_tcp_forward(5432);
+        assert!(matches!(
+            result,
+            Err(RegistrationError::TcpForwardNotFound(5432))
+        ));
+    }
+
     // --- display_pattern / config_key ---
 
+    #[test]
+    fn tags_are_unique_and_checked() {
+        let mut reg = DomainRegistration::new(make_pattern("app.roxy"), vec![]);
+        reg.set_tags(vec!["work".into(), "api".into(), "work".into()]);
+        assert_eq!(reg.tags(), ["work", "api"]);
+        assert!(reg.has_tag("api"));
+        assert!(reg.validate().is_ok());
+
+        reg.set_tags(vec!["side project".into()]);
+        assert!(matches!(
+            reg.validate(),
+            Err(RegistrationError::InvalidTag(_))
+        ));
+
+        reg.set_description(Some("  ".into()));
+        assert_eq!(reg.description(), None);
+    }
+
     #[test]
     fn display_pattern_delegates_to_domain_pattern() {
         let exact =
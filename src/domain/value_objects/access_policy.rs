@@ -0,0 +1,154 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Which clients may use a domain: `any` (the default), `localhost-only`,
+/// `lan` (this machine and private network ranges), or a comma-separated
+/// allowlist of addresses and CIDR ranges such as
+/// `192.168.1.0/24, 10.0.0.7`. Localhost is always allowed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AccessPolicy {
+    #[default]
+    Any,
+    LocalhostOnly,
+    Lan,
+    Allow(Vec<IpNet>),
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid access policy '{0}': expected any, localhost-only, lan, or addresses/CIDR ranges")]
+pub struct AccessPolicyError(String);
+
+impl AccessPolicy {
+    pub fn is_default(&self) -> bool {
+        *self == AccessPolicy::Any
+    }
+
+    pub fn allows(&self, client: IpAddr) -> bool {
+        // IPv4 clients on a dual-stack socket show up as ::ffff:a.b.c.d
+        let client = client.to_canonical();
+        if client.is_loopback() {
+            return true;
+        }
+        match self {
+            AccessPolicy::Any => true,
+            AccessPolicy::LocalhostOnly => false,
+            AccessPolicy::Lan => is_lan(client),
+            AccessPolicy::Allow(nets) => nets.iter().any(|net| net.contains(&client)),
+        }
+    }
+}
+
+fn is_lan(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_link_local(),
+        IpAddr::V6(ip) => ip.is_unique_local() || ip.is_unicast_link_local(),
+    }
+}
+
+impl FromStr for AccessPolicy {
+    type Err = AccessPolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "any" => Ok(AccessPolicy::Any),
+            "localhost-only" => Ok(AccessPolicy::LocalhostOnly),
+            "lan" => Ok(AccessPolicy::Lan),
+            list => list
+                .split(',')
+                .map(|entry| {
+                    let entry = entry.trim();
+                    entry
+                        .parse::<IpNet>()
+                        .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                        .map_err(|_| AccessPolicyError(s.to_string()))
+                })
+                .collect::<Result<_, _>>()
+                .map(AccessPolicy::Allow),
+        }
+    }
+}
+
+impl fmt::Display for AccessPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessPolicy::Any => f.write_str("any"),
+            AccessPolicy::LocalhostOnly => f.write_str("localhost-only"),
+            AccessPolicy::Lan => f.write_str("lan"),
+            AccessPolicy::Allow(nets) => {
+                let nets: Vec<_> = nets.iter().map(ToString::to_string).collect();
+                f.write_str(&nets.join(", "))
+            }
+        }
+    }
+}
+
+impl Serialize for AccessPolicy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for AccessPolicy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn localhost_is_always_allowed() {
+        for policy in ["any", "localhost-only", "lan", "10.0.0.0/8"] {
+            let policy: AccessPolicy = policy.parse().unwrap();
+            assert!(policy.allows(ip("127.0.0.1")));
+            assert!(policy.allows(ip("::1")));
+            assert!(policy.allows(ip("::ffff:127.0.0.1")));
+        }
+        assert!(!AccessPolicy::LocalhostOnly.allows(ip("192.168.1.20")));
+    }
+
+    #[test]
+    fn lan_allows_private_ranges_only() {
+        let lan = AccessPolicy::Lan;
+        assert!(lan.allows(ip("192.168.1.20")));
+        assert!(lan.allows(ip("10.1.2.3")));
+        assert!(lan.allows(ip("::ffff:172.16.0.9")));
+        assert!(lan.allows(ip("fe80::1")));
+        assert!(lan.allows(ip("fd12::1")));
+        assert!(!lan.allows(ip("8.8.8.8")));
+        assert!(!lan.allows(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn allowlist_takes_addresses_and_ranges() {
+        let policy: AccessPolicy = "192.168.1.0/24, 10.0.0.7".parse().unwrap();
+        assert!(policy.allows(ip("192.168.1.99")));
+        assert!(policy.allows(ip("10.0.0.7")));
+        assert!(!policy.allows(ip("10.0.0.8")));
+        assert_eq!(policy.to_string(), "192.168.1.0/24, 10.0.0.7/32");
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!("everyone".parse::<AccessPolicy>().is_err());
+        assert!("10.0.0.0/8, nope".parse::<AccessPolicy>().is_err());
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// HTTP version a route speaks to its backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendProtocol {
+    /// HTTP/2 for gRPC requests, HTTP/1.1 for everything else.
+    #[default]
+    Auto,
+    /// Always HTTP/1.1.
+    H1,
+    /// HTTP/2 without TLS ("prior knowledge"), e.g. gRPC servers and
+    /// h2c dev servers.
+    H2c,
+}
+
+impl BackendProtocol {
+    pub fn is_default(&self) -> bool {
+        *self == BackendProtocol::Auto
+    }
+}
@@ -0,0 +1,30 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// HTTP basic auth users for a domain or route, configured as
+/// `user = "<password hash>"` pairs. An empty set on a route makes it
+/// public even if its domain asks for a login.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BasicAuth(BTreeMap<String, String>);
+
+impl BasicAuth {
+    pub fn is_public(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn password_hash(&self, user: &str) -> Option<&str> {
+        self.0.get(user).map(String::as_str)
+    }
+
+    /// Add a user or replace their password.
+    pub fn set_user(&mut self, user: impl Into<String>, password_hash: impl Into<String>) {
+        self.0.insert(user.into(), password_hash.into());
+    }
+
+    /// Returns whether the user existed.
+    pub fn remove_user(&mut self, user: &str) -> bool {
+        self.0.remove(user).is_some()
+    }
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Find/replace rule for textual response bodies, e.g. pointing
+/// `https://api.example.com` at `https://api.roxy`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BodyRewrite {
+    find: String,
+    replace: String,
+}
+
+#[derive(Debug, Error)]
+pub enum BodyRewriteError {
+    #[error("Body rewrite rule needs a non-empty 'find' string")]
+    EmptyFind,
+}
+
+impl BodyRewrite {
+    pub fn new(
+        find: impl Into<String>,
+        replace: impl Into<String>,
+    ) -> Result<Self, BodyRewriteError> {
+        let find = find.into();
+        if find.is_empty() {
+            return Err(BodyRewriteError::EmptyFind);
+        }
+        Ok(Self {
+            find,
+            replace: replace.into(),
+        })
+    }
+
+    /// Apply every rule in order.
+    pub fn apply_all(rules: &[BodyRewrite], text: &str) -> String {
+        rules.iter().fold(text.to_string(), |text, rule| {
+            text.replace(&rule.find, &rule.replace)
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for BodyRewrite {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Raw {
+            find: String,
+            #[serde(default)]
+            replace: String,
+        }
+
+        let raw = Raw::deserialize(deserializer)?;
+        Self::new(raw.find, raw.replace).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_apply_in_order() {
+        let rules = [
+            BodyRewrite::new("https://api.example.com", "https://api.roxy").unwrap(),
+            BodyRewrite::new("api.roxy", "api2.roxy").unwrap(),
+        ];
+        assert_eq!(
+            BodyRewrite::apply_all(&rules, r#"fetch("https://api.example.com/v1")"#),
+            r#"fetch("https://api2.roxy/v1")"#
+        );
+    }
+
+    #[test]
+    fn empty_find_is_rejected() {
+        assert!(BodyRewrite::new("", "x").is_err());
+
+        let result: Result<BodyRewrite, _> = toml::from_str("find = \"\"");
+        assert!(result.is_err());
+    }
+}
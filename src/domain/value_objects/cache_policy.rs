@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// How a static route handles browser caching and conditional requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CachePolicy {
+    /// Send `Last-Modified` and answer conditional requests with 304.
+    /// Browsers may reuse cached files without asking.
+    #[default]
+    Default,
+    /// Like `Default`, but tell the browser to revalidate every time.
+    Revalidate,
+    /// No validators and no caching - every request gets the full file.
+    Off,
+}
+
+impl CachePolicy {
+    pub fn is_default(&self) -> bool {
+        *self == CachePolicy::Default
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// `SameSite` cookie attribute values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl SameSite {
+    fn as_str(self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+/// Rules applied to `Set-Cookie` headers from a backend, so cookies
+/// meant for a staging domain are stored for the local one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CookieRewrite {
+    /// `Domain` attribute rewrites, e.g. `".example.com" = ".myapp.roxy"`.
+    /// `*` matches any domain; an empty replacement drops the attribute,
+    /// making the cookie host-only.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub domains: BTreeMap<String, String>,
+
+    /// Add (`true`) or strip (`false`) the `Secure` flag.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secure: Option<bool>,
+
+    /// Replace the `SameSite` attribute.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub same_site: Option<SameSite>,
+}
+
+impl CookieRewrite {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Rewrite one `Set-Cookie` header value.
+    pub fn apply(&self, set_cookie: &str) -> String {
+        let mut parts = set_cookie.split(';');
+        let mut out = vec![parts.next().unwrap_or_default().trim().to_string()];
+
+        for attr in parts.map(str::trim).filter(|a| !a.is_empty()) {
+            let (name, value) = match attr.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (attr, None),
+            };
+
+            if name.eq_ignore_ascii_case("domain") {
+                match self.rewrite_domain(value.unwrap_or_default()) {
+                    Some("") => {}
+                    Some(domain) => out.push(format!("Domain={}", domain)),
+                    None => out.push(attr.to_string()),
+                }
+            } else if name.eq_ignore_ascii_case("secure") && self.secure.is_some() {
+                // Re-added below if wanted
+            } else if name.eq_ignore_ascii_case("samesite") && self.same_site.is_some() {
+                // Replaced below
+            } else {
+                out.push(attr.to_string());
+            }
+        }
+
+        if let Some(same_site) = self.same_site {
+            out.push(format!("SameSite={}", same_site.as_str()));
+        }
+        if self.secure == Some(true) {
+            out.push("Secure".to_string());
+        }
+        out.join("; ")
+    }
+
+    /// Replacement for a `Domain` value, if a rule matches. The leading
+    /// dot is optional on both sides.
+    fn rewrite_domain(&self, domain: &str) -> Option<&str> {
+        let bare = domain.trim_start_matches('.');
+        self.domains
+            .iter()
+            .find(|(from, _)| from.trim_start_matches('.').eq_ignore_ascii_case(bare))
+            .or_else(|| self.domains.get_key_value("*"))
+            .map(|(_, to)| to.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrite(domains: &[(&str, &str)]) -> CookieRewrite {
+        CookieRewrite {
+            domains: domains
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..CookieRewrite::default()
+        }
+    }
+
+    #[test]
+    fn rewrites_matching_domain() {
+        let r = rewrite(&[(".example.com", ".myapp.roxy")]);
+        assert_eq!(
+            r.apply("sid=abc; Path=/; Domain=example.com; HttpOnly"),
+            "sid=abc; Path=/; Domain=.myapp.roxy; HttpOnly"
+        );
+        assert_eq!(
+            r.apply("sid=abc; Domain=.other.com"),
+            "sid=abc; Domain=.other.com"
+        );
+    }
+
+    #[test]
+    fn wildcard_and_empty_replacement() {
+        let r = rewrite(&[("*", "")]);
+        assert_eq!(
+            r.apply("sid=abc; domain=.example.com; Path=/"),
+            "sid=abc; Path=/"
+        );
+    }
+
+    #[test]
+    fn adjusts_secure_and_same_site() {
+        let strip = CookieRewrite {
+            secure: Some(false),
+            same_site: Some(SameSite::Lax),
+            ..CookieRewrite::default()
+        };
+        assert_eq!(
+            strip.apply("sid=abc; Secure; SameSite=None; HttpOnly"),
+            "sid=abc; HttpOnly; SameSite=Lax"
+        );
+
+        let add = CookieRewrite {
+            secure: Some(true),
+            ..CookieRewrite::default()
+        };
+        assert_eq!(add.apply("sid=abc"), "sid=abc; Secure");
+        assert_eq!(add.apply("sid=abc; secure"), "sid=abc; Secure");
+    }
+
+    #[test]
+    fn empty_rules_keep_cookie() {
+        assert!(CookieRewrite::default().is_empty());
+        assert_eq!(CookieRewrite::default().apply("a=b; Path=/"), "a=b; Path=/");
+    }
+}
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+
+/// Cross-origin access rules for a domain, configured under
+/// `[domains."<name>".cors]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorsPolicy {
+    /// Origins allowed to call the domain. `*` allows any.
+    #[serde(default = "default_origins")]
+    pub allow_origins: Vec<String>,
+
+    #[serde(default = "default_methods")]
+    pub allow_methods: Vec<String>,
+
+    /// Request headers a preflight may ask for. Empty allows whatever
+    /// the browser asks for.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_headers: Vec<String>,
+
+    /// Let the browser send cookies and auth headers.
+    #[serde(default)]
+    pub allow_credentials: bool,
+
+    /// How long browsers may cache a preflight answer, in seconds.
+    #[serde(default = "default_max_age")]
+    pub max_age: u32,
+}
+
+fn default_origins() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+fn default_methods() -> Vec<String> {
+    ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn default_max_age() -> u32 {
+    600
+}
+
+impl Default for CorsPolicy {
+    fn default() -> Self {
+        Self {
+            allow_origins: default_origins(),
+            allow_methods: default_methods(),
+            allow_headers: Vec::new(),
+            allow_credentials: false,
+            max_age: default_max_age(),
+        }
+    }
+}
+
+impl CorsPolicy {
+    /// Value for `Access-Control-Allow-Origin` when `origin` makes a
+    /// request, or `None` if it isn't allowed. Browsers reject `*` on
+    /// credentialed requests, so the origin is echoed back instead.
+    pub fn allowed_origin(&self, origin: &str) -> Option<String> {
+        if self.allow_origins.iter().any(|o| o == "*") {
+            return Some(if self.allow_credentials {
+                origin.to_string()
+            } else {
+                "*".to_string()
+            });
+        }
+        self.allow_origins
+            .iter()
+            .find(|o| o.eq_ignore_ascii_case(origin))
+            .map(|_| origin.to_string())
+    }
+
+    /// Value for `Access-Control-Allow-Headers`, given the headers the
+    /// preflight asked for.
+    pub fn allowed_headers(&self, requested: Option<&str>) -> Option<String> {
+        if self.allow_headers.is_empty() {
+            requested.map(str::to_string)
+        } else {
+            Some(self.allow_headers.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(origins: &[&str], credentials: bool) -> CorsPolicy {
+        CorsPolicy {
+            allow_origins: origins.iter().map(|o| o.to_string()).collect(),
+            allow_credentials: credentials,
+            ..CorsPolicy::default()
+        }
+    }
+
+    #[test]
+    fn wildcard_allows_any_origin() {
+        let p = policy(&["*"], false);
+        assert_eq!(
+            p.allowed_origin("http://localhost:5173").as_deref(),
+            Some("*")
+        );
+    }
+
+    #[test]
+    fn credentials_echo_the_origin() {
+        let p = policy(&["*"], true);
+        assert_eq!(
+            p.allowed_origin("http://localhost:5173").as_deref(),
+            Some("http://localhost:5173")
+        );
+    }
+
+    #[test]
+    fn listed_origins_only() {
+        let p = policy(&["https://web.roxy"], false);
+        assert_eq!(
+            p.allowed_origin("https://web.roxy").as_deref(),
+            Some("https://web.roxy")
+        );
+        assert!(p.allowed_origin("https://evil.example").is_none());
+    }
+
+    #[test]
+    fn headers_are_echoed_unless_listed() {
+        let mut p = CorsPolicy::default();
+        assert_eq!(
+            p.allowed_headers(Some("content-type")).as_deref(),
+            Some("content-type")
+        );
+        assert!(p.allowed_headers(None).is_none());
+
+        p.allow_headers = vec!["Content-Type".into(), "Authorization".into()];
+        assert_eq!(
+            p.allowed_headers(Some("x-other")).as_deref(),
+            Some("Content-Type, Authorization")
+        );
+    }
+}
@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Certificate and key files served for a domain instead of one Roxy
+/// issues, e.g. a real wildcard certificate. `cert` may hold the whole
+/// chain, leaf first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomCert {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl CustomCert {
+    pub fn files(&self) -> [&PathBuf; 2] {
+        [&self.cert, &self.key]
+    }
+}
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Extra response headers configured for a route, e.g.
+/// `Cross-Origin-Opener-Policy = "same-origin"`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct CustomHeaders(BTreeMap<String, String>);
+
+#[derive(Debug, Error)]
+pub enum CustomHeaderError {
+    #[error("Invalid header name '{0}'")]
+    InvalidName(String),
+
+    #[error("Invalid value for header '{0}': control characters are not allowed")]
+    InvalidValue(String),
+}
+
+impl CustomHeaders {
+    pub fn new(headers: BTreeMap<String, String>) -> Result<Self, CustomHeaderError> {
+        for (name, value) in &headers {
+            if !is_valid_name(name) {
+                return Err(CustomHeaderError::InvalidName(name.clone()));
+            }
+            if value.chars().any(|c| c.is_control() && c != '\t') {
+                return Err(CustomHeaderError::InvalidValue(name.clone()));
+            }
+        }
+        Ok(Self(headers))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+/// RFC 9110 token characters.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
+
+impl<'de> Deserialize<'de> for CustomHeaders {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let headers = BTreeMap::<String, String>::deserialize(deserializer)?;
+        Self::new(headers).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn accepts_valid_headers() {
+        let h = CustomHeaders::new(headers(&[
+            ("Cross-Origin-Opener-Policy", "same-origin"),
+            ("Service-Worker-Allowed", "/"),
+        ]))
+        .unwrap();
+        assert_eq!(h.iter().count(), 2);
+    }
+
+    #[test]
+    fn rejects_invalid_name() {
+        let err = CustomHeaders::new(headers(&[("Bad Header", "x")])).unwrap_err();
+        assert!(matches!(err, CustomHeaderError::InvalidName(_)));
+        assert!(CustomHeaders::new(headers(&[("", "x")])).is_err());
+    }
+
+    #[test]
+    fn rejects_control_characters_in_value() {
+        let err = CustomHeaders::new(headers(&[("X-Test", "a\r\nb")])).unwrap_err();
+        assert!(matches!(err, CustomHeaderError::InvalidValue(_)));
+    }
+}
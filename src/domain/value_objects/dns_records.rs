@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+/// Records a domain answers besides its address.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DnsRecords {
+    /// TXT values, e.g. for tools that verify domain ownership.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub txt: Vec<String>,
+
+    /// Answer as an alias of this name instead of with an address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cname: Option<String>,
+}
+
+impl DnsRecords {
+    pub fn is_default(&self) -> bool {
+        *self == DnsRecords::default()
+    }
+}
+
+/// Whether `name` can be a CNAME target: dot-separated labels of
+/// letters, digits and hyphens, with an optional trailing dot.
+pub(crate) fn is_host_name(name: &str) -> bool {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    !name.is_empty()
+        && name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_txt_and_cname() {
+        let records: DnsRecords =
+            toml::from_str("txt = [\"verify=abc\"]\ncname = \"app.example.com\"").unwrap();
+        assert_eq!(records.txt, vec!["verify=abc"]);
+        assert_eq!(records.cname.as_deref(), Some("app.example.com"));
+        assert!(DnsRecords::default().is_default());
+    }
+
+    #[test]
+    fn checks_host_names() {
+        assert!(is_host_name("app.example.com"));
+        assert!(is_host_name("app.example.com."));
+        assert!(is_host_name("_acme.roxy"));
+        assert!(!is_host_name(""));
+        assert!(!is_host_name("app..roxy"));
+        assert!(!is_host_name("-app.roxy"));
+        assert!(!is_host_name("http://app.roxy"));
+    }
+}
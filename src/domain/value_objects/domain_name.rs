@@ -52,6 +52,27 @@ impl DomainName {
         Ok(Self(name))
     }
 
+    /// The `.roxy` name behind a `<name>.localhost` hostname, for the
+    /// `.localhost` compatibility mode. Browsers resolve those to
+    /// loopback on their own.
+    pub fn from_localhost(hostname: &str) -> Option<Self> {
+        let name = hostname.strip_suffix(".localhost")?;
+        Self::new(format!("{}.roxy", name)).ok()
+    }
+
+    /// The `.roxy` name behind a `<name>.local` hostname published over
+    /// mDNS.
+    pub fn from_local(hostname: &str) -> Option<Self> {
+        let name = hostname.strip_suffix(".local")?;
+        Self::new(format!("{}.roxy", name)).ok()
+    }
+
+    /// The name published for this domain over mDNS.
+    pub fn local_name(&self) -> String {
+        let name = self.0.strip_suffix(".roxy").unwrap_or(&self.0);
+        format!("{}.local", name)
+    }
+
     pub fn as_str(&self) -> &str {
         &self.0
     }
@@ -102,4 +123,32 @@ mod tests {
         assert!(DomainName::new("app-.roxy").is_err()); // Ends with hyphen
         assert!(DomainName::new("app_name.roxy").is_err()); // Underscore
     }
+
+    #[test]
+    fn test_localhost_alias() {
+        assert_eq!(
+            DomainName::from_localhost("app.localhost")
+                .unwrap()
+                .as_str(),
+            "app.roxy"
+        );
+        assert_eq!(
+            DomainName::from_localhost("blog.app.localhost")
+                .unwrap()
+                .as_str(),
+            "blog.app.roxy"
+        );
+        assert!(DomainName::from_localhost("localhost").is_none());
+        assert!(DomainName::from_localhost("app.roxy").is_none());
+        assert!(DomainName::from_localhost("-app.localhost").is_none());
+    }
+
+    #[test]
+    fn test_local_alias() {
+        let name = DomainName::new("app.roxy").unwrap();
+        assert_eq!(name.local_name(), "app.local");
+        assert_eq!(DomainName::from_local("app.local").unwrap(), name);
+        assert!(DomainName::from_local("app.localhost").is_none());
+        assert!(DomainName::from_local("local").is_none());
+    }
 }
@@ -0,0 +1,110 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Fail a share of a route's requests on purpose, written `5% 503`, to
+/// test a frontend's retry and fallback handling. The status defaults
+/// to 503 when left out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fault {
+    /// In hundredths of a percent, so `0.5%` is 50
+    rate: u32,
+    status: u16,
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid fault '{0}': expected a rate and an error status, e.g. 5% 503")]
+pub struct FaultError(String);
+
+impl Fault {
+    const ALWAYS: u32 = 10_000;
+
+    pub fn parse(s: &str) -> Result<Self, FaultError> {
+        let invalid = || FaultError(s.to_string());
+        let (percent, status) = s.split_once('%').ok_or_else(invalid)?;
+
+        let percent: f64 = percent.trim().parse().map_err(|_| invalid())?;
+        if !(0.0..=100.0).contains(&percent) {
+            return Err(invalid());
+        }
+        let status = match status.trim() {
+            "" => 503,
+            status => status.parse().map_err(|_| invalid())?,
+        };
+        if !(400..=599).contains(&status) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            rate: (percent * 100.0).round() as u32,
+            status,
+        })
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Whether the request drawing `random` should fail.
+    pub fn strikes(&self, random: u64) -> bool {
+        random % u64::from(Self::ALWAYS) < u64::from(self.rate)
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let whole = self.rate / 100;
+        match self.rate % 100 {
+            0 => write!(f, "{}%", whole)?,
+            cents if cents % 10 == 0 => write!(f, "{}.{}%", whole, cents / 10)?,
+            cents => write!(f, "{}.{:02}%", whole, cents)?,
+        }
+        write!(f, " {}", self.status)
+    }
+}
+
+impl Serialize for Fault {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Fault {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rate_and_status() {
+        assert_eq!(Fault::parse("5% 503").unwrap().to_string(), "5% 503");
+        assert_eq!(Fault::parse("0.5%500").unwrap().to_string(), "0.5% 500");
+        assert_eq!(Fault::parse("12.25 %").unwrap().to_string(), "12.25% 503");
+        assert!(Fault::parse("5").is_err());
+        assert!(Fault::parse("150% 503").is_err());
+        assert!(Fault::parse("5% 200").is_err());
+        assert!(Fault::parse("5% teapot").is_err());
+    }
+
+    #[test]
+    fn strikes_the_configured_share() {
+        let fault = Fault::parse("5% 503").unwrap();
+        let failed = (0..10_000).filter(|&random| fault.strikes(random)).count();
+        assert_eq!(failed, 500);
+
+        assert!(!Fault::parse("0%").unwrap().strikes(0));
+        assert!(Fault::parse("100%").unwrap().strikes(u64::MAX));
+    }
+}
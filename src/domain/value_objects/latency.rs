@@ -0,0 +1,100 @@
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Artificial delay for a route, written `300ms` or with jitter as
+/// `300ms±100ms` (`300ms+-100ms` works too), to test how a frontend
+/// copes with a slow network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Latency {
+    delay: Duration,
+    jitter: Duration,
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid latency '{0}': expected e.g. 300ms or 300ms±100ms")]
+pub struct LatencyError(String);
+
+impl Latency {
+    pub fn parse(s: &str) -> Result<Self, LatencyError> {
+        let invalid = || LatencyError(s.to_string());
+        let (delay, jitter) = match s.split_once('±').or_else(|| s.split_once("+-")) {
+            Some((delay, jitter)) => (delay, Some(jitter)),
+            None => (s, None),
+        };
+        let parse = |d: &str| humantime::parse_duration(d.trim()).map_err(|_| invalid());
+        Ok(Self {
+            delay: parse(delay)?,
+            jitter: jitter.map(parse).transpose()?.unwrap_or_default(),
+        })
+    }
+
+    /// The delay for one request, where `random` spreads it evenly
+    /// across the jitter range.
+    pub fn pick(&self, random: u64) -> Duration {
+        let min = self.delay.saturating_sub(self.jitter);
+        let max = self.delay + self.jitter;
+        let span = (max - min).as_nanos();
+        let offset = (u128::from(random) * (span + 1)) >> 64;
+        min + Duration::from_nanos(offset as u64)
+    }
+}
+
+impl fmt::Display for Latency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", humantime::format_duration(self.delay))?;
+        if !self.jitter.is_zero() {
+            write!(f, "±{}", humantime::format_duration(self.jitter))?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Latency {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Latency {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_delay_and_jitter() {
+        let latency = Latency::parse("300ms ± 100ms").unwrap();
+        assert_eq!(latency.to_string(), "300ms±100ms");
+        assert_eq!(Latency::parse("1s+-250ms").unwrap().to_string(), "1s±250ms");
+        assert_eq!(Latency::parse("2s").unwrap().to_string(), "2s");
+        assert!(Latency::parse("slow").is_err());
+        assert!(Latency::parse("300ms±").is_err());
+    }
+
+    #[test]
+    fn picks_within_the_jitter_range() {
+        let latency = Latency::parse("300ms±100ms").unwrap();
+        assert_eq!(latency.pick(0), Duration::from_millis(200));
+        assert_eq!(latency.pick(u64::MAX), Duration::from_millis(400));
+        let middle = latency.pick(u64::MAX / 2);
+        assert!(middle > Duration::from_millis(299) && middle < Duration::from_millis(301));
+
+        // Jitter larger than the delay doesn't go negative
+        let latency = Latency::parse("50ms±100ms").unwrap();
+        assert_eq!(latency.pick(0), Duration::ZERO);
+    }
+}
@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+/// How a route with several targets spreads requests across them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LoadBalancing {
+    /// Each target in turn.
+    #[default]
+    RoundRobin,
+    /// The target with the fewest requests in flight.
+    LeastConnections,
+}
+
+impl LoadBalancing {
+    pub fn is_default(&self) -> bool {
+        *self == LoadBalancing::RoundRobin
+    }
+}
@@ -1,12 +1,57 @@
+mod access_policy;
+mod backend_protocol;
+mod basic_auth;
+mod body_rewrite;
+mod cache_policy;
+mod cookie_rewrite;
+mod cors_policy;
+mod custom_cert;
+mod custom_headers;
+mod dns_records;
 mod domain_name;
 mod domain_pattern;
+mod fault;
+mod latency;
+mod load_balancing;
+mod log_level;
+mod path_glob;
 mod path_prefix;
+mod path_rewrite;
 pub mod port;
 mod proxy_target;
+mod rate_limit;
+mod retry_policy;
 mod route;
+mod route_options;
+mod symlink_policy;
+mod tcp_forward;
+mod traffic_split;
 
+pub use access_policy::AccessPolicy;
+pub use backend_protocol::BackendProtocol;
+pub use basic_auth::BasicAuth;
+pub use body_rewrite::BodyRewrite;
+pub use cache_policy::CachePolicy;
+pub use cookie_rewrite::CookieRewrite;
+pub use cors_policy::CorsPolicy;
+pub use custom_cert::CustomCert;
+pub use custom_headers::CustomHeaders;
+pub use dns_records::DnsRecords;
+pub(crate) use dns_records::is_host_name;
 pub use domain_name::DomainName;
 pub use domain_pattern::DomainPattern;
+pub use fault::Fault;
+pub use latency::Latency;
+pub use load_balancing::LoadBalancing;
+pub use log_level::LogLevel;
+pub use path_glob::PathGlob;
 pub use path_prefix::PathPrefix;
+pub use path_rewrite::PathRewrite;
 pub use proxy_target::ProxyTarget;
+pub use rate_limit::RateLimit;
+pub use retry_policy::RetryPolicy;
 pub use route::{Route, RouteTarget};
+pub use route_options::RouteOptions;
+pub use symlink_policy::SymlinkPolicy;
+pub use tcp_forward::TcpForward;
+pub use traffic_split::TrafficSplit;
@@ -0,0 +1,158 @@
+use std::fmt;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Shell-style pattern for paths under a static root, e.g. `*.key` or
+/// `private/**`. `*` and `?` stay within one name, `**` spans
+/// directories. A pattern without a `/` is matched against every name in
+/// the path, so `*.key` covers keys in all directories; one with a `/`
+/// is matched from the root. A path also matches when a directory it's
+/// in does.
+#[derive(Debug, Clone)]
+pub struct PathGlob {
+    glob: String,
+    /// Matched against the whole path rather than each name
+    anchored: bool,
+    pattern: Regex,
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid glob '{0}': expected a pattern like *.key or private/**")]
+pub struct PathGlobError(String);
+
+impl PathGlob {
+    pub fn parse(s: &str) -> Result<Self, PathGlobError> {
+        let glob = s.trim();
+        let body = glob.trim_start_matches('/').trim_end_matches('/');
+        if body.is_empty() {
+            return Err(PathGlobError(s.to_string()));
+        }
+
+        // `dir/**` covers the directory itself as well as what's in it
+        let (stem, below) = match body.strip_suffix("/**") {
+            Some(stem) if !stem.is_empty() => (stem, true),
+            _ => (body, false),
+        };
+
+        let mut regex = String::from("^");
+        let mut chars = stem.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if chars.peek() == Some(&'/') {
+                        chars.next();
+                        regex.push_str("(?:.*/)?");
+                    } else {
+                        regex.push_str(".*");
+                    }
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        if below {
+            regex.push_str("(?:/.*)?");
+        }
+        regex.push('$');
+
+        Ok(Self {
+            glob: glob.to_string(),
+            anchored: body.contains('/') || glob.starts_with('/'),
+            pattern: Regex::new(&regex).map_err(|_| PathGlobError(s.to_string()))?,
+        })
+    }
+
+    /// Whether `path`, relative to the root and separated by `/`, or a
+    /// directory it's in matches.
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.trim_matches('/');
+        if !self.anchored {
+            return path.split('/').any(|name| self.pattern.is_match(name));
+        }
+        path.match_indices('/')
+            .map(|(i, _)| &path[..i])
+            .chain([path])
+            .any(|prefix| self.pattern.is_match(prefix))
+    }
+}
+
+impl PartialEq for PathGlob {
+    fn eq(&self, other: &Self) -> bool {
+        self.glob == other.glob
+    }
+}
+
+impl Eq for PathGlob {}
+
+impl fmt::Display for PathGlob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.glob)
+    }
+}
+
+impl Serialize for PathGlob {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for PathGlob {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(glob: &str, path: &str) -> bool {
+        PathGlob::parse(glob).unwrap().matches(path)
+    }
+
+    #[test]
+    fn names_match_in_any_directory() {
+        assert!(matches("*.key", "server.key"));
+        assert!(matches("*.key", "certs/dev/server.key"));
+        assert!(matches("node_modules", "app/node_modules/react/index.js"));
+        assert!(!matches("*.key", "keys.txt"));
+        assert!(matches("secret?.txt", "secret1.txt"));
+        assert!(!matches("secret?.txt", "secret12.txt"));
+    }
+
+    #[test]
+    fn paths_match_from_the_root() {
+        assert!(matches("private/**", "private"));
+        assert!(matches("private/**", "private/notes/todo.md"));
+        assert!(!matches("private/**", "public/private/todo.md"));
+        assert!(matches("/build", "build/app.js"));
+        assert!(!matches("/build", "src/build"));
+        assert!(matches("**/*.map", "dist/js/app.js.map"));
+        assert!(matches("**/*.map", "app.js.map"));
+        assert!(!matches("docs/*.md", "docs/guide/intro.md"));
+    }
+
+    #[test]
+    fn special_characters_are_literal() {
+        assert!(matches("file(1).txt", "file(1).txt"));
+        assert!(!matches("a.b", "axb"));
+    }
+
+    #[test]
+    fn rejects_empty_globs() {
+        assert!(PathGlob::parse("").is_err());
+        assert!(PathGlob::parse(" / ").is_err());
+        assert_eq!(PathGlob::parse(" *.key ").unwrap().to_string(), "*.key");
+    }
+}
@@ -55,6 +55,28 @@ impl PathPrefix {
         }
     }
 
+    /// The part of a matching request path after this prefix, e.g.
+    /// `/api/users` becomes `/users` and `/api` becomes `/`.
+    pub fn strip(&self, request_path: &str) -> String {
+        if self.0 == "/" {
+            return request_path.to_string();
+        }
+        match request_path.strip_prefix(&self.0) {
+            Some("") => "/".to_string(),
+            Some(rest) => rest.to_string(),
+            None => request_path.to_string(),
+        }
+    }
+
+    /// Undo [`strip`](Self::strip) for a path produced by the backend.
+    pub fn restore(&self, backend_path: &str) -> String {
+        if self.0 == "/" {
+            backend_path.to_string()
+        } else {
+            format!("{}{}", self.0, backend_path)
+        }
+    }
+
     #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         self.0.len()
@@ -117,6 +139,19 @@ mod tests {
         assert!(!api.matches("/other"));
     }
 
+    #[test]
+    fn test_strip_and_restore() {
+        let api = PathPrefix::new("/api").unwrap();
+        assert_eq!(api.strip("/api/users"), "/users");
+        assert_eq!(api.strip("/api"), "/");
+        assert_eq!(api.strip("/api/"), "/");
+        assert_eq!(api.restore("/users"), "/api/users");
+
+        let root = PathPrefix::new("/").unwrap();
+        assert_eq!(root.strip("/users"), "/users");
+        assert_eq!(root.restore("/users"), "/users");
+    }
+
     #[test]
     fn test_nested_prefix_matching() {
         let api_v1 = PathPrefix::new("/api/v1").unwrap();
@@ -0,0 +1,155 @@
+use std::fmt;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Rewrite rule for request paths, written `FROM -> TO`, e.g.
+/// `/old/(.*) -> /new/$1`. `FROM` is a regular expression matched at
+/// the start of the path; the matched part is replaced by `TO`, where
+/// `$1` refers to the first group. A plain prefix like `/v1 -> /v2`
+/// works too.
+#[derive(Debug, Clone)]
+pub struct PathRewrite {
+    from: String,
+    to: String,
+    pattern: Regex,
+}
+
+#[derive(Debug, Error)]
+pub enum PathRewriteError {
+    #[error("Path rewrite must look like 'FROM -> TO', got '{0}'")]
+    Format(String),
+
+    #[error("Invalid path rewrite pattern: {0}")]
+    Pattern(#[from] regex::Error),
+}
+
+impl PathRewrite {
+    pub fn parse(rule: &str) -> Result<Self, PathRewriteError> {
+        let (from, to) = rule
+            .split_once("->")
+            .map(|(from, to)| (from.trim(), to.trim()))
+            .filter(|(from, to)| !from.is_empty() && !to.is_empty())
+            .ok_or_else(|| PathRewriteError::Format(rule.to_string()))?;
+        let pattern = Regex::new(&format!("^(?:{})", from.trim_start_matches('^')))?;
+        Ok(Self {
+            from: from.to_string(),
+            to: to.to_string(),
+            pattern,
+        })
+    }
+
+    /// Rewrite `path` with the first rule that matches it.
+    pub fn apply_first(rules: &[PathRewrite], path: &str) -> Option<String> {
+        rules.iter().find_map(|rule| {
+            rule.pattern
+                .is_match(path)
+                .then(|| rule.pattern.replace(path, rule.to.as_str()).into_owned())
+        })
+    }
+}
+
+impl PartialEq for PathRewrite {
+    fn eq(&self, other: &Self) -> bool {
+        self.from == other.from && self.to == other.to
+    }
+}
+
+impl Eq for PathRewrite {}
+
+impl fmt::Display for PathRewrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.from, self.to)
+    }
+}
+
+impl Serialize for PathRewrite {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for PathRewrite {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(rules: &[&str]) -> Vec<PathRewrite> {
+        rules
+            .iter()
+            .map(|r| PathRewrite::parse(r).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn rewrites_with_groups() {
+        let rules = rules(&["/old/(.*) -> /new/$1"]);
+        assert_eq!(
+            PathRewrite::apply_first(&rules, "/old/users/1"),
+            Some("/new/users/1".to_string())
+        );
+        assert_eq!(PathRewrite::apply_first(&rules, "/other/old/x"), None);
+    }
+
+    #[test]
+    fn plain_prefix_keeps_the_rest() {
+        let rules = rules(&["/v1 -> /api/v2"]);
+        assert_eq!(
+            PathRewrite::apply_first(&rules, "/v1/users"),
+            Some("/api/v2/users".to_string())
+        );
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = rules(&["/a/b -> /first", "/a -> /second"]);
+        assert_eq!(
+            PathRewrite::apply_first(&rules, "/a/b/c"),
+            Some("/first/c".to_string())
+        );
+        assert_eq!(
+            PathRewrite::apply_first(&rules, "/a/x"),
+            Some("/second/x".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        assert!(matches!(
+            PathRewrite::parse("/old"),
+            Err(PathRewriteError::Format(_))
+        ));
+        assert!(matches!(
+            PathRewrite::parse("/old/(.* -> /new"),
+            Err(PathRewriteError::Pattern(_))
+        ));
+    }
+
+    #[test]
+    fn roundtrips_through_toml() {
+        #[derive(Serialize, Deserialize)]
+        struct Wrapper {
+            path_rewrite: Vec<PathRewrite>,
+        }
+
+        let wrapper: Wrapper =
+            toml::from_str(r#"path_rewrite = ["^/old/(.*)  ->  /new/$1"]"#).unwrap();
+        assert_eq!(
+            toml::to_string(&wrapper).unwrap().trim(),
+            r#"path_rewrite = ["^/old/(.*) -> /new/$1"]"#
+        );
+    }
+}
@@ -23,7 +23,6 @@ impl Port {
         Ok(Self(port))
     }
 
-    #[cfg(test)]
     pub fn value(&self) -> u16 {
         self.0
     }
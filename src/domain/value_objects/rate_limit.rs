@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+/// Token-bucket limit for a domain: `requests_per_second` on average,
+/// with bursts of up to `burst` requests (defaults to one second's
+/// worth).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub requests_per_second: u32,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burst: Option<u32>,
+}
+
+impl RateLimit {
+    pub fn burst(&self) -> u32 {
+        self.burst.unwrap_or(self.requests_per_second).max(1)
+    }
+}
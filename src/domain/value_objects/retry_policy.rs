@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Longest wait between two attempts, however many retries are allowed.
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Retries for requests a backend refused, e.g. while a dev server
+/// restarts. Off unless `attempts` is set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Retries after the first try. 0 disables retrying.
+    #[serde(default)]
+    pub attempts: u32,
+
+    /// Milliseconds before the first retry, doubling after each one.
+    #[serde(default = "default_backoff")]
+    pub backoff: u64,
+
+    /// Methods safe to send twice. Only requests that never reached the
+    /// backend are retried, but the body is buffered to resend it.
+    #[serde(default = "default_methods")]
+    pub methods: Vec<String>,
+}
+
+fn default_backoff() -> u64 {
+    100
+}
+
+fn default_methods() -> Vec<String> {
+    vec!["GET".to_string(), "HEAD".to_string()]
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 0,
+            backoff: default_backoff(),
+            methods: default_methods(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether requests with `method` get retried at all.
+    pub fn applies_to(&self, method: &str) -> bool {
+        self.attempts > 0 && self.methods.iter().any(|m| m.eq_ignore_ascii_case(method))
+    }
+
+    /// How long to wait before retry number `retry` (starting at 0).
+    pub fn delay(&self, retry: u32) -> Duration {
+        let millis = self.backoff.saturating_mul(1 << retry.min(16));
+        Duration::from_millis(millis).min(MAX_BACKOFF)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disabled_by_default() {
+        let policy = RetryPolicy::default();
+        assert!(!policy.applies_to("GET"));
+    }
+
+    #[test]
+    fn applies_to_listed_methods_only() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            ..RetryPolicy::default()
+        };
+        assert!(policy.applies_to("GET"));
+        assert!(policy.applies_to("head"));
+        assert!(!policy.applies_to("POST"));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_a_cap() {
+        let policy = RetryPolicy {
+            attempts: 20,
+            backoff: 100,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(1), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(800));
+        assert_eq!(policy.delay(19), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn fills_in_defaults() {
+        let policy: RetryPolicy = toml::from_str("attempts = 2").unwrap();
+        assert_eq!(policy.attempts, 2);
+        assert_eq!(policy.backoff, 100);
+        assert_eq!(policy.methods, ["GET", "HEAD"]);
+    }
+}
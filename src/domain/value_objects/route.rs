@@ -1,5 +1,10 @@
+use super::domain_name::{DomainName, DomainNameError};
+use super::fault::{Fault, FaultError};
+use super::latency::{Latency, LatencyError};
 use super::path_prefix::{PathPrefix, PathPrefixError};
 use super::proxy_target::{ProxyTarget, ProxyTargetError};
+use super::route_options::RouteOptions;
+use super::traffic_split::{TrafficSplit, TrafficSplitError};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::path::PathBuf;
@@ -9,14 +14,26 @@ use thiserror::Error;
 pub struct Route {
     pub path: PathPrefix,
     pub target: RouteTarget,
+    pub options: RouteOptions,
 }
 
 #[derive(Debug, Clone)]
 pub enum RouteTarget {
     Proxy(ProxyTarget),
+    /// Several interchangeable backends, written `3000,3001,3002`.
+    LoadBalanced(Vec<ProxyTarget>),
     StaticFiles(PathBuf),
+    /// Several directories merged into one, written `/app/dist,/app/public`;
+    /// a file is served from the first one that has it.
+    MergedStaticFiles(Vec<PathBuf>),
+    /// Another registered domain, written `domain:old.roxy`, whose routes
+    /// answer the request without leaving the daemon.
+    Domain(DomainName),
 }
 
+/// Prefix marking a route target as another registered domain.
+const DOMAIN_PREFIX: &str = "domain:";
+
 #[derive(Debug, Error)]
 pub enum RouteTargetError {
     #[error("Path does not exist: {0}")]
@@ -27,6 +44,9 @@ pub enum RouteTargetError {
 
     #[error("Invalid proxy target: {0}")]
     InvalidProxyTarget(#[from] ProxyTargetError),
+
+    #[error("Invalid domain target: {0}")]
+    InvalidDomain(#[from] DomainNameError),
 }
 
 #[derive(Debug, Error)]
@@ -39,27 +59,94 @@ pub enum RouteError {
 
     #[error("Invalid route format: expected 'PATH=TARGET', got '{0}'")]
     Format(String),
+
+    #[error(
+        "Unknown route flag '{0}' (supported: strip, sticky, split=WEIGHTS, mirror=TARGET, latency=DELAY, fault=RATE% STATUS, cmd=COMMAND, cwd=DIR)"
+    )]
+    UnknownFlag(String),
+
+    #[error(transparent)]
+    Latency(#[from] LatencyError),
+
+    #[error(transparent)]
+    Fault(#[from] FaultError),
+
+    #[error(transparent)]
+    Split(#[from] TrafficSplitError),
+
+    #[error("Split '{split}' has a weight for {} targets, but the route has {targets}", split.target_count())]
+    SplitMismatch { split: TrafficSplit, targets: usize },
 }
 
 impl RouteTarget {
-    /// Parse target string: absolute path (starting with /) = static files, otherwise proxy
+    /// Parse target string: absolute path (starting with /) = static files,
+    /// several separated by commas = merged static files, `domain:NAME` =
+    /// another registered domain, otherwise proxy
     /// Note: To distinguish from PathPrefix, static file paths must exist on disk
     pub fn parse(s: &str) -> Result<Self, RouteTargetError> {
+        if let Some(name) = s.strip_prefix(DOMAIN_PREFIX) {
+            return Ok(Self::Domain(DomainName::new(name.trim())?));
+        }
+
         // If it starts with / and looks like a filesystem path, try static files
         if s.starts_with('/') {
-            let path = PathBuf::from(s);
-            if path.exists() {
-                if !path.is_dir() {
-                    return Err(RouteTargetError::NotADirectory(path));
-                }
-                return Ok(Self::StaticFiles(path.canonicalize().unwrap_or(path)));
+            if s.contains(',') {
+                let dirs = s
+                    .split(',')
+                    .map(|dir| Self::parse_dir(dir.trim()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                return Ok(Self::MergedStaticFiles(dirs));
             }
-            // Path doesn't exist - could be a typo, report it
-            return Err(RouteTargetError::PathNotFound(path));
+            return Self::parse_dir(s).map(Self::StaticFiles);
         }
 
-        // Otherwise it's a proxy target
-        Ok(Self::Proxy(ProxyTarget::parse(s)?))
+        // Otherwise it's one or more proxy targets
+        Self::parse_proxy(s).map_err(Into::into)
+    }
+
+    fn parse_dir(s: &str) -> Result<PathBuf, RouteTargetError> {
+        let path = PathBuf::from(s);
+        if path.exists() {
+            if !path.is_dir() {
+                return Err(RouteTargetError::NotADirectory(path));
+            }
+            return Ok(path.canonicalize().unwrap_or(path));
+        }
+        // Path doesn't exist - could be a typo, report it
+        Err(RouteTargetError::PathNotFound(path))
+    }
+
+    fn parse_proxy(s: &str) -> Result<Self, ProxyTargetError> {
+        if !s.contains(',') {
+            return ProxyTarget::parse(s).map(Self::Proxy);
+        }
+        let targets = s
+            .split(',')
+            .map(ProxyTarget::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::LoadBalanced(targets))
+    }
+
+    /// Every backend a proxy route sends requests to, none for static
+    /// files.
+    pub fn proxy_targets(&self) -> &[ProxyTarget] {
+        match self {
+            RouteTarget::Proxy(target) => std::slice::from_ref(target),
+            RouteTarget::LoadBalanced(targets) => targets,
+            RouteTarget::StaticFiles(_)
+            | RouteTarget::MergedStaticFiles(_)
+            | RouteTarget::Domain(_) => &[],
+        }
+    }
+
+    /// The directories a static route serves from, in the order they are
+    /// searched, none for proxies.
+    pub fn static_roots(&self) -> &[PathBuf] {
+        match self {
+            RouteTarget::StaticFiles(dir) => std::slice::from_ref(dir),
+            RouteTarget::MergedStaticFiles(dirs) => dirs,
+            RouteTarget::Proxy(_) | RouteTarget::LoadBalanced(_) | RouteTarget::Domain(_) => &[],
+        }
     }
 }
 
@@ -67,7 +154,16 @@ impl fmt::Display for RouteTarget {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             RouteTarget::Proxy(p) => write!(f, "{}", p),
+            RouteTarget::LoadBalanced(targets) => {
+                let targets: Vec<String> = targets.iter().map(ToString::to_string).collect();
+                write!(f, "{}", targets.join(","))
+            }
             RouteTarget::StaticFiles(p) => write!(f, "{}", p.display()),
+            RouteTarget::MergedStaticFiles(dirs) => {
+                let dirs: Vec<String> = dirs.iter().map(|d| d.display().to_string()).collect();
+                write!(f, "{}", dirs.join(","))
+            }
+            RouteTarget::Domain(domain) => write!(f, "{}{}", DOMAIN_PREFIX, domain.as_str()),
         }
     }
 }
@@ -90,32 +186,82 @@ impl<'de> Deserialize<'de> for RouteTarget {
 
         // For deserialization, we need to handle paths that may not exist yet
         // (e.g., loading old config). So we're more lenient here.
-        if s.starts_with('/') {
+        if s.starts_with('/') && s.contains(',') {
+            Ok(Self::MergedStaticFiles(
+                s.split(',').map(|dir| PathBuf::from(dir.trim())).collect(),
+            ))
+        } else if s.starts_with('/') {
             // Assume it's a static files path
             Ok(Self::StaticFiles(PathBuf::from(&s)))
-        } else {
-            ProxyTarget::parse(&s)
-                .map(Self::Proxy)
+        } else if let Some(name) = s.strip_prefix(DOMAIN_PREFIX) {
+            DomainName::new(name.trim())
+                .map(Self::Domain)
                 .map_err(serde::de::Error::custom)
+        } else {
+            Self::parse_proxy(&s).map_err(serde::de::Error::custom)
         }
     }
 }
 
 impl Route {
     pub fn new(path: PathPrefix, target: RouteTarget) -> Self {
-        Self { path, target }
+        Self {
+            path,
+            target,
+            options: RouteOptions::default(),
+        }
     }
 
-    /// Parse from CLI format: "PATH=TARGET" e.g., "/api=3001" or "/=3000"
+    /// Parse from CLI format: "PATH=TARGET" e.g., "/api=3001" or "/=3000",
+    /// optionally followed by flags: "/api=3001,3002;strip;split=90/10;sticky"
     pub fn parse(s: &str) -> Result<Self, RouteError> {
-        let (path_str, target_str) = s
+        let (path_str, rest) = s
             .split_once('=')
             .ok_or_else(|| RouteError::Format(s.to_string()))?;
+        let mut parts = rest.split(';');
+        let target_str = parts.next().unwrap_or_default();
 
         let path = PathPrefix::new(path_str)?;
         let target = RouteTarget::parse(target_str)?;
+        let mut route = Self::new(path, target);
+
+        for flag in parts {
+            match flag.trim().split_once('=') {
+                None if flag.trim() == "strip" => route.options.strip_prefix = true,
+                None if flag.trim() == "sticky" => route.options.sticky = true,
+                Some(("split", split)) => route.options.split = Some(TrafficSplit::parse(split)?),
+                Some(("mirror", mirror)) => {
+                    let mirror =
+                        ProxyTarget::parse(mirror.trim()).map_err(RouteTargetError::from)?;
+                    route.options.mirror = Some(mirror);
+                }
+                Some(("latency", latency)) => {
+                    route.options.latency = Some(Latency::parse(latency)?);
+                }
+                Some(("fault", fault)) => route.options.fault = Some(Fault::parse(fault)?),
+                Some(("cmd", cmd)) if !cmd.trim().is_empty() => {
+                    route.options.cmd = Some(cmd.trim().to_string());
+                }
+                Some(("cwd", cwd)) if !cwd.trim().is_empty() => {
+                    route.options.cwd = Some(cwd.trim().to_string());
+                }
+                _ => return Err(RouteError::UnknownFlag(flag.trim().to_string())),
+            }
+        }
+        route.check_split()?;
+        Ok(route)
+    }
 
-        Ok(Self { path, target })
+    /// A split needs exactly one weight per target.
+    pub fn check_split(&self) -> Result<(), RouteError> {
+        let targets = self.target.proxy_targets().len();
+        match &self.options.split {
+            Some(split) if split.target_count() != targets => Err(RouteError::SplitMismatch {
+                split: split.clone(),
+                targets,
+            }),
+            _ => Ok(()),
+        }
     }
 }
 
@@ -124,11 +270,21 @@ impl Serialize for Route {
     where
         S: serde::Serializer,
     {
-        use serde::ser::SerializeStruct;
-        let mut s = serializer.serialize_struct("Route", 2)?;
-        s.serialize_field("path", &self.path)?;
-        s.serialize_field("target", &self.target)?;
-        s.end()
+        // Options are flattened so each route stays a single flat table
+        #[derive(Serialize)]
+        struct RouteRef<'a> {
+            path: &'a PathPrefix,
+            target: &'a RouteTarget,
+            #[serde(flatten)]
+            options: &'a RouteOptions,
+        }
+
+        RouteRef {
+            path: &self.path,
+            target: &self.target,
+            options: &self.options,
+        }
+        .serialize(serializer)
     }
 }
 
@@ -141,12 +297,15 @@ impl<'de> Deserialize<'de> for Route {
         struct RouteHelper {
             path: PathPrefix,
             target: RouteTarget,
+            #[serde(flatten)]
+            options: RouteOptions,
         }
 
         let helper = RouteHelper::deserialize(deserializer)?;
         Ok(Self {
             path: helper.path,
             target: helper.target,
+            options: helper.options,
         })
     }
 }
@@ -154,6 +313,7 @@ impl<'de> Deserialize<'de> for Route {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::LogLevel;
 
     #[test]
     fn test_parse_proxy_route() {
@@ -178,6 +338,129 @@ mod tests {
         assert_eq!(proxy.port().value(), 3001);
     }
 
+    #[test]
+    fn test_parse_load_balanced_route() {
+        let route = Route::parse("/=3000,3001, 10.0.0.5:3002").unwrap();
+        let RouteTarget::LoadBalanced(targets) = &route.target else {
+            panic!("expected load-balanced target");
+        };
+        assert_eq!(targets.len(), 3);
+        assert_eq!(
+            route.target.to_string(),
+            "127.0.0.1:3000,127.0.0.1:3001,10.0.0.5:3002"
+        );
+        assert_eq!(route.target.proxy_targets(), targets.as_slice());
+
+        assert!(Route::parse("/=3000,").is_err());
+        assert!(Route::parse("/=3000,nope").is_err());
+    }
+
+    #[test]
+    fn test_load_balanced_target_roundtrips_through_toml() {
+        #[derive(Serialize, Deserialize)]
+        struct Wrapper {
+            target: RouteTarget,
+        }
+
+        let wrapper: Wrapper = toml::from_str(r#"target = "3000,3001""#).unwrap();
+        assert_eq!(wrapper.target.proxy_targets().len(), 2);
+        assert_eq!(
+            toml::to_string(&wrapper).unwrap().trim(),
+            r#"target = "127.0.0.1:3000,127.0.0.1:3001""#
+        );
+    }
+
+    #[test]
+    fn test_parse_domain_route() {
+        let route = Route::parse("/legacy=domain:Old.roxy;strip").unwrap();
+        let RouteTarget::Domain(domain) = &route.target else {
+            panic!("expected domain target");
+        };
+        assert_eq!(domain.as_str(), "old.roxy");
+        assert_eq!(route.target.to_string(), "domain:old.roxy");
+        assert!(route.target.proxy_targets().is_empty());
+
+        assert!(Route::parse("/=domain:").is_err());
+        assert!(Route::parse("/=domain:old.com").is_err());
+    }
+
+    #[test]
+    fn test_domain_target_roundtrips_through_toml() {
+        #[derive(Serialize, Deserialize)]
+        struct Wrapper {
+            target: RouteTarget,
+        }
+
+        let wrapper: Wrapper = toml::from_str(r#"target = "domain:old.roxy""#).unwrap();
+        assert!(matches!(wrapper.target, RouteTarget::Domain(_)));
+        assert_eq!(
+            toml::to_string(&wrapper).unwrap().trim(),
+            r#"target = "domain:old.roxy""#
+        );
+    }
+
+    #[test]
+    fn test_parse_strip_flag() {
+        let route = Route::parse("/api=3001;strip").unwrap();
+        assert!(route.options.strip_prefix);
+        assert_eq!(route.target.to_string(), "127.0.0.1:3001");
+        assert!(!Route::parse("/api=3001").unwrap().options.strip_prefix);
+        assert!(matches!(
+            Route::parse("/api=3001;bogus"),
+            Err(RouteError::UnknownFlag(flag)) if flag == "bogus"
+        ));
+    }
+
+    #[test]
+    fn test_parse_latency_flag() {
+        let route = Route::parse("/api=3001;strip;latency=300ms±100ms").unwrap();
+        assert!(route.options.strip_prefix);
+        assert_eq!(route.options.latency.unwrap().to_string(), "300ms±100ms");
+        assert!(matches!(
+            Route::parse("/api=3001;latency=soon"),
+            Err(RouteError::Latency(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_split_flags() {
+        let route = Route::parse("/=3000,3001;split=90/10;sticky").unwrap();
+        assert_eq!(route.options.split.unwrap().to_string(), "90/10");
+        assert!(route.options.sticky);
+        assert!(matches!(
+            Route::parse("/=3000;split=90/10"),
+            Err(RouteError::SplitMismatch { targets: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_mirror_flag() {
+        let route = Route::parse("/api=3001;mirror=4001").unwrap();
+        assert_eq!(route.options.mirror.unwrap().to_string(), "127.0.0.1:4001");
+        assert!(Route::parse("/api=3001;mirror=").is_err());
+    }
+
+    #[test]
+    fn test_parse_fault_flag() {
+        let route = Route::parse("/api=3001;fault=5% 502").unwrap();
+        assert_eq!(route.options.fault.unwrap().to_string(), "5% 502");
+        assert!(matches!(
+            Route::parse("/api=3001;fault=often"),
+            Err(RouteError::Fault(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_command_flags() {
+        let route = Route::parse("/=3000;cmd=PORT=3000 npm run dev;cwd=~/code/app").unwrap();
+        assert_eq!(route.options.cmd.as_deref(), Some("PORT=3000 npm run dev"));
+        assert_eq!(route.options.cwd.as_deref(), Some("~/code/app"));
+        assert!(matches!(
+            Route::parse("/=3000;cmd="),
+            Err(RouteError::UnknownFlag(_))
+        ));
+    }
+
     #[test]
     fn test_invalid_format() {
         assert!(Route::parse("no-equals-sign").is_err());
@@ -192,4 +475,65 @@ mod tests {
         let static_files = RouteTarget::StaticFiles(PathBuf::from("/var/www"));
         assert_eq!(static_files.to_string(), "/var/www");
     }
+
+    #[test]
+    fn test_parse_merged_static_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dist = tmp.path().join("dist");
+        let public = tmp.path().join("public");
+        std::fs::create_dir(&dist).unwrap();
+        std::fs::create_dir(&public).unwrap();
+
+        let target =
+            RouteTarget::parse(&format!("{},{}", dist.display(), public.display())).unwrap();
+        let roots = target.static_roots();
+        assert_eq!(
+            roots,
+            [dist.canonicalize().unwrap(), public.canonicalize().unwrap()]
+        );
+        assert!(target.proxy_targets().is_empty());
+
+        let written = target.to_string();
+        let reread: RouteTarget = toml::Value::String(written.clone()).try_into().unwrap();
+        assert!(matches!(&reread, RouteTarget::MergedStaticFiles(dirs) if dirs == roots));
+        assert_eq!(reread.to_string(), written);
+
+        assert!(matches!(
+            RouteTarget::parse(&format!("{},/does/not/exist", dist.display())),
+            Err(RouteTargetError::PathNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_route_options_roundtrip_through_toml() {
+        #[derive(Serialize, Deserialize)]
+        struct Wrapper {
+            routes: Vec<Route>,
+        }
+
+        let toml_str = r#"
+            [[routes]]
+            path = "/api"
+            target = "127.0.0.1:3001"
+            log_level = "debug"
+            preserve_header_case = true
+            rewrite = [{ find = "https://api.example.com", replace = "https://api.roxy" }]
+
+            [[routes]]
+            path = "/"
+            target = "127.0.0.1:3000"
+        "#;
+
+        let wrapper: Wrapper = toml::from_str(toml_str).unwrap();
+        assert_eq!(wrapper.routes[0].options.log_level, Some(LogLevel::Debug));
+        assert!(wrapper.routes[0].options.preserve_header_case);
+        assert_eq!(wrapper.routes[0].options.rewrite.len(), 1);
+        assert_eq!(wrapper.routes[1].options, RouteOptions::default());
+
+        let serialized = toml::to_string(&wrapper).unwrap();
+        assert!(serialized.contains("log_level = \"debug\""));
+        assert_eq!(serialized.matches("log_level").count(), 1);
+        assert_eq!(serialized.matches("preserve_header_case").count(), 1);
+        assert_eq!(serialized.matches("rewrite").count(), 1);
+    }
 }
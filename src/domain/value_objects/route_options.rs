@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+
+use super::backend_protocol::BackendProtocol;
+use super::basic_auth::BasicAuth;
+use super::body_rewrite::BodyRewrite;
+use super::cache_policy::CachePolicy;
+use super::cookie_rewrite::CookieRewrite;
+use super::custom_headers::CustomHeaders;
+use super::fault::Fault;
+use super::latency::Latency;
+use super::load_balancing::LoadBalancing;
+use super::log_level::LogLevel;
+use super::path_glob::PathGlob;
+use super::path_rewrite::PathRewrite;
+use super::proxy_target::ProxyTarget;
+use super::retry_policy::RetryPolicy;
+use super::symlink_policy::SymlinkPolicy;
+use super::traffic_split::TrafficSplit;
+
+/// Optional per-route behaviour. Every field has a sensible default so
+/// routes without options keep their compact `path`/`target` form in the
+/// config file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteOptions {
+    /// Log verbosity for requests handled by this route.
+    /// Overrides the domain-level setting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<LogLevel>,
+
+    /// Login required for this route, overriding the domain's. Empty
+    /// makes the route public.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<BasicAuth>,
+
+    /// Browser caching for static routes.
+    #[serde(default, skip_serializing_if = "CachePolicy::is_default")]
+    pub cache: CachePolicy,
+
+    /// Extra headers added to static file responses.
+    #[serde(default, skip_serializing_if = "CustomHeaders::is_empty")]
+    pub headers: CustomHeaders,
+
+    /// Serve files and directories whose names start with a dot, like
+    /// `.env` or `.git/`. Static routes hide them by default.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub dotfiles: bool,
+
+    /// Paths a static route never serves or lists, e.g. `*.key` or
+    /// `private/**`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<PathGlob>,
+
+    /// Which symlinks a static route follows: `never`, `within-root`
+    /// (the default) or `always`.
+    #[serde(default, skip_serializing_if = "SymlinkPolicy::is_default")]
+    pub follow_symlinks: SymlinkPolicy,
+
+    /// File names served for a directory on static routes, tried in
+    /// order, e.g. `["index.html", "index.htm"]`. Empty means
+    /// `index.html`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub index: Vec<String>,
+
+    /// Reload pages of a static route in the browser whenever a file
+    /// under its directory changes.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub live_reload: bool,
+
+    /// Accept files into a static route's directory, by `PUT` to their
+    /// path or as a multipart `POST` to a directory.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub uploads: bool,
+
+    /// Serve a static route over WebDAV as well, so it can be mounted
+    /// as a network drive. Read-only unless `uploads` is on.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub webdav: bool,
+
+    /// Page served with a 404 status for missing files on static routes,
+    /// relative to the route's directory, e.g. `errors/missing.html`.
+    /// Unset means `404.html` when there is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_found: Option<String>,
+
+    /// Milliseconds to wait for a backend connection, overriding
+    /// `daemon.connect_timeout`. 0 waits forever.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<u64>,
+
+    /// Milliseconds to wait for a backend's response headers, overriding
+    /// `daemon.request_timeout`. 0 waits forever.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_timeout: Option<u64>,
+
+    /// Milliseconds an event stream may go without sending anything
+    /// before it is closed, overriding `daemon.stream_idle_timeout`.
+    /// 0 keeps it open.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_idle_timeout: Option<u64>,
+
+    /// How requests are spread over a route with several targets.
+    #[serde(default, skip_serializing_if = "LoadBalancing::is_default")]
+    pub balance: LoadBalancing,
+
+    /// Weights that send each target a share of the traffic, overriding
+    /// `balance`, e.g. `90/10` to try a canary.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub split: Option<TrafficSplit>,
+
+    /// Keep each browser on the target it first got, with a cookie.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub sticky: bool,
+
+    /// Backend that gets a copy of every request, its responses thrown
+    /// away, to try a rewrite against real traffic.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<ProxyTarget>,
+
+    /// Shell command that starts the backend, run by the daemon when a
+    /// request arrives and nothing is listening yet, e.g. `npm run dev`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cmd: Option<String>,
+
+    /// Directory `cmd` runs in; `~` is the domain owner's home.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+
+    /// Path the daemon requests to check each target's health, e.g.
+    /// `/healthz`. Without one, a target is up if it accepts connections.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<String>,
+
+    /// Artificial delay before each request is handled, to simulate a
+    /// slow network.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<Latency>,
+
+    /// Share of requests failed with an error status instead of being
+    /// handled, to test how clients recover.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fault: Option<Fault>,
+
+    /// Retries for requests the backend refused.
+    #[serde(default, skip_serializing_if = "RetryPolicy::is_default")]
+    pub retry: RetryPolicy,
+
+    /// Remove the route's path from requests before proxying, so
+    /// `/api/users` reaches the backend as `/users`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub strip_prefix: bool,
+
+    /// Rules for the path sent to a proxied backend, applied after
+    /// `strip_prefix`. The first matching rule wins.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path_rewrite: Vec<PathRewrite>,
+
+    /// HTTP version spoken to a proxied backend.
+    #[serde(default, skip_serializing_if = "BackendProtocol::is_default")]
+    pub protocol: BackendProtocol,
+
+    /// Send headers to HTTP/1.1 backends spelled the way the client sent
+    /// them, for backends that compare header names case-sensitively.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub preserve_header_case: bool,
+
+    /// Send the backend the original `Host` (`myapp.roxy`) instead of
+    /// its own address, for frameworks that route on it.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub preserve_host: bool,
+
+    /// Answer CORS preflight requests without asking the backend, using
+    /// the domain's CORS policy (or a permissive default).
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub preflight: bool,
+
+    /// Keep proxied `GET` responses in memory as their `Cache-Control`
+    /// allows, revalidating with `ETag`/`Last-Modified`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub proxy_cache: bool,
+
+    /// Gzip/brotli-compress responses for clients that accept it, like
+    /// a CDN would.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub compress: bool,
+
+    /// Rewrites for `Set-Cookie` headers from proxied backends.
+    #[serde(default, skip_serializing_if = "CookieRewrite::is_empty")]
+    pub cookies: CookieRewrite,
+
+    /// Find/replace rules for textual response bodies from proxied
+    /// backends, applied in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rewrite: Vec<BodyRewrite>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+/// Which symlinks a static route follows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymlinkPolicy {
+    /// Symlinks are neither served nor listed.
+    Never,
+    /// Follow symlinks that point somewhere inside the route's directory.
+    #[default]
+    WithinRoot,
+    /// Follow every symlink, wherever it points. For build outputs that
+    /// link into a package store, like pnpm's or Bazel's.
+    Always,
+}
+
+impl SymlinkPolicy {
+    pub fn is_default(&self) -> bool {
+        *self == SymlinkPolicy::WithinRoot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Options {
+        follow_symlinks: SymlinkPolicy,
+    }
+
+    #[test]
+    fn uses_kebab_case_names() {
+        let options: Options = toml::from_str("follow_symlinks = \"within-root\"").unwrap();
+        assert_eq!(options.follow_symlinks, SymlinkPolicy::WithinRoot);
+        let options: Options = toml::from_str("follow_symlinks = \"always\"").unwrap();
+        assert_eq!(options.follow_symlinks, SymlinkPolicy::Always);
+        assert!(toml::from_str::<Options>("follow_symlinks = \"sometimes\"").is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::proxy_target::ProxyTarget;
+
+/// A raw TCP port the daemon listens on for a domain, and the backend
+/// its bytes are piped to, written `5432=5433` or `5432=127.0.0.1:5433`.
+/// Lets non-HTTP tools reach a service as `db.myapp.roxy:5432`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpForward {
+    port: u16,
+    target: ProxyTarget,
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid TCP forward '{0}': expected PORT=TARGET, e.g. 5432=127.0.0.1:5433")]
+pub struct TcpForwardError(String);
+
+impl TcpForward {
+    pub fn parse(s: &str) -> Result<Self, TcpForwardError> {
+        let invalid = || TcpForwardError(s.to_string());
+        let (port, target) = s.split_once('=').ok_or_else(invalid)?;
+        let port = port.trim().parse::<u16>().map_err(|_| invalid())?;
+        if port == 0 {
+            return Err(invalid());
+        }
+        let target = ProxyTarget::parse(target).map_err(|_| invalid())?;
+        Ok(Self { port, target })
+    }
+
+    /// The port Roxy listens on.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn target(&self) -> &ProxyTarget {
+        &self.target
+    }
+}
+
+impl fmt::Display for TcpForward {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.port, self.target)
+    }
+}
+
+impl Serialize for TcpForward {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for TcpForward {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_port_and_target() {
+        let forward = TcpForward::parse("5432=5433").unwrap();
+        assert_eq!(forward.port(), 5432);
+        assert_eq!(forward.to_string(), "5432=127.0.0.1:5433");
+        assert_eq!(
+            TcpForward::parse(" 6379 = redis.local:6380 ")
+                .unwrap()
+                .to_string(),
+            "6379=redis.local:6380"
+        );
+        assert!(TcpForward::parse("5432").is_err());
+        assert!(TcpForward::parse("0=5433").is_err());
+        assert!(TcpForward::parse("db=5433").is_err());
+        assert!(TcpForward::parse("5432=").is_err());
+    }
+}
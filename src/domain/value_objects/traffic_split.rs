@@ -0,0 +1,126 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Relative weights for a route's targets, in the same order, written
+/// `90/10` (or `90%/10%`). Used to send a share of traffic to a canary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrafficSplit(Vec<u32>);
+
+#[derive(Debug, Error)]
+#[error("Invalid split '{0}': expected a weight per target, e.g. 90/10")]
+pub struct TrafficSplitError(String);
+
+impl TrafficSplit {
+    pub fn parse(s: &str) -> Result<Self, TrafficSplitError> {
+        let invalid = || TrafficSplitError(s.to_string());
+        let weights = s
+            .split('/')
+            .map(|w| w.trim().trim_end_matches('%').trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        if weights.len() < 2 || weights.iter().all(|&w| w == 0) {
+            return Err(invalid());
+        }
+        Ok(Self(weights))
+    }
+
+    /// How many targets the split is for.
+    pub fn target_count(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Index of the target for the request drawing `random`, among those
+    /// `usable` accepts, or among all of them if it accepts none with a
+    /// weight.
+    pub fn pick(&self, random: u64, usable: impl Fn(usize) -> bool) -> usize {
+        let weight = |i: usize, only_usable: bool| {
+            if only_usable && !usable(i) {
+                0
+            } else {
+                u64::from(self.0[i])
+            }
+        };
+        let mut only_usable = true;
+        let mut total: u64 = (0..self.target_count()).map(|i| weight(i, true)).sum();
+        if total == 0 {
+            only_usable = false;
+            total = self.0.iter().copied().map(u64::from).sum();
+        }
+
+        let mut point = random % total;
+        for i in 0..self.target_count() {
+            let w = weight(i, only_usable);
+            if point < w {
+                return i;
+            }
+            point -= w;
+        }
+        unreachable!("point is below the total weight")
+    }
+}
+
+impl fmt::Display for TrafficSplit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let weights: Vec<String> = self.0.iter().map(ToString::to_string).collect();
+        write!(f, "{}", weights.join("/"))
+    }
+}
+
+impl Serialize for TrafficSplit {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for TrafficSplit {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_weights() {
+        assert_eq!(TrafficSplit::parse("90/10").unwrap().to_string(), "90/10");
+        assert_eq!(
+            TrafficSplit::parse("90% / 10%").unwrap().to_string(),
+            "90/10"
+        );
+        assert_eq!(TrafficSplit::parse("1/1/2").unwrap().target_count(), 3);
+        assert!(TrafficSplit::parse("100").is_err());
+        assert!(TrafficSplit::parse("0/0").is_err());
+        assert!(TrafficSplit::parse("90/ten").is_err());
+    }
+
+    #[test]
+    fn picks_by_weight() {
+        let split = TrafficSplit::parse("90/10").unwrap();
+        let canary = (0..100)
+            .filter(|&random| split.pick(random, |_| true) == 1)
+            .count();
+        assert_eq!(canary, 10);
+    }
+
+    #[test]
+    fn skips_unusable_targets_unless_none_are_left() {
+        let split = TrafficSplit::parse("90/10").unwrap();
+        assert!((0..100).all(|random| split.pick(random, |i| i == 1) == 1));
+        assert_eq!(split.pick(5, |_| false), 0);
+
+        // A zero weight never gets traffic, even as the last one up
+        let split = TrafficSplit::parse("0/100").unwrap();
+        assert_eq!(split.pick(7, |i| i == 0), 1);
+    }
+}
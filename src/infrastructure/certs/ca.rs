@@ -1,21 +1,36 @@
-use rcgen::{BasicConstraints, CertificateParams, IsCa, Issuer, KeyPair, PKCS_ECDSA_P256_SHA256};
+use rcgen::{BasicConstraints, CertificateParams, IsCa, Issuer, KeyPair};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use time::{Duration, OffsetDateTime};
+use x509_parser::pem::parse_x509_pem;
 
-use super::CertError;
+use super::generator::CA_COMMON_NAME;
+use super::{CertError, CertsConfig};
 
 /// Roxy Root Certificate Authority
 ///
 /// This CA is used to sign all domain certificates. Users only need to trust
 /// the CA once, and all domain certificates will be automatically trusted.
+/// It is either generated by Roxy or imported from another tool, such as
+/// mkcert, whose CA is already trusted.
 pub struct RootCA {
     data_dir: PathBuf,
+    settings: CertsConfig,
 }
 
 impl RootCA {
     /// Create a RootCA with the given base directory
     pub fn new(data_dir: PathBuf) -> Self {
-        Self { data_dir }
+        Self {
+            data_dir,
+            settings: CertsConfig::default(),
+        }
+    }
+
+    /// Generate the CA with the validity and key type of `settings`.
+    pub fn with_settings(mut self, settings: &CertsConfig) -> Self {
+        self.settings = settings.clone();
+        self
     }
 
     /// Path to the CA certificate
@@ -35,35 +50,68 @@ impl RootCA {
 
     /// Generate a new Root CA certificate
     pub fn generate(&self) -> Result<(), CertError> {
-        // Ensure directory exists
-        fs::create_dir_all(&self.data_dir).map_err(|e| CertError::WriteError {
-            path: self.data_dir.clone(),
-            source: e,
-        })?;
+        let key_pair = self.settings.key_type.generate()?;
 
-        // Generate ECDSA P-256 key pair
-        let key_pair = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256)
-            .map_err(|e| CertError::GenerationError(e.to_string()))?;
-
-        // Configure CA certificate parameters (shared DN + validity + key usage)
+        // Configure CA certificate parameters (shared DN + key usage)
         let mut params = super::generator::build_ca_cert_params();
         params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.not_after = OffsetDateTime::now_utc() + Duration::days(self.settings.ca_days.into());
 
         // Generate the self-signed CA certificate
         let cert = params
             .self_signed(&key_pair)
             .map_err(|e| CertError::GenerationError(e.to_string()))?;
 
+        self.save(&cert.pem(), &key_pair.serialize_pem())
+    }
+
+    /// Use the CA certificate and key at `cert` and `key` instead of
+    /// generating one, replacing any existing CA. Both are copied.
+    pub fn import(&self, cert: &Path, key: &Path) -> Result<(), CertError> {
+        let read = |path: &Path| {
+            fs::read_to_string(path).map_err(|e| CertError::ReadError {
+                path: path.to_path_buf(),
+                source: e,
+            })
+        };
+        let (cert_pem, key_pem) = (read(cert)?, read(key)?);
+        check_ca_pair(&cert_pem, &key_pem)?;
+        self.save(&cert_pem, &key_pem)
+    }
+
+    /// Whether the CA was imported rather than generated by Roxy.
+    pub fn is_imported(&self) -> bool {
+        let Ok(pem) = fs::read(self.cert_path()) else {
+            return false;
+        };
+        let Ok((_, pem)) = parse_x509_pem(&pem) else {
+            return false;
+        };
+        let Ok(cert) = pem.parse_x509() else {
+            return false;
+        };
+        let common_name = cert.subject().iter_common_name().next();
+        common_name.and_then(|cn| cn.as_str().ok()) != Some(CA_COMMON_NAME)
+    }
+
+    /// Write the CA certificate and key, the key readable by its owner only.
+    fn save(&self, cert_pem: &str, key_pem: &str) -> Result<(), CertError> {
+        // Ensure directory exists
+        fs::create_dir_all(&self.data_dir).map_err(|e| CertError::WriteError {
+            path: self.data_dir.clone(),
+            source: e,
+        })?;
+
         // Save certificate
         let cert_path = self.cert_path();
-        fs::write(&cert_path, cert.pem()).map_err(|e| CertError::WriteError {
+        fs::write(&cert_path, cert_pem).map_err(|e| CertError::WriteError {
             path: cert_path.clone(),
             source: e,
         })?;
 
         // Save private key with restricted permissions
         let key_path = self.key_path();
-        fs::write(&key_path, key_pair.serialize_pem()).map_err(|e| CertError::WriteError {
+        fs::write(&key_path, key_pem).map_err(|e| CertError::WriteError {
             path: key_path.clone(),
             source: e,
         })?;
@@ -105,10 +153,11 @@ impl RootCA {
         params: CertificateParams,
         key_pair: &KeyPair,
     ) -> Result<String, CertError> {
-        let ca_key_pair = self.load_key_pair()?;
-
-        let ca_params = super::generator::build_ca_cert_params();
-        let issuer = Issuer::from_params(&ca_params, &ca_key_pair);
+        let cert_pem = fs::read_to_string(self.cert_path()).map_err(|e| CertError::ReadError {
+            path: self.cert_path(),
+            source: e,
+        })?;
+        let issuer = issuer_from_pem(&cert_pem, self.load_key_pair()?)?;
 
         // Sign the domain certificate
         let cert = params
@@ -141,6 +190,34 @@ impl RootCA {
     }
 }
 
+/// Signer for certificates issued by the CA `cert_pem`, taking its
+/// subject and key identifier from the certificate itself.
+pub fn issuer_from_pem(
+    cert_pem: &str,
+    key_pair: KeyPair,
+) -> Result<Issuer<'static, KeyPair>, CertError> {
+    Issuer::from_ca_cert_pem(cert_pem, key_pair)
+        .map_err(|e| CertError::InvalidCertificate(e.to_string()))
+}
+
+/// Check that `cert_pem` is a CA certificate and `key_pem` its key.
+fn check_ca_pair(cert_pem: &str, key_pem: &str) -> Result<(), CertError> {
+    let invalid = |msg: &str| CertError::InvalidCertificate(msg.to_string());
+    let (_, pem) =
+        parse_x509_pem(cert_pem.as_bytes()).map_err(|_| invalid("not a PEM certificate"))?;
+    let cert = pem
+        .parse_x509()
+        .map_err(|e| CertError::InvalidCertificate(e.to_string()))?;
+    if !cert.is_ca() {
+        return Err(invalid("not a CA certificate"));
+    }
+    let key = KeyPair::from_pem(key_pem).map_err(|_| invalid("not a PEM private key"))?;
+    if key.public_key_raw() != cert.public_key().subject_public_key.data.as_ref() {
+        return Err(invalid("the private key does not belong to the CA"));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -151,4 +228,63 @@ mod tests {
         assert!(ca.cert_path().to_string_lossy().contains("ca.crt"));
         assert!(ca.key_path().to_string_lossy().contains("ca.key"));
     }
+
+    /// A CA like mkcert's: its own name and an RSA key.
+    fn external_ca(dir: &Path, is_ca: bool) -> (PathBuf, PathBuf) {
+        let key = KeyPair::generate_for(&rcgen::PKCS_RSA_SHA256).unwrap();
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "mkcert dev@laptop");
+        if is_ca {
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        }
+        let cert = params.self_signed(&key).unwrap();
+        let (cert_path, key_path) = (dir.join("rootCA.pem"), dir.join("rootCA-key.pem"));
+        fs::write(&cert_path, cert.pem()).unwrap();
+        fs::write(&key_path, key.serialize_pem()).unwrap();
+        (cert_path, key_path)
+    }
+
+    #[test]
+    fn imports_an_external_ca_and_signs_with_it() {
+        let temp = tempfile::tempdir().unwrap();
+        let ca = RootCA::new(temp.path().join("data"));
+        ca.generate().unwrap();
+        assert!(!ca.is_imported());
+
+        let (cert, key) = external_ca(temp.path(), true);
+        ca.import(&cert, &key).unwrap();
+        assert!(ca.is_imported());
+        assert_eq!(fs::read(ca.cert_path()).unwrap(), fs::read(&cert).unwrap());
+
+        let leaf_key = KeyPair::generate().unwrap();
+        let params = CertificateParams::new(vec!["myapp.roxy".to_string()]).unwrap();
+        let leaf = ca.sign_certificate(params, &leaf_key).unwrap();
+        let (_, pem) = parse_x509_pem(leaf.as_bytes()).unwrap();
+        let leaf = pem.parse_x509().unwrap();
+        let issuer = leaf.issuer().iter_common_name().next().unwrap();
+        assert_eq!(issuer.as_str().unwrap(), "mkcert dev@laptop");
+    }
+
+    #[test]
+    fn rejects_a_mismatched_key_or_a_non_ca() {
+        let temp = tempfile::tempdir().unwrap();
+        let ca = RootCA::new(temp.path().join("data"));
+        let (cert, _) = external_ca(temp.path(), true);
+        let other = temp.path().join("other");
+        fs::create_dir(&other).unwrap();
+        let (_, other_key) = external_ca(&other, true);
+        assert!(matches!(
+            ca.import(&cert, &other_key),
+            Err(CertError::InvalidCertificate(_))
+        ));
+
+        let (leaf, leaf_key) = external_ca(&other, false);
+        assert!(matches!(
+            ca.import(&leaf, &leaf_key),
+            Err(CertError::InvalidCertificate(_))
+        ));
+        assert!(!ca.exists());
+    }
 }
@@ -0,0 +1,174 @@
+//! Certificates the user brings for a domain instead of ones Roxy
+//! issues, e.g. a real wildcard certificate for `*.dev.example.com`.
+
+use std::fs;
+use std::path::Path;
+
+use rustls::crypto::aws_lc_rs;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::sign::CertifiedKey;
+use time::OffsetDateTime;
+use x509_parser::extensions::GeneralName;
+
+use super::CertError;
+use super::generator::not_after;
+use crate::domain::CustomCert;
+
+/// A custom certificate read from disk, its key checked against it.
+#[derive(Debug)]
+pub struct LoadedCert {
+    pub certified: CertifiedKey,
+    /// DNS names the certificate covers, wildcards included.
+    pub names: Vec<String>,
+    pub not_after: OffsetDateTime,
+}
+
+/// Read a custom certificate chain and its key, and check they belong
+/// together.
+pub fn load(cert: &CustomCert) -> Result<LoadedCert, CertError> {
+    let invalid = |msg: String| CertError::InvalidCertificate(msg);
+    let cert_pem = read(&cert.cert)?;
+    let key_pem = read(&cert.key)?;
+
+    let chain = CertificateDer::pem_slice_iter(&cert_pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| invalid(format!("{}: {}", cert.cert.display(), e)))?;
+    let Some(leaf) = chain.first() else {
+        return Err(invalid(format!(
+            "{}: no PEM certificate",
+            cert.cert.display()
+        )));
+    };
+    let (_, parsed) = x509_parser::parse_x509_certificate(leaf)
+        .map_err(|e| invalid(format!("{}: {}", cert.cert.display(), e)))?;
+    let names = dns_names(&parsed);
+    let not_after = parsed.validity().not_after.to_datetime();
+
+    let key = PrivateKeyDer::from_pem_slice(&key_pem)
+        .map_err(|_| invalid(format!("{}: no PEM private key", cert.key.display())))?;
+    let certified =
+        CertifiedKey::from_der(chain, key, &aws_lc_rs::default_provider()).map_err(|e| {
+            invalid(format!(
+                "{} does not match {}: {}",
+                cert.key.display(),
+                cert.cert.display(),
+                e
+            ))
+        })?;
+
+    Ok(LoadedCert {
+        certified,
+        names,
+        not_after,
+    })
+}
+
+/// DNS names a custom certificate covers, wildcards included.
+pub fn names(cert: &CustomCert) -> Result<Vec<String>, CertError> {
+    let pem = read(&cert.cert)?;
+    let (_, pem) = x509_parser::pem::parse_x509_pem(&pem)
+        .map_err(|e| CertError::InvalidCertificate(e.to_string()))?;
+    let parsed = pem
+        .parse_x509()
+        .map_err(|e| CertError::InvalidCertificate(e.to_string()))?;
+    Ok(dns_names(&parsed))
+}
+
+/// When a custom certificate expires.
+pub fn expiry(cert: &CustomCert) -> Result<OffsetDateTime, CertError> {
+    not_after(&read(&cert.cert)?)
+}
+
+/// Whether the certificate name `name`, e.g. `*.dev.example.com`,
+/// covers `hostname`. A wildcard covers exactly one label.
+pub fn covers(name: &str, hostname: &str) -> bool {
+    let hostname = hostname.trim_end_matches('.');
+    match name.strip_prefix("*.") {
+        Some(parent) => hostname
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest.eq_ignore_ascii_case(parent)),
+        None => name.eq_ignore_ascii_case(hostname),
+    }
+}
+
+fn dns_names(cert: &x509_parser::certificate::X509Certificate<'_>) -> Vec<String> {
+    let Ok(Some(san)) = cert.subject_alternative_name() else {
+        return Vec::new();
+    };
+    san.value
+        .general_names
+        .iter()
+        .filter_map(|name| match name {
+            GeneralName::DNSName(dns) => Some(dns.to_lowercase()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, CertError> {
+    fs::read(path).map_err(|source| CertError::ReadError {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{CertificateParams, KeyPair};
+
+    fn write_cert(dir: &Path, names: &[&str], key: &KeyPair) -> CustomCert {
+        let names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        let cert = CertificateParams::new(names)
+            .unwrap()
+            .self_signed(key)
+            .unwrap();
+        let custom = CustomCert {
+            cert: dir.join("fullchain.pem"),
+            key: dir.join("privkey.pem"),
+        };
+        fs::write(&custom.cert, cert.pem()).unwrap();
+        fs::write(&custom.key, key.serialize_pem()).unwrap();
+        custom
+    }
+
+    #[test]
+    fn loads_a_certificate_and_its_names() {
+        let tmp = tempfile::tempdir().unwrap();
+        let key = KeyPair::generate().unwrap();
+        let custom = write_cert(tmp.path(), &["*.dev.example.com", "dev.example.com"], &key);
+
+        let loaded = load(&custom).unwrap();
+        assert_eq!(loaded.names, ["*.dev.example.com", "dev.example.com"]);
+        assert_eq!(names(&custom).unwrap(), loaded.names);
+        assert_eq!(expiry(&custom).unwrap(), loaded.not_after);
+    }
+
+    #[test]
+    fn rejects_a_key_from_another_certificate() {
+        let tmp = tempfile::tempdir().unwrap();
+        let custom = write_cert(
+            tmp.path(),
+            &["dev.example.com"],
+            &KeyPair::generate().unwrap(),
+        );
+        fs::write(&custom.key, KeyPair::generate().unwrap().serialize_pem()).unwrap();
+
+        let err = load(&custom).unwrap_err();
+        assert!(err.to_string().contains("does not match"), "{}", err);
+
+        fs::remove_file(&custom.key).unwrap();
+        assert!(matches!(load(&custom), Err(CertError::ReadError { .. })));
+    }
+
+    #[test]
+    fn wildcards_cover_one_label() {
+        assert!(covers("*.dev.example.com", "app.dev.example.com"));
+        assert!(covers("*.dev.example.com", "APP.dev.example.com."));
+        assert!(!covers("*.dev.example.com", "dev.example.com"));
+        assert!(!covers("*.dev.example.com", "a.b.dev.example.com"));
+        assert!(covers("dev.example.com", "dev.example.com"));
+        assert!(!covers("dev.example.com", "app.dev.example.com"));
+    }
+}
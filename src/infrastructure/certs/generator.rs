@@ -1,14 +1,15 @@
-use rcgen::{
-    CertificateParams, DistinguishedName, DnType, KeyPair, KeyUsagePurpose, PKCS_ECDSA_P256_SHA256,
-    SanType,
-};
+use rcgen::{CertificateParams, DistinguishedName, DnType, KeyUsagePurpose, SanType};
 use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::path::PathBuf;
 use time::{Duration, OffsetDateTime};
+use x509_parser::extensions::GeneralName;
 
-use super::CertError;
 use super::ca::RootCA;
+use super::{CertError, CertsConfig};
 use crate::domain::DomainPattern;
+use crate::infrastructure::network::{get_lan_ip, get_lan_ipv6};
+use crate::infrastructure::service_user::inherit_dir_owner;
 
 /// Represents a generated certificate with its key pair
 pub struct Certificate {
@@ -20,9 +21,14 @@ pub struct Certificate {
 
 /// Build certificate parameters for a domain leaf certificate.
 ///
-/// Sets up the Distinguished Name, 1-year validity, key usage for
-/// server authentication, and the given Subject Alternative Names.
-pub(crate) fn build_leaf_cert_params(common_name: &str, sans: Vec<SanType>) -> CertificateParams {
+/// Sets up the Distinguished Name, a validity of `validity_days`, key
+/// usage for server authentication, and the given Subject Alternative
+/// Names.
+pub(crate) fn build_leaf_cert_params(
+    common_name: &str,
+    sans: Vec<SanType>,
+    validity_days: u32,
+) -> CertificateParams {
     let mut params = CertificateParams::default();
 
     let mut dn = DistinguishedName::new();
@@ -32,7 +38,7 @@ pub(crate) fn build_leaf_cert_params(common_name: &str, sans: Vec<SanType>) -> C
 
     let now = OffsetDateTime::now_utc();
     params.not_before = now;
-    params.not_after = now + Duration::days(365);
+    params.not_after = now + Duration::days(validity_days.into());
 
     params.subject_alt_names = sans;
 
@@ -44,12 +50,15 @@ pub(crate) fn build_leaf_cert_params(common_name: &str, sans: Vec<SanType>) -> C
     params
 }
 
+/// Common name of the Root CA Roxy generates. Imported CAs have their own.
+pub(crate) const CA_COMMON_NAME: &str = "Roxy Local Development CA";
+
 /// Build the standard Roxy CA certificate parameters.
 pub(crate) fn build_ca_cert_params() -> CertificateParams {
     let mut params = CertificateParams::default();
 
     let mut dn = DistinguishedName::new();
-    dn.push(DnType::CommonName, "Roxy Local Development CA");
+    dn.push(DnType::CommonName, CA_COMMON_NAME);
     dn.push(DnType::OrganizationName, "Roxy");
     params.distinguished_name = dn;
 
@@ -62,9 +71,11 @@ pub(crate) fn build_ca_cert_params() -> CertificateParams {
     params
 }
 
+#[derive(Debug)]
 pub struct CertificateGenerator {
     base_dir: PathBuf,
     certs_dir: PathBuf,
+    settings: CertsConfig,
 }
 
 impl CertificateGenerator {
@@ -73,13 +84,22 @@ impl CertificateGenerator {
         Self {
             base_dir,
             certs_dir,
+            settings: CertsConfig::default(),
         }
     }
 
+    /// Issue certificates with the validity and key type of `settings`.
+    pub fn with_settings(mut self, settings: &CertsConfig) -> Self {
+        self.settings = settings.clone();
+        self
+    }
+
     /// Generate a certificate for the given domain pattern, signed by the Root CA.
     ///
     /// For exact patterns, generates a single-domain cert.
     /// For wildcard patterns, generates a cert with SANs for base + *.base.
+    /// With `lan_ip_sans`, `localhost` and the loopback and LAN addresses
+    /// are added to either.
     pub fn generate(&self, pattern: &DomainPattern) -> Result<Certificate, CertError> {
         let ca = RootCA::new(self.base_dir.clone());
 
@@ -89,12 +109,21 @@ impl CertificateGenerator {
             ));
         }
 
-        // Generate ECDSA P-256 key pair
-        let key_pair = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256)
-            .map_err(|e| CertError::GenerationError(e.to_string()))?;
+        let key_pair = self.settings.key_type.generate()?;
 
-        let sans = build_sans(pattern)?;
-        let params = build_leaf_cert_params(pattern.base_domain().as_str(), sans);
+        let mut sans = build_sans(pattern)?;
+        let ips = self.ip_sans();
+        if !ips.is_empty() {
+            sans.push(SanType::DnsName("localhost".try_into().map_err(|e| {
+                CertError::GenerationError(format!("Invalid domain name for SAN: {}", e))
+            })?));
+            sans.extend(ips.into_iter().map(SanType::IpAddress));
+        }
+        let params = build_leaf_cert_params(
+            pattern.base_domain().as_str(),
+            sans,
+            self.settings.leaf_days,
+        );
 
         // Sign certificate with CA
         let cert_pem = ca.sign_certificate(params, &key_pair)?;
@@ -146,6 +175,12 @@ impl CertificateGenerator {
             })?;
         }
 
+        // Keep files written via sudo readable by a daemon running as the
+        // service user
+        for path in [cert_path, key_path] {
+            inherit_dir_owner(&path).map_err(|e| CertError::WriteError { path, source: e })?;
+        }
+
         Ok(())
     }
 
@@ -179,6 +214,96 @@ impl CertificateGenerator {
         let key_path = self.certs_dir.join(format!("{}.key", stem));
         cert_path.exists() && key_path.exists()
     }
+
+    /// When the certificate for a domain pattern expires, `None` if it
+    /// has none.
+    pub fn expiry(&self, pattern: &DomainPattern) -> Result<Option<OffsetDateTime>, CertError> {
+        match self.read_cert(pattern)? {
+            Some(pem) => not_after(&pem).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether the certificate for a domain pattern misses an address
+    /// `lan_ip_sans` would add, e.g. because the LAN IP changed since it
+    /// was issued. A pattern without a certificate misses none.
+    pub fn lacks_ip_sans(&self, pattern: &DomainPattern) -> Result<bool, CertError> {
+        let ips = self.ip_sans();
+        if ips.is_empty() {
+            return Ok(false);
+        }
+        let Some(pem) = self.read_cert(pattern)? else {
+            return Ok(false);
+        };
+        let covered = ip_addresses(&pem)?;
+        Ok(ips.iter().any(|ip| !covered.contains(ip)))
+    }
+
+    /// Addresses certificates cover besides their domains: none, or with
+    /// `lan_ip_sans` the loopback addresses and the host's LAN addresses.
+    fn ip_sans(&self) -> Vec<IpAddr> {
+        if !self.settings.lan_ip_sans {
+            return Vec::new();
+        }
+        let mut ips = vec![Ipv4Addr::LOCALHOST.into(), Ipv6Addr::LOCALHOST.into()];
+        for ip in [Some(get_lan_ip().into()), get_lan_ipv6().map(IpAddr::V6)]
+            .into_iter()
+            .flatten()
+        {
+            if !ips.contains(&ip) {
+                ips.push(ip);
+            }
+        }
+        ips
+    }
+
+    /// The PEM certificate for a domain pattern, `None` if it has none.
+    fn read_cert(&self, pattern: &DomainPattern) -> Result<Option<Vec<u8>>, CertError> {
+        let cert_path = self.certs_dir.join(format!("{}.crt", pattern.cert_name()));
+        match fs::read(&cert_path) {
+            Ok(pem) => Ok(Some(pem)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(CertError::ReadError {
+                path: cert_path,
+                source: e,
+            }),
+        }
+    }
+}
+
+/// The IP addresses among a PEM certificate's Subject Alternative Names.
+fn ip_addresses(cert_pem: &[u8]) -> Result<Vec<IpAddr>, CertError> {
+    let (_, pem) = x509_parser::pem::parse_x509_pem(cert_pem)
+        .map_err(|e| CertError::InvalidCertificate(e.to_string()))?;
+    let cert = pem
+        .parse_x509()
+        .map_err(|e| CertError::InvalidCertificate(e.to_string()))?;
+    let Ok(Some(san)) = cert.subject_alternative_name() else {
+        return Ok(Vec::new());
+    };
+    Ok(san
+        .value
+        .general_names
+        .iter()
+        .filter_map(|name| match name {
+            GeneralName::IPAddress(bytes) => match bytes.len() {
+                4 => <[u8; 4]>::try_from(*bytes).ok().map(IpAddr::from),
+                16 => <[u8; 16]>::try_from(*bytes).ok().map(IpAddr::from),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect())
+}
+
+/// The end of the validity period of a PEM certificate.
+pub fn not_after(cert_pem: &[u8]) -> Result<OffsetDateTime, CertError> {
+    let (_, pem) = x509_parser::pem::parse_x509_pem(cert_pem)
+        .map_err(|e| CertError::InvalidCertificate(e.to_string()))?;
+    let cert = pem
+        .parse_x509()
+        .map_err(|e| CertError::InvalidCertificate(e.to_string()))?;
+    Ok(cert.validity().not_after.to_datetime())
 }
 
 /// Build Subject Alternative Names for the given pattern.
@@ -206,6 +331,7 @@ fn build_sans(pattern: &DomainPattern) -> Result<Vec<SanType>, CertError> {
 mod tests {
     use super::*;
     use crate::domain::DomainName;
+    use crate::infrastructure::certs::KeyType;
     use crate::infrastructure::certs::ca::RootCA;
     use tempfile::TempDir;
 
@@ -228,4 +354,78 @@ mod tests {
         assert!(cert.cert_pem.contains("BEGIN CERTIFICATE"));
         assert!(cert.key_pem.contains("BEGIN PRIVATE KEY"));
     }
+
+    #[test]
+    fn test_certificate_expiry() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let base_dir = temp_dir.path().to_path_buf();
+        RootCA::new(base_dir.clone()).generate().unwrap();
+
+        let pattern = DomainPattern::Exact(DomainName::new("test.roxy").unwrap());
+        let generator = CertificateGenerator::new(base_dir.clone(), base_dir.join("certs"));
+        assert!(generator.expiry(&pattern).unwrap().is_none());
+
+        generator
+            .save(&generator.generate(&pattern).unwrap())
+            .unwrap();
+        let expiry = generator.expiry(&pattern).unwrap().unwrap();
+        let days_left = (expiry - OffsetDateTime::now_utc()).whole_days();
+        assert!((364..=365).contains(&days_left));
+
+        assert!(not_after(b"not a certificate").is_err());
+    }
+
+    #[test]
+    fn test_certificate_settings() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let base_dir = temp_dir.path().to_path_buf();
+        let settings = CertsConfig {
+            leaf_days: 90,
+            ca_days: 365,
+            key_type: KeyType::Rsa2048,
+            ..CertsConfig::default()
+        };
+        RootCA::new(base_dir.clone())
+            .with_settings(&settings)
+            .generate()
+            .unwrap();
+
+        let pattern = DomainPattern::Exact(DomainName::new("test.roxy").unwrap());
+        let generator = CertificateGenerator::new(base_dir.clone(), base_dir.join("certs"))
+            .with_settings(&settings);
+        let cert = generator.generate(&pattern).unwrap();
+
+        let key = rcgen::KeyPair::from_pem(&cert.key_pem).unwrap();
+        assert_eq!(key.algorithm(), &rcgen::PKCS_RSA_SHA256);
+        let days_left =
+            (not_after(cert.cert_pem.as_bytes()).unwrap() - OffsetDateTime::now_utc()).whole_days();
+        assert!((89..=90).contains(&days_left));
+    }
+
+    #[test]
+    fn test_certificate_lan_ip_sans() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let base_dir = temp_dir.path().to_path_buf();
+        RootCA::new(base_dir.clone()).generate().unwrap();
+
+        let pattern = DomainPattern::Exact(DomainName::new("test.roxy").unwrap());
+        let plain = CertificateGenerator::new(base_dir.clone(), base_dir.join("certs"));
+        plain.save(&plain.generate(&pattern).unwrap()).unwrap();
+        assert!(!plain.lacks_ip_sans(&pattern).unwrap());
+
+        let settings = CertsConfig {
+            lan_ip_sans: true,
+            ..CertsConfig::default()
+        };
+        let generator = CertificateGenerator::new(base_dir.clone(), base_dir.join("certs"))
+            .with_settings(&settings);
+        assert!(generator.lacks_ip_sans(&pattern).unwrap());
+
+        let cert = generator.generate(&pattern).unwrap();
+        let ips = ip_addresses(cert.cert_pem.as_bytes()).unwrap();
+        assert!(ips.contains(&IpAddr::from(Ipv4Addr::LOCALHOST)));
+        assert!(ips.contains(&IpAddr::from(get_lan_ip())));
+        generator.save(&cert).unwrap();
+        assert!(!generator.lacks_ip_sans(&pattern).unwrap());
+    }
 }
@@ -0,0 +1,66 @@
+//! Locating the Root CA of [mkcert](https://github.com/FiloSottile/mkcert),
+//! so `roxy install --ca-from-mkcert` can reuse it.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use super::CertError;
+use crate::infrastructure::service_user::ServiceUser;
+use crate::infrastructure::user::Caller;
+
+const CERT_FILE: &str = "rootCA.pem";
+const KEY_FILE: &str = "rootCA-key.pem";
+
+/// The certificate and key of mkcert's CA, as `mkcert -CAROOT` would
+/// find them for the user running Roxy (the one who ran sudo, not root).
+pub fn find_ca() -> Result<(PathBuf, PathBuf), CertError> {
+    let home = Caller::current()
+        .name()
+        .and_then(|name| ServiceUser::lookup(name).ok())
+        .map(|user| user.home().to_path_buf())
+        .or_else(|| env::var_os("HOME").map(PathBuf::from));
+    let dir = env::var_os("CAROOT")
+        .map(PathBuf::from)
+        .or_else(|| home.map(|home| default_caroot(&home)))
+        .ok_or_else(|| CertError::MkcertNotFound(PathBuf::from("$CAROOT")))?;
+
+    let (cert, key) = (dir.join(CERT_FILE), dir.join(KEY_FILE));
+    if !cert.exists() || !key.exists() {
+        return Err(CertError::MkcertNotFound(dir));
+    }
+    Ok((cert, key))
+}
+
+/// mkcert's default CAROOT for a user whose home directory is `home`.
+fn default_caroot(home: &Path) -> PathBuf {
+    if cfg!(target_os = "macos") {
+        home.join("Library/Application Support/mkcert")
+    } else if cfg!(windows) {
+        env::var_os("LOCALAPPDATA")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join("AppData").join("Local"))
+            .join("mkcert")
+    } else {
+        // XDG_DATA_HOME belongs to whoever ran sudo only without sudo
+        env::var_os("XDG_DATA_HOME")
+            .filter(|_| env::var_os("SUDO_USER").is_none())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".local/share"))
+            .join("mkcert")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn defaults_to_the_users_data_directory() {
+        let caroot = default_caroot(Path::new("/home/dev"));
+        assert!(caroot.ends_with("mkcert"));
+        if env::var_os("XDG_DATA_HOME").is_none() {
+            assert_eq!(caroot, Path::new("/home/dev/.local/share/mkcert"));
+        }
+    }
+}
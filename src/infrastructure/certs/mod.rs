@@ -1,14 +1,95 @@
+use rcgen::{KeyPair, PKCS_ECDSA_P256_SHA256, PKCS_RSA_SHA256, RsaKeySize};
 use std::path::PathBuf;
 use thiserror::Error;
 
 pub mod ca;
+pub mod custom;
 pub mod generator;
+pub mod mkcert;
 pub mod service;
 pub mod trust_store;
 
 pub use generator::CertificateGenerator;
 pub use service::CertificateService;
 
+/// Longest validity macOS accepts for a TLS server certificate, even
+/// one from a CA the user trusts.
+pub const MAX_LEAF_DAYS: u32 = 825;
+
+fn default_leaf_days() -> u32 {
+    365
+}
+
+fn default_ca_days() -> u32 {
+    3650
+}
+
+fn default_on_demand_cache() -> usize {
+    256
+}
+
+/// The `[certs]` section: how the Root CA and domain certificates are
+/// issued. Changes apply to certificates issued afterwards.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CertsConfig {
+    /// Days a domain certificate is valid for.
+    #[serde(default = "default_leaf_days")]
+    pub leaf_days: u32,
+
+    /// Days the Root CA is valid for.
+    #[serde(default = "default_ca_days")]
+    pub ca_days: u32,
+
+    #[serde(default)]
+    pub key_type: KeyType,
+
+    /// On-demand certificates kept in memory; the least recently used
+    /// one is dropped to make room.
+    #[serde(default = "default_on_demand_cache")]
+    pub on_demand_cache: usize,
+
+    /// Also make domain certificates valid for `localhost`, the loopback
+    /// addresses and the host's LAN IP, for devices that open
+    /// `https://192.168.1.50` directly.
+    #[serde(default)]
+    pub lan_ip_sans: bool,
+}
+
+impl Default for CertsConfig {
+    fn default() -> Self {
+        Self {
+            leaf_days: default_leaf_days(),
+            ca_days: default_ca_days(),
+            key_type: KeyType::default(),
+            on_demand_cache: default_on_demand_cache(),
+            lan_ip_sans: false,
+        }
+    }
+}
+
+/// Key algorithm of the Root CA and domain certificates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum KeyType {
+    /// ECDSA on the P-256 curve: small keys, fast handshakes
+    #[default]
+    #[serde(rename = "ecdsa-p256")]
+    EcdsaP256,
+    /// 2048-bit RSA, for older tooling that rejects ECDSA
+    #[serde(rename = "rsa-2048")]
+    Rsa2048,
+}
+
+impl KeyType {
+    /// Generate a new key pair of this type.
+    pub fn generate(self) -> Result<KeyPair, CertError> {
+        match self {
+            Self::EcdsaP256 => KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256),
+            Self::Rsa2048 => KeyPair::generate_rsa_for(&PKCS_RSA_SHA256, RsaKeySize::_2048),
+        }
+        .map_err(|e| CertError::GenerationError(e.to_string()))
+    }
+}
+
 /// Filename prefix for wildcard certificates stored in `certs_dir`.
 ///
 /// We intentionally use underscores so it can't collide with a valid `.roxy`
@@ -38,11 +119,19 @@ pub enum CertError {
         source: std::io::Error,
     },
 
+    #[error("Invalid certificate: {0}")]
+    InvalidCertificate(String),
+
+    #[error("No mkcert CA in {0}. Run 'mkcert -install' first, or pass --ca-cert and --ca-key")]
+    MkcertNotFound(PathBuf),
+
     #[error("Trust store operation failed: {0}")]
     TrustStoreError(String),
 
     #[error(
         "Permission denied. Trust store modification requires root privileges.\nRun with: sudo roxy register <domain> ..."
     )]
+    #[cfg_attr(not(any(target_os = "macos", windows)), allow(dead_code))]
+    // Used only on macOS and Windows
     PermissionDenied,
 }
@@ -1,7 +1,12 @@
+use std::path::Path;
+
+use time::{Duration, OffsetDateTime};
+
 use super::ca::RootCA;
+use super::custom;
 use super::trust_store::get_trust_store;
-use super::{CertError, CertificateGenerator};
-use crate::domain::DomainPattern;
+use super::{CertError, CertificateGenerator, CertsConfig};
+use crate::domain::{DomainPattern, DomainRegistration};
 use crate::infrastructure::paths::RoxyPaths;
 
 /// High-level service for certificate operations
@@ -19,6 +24,13 @@ impl CertificateService {
         }
     }
 
+    /// Issue the Root CA and certificates per the `[certs]` section.
+    pub fn with_settings(mut self, settings: &CertsConfig) -> Self {
+        self.generator = self.generator.with_settings(settings);
+        self.ca = self.ca.with_settings(settings);
+        self
+    }
+
     /// Initialize the Root CA (called during `roxy install`)
     pub fn init_ca(&self) -> Result<(), CertError> {
         if self.ca.exists() {
@@ -35,11 +47,34 @@ impl CertificateService {
         Ok(())
     }
 
+    /// Make the CA at `cert` and `key` Roxy's Root CA (`roxy install
+    /// --ca-from-mkcert`). It is trusted already, so the trust store is
+    /// left alone, except that a CA Roxy generated earlier is removed.
+    pub fn import_ca(&self, cert: &Path, key: &Path) -> Result<(), CertError> {
+        // Platforms without a trust store have nothing to remove
+        if self.ca.exists()
+            && !self.ca.is_imported()
+            && let Ok(trust_store) = get_trust_store()
+        {
+            trust_store.remove_ca()?;
+        }
+        self.ca.import(cert, key)
+    }
+
+    /// Whether the Root CA was imported rather than generated.
+    pub fn is_ca_imported(&self) -> bool {
+        self.ca.exists() && self.ca.is_imported()
+    }
+
     /// Check if the Root CA exists and is trusted
     pub fn is_ca_installed(&self) -> Result<bool, CertError> {
         if !self.ca.exists() {
             return Ok(false);
         }
+        // Trusting an imported CA is up to the tool it came from
+        if self.ca.is_imported() {
+            return Ok(true);
+        }
 
         let trust_store = get_trust_store()?;
         trust_store.is_ca_trusted()
@@ -71,6 +106,50 @@ impl CertificateService {
         self.generator.exists(pattern)
     }
 
+    /// When the certificate for a domain pattern expires, `None` if it
+    /// has none.
+    pub fn expiry(&self, pattern: &DomainPattern) -> Result<Option<OffsetDateTime>, CertError> {
+        self.generator.expiry(pattern)
+    }
+
+    /// When the certificate a registration serves expires: its custom
+    /// one, or the one Roxy issued.
+    pub fn registration_expiry(
+        &self,
+        registration: &DomainRegistration,
+    ) -> Result<Option<OffsetDateTime>, CertError> {
+        match registration.custom_cert() {
+            Some(cert) => custom::expiry(cert).map(Some),
+            None => self.expiry(registration.pattern()),
+        }
+    }
+
+    /// Issue a new certificate for a domain pattern if its current one
+    /// expires within `window`, or lacks an address `lan_ip_sans` adds.
+    /// Returns whether it was renewed; a pattern without a certificate is
+    /// left alone.
+    pub fn renew_if_expiring(
+        &self,
+        pattern: &DomainPattern,
+        window: Duration,
+    ) -> Result<bool, CertError> {
+        match self.expiry(pattern) {
+            Ok(Some(expiry))
+                if expiry - OffsetDateTime::now_utc() > window
+                    && !self.generator.lacks_ip_sans(pattern)? =>
+            {
+                Ok(false)
+            }
+            Ok(None) => Ok(false),
+            // An unreadable certificate is replaced like an expiring one
+            Ok(Some(_)) | Err(CertError::InvalidCertificate(_)) => {
+                self.create_and_install(pattern)?;
+                Ok(true)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     /// Check if certificate is trusted (CA is trusted = all certs trusted)
     pub fn is_trusted(&self) -> Result<bool, CertError> {
         self.is_ca_installed()
@@ -78,9 +157,53 @@ impl CertificateService {
 
     /// Remove the Root CA (for uninstall)
     pub fn remove_ca(&self) -> Result<(), CertError> {
-        let trust_store = get_trust_store()?;
-        trust_store.remove_ca()?;
+        // An imported CA stays trusted for the tool it came from
+        if !self.ca.is_imported() {
+            let trust_store = get_trust_store()?;
+            trust_store.remove_ca()?;
+        }
         self.ca.delete()?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::DomainName;
+    use tempfile::TempDir;
+
+    #[test]
+    fn renews_certificates_inside_the_window() {
+        let temp_dir = TempDir::new().unwrap();
+        let paths = RoxyPaths {
+            data_dir: temp_dir.path().to_path_buf(),
+            certs_dir: temp_dir.path().join("certs"),
+            ..RoxyPaths::default()
+        };
+        let service = CertificateService::new(&paths);
+        service.ca.generate().unwrap();
+        let pattern = DomainPattern::Exact(DomainName::new("test.roxy").unwrap());
+
+        // Nothing to renew before the domain has a certificate
+        assert!(
+            !service
+                .renew_if_expiring(&pattern, Duration::days(30))
+                .unwrap()
+        );
+
+        service.create_and_install(&pattern).unwrap();
+        let issued = service.expiry(&pattern).unwrap().unwrap();
+        assert!(
+            !service
+                .renew_if_expiring(&pattern, Duration::days(30))
+                .unwrap()
+        );
+        assert!(
+            service
+                .renew_if_expiring(&pattern, Duration::days(400))
+                .unwrap()
+        );
+        assert!(service.expiry(&pattern).unwrap().unwrap() >= issued);
+    }
+}
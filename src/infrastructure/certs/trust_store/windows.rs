@@ -0,0 +1,65 @@
+use std::path::Path;
+use std::process::Command;
+
+use super::super::CertError;
+use super::TrustStore;
+
+const ROXY_CA_NAME: &str = "Roxy Local Development CA";
+
+/// Windows machine trust store (`LocalMachine\Root`), managed with
+/// `certutil`.
+pub struct WindowsTrustStore;
+
+impl WindowsTrustStore {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+fn certutil(args: &[&str]) -> Result<(), CertError> {
+    let output = Command::new("certutil")
+        .args(args)
+        .output()
+        .map_err(|e| CertError::TrustStoreError(format!("Failed to run certutil: {}", e)))?;
+
+    if !output.status.success() {
+        // certutil reports errors on stdout
+        let message = String::from_utf8_lossy(&output.stdout);
+        if message.contains("Access is denied") || message.contains("E_ACCESSDENIED") {
+            return Err(CertError::PermissionDenied);
+        }
+        return Err(CertError::TrustStoreError(message.trim().to_string()));
+    }
+    Ok(())
+}
+
+impl TrustStore for WindowsTrustStore {
+    fn add_ca(&self, cert_path: &Path) -> Result<(), CertError> {
+        let path = cert_path.to_string_lossy();
+        certutil(&["-addstore", "-f", "Root", path.as_ref()])
+    }
+
+    fn remove_ca(&self) -> Result<(), CertError> {
+        if !self.is_ca_trusted()? {
+            return Ok(());
+        }
+        certutil(&["-delstore", "Root", ROXY_CA_NAME])
+    }
+
+    fn is_ca_trusted(&self) -> Result<bool, CertError> {
+        let output = Command::new("certutil")
+            .args(["-store", "Root", ROXY_CA_NAME])
+            .output()
+            .map_err(|e| {
+                CertError::TrustStoreError(format!("Failed to check CA certificate: {}", e))
+            })?;
+
+        Ok(output.status.success())
+    }
+}
+
+impl Default for WindowsTrustStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,238 @@
+//! Reads the services and published ports out of a Docker Compose file,
+//! for `roxy import compose`. Only the parts Roxy needs are parsed; the
+//! rest of the file is ignored.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ComposeError {
+    #[error("Failed to read {path}: {source}")]
+    ReadError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to parse {path}: {source}")]
+    ParseError {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+}
+
+/// A service from the compose file with the TCP ports it publishes on
+/// the host, in file order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComposeService {
+    pub name: String,
+    pub ports: Vec<u16>,
+}
+
+impl ComposeService {
+    /// The `.roxy` name for this service. Compose allows underscores in
+    /// service names, DNS doesn't.
+    pub fn domain(&self) -> String {
+        format!("{}.roxy", self.name.to_lowercase().replace('_', "-"))
+    }
+}
+
+#[derive(Deserialize)]
+struct Document {
+    #[serde(default)]
+    services: BTreeMap<String, Option<Service>>,
+}
+
+#[derive(Deserialize)]
+struct Service {
+    #[serde(default)]
+    ports: Vec<PortEntry>,
+}
+
+/// `ports:` entries come as a bare container port, a short
+/// `[HOST_IP:]HOST_PORT:CONTAINER_PORT[/PROTOCOL]` string, or a mapping.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PortEntry {
+    #[allow(dead_code)] // Only the container port; never published
+    Number(u64),
+    Short(String),
+    Long {
+        published: Option<Published>,
+        protocol: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Published {
+    Number(u16),
+    Text(String),
+}
+
+/// First port of a `8000` or `8000-8010` host port spec; `None` when
+/// Docker picks the port.
+fn first_port(spec: &str) -> Option<u16> {
+    let first = spec.split('-').next()?.trim();
+    first.parse().ok().filter(|port| *port != 0)
+}
+
+impl PortEntry {
+    fn published_tcp_port(&self) -> Option<u16> {
+        match self {
+            Self::Number(_) => None,
+            Self::Short(spec) => {
+                let (mapping, protocol) = spec.split_once('/').unwrap_or((spec, "tcp"));
+                if protocol != "tcp" {
+                    return None;
+                }
+                let (host, _container) = mapping.rsplit_once(':')?;
+                // `host` is `PORT` or `IP:PORT`; IPv6 addresses are
+                // bracketed, so the port is always after the last colon
+                first_port(host.rsplit(':').next()?)
+            }
+            Self::Long {
+                published,
+                protocol,
+            } => {
+                if protocol.as_deref().is_some_and(|p| p != "tcp") {
+                    return None;
+                }
+                match published.as_ref()? {
+                    Published::Number(port) => Some(*port).filter(|port| *port != 0),
+                    Published::Text(spec) => first_port(spec),
+                }
+            }
+        }
+    }
+}
+
+/// Parse compose file contents.
+pub fn parse(contents: &str) -> Result<Vec<ComposeService>, serde_yaml::Error> {
+    let document: Document = serde_yaml::from_str(contents)?;
+
+    Ok(document
+        .services
+        .into_iter()
+        .map(|(name, service)| {
+            let mut ports: Vec<u16> = Vec::new();
+            for port in service
+                .iter()
+                .flat_map(|s| &s.ports)
+                .filter_map(PortEntry::published_tcp_port)
+            {
+                if !ports.contains(&port) {
+                    ports.push(port);
+                }
+            }
+            ComposeService { name, ports }
+        })
+        .collect())
+}
+
+/// Read and parse the compose file at `path`.
+pub fn load(path: &Path) -> Result<Vec<ComposeService>, ComposeError> {
+    let contents = fs::read_to_string(path).map_err(|source| ComposeError::ReadError {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse(&contents).map_err(|source| ComposeError::ParseError {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ports_of(services: &[ComposeService], name: &str) -> Vec<u16> {
+        services
+            .iter()
+            .find(|s| s.name == name)
+            .map(|s| s.ports.clone())
+            .unwrap()
+    }
+
+    #[test]
+    fn reads_short_port_syntax() {
+        let services = parse(
+            r#"
+services:
+  web:
+    image: nginx
+    ports:
+      - "8080:80"
+      - "127.0.0.1:8443:443"
+      - "[::1]:9000:9000/tcp"
+      - "5353:53/udp"
+      - "3000"
+      - 4000
+      - "127.0.0.1::5000"
+      - "6000-6002:6000-6002"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(ports_of(&services, "web"), vec![8080, 8443, 9000, 6000]);
+    }
+
+    #[test]
+    fn reads_long_port_syntax() {
+        let services = parse(
+            r#"
+services:
+  api:
+    ports:
+      - target: 80
+        published: 8081
+      - target: 81
+        published: "8082"
+      - target: 53
+        published: 5300
+        protocol: udp
+      - target: 90
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(ports_of(&services, "api"), vec![8081, 8082]);
+    }
+
+    #[test]
+    fn services_without_ports_are_kept() {
+        let services = parse(
+            r#"
+services:
+  db:
+    image: postgres
+  cache:
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(ports_of(&services, "db"), Vec::<u16>::new());
+        assert_eq!(ports_of(&services, "cache"), Vec::<u16>::new());
+    }
+
+    #[test]
+    fn domain_replaces_underscores() {
+        let service = ComposeService {
+            name: "My_App".into(),
+            ports: vec![],
+        };
+        assert_eq!(service.domain(), "my-app.roxy");
+    }
+
+    #[test]
+    fn load_reports_missing_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            load(&tmp.path().join("docker-compose.yml")),
+            Err(ComposeError::ReadError { .. })
+        ));
+    }
+}
@@ -5,9 +5,14 @@
 //! config file layout, and deserialization can't bypass domain
 //! invariants enforced by `DomainRegistration` methods.
 
+use std::path::{Path, PathBuf};
+
 use serde::{Deserialize, Serialize};
 
-use crate::domain::{DomainPattern, DomainRegistration, Route};
+use crate::domain::{
+    AccessPolicy, BasicAuth, CorsPolicy, CustomCert, DnsRecords, DomainPattern, DomainRegistration,
+    LogLevel, RateLimit, Route, TcpForward,
+};
 
 /// Serializable representation of a domain registration in the config
 /// file. Converted to/from `DomainRegistration` at the `ConfigStore`
@@ -18,6 +23,42 @@ pub struct RegistrationDto {
     pub routes: Vec<Route>,
     #[serde(default)]
     pub https_enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate: Option<CustomCert>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub https_only: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hsts_max_age: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<LogLevel>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cors: Option<CorsPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<BasicAuth>,
+    #[serde(default, skip_serializing_if = "AccessPolicy::is_default")]
+    pub access: AccessPolicy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimit>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub maintenance: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance_message: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub tls_passthrough: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tcp_forwards: Vec<TcpForward>,
+    #[serde(default, skip_serializing_if = "DnsRecords::is_default")]
+    pub dns: DnsRecords,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<PathBuf>,
+    #[serde(default = "default_enabled", skip_serializing_if = "is_true")]
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl From<DomainRegistration> for RegistrationDto {
@@ -25,7 +66,25 @@ impl From<DomainRegistration> for RegistrationDto {
         Self {
             pattern: reg.pattern().clone(),
             routes: reg.routes().to_vec(),
-            https_enabled: reg.is_https_enabled(),
+            https_enabled: reg.has_roxy_cert(),
+            certificate: reg.custom_cert().cloned(),
+            https_only: reg.is_https_only(),
+            hsts_max_age: reg.hsts_max_age(),
+            log_level: reg.log_level(),
+            owner: reg.owner().map(str::to_string),
+            cors: reg.cors().cloned(),
+            auth: reg.auth().cloned(),
+            access: reg.access().clone(),
+            rate_limit: reg.rate_limit().cloned(),
+            maintenance: reg.is_in_maintenance(),
+            maintenance_message: reg.maintenance_message().map(str::to_string),
+            tls_passthrough: reg.is_tls_passthrough(),
+            tcp_forwards: reg.tcp_forwards().to_vec(),
+            dns: reg.dns().clone(),
+            project: reg.project().map(Path::to_path_buf),
+            enabled: reg.is_enabled(),
+            description: reg.description().map(str::to_string),
+            tags: reg.tags().to_vec(),
         }
     }
 }
@@ -36,6 +95,34 @@ impl From<RegistrationDto> for DomainRegistration {
         if dto.https_enabled {
             reg.enable_https();
         }
+        reg.set_custom_cert(dto.certificate);
+        reg.set_https_only(dto.https_only, dto.hsts_max_age);
+        reg.set_log_level(dto.log_level);
+        reg.set_owner(dto.owner);
+        reg.set_cors(dto.cors);
+        reg.set_auth(dto.auth);
+        reg.set_access(dto.access);
+        reg.set_rate_limit(dto.rate_limit);
+        reg.set_maintenance(dto.maintenance, dto.maintenance_message);
+        reg.set_tls_passthrough(dto.tls_passthrough);
+        reg.set_tcp_forwards(dto.tcp_forwards);
+        reg.set_dns(dto.dns);
+        reg.set_project(dto.project);
+        reg.set_enabled(dto.enabled);
+        reg.set_description(dto.description);
+        reg.set_tags(dto.tags);
         reg
     }
 }
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn default_enabled() -> bool {
+    true
+}
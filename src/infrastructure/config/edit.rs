@@ -0,0 +1,385 @@
+//! Dotted-key editing of the raw config document, backing
+//! `roxy config set` and `roxy config unset`.
+//!
+//! Keys follow the TOML layout (`daemon.http_port`,
+//! `domains.myapp.roxy.log_level`) with a few conveniences: the `.roxy`
+//! suffix of a domain may be omitted (`domains.myapp.log_level`) and
+//! routes can be addressed by index or by path
+//! (`domains.myapp.routes./api.log_level`).
+
+use toml::{Table, Value};
+
+use super::ConfigError;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Parse a CLI value as a TOML value, falling back to a plain string
+/// so `debug` doesn't need to be quoted.
+pub fn parse_value(raw: &str) -> Value {
+    raw.parse::<Value>()
+        .unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+/// Set `key` to `value`, creating intermediate tables as needed.
+pub fn set(root: &mut Table, key: &str, value: Value) -> Result<(), ConfigError> {
+    let segments = resolve(root, key)?;
+    with_root(root, |node| set_at(node, &segments, value))
+        .ok_or_else(|| invalid_key(key, "parent is not a table"))
+}
+
+/// Remove `key` from the document.
+pub fn unset(root: &mut Table, key: &str) -> Result<(), ConfigError> {
+    let segments = resolve(root, key)?;
+    with_root(root, |node| remove_at(node, &segments)).ok_or_else(|| invalid_key(key, "not set"))
+}
+
+/// Look up `key` in the document.
+pub fn get<'a>(root: &'a Table, key: &str) -> Option<&'a Value> {
+    let segments = resolve(root, key).ok()?;
+    let (first, rest) = segments.split_first()?;
+    let Segment::Key(name) = first else {
+        return None;
+    };
+    rest.iter()
+        .try_fold(root.get(name)?, |node, segment| match segment {
+            Segment::Key(name) => node.get(name),
+            Segment::Index(idx) => node.get(*idx),
+        })
+}
+
+/// Resolve a dotted key into concrete table keys and array indexes.
+fn resolve(root: &Table, key: &str) -> Result<Vec<Segment>, ConfigError> {
+    let parts: Vec<&str> = key.split('.').collect();
+    if parts.iter().any(|p| p.is_empty()) {
+        return Err(invalid_key(key, "empty key segment"));
+    }
+
+    let mut segments = Vec::new();
+    let mut node = Some(root);
+    let mut array: Option<&Vec<Value>> = None;
+    let mut i = 0;
+
+    while i < parts.len() {
+        let part = parts[i];
+
+        // Domain keys contain dots, so match the longest known name
+        if segments == [Segment::Key("domains".into())] {
+            let domains = node.ok_or_else(|| ConfigError::DomainNotFound(part.to_string()))?;
+            let (name, consumed) = match_domain(domains, &parts[i..])
+                .ok_or_else(|| ConfigError::DomainNotFound(part.to_string()))?;
+            node = domains.get(&name).and_then(Value::as_table);
+            segments.push(Segment::Key(name));
+            i += consumed;
+            continue;
+        }
+
+        if let Some(items) = array.take() {
+            let idx = if part.starts_with('/') {
+                items
+                    .iter()
+                    .position(|r| r.get("path").and_then(Value::as_str) == Some(part))
+                    .ok_or_else(|| invalid_key(key, &format!("no route for path '{}'", part)))?
+            } else {
+                part.parse::<usize>()
+                    .ok()
+                    .filter(|idx| *idx < items.len())
+                    .ok_or_else(|| invalid_key(key, &format!("no entry at index '{}'", part)))?
+            };
+            node = items[idx].as_table();
+            segments.push(Segment::Index(idx));
+            i += 1;
+            continue;
+        }
+
+        let next = node.and_then(|table| table.get(part));
+        let is_last = i + 1 == parts.len();
+        match next {
+            Some(Value::Table(table)) => node = Some(table),
+            Some(Value::Array(items)) if !is_last => {
+                node = None;
+                array = Some(items);
+            }
+            Some(_) if !is_last => {
+                return Err(invalid_key(
+                    key,
+                    &format!("'{}' is not a table", parts[..=i].join(".")),
+                ));
+            }
+            _ => node = None,
+        }
+        segments.push(Segment::Key(part.to_string()));
+        i += 1;
+    }
+
+    Ok(segments)
+}
+
+/// Find the domain key matching the leading parts, preferring the
+/// longest match. The `.roxy` suffix is optional.
+fn match_domain(domains: &Table, parts: &[&str]) -> Option<(String, usize)> {
+    (1..=parts.len()).rev().find_map(|len| {
+        let name = parts[..len].join(".");
+        let with_tld = format!("{}.roxy", name);
+        if domains.contains_key(&name) {
+            Some((name, len))
+        } else if domains.contains_key(&with_tld) {
+            Some((with_tld, len))
+        } else {
+            None
+        }
+    })
+}
+
+fn with_root<T>(root: &mut Table, f: impl FnOnce(&mut Value) -> T) -> T {
+    let mut node = Value::Table(std::mem::take(root));
+    let result = f(&mut node);
+    if let Value::Table(table) = node {
+        *root = table;
+    }
+    result
+}
+
+fn set_at(node: &mut Value, segments: &[Segment], value: Value) -> Option<()> {
+    let (first, rest) = segments.split_first()?;
+    let child = match (node, first) {
+        (Value::Table(table), Segment::Key(name)) => {
+            if rest.is_empty() {
+                table.insert(name.clone(), value);
+                return Some(());
+            }
+            table
+                .entry(name.clone())
+                .or_insert_with(|| Value::Table(Table::new()))
+        }
+        (Value::Array(items), Segment::Index(idx)) => {
+            let item = items.get_mut(*idx)?;
+            if rest.is_empty() {
+                *item = value;
+                return Some(());
+            }
+            item
+        }
+        _ => return None,
+    };
+    set_at(child, rest, value)
+}
+
+fn remove_at(node: &mut Value, segments: &[Segment]) -> Option<()> {
+    let (first, rest) = segments.split_first()?;
+    match (node, first) {
+        (Value::Table(table), Segment::Key(name)) if rest.is_empty() => {
+            table.remove(name).map(|_| ())
+        }
+        (Value::Array(items), Segment::Index(idx)) if rest.is_empty() => {
+            (*idx < items.len()).then(|| {
+                items.remove(*idx);
+            })
+        }
+        (Value::Table(table), Segment::Key(name)) => remove_at(table.get_mut(name)?, rest),
+        (Value::Array(items), Segment::Index(idx)) => remove_at(items.get_mut(*idx)?, rest),
+        _ => None,
+    }
+}
+
+fn invalid_key(key: &str, reason: &str) -> ConfigError {
+    ConfigError::InvalidKey(key.to_string(), reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Table {
+        r#"
+            [daemon]
+            http_port = 80
+
+            [domains."myapp.roxy"]
+            pattern = "myapp.roxy"
+
+            [[domains."myapp.roxy".routes]]
+            path = "/"
+            target = "127.0.0.1:3000"
+
+            [[domains."myapp.roxy".routes]]
+            path = "/api"
+            target = "127.0.0.1:3001"
+
+            [domains."*.shop.roxy"]
+            pattern = "*.shop.roxy"
+        "#
+        .parse()
+        .unwrap()
+    }
+
+    fn domain<'a>(table: &'a Table, name: &str) -> &'a Table {
+        table["domains"][name].as_table().unwrap()
+    }
+
+    #[test]
+    fn parse_value_keeps_typed_values() {
+        assert_eq!(parse_value("8080"), Value::Integer(8080));
+        assert_eq!(parse_value("true"), Value::Boolean(true));
+        assert_eq!(parse_value("\"quoted\""), Value::String("quoted".into()));
+    }
+
+    #[test]
+    fn parse_value_falls_back_to_string() {
+        assert_eq!(parse_value("debug"), Value::String("debug".into()));
+        assert_eq!(parse_value("/var/www"), Value::String("/var/www".into()));
+    }
+
+    #[test]
+    fn set_plain_key() {
+        let mut table = sample();
+        set(&mut table, "daemon.http_port", Value::Integer(8080)).unwrap();
+        assert_eq!(table["daemon"]["http_port"], Value::Integer(8080));
+    }
+
+    #[test]
+    fn set_creates_missing_tables() {
+        let mut table = Table::new();
+        set(&mut table, "daemon.log_level", parse_value("debug")).unwrap();
+        assert_eq!(table["daemon"]["log_level"].as_str(), Some("debug"));
+    }
+
+    #[test]
+    fn set_domain_key_with_full_name() {
+        let mut table = sample();
+        set(
+            &mut table,
+            "domains.myapp.roxy.log_level",
+            parse_value("debug"),
+        )
+        .unwrap();
+        assert_eq!(
+            domain(&table, "myapp.roxy")["log_level"].as_str(),
+            Some("debug")
+        );
+    }
+
+    #[test]
+    fn set_domain_key_without_tld() {
+        let mut table = sample();
+        set(&mut table, "domains.myapp.log_level", parse_value("warn")).unwrap();
+        assert_eq!(
+            domain(&table, "myapp.roxy")["log_level"].as_str(),
+            Some("warn")
+        );
+    }
+
+    #[test]
+    fn set_wildcard_domain_key() {
+        let mut table = sample();
+        set(&mut table, "domains.*.shop.log_level", parse_value("warn")).unwrap();
+        assert_eq!(
+            domain(&table, "*.shop.roxy")["log_level"].as_str(),
+            Some("warn")
+        );
+    }
+
+    #[test]
+    fn set_route_key_by_path() {
+        let mut table = sample();
+        set(
+            &mut table,
+            "domains.myapp.routes./api.log_level",
+            parse_value("debug"),
+        )
+        .unwrap();
+        let routes = domain(&table, "myapp.roxy")["routes"].as_array().unwrap();
+        assert_eq!(routes[1]["log_level"].as_str(), Some("debug"));
+        assert!(routes[0].get("log_level").is_none());
+    }
+
+    #[test]
+    fn set_route_key_by_index() {
+        let mut table = sample();
+        set(
+            &mut table,
+            "domains.myapp.routes.0.log_level",
+            parse_value("error"),
+        )
+        .unwrap();
+        let routes = domain(&table, "myapp.roxy")["routes"].as_array().unwrap();
+        assert_eq!(routes[0]["log_level"].as_str(), Some("error"));
+    }
+
+    #[test]
+    fn set_unknown_domain_fails() {
+        let mut table = sample();
+        let err = set(&mut table, "domains.other.log_level", parse_value("debug")).unwrap_err();
+        assert!(matches!(err, ConfigError::DomainNotFound(_)));
+    }
+
+    #[test]
+    fn set_unknown_route_fails() {
+        let mut table = sample();
+        let err = set(
+            &mut table,
+            "domains.myapp.routes./missing.log_level",
+            parse_value("debug"),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("no route for path '/missing'"));
+    }
+
+    #[test]
+    fn set_below_scalar_fails() {
+        let mut table = sample();
+        let err = set(&mut table, "daemon.http_port.x", Value::Integer(1)).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("'daemon.http_port' is not a table")
+        );
+    }
+
+    #[test]
+    fn empty_segment_is_rejected() {
+        let mut table = sample();
+        assert!(set(&mut table, "daemon..http_port", Value::Integer(1)).is_err());
+    }
+
+    #[test]
+    fn get_resolves_like_set() {
+        let table = sample();
+        assert_eq!(
+            get(&table, "domains.myapp.routes./api.target").and_then(Value::as_str),
+            Some("127.0.0.1:3001")
+        );
+        assert!(get(&table, "domains.myapp.nope").is_none());
+    }
+
+    #[test]
+    fn unset_removes_key() {
+        let mut table = sample();
+        set(&mut table, "domains.myapp.log_level", parse_value("debug")).unwrap();
+        unset(&mut table, "domains.myapp.log_level").unwrap();
+        assert!(domain(&table, "myapp.roxy").get("log_level").is_none());
+    }
+
+    #[test]
+    fn unset_route_key() {
+        let mut table = sample();
+        set(
+            &mut table,
+            "domains.myapp.routes./api.log_level",
+            parse_value("debug"),
+        )
+        .unwrap();
+        unset(&mut table, "domains.myapp.routes./api.log_level").unwrap();
+        let routes = domain(&table, "myapp.roxy")["routes"].as_array().unwrap();
+        assert!(routes[1].get("log_level").is_none());
+    }
+
+    #[test]
+    fn unset_missing_key_fails() {
+        let mut table = sample();
+        let err = unset(&mut table, "domains.myapp.log_level").unwrap_err();
+        assert!(err.to_string().contains("not set"));
+    }
+}
@@ -0,0 +1,92 @@
+//! Domain registrations on their own, written by `roxy export` and read
+//! by `roxy import`, to move a setup to another machine or share it.
+//!
+//! The file has the `[domains]` layout of the config file, so entries
+//! can be copied between the two.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::DomainRegistration;
+
+use super::ConfigError;
+use super::dto::RegistrationDto;
+use super::format::Format;
+
+#[derive(Default, Serialize, Deserialize)]
+struct DomainsFile {
+    #[serde(default)]
+    domains: BTreeMap<String, RegistrationDto>,
+}
+
+/// `registrations` as a TOML domains file. Owners are left out: whoever
+/// imports the file owns what it registers.
+pub fn export_domains(registrations: &[DomainRegistration]) -> Result<String, ConfigError> {
+    let domains = registrations
+        .iter()
+        .map(|registration| {
+            let mut dto = RegistrationDto::from(registration.clone());
+            dto.owner = None;
+            (registration.config_key(), dto)
+        })
+        .collect();
+    Format::Toml.render(&DomainsFile { domains })
+}
+
+/// Read a domains file, as TOML, YAML or JSON by its extension.
+pub fn read_domains(path: &Path) -> Result<Vec<DomainRegistration>, ConfigError> {
+    let content = fs::read_to_string(path)?;
+    let file: DomainsFile = Format::of(path).parse(&content)?;
+    file.domains
+        .into_iter()
+        .map(|(name, dto)| {
+            let registration = DomainRegistration::from(dto);
+            registration
+                .validate()
+                .map_err(|e| ConfigError::InvalidDomain(name, e.to_string()))?;
+            Ok(registration)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{DomainPattern, Route};
+
+    #[test]
+    fn round_trips_registrations_without_owners() {
+        let pattern = DomainPattern::from_name("myapp.roxy", true).unwrap();
+        let routes = vec![
+            Route::parse("/=3000").unwrap(),
+            Route::parse("/api=3001;strip").unwrap(),
+        ];
+        let mut registration = DomainRegistration::new(pattern.clone(), routes);
+        registration.enable_https();
+        registration.set_owner(Some("alice".into()));
+        let plain = DomainRegistration::new(
+            DomainPattern::from_name("plain.roxy", false).unwrap(),
+            vec![Route::parse("/=8080").unwrap()],
+        );
+
+        let exported = export_domains(&[registration, plain]).unwrap();
+        assert!(!exported.contains("alice"));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("domains.toml");
+        fs::write(&path, exported).unwrap();
+        let imported = read_domains(&path).unwrap();
+
+        assert_eq!(imported.len(), 2);
+        let myapp = &imported[0];
+        assert_eq!(myapp.pattern(), &pattern);
+        assert!(myapp.is_https_enabled());
+        assert_eq!(myapp.owner(), None);
+        assert_eq!(myapp.routes().len(), 2);
+        assert!(myapp.routes()[1].options.strip_prefix);
+        assert!(!imported[1].is_https_enabled());
+    }
+}
@@ -0,0 +1,83 @@
+//! Config file formats: TOML, or YAML and JSON for configs written by
+//! other tools, picked by the file's extension.
+
+use std::path::Path;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use super::ConfigError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl Format {
+    /// `.yaml`/`.yml` and `.json` files, anything else is TOML.
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") => {
+                Self::Yaml
+            }
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Toml,
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(self, content: &str) -> Result<T, ConfigError> {
+        Ok(match self {
+            Self::Toml => toml::from_str(content)?,
+            Self::Yaml => serde_yaml::from_str(content)?,
+            Self::Json => serde_json::from_str(content)?,
+        })
+    }
+
+    /// Written through a TOML table, so every format leaves out unset
+    /// values instead of writing `null`s `roxy config set` can't read.
+    pub fn render<T: Serialize>(self, value: &T) -> Result<String, ConfigError> {
+        if self == Self::Toml {
+            return Ok(toml::to_string_pretty(value)?);
+        }
+        let table = toml::Table::try_from(value)?;
+        Ok(match self {
+            Self::Yaml => serde_yaml::to_string(&table)?,
+            _ => serde_json::to_string_pretty(&table)? + "\n",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_format_by_extension() {
+        assert_eq!(Format::of(Path::new("/etc/roxy/config.toml")), Format::Toml);
+        assert_eq!(Format::of(Path::new("roxy.yaml")), Format::Yaml);
+        assert_eq!(Format::of(Path::new("roxy.YML")), Format::Yaml);
+        assert_eq!(Format::of(Path::new("roxy.json")), Format::Json);
+        assert_eq!(Format::of(Path::new("roxy")), Format::Toml);
+    }
+
+    #[test]
+    fn leaves_out_unset_values() {
+        #[derive(Serialize)]
+        struct Section {
+            port: u16,
+            user: Option<String>,
+        }
+        let section = Section {
+            port: 80,
+            user: None,
+        };
+
+        assert_eq!(
+            Format::Json.render(&section).unwrap(),
+            "{\n  \"port\": 80\n}\n"
+        );
+        assert_eq!(Format::Yaml.render(&section).unwrap(), "port: 80\n");
+    }
+}
@@ -0,0 +1,118 @@
+//! Copies of the config file taken before each change, backing
+//! `roxy config history` and `roxy config rollback`.
+//!
+//! Snapshots live in a `history` directory next to the config file,
+//! named after it with a sequence number (`config.toml.7`). Only the
+//! most recent [`LIMIT`] are kept.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Number of snapshots kept.
+pub const LIMIT: usize = 20;
+
+pub struct History {
+    dir: PathBuf,
+    prefix: String,
+}
+
+/// A snapshot on disk and when it was taken.
+pub struct Entry {
+    pub path: PathBuf,
+    pub saved_at: SystemTime,
+}
+
+impl History {
+    pub fn of(config_path: &Path) -> Self {
+        let dir = config_path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join("history");
+        let name = config_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self {
+            dir,
+            prefix: format!("{}.", name),
+        }
+    }
+
+    /// Snapshots, newest first.
+    pub fn list(&self) -> io::Result<Vec<Entry>> {
+        self.numbered()?
+            .into_iter()
+            .map(|(_, path)| {
+                let saved_at = fs::metadata(&path)?.modified()?;
+                Ok(Entry { path, saved_at })
+            })
+            .collect()
+    }
+
+    /// Keep `content` as the newest snapshot, dropping the oldest ones
+    /// beyond the limit.
+    pub fn record(&self, content: &str) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let existing = self.numbered()?;
+        let next = existing.first().map_or(1, |(seq, _)| seq + 1);
+        fs::write(self.dir.join(format!("{}{}", self.prefix, next)), content)?;
+
+        for (_, path) in existing.iter().skip(LIMIT - 1) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Snapshot files with their sequence numbers, highest first.
+    fn numbered(&self) -> io::Result<Vec<(u64, PathBuf)>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut numbered = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let seq: Option<u64> = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(&self.prefix))
+                .and_then(|seq| seq.parse().ok());
+            if let Some(seq) = seq {
+                numbered.push((seq, path));
+            }
+        }
+        numbered.sort_by_key(|(seq, _)| std::cmp::Reverse(*seq));
+        Ok(numbered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_most_recent_snapshots() {
+        let tmp = tempfile::tempdir().unwrap();
+        let history = History::of(&tmp.path().join("config.toml"));
+        assert!(history.list().unwrap().is_empty());
+
+        for i in 0..LIMIT + 3 {
+            history.record(&format!("version {}", i)).unwrap();
+        }
+        // Another config's snapshots are left alone
+        History::of(&tmp.path().join("other.toml"))
+            .record("other")
+            .unwrap();
+
+        let entries = history.list().unwrap();
+        assert_eq!(entries.len(), LIMIT);
+        let newest = fs::read_to_string(&entries[0].path).unwrap();
+        let oldest = fs::read_to_string(&entries[LIMIT - 1].path).unwrap();
+        assert_eq!(newest, format!("version {}", LIMIT + 2));
+        assert_eq!(oldest, "version 3");
+    }
+}
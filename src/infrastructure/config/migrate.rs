@@ -0,0 +1,80 @@
+//! Upgrades config files written by older versions of roxy.
+//!
+//! Every config file records the layout it was written in as its
+//! `version`. When a release changes the layout, it bumps
+//! [`CURRENT_VERSION`] and adds a step to [`MIGRATIONS`] that rewrites
+//! the previous layout, so files upgrade on first load instead of
+//! failing to parse.
+
+use super::ConfigError;
+
+/// Layout this build reads and writes.
+pub const CURRENT_VERSION: u32 = 1;
+
+/// Step `n` turns a version `n` document into a version `n + 1` one.
+const MIGRATIONS: [fn(&mut toml::Table); CURRENT_VERSION as usize] = [unversioned];
+
+/// Bring `document` to [`CURRENT_VERSION`]. Returns the version it was
+/// upgraded from, or `None` if it was current already.
+pub fn upgrade(document: &mut toml::Table) -> Result<Option<u32>, ConfigError> {
+    let version = match document.get("version") {
+        None => 0,
+        Some(value) => value
+            .as_integer()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| {
+                ConfigError::InvalidConfig(format!("version must be a number, got {}", value))
+            })?,
+    };
+    if version > CURRENT_VERSION {
+        return Err(ConfigError::NewerVersion(version, CURRENT_VERSION));
+    }
+    if version == CURRENT_VERSION {
+        return Ok(None);
+    }
+
+    for migrate in &MIGRATIONS[version as usize..] {
+        migrate(document);
+    }
+    document.insert("version".into(), i64::from(CURRENT_VERSION).into());
+    Ok(Some(version))
+}
+
+/// Files from before versioning have the version 1 layout, just
+/// without the number.
+fn unversioned(_document: &mut toml::Table) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamps_unversioned_documents() {
+        let mut document: toml::Table = toml::from_str("[daemon]\nhttp_port = 8080\n").unwrap();
+
+        assert_eq!(upgrade(&mut document).unwrap(), Some(0));
+        assert_eq!(
+            document["version"].as_integer(),
+            Some(CURRENT_VERSION as i64)
+        );
+        assert_eq!(document["daemon"]["http_port"].as_integer(), Some(8080));
+
+        assert_eq!(upgrade(&mut document).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_versions_it_does_not_know() {
+        let mut newer: toml::Table =
+            toml::from_str(&format!("version = {}\n", CURRENT_VERSION + 1)).unwrap();
+        assert!(matches!(
+            upgrade(&mut newer),
+            Err(ConfigError::NewerVersion(..))
+        ));
+
+        let mut garbled: toml::Table = toml::from_str("version = \"one\"\n").unwrap();
+        assert!(matches!(
+            upgrade(&mut garbled),
+            Err(ConfigError::InvalidConfig(_))
+        ));
+    }
+}
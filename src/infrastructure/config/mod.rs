@@ -1,11 +1,28 @@
 mod dto;
+mod edit;
+mod export;
+mod format;
+mod history;
+mod migrate;
+mod profile;
 
-use crate::domain::{DomainPattern, DomainRegistration};
+use crate::domain::{AccessPolicy, DomainPattern, DomainRegistration};
+use crate::infrastructure::certs::{CertsConfig, MAX_LEAF_DAYS};
+use crate::infrastructure::dns::{DnsBackend, UnknownNames};
 use crate::infrastructure::paths::RoxyPaths;
+use crate::infrastructure::tls::TlsConfig;
 use dto::RegistrationDto;
-use std::collections::HashMap;
+pub use export::{export_domains, read_domains};
+use format::Format;
+use history::History;
+use migrate::CURRENT_VERSION;
+use profile::Profile;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::fs;
+use std::net::Ipv4Addr;
 use std::path::PathBuf;
+use std::time::SystemTime;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -19,6 +36,12 @@ pub enum ConfigError {
     #[error("Failed to serialize config: {0}")]
     SerializeError(#[from] toml::ser::Error),
 
+    #[error("Invalid YAML config: {0}")]
+    YamlError(#[from] serde_yaml::Error),
+
+    #[error("Invalid JSON config: {0}")]
+    JsonError(#[from] serde_json::Error),
+
     #[error("Domain already registered: {0}")]
     DomainExists(String),
 
@@ -30,8 +53,60 @@ pub enum ConfigError {
 
     #[error("Invalid domain '{0}': {1}")]
     InvalidDomain(String, String),
+
+    #[error("Invalid config key '{0}': {1}")]
+    InvalidKey(String, String),
+
+    #[error("Unknown config key '{0}'")]
+    UnknownKey(String),
+
+    #[error("Invalid value for {0}: {1}")]
+    InvalidEnv(String, String),
+
+    #[error("No config snapshot {0}. See: roxy config history")]
+    NoSnapshot(usize),
+
+    #[error("Profile '{0}' already exists")]
+    ProfileExists(String),
+
+    #[error("No profile '{0}'. See: roxy profile list")]
+    ProfileNotFound(String),
+
+    #[error("Invalid profile '{0}': {1}")]
+    InvalidProfile(String, String),
+
+    #[error("Config file is version {0}, but this roxy only reads up to version {1}. Upgrade roxy")]
+    NewerVersion(u32, u32),
 }
 
+/// Port mDNS uses, shared with the system's own responder.
+const MDNS_PORT: u16 = 5353;
+
+/// Prefix of the environment variables that override `[daemon]` keys,
+/// e.g. `ROXY_HTTP_PORT` for `http_port`.
+const ENV_PREFIX: &str = "ROXY_";
+
+/// `[daemon]` keys that can be overridden from the environment.
+const ENV_KEYS: &[&str] = &[
+    "http_port",
+    "https_port",
+    "dns_port",
+    "dns_backend",
+    "dns_ttl",
+    "dns_unknown",
+    "log_level",
+    "connect_timeout",
+    "request_timeout",
+    "stream_idle_timeout",
+    "health_check_interval",
+    "cert_renewal_days",
+    "localhost",
+    "mdns",
+    "http3",
+    "user",
+    "proxy_port",
+];
+
 fn default_http_port() -> u16 {
     80
 }
@@ -44,10 +119,34 @@ fn default_dns_port() -> u16 {
     1053
 }
 
+fn default_dns_ttl() -> u32 {
+    1
+}
+
+fn default_connect_timeout() -> u64 {
+    5_000
+}
+
+fn default_request_timeout() -> u64 {
+    60_000
+}
+
+fn default_health_check_interval() -> u64 {
+    10_000
+}
+
+fn default_cert_renewal_days() -> u32 {
+    30
+}
+
 fn default_log_level() -> String {
     "info".to_string()
 }
 
+fn default_proxy_access() -> AccessPolicy {
+    AccessPolicy::Lan
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct DaemonConfig {
     #[serde(default = "default_http_port")]
@@ -59,8 +158,98 @@ pub struct DaemonConfig {
     #[serde(default = "default_dns_port")]
     pub dns_port: u16,
 
+    /// `hosts` writes registered domains to the hosts file instead of
+    /// running the DNS server.
+    #[serde(default)]
+    pub dns_backend: DnsBackend,
+
+    /// Seconds resolvers may cache answers for.
+    #[serde(default = "default_dns_ttl")]
+    pub dns_ttl: u32,
+
+    /// `nxdomain` answers queries for names outside `.roxy` with "no
+    /// such name" instead of refusing them.
+    #[serde(default)]
+    pub dns_unknown: UnknownNames,
+
     #[serde(default = "default_log_level")]
     pub log_level: String,
+
+    /// Milliseconds to wait for a backend connection; 0 waits forever.
+    /// Routes can override it.
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+
+    /// Milliseconds to wait for a backend's response headers; 0 waits
+    /// forever. Routes can override it.
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+
+    /// Milliseconds an event stream (Server-Sent Events) may go without
+    /// sending anything before it is closed; 0 keeps it open. Routes can
+    /// override it.
+    #[serde(default)]
+    pub stream_idle_timeout: u64,
+
+    /// Milliseconds between checks of every proxy backend; 0 turns
+    /// health checks off.
+    #[serde(default = "default_health_check_interval")]
+    pub health_check_interval: u64,
+
+    /// Domain certificates expiring within this many days are renewed
+    /// by the daemon and on `roxy reload`; 0 turns renewal off.
+    #[serde(default = "default_cert_renewal_days")]
+    pub cert_renewal_days: u32,
+
+    /// Also answer `<name>.localhost` for every `<name>.roxy` domain.
+    #[serde(default)]
+    pub localhost: bool,
+
+    /// Publish `<name>.local` for every `<name>.roxy` domain over mDNS
+    /// (Bonjour), for phones and tablets on the same network.
+    #[serde(default)]
+    pub mdns: bool,
+
+    /// Also serve HTTPS over HTTP/3 (QUIC, UDP) on the HTTPS port, and
+    /// tell clients so with `Alt-Svc`.
+    #[serde(default)]
+    pub http3: bool,
+
+    /// Set by `roxy install --rootless`: the daemon binds high ports and
+    /// the firewall forwards 80/443 to them.
+    #[serde(default)]
+    pub rootless: bool,
+
+    /// Extra answers for DNS queries from container VMs, VPNs and VM
+    /// bridges: source network (IPv4 or IPv6 CIDR) to the address the
+    /// host has on it. Checked before everything else, including the
+    /// built-in Docker Desktop, Colima, OrbStack and Podman networks.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub container_networks: BTreeMap<String, Ipv4Addr>,
+
+    /// Account the daemon switches to once its ports are bound.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+
+    /// Port for the optional forward proxy. Disabled when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_port: Option<u16>,
+
+    /// Hosts outside `.roxy` whose HTTPS traffic the forward proxy
+    /// decrypts and logs instead of tunnelling. `*.example.com` also
+    /// matches subdomains.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub proxy_intercept: Vec<String>,
+
+    /// Clients that may use the forward proxy, given like a domain's
+    /// `access`. This machine and private networks by default.
+    #[serde(default = "default_proxy_access")]
+    pub proxy_access: AccessPolicy,
+
+    /// Let the forward proxy connect to loopback and link-local
+    /// addresses, which reach services this machine keeps to itself.
+    #[serde(default)]
+    pub proxy_local_targets: bool,
 }
 
 impl Default for DaemonConfig {
@@ -69,12 +258,43 @@ impl Default for DaemonConfig {
             http_port: default_http_port(),
             https_port: default_https_port(),
             dns_port: default_dns_port(),
+            dns_backend: DnsBackend::default(),
+            dns_ttl: default_dns_ttl(),
+            dns_unknown: UnknownNames::default(),
             log_level: default_log_level(),
+            connect_timeout: default_connect_timeout(),
+            request_timeout: default_request_timeout(),
+            stream_idle_timeout: 0,
+            health_check_interval: default_health_check_interval(),
+            cert_renewal_days: default_cert_renewal_days(),
+            localhost: false,
+            mdns: false,
+            http3: false,
+            rootless: false,
+            container_networks: BTreeMap::new(),
+            user: None,
+            proxy_port: None,
+            proxy_intercept: Vec::new(),
+            proxy_access: default_proxy_access(),
+            proxy_local_targets: false,
         }
     }
 }
 
 impl DaemonConfig {
+    /// The HTTPS port clients connect to: 443 when the firewall
+    /// forwards it to a rootless daemon.
+    pub fn public_https_port(&self) -> u16 {
+        if self.rootless { 443 } else { self.https_port }
+    }
+
+    /// The UDP port HTTP/3 is served on, when it's on. The firewall of
+    /// a rootless install only forwards TCP, so clients are sent to the
+    /// port itself.
+    pub fn http3_port(&self) -> Option<u16> {
+        self.http3.then_some(self.https_port)
+    }
+
     pub fn validate(&self) -> Result<(), ConfigError> {
         if self.http_port == 0 {
             return Err(ConfigError::InvalidConfig("http_port cannot be 0".into()));
@@ -85,6 +305,13 @@ impl DaemonConfig {
         if self.dns_port == 0 {
             return Err(ConfigError::InvalidConfig("dns_port cannot be 0".into()));
         }
+        // RFC 2181 treats larger TTLs as zero
+        if self.dns_ttl > i32::MAX as u32 {
+            return Err(ConfigError::InvalidConfig(format!(
+                "dns_ttl cannot exceed {}",
+                i32::MAX
+            )));
+        }
         if self.http_port == self.https_port {
             return Err(ConfigError::InvalidConfig(
                 "http_port and https_port must be different".into(),
@@ -99,6 +326,32 @@ impl DaemonConfig {
             ));
         }
 
+        if self.mdns && self.dns_port == MDNS_PORT {
+            return Err(ConfigError::InvalidConfig(format!(
+                "dns_port cannot be {MDNS_PORT} while mdns is on"
+            )));
+        }
+
+        if let Some(proxy_port) = self.proxy_port {
+            if proxy_port == 0 {
+                return Err(ConfigError::InvalidConfig("proxy_port cannot be 0".into()));
+            }
+            if proxy_port == self.http_port || proxy_port == self.https_port {
+                return Err(ConfigError::InvalidConfig(
+                    "proxy_port must differ from http_port and https_port".into(),
+                ));
+            }
+        }
+
+        for network in self.container_networks.keys() {
+            if network.parse::<ipnet::IpNet>().is_err() {
+                return Err(ConfigError::InvalidConfig(format!(
+                    "Invalid container network '{}'. Expected CIDR notation, e.g. 192.168.5.0/24 or fd00:1::/64",
+                    network
+                )));
+            }
+        }
+
         let valid_levels = ["error", "warn", "info", "debug"];
         if !valid_levels.contains(&self.log_level.as_str()) {
             return Err(ConfigError::InvalidConfig(format!(
@@ -112,8 +365,17 @@ impl DaemonConfig {
     }
 }
 
-#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct Config {
+    /// Layout of the file, see [`migrate`]. Files from before it was
+    /// recorded are upgraded on load, so it is always set by then.
+    #[serde(default)]
+    version: u32,
+
+    /// Profile whose domains are in `domains`; unset for the default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+
     #[serde(default)]
     pub daemon: DaemonConfig,
 
@@ -121,7 +383,33 @@ pub struct Config {
     pub paths: RoxyPaths,
 
     #[serde(default)]
-    domains: HashMap<String, RegistrationDto>,
+    pub certs: CertsConfig,
+
+    #[serde(default)]
+    pub tls: TlsConfig,
+
+    /// Ordered, so saving the config doesn't reshuffle it.
+    #[serde(default)]
+    domains: BTreeMap<String, RegistrationDto>,
+
+    /// Domains of the profiles not in use.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            profile: None,
+            daemon: DaemonConfig::default(),
+            paths: RoxyPaths::default(),
+            certs: CertsConfig::default(),
+            tls: TlsConfig::default(),
+            domains: BTreeMap::new(),
+            profiles: BTreeMap::new(),
+        }
+    }
 }
 
 impl Config {
@@ -134,8 +422,41 @@ impl Config {
             .collect()
     }
 
+    /// Apply `ROXY_<KEY>` variables from `vars` on top of the `[daemon]`
+    /// section. Values are read like `roxy config set` ones; empty ones
+    /// are ignored.
+    fn override_from(
+        &mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<(), ConfigError> {
+        for (name, value) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX).map(str::to_ascii_lowercase) else {
+                continue;
+            };
+            if !ENV_KEYS.contains(&key.as_str()) || value.is_empty() {
+                continue;
+            }
+
+            let mut daemon = toml::Table::try_from(&self.daemon)?;
+            daemon.insert(key, edit::parse_value(&value));
+            self.daemon = toml::Value::Table(daemon)
+                .try_into()
+                .map_err(|e: toml::de::Error| ConfigError::InvalidEnv(name, e.message().into()))?;
+        }
+        Ok(())
+    }
+
     pub fn validate(&self) -> Result<(), ConfigError> {
         self.daemon.validate()?;
+        self.check_certs()?;
+        self.tls
+            .check()
+            .map_err(|e| ConfigError::InvalidConfig(format!("[tls]: {}", e)))?;
+        if self.daemon.http3 && !self.tls.supports_quic() {
+            return Err(ConfigError::InvalidConfig(
+                "http3 needs TLS13_AES_128_GCM_SHA256 among the [tls] cipher suites".into(),
+            ));
+        }
 
         for (name, dto) in &self.domains {
             let registration = DomainRegistration::from(dto.clone());
@@ -144,18 +465,130 @@ impl Config {
                 .map_err(|e| ConfigError::InvalidDomain(name.clone(), e.to_string()))?;
         }
 
+        self.check_tcp_ports()
+    }
+
+    fn check_certs(&self) -> Result<(), ConfigError> {
+        let certs = &self.certs;
+        if certs.leaf_days == 0 || certs.leaf_days > MAX_LEAF_DAYS {
+            return Err(ConfigError::InvalidConfig(format!(
+                "certs.leaf_days must be between 1 and {} (the most macOS accepts)",
+                MAX_LEAF_DAYS
+            )));
+        }
+        if certs.on_demand_cache == 0 {
+            return Err(ConfigError::InvalidConfig(
+                "certs.on_demand_cache must be at least 1".into(),
+            ));
+        }
+        if certs.ca_days < certs.leaf_days {
+            return Err(ConfigError::InvalidConfig(
+                "certs.ca_days cannot be less than certs.leaf_days".into(),
+            ));
+        }
+        // Otherwise every certificate would be renewed on every check
+        if self.daemon.cert_renewal_days >= certs.leaf_days {
+            return Err(ConfigError::InvalidConfig(format!(
+                "cert_renewal_days must be less than certs.leaf_days ({})",
+                certs.leaf_days
+            )));
+        }
         Ok(())
     }
+
+    /// Each forwarded TCP port can only belong to one domain, and not
+    /// to one of the daemon's own listeners.
+    fn check_tcp_ports(&self) -> Result<(), ConfigError> {
+        let daemon = &self.daemon;
+        let reserved = [
+            Some(daemon.http_port),
+            Some(daemon.https_port),
+            Some(daemon.dns_port),
+            daemon.proxy_port,
+        ];
+        let mut owners = HashMap::new();
+        for (name, dto) in &self.domains {
+            for forward in &dto.tcp_forwards {
+                let port = forward.port();
+                if reserved.contains(&Some(port)) {
+                    return Err(ConfigError::InvalidConfig(format!(
+                        "TCP port {} of {} is used by the daemon itself",
+                        port, name
+                    )));
+                }
+                if let Some(other) = owners.insert(port, name) {
+                    return Err(ConfigError::InvalidConfig(format!(
+                        "TCP port {} is forwarded by both {} and {}",
+                        port, other, name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An earlier version of the config file, kept by [`ConfigStore::save`].
+pub struct Snapshot {
+    pub saved_at: SystemTime,
+    /// Number of registered domains, `None` if the snapshot can't be read.
+    pub domains: Option<usize>,
+}
+
+/// Domains added, removed or changed between two sets of
+/// registrations, by pattern.
+#[derive(Debug, Default, PartialEq)]
+pub struct DomainChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl DomainChanges {
+    /// Compare registrations as they would be written to the config file.
+    pub fn between(before: &[DomainRegistration], after: &[DomainRegistration]) -> Self {
+        let entries = |registrations: &[DomainRegistration]| -> BTreeMap<String, toml::Value> {
+            registrations
+                .iter()
+                .map(|registration| {
+                    let dto = RegistrationDto::from(registration.clone());
+                    let value = toml::Value::try_from(dto)
+                        .unwrap_or_else(|e| toml::Value::String(e.to_string()));
+                    (registration.display_pattern(), value)
+                })
+                .collect()
+        };
+        let before = entries(before);
+        let after = entries(after);
+
+        let mut changes = Self::default();
+        for (pattern, value) in &after {
+            match before.get(pattern) {
+                None => changes.added.push(pattern.clone()),
+                Some(old) if old != value => changes.changed.push(pattern.clone()),
+                Some(_) => {}
+            }
+        }
+        changes.removed = before
+            .into_keys()
+            .filter(|pattern| !after.contains_key(pattern))
+            .collect();
+        changes
+    }
 }
 
 pub struct ConfigStore {
     path: PathBuf,
+    format: Format,
 }
 
 impl ConfigStore {
-    /// Create a new ConfigStore pointing at the given config file path
+    /// Create a new ConfigStore pointing at the given config file path.
+    /// `.yaml`/`.yml` and `.json` files are read and written as YAML and
+    /// JSON, anything else as TOML.
     pub fn new(path: PathBuf) -> Self {
-        Self { path }
+        let format = Format::of(&path);
+        Self { path, format }
     }
 
     fn ensure_config_dir(&self) -> Result<(), ConfigError> {
@@ -167,26 +600,118 @@ impl ConfigStore {
         Ok(())
     }
 
+    /// The config file with `ROXY_*` environment overrides applied, so
+    /// containers and CI can adjust the daemon without editing it.
     pub fn load(&self) -> Result<Config, ConfigError> {
+        let mut config = self.read()?;
+        config.override_from(env::vars())?;
+        Ok(config)
+    }
+
+    /// The config file as written, for changes that are saved back.
+    pub fn read(&self) -> Result<Config, ConfigError> {
         if !self.path.exists() {
             return Ok(Config::default());
         }
 
         let content = fs::read_to_string(&self.path)?;
-        let config = toml::from_str(&content)?;
+        let (document, upgraded_from) = self.parse(&content)?;
+        let config: Config = toml::Value::Table(document).try_into()?;
+
+        // Best effort: without write access (e.g. `roxy list` without
+        // sudo) the upgrade happens again on the next load
+        if let Some(version) = upgraded_from {
+            let _ = self.upgrade_file(&content, version, &config);
+        }
         Ok(config)
     }
 
+    /// A config file's content as a document in the current layout,
+    /// with the version it was upgraded from if it was older.
+    fn parse(&self, content: &str) -> Result<(toml::Table, Option<u32>), ConfigError> {
+        let mut document: toml::Table = self.format.parse(content)?;
+        let upgraded_from = migrate::upgrade(&mut document)?;
+        Ok((document, upgraded_from))
+    }
+
+    /// Rewrite an old config file in the current layout, first copying
+    /// it to `<name>.v<version>.bak` next to it.
+    fn upgrade_file(
+        &self,
+        content: &str,
+        version: u32,
+        config: &Config,
+    ) -> Result<(), ConfigError> {
+        let name = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        fs::write(
+            self.path
+                .with_file_name(format!("{}.v{}.bak", name, version)),
+            content,
+        )?;
+        self.save(config)
+    }
+
     pub fn save(&self, config: &Config) -> Result<(), ConfigError> {
         self.ensure_config_dir()?;
 
-        let content = toml::to_string_pretty(config)?;
+        let content = self.format.render(config)?;
+        self.write(&content)
+    }
+
+    /// Replace the config file, keeping the previous version in its
+    /// history.
+    fn write(&self, content: &str) -> Result<(), ConfigError> {
+        if let Ok(previous) = fs::read_to_string(&self.path)
+            && previous != content
+        {
+            History::of(&self.path).record(&previous)?;
+        }
         fs::write(&self.path, content)?;
         Ok(())
     }
 
+    /// Earlier versions of the config file, newest first.
+    pub fn history(&self) -> Result<Vec<Snapshot>, ConfigError> {
+        let entries = History::of(&self.path).list()?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| Snapshot {
+                saved_at: entry.saved_at,
+                domains: fs::read_to_string(&entry.path)
+                    .ok()
+                    .and_then(|content| self.parse(&content).ok())
+                    .and_then(|(document, _)| toml::Value::Table(document).try_into().ok())
+                    .map(|config: Config| config.domains.len()),
+            })
+            .collect())
+    }
+
+    /// Restore the `n`th most recent snapshot, 1 being the config before
+    /// the last change. The current config is kept as a snapshot too, so
+    /// a rollback can itself be rolled back.
+    pub fn rollback(&self, n: usize) -> Result<Snapshot, ConfigError> {
+        let entry = History::of(&self.path)
+            .list()?
+            .into_iter()
+            .nth(n.wrapping_sub(1))
+            .ok_or(ConfigError::NoSnapshot(n))?;
+        // An older snapshot is upgraded on the next load
+        let content = fs::read_to_string(&entry.path)?;
+        let config = self.validated(self.parse(&content)?.0)?;
+
+        self.write(&content)?;
+        Ok(Snapshot {
+            saved_at: entry.saved_at,
+            domains: Some(config.domains.len()),
+        })
+    }
+
     pub fn add_domain(&self, registration: DomainRegistration) -> Result<(), ConfigError> {
-        let mut config = self.load()?;
+        let mut config = self.read()?;
 
         let key = registration.config_key();
         if config.domains.contains_key(&key) {
@@ -201,7 +726,7 @@ impl ConfigStore {
         &self,
         pattern: &DomainPattern,
     ) -> Result<DomainRegistration, ConfigError> {
-        let mut config = self.load()?;
+        let mut config = self.read()?;
 
         let key = pattern.display_pattern();
         let dto = config
@@ -227,7 +752,7 @@ impl ConfigStore {
     }
 
     pub fn update_domain(&self, registration: DomainRegistration) -> Result<(), ConfigError> {
-        let mut config = self.load()?;
+        let mut config = self.read()?;
 
         let key = registration.config_key();
         if !config.domains.contains_key(&key) {
@@ -235,9 +760,48 @@ impl ConfigStore {
         }
 
         config.domains.insert(key, registration.into());
+        config.check_tcp_ports()?;
+        self.save(&config)
+    }
+
+    /// Set a single value by dotted key, e.g. `domains.myapp.log_level`.
+    /// The result is validated before it is written.
+    pub fn set_value(&self, key: &str, raw_value: &str) -> Result<(), ConfigError> {
+        let mut document = self.load_document()?;
+        edit::set(&mut document, key, edit::parse_value(raw_value))?;
+        let config = self.validated(document)?;
+
+        // Keys the config doesn't know about would silently vanish on save
+        let saved = toml::Table::try_from(&config)?;
+        if edit::get(&saved, key).is_none() {
+            return Err(ConfigError::UnknownKey(key.to_string()));
+        }
+
+        self.save(&config)
+    }
+
+    /// Remove a single value by dotted key, reverting it to its default.
+    pub fn unset_value(&self, key: &str) -> Result<(), ConfigError> {
+        let mut document = self.load_document()?;
+        edit::unset(&mut document, key)?;
+        let config = self.validated(document)?;
         self.save(&config)
     }
 
+    fn load_document(&self) -> Result<toml::Table, ConfigError> {
+        if !self.path.exists() {
+            return Ok(toml::Table::new());
+        }
+        let content = fs::read_to_string(&self.path)?;
+        Ok(self.parse(&content)?.0)
+    }
+
+    fn validated(&self, document: toml::Table) -> Result<Config, ConfigError> {
+        let config: Config = toml::Value::Table(document).try_into()?;
+        config.validate()?;
+        Ok(config)
+    }
+
     pub fn list_domains(&self) -> Result<Vec<DomainRegistration>, ConfigError> {
         let config = self.load()?;
         Ok(config
@@ -266,11 +830,69 @@ mod tests {
             http_port: 8080,
             https_port: 8443,
             dns_port: 5353,
+            dns_backend: DnsBackend::Hosts,
+            dns_ttl: 60,
+            dns_unknown: UnknownNames::NxDomain,
             log_level: "debug".to_string(),
+            connect_timeout: 250,
+            request_timeout: 0,
+            stream_idle_timeout: 300_000,
+            health_check_interval: 0,
+            cert_renewal_days: 0,
+            localhost: true,
+            mdns: false,
+            http3: true,
+            rootless: false,
+            container_networks: BTreeMap::from([(
+                "10.0.2.0/24".to_string(),
+                Ipv4Addr::new(10, 0, 2, 2),
+            )]),
+            user: Some("_roxy".to_string()),
+            proxy_port: Some(8888),
+            proxy_intercept: vec!["api.example.com".to_string()],
+            proxy_access: AccessPolicy::Any,
+            proxy_local_targets: true,
         };
         assert!(config.validate().is_ok());
     }
 
+    #[test]
+    fn mdns_needs_its_port() {
+        let config = DaemonConfig {
+            dns_port: 5353,
+            mdns: true,
+            ..DaemonConfig::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("dns_port cannot be 5353"));
+    }
+
+    #[test]
+    fn container_network_must_be_cidr() {
+        let config = DaemonConfig {
+            container_networks: BTreeMap::from([(
+                "192.168.5.2".to_string(),
+                Ipv4Addr::new(192, 168, 5, 2),
+            )]),
+            ..DaemonConfig::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Invalid container network '192.168.5.2'")
+        );
+    }
+
+    #[test]
+    fn proxy_port_must_not_clash() {
+        let config = DaemonConfig {
+            proxy_port: Some(443),
+            ..DaemonConfig::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("proxy_port must differ"));
+    }
+
     #[test]
     fn zero_http_port_is_invalid() {
         let config = DaemonConfig {
@@ -301,6 +923,107 @@ mod tests {
         assert!(err.to_string().contains("dns_port cannot be 0"));
     }
 
+    #[test]
+    fn out_of_range_dns_ttl_is_invalid() {
+        let config = DaemonConfig {
+            dns_ttl: u32::MAX,
+            ..DaemonConfig::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("dns_ttl cannot exceed"));
+    }
+
+    #[test]
+    fn cert_renewal_window_must_be_shorter_than_validity() {
+        let config = Config {
+            daemon: DaemonConfig {
+                cert_renewal_days: 365,
+                ..DaemonConfig::default()
+            },
+            ..Config::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("cert_renewal_days must be less than certs.leaf_days (365)")
+        );
+    }
+
+    #[test]
+    fn cert_validity_must_be_usable() {
+        let with_certs = |leaf_days, ca_days| Config {
+            daemon: DaemonConfig {
+                cert_renewal_days: 0,
+                ..DaemonConfig::default()
+            },
+            certs: CertsConfig {
+                leaf_days,
+                ca_days,
+                ..CertsConfig::default()
+            },
+            ..Config::default()
+        };
+        assert!(with_certs(90, 90).validate().is_ok());
+        for (leaf_days, ca_days) in [(0, 3650), (826, 3650), (365, 100)] {
+            assert!(with_certs(leaf_days, ca_days).validate().is_err());
+        }
+
+        let mut no_cache = with_certs(90, 90);
+        no_cache.certs.on_demand_cache = 0;
+        assert!(no_cache.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_unusable_tls_section() {
+        let config: Config = toml::from_str(
+            r#"
+            [tls]
+            min_version = "1.3"
+            cipher_suites = ["TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256"]
+            "#,
+        )
+        .unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("[tls]"));
+    }
+
+    #[test]
+    fn http3_needs_the_quic_cipher_suite() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [daemon]
+            http3 = true
+
+            [tls]
+            cipher_suites = ["TLS13_AES_256_GCM_SHA384"]
+            "#,
+        )
+        .unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("TLS13_AES_128_GCM_SHA256"));
+
+        config.daemon.http3 = false;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn parses_certs_section() {
+        let config: Config = toml::from_str(
+            r#"
+            [certs]
+            leaf_days = 90
+            key_type = "rsa-2048"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.certs.leaf_days, 90);
+        assert_eq!(config.certs.ca_days, 3650);
+        assert_eq!(
+            config.certs.key_type,
+            crate::infrastructure::certs::KeyType::Rsa2048
+        );
+    }
+
     #[test]
     fn duplicate_http_and_https_ports_is_invalid() {
         let config = DaemonConfig {
@@ -374,4 +1097,249 @@ mod tests {
         };
         assert!(config.validate().is_err());
     }
+
+    #[test]
+    fn tcp_ports_belong_to_one_domain() {
+        let registration = |name: &str, forward: &str| {
+            let pattern = DomainPattern::from_name(name, false).unwrap();
+            let route = crate::domain::Route::parse("/=3000").unwrap();
+            let mut registration = DomainRegistration::new(pattern, vec![route]);
+            registration
+                .add_tcp_forward(crate::domain::TcpForward::parse(forward).unwrap())
+                .unwrap();
+            RegistrationDto::from(registration)
+        };
+        let mut config = Config::default();
+        config
+            .domains
+            .insert("db.roxy".into(), registration("db.roxy", "5432=5433"));
+        config
+            .domains
+            .insert("cache.roxy".into(), registration("cache.roxy", "6379=6380"));
+        assert!(config.validate().is_ok());
+
+        config
+            .domains
+            .insert("other.roxy".into(), registration("other.roxy", "5432=5434"));
+        assert!(config.validate().is_err());
+
+        config.domains.remove("other.roxy");
+        config.daemon.proxy_port = Some(6379);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn environment_overrides_daemon_keys() {
+        let mut config: Config = toml::from_str("[daemon]\nhttp_port = 8080\n").unwrap();
+        let vars = [
+            ("ROXY_HTTP_PORT", "9080"),
+            ("ROXY_LOG_LEVEL", "debug"),
+            ("ROXY_DNS_BACKEND", "hosts"),
+            ("ROXY_MDNS", "true"),
+            ("ROXY_DNS_PORT", ""),
+            ("ROXY_LOG", "trace"),
+            ("ROXY_URL", "https://myapp.roxy"),
+        ];
+        config
+            .override_from(vars.map(|(k, v)| (k.to_string(), v.to_string())))
+            .unwrap();
+
+        assert_eq!(config.daemon.http_port, 9080);
+        assert_eq!(config.daemon.log_level, "debug");
+        assert_eq!(config.daemon.dns_backend, DnsBackend::Hosts);
+        assert!(config.daemon.mdns);
+        assert_eq!(config.daemon.dns_port, default_dns_port());
+    }
+
+    #[test]
+    fn invalid_environment_override_names_the_variable() {
+        let mut config = Config::default();
+        let err = config
+            .override_from([("ROXY_HTTPS_PORT".to_string(), "secure".to_string())])
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("Invalid value for ROXY_HTTPS_PORT"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn domain_changes_compare_whole_registrations() {
+        let registration = |name: &str, route: &str| {
+            let pattern = DomainPattern::from_name(name, false).unwrap();
+            DomainRegistration::new(pattern, vec![crate::domain::Route::parse(route).unwrap()])
+        };
+        let before = [
+            registration("kept.roxy", "/=3000"),
+            registration("edited.roxy", "/=3001"),
+            registration("gone.roxy", "/=3002"),
+        ];
+        let mut edited = registration("edited.roxy", "/=3001");
+        edited.set_log_level(Some("debug".parse().unwrap()));
+        let after = [
+            registration("kept.roxy", "/=3000"),
+            edited,
+            registration("new.roxy", "/=3003"),
+        ];
+
+        let changes = DomainChanges::between(&before, &after);
+        assert_eq!(changes.added, ["new.roxy"]);
+        assert_eq!(changes.removed, ["gone.roxy"]);
+        assert_eq!(changes.changed, ["edited.roxy"]);
+        assert_eq!(
+            DomainChanges::between(&after, &after),
+            DomainChanges::default()
+        );
+    }
+
+    // --- ConfigStore ---
+
+    #[test]
+    fn owner_survives_a_save() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = ConfigStore::new(tmp.path().join("config.toml"));
+
+        let pattern = DomainPattern::from_name("myapp.roxy", false).unwrap();
+        let route = crate::domain::Route::parse("/=3000").unwrap();
+        let mut registration = DomainRegistration::new(pattern.clone(), vec![route]);
+        registration.set_owner(Some("alice".into()));
+        store.add_domain(registration).unwrap();
+
+        let loaded = store.get_domain(&pattern).unwrap().unwrap();
+        assert_eq!(loaded.owner(), Some("alice"));
+    }
+
+    #[test]
+    fn saves_domains_in_a_stable_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = ConfigStore::new(tmp.path().join("config.toml"));
+        for name in ["web.roxy", "api.roxy", "mail.roxy", "docs.roxy"] {
+            let pattern = DomainPattern::from_name(name, false).unwrap();
+            let route = crate::domain::Route::parse("/=3000").unwrap();
+            store
+                .add_domain(DomainRegistration::new(pattern, vec![route]))
+                .unwrap();
+        }
+
+        let content = fs::read_to_string(tmp.path().join("config.toml")).unwrap();
+        let keys: Vec<_> = content
+            .lines()
+            .filter(|line| line.starts_with("[domains."))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "[domains.\"api.roxy\"]",
+                "[domains.\"docs.roxy\"]",
+                "[domains.\"mail.roxy\"]",
+                "[domains.\"web.roxy\"]",
+            ]
+        );
+
+        store.save(&store.load().unwrap()).unwrap();
+        let resaved = fs::read_to_string(tmp.path().join("config.toml")).unwrap();
+        assert_eq!(resaved, content);
+    }
+
+    #[test]
+    fn upgrades_old_config_files_with_a_backup() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        let old = "[daemon]\nhttp_port = 8080\n";
+        fs::write(&path, old).unwrap();
+        let store = ConfigStore::new(path.clone());
+
+        assert_eq!(store.load().unwrap().daemon.http_port, 8080);
+        let upgraded = fs::read_to_string(&path).unwrap();
+        assert!(upgraded.starts_with(&format!("version = {}\n", CURRENT_VERSION)));
+        let backup = tmp.path().join("config.toml.v0.bak");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), old);
+
+        fs::write(&path, format!("version = {}\n", CURRENT_VERSION + 1)).unwrap();
+        assert!(matches!(store.load(), Err(ConfigError::NewerVersion(..))));
+    }
+
+    #[test]
+    fn yaml_and_json_configs_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pattern = DomainPattern::from_name("myapp.roxy", true).unwrap();
+
+        for (file, section) in [
+            ("config.yaml", "\ndaemon:\n"),
+            ("config.json", "\n  \"daemon\": {\n"),
+        ] {
+            let store = ConfigStore::new(tmp.path().join(file));
+            let route = crate::domain::Route::parse("/api=3001;strip").unwrap();
+            let mut registration = DomainRegistration::new(pattern.clone(), vec![route]);
+            registration.set_owner(Some("alice".into()));
+            store.add_domain(registration).unwrap();
+            store.set_value("daemon.http_port", "8080").unwrap();
+
+            let content = fs::read_to_string(tmp.path().join(file)).unwrap();
+            assert!(content.contains(section), "{}", content);
+
+            let config = store.load().unwrap();
+            config.validate().unwrap();
+            assert_eq!(config.daemon.http_port, 8080);
+            let loaded = store.get_domain(&pattern).unwrap().unwrap();
+            assert_eq!(loaded.owner(), Some("alice"));
+            assert!(loaded.routes()[0].options.strip_prefix);
+        }
+    }
+
+    #[test]
+    fn rollback_restores_an_earlier_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = ConfigStore::new(tmp.path().join("config.toml"));
+        let pattern = DomainPattern::from_name("myapp.roxy", false).unwrap();
+        let route = crate::domain::Route::parse("/=3000").unwrap();
+
+        store.save(&Config::default()).unwrap();
+        assert!(store.history().unwrap().is_empty());
+        store
+            .add_domain(DomainRegistration::new(pattern.clone(), vec![route]))
+            .unwrap();
+        store.remove_domain(&pattern).unwrap();
+
+        let history = store.history().unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].domains, Some(1));
+        assert_eq!(history[1].domains, Some(0));
+
+        store.rollback(1).unwrap();
+        assert!(store.get_domain(&pattern).unwrap().is_some());
+        assert!(matches!(store.rollback(4), Err(ConfigError::NoSnapshot(4))));
+        assert!(matches!(store.rollback(0), Err(ConfigError::NoSnapshot(0))));
+
+        // Undoing the rollback
+        store.rollback(1).unwrap();
+        assert!(store.get_domain(&pattern).unwrap().is_none());
+    }
+
+    #[test]
+    fn project_survives_a_save() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = ConfigStore::new(tmp.path().join("config.toml"));
+
+        let pattern = DomainPattern::from_name("myapp.roxy", false).unwrap();
+        let route = crate::domain::Route::parse("/=3000").unwrap();
+        let mut registration = DomainRegistration::new(pattern.clone(), vec![route]);
+        registration.set_project(Some(tmp.path().to_path_buf()));
+        store.add_domain(registration).unwrap();
+
+        let loaded = store.get_domain(&pattern).unwrap().unwrap();
+        assert_eq!(loaded.project(), Some(tmp.path()));
+    }
+
+    #[test]
+    fn dns_backend_is_set_by_name() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = ConfigStore::new(tmp.path().join("config.toml"));
+
+        store.set_value("daemon.dns_backend", "hosts").unwrap();
+        assert_eq!(store.load().unwrap().daemon.dns_backend, DnsBackend::Hosts);
+        assert!(store.set_value("daemon.dns_backend", "bind").is_err());
+    }
 }
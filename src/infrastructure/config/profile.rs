@@ -0,0 +1,189 @@
+//! Named sets of domains, for switching between projects that want the
+//! same names.
+//!
+//! The active profile's domains are the `[domains]` table everything
+//! else reads, so the daemon only serves those. The others wait under
+//! `[profiles.<name>.domains]` until `roxy profile use` swaps them in.
+
+use std::collections::BTreeMap;
+use std::mem;
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::DomainRegistration;
+
+use super::dto::RegistrationDto;
+use super::{Config, ConfigError, ConfigStore};
+
+/// Profile the domains belong to until another one is created.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Domains of a profile that isn't in use.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    domains: BTreeMap<String, RegistrationDto>,
+}
+
+impl Config {
+    /// Name of the profile whose domains are registered.
+    pub fn active_profile(&self) -> &str {
+        self.profile.as_deref().unwrap_or(DEFAULT_PROFILE)
+    }
+
+    /// Every profile with its domains, the active one first.
+    pub fn profiles(&self) -> Vec<(String, Vec<DomainRegistration>)> {
+        let active = (self.active_profile().to_string(), self.registrations());
+        let others = self.profiles.iter().map(|(name, profile)| {
+            let registrations = profile
+                .domains
+                .values()
+                .cloned()
+                .map(DomainRegistration::from)
+                .collect();
+            (name.clone(), registrations)
+        });
+        std::iter::once(active).chain(others).collect()
+    }
+}
+
+impl ConfigStore {
+    /// Add an empty profile.
+    pub fn create_profile(&self, name: &str) -> Result<(), ConfigError> {
+        check_name(name)?;
+        let mut config = self.read()?;
+        if config.active_profile() == name || config.profiles.contains_key(name) {
+            return Err(ConfigError::ProfileExists(name.to_string()));
+        }
+        config.profiles.insert(name.to_string(), Profile::default());
+        self.save(&config)
+    }
+
+    /// Put the active profile's domains away and register `name`'s
+    /// instead. Returns the newly registered domains.
+    pub fn use_profile(&self, name: &str) -> Result<Vec<DomainRegistration>, ConfigError> {
+        let mut config = self.read()?;
+        if config.active_profile() == name {
+            return Ok(config.registrations());
+        }
+        let incoming = config
+            .profiles
+            .remove(name)
+            .ok_or_else(|| ConfigError::ProfileNotFound(name.to_string()))?;
+
+        let outgoing = Profile {
+            domains: mem::replace(&mut config.domains, incoming.domains),
+        };
+        config
+            .profiles
+            .insert(config.active_profile().to_string(), outgoing);
+        config.profile = (name != DEFAULT_PROFILE).then(|| name.to_string());
+
+        config.validate()?;
+        self.save(&config)?;
+        Ok(config.registrations())
+    }
+
+    /// Remove a profile that isn't in use, with its domains.
+    pub fn delete_profile(&self, name: &str) -> Result<(), ConfigError> {
+        let mut config = self.read()?;
+        if config.active_profile() == name {
+            return Err(ConfigError::InvalidProfile(
+                name.to_string(),
+                "it is in use".into(),
+            ));
+        }
+        config
+            .profiles
+            .remove(name)
+            .ok_or_else(|| ConfigError::ProfileNotFound(name.to_string()))?;
+        self.save(&config)
+    }
+}
+
+/// Letters, digits, `-` and `_`, so a name works unquoted everywhere.
+fn check_name(name: &str) -> Result<(), ConfigError> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(ConfigError::InvalidProfile(
+            name.to_string(),
+            "use letters, digits, '-' and '_'".into(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{DomainPattern, Route};
+
+    fn register(store: &ConfigStore, name: &str, port: u16) {
+        let pattern = DomainPattern::from_name(name, false).unwrap();
+        let route = Route::parse(&format!("/={}", port)).unwrap();
+        store
+            .add_domain(DomainRegistration::new(pattern, vec![route]))
+            .unwrap();
+    }
+
+    fn app_port(store: &ConfigStore) -> String {
+        let pattern = DomainPattern::from_name("app.roxy", false).unwrap();
+        let registration = store.get_domain(&pattern).unwrap().unwrap();
+        registration.routes()[0].target.to_string()
+    }
+
+    #[test]
+    fn profiles_keep_separate_domains() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = ConfigStore::new(tmp.path().join("config.toml"));
+        register(&store, "app.roxy", 3000);
+
+        store.create_profile("client-b").unwrap();
+        assert_eq!(store.use_profile("client-b").unwrap().len(), 0);
+        register(&store, "app.roxy", 4000);
+        register(&store, "admin.roxy", 4001);
+        assert_eq!(app_port(&store), "127.0.0.1:4000");
+
+        let registered = store.use_profile(DEFAULT_PROFILE).unwrap();
+        assert_eq!(registered.len(), 1);
+        assert_eq!(app_port(&store), "127.0.0.1:3000");
+
+        let config = store.load().unwrap();
+        assert_eq!(config.active_profile(), DEFAULT_PROFILE);
+        let profiles = config.profiles();
+        assert_eq!(profiles[0].0, DEFAULT_PROFILE);
+        assert_eq!(profiles[1].0, "client-b");
+        assert_eq!(profiles[1].1.len(), 2);
+    }
+
+    #[test]
+    fn rejects_bad_profile_changes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = ConfigStore::new(tmp.path().join("config.toml"));
+
+        assert!(matches!(
+            store.create_profile("client a"),
+            Err(ConfigError::InvalidProfile(..))
+        ));
+        assert!(matches!(
+            store.create_profile(DEFAULT_PROFILE),
+            Err(ConfigError::ProfileExists(_))
+        ));
+        assert!(matches!(
+            store.use_profile("missing"),
+            Err(ConfigError::ProfileNotFound(_))
+        ));
+
+        store.create_profile("client-a").unwrap();
+        store.use_profile("client-a").unwrap();
+        assert!(matches!(
+            store.delete_profile("client-a"),
+            Err(ConfigError::InvalidProfile(..))
+        ));
+        store.delete_profile(DEFAULT_PROFILE).unwrap();
+        assert_eq!(store.load().unwrap().profiles().len(), 1);
+    }
+}
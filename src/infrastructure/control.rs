@@ -0,0 +1,460 @@
+//! Control channel between the CLI and the running daemon.
+//!
+//! The daemon listens on a Unix socket (`paths.control_socket`), or a
+//! named pipe on Windows. Each
+//! connection carries a single request line; the daemon answers with a
+//! status line (`ok` or `error`) followed by a free-form message and
+//! closes the connection.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::domain::LogLevel;
+
+/// How long the CLI waits for the daemon to answer.
+#[cfg(unix)]
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest request line the daemon accepts.
+pub const MAX_REQUEST_LEN: usize = 4096;
+
+#[derive(Debug, Error)]
+pub enum ControlError {
+    #[error("Roxy daemon is not running.\nStart it with: sudo roxy start")]
+    NotRunning,
+
+    #[error("Permission denied connecting to the daemon at {0}.\nTry again with sudo.")]
+    PermissionDenied(PathBuf),
+
+    #[error("Control channel error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid control request: {0}")]
+    InvalidRequest(String),
+
+    #[error("Unexpected response from the daemon: {0}")]
+    InvalidResponse(String),
+
+    #[error("{0}")]
+    Daemon(String),
+}
+
+/// A command sent to the daemon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlRequest {
+    /// Show the active log filter, or change it when a level is given.
+    LogLevel(Option<LogLevel>),
+    /// Re-read the config file and apply it without a restart.
+    Reload,
+    /// Report per-domain request counters, one `RequestCounts` per line.
+    Stats,
+    /// Report health-checked backends, one `BackendStatus` per line.
+    Health,
+    /// Drop the cached responses of a domain, given as its pattern.
+    PurgeCache(String),
+    /// Report backends started by the daemon, one `ProcessStatus` per line.
+    Processes,
+    /// Restart the started backends of a domain, given as its pattern.
+    RestartBackend(String),
+}
+
+impl fmt::Display for ControlRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlRequest::LogLevel(None) => write!(f, "log-level"),
+            ControlRequest::LogLevel(Some(level)) => write!(f, "log-level {}", level),
+            ControlRequest::Reload => write!(f, "reload"),
+            ControlRequest::Stats => write!(f, "stats"),
+            ControlRequest::Health => write!(f, "health"),
+            ControlRequest::PurgeCache(domain) => write!(f, "cache-purge {}", domain),
+            ControlRequest::Processes => write!(f, "ps"),
+            ControlRequest::RestartBackend(domain) => write!(f, "restart-backend {}", domain),
+        }
+    }
+}
+
+impl FromStr for ControlRequest {
+    type Err = ControlError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut parts = line.split_whitespace();
+        let command = parts.next().unwrap_or_default();
+        let args: Vec<&str> = parts.collect();
+
+        match (command, args.as_slice()) {
+            ("log-level", []) => Ok(ControlRequest::LogLevel(None)),
+            ("log-level", [level]) => level
+                .parse::<LogLevel>()
+                .map(|level| ControlRequest::LogLevel(Some(level)))
+                .map_err(|e| ControlError::InvalidRequest(e.to_string())),
+            ("reload", []) => Ok(ControlRequest::Reload),
+            ("stats", []) => Ok(ControlRequest::Stats),
+            ("health", []) => Ok(ControlRequest::Health),
+            ("cache-purge", [domain]) => Ok(ControlRequest::PurgeCache(domain.to_string())),
+            ("ps", []) => Ok(ControlRequest::Processes),
+            ("restart-backend", [domain]) => Ok(ControlRequest::RestartBackend(domain.to_string())),
+            _ => Err(ControlError::InvalidRequest(line.to_string())),
+        }
+    }
+}
+
+/// Requests served for one domain since the daemon started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestCounts {
+    /// Domain pattern as shown to users, e.g. `*.myapp.roxy`.
+    pub domain: String,
+    pub requests: u64,
+    /// Responses with a 5xx status.
+    pub errors: u64,
+}
+
+impl fmt::Display for RequestCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.domain, self.requests, self.errors)
+    }
+}
+
+impl FromStr for RequestCounts {
+    type Err = ControlError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let invalid = || ControlError::InvalidResponse(line.to_string());
+        let mut parts = line.split_whitespace();
+        let (Some(domain), Some(requests), Some(errors), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+
+        Ok(Self {
+            domain: domain.to_string(),
+            requests: requests.parse().map_err(|_| invalid())?,
+            errors: errors.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Last health check result for one proxy backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendStatus {
+    /// Backend address, e.g. `127.0.0.1:3000`.
+    pub target: String,
+    pub up: bool,
+    /// Seconds since the backend last went up or down.
+    pub for_secs: u64,
+}
+
+impl fmt::Display for BackendStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = if self.up { "up" } else { "down" };
+        write!(f, "{} {} {}", self.target, state, self.for_secs)
+    }
+}
+
+impl FromStr for BackendStatus {
+    type Err = ControlError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let invalid = || ControlError::InvalidResponse(line.to_string());
+        let mut parts = line.split_whitespace();
+        let (Some(target), Some(state), Some(for_secs), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+
+        Ok(Self {
+            target: target.to_string(),
+            up: match state {
+                "up" => true,
+                "down" => false,
+                _ => return Err(invalid()),
+            },
+            for_secs: for_secs.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// A backend the daemon started for a route with a `cmd`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessStatus {
+    /// Domain pattern as shown to users, e.g. `*.myapp.roxy`.
+    pub domain: String,
+    /// Backend address, e.g. `127.0.0.1:3000`.
+    pub target: String,
+    /// `running`, `restarting`, `failed` or `stopped`.
+    pub state: String,
+    /// Only known while running.
+    pub pid: Option<u32>,
+    /// Seconds since the backend was last started, while running.
+    pub uptime_secs: u64,
+    /// Times the daemon started the backend again.
+    pub restarts: u32,
+    /// The route's command. Written last as it may contain spaces.
+    pub cmd: String,
+}
+
+impl fmt::Display for ProcessStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pid = self
+            .pid
+            .map_or_else(|| "-".to_string(), |pid| pid.to_string());
+        write!(
+            f,
+            "{} {} {} {} {} {} {}",
+            self.domain, self.target, self.state, pid, self.uptime_secs, self.restarts, self.cmd
+        )
+    }
+}
+
+impl FromStr for ProcessStatus {
+    type Err = ControlError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let invalid = || ControlError::InvalidResponse(line.to_string());
+        let mut parts = line.splitn(7, ' ');
+        let (
+            Some(domain),
+            Some(target),
+            Some(state),
+            Some(pid),
+            Some(uptime_secs),
+            Some(restarts),
+            Some(cmd),
+        ) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        )
+        else {
+            return Err(invalid());
+        };
+
+        Ok(Self {
+            domain: domain.to_string(),
+            target: target.to_string(),
+            state: state.to_string(),
+            pid: match pid {
+                "-" => None,
+                pid => Some(pid.parse().map_err(|_| invalid())?),
+            },
+            uptime_secs: uptime_secs.parse().map_err(|_| invalid())?,
+            restarts: restarts.parse().map_err(|_| invalid())?,
+            cmd: cmd.to_string(),
+        })
+    }
+}
+
+/// Encode the daemon's answer for the wire.
+pub fn encode_response(response: &Result<String, String>) -> String {
+    match response {
+        Ok(message) => format!("ok\n{}", message),
+        Err(message) => format!("error\n{}", message),
+    }
+}
+
+fn decode_response(raw: &str) -> Result<String, ControlError> {
+    let (status, message) = raw.split_once('\n').unwrap_or((raw, ""));
+    match status {
+        "ok" => Ok(message.to_string()),
+        "error" => Err(ControlError::Daemon(message.to_string())),
+        _ => Err(ControlError::InvalidResponse(raw.to_string())),
+    }
+}
+
+/// CLI side of the control channel.
+pub struct ControlClient {
+    socket_path: PathBuf,
+}
+
+impl ControlClient {
+    pub fn new(socket_path: PathBuf) -> Self {
+        Self { socket_path }
+    }
+
+    /// Send a request and return the daemon's message.
+    pub fn send(&self, request: &ControlRequest) -> Result<String, ControlError> {
+        let mut stream = connect(&self.socket_path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused => ControlError::NotRunning,
+            io::ErrorKind::PermissionDenied => {
+                ControlError::PermissionDenied(self.socket_path.clone())
+            }
+            _ => ControlError::Io(e),
+        })?;
+
+        writeln!(stream, "{}", request)?;
+        stream.flush()?;
+
+        let mut raw = String::new();
+        stream.read_to_string(&mut raw)?;
+        decode_response(&raw)
+    }
+}
+
+#[cfg(unix)]
+fn connect(path: &Path) -> io::Result<UnixStream> {
+    let stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    Ok(stream)
+}
+
+/// Named pipes open like files. They have no read timeout.
+#[cfg(windows)]
+fn connect(path: &Path) -> io::Result<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use std::io::{BufRead, BufReader};
+    #[cfg(unix)]
+    use std::os::unix::net::UnixListener;
+    #[cfg(unix)]
+    use std::thread;
+
+    #[test]
+    fn request_roundtrips_through_text() {
+        for request in [
+            ControlRequest::LogLevel(None),
+            ControlRequest::LogLevel(Some(LogLevel::Debug)),
+            ControlRequest::Reload,
+            ControlRequest::Stats,
+            ControlRequest::Health,
+            ControlRequest::PurgeCache("*.myapp.roxy".into()),
+            ControlRequest::Processes,
+            ControlRequest::RestartBackend("myapp.roxy".into()),
+        ] {
+            let parsed: ControlRequest = request.to_string().parse().unwrap();
+            assert_eq!(parsed, request);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_requests() {
+        assert!("reboot".parse::<ControlRequest>().is_err());
+        assert!("log-level verbose".parse::<ControlRequest>().is_err());
+        assert!("log-level debug extra".parse::<ControlRequest>().is_err());
+        assert!("reload now".parse::<ControlRequest>().is_err());
+        assert!("cache-purge".parse::<ControlRequest>().is_err());
+        assert!("restart-backend".parse::<ControlRequest>().is_err());
+    }
+
+    #[test]
+    fn request_counts_roundtrip_through_text() {
+        let counts = RequestCounts {
+            domain: "*.myapp.roxy".into(),
+            requests: 12,
+            errors: 1,
+        };
+        assert_eq!(counts.to_string().parse::<RequestCounts>().unwrap(), counts);
+        assert!("myapp.roxy 12".parse::<RequestCounts>().is_err());
+        assert!("myapp.roxy x 1".parse::<RequestCounts>().is_err());
+    }
+
+    #[test]
+    fn backend_status_roundtrips_through_text() {
+        let status = BackendStatus {
+            target: "127.0.0.1:3000".into(),
+            up: false,
+            for_secs: 120,
+        };
+        assert_eq!(status.to_string(), "127.0.0.1:3000 down 120");
+        assert_eq!(status.to_string().parse::<BackendStatus>().unwrap(), status);
+        assert!(
+            "127.0.0.1:3000 sideways 1"
+                .parse::<BackendStatus>()
+                .is_err()
+        );
+        assert!("127.0.0.1:3000 up".parse::<BackendStatus>().is_err());
+    }
+
+    #[test]
+    fn process_status_roundtrips_through_text() {
+        let status = ProcessStatus {
+            domain: "*.myapp.roxy".into(),
+            target: "127.0.0.1:3000".into(),
+            state: "running".into(),
+            pid: Some(4242),
+            uptime_secs: 90,
+            restarts: 2,
+            cmd: "npm run dev -- --port 3000".into(),
+        };
+        assert_eq!(
+            status.to_string(),
+            "*.myapp.roxy 127.0.0.1:3000 running 4242 90 2 npm run dev -- --port 3000"
+        );
+        assert_eq!(status.to_string().parse::<ProcessStatus>().unwrap(), status);
+
+        let failed = ProcessStatus {
+            state: "failed".into(),
+            pid: None,
+            uptime_secs: 0,
+            ..status
+        };
+        assert_eq!(failed.to_string().parse::<ProcessStatus>().unwrap(), failed);
+        assert!(
+            "myapp.roxy 127.0.0.1:3000 running 1 2"
+                .parse::<ProcessStatus>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn decodes_responses() {
+        assert_eq!(
+            decode_response(&encode_response(&Ok("done".into()))).unwrap(),
+            "done"
+        );
+        let err = decode_response(&encode_response(&Err("nope".into()))).unwrap_err();
+        assert!(matches!(err, ControlError::Daemon(m) if m == "nope"));
+        assert!(decode_response("garbage").is_err());
+    }
+
+    #[test]
+    fn missing_socket_means_not_running() {
+        let tmp = tempfile::tempdir().unwrap();
+        let client = ControlClient::new(tmp.path().join("roxy.sock"));
+        let err = client.send(&ControlRequest::LogLevel(None)).unwrap_err();
+        assert!(matches!(err, ControlError::NotRunning));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn client_talks_to_server() {
+        let tmp = tempfile::tempdir().unwrap();
+        let socket = tmp.path().join("roxy.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            let request: ControlRequest = line.trim().parse().unwrap();
+            let response = encode_response(&Ok(format!("got {}", request)));
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+
+        let client = ControlClient::new(socket);
+        let message = client
+            .send(&ControlRequest::LogLevel(Some(LogLevel::Warn)))
+            .unwrap();
+        assert_eq!(message, "got log-level warn");
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,187 @@
+use super::{DnsError, DnsService};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Directories dnsmasq reads extra config from, in the order they are
+/// tried: the distro default, Homebrew on Apple silicon and Intel, and
+/// the instance NetworkManager runs.
+const CONF_DIRS: &[&str] = &[
+    "/etc/dnsmasq.d",
+    "/opt/homebrew/etc/dnsmasq.d",
+    "/usr/local/etc/dnsmasq.d",
+    "/etc/NetworkManager/dnsmasq.d",
+];
+
+const SNIPPET_FILE: &str = "roxy.conf";
+
+/// dnsmasq has no notion of source-based answers, so every client gets
+/// loopback.
+const ADDRESS: &str = "127.0.0.1";
+
+/// Port dnsmasq answers on; the system resolver is expected to use it.
+const DNSMASQ_PORT: u16 = 53;
+
+/// Points `.roxy` at the daemon through an existing dnsmasq, for
+/// machines that can't have a second DNS listener.
+pub struct DnsmasqDnsService {
+    conf_dir: PathBuf,
+}
+
+impl DnsmasqDnsService {
+    pub fn new(conf_dir: PathBuf) -> Self {
+        Self { conf_dir }
+    }
+
+    /// Use the first dnsmasq config directory that exists.
+    pub fn detect() -> Result<Self, DnsError> {
+        CONF_DIRS
+            .iter()
+            .map(Path::new)
+            .find(|dir| dir.is_dir())
+            .map(|dir| Self::new(dir.to_path_buf()))
+            .ok_or_else(|| DnsError::DnsmasqNotFound(CONF_DIRS.join(", ")))
+    }
+
+    pub fn snippet_path(&self) -> PathBuf {
+        self.conf_dir.join(SNIPPET_FILE)
+    }
+
+    /// dnsmasq only reads `address=` lines at startup, so a restart is
+    /// needed for changes to apply.
+    fn restart(&self) -> Result<(), DnsError> {
+        let (program, args) = restart_command(&self.conf_dir);
+        let output = Command::new(program)
+            .args(&args)
+            .output()
+            .map_err(|e| DnsError::ValidationFailed(format!("Failed to run {}: {}", program, e)))?;
+
+        if !output.status.success() {
+            return Err(DnsError::ValidationFailed(format!(
+                "'{} {}' failed: {}. Restart dnsmasq manually to apply {}",
+                program,
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim(),
+                self.snippet_path().display()
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn snippet_content() -> String {
+    format!(
+        "# Managed by roxy: send .roxy names to the local daemon\naddress=/roxy/{}\n",
+        ADDRESS
+    )
+}
+
+/// How to restart the dnsmasq instance that reads `conf_dir`.
+fn restart_command(conf_dir: &Path) -> (&'static str, Vec<&'static str>) {
+    if conf_dir.starts_with("/etc/NetworkManager") {
+        ("systemctl", vec!["restart", "NetworkManager"])
+    } else if conf_dir.starts_with("/etc") {
+        ("systemctl", vec!["restart", "dnsmasq"])
+    } else {
+        ("brew", vec!["services", "restart", "dnsmasq"])
+    }
+}
+
+fn map_write_error(path: &Path, e: std::io::Error) -> DnsError {
+    if e.kind() == std::io::ErrorKind::PermissionDenied {
+        DnsError::PermissionDenied
+    } else {
+        DnsError::WriteError {
+            path: path.to_path_buf(),
+            source: e,
+        }
+    }
+}
+
+impl DnsService for DnsmasqDnsService {
+    /// dnsmasq keeps answering on its own port; `port` is not used.
+    fn setup(&self, _port: u16) -> Result<(), DnsError> {
+        let path = self.snippet_path();
+        fs::write(&path, snippet_content()).map_err(|e| map_write_error(&path, e))?;
+        self.restart()
+    }
+
+    fn cleanup(&self) -> Result<(), DnsError> {
+        let path = self.snippet_path();
+        if !path.exists() {
+            return Ok(());
+        }
+        fs::remove_file(&path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::PermissionDenied => DnsError::PermissionDenied,
+            _ => DnsError::RemoveError {
+                path: path.clone(),
+                source: e,
+            },
+        })?;
+        self.restart()
+    }
+
+    fn validate(&self) -> Result<(), DnsError> {
+        let path = self.snippet_path();
+        let content = fs::read_to_string(&path).map_err(|e| {
+            DnsError::ValidationFailed(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+
+        if !content.contains("address=/roxy/") {
+            return Err(DnsError::ValidationFailed(format!(
+                "{} has incorrect content",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    fn is_configured(&self) -> bool {
+        self.snippet_path().exists()
+    }
+
+    fn configured_port(&self) -> Option<u16> {
+        self.is_configured().then_some(DNSMASQ_PORT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snippet_sends_roxy_to_loopback() {
+        let content = snippet_content();
+        assert!(content.ends_with('\n'));
+        assert!(content.contains("address=/roxy/127.0.0.1"));
+    }
+
+    #[test]
+    fn restart_matches_the_instance() {
+        assert_eq!(
+            restart_command(Path::new("/etc/NetworkManager/dnsmasq.d")),
+            ("systemctl", vec!["restart", "NetworkManager"])
+        );
+        assert_eq!(
+            restart_command(Path::new("/etc/dnsmasq.d")),
+            ("systemctl", vec!["restart", "dnsmasq"])
+        );
+        assert_eq!(
+            restart_command(Path::new("/opt/homebrew/etc/dnsmasq.d")),
+            ("brew", vec!["services", "restart", "dnsmasq"])
+        );
+    }
+
+    #[test]
+    fn validate_checks_the_snippet() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dns = DnsmasqDnsService::new(tmp.path().to_path_buf());
+        assert!(!dns.is_configured());
+        assert!(dns.validate().is_err());
+
+        fs::write(dns.snippet_path(), snippet_content()).unwrap();
+        assert!(dns.is_configured());
+        assert!(dns.validate().is_ok());
+        assert_eq!(dns.configured_port(), Some(53));
+    }
+}
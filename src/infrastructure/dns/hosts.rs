@@ -0,0 +1,239 @@
+//! Hosts-file fallback for systems where the resolver can't be pointed
+//! at Roxy's DNS server. Every registered domain gets a line inside a
+//! block Roxy owns; the rest of the file is left alone.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use super::DnsError;
+use crate::domain::DomainRegistration;
+
+const BEGIN_MARKER: &str = "# BEGIN roxy (managed by roxy, do not edit)";
+const END_MARKER: &str = "# END roxy";
+
+/// The daemon only listens on IPv4, so no `::1` lines are written.
+const LOOPBACK: &str = "127.0.0.1";
+
+pub struct HostsFile {
+    path: PathBuf,
+}
+
+impl HostsFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The system hosts file.
+    pub fn system() -> Self {
+        #[cfg(windows)]
+        {
+            let root = std::env::var_os("SystemRoot").unwrap_or_else(|| "C:\\Windows".into());
+            Self::new(PathBuf::from(root).join(r"System32\drivers\etc\hosts"))
+        }
+
+        #[cfg(not(windows))]
+        {
+            Self::new(PathBuf::from("/etc/hosts"))
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Make the managed block list exactly `names`. An empty list removes
+    /// the block. Returns whether the file had to change; an up-to-date
+    /// file is not written, so this needs no privileges in the common case.
+    pub fn sync(&self, names: &[String]) -> Result<bool, DnsError> {
+        let content = self.read()?;
+        let updated = replace_block(&content, names);
+        if updated == content {
+            return Ok(false);
+        }
+
+        fs::write(&self.path, updated).map_err(|e| match e.kind() {
+            ErrorKind::PermissionDenied => DnsError::PermissionDenied,
+            _ => DnsError::WriteError {
+                path: self.path.clone(),
+                source: e,
+            },
+        })?;
+        Ok(true)
+    }
+
+    /// Drop the managed block. Returns whether there was one.
+    pub fn remove(&self) -> Result<bool, DnsError> {
+        self.sync(&[])
+    }
+
+    /// Names currently listed in the managed block.
+    pub fn managed_names(&self) -> Result<Vec<String>, DnsError> {
+        Ok(managed_names(&self.read()?))
+    }
+
+    fn read(&self) -> Result<String, DnsError> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => Err(DnsError::ValidationFailed(format!(
+                "Failed to read {}: {}",
+                self.path.display(),
+                e
+            ))),
+        }
+    }
+}
+
+/// Names to list for the given registrations, sorted and de-duplicated.
+///
+/// A hosts file can't express wildcards, so `*.myapp.roxy` only gets
+/// `myapp.roxy`; its subdomains still need the DNS server.
+pub fn hostnames(registrations: &[DomainRegistration]) -> Vec<String> {
+    let mut names: Vec<String> = registrations
+        .iter()
+        .map(|r| r.domain().as_str().to_string())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Replace (or append) the managed block in `content`.
+fn replace_block(content: &str, names: &[String]) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut block_at = None;
+    let mut in_block = false;
+
+    for line in content.lines() {
+        match line.trim() {
+            BEGIN_MARKER => {
+                in_block = true;
+                block_at.get_or_insert(lines.len());
+            }
+            END_MARKER if in_block => in_block = false,
+            _ if in_block => {}
+            _ => lines.push(line),
+        }
+    }
+
+    let block: Vec<String> = if names.is_empty() {
+        Vec::new()
+    } else {
+        std::iter::once(BEGIN_MARKER.to_string())
+            .chain(names.iter().map(|name| format!("{} {}", LOOPBACK, name)))
+            .chain(std::iter::once(END_MARKER.to_string()))
+            .collect()
+    };
+
+    let mut out: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    match block_at {
+        Some(at) => {
+            out.splice(at..at, block);
+        }
+        None if !block.is_empty() => out.extend(block),
+        None => return content.to_string(),
+    }
+
+    if out.is_empty() {
+        String::new()
+    } else {
+        out.join("\n") + "\n"
+    }
+}
+
+fn managed_names(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .skip_while(|line| line.trim() != BEGIN_MARKER)
+        .skip(1)
+        .take_while(|line| line.trim() != END_MARKER)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{DomainPattern, Route};
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    const SYSTEM: &str = "127.0.0.1 localhost\n::1 localhost\n";
+
+    #[test]
+    fn appends_block_after_existing_entries() {
+        let updated = replace_block(SYSTEM, &names(&["myapp.roxy"]));
+        assert_eq!(
+            updated,
+            format!(
+                "{}{}\n127.0.0.1 myapp.roxy\n{}\n",
+                SYSTEM, BEGIN_MARKER, END_MARKER
+            )
+        );
+    }
+
+    #[test]
+    fn replaces_block_in_place() {
+        let content = format!(
+            "127.0.0.1 localhost\n{}\n127.0.0.1 old.roxy\n{}\n10.0.0.1 nas\n",
+            BEGIN_MARKER, END_MARKER
+        );
+        let updated = replace_block(&content, &names(&["a.roxy", "b.roxy"]));
+        assert_eq!(
+            updated,
+            format!(
+                "127.0.0.1 localhost\n{}\n127.0.0.1 a.roxy\n127.0.0.1 b.roxy\n{}\n10.0.0.1 nas\n",
+                BEGIN_MARKER, END_MARKER
+            )
+        );
+    }
+
+    #[test]
+    fn empty_list_removes_block() {
+        let content = replace_block(SYSTEM, &names(&["myapp.roxy"]));
+        assert_eq!(replace_block(&content, &[]), SYSTEM);
+    }
+
+    #[test]
+    fn empty_list_leaves_unmanaged_file_untouched() {
+        let content = "127.0.0.1 localhost"; // no trailing newline
+        assert_eq!(replace_block(content, &[]), content);
+    }
+
+    #[test]
+    fn reads_managed_names() {
+        let content = replace_block(SYSTEM, &names(&["a.roxy", "b.roxy"]));
+        assert_eq!(managed_names(&content), names(&["a.roxy", "b.roxy"]));
+        assert!(managed_names(SYSTEM).is_empty());
+    }
+
+    #[test]
+    fn hostnames_use_base_domain_of_wildcards() {
+        let route = || vec![Route::parse("/=3000").unwrap()];
+        let registrations = vec![
+            DomainRegistration::new(DomainPattern::from_name("b.roxy", true).unwrap(), route()),
+            DomainRegistration::new(DomainPattern::from_name("a.roxy", false).unwrap(), route()),
+        ];
+        assert_eq!(hostnames(&registrations), names(&["a.roxy", "b.roxy"]));
+    }
+
+    #[test]
+    fn sync_writes_only_on_change() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("hosts");
+        fs::write(&path, SYSTEM).unwrap();
+        let hosts = HostsFile::new(path.clone());
+
+        assert!(hosts.sync(&names(&["myapp.roxy"])).unwrap());
+        assert!(!hosts.sync(&names(&["myapp.roxy"])).unwrap());
+        assert_eq!(hosts.managed_names().unwrap(), names(&["myapp.roxy"]));
+
+        assert!(hosts.remove().unwrap());
+        assert!(!hosts.remove().unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), SYSTEM);
+    }
+}
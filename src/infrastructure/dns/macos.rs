@@ -8,6 +8,9 @@ use std::time::Duration;
 const RESOLVER_DIR: &str = "/etc/resolver";
 const RESOLVER_FILE: &str = "/etc/resolver/roxy";
 
+/// Standard DNS port; the resolver file only needs a `port` line otherwise.
+const DEFAULT_DNS_PORT: u16 = 53;
+
 pub struct MacOsDnsService;
 
 impl MacOsDnsService {
@@ -33,6 +36,22 @@ impl MacOsDnsService {
     }
 }
 
+fn resolver_content(port: u16) -> String {
+    let mut content = String::from("nameserver 127.0.0.1\nnameserver ::1\n");
+    if port != DEFAULT_DNS_PORT {
+        content.push_str(&format!("port {}\n", port));
+    }
+    content
+}
+
+/// The `port` line of a resolver file, or the standard port if absent.
+fn resolver_port(content: &str) -> u16 {
+    content
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("port")?.trim().parse().ok())
+        .unwrap_or(DEFAULT_DNS_PORT)
+}
+
 impl Default for MacOsDnsService {
     fn default() -> Self {
         Self::new()
@@ -43,9 +62,7 @@ impl DnsService for MacOsDnsService {
     fn setup(&self, port: u16) -> Result<(), DnsError> {
         self.ensure_resolver_dir()?;
 
-        let content = format!("nameserver 127.0.0.1\nnameserver ::1\nport {}\n", port);
-
-        fs::write(RESOLVER_FILE, content).map_err(|e| {
+        fs::write(RESOLVER_FILE, resolver_content(port)).map_err(|e| {
             if e.kind() == std::io::ErrorKind::PermissionDenied {
                 DnsError::PermissionDenied
             } else {
@@ -133,17 +150,61 @@ impl DnsService for MacOsDnsService {
     fn is_configured(&self) -> bool {
         Path::new(RESOLVER_FILE).exists()
     }
+
+    fn configured_port(&self) -> Option<u16> {
+        fs::read_to_string(RESOLVER_FILE)
+            .ok()
+            .map(|content| resolver_port(&content))
+    }
+}
+
+/// Empty the Directory Services cache and make mDNSResponder drop its
+/// own, as `sudo killall -HUP mDNSResponder` does.
+pub fn flush_cache() -> Result<(), DnsError> {
+    for (program, args) in [
+        ("dscacheutil", &["-flushcache"][..]),
+        ("killall", &["-HUP", "mDNSResponder"][..]),
+    ] {
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| DnsError::FlushFailed(format!("Failed to run {}: {}", program, e)))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(DnsError::FlushFailed(format!(
+                "{} failed: {}",
+                program,
+                stderr.trim()
+            )));
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn test_resolver_content_format() {
-        let content = format!("nameserver 127.0.0.1\nnameserver ::1\nport {}\n", 1053);
+        let content = resolver_content(1053);
         assert!(content.ends_with('\n'));
         assert!(content.contains("nameserver"));
         assert!(content.contains("127.0.0.1"));
         assert!(content.contains("::1"));
         assert!(content.contains("port 1053"));
     }
+
+    #[test]
+    fn test_resolver_content_omits_default_port() {
+        let content = resolver_content(53);
+        assert!(content.contains("nameserver 127.0.0.1"));
+        assert!(!content.contains("port"));
+    }
+
+    #[test]
+    fn test_resolver_port_roundtrip() {
+        assert_eq!(resolver_port(&resolver_content(1053)), 1053);
+        assert_eq!(resolver_port(&resolver_content(53)), 53);
+    }
 }
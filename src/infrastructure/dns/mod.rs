@@ -15,6 +15,7 @@ pub enum DnsError {
     },
 
     #[error("Failed to remove DNS configuration from {path}: {source}")]
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
     RemoveError {
         path: PathBuf,
         source: std::io::Error,
@@ -23,11 +24,48 @@ pub enum DnsError {
     #[error("DNS validation failed: {0}")]
     ValidationFailed(String),
 
+    #[error("Failed to flush the DNS cache: {0}")]
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    FlushFailed(String),
+
+    #[error(
+        "Windows only sends .roxy queries to port 53, but dns_port is {0}.\nSet it with: roxy config set daemon.dns_port 53"
+    )]
+    #[cfg_attr(not(windows), allow(dead_code))] // Used only on Windows
+    PortNotSupported(u16),
+
+    #[error("No dnsmasq configuration directory found (looked in {0}).\nIs dnsmasq installed?")]
+    DnsmasqNotFound(String),
+
     #[error("Unsupported platform: {0}")]
-    #[allow(dead_code)] // Used only on non-macOS platforms
+    #[allow(dead_code)] // Used only on platforms without a DNS service
     UnsupportedPlatform(String),
 }
 
+/// How `.roxy` names reach the daemon.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsBackend {
+    /// Roxy's DNS server, with the system resolver pointed at it
+    #[default]
+    Resolver,
+    /// Entries in the hosts file; no DNS server is started
+    Hosts,
+    /// A snippet for an existing dnsmasq; no DNS server is started
+    Dnsmasq,
+}
+
+/// What the DNS server answers for names outside `.roxy`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnknownNames {
+    /// REFUSED: ask another server
+    #[default]
+    Refused,
+    /// NXDOMAIN: the name doesn't exist
+    NxDomain,
+}
+
 pub trait DnsService {
     /// Configure wildcard DNS for *.roxy → 127.0.0.1
     /// The port parameter specifies which port the DNS server listens on
@@ -41,14 +79,28 @@ pub trait DnsService {
 
     /// Check if DNS is already configured
     fn is_configured(&self) -> bool;
+
+    /// Port the system resolver sends `.roxy` queries to, if configured
+    fn configured_port(&self) -> Option<u16>;
 }
 
+mod dnsmasq;
+pub mod hosts;
+
+pub use dnsmasq::DnsmasqDnsService;
+
 #[cfg(target_os = "macos")]
 mod macos;
 
 #[cfg(target_os = "macos")]
 pub use macos::MacOsDnsService;
 
+#[cfg(windows)]
+mod windows;
+
+#[cfg(windows)]
+pub use windows::WindowsDnsService;
+
 /// Get the DNS service for the current platform
 pub fn get_dns_service() -> Result<Box<dyn DnsService>, DnsError> {
     #[cfg(target_os = "macos")]
@@ -56,10 +108,30 @@ pub fn get_dns_service() -> Result<Box<dyn DnsService>, DnsError> {
         Ok(Box::new(MacOsDnsService::new()))
     }
 
-    #[cfg(not(target_os = "macos"))]
+    #[cfg(windows)]
+    {
+        Ok(Box::new(WindowsDnsService::new()))
+    }
+
+    #[cfg(not(any(target_os = "macos", windows)))]
     {
         Err(DnsError::UnsupportedPlatform(
             std::env::consts::OS.to_string(),
         ))
     }
 }
+
+/// Drop the system resolver's cached answers, including the NXDOMAIN it
+/// may remember for a name registered a moment ago. `Ok(false)` where
+/// there is no cache to flush.
+pub fn flush_cache() -> Result<bool, DnsError> {
+    #[cfg(target_os = "macos")]
+    {
+        macos::flush_cache().map(|()| true)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        Ok(false)
+    }
+}
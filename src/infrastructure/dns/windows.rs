@@ -0,0 +1,91 @@
+use super::{DnsError, DnsService};
+use std::process::{Command, Output};
+
+/// Marks the NRPT rule Roxy owns, so cleanup leaves other rules alone.
+const RULE_COMMENT: &str = "roxy";
+
+/// NRPT rules name a server but not a port.
+const DEFAULT_DNS_PORT: u16 = 53;
+
+/// Sends `.roxy` queries to the local DNS server through a Name
+/// Resolution Policy Table rule, the Windows counterpart of
+/// `/etc/resolver`.
+pub struct WindowsDnsService;
+
+impl WindowsDnsService {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for WindowsDnsService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run a PowerShell snippet and fail on a non-zero exit.
+fn powershell(script: &str) -> Result<Output, DnsError> {
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .output()
+        .map_err(|e| DnsError::ValidationFailed(format!("Failed to run PowerShell: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("Access is denied") || stderr.contains("PermissionDenied") {
+            return Err(DnsError::PermissionDenied);
+        }
+        return Err(DnsError::ValidationFailed(stderr.trim().to_string()));
+    }
+    Ok(output)
+}
+
+fn roxy_rules() -> String {
+    format!(
+        "Get-DnsClientNrptRule | Where-Object {{ $_.Comment -eq '{}' }}",
+        RULE_COMMENT
+    )
+}
+
+impl DnsService for WindowsDnsService {
+    fn setup(&self, port: u16) -> Result<(), DnsError> {
+        if port != DEFAULT_DNS_PORT {
+            return Err(DnsError::PortNotSupported(port));
+        }
+
+        self.cleanup()?;
+        powershell(&format!(
+            "Add-DnsClientNrptRule -Namespace '.roxy' -NameServers '127.0.0.1' -Comment '{}'",
+            RULE_COMMENT
+        ))?;
+        Ok(())
+    }
+
+    fn cleanup(&self) -> Result<(), DnsError> {
+        powershell(&format!(
+            "{} | Remove-DnsClientNrptRule -Force",
+            roxy_rules()
+        ))?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), DnsError> {
+        if !self.is_configured() {
+            return Err(DnsError::ValidationFailed(
+                "No NRPT rule for .roxy found. Run: roxy install".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn is_configured(&self) -> bool {
+        powershell(&format!("({} | Measure-Object).Count", roxy_rules()))
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim() != "0")
+            .unwrap_or(false)
+    }
+
+    fn configured_port(&self) -> Option<u16> {
+        self.is_configured().then_some(DEFAULT_DNS_PORT)
+    }
+}
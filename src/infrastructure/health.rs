@@ -0,0 +1,392 @@
+//! Health checks run by `roxy status` against the live daemon.
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName};
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use simple_dns::rdata::RData;
+use simple_dns::{CLASS, Name, Packet, PacketFlag, Question, RCODE, TYPE};
+
+use crate::domain::ProxyTarget;
+use crate::infrastructure::dns::hosts::HostsFile;
+use crate::infrastructure::dns::{DnsError, DnsService};
+use crate::infrastructure::port_forward::{PortForwardError, PortForwarder};
+
+/// How long each check waits for the daemon.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Backends run locally, so anything slower than this counts as down.
+const BACKEND_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Hostname used when no registered HTTPS domain is available. The
+/// daemon answers any `.roxy` name, with an on-demand cert if needed.
+pub const PROBE_HOSTNAME: &str = "roxy-health-check.roxy";
+
+/// Outcome of a single check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthStatus {
+    Ok(String),
+    Broken { reason: String, hint: String },
+    Skipped(String),
+}
+
+impl HealthStatus {
+    fn broken(reason: impl Into<String>, hint: impl Into<String>) -> Self {
+        HealthStatus::Broken {
+            reason: reason.into(),
+            hint: hint.into(),
+        }
+    }
+}
+
+/// Ask the DNS server on `port` to resolve a `.roxy` name.
+pub fn check_dns(port: u16) -> HealthStatus {
+    match query_dns(port) {
+        Ok(ip) => HealthStatus::Ok(format!("port {} answers ({})", port, ip)),
+        Err(reason) => HealthStatus::broken(
+            reason,
+            format!(
+                "Check the log with 'roxy logs' or see what holds the port: sudo lsof -i :{}",
+                port
+            ),
+        ),
+    }
+}
+
+fn query_dns(port: u16) -> Result<Ipv4Addr, String> {
+    let mut packet = Packet::new_query(0x0a11);
+    packet.set_flags(PacketFlag::RECURSION_DESIRED);
+    packet.questions.push(Question::new(
+        Name::new_unchecked(PROBE_HOSTNAME),
+        TYPE::A.into(),
+        CLASS::IN.into(),
+        false,
+    ));
+    let query = packet.build_bytes_vec().map_err(|e| e.to_string())?;
+
+    let socket = UdpSocket::bind(("127.0.0.1", 0)).map_err(|e| e.to_string())?;
+    socket
+        .set_read_timeout(Some(CHECK_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    socket
+        .send_to(&query, ("127.0.0.1", port))
+        .map_err(|e| format!("query failed: {}", e))?;
+
+    let mut buf = [0u8; 512];
+    let len = socket
+        .recv(&mut buf)
+        .map_err(|_| format!("no answer on port {}", port))?;
+
+    let response = Packet::parse(&buf[..len]).map_err(|e| format!("bad answer: {}", e))?;
+    if response.rcode() != RCODE::NoError {
+        return Err(format!("answered {:?}", response.rcode()));
+    }
+    response
+        .answers
+        .iter()
+        .find_map(|record| match &record.rdata {
+            RData::A(a) => Some(Ipv4Addr::from(a.address)),
+            _ => None,
+        })
+        .ok_or_else(|| "answer has no address".to_string())
+}
+
+/// Make sure the system resolver sends `.roxy` queries to `port`.
+pub fn check_resolver(dns: Result<Box<dyn DnsService>, DnsError>, port: u16) -> HealthStatus {
+    let dns = match dns {
+        Ok(dns) => dns,
+        Err(e) => return HealthStatus::Skipped(e.to_string()),
+    };
+
+    match dns.configured_port() {
+        None => HealthStatus::broken("not configured", "Run: sudo roxy install"),
+        Some(configured) if configured != port => HealthStatus::broken(
+            format!("points at port {}, daemon uses {}", configured, port),
+            "Run: sudo roxy reload (or sudo roxy install)",
+        ),
+        Some(_) => HealthStatus::Ok(format!("points at port {}", port)),
+    }
+}
+
+/// Make sure the rootless-mode redirects are still loaded; firewall
+/// rules don't survive a reboot.
+pub fn check_port_forwarding(
+    forwarder: Result<Box<dyn PortForwarder>, PortForwardError>,
+) -> HealthStatus {
+    match forwarder {
+        Ok(forwarder) if forwarder.is_configured() => HealthStatus::Ok("rules loaded".into()),
+        Ok(_) => HealthStatus::broken("rules not loaded", "Run: sudo roxy install --rootless"),
+        Err(e) => HealthStatus::Skipped(e.to_string()),
+    }
+}
+
+/// Make sure every registered domain is listed in the hosts file.
+pub fn check_hosts_file(hosts: &HostsFile, names: &[String]) -> HealthStatus {
+    let listed = match hosts.managed_names() {
+        Ok(listed) => listed,
+        Err(e) => return HealthStatus::Skipped(e.to_string()),
+    };
+
+    let missing: Vec<&str> = names
+        .iter()
+        .filter(|name| !listed.contains(name))
+        .map(String::as_str)
+        .collect();
+    if missing.is_empty() {
+        HealthStatus::Ok(format!(
+            "{} lists {} domain(s)",
+            hosts.path().display(),
+            names.len()
+        ))
+    } else {
+        HealthStatus::broken(
+            format!("missing {}", missing.join(", ")),
+            "Run: sudo roxy install",
+        )
+    }
+}
+
+/// Complete a TLS handshake with the HTTPS listener, verifying the
+/// certificate against Roxy's Root CA.
+pub fn check_tls(port: u16, hostname: &str, ca_cert: &Path) -> HealthStatus {
+    if !ca_cert.exists() {
+        return HealthStatus::Skipped("Root CA not installed".into());
+    }
+
+    match handshake(port, hostname, ca_cert) {
+        Ok(()) => HealthStatus::Ok(format!("{} on port {}", hostname, port)),
+        Err(reason) => HealthStatus::broken(
+            reason,
+            "Check 'roxy logs' for TLS errors; re-register the domain to renew its certificate",
+        ),
+    }
+}
+
+fn handshake(port: u16, hostname: &str, ca_cert: &Path) -> Result<(), String> {
+    let ca =
+        CertificateDer::from_pem_file(ca_cert).map_err(|e| format!("can't read Root CA: {}", e))?;
+    let mut roots = RootCertStore::empty();
+    roots
+        .add(ca)
+        .map_err(|e| format!("invalid Root CA: {}", e))?;
+
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let server_name =
+        ServerName::try_from(hostname.to_string()).map_err(|e| format!("{}: {}", hostname, e))?;
+    let connection = ClientConnection::new(Arc::new(config), server_name)
+        .map_err(|e| format!("TLS setup failed: {}", e))?;
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let socket = TcpStream::connect_timeout(&addr, CHECK_TIMEOUT)
+        .map_err(|e| format!("can't connect to port {}: {}", port, e))?;
+    socket
+        .set_read_timeout(Some(CHECK_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+
+    let mut tls = StreamOwned::new(connection, socket);
+    while tls.conn.is_handshaking() {
+        tls.conn
+            .complete_io(&mut tls.sock)
+            .map_err(|e| format!("handshake failed: {}", e))?;
+    }
+
+    // Be polite and close the session; errors here don't matter
+    tls.conn.send_close_notify();
+    let _ = tls.flush();
+    let _ = tls.read(&mut [0u8; 1]);
+    Ok(())
+}
+
+/// Whether a proxy backend accepts TCP connections.
+pub fn is_backend_up(target: &ProxyTarget) -> bool {
+    let Ok(addrs) = target.to_string().to_socket_addrs() else {
+        return false;
+    };
+    addrs
+        .into_iter()
+        .any(|addr| TcpStream::connect_timeout(&addr, BACKEND_TIMEOUT).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_dns::ResourceRecord;
+    use simple_dns::rdata::A;
+    use std::thread;
+
+    struct FakeResolver(Option<u16>);
+
+    impl DnsService for FakeResolver {
+        fn setup(&self, _port: u16) -> Result<(), DnsError> {
+            Ok(())
+        }
+        fn cleanup(&self) -> Result<(), DnsError> {
+            Ok(())
+        }
+        fn validate(&self) -> Result<(), DnsError> {
+            Ok(())
+        }
+        fn is_configured(&self) -> bool {
+            self.0.is_some()
+        }
+        fn configured_port(&self) -> Option<u16> {
+            self.0
+        }
+    }
+
+    fn resolver(port: Option<u16>) -> Result<Box<dyn DnsService>, DnsError> {
+        Ok(Box::new(FakeResolver(port)))
+    }
+
+    #[test]
+    fn dns_check_reads_the_answer() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = server.local_addr().unwrap().port();
+
+        let responder = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (len, from) = server.recv_from(&mut buf).unwrap();
+            let query = Packet::parse(&buf[..len]).unwrap();
+            let mut reply = Packet::new_reply(query.id());
+            reply.set_flags(PacketFlag::RESPONSE);
+            reply.answers.push(ResourceRecord::new(
+                query.questions[0].qname.clone(),
+                CLASS::IN,
+                1,
+                RData::A(A::from(Ipv4Addr::LOCALHOST)),
+            ));
+            server
+                .send_to(&reply.build_bytes_vec().unwrap(), from)
+                .unwrap();
+        });
+
+        assert_eq!(
+            check_dns(port),
+            HealthStatus::Ok(format!("port {} answers (127.0.0.1)", port))
+        );
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn dns_check_reports_silence() {
+        // Bound but never answering
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = server.local_addr().unwrap().port();
+        assert!(matches!(check_dns(port), HealthStatus::Broken { .. }));
+    }
+
+    #[test]
+    fn resolver_check_compares_ports() {
+        assert!(matches!(
+            check_resolver(resolver(Some(1053)), 1053),
+            HealthStatus::Ok(_)
+        ));
+        assert!(matches!(
+            check_resolver(resolver(Some(53)), 1053),
+            HealthStatus::Broken { .. }
+        ));
+        assert!(matches!(
+            check_resolver(resolver(None), 1053),
+            HealthStatus::Broken { .. }
+        ));
+        assert!(matches!(
+            check_resolver(Err(DnsError::UnsupportedPlatform("test".into())), 1053),
+            HealthStatus::Skipped(_)
+        ));
+    }
+
+    #[test]
+    fn hosts_check_reports_missing_names() {
+        let tmp = tempfile::tempdir().unwrap();
+        let hosts = HostsFile::new(tmp.path().join("hosts"));
+        hosts.sync(&["a.roxy".to_string()]).unwrap();
+
+        assert!(matches!(
+            check_hosts_file(&hosts, &["a.roxy".to_string()]),
+            HealthStatus::Ok(_)
+        ));
+        assert_eq!(
+            check_hosts_file(&hosts, &["a.roxy".to_string(), "b.roxy".to_string()]),
+            HealthStatus::broken("missing b.roxy", "Run: sudo roxy install")
+        );
+    }
+
+    struct FakeForwarder(bool);
+
+    impl PortForwarder for FakeForwarder {
+        fn setup(
+            &self,
+            _: &[crate::infrastructure::port_forward::Redirect],
+        ) -> Result<(), PortForwardError> {
+            Ok(())
+        }
+
+        fn cleanup(&self) -> Result<(), PortForwardError> {
+            Ok(())
+        }
+
+        fn is_configured(&self) -> bool {
+            self.0
+        }
+    }
+
+    #[test]
+    fn port_forwarding_check_reports_missing_rules() {
+        assert!(matches!(
+            check_port_forwarding(Ok(Box::new(FakeForwarder(true)))),
+            HealthStatus::Ok(_)
+        ));
+        assert_eq!(
+            check_port_forwarding(Ok(Box::new(FakeForwarder(false)))),
+            HealthStatus::broken("rules not loaded", "Run: sudo roxy install --rootless")
+        );
+        assert!(matches!(
+            check_port_forwarding(Err(PortForwardError::UnsupportedPlatform("plan9".into()))),
+            HealthStatus::Skipped(_)
+        ));
+    }
+
+    #[test]
+    fn tls_check_reports_closed_port() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ca = crate::infrastructure::certs::ca::RootCA::new(tmp.path().to_path_buf());
+        ca.generate().unwrap();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        assert!(matches!(
+            check_tls(port, PROBE_HOSTNAME, &ca.cert_path()),
+            HealthStatus::Broken { .. }
+        ));
+    }
+
+    #[test]
+    fn tls_check_skips_without_ca() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            check_tls(443, PROBE_HOSTNAME, &tmp.path().join("ca.crt")),
+            HealthStatus::Skipped(_)
+        ));
+    }
+
+    #[test]
+    fn backend_check_connects() {
+        use crate::domain::value_objects::port::Port;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = Port::new(listener.local_addr().unwrap().port()).unwrap();
+        assert!(is_backend_up(&ProxyTarget::new("127.0.0.1", port)));
+
+        drop(listener);
+        assert!(!is_backend_up(&ProxyTarget::new("127.0.0.1", port)));
+    }
+}
@@ -0,0 +1,193 @@
+//! `tail -f` for the daemon log, driven by file system notifications.
+//!
+//! The parent directory is watched rather than the file itself so that
+//! a log that is deleted, truncated or replaced (e.g. by `roxy logs
+//! --clear` or log rotation) is picked up again without restarting.
+
+use std::fs::{self, File, Metadata};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use notify::{RecursiveMode, Watcher};
+
+/// Safety net for platforms or file systems that drop events.
+const FALLBACK_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Follow `path`, writing new content to `out` until an I/O error occurs.
+pub fn follow(path: &Path, out: &mut impl Write) -> Result<()> {
+    let watch_dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).context("Failed to start log file watcher")?;
+    watcher
+        .watch(watch_dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {}", watch_dir.display()))?;
+
+    let mut follower = LogFollower::at_end(path.to_path_buf());
+
+    loop {
+        // Any change in the log directory is worth a look; event paths
+        // aren't reliably comparable (symlinked dirs such as /var on macOS)
+        match rx.recv_timeout(FALLBACK_POLL_INTERVAL) {
+            Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                anyhow::bail!("Log file watcher stopped unexpectedly")
+            }
+        }
+
+        follower.poll(out).context("Error reading log file")?;
+    }
+}
+
+/// Tracks the read position in a log file across truncation and
+/// replacement.
+pub struct LogFollower {
+    path: PathBuf,
+    file: Option<File>,
+    position: u64,
+    identity: Option<u64>,
+}
+
+impl LogFollower {
+    /// Start following from the current end of the file, so only lines
+    /// written from now on are reported.
+    pub fn at_end(path: PathBuf) -> Self {
+        let mut follower = Self {
+            path,
+            file: None,
+            position: 0,
+            identity: None,
+        };
+        if let Ok(file) = File::open(&follower.path)
+            && let Ok(meta) = file.metadata()
+        {
+            follower.position = meta.len();
+            follower.identity = file_identity(&meta);
+            follower.file = Some(file);
+        }
+        follower
+    }
+
+    /// Copy anything appended since the last call to `out`.
+    pub fn poll(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let meta = match fs::metadata(&self.path) {
+            Ok(meta) => meta,
+            // Removed - wait for it to be recreated
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.file = None;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+
+        let replaced = self.file.is_none() || file_identity(&meta) != self.identity;
+        if replaced {
+            self.file = Some(File::open(&self.path)?);
+            self.identity = file_identity(&meta);
+            self.position = 0;
+        } else if meta.len() < self.position {
+            // Truncated in place - start over from the beginning
+            self.position = 0;
+        }
+
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+        file.seek(SeekFrom::Start(self.position))?;
+        let copied = io::copy(file, out)?;
+        self.position += copied;
+        out.flush()
+    }
+}
+
+#[cfg(unix)]
+fn file_identity(meta: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(meta.ino())
+}
+
+#[cfg(not(unix))]
+fn file_identity(_meta: &Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+
+    fn append(path: &Path, text: &str) {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    fn poll(follower: &mut LogFollower) -> String {
+        let mut out = Vec::new();
+        follower.poll(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn reports_only_new_lines() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("roxy.log");
+        append(&path, "old\n");
+
+        let mut follower = LogFollower::at_end(path.clone());
+        assert_eq!(poll(&mut follower), "");
+
+        append(&path, "new\n");
+        assert_eq!(poll(&mut follower), "new\n");
+        assert_eq!(poll(&mut follower), "");
+    }
+
+    #[test]
+    fn restarts_after_truncation() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("roxy.log");
+        append(&path, "a long line before clearing\n");
+
+        let mut follower = LogFollower::at_end(path.clone());
+        File::create(&path).unwrap();
+        append(&path, "fresh\n");
+
+        assert_eq!(poll(&mut follower), "fresh\n");
+    }
+
+    #[test]
+    fn picks_up_recreated_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("roxy.log");
+        append(&path, "before\n");
+
+        let mut follower = LogFollower::at_end(path.clone());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(poll(&mut follower), "");
+
+        append(&path, "after rotation, longer than before\n");
+        assert_eq!(poll(&mut follower), "after rotation, longer than before\n");
+    }
+
+    #[test]
+    fn waits_for_missing_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("roxy.log");
+
+        let mut follower = LogFollower::at_end(path.clone());
+        assert_eq!(poll(&mut follower), "");
+
+        append(&path, "first\n");
+        assert_eq!(poll(&mut follower), "first\n");
+    }
+}
@@ -1,7 +1,19 @@
 pub mod certs;
+pub mod compose;
 pub mod config;
+pub mod control;
 pub mod dns;
+pub mod health;
+pub mod log_follow;
 pub mod network;
+pub mod password;
 pub mod paths;
 pub mod pid;
+pub mod port_forward;
+pub mod project;
+pub mod qr;
+pub mod service;
+pub mod service_user;
+pub mod tls;
 pub mod tracing;
+pub mod user;
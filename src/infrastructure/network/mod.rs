@@ -1,11 +1,94 @@
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+pub mod wsl;
 
 /// Get the primary LAN IPv4 address of the host.
 /// Returns 127.0.0.1 as fallback if no network is available.
+/// Under WSL2 with mirrored networking this is the address shared with
+/// Windows.
 pub fn get_lan_ip() -> Ipv4Addr {
     get_lan_ip_impl().unwrap_or(Ipv4Addr::new(127, 0, 0, 1))
 }
 
+/// Get an IPv6 address other machines on the LAN can reach the host
+/// at, if it has one. Unique local addresses (fd00::/8) are preferred:
+/// they stay put when the provider changes the global prefix.
+pub fn get_lan_ipv6() -> Option<Ipv6Addr> {
+    pick_lan_ipv6(get_ipv6_candidates())
+}
+
+fn pick_lan_ipv6(candidates: impl IntoIterator<Item = Ipv6Addr>) -> Option<Ipv6Addr> {
+    candidates
+        .into_iter()
+        .filter(is_lan_ipv6)
+        .min_by_key(|ip| !ip.is_unique_local())
+}
+
+/// Global unicast (2000::/3) or unique local (fc00::/7).
+fn is_lan_ipv6(ip: &Ipv6Addr) -> bool {
+    ip.is_unique_local() || ip.segments()[0] & 0xe000 == 0x2000
+}
+
+#[cfg(target_os = "macos")]
+fn get_ipv6_candidates() -> Vec<Ipv6Addr> {
+    (0..=5)
+        .filter_map(|i| {
+            std::process::Command::new("ifconfig")
+                .arg(format!("en{}", i))
+                .output()
+                .ok()
+        })
+        .filter(|output| output.status.success())
+        .flat_map(|output| parse_ifconfig_ipv6(&String::from_utf8_lossy(&output.stdout)))
+        .collect()
+}
+
+/// The `inet6` addresses in `ifconfig` output, leaving out temporary
+/// (privacy) addresses, which change every day or so.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_ifconfig_ipv6(output: &str) -> Vec<Ipv6Addr> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            if fields.next() != Some("inet6") {
+                return None;
+            }
+            let address = fields.next()?.split('%').next()?;
+            let flags: Vec<_> = fields.collect();
+            if flags
+                .iter()
+                .any(|flag| ["temporary", "deprecated", "detached"].contains(flag))
+            {
+                return None;
+            }
+            address.parse().ok()
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn get_ipv6_candidates() -> Vec<Ipv6Addr> {
+    // hostname -I lists global and unique local addresses too
+    std::process::Command::new("hostname")
+        .arg("-I")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .split_whitespace()
+                .filter_map(|ip| ip.parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn get_ipv6_candidates() -> Vec<Ipv6Addr> {
+    Vec::new()
+}
+
 #[cfg(target_os = "macos")]
 fn get_lan_ip_impl() -> Option<Ipv4Addr> {
     // Try en0 first (usually WiFi on Mac)
@@ -62,6 +145,7 @@ fn get_lan_ip_impl() -> Option<Ipv4Addr> {
             if let Ok(ip) = ip_str.parse::<Ipv4Addr>()
                 && ip.is_private()
                 && !ip.is_link_local()
+                && ip != wsl::DNS_TUNNEL_IP
             {
                 return Some(ip);
             }
@@ -87,6 +171,38 @@ mod tests {
         assert!(ip.is_private() || ip.is_loopback());
     }
 
+    #[test]
+    fn test_pick_lan_ipv6_prefers_unique_local() {
+        let candidates = ["fe80::1", "2001:db8::5", "fd12:3456::5", "::1"]
+            .map(|ip| ip.parse::<Ipv6Addr>().unwrap());
+        assert_eq!(
+            pick_lan_ipv6(candidates),
+            Some("fd12:3456::5".parse().unwrap())
+        );
+        assert_eq!(
+            pick_lan_ipv6(["2001:db8::5".parse().unwrap()]),
+            Some("2001:db8::5".parse().unwrap())
+        );
+        assert_eq!(pick_lan_ipv6(["fe80::1".parse().unwrap()]), None);
+    }
+
+    #[test]
+    fn test_parse_ifconfig_ipv6_skips_temporary_addresses() {
+        let output = "en0: flags=8863<UP,BROADCAST,RUNNING> mtu 1500
+\tinet6 fe80::1c2b:3dff:fe4e:5f60%en0 prefixlen 64 secured scopeid 0xe
+\tinet 192.168.1.20 netmask 0xffffff00 broadcast 192.168.1.255
+\tinet6 2001:db8::1c2b:3dff:fe4e:5f60 prefixlen 64 autoconf secured
+\tinet6 2001:db8::9a7:1234:5678:9abc prefixlen 64 autoconf temporary
+";
+        assert_eq!(
+            parse_ifconfig_ipv6(output),
+            vec![
+                "fe80::1c2b:3dff:fe4e:5f60".parse::<Ipv6Addr>().unwrap(),
+                "2001:db8::1c2b:3dff:fe4e:5f60".parse().unwrap(),
+            ]
+        );
+    }
+
     #[test]
     fn test_get_lan_ip_not_link_local() {
         let ip = get_lan_ip();
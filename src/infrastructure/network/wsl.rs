@@ -0,0 +1,117 @@
+//! WSL2 detection. A daemon inside WSL2 sits behind a NAT that Windows
+//! browsers reach through localhost forwarding, unless WSL runs with
+//! mirrored networking and shares the Windows addresses.
+
+use std::fs;
+use std::net::Ipv4Addr;
+use std::process::Command;
+
+/// Address WSL puts on the loopback interface for DNS tunneling. It
+/// looks private but nothing outside WSL can reach it.
+pub const DNS_TUNNEL_IP: Ipv4Addr = Ipv4Addr::new(10, 255, 255, 254);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WslNetworking {
+    /// The default: WSL gets its own address behind a NAT on the Windows
+    /// host.
+    Nat,
+    /// WSL shares the Windows network interfaces and addresses.
+    Mirrored,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wsl {
+    pub networking: WslNetworking,
+    /// The Windows side of the NAT, i.e. the address queries from
+    /// Windows arrive from. `None` in mirrored mode.
+    pub windows_host: Option<Ipv4Addr>,
+}
+
+/// Detect whether this is a WSL2 distribution and how it is networked.
+pub fn detect() -> Option<Wsl> {
+    let release = fs::read_to_string("/proc/sys/kernel/osrelease").ok()?;
+    if !is_wsl2_kernel(&release) {
+        return None;
+    }
+
+    // `wslinfo` ships with WSL 2.0, which introduced mirrored mode
+    let networking = Command::new("wslinfo")
+        .arg("--networking-mode")
+        .output()
+        .ok()
+        .and_then(|output| parse_networking_mode(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or(WslNetworking::Nat);
+
+    let windows_host = match networking {
+        WslNetworking::Nat => fs::read_to_string("/proc/net/route")
+            .ok()
+            .and_then(|routes| default_gateway(&routes)),
+        WslNetworking::Mirrored => None,
+    };
+
+    Some(Wsl {
+        networking,
+        windows_host,
+    })
+}
+
+/// WSL2 kernels are named like `5.15.153.1-microsoft-standard-WSL2`.
+/// WSL1 (`4.4.0-19041-Microsoft`) shares the Windows network stack and
+/// needs no special handling.
+fn is_wsl2_kernel(release: &str) -> bool {
+    let release = release.trim().to_lowercase();
+    release.contains("microsoft-standard") || release.contains("wsl2")
+}
+
+fn parse_networking_mode(output: &str) -> Option<WslNetworking> {
+    match output.trim() {
+        "nat" => Some(WslNetworking::Nat),
+        "mirrored" => Some(WslNetworking::Mirrored),
+        _ => None,
+    }
+}
+
+/// Gateway of the default route in `/proc/net/route`, where addresses
+/// are little-endian hex.
+fn default_gateway(routes: &str) -> Option<Ipv4Addr> {
+    routes.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.get(1) != Some(&"00000000") {
+            return None;
+        }
+        let gateway = u32::from_str_radix(fields.get(2)?, 16).ok()?;
+        Some(Ipv4Addr::from(gateway.to_le_bytes()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_wsl2_kernels() {
+        assert!(is_wsl2_kernel("5.15.153.1-microsoft-standard-WSL2\n"));
+        assert!(is_wsl2_kernel("4.19.104-microsoft-standard"));
+        assert!(!is_wsl2_kernel("4.4.0-19041-Microsoft"));
+        assert!(!is_wsl2_kernel("6.8.0-45-generic"));
+    }
+
+    #[test]
+    fn parses_networking_mode() {
+        assert_eq!(parse_networking_mode("nat\n"), Some(WslNetworking::Nat));
+        assert_eq!(
+            parse_networking_mode("mirrored\n"),
+            Some(WslNetworking::Mirrored)
+        );
+        assert_eq!(parse_networking_mode(""), None);
+    }
+
+    #[test]
+    fn reads_default_gateway() {
+        let routes = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\n\
+                      eth0\t0010A8C0\t00000000\t0001\t0\t0\t0\t00F0FFFF\n\
+                      eth0\t00000000\t0100A8C0\t0003\t0\t0\t0\t00000000\n";
+        assert_eq!(default_gateway(routes), Some(Ipv4Addr::new(192, 168, 0, 1)));
+        assert_eq!(default_gateway("Iface\tDestination\n"), None);
+    }
+}
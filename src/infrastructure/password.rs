@@ -0,0 +1,109 @@
+//! Password hashes for basic auth, stored in the config as
+//! `pbkdf2-sha256$<iterations>$<salt>$<hash>` with base64 salt and hash.
+
+use std::num::NonZeroU32;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD_NO_PAD as BASE64};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use thiserror::Error;
+
+const SCHEME: &str = "pbkdf2-sha256";
+const ITERATIONS: NonZeroU32 = NonZeroU32::new(100_000).unwrap();
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+
+#[derive(Debug, Error)]
+pub enum PasswordError {
+    #[error("Password must not be empty")]
+    Empty,
+
+    #[error("Failed to generate a random salt")]
+    Random,
+}
+
+/// Hash `password` with a fresh random salt.
+pub fn hash(password: &str) -> Result<String, PasswordError> {
+    if password.is_empty() {
+        return Err(PasswordError::Empty);
+    }
+    let mut salt = [0u8; SALT_LEN];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| PasswordError::Random)?;
+    let mut hash = [0u8; HASH_LEN];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        ITERATIONS,
+        &salt,
+        password.as_bytes(),
+        &mut hash,
+    );
+    Ok(format!(
+        "{}${}${}${}",
+        SCHEME,
+        ITERATIONS,
+        BASE64.encode(salt),
+        BASE64.encode(hash)
+    ))
+}
+
+/// Check `password` against a hash made by [`hash`]. Malformed hashes
+/// never match.
+pub fn verify(hash: &str, password: &str) -> bool {
+    let mut parts = hash.split('$');
+    let (Some(SCHEME), Some(iterations), Some(salt), Some(expected), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return false;
+    };
+    let Some(iterations) = iterations.parse().ok().and_then(NonZeroU32::new) else {
+        return false;
+    };
+    let (Ok(salt), Ok(expected)) = (BASE64.decode(salt), BASE64.decode(expected)) else {
+        return false;
+    };
+    pbkdf2::verify(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        &salt,
+        password.as_bytes(),
+        &expected,
+    )
+    .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_only_the_right_password() {
+        let hashed = hash("hunter2").unwrap();
+        assert!(hashed.starts_with("pbkdf2-sha256$100000$"));
+        assert!(verify(&hashed, "hunter2"));
+        assert!(!verify(&hashed, "hunter3"));
+    }
+
+    #[test]
+    fn salts_every_hash() {
+        assert_ne!(hash("hunter2").unwrap(), hash("hunter2").unwrap());
+    }
+
+    #[test]
+    fn rejects_empty_passwords() {
+        assert!(matches!(hash(""), Err(PasswordError::Empty)));
+    }
+
+    #[test]
+    fn malformed_hashes_never_match() {
+        assert!(!verify("hunter2", "hunter2"));
+        assert!(!verify("pbkdf2-sha256$0$AAAA$AAAA", ""));
+        assert!(!verify("bcrypt$10$AAAA$AAAA", "hunter2"));
+        assert!(!verify("pbkdf2-sha256$1$not base64$AAAA", "hunter2"));
+    }
+}
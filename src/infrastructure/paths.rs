@@ -1,21 +1,78 @@
 use std::path::PathBuf;
 
+use crate::domain::DomainPattern;
+
+#[cfg(unix)]
 fn default_data_dir() -> PathBuf {
     PathBuf::from("/etc/roxy")
 }
 
+#[cfg(unix)]
 fn default_pid_file() -> PathBuf {
     PathBuf::from("/var/run/roxy.pid")
 }
 
+#[cfg(unix)]
 fn default_log_file() -> PathBuf {
     PathBuf::from("/var/log/roxy/roxy.log")
 }
 
+#[cfg(unix)]
 fn default_certs_dir() -> PathBuf {
     PathBuf::from("/etc/roxy/certs")
 }
 
+#[cfg(unix)]
+fn default_control_socket() -> PathBuf {
+    PathBuf::from("/var/run/roxy.sock")
+}
+
+/// Everything lives under `%ProgramData%\roxy` on Windows.
+#[cfg(windows)]
+fn program_data() -> PathBuf {
+    std::env::var_os("ProgramData")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
+        .join("roxy")
+}
+
+#[cfg(windows)]
+fn default_data_dir() -> PathBuf {
+    program_data()
+}
+
+#[cfg(windows)]
+fn default_pid_file() -> PathBuf {
+    program_data().join("roxy.pid")
+}
+
+#[cfg(windows)]
+fn default_log_file() -> PathBuf {
+    program_data().join("logs").join("roxy.log")
+}
+
+#[cfg(windows)]
+fn default_certs_dir() -> PathBuf {
+    program_data().join("certs")
+}
+
+/// The control channel is a named pipe on Windows.
+#[cfg(windows)]
+fn default_control_socket() -> PathBuf {
+    PathBuf::from(r"\\.\pipe\roxy")
+}
+
+/// Config file used when `--config` isn't given.
+#[cfg(unix)]
+pub fn default_config_path() -> PathBuf {
+    PathBuf::from("/etc/roxy/config.toml")
+}
+
+#[cfg(windows)]
+pub fn default_config_path() -> PathBuf {
+    program_data().join("config.toml")
+}
+
 /// All resolved paths needed by Roxy components.
 /// Loaded once from config, then passed to components via DI.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -31,6 +88,9 @@ pub struct RoxyPaths {
 
     #[serde(default = "default_certs_dir")]
     pub certs_dir: PathBuf,
+
+    #[serde(default = "default_control_socket")]
+    pub control_socket: PathBuf,
 }
 
 impl Default for RoxyPaths {
@@ -40,6 +100,24 @@ impl Default for RoxyPaths {
             pid_file: default_pid_file(),
             log_file: default_log_file(),
             certs_dir: default_certs_dir(),
+            control_socket: default_control_socket(),
         }
     }
 }
+
+impl RoxyPaths {
+    /// Where the output of backends started by the daemon is kept, next
+    /// to the daemon's own log.
+    pub fn backend_logs_dir(&self) -> PathBuf {
+        self.log_file
+            .parent()
+            .map(|dir| dir.join("backends"))
+            .unwrap_or_else(|| PathBuf::from("backends"))
+    }
+
+    /// Log file for the backends of one domain, e.g. `myapp.roxy.log`.
+    pub fn backend_log(&self, pattern: &DomainPattern) -> PathBuf {
+        self.backend_logs_dir()
+            .join(format!("{}.log", pattern.cert_name()))
+    }
+}
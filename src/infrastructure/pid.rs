@@ -102,3 +102,34 @@ fn process_exists(pid: u32) -> bool {
         .map(|o| o.status.success())
         .unwrap_or(false)
 }
+
+/// Ask the process to exit, wait, then force it. Windows has no
+/// SIGTERM; a plain `taskkill` sends the closest equivalent.
+#[cfg(windows)]
+fn terminate_process(pid: u32, timeout: Duration) -> Result<()> {
+    use std::process::Command;
+
+    Command::new("taskkill")
+        .args(["/PID", &pid.to_string()])
+        .output()?;
+
+    std::thread::sleep(timeout);
+
+    if process_exists(pid) {
+        Command::new("taskkill")
+            .args(["/F", "/PID", &pid.to_string()])
+            .output()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(windows)]
+fn process_exists(pid: u32) -> bool {
+    use std::process::Command;
+    Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH", "/FO", "CSV"])
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).contains(&format!("\"{}\"", pid)))
+        .unwrap_or(false)
+}
@@ -0,0 +1,217 @@
+use super::{PortForwardError, PortForwarder, Redirect, run};
+
+/// nftables table holding Roxy's rules, so cleanup is a single delete.
+const NFT_TABLE: &str = "roxy";
+
+/// Marks the iptables rules Roxy adds, so cleanup leaves others alone.
+const IPTABLES_COMMENT: &str = "roxy";
+
+/// Redirects with an nftables table of its own. Traffic from this
+/// machine is caught in `output`, traffic from the LAN in `prerouting`.
+pub struct NftForwarder;
+
+impl NftForwarder {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for NftForwarder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn nft_script(redirects: &[Redirect]) -> String {
+    let rules: String = redirects
+        .iter()
+        .map(|r| {
+            format!(
+                "        fib daddr type local {} dport {} redirect to :{}\n",
+                r.protocol, r.from, r.to
+            )
+        })
+        .collect();
+
+    format!(
+        "table ip {table} {{
+    chain prerouting {{
+        type nat hook prerouting priority dstnat; policy accept;
+{rules}    }}
+    chain output {{
+        type nat hook output priority -100; policy accept;
+{rules}    }}
+}}
+",
+        table = NFT_TABLE,
+        rules = rules
+    )
+}
+
+impl PortForwarder for NftForwarder {
+    fn setup(&self, redirects: &[Redirect]) -> Result<(), PortForwardError> {
+        self.cleanup()?;
+        run("nft", &["-f", "-"], Some(&nft_script(redirects)))?;
+        Ok(())
+    }
+
+    fn cleanup(&self) -> Result<(), PortForwardError> {
+        if self.is_configured() {
+            run("nft", &["delete", "table", "ip", NFT_TABLE], None)?;
+        }
+        Ok(())
+    }
+
+    fn is_configured(&self) -> bool {
+        run("nft", &["list", "table", "ip", NFT_TABLE], None).is_ok()
+    }
+}
+
+/// Fallback for systems without `nft`: `REDIRECT` rules in the `nat`
+/// table, tagged with a comment.
+pub struct IptablesForwarder;
+
+impl IptablesForwarder {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn rules(&self) -> Result<String, PortForwardError> {
+        run("iptables", &["-t", "nat", "-S"], None)
+    }
+}
+
+impl Default for IptablesForwarder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `iptables -t nat` arguments adding each redirect to both chains.
+fn iptables_rules(redirects: &[Redirect]) -> Vec<Vec<String>> {
+    ["PREROUTING", "OUTPUT"]
+        .iter()
+        .flat_map(|chain| {
+            redirects.iter().map(move |r| {
+                [
+                    "-A",
+                    chain,
+                    "-p",
+                    &r.protocol.to_string(),
+                    "--dport",
+                    &r.from.to_string(),
+                    "-m",
+                    "addrtype",
+                    "--dst-type",
+                    "LOCAL",
+                    "-m",
+                    "comment",
+                    "--comment",
+                    IPTABLES_COMMENT,
+                    "-j",
+                    "REDIRECT",
+                    "--to-ports",
+                    &r.to.to_string(),
+                ]
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect()
+            })
+        })
+        .collect()
+}
+
+/// Turn Roxy's rules in `iptables -S` output into delete commands.
+fn iptables_deletes(listing: &str) -> Vec<Vec<String>> {
+    let marker = format!("--comment {}", IPTABLES_COMMENT);
+    listing
+        .lines()
+        .filter(|line| line.starts_with("-A ") && line.contains(&marker))
+        .map(|line| {
+            std::iter::once("-D".to_string())
+                .chain(line.split_whitespace().skip(1).map(str::to_string))
+                .collect()
+        })
+        .collect()
+}
+
+fn iptables(args: &[String]) -> Result<(), PortForwardError> {
+    let args: Vec<&str> = ["-t", "nat"]
+        .into_iter()
+        .chain(args.iter().map(String::as_str))
+        .collect();
+    run("iptables", &args, None)?;
+    Ok(())
+}
+
+impl PortForwarder for IptablesForwarder {
+    fn setup(&self, redirects: &[Redirect]) -> Result<(), PortForwardError> {
+        self.cleanup()?;
+        iptables_rules(redirects)
+            .iter()
+            .try_for_each(|rule| iptables(rule))
+    }
+
+    fn cleanup(&self) -> Result<(), PortForwardError> {
+        iptables_deletes(&self.rules()?)
+            .iter()
+            .try_for_each(|rule| iptables(rule))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.rules()
+            .map(|listing| !iptables_deletes(&listing).is_empty())
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Protocol;
+    use super::*;
+
+    fn redirects() -> Vec<Redirect> {
+        vec![
+            Redirect::new(Protocol::Tcp, 80, 8080),
+            Redirect::new(Protocol::Udp, 53, 5353),
+        ]
+    }
+
+    #[test]
+    fn nft_script_covers_both_hooks() {
+        let script = nft_script(&redirects());
+        assert!(script.starts_with("table ip roxy {"));
+        assert!(script.contains("type nat hook prerouting"));
+        assert!(script.contains("type nat hook output"));
+        assert_eq!(
+            script
+                .matches("fib daddr type local tcp dport 80 redirect to :8080")
+                .count(),
+            2
+        );
+        assert!(script.contains("udp dport 53 redirect to :5353"));
+    }
+
+    #[test]
+    fn iptables_rules_are_tagged() {
+        let rules = iptables_rules(&redirects());
+        assert_eq!(rules.len(), 4);
+        assert_eq!(
+            rules[0].join(" "),
+            "-A PREROUTING -p tcp --dport 80 -m addrtype --dst-type LOCAL \
+             -m comment --comment roxy -j REDIRECT --to-ports 8080"
+        );
+    }
+
+    #[test]
+    fn iptables_cleanup_only_touches_roxy_rules() {
+        let listing = "-P PREROUTING ACCEPT\n\
+             -A PREROUTING -p tcp -m tcp --dport 80 -m addrtype --dst-type LOCAL -m comment --comment roxy -j REDIRECT --to-ports 8080\n\
+             -A OUTPUT -p tcp -m tcp --dport 22 -j REDIRECT --to-ports 2222\n";
+        let deletes = iptables_deletes(listing);
+        assert_eq!(deletes.len(), 1);
+        assert_eq!(deletes[0][0], "-D");
+        assert_eq!(deletes[0][1], "PREROUTING");
+        assert!(deletes[0].contains(&"8080".to_string()));
+    }
+}
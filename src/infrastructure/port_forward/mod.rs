@@ -0,0 +1,145 @@
+//! Port redirection for rootless mode: the daemon binds high ports and
+//! the firewall sends traffic for 80/443 (and 53) to them.
+
+use std::fmt;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PortForwardError {
+    #[error(
+        "Permission denied. Port forwarding requires root privileges.\nRun with: sudo roxy install --rootless"
+    )]
+    #[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+    // Used only on macOS and Linux
+    PermissionDenied,
+
+    #[error("'{command}' failed: {message}")]
+    #[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+    CommandFailed { command: String, message: String },
+
+    #[error("Unsupported platform: {0}")]
+    #[allow(dead_code)] // Used only on platforms without port forwarding
+    UnsupportedPlatform(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp => write!(f, "tcp"),
+            Self::Udp => write!(f, "udp"),
+        }
+    }
+}
+
+/// Traffic for `from` on this machine goes to `to` instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Redirect {
+    pub protocol: Protocol,
+    pub from: u16,
+    pub to: u16,
+}
+
+impl Redirect {
+    pub fn new(protocol: Protocol, from: u16, to: u16) -> Self {
+        Self { protocol, from, to }
+    }
+}
+
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} -> {}", self.from, self.protocol, self.to)
+    }
+}
+
+pub trait PortForwarder {
+    /// Replace Roxy's redirect rules with `redirects`
+    fn setup(&self, redirects: &[Redirect]) -> Result<(), PortForwardError>;
+
+    /// Remove Roxy's redirect rules
+    fn cleanup(&self) -> Result<(), PortForwardError>;
+
+    /// Check if Roxy's redirect rules are loaded
+    fn is_configured(&self) -> bool;
+}
+
+#[cfg(target_os = "macos")]
+mod pf;
+
+#[cfg(target_os = "macos")]
+pub use pf::PfForwarder;
+
+#[cfg(target_os = "linux")]
+mod linux;
+
+#[cfg(target_os = "linux")]
+pub use linux::{IptablesForwarder, NftForwarder};
+
+/// Get the port forwarder for the current platform. On Linux nftables is
+/// preferred, with iptables as the fallback.
+pub fn get_port_forwarder() -> Result<Box<dyn PortForwarder>, PortForwardError> {
+    #[cfg(target_os = "macos")]
+    {
+        Ok(Box::new(PfForwarder::new()))
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        if Command::new("nft").arg("--version").output().is_ok() {
+            Ok(Box::new(NftForwarder::new()))
+        } else {
+            Ok(Box::new(IptablesForwarder::new()))
+        }
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        Err(PortForwardError::UnsupportedPlatform(
+            std::env::consts::OS.to_string(),
+        ))
+    }
+}
+
+/// Run a firewall command, optionally feeding it `input`, and return its
+/// stdout. Fails on a non-zero exit.
+#[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+fn run(program: &str, args: &[&str], input: Option<&str>) -> Result<String, PortForwardError> {
+    let command = format!("{} {}", program, args.join(" "));
+    let failed = |message: String| PortForwardError::CommandFailed {
+        command: command.clone(),
+        message,
+    };
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(e.to_string()))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| failed(e.to_string()))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| failed(e.to_string()))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("Permission denied") || stderr.contains("Operation not permitted") {
+            return Err(PortForwardError::PermissionDenied);
+        }
+        return Err(failed(stderr.trim().to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,84 @@
+use super::{PortForwardError, PortForwarder, Redirect, run};
+
+/// The stock `/etc/pf.conf` already evaluates `com.apple/*`, so rules
+/// loaded here take effect without editing it.
+const ANCHOR: &str = "com.apple/roxy";
+
+/// Redirects with pf. Only traffic on the loopback interface is caught;
+/// pf can't hand LAN traffic to a loopback address.
+pub struct PfForwarder;
+
+impl PfForwarder {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for PfForwarder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn pf_rules(redirects: &[Redirect]) -> String {
+    redirects
+        .iter()
+        .map(|r| {
+            format!(
+                "rdr pass on lo0 inet proto {} from any to any port {} -> 127.0.0.1 port {}\n",
+                r.protocol, r.from, r.to
+            )
+        })
+        .collect()
+}
+
+impl PortForwarder for PfForwarder {
+    fn setup(&self, redirects: &[Redirect]) -> Result<(), PortForwardError> {
+        run(
+            "pfctl",
+            &["-a", ANCHOR, "-f", "-"],
+            Some(&pf_rules(redirects)),
+        )?;
+
+        // pf is off by default; enabling it again only fails with
+        // "pf already enabled"
+        match run("pfctl", &["-e"], None) {
+            Err(PortForwardError::CommandFailed { message, .. })
+                if message.contains("already enabled") =>
+            {
+                Ok(())
+            }
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn cleanup(&self) -> Result<(), PortForwardError> {
+        run("pfctl", &["-a", ANCHOR, "-F", "all"], None)?;
+        Ok(())
+    }
+
+    fn is_configured(&self) -> bool {
+        run("pfctl", &["-a", ANCHOR, "-s", "nat"], None)
+            .map(|rules| rules.contains("rdr"))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Protocol;
+    use super::*;
+
+    #[test]
+    fn rules_redirect_on_loopback() {
+        let rules = pf_rules(&[
+            Redirect::new(Protocol::Tcp, 80, 8080),
+            Redirect::new(Protocol::Tcp, 443, 8443),
+        ]);
+        assert_eq!(
+            rules,
+            "rdr pass on lo0 inet proto tcp from any to any port 80 -> 127.0.0.1 port 8080\n\
+             rdr pass on lo0 inet proto tcp from any to any port 443 -> 127.0.0.1 port 8443\n"
+        );
+    }
+}
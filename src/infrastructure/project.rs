@@ -0,0 +1,278 @@
+//! A project's `.roxy.toml`: the domains and routes a repository needs,
+//! for `roxy link` to register and `roxy unlink` to remove again.
+//!
+//! ```toml
+//! [domains."myapp.roxy"]
+//! routes = ["/=3000", "/api=3001;strip", "/docs=./site"]
+//!
+//! [domains."shop.roxy"]
+//! wildcard = true
+//! https = false
+//! routes = ["/=8080;cmd=npm run dev"]
+//! ```
+//!
+//! Routes are written as for `roxy register --route`. Relative static
+//! directories and backend commands are resolved against the project
+//! directory, so the file works wherever the repository is checked out.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::domain::{DomainPattern, Route};
+
+/// Name of the project file.
+pub const PROJECT_FILE: &str = ".roxy.toml";
+
+#[derive(Error, Debug)]
+pub enum ProjectError {
+    #[error("No {PROJECT_FILE} in {0} or its parents")]
+    NotFound(PathBuf),
+
+    #[error("Failed to read {path}: {source}")]
+    ReadError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to parse {path}: {source}")]
+    ParseError {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("{path}: {domain}: {message}")]
+    InvalidDomain {
+        path: PathBuf,
+        domain: String,
+        message: String,
+    },
+}
+
+/// The domains a project file describes, and the directory it's in.
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub dir: PathBuf,
+    pub domains: Vec<ProjectDomain>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectDomain {
+    pub pattern: DomainPattern,
+    pub routes: Vec<Route>,
+    pub https: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Document {
+    #[serde(default)]
+    domains: BTreeMap<String, DomainEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DomainEntry {
+    routes: Vec<String>,
+    #[serde(default)]
+    wildcard: bool,
+    #[serde(default = "default_https")]
+    https: bool,
+}
+
+fn default_https() -> bool {
+    true
+}
+
+/// The project directory for `start`: the nearest one up from it with a
+/// project file.
+pub fn find(start: &Path) -> Result<PathBuf, ProjectError> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(PROJECT_FILE).is_file())
+        .map(Path::to_path_buf)
+        .ok_or_else(|| ProjectError::NotFound(start.to_path_buf()))
+}
+
+/// Read the project file in `dir`.
+pub fn load(dir: &Path) -> Result<Project, ProjectError> {
+    let path = dir.join(PROJECT_FILE);
+    let content = fs::read_to_string(&path).map_err(|source| ProjectError::ReadError {
+        path: path.clone(),
+        source,
+    })?;
+    let document: Document =
+        toml::from_str(&content).map_err(|source| ProjectError::ParseError {
+            path: path.clone(),
+            source,
+        })?;
+
+    let domains = document
+        .domains
+        .into_iter()
+        .map(|(name, entry)| {
+            let invalid = |message: String| ProjectError::InvalidDomain {
+                path: path.clone(),
+                domain: name.clone(),
+                message,
+            };
+            let pattern = DomainPattern::from_name(&name, entry.wildcard)
+                .map_err(|e| invalid(e.to_string()))?;
+            if entry.routes.is_empty() {
+                return Err(invalid("needs at least one route".into()));
+            }
+            let routes = entry
+                .routes
+                .iter()
+                .map(|spec| {
+                    Route::parse(&resolve_target(spec, dir)).map(|route| run_in(route, dir))
+                })
+                .collect::<Result<_, _>>()
+                .map_err(|e| invalid(format!("invalid route: {}", e)))?;
+            Ok(ProjectDomain {
+                pattern,
+                routes,
+                https: entry.https,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Project {
+        dir: dir.to_path_buf(),
+        domains,
+    })
+}
+
+/// A route as written in the project file, with relative static
+/// directories resolved against `dir`.
+fn resolve_target(spec: &str, dir: &Path) -> String {
+    let Some((path, rest)) = spec.split_once('=') else {
+        return spec.to_string();
+    };
+    let (target, flags) = match rest.split_once(';') {
+        Some((target, flags)) => (target, Some(flags)),
+        None => (rest, None),
+    };
+    let target = target
+        .split(',')
+        .map(|part| resolve(part.trim(), dir))
+        .collect::<Vec<_>>()
+        .join(",");
+    match flags {
+        Some(flags) => format!("{}={};{}", path, target, flags),
+        None => format!("{}={}", path, target),
+    }
+}
+
+/// Run a route's backend command in `dir`, or in its `cwd` relative to it.
+fn run_in(mut route: Route, dir: &Path) -> Route {
+    if route.options.cmd.is_some() {
+        let cwd = match route.options.cwd.take() {
+            Some(cwd) => resolve(&cwd, dir),
+            None => dir.display().to_string(),
+        };
+        route.options.cwd = Some(cwd);
+    }
+    route
+}
+
+/// `path` joined onto `dir` if it's `.`, or starts with `./` or `../`.
+fn resolve(path: &str, dir: &Path) -> String {
+    if path == "." || path.starts_with("./") || path.starts_with("../") {
+        dir.join(path).display().to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::RouteTarget;
+
+    fn write(dir: &Path, content: &str) {
+        fs::write(dir.join(PROJECT_FILE), content).unwrap();
+    }
+
+    #[test]
+    fn reads_domains_and_routes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().canonicalize().unwrap();
+        fs::create_dir(dir.join("site")).unwrap();
+        write(
+            &dir,
+            r#"
+            [domains."myapp.roxy"]
+            routes = ["/=3000", "/docs=./site"]
+
+            [domains."shop.roxy"]
+            wildcard = true
+            https = false
+            routes = ["/=8080;cmd=npm run dev"]
+            "#,
+        );
+
+        let project = load(&dir).unwrap();
+        assert_eq!(project.domains.len(), 2);
+
+        let myapp = &project.domains[0];
+        assert_eq!(myapp.pattern.to_string(), "myapp.roxy");
+        assert!(myapp.https);
+        assert!(matches!(
+            &myapp.routes[1].target,
+            RouteTarget::StaticFiles(path) if *path == dir.join("site")
+        ));
+
+        let shop = &project.domains[1];
+        assert_eq!(shop.pattern.to_string(), "*.shop.roxy");
+        assert!(!shop.https);
+        assert_eq!(
+            shop.routes[0].options.cwd.as_deref(),
+            Some(dir.display().to_string().as_str())
+        );
+    }
+
+    #[test]
+    fn finds_the_file_in_a_parent_directory() {
+        let tmp = tempfile::tempdir().unwrap();
+        let nested = tmp.path().join("src/components");
+        fs::create_dir_all(&nested).unwrap();
+        assert!(matches!(find(&nested), Err(ProjectError::NotFound(_))));
+
+        write(tmp.path(), "");
+        assert_eq!(find(&nested).unwrap(), tmp.path());
+    }
+
+    #[test]
+    fn reports_the_domain_at_fault() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "[domains.\"myapp.roxy\"]\nroutes = []\n");
+        let err = load(tmp.path()).unwrap_err().to_string();
+        assert!(
+            err.contains("myapp.roxy: needs at least one route"),
+            "{}",
+            err
+        );
+
+        write(
+            tmp.path(),
+            "[domains.\"myapp.roxy\"]\nroutes = [\"/=nope\"]\n",
+        );
+        assert!(matches!(
+            load(tmp.path()),
+            Err(ProjectError::InvalidDomain { .. })
+        ));
+
+        write(
+            tmp.path(),
+            "[domains.\"myapp.roxy\"]\nroute = [\"/=3000\"]\n",
+        );
+        assert!(matches!(
+            load(tmp.path()),
+            Err(ProjectError::ParseError { .. })
+        ));
+    }
+}
@@ -0,0 +1,517 @@
+//! A small QR code encoder for `roxy share-ca`: byte mode at error
+//! correction level L, versions 1 to 6, enough for a URL of up to 134
+//! bytes.
+
+use thiserror::Error;
+
+/// Total codewords, error correction codewords per block, and blocks of
+/// versions 1 to 6 at level L.
+const VERSIONS: [(usize, usize, usize); 6] = [
+    (26, 7, 1),
+    (44, 10, 1),
+    (70, 15, 1),
+    (100, 20, 1),
+    (134, 26, 1),
+    (172, 18, 2),
+];
+
+/// Longest input that fits: version 6 less the mode and length fields.
+pub const MAX_LEN: usize = 134;
+
+/// Light modules around the code, as scanners expect.
+const QUIET_ZONE: usize = 4;
+
+/// Dark-light-dark-dark-dark-light-dark followed by four light modules,
+/// which looks like a finder pattern to scanners.
+const FINDER_LIKE: [bool; 11] = [
+    true, false, true, true, true, false, true, false, false, false, false,
+];
+
+#[derive(Error, Debug)]
+pub enum QrError {
+    #[error("{0} bytes don't fit in a QR code, at most {MAX_LEN} do")]
+    TooLong(usize),
+}
+
+/// An encoded QR code.
+#[derive(Debug, Clone)]
+pub struct QrCode {
+    size: usize,
+    /// Row by row, `true` for dark.
+    modules: Vec<bool>,
+}
+
+impl QrCode {
+    /// Encode `data` in the smallest version it fits, with the mask that
+    /// scans best.
+    pub fn encode(data: &[u8]) -> Result<Self, QrError> {
+        let version = (1..=VERSIONS.len())
+            .find(|&v| 12 + data.len() * 8 <= data_capacity(v) * 8)
+            .ok_or(QrError::TooLong(data.len()))?;
+
+        let mut grid = Grid::new(version);
+        grid.draw_codewords(&add_error_correction(
+            version,
+            &data_codewords(version, data),
+        ));
+        let mask = (0..8)
+            .min_by_key(|&mask| {
+                let mut masked = grid.clone();
+                masked.apply_mask(mask);
+                masked.draw_format(mask);
+                masked.penalty()
+            })
+            .unwrap_or(0);
+        grid.apply_mask(mask);
+        grid.draw_format(mask);
+
+        Ok(Self {
+            size: grid.size,
+            modules: grid.modules,
+        })
+    }
+
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    /// Draw the code for a terminal with half blocks, two rows of
+    /// modules a line, black on white whatever the terminal's colors.
+    pub fn to_terminal(&self) -> String {
+        let span = self.size + 2 * QUIET_ZONE;
+        let inside = QUIET_ZONE..QUIET_ZONE + self.size;
+        let dark = |x: usize, y: usize| {
+            inside.contains(&x)
+                && inside.contains(&y)
+                && self.is_dark(x - QUIET_ZONE, y - QUIET_ZONE)
+        };
+
+        let mut out = String::new();
+        for y in (0..span).step_by(2) {
+            out.push_str("\x1b[30;47m");
+            for x in 0..span {
+                out.push(match (dark(x, y), dark(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            out.push_str("\x1b[0m\n");
+        }
+        out
+    }
+}
+
+/// Data codewords a version holds at level L.
+fn data_capacity(version: usize) -> usize {
+    let (total, ec_len, blocks) = VERSIONS[version - 1];
+    total - ec_len * blocks
+}
+
+/// `data` in byte mode, terminated and padded to the version's capacity.
+fn data_codewords(version: usize, data: &[u8]) -> Vec<u8> {
+    let capacity = data_capacity(version);
+    let mut bits = Vec::new();
+    push_bits(&mut bits, 0b0100, 4);
+    push_bits(&mut bits, data.len() as u32, 8);
+    for &byte in data {
+        push_bits(&mut bits, byte.into(), 8);
+    }
+    let terminator = (capacity * 8 - bits.len()).min(4);
+    push_bits(&mut bits, 0, terminator);
+    let padding = (8 - bits.len() % 8) % 8;
+    push_bits(&mut bits, 0, padding);
+
+    let mut codewords: Vec<u8> = bits
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | u8::from(bit)))
+        .collect();
+    for pad in [0xEC, 0x11].into_iter().cycle() {
+        if codewords.len() >= capacity {
+            break;
+        }
+        codewords.push(pad);
+    }
+    codewords
+}
+
+fn push_bits(bits: &mut Vec<bool>, value: u32, len: usize) {
+    bits.extend((0..len).rev().map(|i| value >> i & 1 == 1));
+}
+
+/// Split the data codewords into blocks, append each block's error
+/// correction codewords and interleave them.
+fn add_error_correction(version: usize, data: &[u8]) -> Vec<u8> {
+    let (_, ec_len, blocks) = VERSIONS[version - 1];
+    let block_len = data.len() / blocks;
+    let divisor = rs_divisor(ec_len);
+    let blocks: Vec<_> = data
+        .chunks(block_len)
+        .map(|block| (block, rs_remainder(block, &divisor)))
+        .collect();
+
+    let mut out = Vec::with_capacity(data.len() + ec_len * blocks.len());
+    for i in 0..block_len {
+        out.extend(blocks.iter().map(|(block, _)| block[i]));
+    }
+    for i in 0..ec_len {
+        out.extend(blocks.iter().map(|(_, ec)| ec[i]));
+    }
+    out
+}
+
+/// Reed-Solomon generator polynomial of `degree`, highest power first
+/// and its leading 1 left out.
+fn rs_divisor(degree: usize) -> Vec<u8> {
+    let mut divisor = vec![0; degree];
+    divisor[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            divisor[j] = gf_mul(divisor[j], root);
+            if j + 1 < degree {
+                divisor[j] ^= divisor[j + 1];
+            }
+        }
+        root = gf_mul(root, 0x02);
+    }
+    divisor
+}
+
+/// Error correction codewords of `data`.
+fn rs_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut remainder = vec![0; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ remainder.remove(0);
+        remainder.push(0);
+        for (r, &d) in remainder.iter_mut().zip(divisor) {
+            *r ^= gf_mul(d, factor);
+        }
+    }
+    remainder
+}
+
+/// Multiplication in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1.
+fn gf_mul(x: u8, y: u8) -> u8 {
+    let mut z: u32 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11D);
+        z ^= (u32::from(y) >> i & 1) * u32::from(x);
+    }
+    z as u8
+}
+
+/// The 15 format bits for level L and `mask`, error correction included.
+fn format_bits(mask: u32) -> u32 {
+    // Level L is 01
+    let data = 1 << 3 | mask;
+    let mut remainder = data;
+    for _ in 0..10 {
+        remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+    (data << 10 | remainder) ^ 0x5412
+}
+
+/// Modules being laid out, and which of them belong to function
+/// patterns rather than data.
+#[derive(Debug, Clone)]
+struct Grid {
+    size: usize,
+    modules: Vec<bool>,
+    function: Vec<bool>,
+}
+
+impl Grid {
+    /// A grid of `version` with its finder, timing and alignment patterns
+    /// drawn and the format areas reserved.
+    fn new(version: usize) -> Self {
+        let size = version * 4 + 17;
+        let mut grid = Self {
+            size,
+            modules: vec![false; size * size],
+            function: vec![false; size * size],
+        };
+
+        for i in 0..size {
+            grid.set_function(6, i, i % 2 == 0);
+            grid.set_function(i, 6, i % 2 == 0);
+        }
+        for (x, y) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            grid.draw_finder(x, y);
+        }
+        // Versions up to 6 have one alignment pattern, off the finders
+        if version > 1 {
+            grid.draw_alignment(size - 7, size - 7);
+        }
+        grid.draw_format(0);
+        grid
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        let i = y * self.size + x;
+        self.modules[i] = dark;
+        self.function[i] = true;
+    }
+
+    /// A finder pattern centered on `(x, y)` with its light separator.
+    fn draw_finder(&mut self, x: usize, y: usize) {
+        for dy in -4_isize..=4 {
+            for dx in -4_isize..=4 {
+                let (Some(mx), Some(my)) = (x.checked_add_signed(dx), y.checked_add_signed(dy))
+                else {
+                    continue;
+                };
+                if mx < self.size && my < self.size {
+                    let ring = dx.abs().max(dy.abs());
+                    self.set_function(mx, my, ring != 2 && ring != 4);
+                }
+            }
+        }
+    }
+
+    fn draw_alignment(&mut self, x: usize, y: usize) {
+        for dy in -2_isize..=2 {
+            for dx in -2_isize..=2 {
+                let ring = dx.abs().max(dy.abs());
+                self.set_function(
+                    x.saturating_add_signed(dx),
+                    y.saturating_add_signed(dy),
+                    ring != 1,
+                );
+            }
+        }
+    }
+
+    /// Both copies of the format bits for `mask`, and the dark module.
+    fn draw_format(&mut self, mask: u32) {
+        let bits = format_bits(mask);
+        let bit = |i: usize| bits >> i & 1 == 1;
+        let size = self.size;
+
+        for i in 0..6 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    /// Where data bits go, in order: upwards and downwards in two-module
+    /// columns from the right, skipping the vertical timing pattern.
+    fn data_positions(&self) -> Vec<(usize, usize)> {
+        let size = self.size;
+        let mut positions = Vec::new();
+        let mut right = size - 1;
+        loop {
+            if right == 6 {
+                right = 5;
+            }
+            let upward = (right + 1) & 2 == 0;
+            for vert in 0..size {
+                let y = if upward { size - 1 - vert } else { vert };
+                for x in [right, right - 1] {
+                    if !self.function[y * size + x] {
+                        positions.push((x, y));
+                    }
+                }
+            }
+            if right < 3 {
+                break;
+            }
+            right -= 2;
+        }
+        positions
+    }
+
+    /// Lay out the codewords, leaving the remainder bits light.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let mut bits = codewords
+            .iter()
+            .flat_map(|&byte| (0..8).rev().map(move |i| byte >> i & 1 == 1));
+        for (x, y) in self.data_positions() {
+            self.modules[y * self.size + x] = bits.next().unwrap_or(false);
+        }
+    }
+
+    /// Invert the data modules that `mask` selects. Applying it twice
+    /// undoes it.
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                let i = y * self.size + x;
+                if invert && !self.function[i] {
+                    self.modules[i] = !self.modules[i];
+                }
+            }
+        }
+    }
+
+    /// How hard the code is to scan: long runs, blocks of one color,
+    /// finder look-alikes and an uneven balance of dark and light.
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let dark = |x: usize, y: usize| self.modules[y * size + x];
+        let mut score = 0;
+
+        for a in 0..size {
+            let row: Vec<bool> = (0..size).map(|b| dark(b, a)).collect();
+            let column: Vec<bool> = (0..size).map(|b| dark(a, b)).collect();
+            for line in [row, column] {
+                for run in line.chunk_by(|p, q| p == q) {
+                    if run.len() >= 5 {
+                        score += run.len() - 2;
+                    }
+                }
+                for window in line.windows(FINDER_LIKE.len()) {
+                    if window == FINDER_LIKE || window.iter().rev().eq(FINDER_LIKE.iter()) {
+                        score += 40;
+                    }
+                }
+            }
+        }
+
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let color = dark(x, y);
+                if color == dark(x + 1, y) && color == dark(x, y + 1) && color == dark(x + 1, y + 1)
+                {
+                    score += 3;
+                }
+            }
+        }
+
+        let dark_count = self.modules.iter().filter(|&&d| d).count();
+        let percent = dark_count * 100 / self.modules.len();
+        score + percent.abs_diff(50) / 5 * 10
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read `qr` back: find its mask from the format bits, undo it and
+    /// parse the byte-mode segment out of the data codewords.
+    fn decode(qr: &QrCode) -> Vec<u8> {
+        let version = (qr.size - 17) / 4;
+        let template = Grid::new(version);
+        let mask = (0..8)
+            .find(|&mask| {
+                let mut grid = template.clone();
+                grid.draw_format(mask);
+                (0..grid.modules.len())
+                    .filter(|&i| grid.function[i])
+                    .all(|i| grid.modules[i] == qr.modules[i])
+            })
+            .expect("function patterns and format bits");
+
+        let mut grid = template.clone();
+        grid.modules.clone_from(&qr.modules);
+        grid.apply_mask(mask);
+        let bits: Vec<bool> = grid
+            .data_positions()
+            .into_iter()
+            .map(|(x, y)| grid.modules[y * grid.size + x])
+            .collect();
+        let codewords: Vec<u8> = bits
+            .chunks_exact(8)
+            .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | u8::from(bit)))
+            .collect();
+
+        let (_, _, blocks) = VERSIONS[version - 1];
+        let capacity = data_capacity(version);
+        let data: Vec<u8> = (0..blocks)
+            .flat_map(|block| (0..capacity / blocks).map(move |i| i * blocks + block))
+            .map(|i| codewords[i])
+            .collect();
+        assert_eq!(data[0] >> 4, 0b0100, "byte mode");
+        let len = usize::from(data[0] << 4 | data[1] >> 4);
+        (0..len)
+            .map(|i| data[i + 1] << 4 | data[i + 2] >> 4)
+            .collect()
+    }
+
+    #[test]
+    fn encodes_in_the_smallest_version_that_fits() {
+        let short = QrCode::encode(b"http://192.168.1.50/roxy-ca").unwrap();
+        assert_eq!(short.size, 25);
+        assert_eq!(decode(&short), b"http://192.168.1.50/roxy-ca");
+
+        // Version 6 splits the data into two blocks
+        let long = "http://[fd00:1234:5678:9abc:def0:1234:5678:9abc]:49152/roxy-ca".repeat(2);
+        let qr = QrCode::encode(long.as_bytes()).unwrap();
+        assert_eq!(qr.size, 41);
+        assert_eq!(decode(&qr), long.as_bytes());
+
+        let max = vec![b'a'; MAX_LEN];
+        assert_eq!(decode(&QrCode::encode(&max).unwrap()), max);
+        assert!(matches!(
+            QrCode::encode(&[b'a'; MAX_LEN + 1]),
+            Err(QrError::TooLong(135))
+        ));
+    }
+
+    #[test]
+    fn draws_finder_patterns_in_three_corners() {
+        let qr = QrCode::encode(b"roxy").unwrap();
+        let last = qr.size - 7;
+        for (x0, y0) in [(0, 0), (last, 0), (0, last)] {
+            for i in 0..7 {
+                for edge in [0, 6] {
+                    assert!(qr.is_dark(x0 + i, y0 + edge));
+                    assert!(qr.is_dark(x0 + edge, y0 + i));
+                }
+            }
+            assert!(!qr.is_dark(x0 + 1, y0 + 1));
+            assert!(qr.is_dark(x0 + 3, y0 + 3));
+        }
+    }
+
+    #[test]
+    fn computes_error_correction_like_the_standard() {
+        // "01234567" at 1-M, from the example in ISO/IEC 18004
+        let data = [
+            0x10, 0x20, 0x0C, 0x56, 0x61, 0x80, 0xEC, 0x11, 0xEC, 0x11, 0xEC, 0x11, 0xEC, 0x11,
+            0xEC, 0x11,
+        ];
+        assert_eq!(
+            rs_remainder(&data, &rs_divisor(10)),
+            [0xA5, 0x24, 0xD4, 0xC1, 0xED, 0x36, 0xC7, 0x87, 0x2C, 0x55]
+        );
+
+        // Level L with masks 0 and 7
+        assert_eq!(format_bits(0), 0b111011111000100);
+        assert_eq!(format_bits(7), 0b110100101110110);
+    }
+
+    #[test]
+    fn renders_two_rows_per_line() {
+        let qr = QrCode::encode(b"roxy").unwrap();
+        let drawn = qr.to_terminal();
+        let lines: Vec<&str> = drawn.lines().collect();
+        assert_eq!(lines.len(), (qr.size + 2 * QUIET_ZONE).div_ceil(2));
+        assert!(lines[2].contains('█'));
+    }
+}
@@ -0,0 +1,185 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::{ServiceError, ServiceManager, map_write_error, run};
+
+const LABEL: &str = "io.github.rbas.roxy";
+
+/// A LaunchDaemon rather than a LaunchAgent: it runs as root at boot,
+/// so it can bind ports 80 and 443 before anyone logs in.
+const PLIST_DIR: &str = "/Library/LaunchDaemons";
+
+/// Runs the daemon under launchd, which starts it at boot and brings it
+/// back if it crashes.
+pub struct LaunchdService;
+
+impl LaunchdService {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn target() -> String {
+        format!("system/{}", LABEL)
+    }
+
+    /// Whether launchd currently has the service loaded.
+    fn is_loaded(&self) -> bool {
+        Command::new("launchctl")
+            .args(["print", &Self::target()])
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    }
+
+    fn bootstrap(&self) -> Result<(), ServiceError> {
+        let plist = self.definition_path();
+        run(
+            "launchctl",
+            &["bootstrap", "system", &plist.to_string_lossy()],
+        )
+    }
+}
+
+impl Default for LaunchdService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn plist_content(exe: &Path, config_path: &Path) -> String {
+    let args = [
+        exe.to_string_lossy(),
+        "--config".into(),
+        config_path.to_string_lossy(),
+        "start".into(),
+        "--foreground".into(),
+    ];
+    let args: String = args
+        .iter()
+        .map(|arg| format!("        <string>{}</string>\n", xml_escape(arg)))
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+{args}    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+</dict>
+</plist>
+"#,
+        label = LABEL,
+        args = args
+    )
+}
+
+impl ServiceManager for LaunchdService {
+    fn name(&self) -> &'static str {
+        "launchd"
+    }
+
+    fn definition_path(&self) -> PathBuf {
+        Path::new(PLIST_DIR).join(format!("{}.plist", LABEL))
+    }
+
+    fn install(&self, exe: &Path, config_path: &Path) -> Result<(), ServiceError> {
+        if self.is_loaded() {
+            run("launchctl", &["bootout", &Self::target()])?;
+        }
+
+        let path = self.definition_path();
+        fs::write(&path, plist_content(exe, config_path)).map_err(|e| map_write_error(&path, e))?;
+        self.bootstrap()
+    }
+
+    fn uninstall(&self) -> Result<(), ServiceError> {
+        if self.is_loaded() {
+            run("launchctl", &["bootout", &Self::target()])?;
+        }
+
+        let path = self.definition_path();
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| match e.kind() {
+                std::io::ErrorKind::PermissionDenied => ServiceError::PermissionDenied,
+                _ => ServiceError::RemoveError {
+                    path: path.clone(),
+                    source: e,
+                },
+            })?;
+        }
+        Ok(())
+    }
+
+    fn start(&self) -> Result<(), ServiceError> {
+        if self.is_loaded() {
+            run("launchctl", &["kickstart", &Self::target()])
+        } else {
+            self.bootstrap()
+        }
+    }
+
+    /// Unloading keeps launchd from restarting the daemon; it comes back
+    /// at the next boot or `roxy start`.
+    fn stop(&self) -> Result<(), ServiceError> {
+        run("launchctl", &["bootout", &Self::target()])
+    }
+
+    fn restart(&self) -> Result<(), ServiceError> {
+        if self.is_loaded() {
+            run("launchctl", &["kickstart", "-k", &Self::target()])
+        } else {
+            self.bootstrap()
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        Command::new("launchctl")
+            .args(["print", &Self::target()])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).contains("state = running"))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plist_runs_daemon_in_foreground() {
+        let content = plist_content(
+            Path::new("/opt/homebrew/bin/roxy"),
+            Path::new("/etc/roxy/config.toml"),
+        );
+        assert!(content.contains("<string>io.github.rbas.roxy</string>"));
+        assert!(content.contains("<string>/opt/homebrew/bin/roxy</string>"));
+        assert!(content.contains("<string>/etc/roxy/config.toml</string>"));
+        assert!(content.contains("<string>--foreground</string>"));
+        assert!(content.contains("<key>RunAtLoad</key>"));
+    }
+
+    #[test]
+    fn plist_escapes_paths() {
+        let content = plist_content(Path::new("/Apps/R&D/roxy"), Path::new("/etc/roxy.toml"));
+        assert!(content.contains("<string>/Apps/R&amp;D/roxy</string>"));
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ServiceError {
+    #[error(
+        "Permission denied. Managing the system service requires root privileges.\nRun with: sudo roxy service install"
+    )]
+    #[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+    // Used only on platforms with a service manager
+    PermissionDenied,
+
+    #[error("Failed to write service file {path}: {source}")]
+    #[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+    WriteError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to remove service file {path}: {source}")]
+    #[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+    RemoveError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("'{command}' failed: {message}")]
+    #[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+    CommandFailed { command: String, message: String },
+
+    #[error("Unsupported platform: {0}")]
+    #[allow(dead_code)] // Used only on platforms without a service manager
+    UnsupportedPlatform(String),
+}
+
+/// A system service manager that keeps the daemon running across
+/// reboots and restarts it if it crashes.
+pub trait ServiceManager {
+    /// Name shown to the user, e.g. `launchd`
+    fn name(&self) -> &'static str;
+
+    /// Where the service definition is written
+    fn definition_path(&self) -> PathBuf;
+
+    /// Write the service definition for `exe` and load it, which also
+    /// starts the daemon
+    fn install(&self, exe: &Path, config_path: &Path) -> Result<(), ServiceError>;
+
+    /// Stop the daemon and remove the service definition
+    fn uninstall(&self) -> Result<(), ServiceError>;
+
+    fn start(&self) -> Result<(), ServiceError>;
+
+    fn stop(&self) -> Result<(), ServiceError>;
+
+    fn restart(&self) -> Result<(), ServiceError>;
+
+    /// Whether the service manager reports the daemon as running
+    fn is_running(&self) -> bool;
+
+    fn is_installed(&self) -> bool {
+        self.definition_path().exists()
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod launchd;
+
+#[cfg(target_os = "macos")]
+pub use launchd::LaunchdService;
+
+#[cfg(target_os = "linux")]
+mod systemd;
+
+#[cfg(target_os = "linux")]
+pub use systemd::SystemdService;
+
+/// Get the service manager for the current platform
+pub fn get_service_manager() -> Result<Box<dyn ServiceManager>, ServiceError> {
+    #[cfg(target_os = "macos")]
+    {
+        Ok(Box::new(LaunchdService::new()))
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        Ok(Box::new(SystemdService::new()))
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        Err(ServiceError::UnsupportedPlatform(
+            std::env::consts::OS.to_string(),
+        ))
+    }
+}
+
+/// Run a service manager command, failing on a non-zero exit.
+#[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+fn run(program: &str, args: &[&str]) -> Result<(), ServiceError> {
+    let command = format!("{} {}", program, args.join(" "));
+    let output =
+        Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| ServiceError::CommandFailed {
+                command: command.clone(),
+                message: e.to_string(),
+            })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("Operation not permitted") || stderr.contains("Permission denied") {
+            return Err(ServiceError::PermissionDenied);
+        }
+        return Err(ServiceError::CommandFailed {
+            command,
+            message: stderr.trim().to_string(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+fn map_write_error(path: &Path, e: std::io::Error) -> ServiceError {
+    if e.kind() == std::io::ErrorKind::PermissionDenied {
+        ServiceError::PermissionDenied
+    } else {
+        ServiceError::WriteError {
+            path: path.to_path_buf(),
+            source: e,
+        }
+    }
+}
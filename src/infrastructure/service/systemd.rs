@@ -0,0 +1,156 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::{ServiceError, ServiceManager, map_write_error, run};
+
+const UNIT: &str = "roxy.service";
+const UNIT_DIR: &str = "/etc/systemd/system";
+
+/// Runs the daemon as a systemd unit, started at boot and restarted if
+/// it crashes.
+pub struct SystemdService;
+
+impl SystemdService {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for SystemdService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Quote one `ExecStart=` argument. `%` starts a specifier in unit
+/// files, so it is doubled even inside quotes.
+fn quote(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%");
+    if escaped
+        .chars()
+        .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';'))
+    {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        escaped
+    }
+}
+
+fn unit_content(exe: &Path, config_path: &Path) -> String {
+    let exec_start = [
+        quote(&exe.to_string_lossy()),
+        "--config".into(),
+        quote(&config_path.to_string_lossy()),
+        "start".into(),
+        "--foreground".into(),
+    ]
+    .join(" ");
+
+    format!(
+        "[Unit]
+Description=Roxy local development proxy
+Wants=network-online.target
+After=network-online.target
+
+[Service]
+Type=simple
+ExecStart={}
+Restart=on-failure
+# Lets the daemon bind ports 80, 443 and 53 without root when a
+# User= is set in a drop-in
+AmbientCapabilities=CAP_NET_BIND_SERVICE
+
+[Install]
+WantedBy=multi-user.target
+",
+        exec_start
+    )
+}
+
+impl ServiceManager for SystemdService {
+    fn name(&self) -> &'static str {
+        "systemd"
+    }
+
+    fn definition_path(&self) -> PathBuf {
+        Path::new(UNIT_DIR).join(UNIT)
+    }
+
+    fn install(&self, exe: &Path, config_path: &Path) -> Result<(), ServiceError> {
+        let path = self.definition_path();
+        fs::write(&path, unit_content(exe, config_path)).map_err(|e| map_write_error(&path, e))?;
+        // A unit systemd can't load would still count as installed and
+        // make `roxy start` go through it
+        if let Err(e) =
+            run("systemctl", &["daemon-reload"]).and_then(|()| run("systemctl", &["enable", UNIT]))
+        {
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+        // Picks up a changed unit when the service was already running
+        run("systemctl", &["restart", UNIT])
+    }
+
+    fn uninstall(&self) -> Result<(), ServiceError> {
+        let path = self.definition_path();
+        if !path.exists() {
+            return Ok(());
+        }
+
+        run("systemctl", &["disable", "--now", UNIT])?;
+        fs::remove_file(&path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::PermissionDenied => ServiceError::PermissionDenied,
+            _ => ServiceError::RemoveError {
+                path: path.clone(),
+                source: e,
+            },
+        })?;
+        run("systemctl", &["daemon-reload"])
+    }
+
+    fn start(&self) -> Result<(), ServiceError> {
+        run("systemctl", &["start", UNIT])
+    }
+
+    fn stop(&self) -> Result<(), ServiceError> {
+        run("systemctl", &["stop", UNIT])
+    }
+
+    fn restart(&self) -> Result<(), ServiceError> {
+        run("systemctl", &["restart", UNIT])
+    }
+
+    fn is_running(&self) -> bool {
+        Command::new("systemctl")
+            .args(["is-active", "--quiet", UNIT])
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_runs_daemon_in_foreground() {
+        let content = unit_content(
+            Path::new("/usr/local/bin/roxy"),
+            Path::new("/etc/roxy/config.toml"),
+        );
+        assert!(content.contains(
+            "ExecStart=/usr/local/bin/roxy --config /etc/roxy/config.toml start --foreground\n"
+        ));
+        assert!(content.contains("AmbientCapabilities=CAP_NET_BIND_SERVICE\n"));
+        assert!(content.contains("WantedBy=multi-user.target\n"));
+    }
+
+    #[test]
+    fn exec_start_arguments_are_quoted() {
+        assert_eq!(quote("/opt/my roxy/roxy"), "\"/opt/my roxy/roxy\"");
+        assert_eq!(quote("/etc/100%/config.toml"), "/etc/100%%/config.toml");
+        assert_eq!(quote("/etc/roxy/config.toml"), "/etc/roxy/config.toml");
+    }
+}
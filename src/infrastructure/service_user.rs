@@ -0,0 +1,337 @@
+//! The `_roxy` service account. The daemon binds its ports as root and
+//! then switches to this account, so the Root CA key and the issued
+//! certificates belong to a user that does nothing else.
+
+#[cfg(unix)]
+use std::ffi::{CStr, CString};
+#[cfg(unix)]
+use std::fs;
+use std::io;
+#[cfg(unix)]
+use std::os::unix::fs::{MetadataExt, chown};
+use std::path::{Path, PathBuf};
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::process::Command;
+
+use thiserror::Error;
+
+/// Name of the account created by `roxy install --service-user`.
+pub const SERVICE_USER: &str = "_roxy";
+
+#[derive(Error, Debug)]
+pub enum ServiceUserError {
+    #[error("User '{0}' does not exist. Create it with: sudo roxy install --service-user")]
+    NotFound(String),
+
+    #[error("Failed to create user '{name}': {reason}")]
+    CreateFailed { name: String, reason: String },
+
+    #[error("Failed to change owner of {path}: {source}")]
+    ChownFailed { path: PathBuf, source: io::Error },
+
+    #[error("Failed to switch to user '{name}': {source}")]
+    SwitchFailed { name: String, source: io::Error },
+
+    #[error("Service accounts are not supported on {0}")]
+    #[cfg_attr(unix, allow(dead_code))] // Used only on non-Unix platforms
+    Unsupported(&'static str),
+}
+
+/// A system account, resolved to its numeric ids.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(unix), allow(dead_code))] // Never looked up off Unix
+pub struct ServiceUser {
+    name: String,
+    uid: u32,
+    gid: u32,
+    home: PathBuf,
+}
+
+impl ServiceUser {
+    #[cfg(unix)]
+    pub fn lookup(name: &str) -> Result<Self, ServiceUserError> {
+        let not_found = || ServiceUserError::NotFound(name.to_string());
+        let c_name = CString::new(name).map_err(|_| not_found())?;
+
+        // SAFETY: getpwnam returns null or a pointer to static storage that
+        // stays valid until the next call; the fields are copied out right
+        // away.
+        let (uid, gid, home) = unsafe {
+            let entry = libc::getpwnam(c_name.as_ptr());
+            if entry.is_null() {
+                return Err(not_found());
+            }
+            let home = CStr::from_ptr((*entry).pw_dir).to_string_lossy();
+            ((*entry).pw_uid, (*entry).pw_gid, PathBuf::from(&*home))
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            uid,
+            gid,
+            home,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn lookup(_name: &str) -> Result<Self, ServiceUserError> {
+        Err(ServiceUserError::Unsupported(std::env::consts::OS))
+    }
+
+    /// Look up the account, creating it first if it doesn't exist.
+    /// Requires root.
+    pub fn create(name: &str) -> Result<Self, ServiceUserError> {
+        match Self::lookup(name) {
+            Err(ServiceUserError::NotFound(_)) => {
+                create_account(name)?;
+                Self::lookup(name)
+            }
+            result => result,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn home(&self) -> &Path {
+        &self.home
+    }
+
+    #[cfg(unix)]
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    #[cfg(unix)]
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    /// Hand `path`, and everything below it if it is a directory, to this
+    /// user. Missing paths are ignored.
+    #[cfg(unix)]
+    pub fn chown_all(&self, path: &Path) -> Result<(), ServiceUserError> {
+        let failed = |source| ServiceUserError::ChownFailed {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(failed(e)),
+        };
+        chown(path, Some(self.uid), Some(self.gid)).map_err(failed)?;
+
+        if metadata.is_dir() {
+            for entry in fs::read_dir(path).map_err(failed)? {
+                self.chown_all(&entry.map_err(failed)?.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn chown_all(&self, _path: &Path) -> Result<(), ServiceUserError> {
+        Err(ServiceUserError::Unsupported(std::env::consts::OS))
+    }
+
+    /// Give up root for good. Call this only once every privileged port
+    /// is bound; the process can't get root back afterwards.
+    #[cfg(unix)]
+    pub fn switch_to(&self) -> Result<(), ServiceUserError> {
+        let failed = || ServiceUserError::SwitchFailed {
+            name: self.name.clone(),
+            source: io::Error::last_os_error(),
+        };
+
+        // SAFETY: plain syscalls; the only pointer is to a gid on the stack.
+        // Groups first, since changing them needs the root uid.
+        unsafe {
+            if libc::setgroups(1, &self.gid) != 0 {
+                return Err(failed());
+            }
+            if libc::setgid(self.gid) != 0 {
+                return Err(failed());
+            }
+            if libc::setuid(self.uid) != 0 {
+                return Err(failed());
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn switch_to(&self) -> Result<(), ServiceUserError> {
+        Err(ServiceUserError::Unsupported(std::env::consts::OS))
+    }
+}
+
+#[cfg(unix)]
+pub fn is_root() -> bool {
+    // SAFETY: geteuid has no preconditions and can't fail
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// There is no single superuser id to switch away from.
+#[cfg(not(unix))]
+pub fn is_root() -> bool {
+    false
+}
+
+/// The user id this process runs as.
+#[cfg(unix)]
+pub fn current_uid() -> u32 {
+    // SAFETY: geteuid has no preconditions and can't fail
+    unsafe { libc::geteuid() }
+}
+
+/// Whether this process's user owns `path`.
+#[cfg(unix)]
+pub fn owns(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.uid() == current_uid())
+}
+
+#[cfg(not(unix))]
+pub fn owns(_path: &Path) -> bool {
+    false
+}
+
+/// Give a file written by root the owner of its directory. Keeps files
+/// that `sudo roxy ...` writes into the service user's directories
+/// readable by the daemon. Does nothing unless running as root.
+#[cfg(unix)]
+pub fn inherit_dir_owner(path: &Path) -> io::Result<()> {
+    let Some(dir) = path.parent() else {
+        return Ok(());
+    };
+    if !is_root() {
+        return Ok(());
+    }
+
+    let metadata = fs::metadata(dir)?;
+    if metadata.uid() == 0 {
+        return Ok(());
+    }
+    chown(path, Some(metadata.uid()), Some(metadata.gid()))
+}
+
+#[cfg(not(unix))]
+pub fn inherit_dir_owner(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn create_account(name: &str) -> Result<(), ServiceUserError> {
+    run(
+        name,
+        Command::new("useradd").args([
+            "--system",
+            "--user-group",
+            "--no-create-home",
+            "--home-dir",
+            "/nonexistent",
+            "--shell",
+            "/usr/sbin/nologin",
+            name,
+        ]),
+    )?;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn create_account(name: &str) -> Result<(), ServiceUserError> {
+    let mut used = list_ids(name, "/Users", "UniqueID")?;
+    used.extend(list_ids(name, "/Groups", "PrimaryGroupID")?);
+    let id = free_id(&used)
+        .ok_or_else(|| ServiceUserError::CreateFailed {
+            name: name.to_string(),
+            reason: "no free id between 200 and 400".into(),
+        })?
+        .to_string();
+
+    let group = format!("/Groups/{}", name);
+    let user = format!("/Users/{}", name);
+    let commands: [&[&str]; 8] = [
+        &["-create", &group, "PrimaryGroupID", &id],
+        &["-create", &group, "RealName", "Roxy daemon"],
+        &["-create", &user, "UniqueID", &id],
+        &["-create", &user, "PrimaryGroupID", &id],
+        &["-create", &user, "UserShell", "/usr/bin/false"],
+        &["-create", &user, "NFSHomeDirectory", "/var/empty"],
+        &["-create", &user, "RealName", "Roxy daemon"],
+        &["-create", &user, "IsHidden", "1"],
+    ];
+    for args in commands {
+        run(name, Command::new("dscl").arg(".").args(args))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn create_account(_name: &str) -> Result<(), ServiceUserError> {
+    Err(ServiceUserError::Unsupported(std::env::consts::OS))
+}
+
+/// Ids already taken in a directory service node, from lines like
+/// `_www    70`.
+#[cfg(target_os = "macos")]
+fn list_ids(name: &str, node: &str, key: &str) -> Result<Vec<u32>, ServiceUserError> {
+    let output = run(name, Command::new("dscl").args([".", "-list", node, key]))?;
+    Ok(output
+        .lines()
+        .filter_map(|line| line.split_whitespace().last()?.parse().ok())
+        .collect())
+}
+
+/// macOS keeps ids below 500 for system accounts; Apple's own start low,
+/// so pick from the 200-399 range.
+#[cfg(target_os = "macos")]
+fn free_id(used: &[u32]) -> Option<u32> {
+    (200..400).find(|id| !used.contains(id))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn run(name: &str, command: &mut Command) -> Result<String, ServiceUserError> {
+    let failed = |reason: String| ServiceUserError::CreateFailed {
+        name: name.to_string(),
+        reason,
+    };
+
+    let output = command.output().map_err(|e| failed(e.to_string()))?;
+    if !output.status.success() {
+        return Err(failed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_resolves_root() {
+        let root = ServiceUser::lookup("root").unwrap();
+        assert_eq!(root.uid, 0);
+        assert_eq!(root.name(), "root");
+        assert!(root.home().is_absolute());
+    }
+
+    #[test]
+    fn lookup_reports_missing_user() {
+        assert!(matches!(
+            ServiceUser::lookup("roxy-no-such-user"),
+            Err(ServiceUserError::NotFound(_))
+        ));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn free_id_skips_taken_ids() {
+        assert_eq!(free_id(&[200, 201, 203]), Some(202));
+        assert_eq!(free_id(&(200..400).collect::<Vec<_>>()), None);
+    }
+}
@@ -0,0 +1,208 @@
+//! The `[tls]` section: which protocol versions and cipher suites the
+//! HTTPS listener negotiates.
+
+use std::fmt;
+
+use rustls::SupportedCipherSuite;
+use rustls::SupportedProtocolVersion;
+use rustls::crypto::CryptoProvider;
+use rustls::crypto::aws_lc_rs::{self, ALL_CIPHER_SUITES};
+
+static TLS13_ONLY: &[&SupportedProtocolVersion] = &[&rustls::version::TLS13];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TlsConfig {
+    /// Oldest protocol version accepted.
+    #[serde(default)]
+    pub min_version: TlsVersion,
+
+    /// Cipher suites offered, by their IANA names, e.g.
+    /// `TLS13_AES_128_GCM_SHA256`. Empty means rustls' defaults.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cipher_suites: Vec<String>,
+}
+
+impl TlsConfig {
+    /// The protocol versions to enable, newest first.
+    pub fn protocol_versions(&self) -> &'static [&'static SupportedProtocolVersion] {
+        match self.min_version {
+            TlsVersion::Tls12 => rustls::ALL_VERSIONS,
+            TlsVersion::Tls13 => TLS13_ONLY,
+        }
+    }
+
+    /// The crypto provider restricted to the configured cipher suites.
+    /// Unknown names are skipped; `check` reports them.
+    pub fn crypto_provider(&self) -> CryptoProvider {
+        let mut provider = aws_lc_rs::default_provider();
+        if !self.cipher_suites.is_empty() {
+            provider.cipher_suites = self
+                .cipher_suites
+                .iter()
+                .filter_map(|name| cipher_suite(name))
+                .collect();
+        }
+        provider
+    }
+
+    /// Whether QUIC can be offered: its first packets are always
+    /// protected with `TLS13_AES_128_GCM_SHA256`.
+    pub fn supports_quic(&self) -> bool {
+        self.crypto_provider()
+            .cipher_suites
+            .iter()
+            .any(|suite| suite.suite() == rustls::CipherSuite::TLS13_AES_128_GCM_SHA256)
+    }
+
+    /// Why the section can't be used, if it can't: an unknown cipher
+    /// suite, or none left for the enabled versions.
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(name) = self
+            .cipher_suites
+            .iter()
+            .find(|name| cipher_suite(name).is_none())
+        {
+            let known: Vec<_> = ALL_CIPHER_SUITES.iter().filter_map(suite_name).collect();
+            return Err(format!(
+                "Unknown cipher suite '{}'. Known: {}",
+                name,
+                known.join(", ")
+            ));
+        }
+
+        let versions = self.protocol_versions();
+        let usable = self
+            .crypto_provider()
+            .cipher_suites
+            .iter()
+            .any(|suite| versions.contains(&suite.version()));
+        if !usable {
+            return Err(format!(
+                "None of the cipher suites work with TLS {} or later",
+                self.min_version
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A protocol version as written in the config: `"1.2"` or `"1.3"`.
+/// `roxy config set` stores `1.3` as a number, which is accepted too.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "RawVersion", into = "String")]
+pub enum TlsVersion {
+    #[default]
+    Tls12,
+    Tls13,
+}
+
+impl fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Tls12 => "1.2",
+            Self::Tls13 => "1.3",
+        })
+    }
+}
+
+impl From<TlsVersion> for String {
+    fn from(version: TlsVersion) -> Self {
+        version.to_string()
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum RawVersion {
+    Text(String),
+    Number(f64),
+}
+
+impl TryFrom<RawVersion> for TlsVersion {
+    type Error = String;
+
+    fn try_from(raw: RawVersion) -> Result<Self, Self::Error> {
+        let text = match raw {
+            RawVersion::Text(text) => text,
+            RawVersion::Number(number) => number.to_string(),
+        };
+        match text.trim_start_matches("TLS").trim() {
+            "1.2" => Ok(Self::Tls12),
+            "1.3" => Ok(Self::Tls13),
+            _ => Err(format!(
+                "unsupported TLS version '{}', expected \"1.2\" or \"1.3\"",
+                text
+            )),
+        }
+    }
+}
+
+/// The suite with the IANA `name`, ignoring case.
+fn cipher_suite(name: &str) -> Option<SupportedCipherSuite> {
+    ALL_CIPHER_SUITES
+        .iter()
+        .copied()
+        .find(|suite| suite_name(suite).is_some_and(|n| n.eq_ignore_ascii_case(name)))
+}
+
+fn suite_name(suite: &SupportedCipherSuite) -> Option<&'static str> {
+    suite.suite().as_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> TlsConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn reads_versions_as_text_or_number() {
+        assert_eq!(parse("").min_version, TlsVersion::Tls12);
+        assert_eq!(
+            parse("min_version = \"1.3\"").min_version,
+            TlsVersion::Tls13
+        );
+        assert_eq!(parse("min_version = 1.3").min_version, TlsVersion::Tls13);
+        assert!(toml::from_str::<TlsConfig>("min_version = \"1.1\"").is_err());
+
+        let written = toml::to_string(&parse("min_version = 1.3")).unwrap();
+        assert_eq!(written.trim(), "min_version = \"1.3\"");
+    }
+
+    #[test]
+    fn restricts_cipher_suites() {
+        let tls = parse("cipher_suites = [\"tls13_aes_128_gcm_sha256\"]");
+        assert!(tls.check().is_ok());
+        let suites = tls.crypto_provider().cipher_suites;
+        assert_eq!(suites.len(), 1);
+        assert_eq!(suite_name(&suites[0]), Some("TLS13_AES_128_GCM_SHA256"));
+
+        assert!(parse("").crypto_provider().cipher_suites.len() > 1);
+    }
+
+    #[test]
+    fn quic_needs_its_initial_suite() {
+        assert!(parse("").supports_quic());
+        assert!(parse("cipher_suites = [\"TLS13_AES_128_GCM_SHA256\"]").supports_quic());
+        assert!(!parse("cipher_suites = [\"TLS13_AES_256_GCM_SHA384\"]").supports_quic());
+    }
+
+    #[test]
+    fn rejects_unknown_or_unusable_suites() {
+        let unknown = parse("cipher_suites = [\"TLS_RSA_WITH_RC4_128_MD5\"]");
+        assert!(
+            unknown
+                .check()
+                .unwrap_err()
+                .contains("Unknown cipher suite")
+        );
+
+        let tls12_only = parse(
+            "min_version = \"1.3\"\n\
+             cipher_suites = [\"TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256\"]",
+        );
+        assert!(tls12_only.check().unwrap_err().contains("TLS 1.3"));
+    }
+}
@@ -1,7 +1,19 @@
 use std::fs::{self, OpenOptions};
 use std::path::PathBuf;
 
-use tracing_subscriber::{EnvFilter, fmt, prelude::*};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Level, Metadata, Span, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, Filter};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{EnvFilter, Registry, fmt, prelude::*, reload};
+
+use crate::domain::LogLevel;
+
+/// Name of the span that carries a per-domain or per-route log level.
+const LOG_SCOPE_SPAN: &str = "log_scope";
 
 /// Output destination for tracing
 pub enum TracingOutput {
@@ -11,19 +23,37 @@ pub enum TracingOutput {
     File(PathBuf),
 }
 
+/// Handle for changing the daemon's log level while it is running.
+#[derive(Clone)]
+pub struct LogLevelHandle(reload::Handle<ScopedLevelFilter, Registry>);
+
+impl LogLevelHandle {
+    /// Replace the global filter with `roxy=<level>`.
+    /// Per-domain and per-route overrides keep applying on top.
+    pub fn set(&self, level: LogLevel) -> Result<(), reload::Error> {
+        self.0
+            .modify(|filter| filter.base = EnvFilter::new(format!("roxy={}", level)))
+    }
+
+    /// The active filter directives, e.g. `roxy=info`.
+    pub fn current(&self) -> Option<String> {
+        self.0.with_current(|filter| filter.base.to_string()).ok()
+    }
+}
+
 /// Initialize tracing based on configuration
-/// Priority: ROXY_LOG env > verbose flag > default (info)
-pub fn init_tracing(verbose: bool, output: TracingOutput) {
+/// Priority: ROXY_LOG env > verbose flag > config log_level
+pub fn init_tracing(verbose: bool, log_level: &str, output: TracingOutput) -> LogLevelHandle {
     let filter = EnvFilter::try_from_env("ROXY_LOG").unwrap_or_else(|_| {
-        let level = if verbose { "debug" } else { "info" };
+        let level = if verbose { "debug" } else { log_level };
         EnvFilter::new(format!("roxy={}", level))
     });
+    let (filter, handle) = reload::Layer::new(ScopedLevelFilter::new(filter));
 
     match output {
         TracingOutput::Stdout => {
             tracing_subscriber::registry()
-                .with(filter)
-                .with(fmt::layer().with_target(false))
+                .with(fmt::layer().with_target(false).with_filter(filter))
                 .init();
         }
         TracingOutput::File(path) => {
@@ -40,14 +70,210 @@ pub fn init_tracing(verbose: bool, output: TracingOutput) {
                 .expect("Failed to open log file");
 
             tracing_subscriber::registry()
-                .with(filter)
                 .with(
                     fmt::layer()
                         .with_target(false)
                         .with_ansi(false)
-                        .with_writer(file),
+                        .with_writer(file)
+                        .with_filter(filter),
                 )
                 .init();
         }
     }
+
+    LogLevelHandle(handle)
+}
+
+/// Create a span that applies `level` to every Roxy event recorded
+/// inside it, regardless of the global filter.
+pub fn log_scope(level: LogLevel) -> Span {
+    tracing::span!(Level::ERROR, LOG_SCOPE_SPAN, log_level = level.as_str())
+}
+
+/// Level override stored in the extensions of a `log_scope` span.
+struct ScopedLevel(LevelFilter);
+
+/// Wraps the global `EnvFilter` and lets `log_scope` spans raise or lower
+/// verbosity for the events inside them.
+///
+/// `EnvFilter` span directives can only enable more output, never less,
+/// so quietening a chatty route needs a filter of our own.
+pub struct ScopedLevelFilter {
+    base: EnvFilter,
+}
+
+impl ScopedLevelFilter {
+    fn new(base: EnvFilter) -> Self {
+        Self { base }
+    }
+
+    fn scoped_level<S>(ctx: &Context<'_, S>) -> Option<LevelFilter>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let current = ctx.lookup_current()?;
+        current
+            .scope()
+            .find_map(|span| span.extensions().get::<ScopedLevel>().map(|l| l.0))
+    }
+}
+
+fn to_level_filter(level: LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Error => LevelFilter::ERROR,
+        LogLevel::Warn => LevelFilter::WARN,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Debug => LevelFilter::DEBUG,
+    }
+}
+
+impl<S> Filter<S> for ScopedLevelFilter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn enabled(&self, meta: &Metadata<'_>, ctx: &Context<'_, S>) -> bool {
+        if meta.is_span() && meta.name() == LOG_SCOPE_SPAN {
+            return true;
+        }
+
+        if meta.target().starts_with("roxy")
+            && let Some(level) = Self::scoped_level(ctx)
+        {
+            return level >= *meta.level();
+        }
+
+        Filter::enabled(&self.base, meta, ctx)
+    }
+
+    fn callsite_enabled(&self, meta: &'static Metadata<'static>) -> Interest {
+        // Let the env filter register its directives, but always re-check
+        // per event since the answer depends on the current span.
+        let _ = Filter::<S>::callsite_enabled(&self.base, meta);
+        Interest::sometimes()
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Filter::<S>::max_level_hint(&self.base).map(|hint| hint.max(LevelFilter::DEBUG))
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() == LOG_SCOPE_SPAN {
+            let mut visitor = LogLevelVisitor(None);
+            attrs.record(&mut visitor);
+            if let (Some(level), Some(span)) = (visitor.0, ctx.span(id)) {
+                span.extensions_mut()
+                    .insert(ScopedLevel(to_level_filter(level)));
+            }
+        }
+        self.base.on_new_span(attrs, id, ctx);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        Filter::on_record(&self.base, id, values, ctx);
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        Filter::on_enter(&self.base, id, ctx);
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        Filter::on_exit(&self.base, id, ctx);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        Filter::on_close(&self.base, id, ctx);
+    }
+}
+
+/// Extracts the `log_level` field from a `log_scope` span.
+struct LogLevelVisitor(Option<LogLevel>);
+
+impl Visit for LogLevelVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "log_level" {
+            self.0 = value.parse().ok();
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn capture(base: &str, f: impl FnOnce()) -> String {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone())
+                .with_filter(ScopedLevelFilter::new(EnvFilter::new(base))),
+        );
+        tracing::subscriber::with_default(subscriber, f);
+        let bytes = captured.0.lock().unwrap().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn global_filter_applies_outside_scope() {
+        let output = capture("roxy=info", || {
+            tracing::debug!("hidden");
+            tracing::info!("shown");
+        });
+        assert!(!output.contains("hidden"));
+        assert!(output.contains("shown"));
+    }
+
+    #[test]
+    fn scope_can_raise_verbosity() {
+        let output = capture("roxy=info", || {
+            log_scope(LogLevel::Debug).in_scope(|| tracing::debug!("inside"));
+            tracing::debug!("outside");
+        });
+        assert!(output.contains("inside"));
+        assert!(!output.contains("outside"));
+    }
+
+    #[test]
+    fn scope_can_lower_verbosity() {
+        let output = capture("roxy=debug", || {
+            log_scope(LogLevel::Warn).in_scope(|| {
+                tracing::info!("quiet");
+                tracing::warn!("loud");
+            });
+            tracing::info!("regular");
+        });
+        assert!(!output.contains("quiet"));
+        assert!(output.contains("loud"));
+        assert!(output.contains("regular"));
+    }
+
+    #[test]
+    fn handle_changes_the_global_level() {
+        let (_layer, handle) =
+            reload::Layer::<_, Registry>::new(ScopedLevelFilter::new(EnvFilter::new("roxy=info")));
+        let handle = LogLevelHandle(handle);
+        assert_eq!(handle.current().as_deref(), Some("roxy=info"));
+
+        handle.set(LogLevel::Debug).unwrap();
+        assert_eq!(handle.current().as_deref(), Some("roxy=debug"));
+    }
 }
@@ -0,0 +1,79 @@
+use std::env;
+
+/// The person running a command. Under `sudo` this is the user who
+/// invoked sudo, not root, so registrations can be attributed to them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Caller {
+    /// A root shell (not via sudo). May manage every registration.
+    Root,
+    User(String),
+}
+
+impl Caller {
+    pub fn current() -> Self {
+        Self::from_env(
+            env::var("SUDO_USER").ok(),
+            env::var("USER")
+                .or_else(|_| env::var("LOGNAME"))
+                .or_else(|_| env::var("USERNAME"))
+                .ok(),
+        )
+    }
+
+    fn from_env(sudo_user: Option<String>, user: Option<String>) -> Self {
+        [sudo_user, user]
+            .into_iter()
+            .flatten()
+            .find(|name| !name.is_empty() && name != "root")
+            .map_or(Caller::Root, Caller::User)
+    }
+
+    /// Name to record as the owner of new registrations.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Caller::Root => None,
+            Caller::User(name) => Some(name),
+        }
+    }
+
+    /// Whether this caller may change a registration owned by `owner`.
+    /// Unowned registrations are open to everyone.
+    pub fn can_manage(&self, owner: Option<&str>) -> bool {
+        match (self, owner) {
+            (Caller::Root, _) | (_, None) => true,
+            (Caller::User(name), Some(owner)) => name == owner,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn some(name: &str) -> Option<String> {
+        Some(name.to_string())
+    }
+
+    #[test]
+    fn sudo_user_wins_over_root() {
+        assert_eq!(
+            Caller::from_env(some("alice"), some("root")),
+            Caller::User("alice".into())
+        );
+        assert_eq!(
+            Caller::from_env(None, some("bob")),
+            Caller::User("bob".into())
+        );
+        assert_eq!(Caller::from_env(some("root"), some("root")), Caller::Root);
+        assert_eq!(Caller::from_env(None, None), Caller::Root);
+    }
+
+    #[test]
+    fn users_manage_only_their_own_domains() {
+        let alice = Caller::User("alice".into());
+        assert!(alice.can_manage(Some("alice")));
+        assert!(!alice.can_manage(Some("bob")));
+        assert!(alice.can_manage(None));
+        assert!(Caller::Root.can_manage(Some("bob")));
+    }
+}
@@ -11,7 +11,7 @@ mod domain;
 mod infrastructure;
 
 use infrastructure::config::{Config, ConfigStore};
-use infrastructure::paths::RoxyPaths;
+use infrastructure::paths::{RoxyPaths, default_config_path};
 
 #[derive(Parser)]
 #[command(name = "roxy")]
@@ -22,7 +22,7 @@ use infrastructure::paths::RoxyPaths;
 )]
 struct Cli {
     /// Path to the config file
-    #[arg(short, long, global = true, default_value = "/etc/roxy/config.toml")]
+    #[arg(short, long, global = true, default_value_os_t = default_config_path())]
     config: PathBuf,
 
     /// Enable verbose output
@@ -36,7 +36,27 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Initial setup - configures DNS and prepares Roxy for use
-    Install,
+    Install {
+        /// Create the _roxy account and run the daemon as it instead of root
+        #[arg(long)]
+        service_user: bool,
+
+        /// Run the daemon without root on high ports, with 80/443 forwarded to them
+        #[arg(long, conflicts_with = "service_user")]
+        rootless: bool,
+
+        /// Use mkcert's CA, already trusted, instead of generating a Root CA
+        #[arg(long, conflicts_with = "ca_cert")]
+        ca_from_mkcert: bool,
+
+        /// Use this CA certificate (PEM) instead of generating a Root CA
+        #[arg(long, value_name = "FILE", requires = "ca_key")]
+        ca_cert: Option<PathBuf>,
+
+        /// Private key (PEM) of the CA given with --ca-cert
+        #[arg(long, value_name = "FILE", requires = "ca_cert")]
+        ca_key: Option<PathBuf>,
+    },
 
     /// Remove all Roxy configuration from the system
     Uninstall {
@@ -56,8 +76,34 @@ enum Commands {
 
         /// Route in format PATH=TARGET (e.g., "/=3000" or "/api=3001")
         /// TARGET can be: port (3000), host:port (192.168.1.50:3000), or path (/var/www)
+        /// Append ";strip" to remove PATH before proxying ("/api=3001;strip")
+        /// Append ";mirror=4000" to send a copy of every request to another target
+        /// Append ";latency=300ms±100ms" to delay every request
+        /// Append ";fault=5% 503" to fail a share of requests
+        /// With several targets ("/=3000,3001"), append ";split=90/10" to weigh
+        /// them and ";sticky" to keep each browser on one
         #[arg(long, short = 'r', value_name = "PATH=TARGET", required = true)]
         route: Vec<String>,
+
+        /// Serve this certificate (PEM, chain included) instead of issuing one
+        #[arg(long, value_name = "FILE", requires = "key")]
+        cert: Option<PathBuf>,
+
+        /// Private key (PEM) of the certificate given with --cert
+        #[arg(long, value_name = "FILE", requires = "cert")]
+        key: Option<PathBuf>,
+
+        /// Serve the domain over plain HTTP only, without a certificate
+        #[arg(long, conflicts_with = "cert")]
+        no_https: bool,
+
+        /// What the domain is for, shown by `roxy list`
+        #[arg(long = "desc", value_name = "TEXT")]
+        description: Option<String>,
+
+        /// Label the domain, to filter `roxy list` by (repeatable)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
     },
 
     /// Unregister a domain
@@ -74,14 +120,135 @@ enum Commands {
         force: bool,
     },
 
+    /// Register the domains in the current project's .roxy.toml, or
+    /// update them to match it
+    Link,
+
+    /// Unregister the domains linked from the current project
+    Unlink,
+
+    /// Print every domain registration, for `roxy import` on another
+    /// machine
+    Export,
+
+    /// Register domains from `roxy export`, or from another tool's
+    /// configuration
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Import {
+        #[command(subcommand)]
+        command: Option<ImportCommands>,
+
+        /// Domains file written by `roxy export`
+        #[arg(required = true)]
+        file: Option<PathBuf>,
+
+        /// Overwrite domains that are already registered
+        #[arg(long, conflicts_with = "replace")]
+        merge: bool,
+
+        /// Overwrite domains that are already registered and unregister
+        /// the ones the file doesn't have
+        #[arg(long)]
+        replace: bool,
+    },
+
     /// Manage routes for a domain
     Route {
         #[command(subcommand)]
         command: RouteCommands,
     },
 
+    /// Forward raw TCP ports for a domain, e.g. for a database
+    Tcp {
+        #[command(subcommand)]
+        command: TcpCommands,
+    },
+
+    /// Require a login for a domain or route
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommands,
+    },
+
+    /// Share the Root CA so other devices trust Roxy's certificates
+    Ca {
+        #[command(subcommand)]
+        command: CaCommands,
+    },
+
+    /// Show a QR code that installs the Root CA on a phone
+    ShareCa {
+        /// Port to serve the install page on while the daemon isn't running
+        #[arg(long)]
+        port: Option<u16>,
+    },
+
+    /// Manage the daemon's response cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+
+    /// Serve a disabled domain again
+    Enable {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Domain name
+        domain: String,
+    },
+
+    /// Stop serving a domain without unregistering it
+    Disable {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Domain name
+        domain: String,
+    },
+
+    /// Take a domain offline with a maintenance page, or bring it back
+    Maintenance {
+        #[command(subcommand)]
+        command: MaintenanceCommands,
+    },
+
+    /// Send a domain's HTTPS connections to its backend undecrypted
+    Passthrough {
+        #[command(subcommand)]
+        command: PassthroughCommands,
+    },
+
+    /// Turn HTTPS on or off for a domain
+    Https {
+        #[command(subcommand)]
+        command: HttpsCommands,
+    },
+
     /// List all registered domains
-    List,
+    List {
+        /// Also list the domains of profiles not in use
+        #[arg(long)]
+        all_profiles: bool,
+
+        /// Only list domains with this tag
+        #[arg(long)]
+        tag: Option<String>,
+    },
+
+    /// Switch between named sets of domains
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommands,
+    },
+
+    /// Change individual config values
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
 
     /// Start the Roxy daemon
     Start {
@@ -97,7 +264,11 @@ enum Commands {
     Restart,
 
     /// Show daemon and domain status
-    Status,
+    Status {
+        /// Keep refreshing, every INTERVAL (default 2s)
+        #[arg(long, value_name = "INTERVAL", num_args = 0..=1, default_missing_value = "2s")]
+        watch: Option<humantime::Duration>,
+    },
 
     /// View daemon logs
     Logs {
@@ -112,11 +283,55 @@ enum Commands {
         /// Follow log output (like tail -f)
         #[arg(short = 'f', long)]
         follow: bool,
+
+        /// Show the output of the backends started for this domain instead
+        #[arg(long, value_name = "DOMAIN")]
+        backend: Option<String>,
+
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long, requires = "backend")]
+        wildcard: bool,
+    },
+
+    /// List the backends the daemon started for routes with a cmd
+    Ps,
+
+    /// Restart the backends the daemon started for a domain
+    RestartBackend {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Domain name
+        domain: String,
+    },
+
+    /// Run a command with a domain's URL in its environment
+    /// (ROXY_URL, ROXY_HOST, ROXY_LAN_URL)
+    Exec {
+        /// Registered domain, e.g. myapp.roxy
+        domain: String,
+
+        /// Command to run, after --
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
     },
 
     /// Reload daemon configuration
     Reload,
 
+    /// Show or change the running daemon's log level
+    LogLevel {
+        /// New level: error, warn, info, or debug (omit to show the current one)
+        level: Option<String>,
+    },
+
+    /// Run the daemon as a system service that starts at boot
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommands,
+    },
+
     /// Generate shell completions
     Completions {
         /// Shell to generate completions for
@@ -140,6 +355,9 @@ enum RouteCommands {
 
         /// Target: port, host:port, or filesystem path
         target: String,
+
+        #[command(flatten)]
+        flags: cli::route::RouteFlags,
     },
 
     /// Remove a route from a domain
@@ -166,6 +384,257 @@ enum RouteCommands {
     },
 }
 
+#[derive(Subcommand)]
+enum TcpCommands {
+    /// Listen on a port and pipe its connections to a target
+    Add {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Domain name
+        domain: String,
+
+        /// PORT=TARGET, e.g. "5432=5433" or "5432=127.0.0.1:5433"
+        forward: String,
+    },
+
+    /// Stop forwarding a port
+    Remove {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Domain name
+        domain: String,
+
+        /// Port to stop listening on
+        port: u16,
+    },
+
+    /// List TCP forwards for a domain
+    List {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Domain name
+        domain: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuthCommands {
+    /// Add a user, or change their password (read from stdin)
+    Add {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Only protect the route with this path prefix
+        #[arg(long)]
+        path: Option<String>,
+
+        /// Domain name
+        domain: String,
+
+        /// User name
+        user: String,
+    },
+
+    /// Remove a user; without users the login is off
+    Remove {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Route path prefix the user was added to
+        #[arg(long)]
+        path: Option<String>,
+
+        /// Domain name
+        domain: String,
+
+        /// User name
+        user: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CaCommands {
+    /// Print the Root CA certificate (PEM), or write it to a file
+    Export {
+        /// File to write instead of printing
+        #[arg(long, value_name = "FILE")]
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommands {
+    /// Drop all cached responses for a domain
+    Purge {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Domain name
+        domain: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum MaintenanceCommands {
+    /// Answer every request with a 503 maintenance page
+    On {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Text shown on the page, e.g. "Back at 5pm"
+        #[arg(long, short = 'm')]
+        message: Option<String>,
+
+        /// Domain name
+        domain: String,
+    },
+
+    /// Serve the domain's routes again
+    Off {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Domain name
+        domain: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum PassthroughCommands {
+    /// Stream HTTPS connections to the backend of the / route as raw TCP
+    On {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Domain name
+        domain: String,
+    },
+
+    /// Terminate TLS in Roxy again
+    Off {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Domain name
+        domain: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// Add an empty profile
+    Create {
+        /// Profile name
+        name: String,
+    },
+
+    /// Register the profile's domains in place of the active profile's
+    Use {
+        /// Profile name ("default" for the domains registered before any
+        /// profile was created)
+        name: String,
+    },
+
+    /// List profiles, marking the active one
+    List,
+
+    /// Remove a profile that isn't in use, with its domains
+    Delete {
+        /// Profile name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum HttpsCommands {
+    /// Serve the domain over HTTPS, issuing a certificate if it has none
+    On {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Domain name
+        domain: String,
+    },
+
+    /// Serve the domain over plain HTTP only
+    Off {
+        /// Manage wildcard registration for this domain (matches myapp.roxy and *.myapp.roxy)
+        #[arg(long)]
+        wildcard: bool,
+
+        /// Domain name
+        domain: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImportCommands {
+    /// Register <service>.roxy for every service in a Docker Compose file
+    /// that publishes a port
+    Compose {
+        /// Compose file
+        #[arg(default_value = "docker-compose.yml")]
+        file: PathBuf,
+
+        /// Show what would be registered without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServiceCommands {
+    /// Install and start the service (launchd on macOS, systemd on Linux)
+    Install,
+
+    /// Stop and remove the service
+    Uninstall,
+
+    /// Show whether the service is installed and running
+    Status,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Set a value (e.g., "domains.myapp.log_level debug")
+    Set {
+        /// Dotted key; routes can be addressed by path (domains.myapp.routes./api.log_level)
+        key: String,
+
+        /// New value
+        value: String,
+    },
+
+    /// Remove a value, reverting it to the default
+    Unset {
+        /// Dotted key
+        key: String,
+    },
+
+    /// List the snapshots kept before each config change
+    History,
+
+    /// Restore a config snapshot
+    Rollback {
+        /// Snapshot number from `roxy config history` (default: 1, the config before the last change)
+        n: Option<usize>,
+    },
+}
+
 /// Load config from file, or return defaults if the file doesn't exist.
 /// For `install`, the config file may not exist yet, so defaults are fine.
 fn load_config_and_paths(config_path: &Path) -> Result<(Config, RoxyPaths)> {
@@ -182,25 +651,72 @@ fn main() -> Result<()> {
     let (config, paths) = load_config_and_paths(config_path)?;
 
     match cli.command {
-        Commands::Install => cli::install::execute(config_path, &paths, &config),
+        Commands::Install {
+            service_user,
+            rootless,
+            ca_from_mkcert,
+            ca_cert,
+            ca_key,
+        } => cli::install::execute(
+            service_user,
+            rootless,
+            ca_from_mkcert,
+            ca_cert.zip(ca_key),
+            config_path,
+            &paths,
+            &config,
+        ),
         Commands::Uninstall { force } => cli::uninstall::execute(force, config_path, &paths),
         Commands::Register {
             domain,
             wildcard,
             route,
-        } => cli::register::execute(domain, wildcard, route, config_path, &paths),
+            cert,
+            key,
+            no_https,
+            description,
+            tags,
+        } => cli::register::execute(
+            domain,
+            wildcard,
+            route,
+            cli::register::Options {
+                custom_cert: cert.zip(key),
+                no_https,
+                description,
+                tags,
+            },
+            config_path,
+            &paths,
+        ),
         Commands::Unregister {
             domain,
             wildcard,
             force,
         } => cli::unregister::execute(domain, wildcard, force, config_path, &paths),
+        Commands::Link => cli::link::link(config_path, &paths),
+        Commands::Unlink => cli::link::unlink(config_path, &paths),
+        Commands::Export => cli::export::execute(config_path),
+        Commands::Import {
+            command,
+            file,
+            merge,
+            replace,
+        } => match (command, file) {
+            (Some(ImportCommands::Compose { file, dry_run }), _) => {
+                cli::import::compose(&file, dry_run, config_path, &paths)
+            }
+            (None, Some(file)) => cli::import::domains(&file, merge, replace, config_path, &paths),
+            (None, None) => unreachable!("clap requires a file without a subcommand"),
+        },
         Commands::Route { command } => match command {
             RouteCommands::Add {
                 wildcard,
                 domain,
                 path,
                 target,
-            } => cli::route::add(domain, wildcard, path, target, config_path),
+                flags,
+            } => cli::route::add(domain, wildcard, path, target, flags, config_path),
             RouteCommands::Remove {
                 wildcard,
                 domain,
@@ -210,19 +726,112 @@ fn main() -> Result<()> {
                 cli::route::list(domain, wildcard, config_path)
             }
         },
-        Commands::List => cli::list::execute(config_path, &paths),
+        Commands::Tcp { command } => match command {
+            TcpCommands::Add {
+                wildcard,
+                domain,
+                forward,
+            } => cli::tcp::add(domain, wildcard, forward, config_path),
+            TcpCommands::Remove {
+                wildcard,
+                domain,
+                port,
+            } => cli::tcp::remove(domain, wildcard, port, config_path),
+            TcpCommands::List { wildcard, domain } => cli::tcp::list(domain, wildcard, config_path),
+        },
+        Commands::Auth { command } => match command {
+            AuthCommands::Add {
+                wildcard,
+                path,
+                domain,
+                user,
+            } => cli::auth::add(domain, wildcard, user, path, config_path),
+            AuthCommands::Remove {
+                wildcard,
+                path,
+                domain,
+                user,
+            } => cli::auth::remove(domain, wildcard, user, path, config_path),
+        },
+        Commands::Ca { command } => match command {
+            CaCommands::Export { path } => cli::ca::export(path, &config, &paths),
+        },
+        Commands::ShareCa { port } => cli::ca::share(port, &config, &paths),
+        Commands::Cache { command } => match command {
+            CacheCommands::Purge { wildcard, domain } => {
+                cli::cache::purge(domain, wildcard, &paths)
+            }
+        },
+        Commands::Enable { wildcard, domain } => cli::enable::enable(domain, wildcard, config_path),
+        Commands::Disable { wildcard, domain } => {
+            cli::enable::disable(domain, wildcard, config_path)
+        }
+        Commands::Maintenance { command } => match command {
+            MaintenanceCommands::On {
+                wildcard,
+                message,
+                domain,
+            } => cli::maintenance::on(domain, wildcard, message, config_path),
+            MaintenanceCommands::Off { wildcard, domain } => {
+                cli::maintenance::off(domain, wildcard, config_path)
+            }
+        },
+        Commands::Passthrough { command } => match command {
+            PassthroughCommands::On { wildcard, domain } => {
+                cli::passthrough::on(domain, wildcard, config_path)
+            }
+            PassthroughCommands::Off { wildcard, domain } => {
+                cli::passthrough::off(domain, wildcard, config_path)
+            }
+        },
+        Commands::Https { command } => match command {
+            HttpsCommands::On { wildcard, domain } => {
+                cli::https::on(domain, wildcard, config_path, &paths)
+            }
+            HttpsCommands::Off { wildcard, domain } => {
+                cli::https::off(domain, wildcard, config_path, &paths)
+            }
+        },
+        Commands::List { all_profiles, tag } => {
+            cli::list::execute(all_profiles, tag.as_deref(), config_path, &paths)
+        }
+        Commands::Profile { command } => match command {
+            ProfileCommands::Create { name } => cli::profile::create(name, config_path),
+            ProfileCommands::Use { name } => cli::profile::switch(name, config_path, &paths),
+            ProfileCommands::List => cli::profile::list(config_path),
+            ProfileCommands::Delete { name } => cli::profile::delete(name, config_path),
+        },
+        Commands::Config { command } => match command {
+            ConfigCommands::Set { key, value } => cli::config::set(key, value, config_path),
+            ConfigCommands::Unset { key } => cli::config::unset(key, config_path),
+            ConfigCommands::History => cli::config::history(config_path),
+            ConfigCommands::Rollback { n } => cli::config::rollback(n, config_path),
+        },
         Commands::Start { foreground } => {
             cli::start::execute(foreground, cli.verbose, config_path, &paths, &config)
         }
         Commands::Stop => cli::stop::execute(&paths),
         Commands::Restart => cli::restart::execute(cli.verbose, config_path, &paths),
-        Commands::Status => cli::status::execute(config_path, &paths),
+        Commands::Status { watch } => {
+            cli::status::execute(watch.map(Into::into), config_path, &paths)
+        }
         Commands::Logs {
             lines,
             clear,
             follow,
-        } => cli::logs::execute(lines, clear, follow, &paths),
+            backend,
+            wildcard,
+        } => cli::logs::execute(lines, clear, follow, backend, wildcard, &paths),
+        Commands::Ps => cli::ps::execute(&paths),
+        Commands::RestartBackend { wildcard, domain } => cli::ps::restart(domain, wildcard, &paths),
+        Commands::Exec { domain, command } => cli::exec::execute(domain, command, &config),
         Commands::Reload => cli::reload::execute(cli.verbose, config_path, &paths),
+        Commands::LogLevel { level } => cli::log_level::execute(level, &paths),
+        Commands::Service { command } => match command {
+            ServiceCommands::Install => cli::service::install(config_path, &paths),
+            ServiceCommands::Uninstall => cli::service::uninstall(),
+            ServiceCommands::Status => cli::service::status(),
+        },
         Commands::Completions { shell } => {
             clap_complete::generate(shell, &mut Cli::command(), "roxy", &mut std::io::stdout());
             Ok(())